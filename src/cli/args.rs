@@ -46,4 +46,7 @@ pub enum CliCommand {
     /// Generate the dns server configuration
     #[structopt(version = get_version())]
     GenerateDnsConfig,
+    /// Lowercase and deduplicate the domains on the whitelist and blacklist
+    #[structopt(version = get_version())]
+    NormalizeLists,
 }
@@ -9,12 +9,12 @@
 // Please see LICENSE file for your rights under this license.
 
 use crate::{
-    env::{Config, Env},
-    routes::settings::restart_dns,
+    env::{Config, Env, FileLocks},
+    services::{restart::restart_dns, settings::SettingsRepositoryImpl},
     settings::generate_dnsmasq_config,
     util::Error,
 };
-use std::path::Path;
+use std::{path::Path, sync::Arc};
 
 /// Generate the dnsmasq config using [`generate_dnsmasq_config`]. Dnsmasq (FTL)
 /// will be restarted in the process of applying the changes. This should be
@@ -25,11 +25,12 @@ use std::path::Path;
 /// [`GenerateDnsmasq`]: ../args/enum.CliCommand.html#variant.GenerateDnsmasq
 pub fn generate_dnsmasq_cli(config_location: &Path) -> Result<(), Error> {
     let config = Config::load(config_location)?;
-    let env = Env::Production(config);
+    let env = Arc::new(Env::Production(config, FileLocks::default()));
+    let settings = SettingsRepositoryImpl::new(Arc::clone(&env));
 
     println!("Generating dnsmasq config...");
 
-    generate_dnsmasq_config(&env)?;
+    generate_dnsmasq_config(&settings, &env)?;
     restart_dns(&env)?;
 
     println!("Done");
@@ -12,6 +12,7 @@ use crate::{
     cli::{
         args::{CliArgs, CliCommand},
         dnsmasq::generate_dnsmasq_cli,
+        lists::normalize_lists_cli,
     },
     setup::start,
     util::Error,
@@ -32,6 +33,7 @@ pub async fn handle_cli() -> Result<(), Error> {
             CliCommand::Branch => println!("{}", get_branch()),
             CliCommand::Hash => println!("{}", get_hash()),
             CliCommand::GenerateDnsConfig => generate_dnsmasq_cli(&args.config)?,
+            CliCommand::NormalizeLists => normalize_lists_cli(&args.config)?,
         },
         // No command given, start the API
         None => start(&args.config).await?,
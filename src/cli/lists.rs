@@ -0,0 +1,52 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Normalize The Domain Lists From CLI
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    databases::{
+        custom_connection::CustomSqliteConnection,
+        gravity::{GravityDatabase, GravitySchema},
+        load_gravity_db_config,
+    },
+    env::{Config, Env, FileLocks},
+    services::lists::{List, ListRepository, ListRepositoryImpl},
+    util::{Error, ErrorKind},
+};
+use failure::ResultExt;
+use std::path::Path;
+
+/// Lowercase and deduplicate the domains on the exact lists using
+/// [`ListRepository::normalize_case`]. This only needs to be run once for
+/// databases which were written before domains were stored in lowercase.
+/// This should be called when handling the [`NormalizeLists`] command on the
+/// CLI.
+///
+/// [`ListRepository::normalize_case`]:
+/// ../../services/lists/trait.ListRepository.html#tymethod.normalize_case
+/// [`NormalizeLists`]: ../args/enum.CliCommand.html#variant.NormalizeLists
+pub fn normalize_lists_cli(config_location: &Path) -> Result<(), Error> {
+    let config = Config::load(config_location)?;
+    let env = Env::Production(config, FileLocks::default());
+    let pool = CustomSqliteConnection::pool(load_gravity_db_config(&env)?)
+        .context(ErrorKind::GravityDatabase)?;
+    let connection = pool.get().context(ErrorKind::GravityDatabase)?;
+    let schema = GravitySchema::detect(&connection)?;
+    let repo = ListRepositoryImpl::new(Box::new(GravityDatabase { connection, schema }));
+
+    println!("Normalizing the domain lists...");
+
+    for list in &[List::White, List::Black] {
+        let changed = repo.normalize_case(*list)?;
+        println!("Changed {} entries on the {}", changed, list.name());
+    }
+
+    println!("Done");
+
+    Ok(())
+}
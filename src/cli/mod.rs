@@ -11,5 +11,6 @@
 mod args;
 mod dnsmasq;
 mod handler;
+mod lists;
 
 pub use self::handler::handle_cli;
@@ -107,9 +107,23 @@ impl From<FtlDbQuery> for QueryReply {
             status: query.status as u8,
             domain: query.domain,
             client: query.client,
-            dnssec: FtlDnssecType::Unknown as u8,
+            client_name: None,
+            dnssec: FtlDnssecType::Unknown.name(),
             reply: FtlQueryReplyType::Unknown as u8,
             response_time: 0,
         }
     }
 }
+
+#[cfg_attr(test, derive(PartialEq, Debug))]
+#[derive(Queryable)]
+pub struct FtlDbNetworkDevice {
+    pub id: i32,
+    pub ip: String,
+    pub hwaddr: String,
+    pub interface: String,
+    pub name: Option<String>,
+    pub first_seen: i32,
+    pub last_query: i32,
+    pub num_queries: i32,
+}
@@ -13,7 +13,7 @@ use crate::databases::{
     custom_connection::CustomSqliteConnectionManager,
     ftl::FtlDatabase,
 };
-use diesel::{r2d2::Pool, SqliteConnection};
+use diesel::{insert_into, prelude::*, r2d2::Pool, SqliteConnection};
 
 pub const TEST_FTL_DATABASE_SCHEMA: &str = include_str!("../../../test/FTL.sql");
 
@@ -31,3 +31,27 @@ pub fn connect_to_ftl_test_db() -> FtlDatabase {
 
     db
 }
+
+/// Add A queries to the test database. Each query is given as its timestamp,
+/// status, domain, and client. The queries are removed when the test
+/// transaction is rolled back.
+pub fn insert_test_queries(db: &SqliteConnection, rows: &[(i32, i32, &str, &str)]) {
+    use crate::databases::ftl::queries::dsl::*;
+
+    let values: Vec<_> = rows
+        .iter()
+        .map(
+            |&(query_timestamp, query_status, query_domain, query_client)| {
+                (
+                    timestamp.eq(query_timestamp),
+                    query_type.eq(1),
+                    status.eq(query_status),
+                    domain.eq(query_domain),
+                    client.eq(query_client),
+                )
+            },
+        )
+        .collect();
+
+    insert_into(queries).values(&values).execute(db).unwrap();
+}
@@ -18,7 +18,7 @@ use crate::{
     settings::{ConfigEntry, FtlConfEntry},
     util::{self, ErrorKind},
 };
-use diesel::{r2d2::Pool, SqliteConnection};
+use diesel::{dsl::sql, r2d2::Pool, sql_types::Bool, RunQueryDsl, SqliteConnection};
 use failure::{Fail, ResultExt};
 use rocket_sync_db_pools::r2d2::PooledConnection;
 use shaku::{Component, HasComponent, Module, Provider};
@@ -39,25 +39,48 @@ fn default_connection() -> Pool<CustomSqliteConnectionManager> {
 pub struct GravityDatabasePool {
     #[shaku(default = default_connection())]
     pool: Pool<CustomSqliteConnectionManager>,
+    /// The schema of the database, which is detected once when the pool is
+    /// built
+    #[shaku(default = GravitySchema::Legacy)]
+    schema: GravitySchema,
+}
+
+impl GravityDatabasePoolParameters {
+    /// Create the parameters of the pool, detecting the schema of its
+    /// database
+    pub fn new(pool: Pool<CustomSqliteConnectionManager>) -> Result<Self, util::Error> {
+        let connection = pool.get().context(ErrorKind::GravityDatabase)?;
+        let schema = GravitySchema::detect(&connection)?;
+
+        Ok(GravityDatabasePoolParameters { pool, schema })
+    }
 }
 
 impl DatabaseService<GravityDatabase> for GravityDatabasePool {
     fn get_connection(&self) -> Result<GravityDatabase, util::Error> {
         self.pool
             .get()
-            .map(GravityDatabase)
+            .map(|connection| GravityDatabase {
+                connection,
+                schema: self.schema,
+            })
             .context(ErrorKind::GravityDatabase)
             .map_err(Into::into)
     }
 }
 
-pub struct GravityDatabase(pub PooledConnection<CustomSqliteConnectionManager>);
+/// A connection to the gravity database, along with the schema of the
+/// database
+pub struct GravityDatabase {
+    pub connection: PooledConnection<CustomSqliteConnectionManager>,
+    pub schema: GravitySchema,
+}
 
 impl Deref for GravityDatabase {
     type Target = SqliteConnection;
 
     fn deref(&self) -> &Self::Target {
-        &self.0
+        &self.connection
     }
 }
 
@@ -72,3 +95,32 @@ impl<M: Module + HasComponent<dyn DatabaseService<GravityDatabase>>> Provider<M>
         Ok(Box::new(pool.get_connection().map_err(Fail::compat)?))
     }
 }
+
+/// The layout of the domain lists in the gravity database. Older databases
+/// have a table for each list, while newer databases keep every list in the
+/// `domainlist` table and use its `type` column to tell them apart.
+#[cfg_attr(test, derive(Debug))]
+#[derive(Copy, Clone, PartialEq)]
+pub enum GravitySchema {
+    Legacy,
+    Domainlist,
+}
+
+impl GravitySchema {
+    /// Detect the schema of the database by checking for the `domainlist`
+    /// table
+    pub fn detect(db: &SqliteConnection) -> Result<Self, util::Error> {
+        let has_domainlist = diesel::select(sql::<Bool>(
+            "EXISTS(SELECT 1 FROM sqlite_master \
+             WHERE type = 'table' AND name = 'domainlist')",
+        ))
+        .get_result(db)
+        .context(ErrorKind::GravityDatabase)?;
+
+        Ok(if has_domainlist {
+            GravitySchema::Domainlist
+        } else {
+            GravitySchema::Legacy
+        })
+    }
+}
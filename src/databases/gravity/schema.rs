@@ -53,8 +53,29 @@ table! {
 }
 
 table! {
-    gravity (domain) {
+    domainlist (id) {
+        id -> Integer,
+        #[sql_name = "type"]
+        type_ -> Integer,
+        domain -> Text,
+        enabled -> Bool,
+        date_added -> Integer,
+        date_modified -> Integer,
+        comment -> Nullable<Text>,
+    }
+}
+
+table! {
+    domainlist_by_group (domainlist_id, group_id) {
+        domainlist_id -> Integer,
+        group_id -> Integer,
+    }
+}
+
+table! {
+    gravity (domain, adlist_id) {
         domain -> Text,
+        adlist_id -> Integer,
     }
 }
 
@@ -92,6 +113,24 @@ table! {
     }
 }
 
+table! {
+    regex_whitelist (id) {
+        id -> Integer,
+        domain -> Text,
+        enabled -> Bool,
+        date_added -> Integer,
+        date_modified -> Integer,
+        comment -> Nullable<Text>,
+    }
+}
+
+table! {
+    regex_whitelist_by_group (regex_whitelist_id, group_id) {
+        regex_whitelist_id -> Integer,
+        group_id -> Integer,
+    }
+}
+
 table! {
     whitelist (id) {
         id -> Integer,
@@ -114,8 +153,13 @@ joinable!(adlist_by_group -> adlist (adlist_id));
 joinable!(adlist_by_group -> group (group_id));
 joinable!(blacklist_by_group -> blacklist (blacklist_id));
 joinable!(blacklist_by_group -> group (group_id));
+joinable!(domainlist_by_group -> domainlist (domainlist_id));
+joinable!(domainlist_by_group -> group (group_id));
+joinable!(gravity -> adlist (adlist_id));
 joinable!(regex_by_group -> group (group_id));
 joinable!(regex_by_group -> regex (regex_id));
+joinable!(regex_whitelist_by_group -> group (group_id));
+joinable!(regex_whitelist_by_group -> regex_whitelist (regex_whitelist_id));
 joinable!(whitelist_by_group -> group (group_id));
 joinable!(whitelist_by_group -> whitelist (whitelist_id));
 
@@ -125,11 +169,15 @@ allow_tables_to_appear_in_same_query!(
     blacklist,
     blacklist_by_group,
     domain_audit,
+    domainlist,
+    domainlist_by_group,
     gravity,
     group,
     info,
     regex,
     regex_by_group,
+    regex_whitelist,
+    regex_whitelist_by_group,
     whitelist,
     whitelist_by_group,
 );
@@ -8,7 +8,10 @@
 // This file is copyright under the latest version of the EUPL.
 // Please see LICENSE file for your rights under this license.
 
-use crate::databases::{common::create_memory_db, gravity::GravityDatabase};
+use crate::databases::{
+    common::create_memory_db,
+    gravity::{GravityDatabase, GravitySchema},
+};
 
 pub const TEST_GRAVITY_DATABASE_SCHEMA: &str = include_str!("../../../test/gravity.sql");
 
@@ -17,5 +20,22 @@ pub const TEST_GRAVITY_DATABASE_SCHEMA: &str = include_str!("../../../test/gravi
 pub fn connect_to_gravity_test_db() -> Box<GravityDatabase> {
     let pool = create_memory_db(TEST_GRAVITY_DATABASE_SCHEMA, 1);
 
-    Box::new(GravityDatabase(pool.get().unwrap()))
+    Box::new(GravityDatabase {
+        connection: pool.get().unwrap(),
+        schema: GravitySchema::Legacy,
+    })
+}
+
+pub const TEST_GRAVITY_V5_DATABASE_SCHEMA: &str = include_str!("../../../test/gravity_v5.sql");
+
+/// Connect to a testing database which uses the unified `domainlist` schema.
+/// This creates a new in-memory database so that it is isolated from other
+/// tests.
+pub fn connect_to_gravity_v5_test_db() -> Box<GravityDatabase> {
+    let pool = create_memory_db(TEST_GRAVITY_V5_DATABASE_SCHEMA, 1);
+
+    Box::new(GravityDatabase {
+        connection: pool.get().unwrap(),
+        schema: GravitySchema::Domainlist,
+    })
 }
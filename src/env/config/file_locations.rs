@@ -39,6 +39,14 @@ pub struct Files {
     black_list: String,
     #[serde(default = "default_black_list_backup")]
     black_list_backup: String,
+    #[serde(default = "default_custom_list")]
+    custom_list: String,
+    #[serde(default = "default_cname_config")]
+    cname_config: String,
+    #[serde(default = "default_static_dhcp_config")]
+    static_dhcp_config: String,
+    #[serde(default = "default_dhcp_leases")]
+    dhcp_leases: String,
 }
 
 impl Default for Files {
@@ -56,6 +64,10 @@ impl Default for Files {
             gravity_backup: default_gravity_backup(),
             black_list: default_black_list(),
             black_list_backup: default_black_list_backup(),
+            custom_list: default_custom_list(),
+            cname_config: default_cname_config(),
+            static_dhcp_config: default_static_dhcp_config(),
+            dhcp_leases: default_dhcp_leases(),
         }
     }
 }
@@ -75,6 +87,10 @@ impl Files {
             &self.gravity_backup,
             &self.black_list,
             &self.black_list_backup,
+            &self.custom_list,
+            &self.cname_config,
+            &self.static_dhcp_config,
+            &self.dhcp_leases,
         ]
         .iter()
         .all(|file| Path::new(file).is_absolute())
@@ -95,6 +111,10 @@ impl Files {
             PiholeFile::GravityBackup => &self.gravity_backup,
             PiholeFile::BlackList => &self.black_list,
             PiholeFile::BlackListBackup => &self.black_list_backup,
+            PiholeFile::CustomList => &self.custom_list,
+            PiholeFile::CnameConfig => &self.cname_config,
+            PiholeFile::StaticDhcpConfig => &self.static_dhcp_config,
+            PiholeFile::DhcpLeases => &self.dhcp_leases,
         }
     }
 }
@@ -120,6 +140,10 @@ default!(default_gravity, Gravity);
 default!(default_gravity_backup, GravityBackup);
 default!(default_black_list, BlackList);
 default!(default_black_list_backup, BlackListBackup);
+default!(default_custom_list, CustomList);
+default!(default_cname_config, CnameConfig);
+default!(default_static_dhcp_config, StaticDhcpConfig);
+default!(default_dhcp_leases, DhcpLeases);
 
 #[cfg(test)]
 mod test {
@@ -8,6 +8,7 @@
 // This file is copyright under the latest version of the EUPL.
 // Please see LICENSE file for your rights under this license.
 
+use chrono_tz::Tz;
 use rocket::config::LogLevel;
 use serde::{Deserialize, Deserializer};
 use std::{net::Ipv4Addr, str::FromStr};
@@ -29,6 +30,29 @@ pub struct General {
         deserialize_with = "deserialize_logging_level"
     )]
     pub log_level: LogLevel,
+
+    /// How many seconds the number of domains in gravity is cached for
+    #[serde(default = "default_gravity_count_ttl")]
+    pub gravity_count_ttl: u64,
+
+    /// If clients which FTL has not named should be named with reverse DNS
+    #[serde(default)]
+    pub reverse_dns_clients: bool,
+
+    /// The IANA time zone which over time buckets are aligned to by default,
+    /// such as `Europe/Berlin`. Buckets are aligned to UTC if this is not set.
+    #[serde(default)]
+    pub timezone: Option<String>,
+
+    /// If the DNS server should be reloaded after the local DNS or CNAME
+    /// records are changed, so the changes take effect immediately
+    #[serde(default = "default_reload_local_records")]
+    pub reload_local_records: bool,
+
+    /// If the API may reload or restart the DNS server. Turn this off if the
+    /// API can not control FTL, such as when they run in separate containers.
+    #[serde(default = "default_allow_dns_restart")]
+    pub allow_dns_restart: bool,
 }
 
 impl Default for General {
@@ -37,13 +61,23 @@ impl Default for General {
             address: default_address(),
             port: default_port(),
             log_level: default_log_level(),
+            gravity_count_ttl: default_gravity_count_ttl(),
+            reverse_dns_clients: false,
+            timezone: None,
+            reload_local_records: default_reload_local_records(),
+            allow_dns_restart: default_allow_dns_restart(),
         }
     }
 }
 
 impl General {
     pub fn is_valid(&self) -> bool {
-        Ipv4Addr::from_str(&self.address).is_ok() && self.port <= 65535
+        Ipv4Addr::from_str(&self.address).is_ok()
+            && self.port <= 65535
+            && self
+                .timezone
+                .as_ref()
+                .map_or(true, |timezone| timezone.parse::<Tz>().is_ok())
     }
 }
 
@@ -70,6 +104,18 @@ fn default_log_level() -> LogLevel {
     LogLevel::Critical
 }
 
+fn default_gravity_count_ttl() -> u64 {
+    60
+}
+
+fn default_reload_local_records() -> bool {
+    true
+}
+
+fn default_allow_dns_restart() -> bool {
+    true
+}
+
 #[cfg(test)]
 mod test {
     use super::General;
@@ -103,4 +149,15 @@ mod test {
 
         assert!(!general.is_valid());
     }
+
+    /// An unknown time zone flags the config as invalid
+    #[test]
+    fn invalid_general_timezone() {
+        let general = General {
+            timezone: Some("Mars/Olympus_Mons".to_owned()),
+            ..General::default()
+        };
+
+        assert!(!general.is_valid());
+    }
 }
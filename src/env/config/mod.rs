@@ -12,5 +12,9 @@ mod file_locations;
 mod general;
 mod root_config;
 mod web;
+mod webhooks;
 
-pub use self::root_config::{Config, DEFAULT_CONFIG_LOCATION};
+pub use self::{
+    root_config::{Config, DEFAULT_CONFIG_LOCATION},
+    webhooks::WebhookConfig,
+};
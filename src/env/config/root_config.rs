@@ -9,7 +9,9 @@
 // Please see LICENSE file for your rights under this license.
 
 use crate::{
-    env::config::{file_locations::Files, general::General, web::WebConfig},
+    env::config::{
+        file_locations::Files, general::General, web::WebConfig, webhooks::WebhookConfig,
+    },
     util::{Error, ErrorKind},
 };
 use failure::{Fail, ResultExt};
@@ -31,6 +33,8 @@ pub struct Config {
     pub file_locations: Files,
     #[serde(default)]
     pub web: WebConfig,
+    #[serde(default)]
+    pub webhooks: Option<WebhookConfig>,
 }
 
 impl Config {
@@ -74,7 +78,10 @@ impl Config {
 
     /// Check if the config settings are valid
     pub fn is_valid(&self) -> bool {
-        self.general.is_valid() && self.file_locations.is_valid() && self.web.is_valid()
+        self.general.is_valid()
+            && self.file_locations.is_valid()
+            && self.web.is_valid()
+            && self.webhooks.as_ref().map_or(true, WebhookConfig::is_valid)
     }
 }
 
@@ -0,0 +1,80 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Webhook Config
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use std::fmt::{self, Debug, Formatter};
+
+/// Configuration settings for notifying another service of list changes
+#[derive(Deserialize, Clone)]
+pub struct WebhookConfig {
+    /// The URL which list change events are POSTed to
+    pub url: String,
+
+    /// The secret used to sign the events, so the receiver can verify that
+    /// they came from the API
+    #[serde(default)]
+    pub secret: String,
+}
+
+impl WebhookConfig {
+    pub fn is_valid(&self) -> bool {
+        self.url.starts_with("http://") || self.url.starts_with("https://")
+    }
+}
+
+/// The secret is redacted, because the config is printed on startup
+impl Debug for WebhookConfig {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
+        f.debug_struct("WebhookConfig")
+            .field("url", &self.url)
+            .field("secret", &"<redacted>")
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::WebhookConfig;
+
+    /// HTTP(S) URLs are valid
+    #[test]
+    fn valid_webhooks() {
+        let webhook_config = WebhookConfig {
+            url: "https://example.com/hook".to_owned(),
+            secret: "secret".to_owned(),
+        };
+
+        assert!(webhook_config.is_valid());
+    }
+
+    /// Using a URL without an HTTP(S) scheme makes the config invalid
+    #[test]
+    fn invalid_webhooks_url() {
+        let webhook_config = WebhookConfig {
+            url: "example.com/hook".to_owned(),
+            secret: "secret".to_owned(),
+        };
+
+        assert!(!webhook_config.is_valid());
+    }
+
+    /// The secret is not printed when debugging the config
+    #[test]
+    fn secret_redacted() {
+        let webhook_config = WebhookConfig {
+            url: "https://example.com/hook".to_owned(),
+            secret: "secret".to_owned(),
+        };
+
+        assert_eq!(
+            format!("{:?}", webhook_config),
+            "WebhookConfig { url: \"https://example.com/hook\", secret: \"<redacted>\" }"
+        );
+    }
+}
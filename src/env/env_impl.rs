@@ -13,32 +13,88 @@ use crate::{
     util::{Error, ErrorKind},
 };
 use failure::ResultExt;
+use nix::unistd::{chown, Gid, Uid};
 use std::{
-    fs::{self, File, OpenOptions},
-    io::{BufRead, BufReader},
-    os::unix::fs::OpenOptionsExt,
+    collections::HashMap,
+    fs::{self, File, OpenOptions, Permissions},
+    io::{self, BufRead, BufReader, Write},
+    os::unix::fs::{MetadataExt, OpenOptionsExt, PermissionsExt},
     path::Path,
+    sync::{Arc, Mutex},
+    time::SystemTime,
 };
+use tempfile::NamedTempFile;
 
 #[cfg(test)]
 use failure::Fail;
 use shaku::{Component, Module, ModuleBuildContext};
 #[cfg(test)]
-use std::{
-    collections::HashMap,
-    io::{self, Read, Write},
-};
+use std::io::Read;
 #[cfg(test)]
-use tempfile::{tempfile, NamedTempFile};
+use tempfile::tempfile;
+
+/// The permissions of new files
+const NEW_FILE_MODE: u32 = 0o644;
 
 /// Environment of the Pi-hole API. Stores the config and abstracts away some
 /// systems to make testing easier.
 pub enum Env {
-    Production(Config),
+    Production(Config, FileLocks),
     #[cfg(test)]
     Test(Config, HashMap<PiholeFile, NamedTempFile>),
 }
 
+/// Identifies a version of a file, so cached contents can be checked. The
+/// modification time alone is not enough, because its resolution can be
+/// coarser than the time between two writes.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct FileStamp {
+    pub modified: SystemTime,
+    pub len: u64,
+    pub inode: u64,
+}
+
+/// The locks which serialize the writes to each file. Clones share the same
+/// locks.
+#[derive(Clone, Default)]
+pub struct FileLocks(Arc<Mutex<HashMap<PiholeFile, Arc<Mutex<()>>>>>);
+
+impl FileLocks {
+    /// Get the lock of the file
+    fn get(&self, file: PiholeFile) -> Arc<Mutex<()>> {
+        Arc::clone(self.0.lock().unwrap().entry(file).or_default())
+    }
+}
+
+/// Replaces a file while its lock is held, see `Env::with_file_lock`
+pub struct FileWriter<'a> {
+    env: &'a Env,
+    file: PiholeFile,
+}
+
+impl FileWriter<'_> {
+    /// Replace the contents of the file the same way as
+    /// `Env::write_file_atomic`
+    pub fn replace(&self, contents: &[u8]) -> Result<(), Error> {
+        let location = self.env.file_location(self.file);
+
+        match self.env {
+            Env::Production(..) => replace_file(Path::new(location), contents, || Ok(())),
+            #[cfg(test)]
+            Env::Test(_, _) => {
+                // The test files can not be renamed, because they are reopened
+                // by path, so they are overwritten instead
+                self.env
+                    .write_file(self.file, false)?
+                    .write_all(contents)
+                    .context(ErrorKind::FileWrite(location.to_owned()))?;
+
+                Ok(())
+            }
+        }
+    }
+}
+
 // TODO: make this less awkward?
 impl<M: Module> Component<M> for Env {
     type Interface = Self;
@@ -51,14 +107,14 @@ impl<M: Module> Component<M> for Env {
 
 impl Default for Env {
     fn default() -> Self {
-        Env::Production(Config::default())
+        Env::Production(Config::default(), FileLocks::default())
     }
 }
 
 impl Clone for Env {
     fn clone(&self) -> Self {
         match self {
-            Env::Production(config) => Env::Production(config.clone()),
+            Env::Production(config, locks) => Env::Production(config.clone(), locks.clone()),
             // There is no good way to copy NamedTempFiles, and we shouldn't be
             // doing that during a test anyways
             #[cfg(test)]
@@ -71,7 +127,7 @@ impl Env {
     /// Get the API config that was loaded
     pub fn config(&self) -> &Config {
         match self {
-            Env::Production(config) => config,
+            Env::Production(config, _) => config,
             #[cfg(test)]
             Env::Test(config, _) => config,
         }
@@ -80,7 +136,7 @@ impl Env {
     /// Get the location of a file
     pub fn file_location(&self, file: PiholeFile) -> &str {
         match self {
-            Env::Production(config) => config.file_locations.get(file),
+            Env::Production(config, _) => config.file_locations.get(file),
             #[cfg(test)]
             Env::Test(_, _) => file.default_location(),
         }
@@ -89,7 +145,7 @@ impl Env {
     /// Open a file for reading
     pub fn read_file(&self, file: PiholeFile) -> Result<File, Error> {
         match self {
-            Env::Production(_) => {
+            Env::Production(..) => {
                 let file_location = self.file_location(file);
                 File::open(file_location)
                     .context(ErrorKind::FileRead(file_location.to_owned()))
@@ -121,7 +177,7 @@ impl Env {
     /// truncated.
     pub fn write_file(&self, file: PiholeFile, append: bool) -> Result<File, Error> {
         match self {
-            Env::Production(_) => {
+            Env::Production(..) => {
                 let mut open_options = OpenOptions::new();
                 open_options.create(true).write(true).mode(0o644);
 
@@ -159,10 +215,37 @@ impl Env {
         }
     }
 
+    /// Replace the contents of a file, without ever leaving it half written.
+    /// The contents are written to a temporary file in the same directory,
+    /// synced to disk, and renamed over the original, keeping its permissions
+    /// and owner. Writes to the same file are serialized.
+    pub fn write_file_atomic(&self, file: PiholeFile, contents: &[u8]) -> Result<(), Error> {
+        self.with_file_lock(file, |writer| writer.replace(contents))
+    }
+
+    /// Run `update` while holding the lock which serializes the writes to the
+    /// file, so the file can be read and replaced without losing the writes
+    /// made in between. The file is replaced through the `FileWriter`, since
+    /// `write_file_atomic` would wait for the lock which is already held.
+    pub fn with_file_lock<T>(
+        &self,
+        file: PiholeFile,
+        update: impl FnOnce(FileWriter) -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        let lock = match self {
+            Env::Production(_, locks) => Some(locks.get(file)),
+            #[cfg(test)]
+            Env::Test(_, _) => None,
+        };
+        let _guard = lock.as_ref().map(|lock| lock.lock().unwrap());
+
+        update(FileWriter { env: self, file })
+    }
+
     /// Rename (move) a file from `from` to `to`
     pub fn rename_file(&self, from: PiholeFile, to: PiholeFile) -> Result<(), Error> {
         match self {
-            Env::Production(_) => {
+            Env::Production(..) => {
                 let to_path = self.file_location(to);
 
                 fs::rename(self.file_location(from), to_path)
@@ -209,18 +292,163 @@ impl Env {
     /// Check if a file exists
     pub fn file_exists(&self, file: PiholeFile) -> bool {
         match self {
-            Env::Production(_) => Path::new(self.file_location(file)).is_file(),
+            Env::Production(..) => Path::new(self.file_location(file)).is_file(),
             #[cfg(test)]
             Env::Test(_, map) => map.contains_key(&file),
         }
     }
 
+    /// Get the stamp of the current version of a file, or `None` if it can
+    /// not be found
+    pub fn file_stamp(&self, file: PiholeFile) -> Option<FileStamp> {
+        let metadata = match self {
+            Env::Production(..) => fs::metadata(self.file_location(file)).ok()?,
+            #[cfg(test)]
+            Env::Test(_, map) => map.get(&file)?.as_file().metadata().ok()?,
+        };
+
+        Some(FileStamp {
+            modified: metadata.modified().ok()?,
+            len: metadata.len(),
+            inode: metadata.ino(),
+        })
+    }
+
     /// Check if we're in a testing environment
     pub fn is_test(&self) -> bool {
         match self {
-            Env::Production(_) => false,
+            Env::Production(..) => false,
             #[cfg(test)]
             Env::Test(_, _) => true,
         }
     }
 }
+
+/// Replace the file at the path with the contents. The contents are written
+/// to a temporary file in the same directory, synced to disk, and renamed over
+/// the original, so the original is untouched until the rename. The original
+/// file's permissions and owner are copied to the new file. `before_rename` is
+/// called right before the rename, so tests can simulate a failure there.
+fn replace_file(
+    path: &Path,
+    contents: &[u8],
+    before_rename: impl FnOnce() -> io::Result<()>,
+) -> Result<(), Error> {
+    let location = path.to_string_lossy().into_owned();
+    let context = || ErrorKind::FileWrite(location.clone());
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+
+    let mut temp_file = NamedTempFile::new_in(dir).with_context(|_| context())?;
+    temp_file.write_all(contents).with_context(|_| context())?;
+
+    match fs::metadata(path) {
+        Ok(metadata) => {
+            fs::set_permissions(temp_file.path(), metadata.permissions())
+                .with_context(|_| context())?;
+
+            // Only change the owner if it is different, which needs root
+            let temp_metadata = temp_file.as_file().metadata().with_context(|_| context())?;
+            if (temp_metadata.uid(), temp_metadata.gid()) != (metadata.uid(), metadata.gid()) {
+                chown(
+                    temp_file.path(),
+                    Some(Uid::from_raw(metadata.uid())),
+                    Some(Gid::from_raw(metadata.gid())),
+                )
+                .with_context(|_| context())?;
+            }
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            fs::set_permissions(temp_file.path(), Permissions::from_mode(NEW_FILE_MODE))
+                .with_context(|_| context())?;
+        }
+        Err(e) => return Err(e).with_context(|_| context()).map_err(Error::from),
+    }
+
+    temp_file.as_file().sync_all().with_context(|_| context())?;
+    before_rename().with_context(|_| context())?;
+    temp_file
+        .persist(path)
+        .map_err(|e| e.error)
+        .with_context(|_| context())?;
+
+    // Sync the directory, so the rename itself is on disk
+    File::open(dir)
+        .and_then(|dir| dir.sync_all())
+        .with_context(|_| context())?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{replace_file, Env, FileLocks};
+    use crate::env::{Config, PiholeFile};
+    use std::{
+        fs,
+        io::{self, ErrorKind},
+        os::unix::fs::PermissionsExt,
+    };
+    use tempfile::tempdir;
+
+    /// The contents are replaced, and the permissions of the original file
+    /// are kept
+    #[test]
+    fn replace_keeps_permissions() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("setupVars.conf");
+        fs::write(&path, "PIHOLE_DNS_1=8.8.8.8\n").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o640)).unwrap();
+
+        replace_file(&path, b"PIHOLE_DNS_1=1.1.1.1\n", || Ok(())).unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "PIHOLE_DNS_1=1.1.1.1\n");
+        assert_eq!(
+            fs::metadata(&path).unwrap().permissions().mode() & 0o777,
+            0o640
+        );
+    }
+
+    /// A failure between writing the temporary file and renaming it leaves
+    /// the original untouched, and the temporary file is removed
+    #[test]
+    fn failure_before_rename() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("setupVars.conf");
+        fs::write(&path, "PIHOLE_DNS_1=8.8.8.8\n").unwrap();
+
+        assert!(
+            replace_file(&path, b"PIHOLE_DNS_1=1.1.1.1\n", || Err(io::Error::from(
+                ErrorKind::Other
+            )))
+            .is_err()
+        );
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "PIHOLE_DNS_1=8.8.8.8\n");
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    /// A missing file is created with the default permissions
+    #[test]
+    fn create_missing_file() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("pihole-FTL.conf");
+        let config = Config {
+            file_locations: toml::from_str(&format!("ftl_config = \"{}\"", path.display()))
+                .unwrap(),
+            ..Config::default()
+        };
+        let env = Env::Production(config, FileLocks::default());
+
+        env.write_file_atomic(PiholeFile::FtlConfig, b"PRIVACYLEVEL=1\n")
+            .unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "PRIVACYLEVEL=1\n");
+        assert_eq!(
+            fs::metadata(&path).unwrap().permissions().mode() & 0o777,
+            0o644
+        );
+    }
+}
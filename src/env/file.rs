@@ -23,6 +23,10 @@ pub enum PiholeFile {
     GravityBackup,
     BlackList,
     BlackListBackup,
+    CustomList,
+    CnameConfig,
+    StaticDhcpConfig,
+    DhcpLeases,
 }
 
 impl PiholeFile {
@@ -41,6 +45,10 @@ impl PiholeFile {
             PiholeFile::GravityBackup => "/etc/pihole/gravity.list.bck",
             PiholeFile::BlackList => "/etc/pihole/black.list",
             PiholeFile::BlackListBackup => "/etc/pihole/black.list.bck",
+            PiholeFile::CustomList => "/etc/pihole/custom.list",
+            PiholeFile::CnameConfig => "/etc/dnsmasq.d/05-pihole-custom-cname.conf",
+            PiholeFile::StaticDhcpConfig => "/etc/dnsmasq.d/04-pihole-static-dhcp.conf",
+            PiholeFile::DhcpLeases => "/etc/pihole/dhcp.leases",
         }
     }
 }
@@ -13,7 +13,7 @@ mod env_impl;
 mod file;
 
 pub use self::{
-    config::{Config, DEFAULT_CONFIG_LOCATION},
-    env_impl::Env,
+    config::{Config, WebhookConfig, DEFAULT_CONFIG_LOCATION},
+    env_impl::{Env, FileLocks, FileStamp},
     file::PiholeFile,
 };
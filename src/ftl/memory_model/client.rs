@@ -22,7 +22,7 @@ use std::fmt::{
 #[derive(Serialize)]
 #[cfg_attr(test, derive(Debug, PartialEq))]
 pub struct ClientReply {
-    pub name: String,
+    pub name: Option<String>,
     pub ip: String,
 }
 
@@ -100,11 +100,11 @@ impl FtlClient {
 
     /// Convert this FTL client into the reply format
     pub fn as_reply(&self, strings: &FtlStrings) -> ClientReply {
-        let name = self.get_name(&strings).unwrap_or_default();
+        let name = self.get_name(&strings);
         let ip = self.get_ip(&strings);
 
         ClientReply {
-            name: name.to_owned(),
+            name: name.map(str::to_owned),
             ip: ip.to_owned(),
         }
     }
@@ -8,6 +8,7 @@
 // This file is copyright under the latest version of the EUPL.
 // Please see LICENSE file for your rights under this license.
 
+use crate::util::{Error, ErrorKind};
 use rocket::{
     form,
     form::{FromFormField, ValueField},
@@ -16,7 +17,7 @@ use rocket::{
 /// The FTL counters stored in shared memory
 #[repr(C)]
 #[cfg_attr(test, derive(Default))]
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Serialize)]
 pub struct FtlCounters {
     pub total_queries: libc::c_int,
     pub blocked_queries: libc::c_int,
@@ -41,6 +42,24 @@ pub struct FtlCounters {
 }
 
 impl FtlCounters {
+    /// Check if the counters are consistent with the lengths of the shared
+    /// memory arrays. If FTL was interrupted while adding an entry, a counter
+    /// can be ahead of the memory it describes.
+    pub fn is_consistent(
+        &self,
+        queries: usize,
+        domains: usize,
+        clients: usize,
+        upstreams: usize,
+    ) -> bool {
+        let fits = |count: libc::c_int, len: usize| count >= 0 && count as usize <= len;
+
+        fits(self.total_queries, queries)
+            && fits(self.total_domains, domains)
+            && fits(self.total_clients, clients)
+            && fits(self.total_upstreams, upstreams)
+    }
+
     pub fn query_type(&self, query_type: FtlQueryType) -> usize {
         self.query_type_counters[query_type as usize - 1] as usize
     }
@@ -107,4 +126,95 @@ impl FtlQueryType {
     pub fn get_name(self) -> String {
         format!("{:?}", self)
     }
+
+    /// Parse a comma separated list of query type names (in any case) and
+    /// numbers. An unknown value is an error listing the valid values.
+    pub fn parse_list(list: &str) -> Result<Vec<Self>, Error> {
+        let mut query_types = Vec::new();
+
+        for name in list.split(',').map(str::trim) {
+            let query_type = Self::variants()
+                .iter()
+                .copied()
+                .find(|query_type| query_type.get_name().eq_ignore_ascii_case(name))
+                .or_else(|| name.parse::<isize>().ok().and_then(Self::from_number))
+                .ok_or_else(|| {
+                    Error::from(ErrorKind::InvalidParameter(
+                        "query_type".to_owned(),
+                        name.to_owned(),
+                        Self::variants()
+                            .iter()
+                            .map(|query_type| query_type.get_name())
+                            .chain((1..=Self::variants().len()).map(|num| num.to_string()))
+                            .collect(),
+                    ))
+                })?;
+
+            if !query_types.contains(&query_type) {
+                query_types.push(query_type);
+            }
+        }
+
+        Ok(query_types)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{FtlCounters, FtlQueryType};
+    use crate::util::ErrorKind;
+
+    /// Counters within the lengths of the arrays are consistent
+    #[test]
+    fn consistent() {
+        let counters = FtlCounters {
+            total_queries: 10,
+            total_domains: 3,
+            total_clients: 2,
+            total_upstreams: 0,
+            ..FtlCounters::default()
+        };
+
+        assert!(counters.is_consistent(10, 3, 4, 1));
+    }
+
+    /// Counters past the end of an array, or negative counters, are not
+    /// consistent
+    #[test]
+    fn inconsistent() {
+        let counters = FtlCounters {
+            total_queries: 11,
+            ..FtlCounters::default()
+        };
+        let negative = FtlCounters {
+            total_clients: -1,
+            ..FtlCounters::default()
+        };
+
+        assert!(!counters.is_consistent(10, 0, 0, 0));
+        assert!(!negative.is_consistent(10, 0, 0, 0));
+    }
+
+    /// Query types are parsed by name in any case or by number, without
+    /// duplicates
+    #[test]
+    fn parse_query_types() {
+        assert_eq!(
+            FtlQueryType::parse_list("A,aaaa, 6,1").unwrap(),
+            vec![FtlQueryType::A, FtlQueryType::AAAA, FtlQueryType::PTR]
+        );
+    }
+
+    /// Unknown and empty query types are rejected
+    #[test]
+    fn parse_invalid_query_types() {
+        for list in &["A,MX", "", "A,"] {
+            match FtlQueryType::parse_list(list).unwrap_err().kind() {
+                ErrorKind::InvalidParameter(parameter, _, _) => {
+                    assert_eq!(parameter, "query_type")
+                }
+                kind => panic!("Unexpected error {:?}", kind),
+            }
+        }
+    }
 }
@@ -33,3 +33,6 @@ pub use self::{
     strings::FtlStrings,
     upstream::FtlUpstream,
 };
+
+#[cfg(test)]
+pub use self::settings::FTL_SHM_VERSION;
@@ -8,7 +8,11 @@
 // This file is copyright under the latest version of the EUPL.
 // Please see LICENSE file for your rights under this license.
 
-use crate::{ftl::FtlQueryType, settings::FtlPrivacyLevel};
+use crate::{
+    ftl::FtlQueryType,
+    settings::FtlPrivacyLevel,
+    util::{Error, ErrorKind},
+};
 use rocket::{
     form,
     form::{FromFormField, ValueField},
@@ -24,6 +28,10 @@ pub const BLOCKED_STATUSES: [i32; 6] = [
     FtlQueryStatus::ExternalBlockNxdomainRa as i32,
 ];
 
+/// The names of groups of query statuses, which can be used to filter queries
+/// by status along with the status numbers
+pub const STATUS_NAMES: [&str; 4] = ["blocked", "permitted", "cached", "forwarded"];
+
 /// The query struct stored in shared memory
 #[repr(C)]
 #[cfg_attr(test, derive(PartialEq, Debug))]
@@ -53,6 +61,17 @@ impl FtlQuery {
     pub fn is_blocked(&self) -> bool {
         BLOCKED_STATUSES.contains(&(self.status as i32))
     }
+
+    /// Get the response time of the query in units of 1/10 milliseconds, or
+    /// `None` if no reply was received. FTL uses a response time of zero, or
+    /// of at least 30 minutes, for queries without a reply.
+    pub fn reply_time(&self) -> Option<u64> {
+        if self.response_time > 0 && self.response_time < 18_000_000 {
+            Some(self.response_time as u64)
+        } else {
+            None
+        }
+    }
 }
 
 /// The statuses an FTL query can have
@@ -72,6 +91,37 @@ pub enum FtlQueryStatus {
 }
 
 impl FtlQueryStatus {
+    /// A list of all `FtlQueryStatus` variants
+    pub fn variants() -> &'static [FtlQueryStatus] {
+        &[
+            FtlQueryStatus::Unknown,
+            FtlQueryStatus::Gravity,
+            FtlQueryStatus::Forward,
+            FtlQueryStatus::Cache,
+            FtlQueryStatus::Wildcard,
+            FtlQueryStatus::Blacklist,
+            FtlQueryStatus::ExternalBlockIp,
+            FtlQueryStatus::ExternalBlockNull,
+            FtlQueryStatus::ExternalBlockNxdomainRa,
+        ]
+    }
+
+    /// Get the name of the query status. The permitted statuses use the same
+    /// names as in `STATUS_NAMES`.
+    pub fn get_name(self) -> &'static str {
+        match self {
+            FtlQueryStatus::Unknown => "unknown",
+            FtlQueryStatus::Gravity => "gravity",
+            FtlQueryStatus::Forward => "forwarded",
+            FtlQueryStatus::Cache => "cached",
+            FtlQueryStatus::Wildcard => "regex",
+            FtlQueryStatus::Blacklist => "blacklist",
+            FtlQueryStatus::ExternalBlockIp => "external_blocked_ip",
+            FtlQueryStatus::ExternalBlockNull => "external_blocked_null",
+            FtlQueryStatus::ExternalBlockNxdomainRa => "external_blocked_nxra",
+        }
+    }
+
     /// Get the query status from its ordinal value
     pub fn from_number(num: isize) -> Option<Self> {
         match num {
@@ -87,6 +137,80 @@ impl FtlQueryStatus {
             _ => None,
         }
     }
+
+    /// Get the query statuses referenced by a group name (see
+    /// `STATUS_NAMES`), the name of a single status, or a status number
+    pub fn from_name(name: &str) -> Option<Vec<Self>> {
+        if let Some(&status) = Self::variants()
+            .iter()
+            .find(|status| status.get_name() == name)
+        {
+            return Some(vec![status]);
+        }
+
+        match name {
+            "blocked" => Some(
+                BLOCKED_STATUSES
+                    .iter()
+                    .filter_map(|&status| Self::from_number(status as isize))
+                    .collect(),
+            ),
+            "permitted" => Some(vec![FtlQueryStatus::Forward, FtlQueryStatus::Cache]),
+            "cached" => Some(vec![FtlQueryStatus::Cache]),
+            "forwarded" => Some(vec![FtlQueryStatus::Forward]),
+            _ => name
+                .parse::<isize>()
+                .ok()
+                .and_then(Self::from_number)
+                .map(|status| vec![status]),
+        }
+    }
+
+    /// Parse a comma separated list of status names and numbers into the
+    /// query statuses they reference. An unknown value is an error listing
+    /// the valid values.
+    pub fn parse_list(list: &str) -> Result<Vec<Self>, Error> {
+        let mut statuses = Vec::new();
+
+        for name in list.split(',').map(str::trim) {
+            let named_statuses = Self::from_name(name).ok_or_else(|| {
+                Error::from(ErrorKind::InvalidParameter(
+                    "status".to_owned(),
+                    name.to_owned(),
+                    Self::valid_names(),
+                ))
+            })?;
+
+            for status in named_statuses {
+                if !statuses.contains(&status) {
+                    statuses.push(status);
+                }
+            }
+        }
+
+        Ok(statuses)
+    }
+
+    /// Get the names and numbers which can be used to reference statuses
+    fn valid_names() -> Vec<String> {
+        let mut names: Vec<&str> = STATUS_NAMES.to_vec();
+
+        for status in Self::variants() {
+            if !names.contains(&status.get_name()) {
+                names.push(status.get_name());
+            }
+        }
+
+        names
+            .into_iter()
+            .map(str::to_owned)
+            .chain(
+                (0..)
+                    .take_while(|&num| Self::from_number(num).is_some())
+                    .map(|num| num.to_string()),
+            )
+            .collect()
+    }
 }
 
 impl<'v> FromFormField<'v> for FtlQueryStatus {
@@ -120,6 +244,39 @@ pub enum FtlQueryReplyType {
 }
 
 impl FtlQueryReplyType {
+    /// A list of the reply types which have a name. Queries with any other
+    /// reply type are counted as other.
+    pub fn variants() -> &'static [FtlQueryReplyType] {
+        &[
+            FtlQueryReplyType::NODATA,
+            FtlQueryReplyType::NXDOMAIN,
+            FtlQueryReplyType::CNAME,
+            FtlQueryReplyType::IP,
+            FtlQueryReplyType::DOMAIN,
+            FtlQueryReplyType::RRNAME,
+            FtlQueryReplyType::SERVFAIL,
+            FtlQueryReplyType::REFUSED,
+            FtlQueryReplyType::NOTIMP,
+        ]
+    }
+
+    /// Get the name of the reply type
+    pub fn get_name(self) -> &'static str {
+        match self {
+            FtlQueryReplyType::Unknown => "UNKNOWN",
+            FtlQueryReplyType::NODATA => "NODATA",
+            FtlQueryReplyType::NXDOMAIN => "NXDOMAIN",
+            FtlQueryReplyType::CNAME => "CNAME",
+            FtlQueryReplyType::IP => "IP",
+            FtlQueryReplyType::DOMAIN => "DOMAIN",
+            FtlQueryReplyType::RRNAME => "RRNAME",
+            FtlQueryReplyType::SERVFAIL => "SERVFAIL",
+            FtlQueryReplyType::REFUSED => "REFUSED",
+            FtlQueryReplyType::NOTIMP => "NOTIMP",
+            FtlQueryReplyType::OTHER => "OTHER",
+        }
+    }
+
     /// Get the query reply type from its ordinal value
     pub fn from_number(num: isize) -> Option<Self> {
         match num {
@@ -176,10 +333,33 @@ impl FtlDnssecType {
             _ => None,
         }
     }
+
+    /// Get the name of the DNSSEC type used in API responses. Queries made
+    /// while DNSSEC was disabled do not have a DNSSEC type, so they are
+    /// unknown.
+    pub fn name(self) -> &'static str {
+        match self {
+            FtlDnssecType::Unspecified | FtlDnssecType::Unknown => "unknown",
+            FtlDnssecType::Secure => "secure",
+            FtlDnssecType::Insecure => "insecure",
+            FtlDnssecType::Bogus => "bogus",
+            FtlDnssecType::Abandoned => "abandoned",
+        }
+    }
 }
 
 impl<'v> FromFormField<'v> for FtlDnssecType {
+    /// The DNSSEC type can be given by name or by number
     fn from_value(field: ValueField<'v>) -> form::Result<'v, Self> {
+        match field.value {
+            "unknown" => return Ok(FtlDnssecType::Unknown),
+            "secure" => return Ok(FtlDnssecType::Secure),
+            "insecure" => return Ok(FtlDnssecType::Insecure),
+            "bogus" => return Ok(FtlDnssecType::Bogus),
+            "abandoned" => return Ok(FtlDnssecType::Abandoned),
+            _ => (),
+        }
+
         let num = field
             .value
             .parse::<u8>()
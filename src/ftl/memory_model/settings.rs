@@ -8,6 +8,11 @@
 // This file is copyright under the latest version of the EUPL.
 // Please see LICENSE file for your rights under this license.
 
+use crate::util::{Error, ErrorKind};
+
+/// The version of FTL's shared memory layout which this API was built against
+pub const FTL_SHM_VERSION: libc::c_int = 7;
+
 /// The settings structure used to share version information and other settings
 #[derive(Copy, Clone)]
 #[repr(C)]
@@ -20,9 +25,84 @@ pub struct FtlSettings {
 impl Default for FtlSettings {
     fn default() -> Self {
         FtlSettings {
-            version: 0,
+            version: FTL_SHM_VERSION,
             global_shm_counter: 0,
             next_str_pos: 1, // 0 is used as the empty string
         }
     }
 }
+
+impl FtlSettings {
+    /// Check that FTL's shared memory uses the layout this API was built
+    /// against. The other segments can not be read if it does not, because
+    /// their structures may have changed.
+    pub fn check_version(&self) -> Result<(), Error> {
+        if self.version == FTL_SHM_VERSION {
+            Ok(())
+        } else {
+            Err(Error::from(ErrorKind::SharedMemoryVersion(
+                self.version as usize,
+                FTL_SHM_VERSION as usize,
+            )))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{FtlSettings, FTL_SHM_VERSION};
+    use crate::{
+        ftl::FtlMemory,
+        testing::{TestBuilder, TestFtlMemoryBuilder},
+        util::ErrorKind,
+    };
+    use rocket::http::Status;
+
+    /// The supported version passes the check
+    #[test]
+    fn supported_version() {
+        assert!(FtlSettings::default().check_version().is_ok());
+    }
+
+    /// Any other version is rejected with both versions in the error
+    #[test]
+    fn unsupported_version() {
+        let settings = FtlSettings {
+            version: FTL_SHM_VERSION + 1,
+            ..FtlSettings::default()
+        };
+
+        assert_eq!(
+            settings.check_version().unwrap_err().kind(),
+            ErrorKind::SharedMemoryVersion(FTL_SHM_VERSION as usize + 1, FTL_SHM_VERSION as usize)
+        );
+    }
+
+    /// Endpoints reading shared memory return an error instead of data if the
+    /// version is not supported
+    #[test]
+    fn endpoint_version_mismatch() {
+        let mut ftl_memory = TestFtlMemoryBuilder::new().build();
+
+        if let FtlMemory::Test { settings, .. } = &mut ftl_memory {
+            settings.version = FTL_SHM_VERSION + 1;
+        }
+
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/query_statuses")
+            .ftl_memory(ftl_memory)
+            .expect_status(Status::InternalServerError)
+            .expect_json(json!({
+                "error": {
+                    "key": "shared_memory_version",
+                    "message": format!(
+                        "FTL shared memory version {} is not supported, expected {}",
+                        FTL_SHM_VERSION + 1,
+                        FTL_SHM_VERSION
+                    ),
+                    "data": null
+                }
+            }))
+            .test();
+    }
+}
@@ -8,8 +8,9 @@
 // This file is copyright under the latest version of the EUPL.
 // Please see LICENSE file for your rights under this license.
 
+use crate::ftl::shm_cache::SharedMapping;
 use shmem::Array;
-use std::{ffi::CStr, marker::PhantomData};
+use std::{marker::PhantomData, slice, str};
 
 #[cfg(test)]
 use std::collections::HashMap;
@@ -22,7 +23,7 @@ use std::collections::HashMap;
 pub enum FtlStrings<'test> {
     // Use `PhantomData` because when not in testing mode, the 'test lifetime will be unused and
     // will cause an error. The `PhantomData` is zero-sized, so it will not actually exist.
-    Production(Array<libc::c_char>, PhantomData<&'test bool>),
+    Production(SharedMapping<Array<libc::c_char>>, PhantomData<&'test bool>),
     #[cfg(test)]
     Test(&'test HashMap<usize, String>),
 }
@@ -46,17 +47,39 @@ impl<'test> FtlStrings<'test> {
         }
     }
 
-    /// This function is used for `FtlStrings::Production`. It checks to see
-    /// if the string exists, and then creates a `CStr` from a pointer. It
-    /// is assumed that the string has a null terminator. Then the `CStr` is
-    /// converted into `&str`. If the conversion fails, `None` is returned.
-    fn get_str_prod(strings: &[libc::c_char], id: usize) -> Option<&str> {
-        if strings.get(id).is_some() {
-            unsafe { CStr::from_ptr(&strings[id]) }.to_str().ok()
-        } else {
-            None
+    /// Get the size of the string memory in bytes. The test strings are
+    /// measured as if they were laid out in shared memory, ending at the
+    /// null terminator of the last string.
+    pub fn len(&self) -> usize {
+        match self {
+            FtlStrings::Production(strings, ..) => strings.len(),
+            #[cfg(test)]
+            FtlStrings::Test(strings) => strings
+                .iter()
+                .map(|(id, string)| id + string.len() + 1)
+                .max()
+                .unwrap_or(0),
         }
     }
+
+    /// Check if there is no string memory
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// This function is used for `FtlStrings::Production`. It searches the
+    /// string memory from `id` for the null terminator, and converts the bytes
+    /// before it into `&str`. If the string does not exist, has no null
+    /// terminator, or the conversion fails, `None` is returned.
+    fn get_str_prod(strings: &[libc::c_char], id: usize) -> Option<&str> {
+        let strings = strings.get(id..)?;
+
+        // `c_char` is either `i8` or `u8`, which have the same layout
+        let bytes = unsafe { slice::from_raw_parts(strings.as_ptr() as *const u8, strings.len()) };
+        let end = bytes.iter().position(|&byte| byte == 0)?;
+
+        str::from_utf8(&bytes[..end]).ok()
+    }
 }
 
 #[cfg(test)]
@@ -86,4 +109,28 @@ mod tests {
         assert_eq!(FtlStrings::get_str_prod(&strings, 1), Some("test"));
         assert_eq!(FtlStrings::get_str_prod(&strings, 6), None);
     }
+
+    /// A string without a null terminator is not read past the end of the
+    /// string memory
+    #[test]
+    fn get_str_prod_unterminated() {
+        let strings: Vec<libc::c_char> = ['\0', 't', 'e', 's', 't']
+            .iter()
+            .map(|&c| c as libc::c_char)
+            .collect();
+
+        assert_eq!(FtlStrings::get_str_prod(&strings, 0), Some(""));
+        assert_eq!(FtlStrings::get_str_prod(&strings, 1), None);
+    }
+
+    /// The test strings are measured up to the end of the last string
+    #[test]
+    fn len() {
+        let mut data = HashMap::new();
+        data.insert(1, "test".to_owned());
+        data.insert(6, "example.com".to_owned());
+
+        assert_eq!(FtlStrings::Test(&data).len(), 18);
+        assert!(FtlStrings::Test(&HashMap::new()).is_empty());
+    }
 }
@@ -10,12 +10,15 @@
 
 mod lock_thread;
 mod memory_model;
+mod redactor;
 mod shared_lock;
 mod shared_memory;
+mod shm_cache;
 mod socket;
 
 pub use self::{
     memory_model::*,
+    redactor::FtlRedactor,
     shared_lock::{ShmLock, ShmLockGuard},
     shared_memory::FtlMemory,
     socket::{FtlConnection, FtlConnectionType},
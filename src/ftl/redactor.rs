@@ -0,0 +1,195 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// FTL Privacy Redaction
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    ftl::{FtlClient, FtlDomain, FtlQuery, FtlStrings},
+    settings::{FtlPrivacyLevel, HIDDEN_CLIENT, HIDDEN_DOMAIN},
+};
+
+/// Applies the privacy level to the data read from FTL's shared memory.
+/// Endpoints check if they can return data at all with `allows`, and read
+/// domains and clients through the redactor instead of applying the privacy
+/// rules themselves.
+#[cfg_attr(test, derive(Debug))]
+#[derive(Copy, Clone, PartialEq)]
+pub struct FtlRedactor {
+    level: FtlPrivacyLevel,
+}
+
+impl FtlRedactor {
+    /// Create a redactor for the configured privacy level
+    pub fn new(level: FtlPrivacyLevel) -> Self {
+        FtlRedactor { level }
+    }
+
+    /// Get the configured privacy level
+    pub fn level(self) -> FtlPrivacyLevel {
+        self.level
+    }
+
+    /// Check if an endpoint which returns no data at `hidden_at` and higher
+    /// levels can return data
+    pub fn allows(self, hidden_at: FtlPrivacyLevel) -> bool {
+        self.level.allows(hidden_at)
+    }
+
+    /// Get the level which applies to the query. This is the stricter of the
+    /// configured level and the level the query was made with.
+    pub fn query_level(self, query: &FtlQuery) -> FtlPrivacyLevel {
+        self.level.stricter(query.privacy_level)
+    }
+
+    /// Get the domain of the query, or the hidden domain if it is private. A
+    /// domain ID past the end of shared memory gives an empty string.
+    pub fn query_domain<'a>(
+        self,
+        query: &FtlQuery,
+        domains: &[FtlDomain],
+        strings: &'a FtlStrings,
+    ) -> &'a str {
+        self.query_level(query).redact_domain(
+            domains
+                .get(query.domain_id as usize)
+                .map(|domain| domain.get_domain(strings))
+                .unwrap_or_default(),
+        )
+    }
+
+    /// Check if the client of the query is private
+    pub fn hides_query_client(self, query: &FtlQuery) -> bool {
+        self.query_level(query).hides_clients()
+    }
+
+    /// Remove the domains which can not be shown. These are all of the
+    /// domains if the level hides domains, and otherwise the placeholder
+    /// domain FTL stores for private queries.
+    pub fn retain_domains(self, domains: &mut Vec<&FtlDomain>, strings: &FtlStrings) {
+        if self.level.hides_domains() {
+            domains.clear();
+        } else {
+            domains.retain(|domain| domain.get_domain(strings) != HIDDEN_DOMAIN);
+        }
+    }
+
+    /// Remove the clients which can not be shown. These are all of the
+    /// clients if the level hides clients, and otherwise the placeholder
+    /// client FTL stores for private queries.
+    pub fn retain_clients(self, clients: &mut Vec<&FtlClient>, strings: &FtlStrings) {
+        if self.level.hides_clients() {
+            clients.clear();
+        } else {
+            clients.retain(|client| client.get_ip(strings) != HIDDEN_CLIENT);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::FtlRedactor;
+    use crate::{
+        ftl::{FtlClient, FtlDomain, ShmLockGuard},
+        settings::FtlPrivacyLevel,
+        testing::privacy_test_memory,
+    };
+
+    /// What the redactor shows of the privacy fixture
+    #[derive(Debug, PartialEq)]
+    struct Shown<'a> {
+        /// If endpoints which are hidden at the maximum level return data
+        allows_queries: bool,
+        /// The domains of the queries, oldest first
+        query_domains: Vec<&'a str>,
+        /// The number of queries whose client can be shown
+        query_clients: usize,
+        domains: Vec<&'a str>,
+        clients: Vec<&'a str>,
+    }
+
+    /// Each level hides at least as much as the level below it, and queries
+    /// which FTL recorded with a stricter level stay hidden at every level
+    #[test]
+    fn levels() {
+        let nothing = || Shown {
+            allows_queries: false,
+            query_domains: vec!["hidden"; 4],
+            query_clients: 0,
+            domains: Vec::new(),
+            clients: Vec::new(),
+        };
+        let table = vec![
+            (
+                FtlPrivacyLevel::ShowAll,
+                Shown {
+                    allows_queries: true,
+                    query_domains: vec!["example.com", "ads.com", "hidden", "hidden"],
+                    query_clients: 3,
+                    domains: vec!["example.com", "ads.com", "tracker.com"],
+                    clients: vec!["10.1.1.1", "10.1.1.2"],
+                },
+            ),
+            (
+                FtlPrivacyLevel::HideDomains,
+                Shown {
+                    allows_queries: true,
+                    query_clients: 3,
+                    clients: vec!["10.1.1.1", "10.1.1.2"],
+                    ..nothing()
+                },
+            ),
+            (
+                FtlPrivacyLevel::HideDomainsAndClients,
+                Shown {
+                    allows_queries: true,
+                    ..nothing()
+                },
+            ),
+            (FtlPrivacyLevel::Maximum, nothing()),
+            (FtlPrivacyLevel::NoStats, nothing()),
+        ];
+
+        let ftl_memory = privacy_test_memory();
+        let lock = ShmLockGuard::Test;
+        let strings = ftl_memory.strings(&lock).unwrap();
+        let queries = ftl_memory.queries(&lock).unwrap();
+        let ftl_domains = ftl_memory.domains(&lock).unwrap();
+        let ftl_clients = ftl_memory.clients(&lock).unwrap();
+
+        for (level, expected) in table {
+            let redactor = FtlRedactor::new(level);
+
+            let mut domains: Vec<&FtlDomain> = ftl_domains.iter().collect();
+            redactor.retain_domains(&mut domains, &strings);
+            let mut clients: Vec<&FtlClient> = ftl_clients.iter().collect();
+            redactor.retain_clients(&mut clients, &strings);
+
+            let shown = Shown {
+                allows_queries: redactor.allows(FtlPrivacyLevel::Maximum),
+                query_domains: queries
+                    .iter()
+                    .map(|query| redactor.query_domain(query, &ftl_domains, &strings))
+                    .collect(),
+                query_clients: queries
+                    .iter()
+                    .filter(|query| !redactor.hides_query_client(query))
+                    .count(),
+                domains: domains
+                    .iter()
+                    .map(|domain| domain.get_domain(&strings))
+                    .collect(),
+                clients: clients
+                    .iter()
+                    .map(|client| client.get_ip(&strings))
+                    .collect(),
+            };
+
+            assert_eq!((level, shown), (level, expected));
+        }
+    }
+}
@@ -53,6 +53,23 @@ impl ShmLock {
         }
     }
 
+    /// Create a `ShmLock` which grants every request without locking FTL's
+    /// shared memory, for testing with segments which FTL does not use
+    #[cfg(test)]
+    pub fn unlocked() -> ShmLock {
+        let (request_sender, request_receiver) = channel::<LockRequest>();
+
+        thread::spawn(move || {
+            for (_, response_sender) in request_receiver.iter() {
+                response_sender.send(Ok(0)).unwrap();
+            }
+        });
+
+        ShmLock {
+            sender: Mutex::new(request_sender),
+        }
+    }
+
     /// Acquire a read lock on the shared memory. It will last as long as the
     /// guard (return value) lives.
     pub fn read(&self) -> Result<ShmLockGuard, Error> {
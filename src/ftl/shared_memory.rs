@@ -10,37 +10,71 @@
 
 use crate::{
     ftl::{
+        shm_cache::{SegmentId, ShmCache},
         FtlClient, FtlCounters, FtlDomain, FtlOverTime, FtlQuery, FtlStrings, FtlUpstream, ShmLock,
         ShmLockGuard,
     },
     util::Error,
 };
 use shmem::{Array, Map, Object};
-use std::{marker::PhantomData, ops::Deref};
+use std::{
+    marker::PhantomData,
+    ops::Deref,
+    path::{Path, PathBuf},
+    thread,
+    time::Duration,
+};
 
 use crate::{ftl::memory_model::FtlSettings, util::ErrorKind};
 #[cfg(test)]
 use std::collections::HashMap;
 
-const FTL_SHM_VERSION: usize = 7;
+/// The number of times to try to get a consistent view of shared memory
+/// before giving up
+const FTL_SHM_READ_ATTEMPTS: usize = 3;
+
+/// How long to give FTL to finish an update before trying again
+const FTL_SHM_RETRY_DELAY: Duration = Duration::from_millis(10);
+
+/// The directory where shared memory segments are created as files
+const SHM_DIRECTORY: &str = "/dev/shm";
+
+/// The prefix of FTL's shared memory segment names
+const FTL_SHM_PREFIX: &str = "/FTL-";
 
-const FTL_SHM_CLIENTS: &str = "/FTL-clients";
-const FTL_SHM_DOMAINS: &str = "/FTL-domains";
-const FTL_SHM_FORWARDED: &str = "/FTL-forwarded";
-const FTL_SHM_OVERTIME: &str = "/FTL-overTime";
-const FTL_SHM_QUERIES: &str = "/FTL-queries";
-const FTL_SHM_STRINGS: &str = "/FTL-strings";
-const FTL_SHM_COUNTERS: &str = "/FTL-counters";
-const FTL_SHM_SETTINGS: &str = "/FTL-settings";
+const FTL_SHM_CLIENTS: &str = "clients";
+const FTL_SHM_DOMAINS: &str = "domains";
+const FTL_SHM_FORWARDED: &str = "forwarded";
+const FTL_SHM_OVERTIME: &str = "overTime";
+const FTL_SHM_QUERIES: &str = "queries";
+const FTL_SHM_STRINGS: &str = "strings";
+const FTL_SHM_COUNTERS: &str = "counters";
+const FTL_SHM_SETTINGS: &str = "settings";
+
+/// Open the shared memory segment with the name, such as `/FTL-clients`
+/// for the `clients` segment of FTL
+fn open_segment(prefix: &str, name: &str) -> Result<Object, Error> {
+    Ok(Object::open(format!("{}{}", prefix, name))?)
+}
+
+/// Get the path of the file backing the shared memory segment
+fn segment_path(prefix: &str, name: &str) -> PathBuf {
+    Path::new(SHM_DIRECTORY).join(format!("{}{}", prefix.trim_start_matches('/'), name))
+}
 
 /// A wrapper for accessing FTL's shared memory.
 ///
-/// - Production mode connects to the real FTL shared memory.
+/// - Production mode connects to the real FTL shared memory. The mappings are
+///   kept open between requests until FTL changes the memory layout.
 /// - Test mode uses the associated test data to mock FTL's shared memory.
 #[allow(clippy::large_enum_variant)]
 pub enum FtlMemory {
     Production {
         lock: ShmLock,
+        cache: ShmCache,
+        /// The prefix of the segment names, which is `/FTL-` for FTL's
+        /// shared memory
+        prefix: String,
     },
     #[cfg(test)]
     Test {
@@ -60,6 +94,8 @@ impl FtlMemory {
     pub fn production() -> FtlMemory {
         FtlMemory::Production {
             lock: ShmLock::new(),
+            cache: ShmCache::default(),
+            prefix: FTL_SHM_PREFIX.to_owned(),
         }
     }
 
@@ -69,28 +105,58 @@ impl FtlMemory {
     /// [`ShmLockGuard`]: ../shared_lock/enum.ShmLockGuard.html
     pub fn lock(&self) -> Result<ShmLockGuard, Error> {
         match self {
-            FtlMemory::Production { lock } => {
-                let guard = lock.read()?;
-
-                // Check the version of shared memory, in case it is not the
-                // same version used by this API
-                let settings = self.settings(&guard)?;
-                let version = settings.version as usize;
-
-                if version == FTL_SHM_VERSION {
-                    Ok(guard)
-                } else {
-                    Err(Error::from(ErrorKind::SharedMemoryVersion(
-                        version,
-                        FTL_SHM_VERSION,
-                    )))
+            FtlMemory::Production {
+                lock,
+                cache,
+                prefix,
+            } => {
+                for _ in 0..FTL_SHM_READ_ATTEMPTS {
+                    let guard = lock.read()?;
+
+                    // Drop the cached mappings if FTL resized its memory or
+                    // restarted since they were opened, and check the version
+                    // of shared memory in case it is not the same version used
+                    // by this API. The settings segment is identified after
+                    // reading it, so if FTL restarts in between, the mappings
+                    // are opened again instead of being kept.
+                    let settings = self.settings(&guard)?;
+                    let segment = SegmentId::of(&segment_path(prefix, FTL_SHM_SETTINGS))?;
+                    cache.validate(&settings, segment)?;
+
+                    if self.is_consistent(&guard)? {
+                        return Ok(guard);
+                    }
+
+                    // FTL was caught in the middle of an update. Release the
+                    // lock so it can finish, and map the memory again.
+                    drop(guard);
+                    cache.clear();
+                    thread::sleep(FTL_SHM_RETRY_DELAY);
                 }
+
+                Err(Error::from(ErrorKind::SharedMemoryInconsistent))
             }
             #[cfg(test)]
-            FtlMemory::Test { .. } => Ok(ShmLockGuard::Test),
+            FtlMemory::Test { settings, .. } => {
+                settings.check_version()?;
+                Ok(ShmLockGuard::Test)
+            }
         }
     }
 
+    /// Check if the counters agree with the lengths of the shared memory
+    /// arrays, so the arrays can be walked using the counters
+    fn is_consistent<'lock>(&'lock self, lock_guard: &ShmLockGuard<'lock>) -> Result<bool, Error> {
+        let counters = self.counters(lock_guard)?;
+
+        Ok(counters.is_consistent(
+            self.queries(lock_guard)?.len(),
+            self.domains(lock_guard)?.len(),
+            self.clients(lock_guard)?.len(),
+            self.upstreams(lock_guard)?.len(),
+        ))
+    }
+
     /// Get the FTL shared memory client data. The resulting trait object can
     /// dereference into `&[FtlClient]`.
     pub fn clients<'lock>(
@@ -98,9 +164,11 @@ impl FtlMemory {
         _lock_guard: &ShmLockGuard<'lock>,
     ) -> Result<Box<dyn Deref<Target = [FtlClient]> + 'lock>, Error> {
         Ok(match self {
-            FtlMemory::Production { .. } => Box::new(
-                // Load the shared memory
-                Array::new(Object::open(FTL_SHM_CLIENTS)?)?,
+            FtlMemory::Production { cache, prefix, .. } => Box::new(
+                // Load the shared memory, if it is not already mapped
+                cache
+                    .clients
+                    .get_or_open(|| Ok(Array::new(open_segment(prefix, FTL_SHM_CLIENTS)?)?))?,
             ),
             #[cfg(test)]
             FtlMemory::Test { clients, .. } => Box::new(clients.as_slice()),
@@ -114,9 +182,11 @@ impl FtlMemory {
         _lock_guard: &ShmLockGuard<'lock>,
     ) -> Result<Box<dyn Deref<Target = [FtlDomain]> + 'lock>, Error> {
         Ok(match self {
-            FtlMemory::Production { .. } => Box::new(
-                // Load the shared memory
-                Array::new(Object::open(FTL_SHM_DOMAINS)?)?,
+            FtlMemory::Production { cache, prefix, .. } => Box::new(
+                // Load the shared memory, if it is not already mapped
+                cache
+                    .domains
+                    .get_or_open(|| Ok(Array::new(open_segment(prefix, FTL_SHM_DOMAINS)?)?))?,
             ),
             #[cfg(test)]
             FtlMemory::Test { domains, .. } => Box::new(domains.as_slice()),
@@ -130,9 +200,11 @@ impl FtlMemory {
         _lock_guard: &ShmLockGuard<'lock>,
     ) -> Result<Box<dyn Deref<Target = [FtlOverTime]> + 'lock>, Error> {
         Ok(match self {
-            FtlMemory::Production { .. } => Box::new(
-                // Load the shared memory
-                Array::new(Object::open(FTL_SHM_OVERTIME)?)?,
+            FtlMemory::Production { cache, prefix, .. } => Box::new(
+                // Load the shared memory, if it is not already mapped
+                cache
+                    .over_time
+                    .get_or_open(|| Ok(Array::new(open_segment(prefix, FTL_SHM_OVERTIME)?)?))?,
             ),
             #[cfg(test)]
             FtlMemory::Test { over_time, .. } => Box::new(over_time.as_slice()),
@@ -146,9 +218,11 @@ impl FtlMemory {
         _lock_guard: &ShmLockGuard<'lock>,
     ) -> Result<Box<dyn Deref<Target = [FtlUpstream]> + 'lock>, Error> {
         Ok(match self {
-            FtlMemory::Production { .. } => Box::new(
-                // Load the shared memory
-                Array::new(Object::open(FTL_SHM_FORWARDED)?)?,
+            FtlMemory::Production { cache, prefix, .. } => Box::new(
+                // Load the shared memory, if it is not already mapped
+                cache
+                    .upstreams
+                    .get_or_open(|| Ok(Array::new(open_segment(prefix, FTL_SHM_FORWARDED)?)?))?,
             ),
             #[cfg(test)]
             FtlMemory::Test { upstreams, .. } => Box::new(upstreams.as_slice()),
@@ -162,9 +236,11 @@ impl FtlMemory {
         _lock_guard: &ShmLockGuard<'lock>,
     ) -> Result<Box<dyn Deref<Target = [FtlQuery]> + 'lock>, Error> {
         Ok(match self {
-            FtlMemory::Production { .. } => Box::new(
-                // Load the shared memory
-                Array::new(Object::open(FTL_SHM_QUERIES)?)?,
+            FtlMemory::Production { cache, prefix, .. } => Box::new(
+                // Load the shared memory, if it is not already mapped
+                cache
+                    .queries
+                    .get_or_open(|| Ok(Array::new(open_segment(prefix, FTL_SHM_QUERIES)?)?))?,
             ),
             #[cfg(test)]
             FtlMemory::Test { queries, .. } => Box::new(queries.as_slice()),
@@ -177,9 +253,12 @@ impl FtlMemory {
         _lock_guard: &ShmLockGuard<'lock>,
     ) -> Result<FtlStrings<'lock>, Error> {
         Ok(match self {
-            FtlMemory::Production { .. } => {
-                FtlStrings::Production(Array::new(Object::open(FTL_SHM_STRINGS)?)?, PhantomData)
-            }
+            FtlMemory::Production { cache, prefix, .. } => FtlStrings::Production(
+                cache
+                    .strings
+                    .get_or_open(|| Ok(Array::new(open_segment(prefix, FTL_SHM_STRINGS)?)?))?,
+                PhantomData,
+            ),
             #[cfg(test)]
             FtlMemory::Test { strings, .. } => FtlStrings::Test(&strings),
         })
@@ -192,7 +271,11 @@ impl FtlMemory {
         _lock_guard: &ShmLockGuard<'lock>,
     ) -> Result<Box<dyn Deref<Target = FtlCounters> + 'lock>, Error> {
         Ok(match self {
-            FtlMemory::Production { .. } => Box::new(Map::new(Object::open(FTL_SHM_COUNTERS)?)?),
+            FtlMemory::Production { cache, prefix, .. } => Box::new(
+                cache
+                    .counters
+                    .get_or_open(|| Ok(Map::new(open_segment(prefix, FTL_SHM_COUNTERS)?)?))?,
+            ),
             #[cfg(test)]
             FtlMemory::Test { counters, .. } => Box::new(counters),
         })
@@ -205,9 +288,190 @@ impl FtlMemory {
         _lock_guard: &ShmLockGuard<'lock>,
     ) -> Result<Box<dyn Deref<Target = FtlSettings> + 'lock>, Error> {
         Ok(match self {
-            FtlMemory::Production { .. } => Box::new(Map::new(Object::open(FTL_SHM_SETTINGS)?)?),
+            FtlMemory::Production { prefix, .. } => {
+                Box::new(Map::new(open_segment(prefix, FTL_SHM_SETTINGS)?)?)
+            }
             #[cfg(test)]
             FtlMemory::Test { settings, .. } => Box::new(settings),
         })
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{
+        segment_path, FtlMemory, FTL_SHM_CLIENTS, FTL_SHM_COUNTERS, FTL_SHM_DOMAINS,
+        FTL_SHM_FORWARDED, FTL_SHM_OVERTIME, FTL_SHM_QUERIES, FTL_SHM_SETTINGS, FTL_SHM_STRINGS,
+    };
+    use crate::{
+        ftl::{shm_cache::ShmCache, FtlQueryStatus, FtlStrings, ShmLock},
+        testing::{TestBuilder, TestFtlMemoryBuilder, TestStep},
+    };
+    use std::{
+        fs, mem, process, ptr,
+        rc::Rc,
+        slice,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    /// The segments which are mapped through the cache
+    const CACHED_SEGMENTS: [&str; 7] = [
+        FTL_SHM_CLIENTS,
+        FTL_SHM_DOMAINS,
+        FTL_SHM_FORWARDED,
+        FTL_SHM_OVERTIME,
+        FTL_SHM_QUERIES,
+        FTL_SHM_STRINGS,
+        FTL_SHM_COUNTERS,
+    ];
+
+    /// Shared memory segments with a unique prefix, filled with the contents
+    /// of a test `FtlMemory`, so the production code can be tested without
+    /// FTL. The segments are removed when this is dropped.
+    struct TestSegments {
+        prefix: String,
+    }
+
+    impl TestSegments {
+        fn new() -> Self {
+            static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+            TestSegments {
+                prefix: format!(
+                    "/FTL-test-{}-{}-",
+                    process::id(),
+                    NEXT_ID.fetch_add(1, Ordering::SeqCst)
+                ),
+            }
+        }
+
+        /// Get a production `FtlMemory` which reads these segments
+        fn memory(&self) -> FtlMemory {
+            FtlMemory::Production {
+                lock: ShmLock::unlocked(),
+                cache: ShmCache::default(),
+                prefix: self.prefix.clone(),
+            }
+        }
+
+        /// Create the segments with the contents of the test memory. Existing
+        /// segments are removed first, like when FTL restarts.
+        fn write(&self, memory: &FtlMemory) {
+            self.remove(&[FTL_SHM_SETTINGS]);
+            self.remove(&CACHED_SEGMENTS);
+
+            match memory {
+                FtlMemory::Test {
+                    clients,
+                    domains,
+                    over_time,
+                    upstreams,
+                    queries,
+                    strings,
+                    counters,
+                    settings,
+                } => {
+                    self.write_segment(FTL_SHM_CLIENTS, as_bytes(clients));
+                    self.write_segment(FTL_SHM_DOMAINS, as_bytes(domains));
+                    self.write_segment(FTL_SHM_OVERTIME, as_bytes(over_time));
+                    self.write_segment(FTL_SHM_FORWARDED, as_bytes(upstreams));
+                    self.write_segment(FTL_SHM_QUERIES, as_bytes(queries));
+                    self.write_segment(FTL_SHM_COUNTERS, as_bytes(slice::from_ref(counters)));
+                    self.write_segment(FTL_SHM_SETTINGS, as_bytes(slice::from_ref(settings)));
+
+                    // Lay the strings out at their offsets, each with a null
+                    // terminator. Offset 0 is the empty string.
+                    let mut table = vec![0; FtlStrings::Test(strings).len().max(1)];
+                    for (id, string) in strings {
+                        table[*id..*id + string.len()].copy_from_slice(string.as_bytes());
+                    }
+                    self.write_segment(FTL_SHM_STRINGS, table);
+                }
+                _ => unreachable!(),
+            }
+        }
+
+        /// Remove the segments. Mappings which are already open stay valid.
+        fn remove(&self, names: &[&str]) {
+            for name in names {
+                let _ = fs::remove_file(segment_path(&self.prefix, name));
+            }
+        }
+
+        fn write_segment(&self, name: &str, contents: Vec<u8>) {
+            fs::write(segment_path(&self.prefix, name), contents).unwrap();
+        }
+    }
+
+    impl Drop for TestSegments {
+        fn drop(&mut self) {
+            self.remove(&[FTL_SHM_SETTINGS]);
+            self.remove(&CACHED_SEGMENTS);
+        }
+    }
+
+    /// Copy the structs into bytes, as they would be laid out in shared memory
+    fn as_bytes<T>(items: &[T]) -> Vec<u8> {
+        let len = mem::size_of_val(items);
+        let mut bytes = vec![0u8; len];
+
+        unsafe {
+            ptr::copy_nonoverlapping(items.as_ptr() as *const u8, bytes.as_mut_ptr(), len);
+        }
+
+        bytes
+    }
+
+    /// Create test memory with the number of cached queries
+    fn test_memory(queries: usize) -> FtlMemory {
+        let mut builder = TestFtlMemoryBuilder::new();
+        let client = builder.client("10.0.0.2", "laptop");
+        let upstream = builder.upstream("8.8.8.8", "google-public-dns-a.google.com");
+
+        builder.query(
+            1000,
+            "example.com",
+            client,
+            FtlQueryStatus::Forward,
+            Some(upstream),
+        );
+        for i in 1..queries {
+            builder.query(1000 + i, "example.com", client, FtlQueryStatus::Cache, None);
+        }
+
+        builder.build()
+    }
+
+    /// Request the query types and check the total
+    fn query_types_step(total: usize) -> TestStep {
+        TestStep::new()
+            .endpoint("/admin/api/stats/query_types")
+            .expect_json_contains(json!({ "total": total, "other": 0 }))
+    }
+
+    /// Repeated requests reuse the mappings opened by the first request, so
+    /// they still succeed after the segments are removed. Once FTL restarts
+    /// with new segments, the new segments are mapped.
+    #[test]
+    fn requests_reuse_mappings() {
+        let segments = Rc::new(TestSegments::new());
+        segments.write(&test_memory(1));
+
+        let removed = Rc::clone(&segments);
+        let restarted = Rc::clone(&segments);
+
+        TestBuilder::new()
+            .ftl_memory(segments.memory())
+            .step(query_types_step(1))
+            .step_with(move |_| {
+                // Only the settings are read again for each request
+                removed.remove(&CACHED_SEGMENTS);
+                query_types_step(1)
+            })
+            .step_with(move |_| {
+                restarted.write(&test_memory(3));
+                query_types_step(3)
+            })
+            .test();
+    }
+}
@@ -0,0 +1,327 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// FTL Shared Memory Mapping Cache
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    ftl::{FtlClient, FtlCounters, FtlDomain, FtlOverTime, FtlQuery, FtlSettings, FtlUpstream},
+    util::{Error, ErrorKind},
+};
+use shmem::{Array, Map};
+use std::{
+    fs,
+    ops::Deref,
+    os::unix::fs::MetadataExt,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+/// A shared memory mapping which can be handed out to multiple requests. The
+/// mapping is unmapped when the last handle is dropped, so a request holding a
+/// handle is not affected if the cache is invalidated in the meantime.
+pub struct SharedMapping<M>(Arc<M>);
+
+/// A shared memory mapping which is safe to share between threads. shmem's
+/// mappings hold a raw pointer, so they are not `Send` or `Sync`.
+///
+/// # Safety
+///
+/// The mapped data must be plain data which is only read while holding the
+/// shared memory lock, like FTL's structs.
+pub unsafe trait ShareableMapping {}
+
+unsafe impl<T: Copy + Send + Sync + 'static> ShareableMapping for Array<T> {}
+unsafe impl<T: Copy + Send + Sync + 'static> ShareableMapping for Map<T> {}
+
+unsafe impl<M: ShareableMapping> Send for SharedMapping<M> {}
+unsafe impl<M: ShareableMapping> Sync for SharedMapping<M> {}
+
+impl<M> SharedMapping<M> {
+    /// Wrap a newly opened mapping
+    pub fn new(mapping: M) -> Self {
+        SharedMapping(Arc::new(mapping))
+    }
+}
+
+impl<M> Clone for SharedMapping<M> {
+    fn clone(&self) -> Self {
+        SharedMapping(Arc::clone(&self.0))
+    }
+}
+
+impl<M: Deref> Deref for SharedMapping<M> {
+    type Target = M::Target;
+
+    fn deref(&self) -> &M::Target {
+        &self.0
+    }
+}
+
+/// Identifies a shared memory segment by the device and inode of its file.
+/// FTL removes its segments when it stops and creates new ones when it
+/// starts, so a segment with a new identity belongs to a new FTL process.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SegmentId {
+    dev: u64,
+    ino: u64,
+}
+
+impl SegmentId {
+    /// Get the identity of the segment file at the path
+    pub fn of(path: &Path) -> Result<Self, Error> {
+        let metadata = fs::metadata(path)
+            .map_err(|e| Error::from(ErrorKind::SharedMemoryOpen(e.to_string())))?;
+
+        Ok(SegmentId {
+            dev: metadata.dev(),
+            ino: metadata.ino(),
+        })
+    }
+}
+
+/// Identifies the layout of FTL's shared memory. FTL increments the global
+/// counter every time it resizes a segment, and a restart creates new
+/// segments (and may change the version), so a change in any of them means
+/// the mappings have to be opened again.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ShmEpoch {
+    version: libc::c_int,
+    global_shm_counter: libc::c_uint,
+    segment: SegmentId,
+}
+
+impl ShmEpoch {
+    /// Get the epoch described by FTL's settings and the identity of the
+    /// settings segment
+    pub fn new(settings: &FtlSettings, segment: SegmentId) -> Self {
+        ShmEpoch {
+            version: settings.version,
+            global_shm_counter: settings.global_shm_counter,
+            segment,
+        }
+    }
+}
+
+/// A single cached mapping, which is opened on first use
+pub struct CachedMapping<M> {
+    mapping: Mutex<Option<SharedMapping<M>>>,
+}
+
+impl<M> Default for CachedMapping<M> {
+    fn default() -> Self {
+        CachedMapping {
+            mapping: Mutex::new(None),
+        }
+    }
+}
+
+impl<M> CachedMapping<M> {
+    /// Get the cached mapping, or open it with `open` if it is not cached
+    pub fn get_or_open(
+        &self,
+        open: impl FnOnce() -> Result<M, Error>,
+    ) -> Result<SharedMapping<M>, Error> {
+        let mut mapping = self.mapping.lock().unwrap();
+
+        if let Some(mapping) = mapping.as_ref() {
+            return Ok(mapping.clone());
+        }
+
+        let opened = SharedMapping::new(open()?);
+        *mapping = Some(opened.clone());
+        Ok(opened)
+    }
+
+    /// Drop the cached mapping, so it is opened again on next use
+    pub fn clear(&self) {
+        *self.mapping.lock().unwrap() = None;
+    }
+}
+
+/// Keeps FTL's shared memory mapped between requests. The settings segment is
+/// not cached, because it is used to detect when the other mappings are out of
+/// date.
+#[derive(Default)]
+pub struct ShmCache {
+    epoch: Mutex<Option<ShmEpoch>>,
+    pub clients: CachedMapping<Array<FtlClient>>,
+    pub domains: CachedMapping<Array<FtlDomain>>,
+    pub over_time: CachedMapping<Array<FtlOverTime>>,
+    pub upstreams: CachedMapping<Array<FtlUpstream>>,
+    pub queries: CachedMapping<Array<FtlQuery>>,
+    pub strings: CachedMapping<Array<libc::c_char>>,
+    pub counters: CachedMapping<Map<FtlCounters>>,
+}
+
+impl ShmCache {
+    /// Update the cache to the epoch described by FTL's settings and the
+    /// identity of the settings segment, dropping the cached mappings if they
+    /// are out of date. The shared memory version is checked once each time
+    /// the memory is attached, and the mappings are not used until a supported
+    /// version is found. This must be called while holding the shared memory
+    /// lock, so FTL can not change the layout before the mappings are used.
+    pub fn validate(&self, settings: &FtlSettings, segment: SegmentId) -> Result<(), Error> {
+        let current = ShmEpoch::new(settings, segment);
+        let mut epoch = self.epoch.lock().unwrap();
+
+        if *epoch != Some(current) {
+            self.clear_mappings();
+            *epoch = None;
+            settings.check_version()?;
+        }
+
+        *epoch = Some(current);
+        Ok(())
+    }
+
+    /// Drop all of the cached mappings, so they are opened again on next use
+    pub fn clear(&self) {
+        *self.epoch.lock().unwrap() = None;
+        self.clear_mappings();
+    }
+
+    /// Drop the cached mappings without changing the epoch
+    fn clear_mappings(&self) {
+        self.clients.clear();
+        self.domains.clear();
+        self.over_time.clear();
+        self.upstreams.clear();
+        self.queries.clear();
+        self.strings.clear();
+        self.counters.clear();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{CachedMapping, SegmentId, ShmCache, ShmEpoch};
+    use crate::{
+        ftl::{FtlSettings, FTL_SHM_VERSION},
+        util::ErrorKind,
+    };
+    use std::cell::Cell;
+
+    /// Create the identity of a segment from its inode
+    fn segment(ino: u64) -> SegmentId {
+        SegmentId { dev: 1, ino }
+    }
+
+    /// Create an epoch from the shared memory counter and the inode of the
+    /// settings segment
+    fn epoch(global_shm_counter: libc::c_uint, ino: u64) -> ShmEpoch {
+        ShmEpoch::new(
+            &FtlSettings {
+                global_shm_counter,
+                ..FtlSettings::default()
+            },
+            segment(ino),
+        )
+    }
+
+    /// The mapping is only opened once, and reused by later calls
+    #[test]
+    fn reuses_mapping() {
+        let cache = CachedMapping::default();
+        let opened = Cell::new(0);
+        let open = || {
+            opened.set(opened.get() + 1);
+            Ok(vec![1, 2, 3])
+        };
+
+        let first = cache.get_or_open(open).unwrap();
+        let second = cache.get_or_open(open).unwrap();
+
+        assert_eq!(opened.get(), 1);
+        assert_eq!(*first, [1, 2, 3]);
+        assert_eq!(*second, [1, 2, 3]);
+    }
+
+    /// The mapping is opened again after the cache is cleared, while existing
+    /// handles keep the old mapping
+    #[test]
+    fn reopens_after_clear() {
+        let cache = CachedMapping::default();
+        let old = cache.get_or_open(|| Ok(vec![1])).unwrap();
+
+        cache.clear();
+        let new = cache.get_or_open(|| Ok(vec![2])).unwrap();
+
+        assert_eq!(*old, [1]);
+        assert_eq!(*new, [2]);
+    }
+
+    /// Errors while opening are not cached
+    #[test]
+    fn open_error() {
+        let cache = CachedMapping::default();
+
+        assert!(cache
+            .get_or_open(|| Err(ErrorKind::Unknown.into()))
+            .is_err());
+        assert_eq!(*cache.get_or_open(|| Ok(vec![1])).unwrap(), [1]);
+    }
+
+    /// New strings do not change the epoch, since they are only reflected in
+    /// the settings' next string position
+    #[test]
+    fn epoch_new_strings() {
+        let settings = FtlSettings {
+            global_shm_counter: 3,
+            next_str_pos: 250,
+            ..FtlSettings::default()
+        };
+
+        assert_eq!(epoch(3, 100), ShmEpoch::new(&settings, segment(100)));
+    }
+
+    /// Resizing a segment changes the epoch
+    #[test]
+    fn epoch_resize() {
+        assert_ne!(epoch(3, 100), epoch(4, 100));
+    }
+
+    /// A restart of FTL changes the epoch, even if the counter is the same
+    #[test]
+    fn epoch_restart() {
+        assert_ne!(epoch(0, 100), epoch(0, 101));
+    }
+
+    /// A version change is a new epoch
+    #[test]
+    fn epoch_version() {
+        let new = ShmEpoch::new(
+            &FtlSettings {
+                version: FTL_SHM_VERSION + 1,
+                global_shm_counter: 3,
+                ..FtlSettings::default()
+            },
+            segment(100),
+        );
+
+        assert_ne!(epoch(3, 100), new);
+    }
+
+    /// An unsupported version is rejected and the cached mappings are dropped,
+    /// and the memory is accepted again once the version is supported
+    #[test]
+    fn validate_version() {
+        let cache = ShmCache::default();
+        let supported = FtlSettings::default();
+        let unsupported = FtlSettings {
+            version: FTL_SHM_VERSION + 1,
+            ..FtlSettings::default()
+        };
+
+        assert!(cache.validate(&supported, segment(1)).is_ok());
+        assert_eq!(
+            cache.validate(&unsupported, segment(1)).unwrap_err().kind(),
+            ErrorKind::SharedMemoryVersion(FTL_SHM_VERSION as usize + 1, FTL_SHM_VERSION as usize)
+        );
+        assert!(cache.validate(&supported, segment(1)).is_ok());
+    }
+}
@@ -17,6 +17,7 @@ use rmp::{
 use std::{
     io::{prelude::*, BufReader},
     os::unix::net::UnixStream,
+    path::Path,
 };
 
 use shaku::{Component, Module, ModuleBuildContext};
@@ -24,6 +25,8 @@ use shaku::{Component, Module, ModuleBuildContext};
 use std::collections::HashMap;
 #[cfg(test)]
 use std::io::Cursor;
+#[cfg(test)]
+use std::path::PathBuf;
 
 /// The location of the FTL socket
 const SOCKET_LOCATION: &str = "/var/run/pihole/FTL.sock";
@@ -37,10 +40,14 @@ pub struct FtlConnection<'test>(Box<dyn Read + 'test>);
 /// - Socket refers to the normal Unix socket connection.
 /// - Test is for testing, so that a test can pass in arbitrary MessagePack
 /// data to be processed.   The map in Test maps FTL commands to data.
+/// - TestSocket is for testing, and connects to a Unix socket at the path
+/// instead of the real FTL socket (see `testing::MockFtlServer`).
 pub enum FtlConnectionType {
     Socket,
     #[cfg(test)]
     Test(HashMap<String, Vec<u8>>),
+    #[cfg(test)]
+    TestSocket(PathBuf),
 }
 
 impl<M: Module> Component<M> for FtlConnectionType {
@@ -63,17 +70,7 @@ impl FtlConnectionType {
     pub fn connect(&self, command: &str) -> Result<FtlConnection, Error> {
         // Determine the type of connection to create
         match *self {
-            FtlConnectionType::Socket => {
-                // Try to connect to FTL
-                let mut stream =
-                    UnixStream::connect(SOCKET_LOCATION).context(ErrorKind::FtlConnectionFail)?;
-
-                // Send the command
-                writeln!(stream, ">{}", command).context(ErrorKind::FtlConnectionFail)?;
-
-                // Return the connection so the API can read the response
-                Ok(FtlConnection(Box::new(BufReader::new(stream))))
-            }
+            FtlConnectionType::Socket => Self::connect_socket(Path::new(SOCKET_LOCATION), command),
             #[cfg(test)]
             FtlConnectionType::Test(ref map) => {
                 // Return a connection reading the testing data
@@ -85,8 +82,22 @@ impl FtlConnectionType {
                     },
                 ))))
             }
+            #[cfg(test)]
+            FtlConnectionType::TestSocket(ref path) => Self::connect_socket(path, command),
         }
     }
+
+    /// Connect to the FTL socket at the path and send the command
+    fn connect_socket(path: &Path, command: &str) -> Result<FtlConnection<'static>, Error> {
+        // Try to connect to FTL
+        let mut stream = UnixStream::connect(path).context(ErrorKind::FtlConnectionFail)?;
+
+        // Send the command
+        writeln!(stream, ">{}", command).context(ErrorKind::FtlConnectionFail)?;
+
+        // Return the connection so the API can read the response
+        Ok(FtlConnection(Box::new(BufReader::new(stream))))
+    }
 }
 
 impl<'test> FtlConnection<'test> {
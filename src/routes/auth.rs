@@ -143,8 +143,8 @@ pub fn logout(user: User, cookies: &CookieJar) -> Reply {
 
 #[cfg(test)]
 mod test {
-    use crate::testing::TestBuilder;
-    use rocket::http::{Header, Status};
+    use crate::testing::{TestBuilder, TestStep};
+    use rocket::http::{Header, Method, Status};
     use serde_json::Value;
 
     /// Providing the correct authentication should authorize the request
@@ -210,4 +210,43 @@ mod test {
             }))
             .test();
     }
+
+    /// After authenticating, the session cookie authorizes requests until the
+    /// user logs out
+    #[test]
+    fn session_cookie() {
+        TestBuilder::new()
+            .step(
+                TestStep::new()
+                    .endpoint("/admin/api/auth")
+                    .expect_json(json!({ "status": "success" })),
+            )
+            .step(
+                TestStep::new()
+                    .endpoint("/admin/api/auth")
+                    .should_auth(false)
+                    .expect_json(json!({ "status": "success" })),
+            )
+            .step(
+                TestStep::new()
+                    .endpoint("/admin/api/auth")
+                    .method(Method::Delete)
+                    .should_auth(false)
+                    .expect_json(json!({ "status": "success" })),
+            )
+            .step(
+                TestStep::new()
+                    .endpoint("/admin/api/auth")
+                    .should_auth(false)
+                    .expect_status(Status::Unauthorized)
+                    .expect_json(json!({
+                        "error": {
+                            "key": "unauthorized",
+                            "message": "Unauthorized",
+                            "data": Value::Null
+                        }
+                    })),
+            )
+            .test();
+    }
 }
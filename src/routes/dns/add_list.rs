@@ -11,40 +11,81 @@
 use crate::{
     routes::auth::User,
     services::{
-        lists::{List, ListService},
+        lists::{wildcard_comment, wildcard_regex, List, ListService},
         PiholeModule,
     },
-    util::{reply_success, Reply},
+    util::{reply_data, reply_success, Reply},
 };
 use rocket::serde::json::Json;
 use shaku_rocket::InjectProvided;
 
-/// Represents an API input containing a domain
+/// Represents an API input containing a domain and an optional comment. On
+/// the blacklist, `wildcard` blocks the domain and all of its subdomains.
 #[derive(Deserialize)]
 pub struct DomainInput {
     domain: String,
+    comment: Option<String>,
+    #[serde(default)]
+    wildcard: bool,
 }
 
-/// Add a domain to the whitelist
-#[post("/dns/whitelist", data = "<domain_input>")]
+/// Represents an API input containing multiple domains
+#[derive(Deserialize)]
+pub struct DomainsInput {
+    pub domains: Vec<String>,
+}
+
+/// Add a domain to the whitelist. If the domain is on the blacklist, the add
+/// is rejected unless `force` is true, which moves it to the whitelist.
+#[post("/dns/whitelist?<force>", data = "<domain_input>")]
 pub fn add_whitelist(
     _auth: User,
     list_service: InjectProvided<PiholeModule, dyn ListService>,
+    force: Option<bool>,
     domain_input: Json<DomainInput>,
 ) -> Reply {
-    list_service.add(List::White, &domain_input.0.domain)?;
-    reply_success()
+    let domain_input = domain_input.into_inner();
+    let removed_from = list_service.add(
+        List::White,
+        &domain_input.domain,
+        domain_input.comment,
+        force.unwrap_or(false),
+    )?;
+
+    add_reply(List::White, removed_from)
 }
 
-/// Add a domain to the blacklist
-#[post("/dns/blacklist", data = "<domain_input>")]
+/// Add a domain to the blacklist. Wildcards are stored on the regex list as
+/// the generated regex. If the domain is on the whitelist, the add is
+/// rejected unless `force` is true, which moves it to the blacklist.
+#[post("/dns/blacklist?<force>", data = "<domain_input>")]
 pub fn add_blacklist(
     _auth: User,
     list_service: InjectProvided<PiholeModule, dyn ListService>,
+    force: Option<bool>,
     domain_input: Json<DomainInput>,
 ) -> Reply {
-    list_service.add(List::Black, &domain_input.0.domain)?;
-    reply_success()
+    let domain_input = domain_input.into_inner();
+
+    if domain_input.wildcard {
+        let pattern = wildcard_regex(&domain_input.domain)?;
+        let comment = match domain_input.comment {
+            Some(comment) => comment,
+            None => wildcard_comment(&domain_input.domain),
+        };
+
+        list_service.add(List::Regex, &pattern, Some(comment), false)?;
+        reply_success()
+    } else {
+        let removed_from = list_service.add(
+            List::Black,
+            &domain_input.domain,
+            domain_input.comment,
+            force.unwrap_or(false),
+        )?;
+
+        add_reply(List::Black, removed_from)
+    }
 }
 
 /// Add a domain to the regex list
@@ -54,18 +95,98 @@ pub fn add_regexlist(
     list_service: InjectProvided<PiholeModule, dyn ListService>,
     domain_input: Json<DomainInput>,
 ) -> Reply {
-    list_service.add(List::Regex, &domain_input.0.domain)?;
+    let domain_input = domain_input.into_inner();
+    list_service.add(
+        List::Regex,
+        &domain_input.domain,
+        domain_input.comment,
+        false,
+    )?;
+    reply_success()
+}
+
+/// Add a domain to the regex whitelist
+#[post("/dns/regexWhitelist", data = "<domain_input>")]
+pub fn add_regex_whitelist(
+    _auth: User,
+    list_service: InjectProvided<PiholeModule, dyn ListService>,
+    domain_input: Json<DomainInput>,
+) -> Reply {
+    let domain_input = domain_input.into_inner();
+    list_service.add(
+        List::RegexWhite,
+        &domain_input.domain,
+        domain_input.comment,
+        false,
+    )?;
     reply_success()
 }
 
+/// Add multiple domains to the whitelist. Domains which are on the blacklist
+/// are reported as conflicts unless `force` is true, which moves them to the
+/// whitelist.
+#[post("/dns/whitelist/batch?<force>", data = "<domains_input>")]
+pub fn add_whitelist_batch(
+    _auth: User,
+    list_service: InjectProvided<PiholeModule, dyn ListService>,
+    force: Option<bool>,
+    domains_input: Json<DomainsInput>,
+) -> Reply {
+    reply_data(list_service.add_many(
+        List::White,
+        &domains_input.0.domains,
+        force.unwrap_or(false),
+    )?)
+}
+
+/// Add multiple domains to the blacklist. Domains which are on the whitelist
+/// are reported as conflicts unless `force` is true, which moves them to the
+/// blacklist.
+#[post("/dns/blacklist/batch?<force>", data = "<domains_input>")]
+pub fn add_blacklist_batch(
+    _auth: User,
+    list_service: InjectProvided<PiholeModule, dyn ListService>,
+    force: Option<bool>,
+    domains_input: Json<DomainsInput>,
+) -> Reply {
+    reply_data(list_service.add_many(
+        List::Black,
+        &domains_input.0.domains,
+        force.unwrap_or(false),
+    )?)
+}
+
+/// Add multiple domains to the regex list
+#[post("/dns/regexlist/batch", data = "<domains_input>")]
+pub fn add_regexlist_batch(
+    _auth: User,
+    list_service: InjectProvided<PiholeModule, dyn ListService>,
+    domains_input: Json<DomainsInput>,
+) -> Reply {
+    reply_data(list_service.add_many(List::Regex, &domains_input.0.domains, false)?)
+}
+
+/// Create the reply for an add, stating if the domain was moved from the
+/// opposite list
+fn add_reply(list: List, removed_from: Option<List>) -> Reply {
+    match removed_from {
+        Some(removed_from) => reply_data(json!({
+            "status": "success",
+            "message": format!("Removed from {}, added to {}", removed_from.name(), list.name())
+        })),
+        None => reply_success(),
+    }
+}
+
 #[cfg(test)]
 mod test {
     use crate::{
-        services::lists::{List, ListService, MockListService},
-        testing::TestBuilder,
+        services::lists::{BatchAddResult, List, ListService, MockListService},
+        testing::{TestBuilder, TestStep},
+        util::{Error, ErrorKind},
     };
     use mockall::predicate::*;
-    use rocket::http::Method;
+    use rocket::http::{Method, Status};
 
     /// Test that a successful add returns success
     fn add_test(list: List, endpoint: &str, domain: &'static str) {
@@ -77,8 +198,8 @@ mod test {
 
                 service
                     .expect_add()
-                    .with(eq(list), eq(domain))
-                    .return_const(Ok(()));
+                    .with(eq(list), eq(domain), eq(None), eq(false))
+                    .return_const(Ok(None));
 
                 Ok(Box::new(service))
             }))
@@ -87,6 +208,133 @@ mod test {
             .test();
     }
 
+    /// The comment is passed along with the domain
+    #[test]
+    fn add_with_comment() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/blacklist")
+            .method(Method::Post)
+            .mock_provider::<dyn ListService>(Box::new(move |_| {
+                let mut service = MockListService::new();
+
+                service
+                    .expect_add()
+                    .with(
+                        eq(List::Black),
+                        eq("example.com"),
+                        eq(Some("Ads".to_owned())),
+                        eq(false),
+                    )
+                    .return_const(Ok(None));
+
+                Ok(Box::new(service))
+            }))
+            .body(json!({ "domain": "example.com", "comment": "Ads" }))
+            .expect_json(json!({ "status": "success" }))
+            .test();
+    }
+
+    /// A wildcard is added to the regex list as the generated regex, with a
+    /// comment noting the domain
+    #[test]
+    fn add_wildcard() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/blacklist")
+            .method(Method::Post)
+            .mock_provider::<dyn ListService>(Box::new(move |_| {
+                let mut service = MockListService::new();
+
+                service
+                    .expect_add()
+                    .with(
+                        eq(List::Regex),
+                        eq("(\\.|^)example\\.com$"),
+                        eq(Some("Wildcard for example.com".to_owned())),
+                        eq(false),
+                    )
+                    .return_const(Ok(None));
+
+                Ok(Box::new(service))
+            }))
+            .body(json!({ "domain": "example.com", "wildcard": true }))
+            .expect_json(json!({ "status": "success" }))
+            .test();
+    }
+
+    /// An invalid regex is rejected with the pattern in the error data
+    #[test]
+    fn add_invalid_regex() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/regexlist")
+            .method(Method::Post)
+            .mock_provider::<dyn ListService>(Box::new(move |_| {
+                let mut service = MockListService::new();
+
+                service
+                    .expect_add()
+                    .with(eq(List::Regex), eq("([a-z"), eq(None), eq(false))
+                    .return_const(Err(Error::from(ErrorKind::InvalidRegex(
+                        "([a-z".to_owned(),
+                        "unclosed character class".to_owned(),
+                    ))));
+
+                Ok(Box::new(service))
+            }))
+            .body(json!({ "domain": "([a-z" }))
+            .expect_status(Status::BadRequest)
+            .expect_json(json!({
+                "error": {
+                    "key": "invalid_regex",
+                    "message": "Invalid regex ([a-z: unclosed character class",
+                    "data": {
+                        "pattern": "([a-z",
+                        "reason": "unclosed character class"
+                    }
+                }
+            }))
+            .test();
+    }
+
+    /// Test that adding a domain which is already on the list is a conflict
+    fn add_duplicate_test(endpoint: &str, domain: &str, list_name: &str) {
+        TestBuilder::new()
+            .endpoint(endpoint)
+            .method(Method::Post)
+            .need_database(true)
+            .body(json!({ "domain": domain }))
+            .expect_status(Status::Conflict)
+            .expect_json(json!({
+                "error": {
+                    "key": "already_exists",
+                    "message": format!("{} is already on the {}", domain, list_name),
+                    "data": {
+                        "domain": domain,
+                        "list": list_name
+                    }
+                }
+            }))
+            .test();
+    }
+
+    #[test]
+    fn add_duplicate_whitelist() {
+        add_duplicate_test("/admin/api/dns/whitelist", "test.com", "whitelist");
+    }
+
+    #[test]
+    fn add_duplicate_blacklist() {
+        add_duplicate_test("/admin/api/dns/blacklist", "example.com", "blacklist");
+    }
+
+    #[test]
+    fn add_duplicate_regexlist() {
+        add_duplicate_test(
+            "/admin/api/dns/regexlist",
+            "(^|\\.)example\\.com$",
+            "regex list",
+        );
+    }
+
     #[test]
     fn add_whitelist() {
         add_test(List::White, "/admin/api/dns/whitelist", "example.com");
@@ -103,4 +351,208 @@ mod test {
     fn test_add_regexlist() {
         add_test(List::Regex, "/admin/api/dns/regexlist", "^.*example.com$");
     }
+
+    /// A successful add returns success
+    #[test]
+    fn test_add_regex_whitelist() {
+        add_test(
+            List::RegexWhite,
+            "/admin/api/dns/regexWhitelist",
+            "^.*example.com$",
+        );
+    }
+
+    /// Whitelisting a domain which is on the blacklist is rejected, and the
+    /// domain stays on the blacklist
+    #[test]
+    fn add_opposite_list_rejected() {
+        TestBuilder::new()
+            .need_database(true)
+            .step(
+                TestStep::new()
+                    .endpoint("/admin/api/dns/whitelist")
+                    .method(Method::Post)
+                    .body(json!({ "domain": "example.com" }))
+                    .expect_status(Status::Conflict)
+                    .expect_json(json!({
+                        "error": {
+                            "key": "domain_conflict",
+                            "message": "example.com is on the blacklist, use force to move it",
+                            "data": {
+                                "domain": "example.com",
+                                "list": "blacklist"
+                            }
+                        }
+                    })),
+            )
+            .step(
+                TestStep::new()
+                    .endpoint("/admin/api/dns/whitelist/example.com")
+                    .expect_status(Status::NotFound)
+                    .expect_json_contains(json!({ "error": { "key": "not_found" } })),
+            )
+            .step(
+                TestStep::new()
+                    .endpoint("/admin/api/dns/blacklist/example.com")
+                    .ignore_fields(&["date_added", "date_modified"])
+                    .expect_json(json!({
+                        "domain": "example.com",
+                        "enabled": true,
+                        "comment": null,
+                        "groups": []
+                    })),
+            )
+            .test();
+    }
+
+    /// Forcing the add moves the domain from the blacklist to the whitelist
+    #[test]
+    fn add_opposite_list_forced() {
+        TestBuilder::new()
+            .need_database(true)
+            .step(
+                TestStep::new()
+                    .endpoint("/admin/api/dns/whitelist?force=true")
+                    .method(Method::Post)
+                    .body(json!({ "domain": "example.com", "comment": "Needed" }))
+                    .expect_json(json!({
+                        "status": "success",
+                        "message": "Removed from blacklist, added to whitelist"
+                    })),
+            )
+            .step(
+                TestStep::new()
+                    .endpoint("/admin/api/dns/whitelist/example.com")
+                    .ignore_fields(&["date_added", "date_modified"])
+                    .expect_json(json!({
+                        "domain": "example.com",
+                        "enabled": true,
+                        "comment": "Needed",
+                        "groups": []
+                    })),
+            )
+            .step(
+                TestStep::new()
+                    .endpoint("/admin/api/dns/blacklist/example.com")
+                    .expect_status(Status::NotFound)
+                    .expect_json_contains(json!({ "error": { "key": "not_found" } })),
+            )
+            .test();
+    }
+
+    /// The per-domain results of a batch add are returned
+    #[test]
+    fn add_blacklist_batch() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/blacklist/batch")
+            .method(Method::Post)
+            .mock_provider::<dyn ListService>(Box::new(move |_| {
+                let mut service = MockListService::new();
+
+                service
+                    .expect_add_many()
+                    .withf(|list, domains, force| {
+                        *list == List::Black
+                            && domains == ["a.com", "b.com", "not valid", "c.com"]
+                            && !*force
+                    })
+                    .return_const(Ok(BatchAddResult {
+                        added: vec!["a.com".to_owned()],
+                        already_exists: vec!["b.com".to_owned()],
+                        invalid: vec!["not valid".to_owned()],
+                        conflicts: vec!["c.com".to_owned()],
+                        moved: Vec::new(),
+                    }));
+
+                Ok(Box::new(service))
+            }))
+            .body(json!({ "domains": ["a.com", "b.com", "not valid", "c.com"] }))
+            .expect_json(json!({
+                "added": ["a.com"],
+                "already_exists": ["b.com"],
+                "invalid": ["not valid"],
+                "conflicts": ["c.com"],
+                "moved": []
+            }))
+            .test();
+    }
+
+    /// Forcing a batch add moves the domains which are on the opposite list
+    #[test]
+    fn add_whitelist_batch_forced() {
+        TestBuilder::new()
+            .need_database(true)
+            .step(
+                TestStep::new()
+                    .endpoint("/admin/api/dns/whitelist/batch")
+                    .method(Method::Post)
+                    .body(json!({ "domains": ["example.com", "new.com"] }))
+                    .expect_json(json!({
+                        "added": ["new.com"],
+                        "already_exists": [],
+                        "invalid": [],
+                        "conflicts": ["example.com"],
+                        "moved": []
+                    })),
+            )
+            .step(
+                TestStep::new()
+                    .endpoint("/admin/api/dns/whitelist/batch?force=true")
+                    .method(Method::Post)
+                    .body(json!({ "domains": ["example.com"] }))
+                    .expect_json(json!({
+                        "added": ["example.com"],
+                        "already_exists": [],
+                        "invalid": [],
+                        "conflicts": [],
+                        "moved": ["example.com"]
+                    })),
+            )
+            .step(
+                TestStep::new()
+                    .endpoint("/admin/api/dns/blacklist/example.com")
+                    .expect_status(Status::NotFound)
+                    .expect_json_contains(json!({ "error": { "key": "not_found" } })),
+            )
+            .test();
+    }
+
+    /// Domains are stored in lowercase, so mixed case adds can be looked up
+    /// in lowercase, and adding them again in another case is a conflict
+    #[test]
+    fn mixed_case_database() {
+        TestBuilder::new()
+            .need_database(true)
+            .step(
+                TestStep::new()
+                    .endpoint("/admin/api/dns/blacklist")
+                    .method(Method::Post)
+                    .body(json!({ "domain": "Ads.Example.COM" }))
+                    .expect_json(json!({ "status": "success" })),
+            )
+            .step(
+                TestStep::new()
+                    .endpoint("/admin/api/dns/blacklist/ads.example.com")
+                    .ignore_fields(&["date_added", "date_modified"])
+                    .expect_json(json!({
+                        "domain": "ads.example.com",
+                        "enabled": true,
+                        "comment": null,
+                        "groups": []
+                    })),
+            )
+            .step(
+                TestStep::new()
+                    .endpoint("/admin/api/dns/blacklist")
+                    .method(Method::Post)
+                    .body(json!({ "domain": "ADS.example.com" }))
+                    .expect_status(Status::Conflict)
+                    .expect_json_contains(json!({
+                        "error": {
+                            "key": "already_exists"
+                        }
+                    })),
+            )
+            .test();
+    }
 }
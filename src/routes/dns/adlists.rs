@@ -0,0 +1,453 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Endpoints For Managing Adlists
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    routes::auth::User,
+    services::{
+        adlists::{validate_address, AdlistRepository},
+        PiholeModule,
+    },
+    util::{reply_data, reply_success, Reply},
+};
+use rocket::serde::json::Json;
+use shaku_rocket::InjectProvided;
+
+/// Represents an API input containing a new adlist
+#[derive(Deserialize)]
+pub struct AdlistInput {
+    address: String,
+    comment: Option<String>,
+}
+
+/// Represents an API input containing the new state of an adlist
+#[derive(Deserialize)]
+pub struct AdlistUpdateInput {
+    enabled: bool,
+}
+
+/// Represents the reply structure for the statistics of an adlist
+#[derive(Serialize)]
+pub struct AdlistStats {
+    domain_count: usize,
+}
+
+/// Get all of the adlists, including whether each one is enabled
+#[get("/dns/adlists")]
+pub fn get_adlists(repo: InjectProvided<PiholeModule, dyn AdlistRepository>) -> Reply {
+    reply_data(repo.get()?)
+}
+
+/// Get the statistics of an adlist. Counting the domains is done separately
+/// from getting the adlists because the gravity table can be very large.
+#[get("/dns/adlists/<id>/stats")]
+pub fn get_adlist_stats(
+    repo: InjectProvided<PiholeModule, dyn AdlistRepository>,
+    id: i32,
+) -> Reply {
+    reply_data(AdlistStats {
+        domain_count: repo.domain_count(id)?,
+    })
+}
+
+/// Get the result of the last time gravity downloaded an adlist. The fields
+/// are null if the gravity database does not record them.
+#[get("/dns/adlists/<id>/status")]
+pub fn get_adlist_status(
+    repo: InjectProvided<PiholeModule, dyn AdlistRepository>,
+    id: i32,
+) -> Reply {
+    reply_data(repo.status(id)?)
+}
+
+/// Add an adlist. The address must be an HTTP(S) or file URL.
+#[post("/dns/adlists", data = "<adlist_input>")]
+pub fn add_adlist(
+    _auth: User,
+    repo: InjectProvided<PiholeModule, dyn AdlistRepository>,
+    adlist_input: Json<AdlistInput>,
+) -> Reply {
+    let adlist_input = adlist_input.into_inner();
+
+    validate_address(&adlist_input.address)?;
+    repo.add(&adlist_input.address, adlist_input.comment)?;
+    reply_success()
+}
+
+/// Delete an adlist
+#[delete("/dns/adlists/<id>")]
+pub fn delete_adlist(
+    _auth: User,
+    repo: InjectProvided<PiholeModule, dyn AdlistRepository>,
+    id: i32,
+) -> Reply {
+    repo.remove(id)?;
+    reply_success()
+}
+
+/// Enable or disable an adlist. Gravity is not rebuilt, so the change takes
+/// effect on the next gravity run.
+#[put("/dns/adlists/<id>", data = "<update_input>")]
+pub fn update_adlist(
+    _auth: User,
+    repo: InjectProvided<PiholeModule, dyn AdlistRepository>,
+    id: i32,
+    update_input: Json<AdlistUpdateInput>,
+) -> Reply {
+    repo.set_enabled(id, update_input.enabled)?;
+    reply_success()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        services::adlists::{AdlistRepository, AdlistStatus, MockAdlistRepository},
+        testing::{TestBuilder, TestStep},
+    };
+    use mockall::predicate::*;
+    use rocket::http::{Method, Status};
+    use serde_json::Value;
+
+    /// All adlists are returned from the database
+    #[test]
+    fn get_adlists() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/adlists")
+            .need_database(true)
+            .expect_json(json!([
+                {
+                    "id": 1,
+                    "address": "https://raw.githubusercontent.com/StevenBlack/hosts/master/hosts",
+                    "enabled": true,
+                    "comment": "Migrated from /etc/pihole/adlists.list",
+                    "date_added": 1_557_712_118,
+                    "date_modified": 1_557_712_118
+                },
+                {
+                    "id": 2,
+                    "address": "https://mirror1.malwaredomains.com/files/justdomains",
+                    "enabled": true,
+                    "comment": "Migrated from /etc/pihole/adlists.list",
+                    "date_added": 1_557_712_118,
+                    "date_modified": 1_557_712_118
+                }
+            ]))
+            .test();
+    }
+
+    /// The domain count of the adlist is returned
+    #[test]
+    fn get_adlist_stats() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/adlists/2/stats")
+            .mock_provider::<dyn AdlistRepository>(Box::new(move |_| {
+                let mut repo = MockAdlistRepository::new();
+
+                repo.expect_domain_count().with(eq(2)).return_const(Ok(4));
+
+                Ok(Box::new(repo))
+            }))
+            .expect_json(json!({ "domain_count": 4 }))
+            .test();
+    }
+
+    /// The test database does not record the adlist of each gravity domain,
+    /// so the domain count is zero
+    #[test]
+    fn get_adlist_stats_database() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/adlists/2/stats")
+            .need_database(true)
+            .expect_json(json!({ "domain_count": 0 }))
+            .test();
+    }
+
+    /// Getting the statistics of an adlist which does not exist is a not
+    /// found error
+    #[test]
+    fn get_adlist_stats_not_found() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/adlists/10/stats")
+            .need_database(true)
+            .expect_status(Status::NotFound)
+            .expect_json(json!({
+                "error": {
+                    "key": "not_found",
+                    "message": "Not found",
+                    "data": Value::Null
+                }
+            }))
+            .test();
+    }
+
+    /// The status of the adlist's last download is returned
+    #[test]
+    fn get_adlist_status() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/adlists/2/status")
+            .mock_provider::<dyn AdlistRepository>(Box::new(move |_| {
+                let mut repo = MockAdlistRepository::new();
+
+                repo.expect_status()
+                    .with(eq(2))
+                    .return_const(Ok(AdlistStatus {
+                        date_updated: Some(1_557_712_200),
+                        number_of_domains: Some(4),
+                        fetch_failed: Some(true),
+                    }));
+
+                Ok(Box::new(repo))
+            }))
+            .expect_json(json!({
+                "date_updated": 1_557_712_200,
+                "number_of_domains": 4,
+                "fetch_failed": true
+            }))
+            .test();
+    }
+
+    /// The test database does not record the status of adlists, so the
+    /// fields are null
+    #[test]
+    fn get_adlist_status_unknown() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/adlists/2/status")
+            .need_database(true)
+            .expect_json(json!({
+                "date_updated": null,
+                "number_of_domains": null,
+                "fetch_failed": null
+            }))
+            .test();
+    }
+
+    /// A valid adlist is added to the repository
+    #[test]
+    fn add_adlist() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/adlists")
+            .method(Method::Post)
+            .body(json!({ "address": "https://example.com/hosts", "comment": "Example" }))
+            .mock_provider::<dyn AdlistRepository>(Box::new(move |_| {
+                let mut repo = MockAdlistRepository::new();
+
+                repo.expect_add()
+                    .with(
+                        eq("https://example.com/hosts"),
+                        eq(Some("Example".to_owned())),
+                    )
+                    .return_const(Ok(()));
+
+                Ok(Box::new(repo))
+            }))
+            .expect_json(json!({ "status": "success" }))
+            .test();
+    }
+
+    /// Added adlists are returned with the existing adlists
+    #[test]
+    fn add_adlist_database() {
+        TestBuilder::new()
+            .need_database(true)
+            .step(
+                TestStep::new()
+                    .endpoint("/admin/api/dns/adlists")
+                    .method(Method::Post)
+                    .body(json!({ "address": "file:///etc/pihole/local.list" }))
+                    .expect_json(json!({ "status": "success" })),
+            )
+            .step(
+                TestStep::new()
+                    .endpoint("/admin/api/dns/adlists")
+                    .ignore_fields(&["*.date_added", "*.date_modified"])
+                    .expect_json(json!([
+                        {
+                            "id": 1,
+                            "address": "https://raw.githubusercontent.com/StevenBlack/hosts/master/hosts",
+                            "enabled": true,
+                            "comment": "Migrated from /etc/pihole/adlists.list"
+                        },
+                        {
+                            "id": 2,
+                            "address": "https://mirror1.malwaredomains.com/files/justdomains",
+                            "enabled": true,
+                            "comment": "Migrated from /etc/pihole/adlists.list"
+                        },
+                        {
+                            "id": 3,
+                            "address": "file:///etc/pihole/local.list",
+                            "enabled": true,
+                            "comment": null
+                        }
+                    ])),
+            )
+            .test();
+    }
+
+    /// Addresses without a supported scheme are rejected
+    #[test]
+    fn add_invalid_scheme() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/adlists")
+            .method(Method::Post)
+            .body(json!({ "address": "ftp://example.com/hosts" }))
+            .mock_provider::<dyn AdlistRepository>(Box::new(move |_| {
+                Ok(Box::new(MockAdlistRepository::new()))
+            }))
+            .expect_status(Status::BadRequest)
+            .expect_json(json!({
+                "error": {
+                    "key": "invalid_adlist",
+                    "message": "Invalid adlist address ftp://example.com/hosts",
+                    "data": { "address": "ftp://example.com/hosts" }
+                }
+            }))
+            .test();
+    }
+
+    /// Adding an adlist which already exists is a conflict
+    #[test]
+    fn add_duplicate() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/adlists")
+            .method(Method::Post)
+            .need_database(true)
+            .body(json!({ "address": "https://mirror1.malwaredomains.com/files/justdomains" }))
+            .expect_status(Status::Conflict)
+            .expect_json(json!({
+                "error": {
+                    "key": "already_exists",
+                    "message": "Item already exists",
+                    "data": Value::Null
+                }
+            }))
+            .test();
+    }
+
+    /// Deleting an adlist removes it from the database
+    #[test]
+    fn delete_adlist_database() {
+        TestBuilder::new()
+            .need_database(true)
+            .step(
+                TestStep::new()
+                    .endpoint("/admin/api/dns/adlists/1")
+                    .method(Method::Delete)
+                    .expect_json(json!({ "status": "success" })),
+            )
+            .step(
+                TestStep::new()
+                    .endpoint("/admin/api/dns/adlists")
+                    .expect_json(json!([
+                        {
+                            "id": 2,
+                            "address": "https://mirror1.malwaredomains.com/files/justdomains",
+                            "enabled": true,
+                            "comment": "Migrated from /etc/pihole/adlists.list",
+                            "date_added": 1_557_712_118,
+                            "date_modified": 1_557_712_118
+                        }
+                    ])),
+            )
+            .test();
+    }
+
+    /// Deleting an adlist which does not exist is a not found error
+    #[test]
+    fn delete_not_found() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/adlists/10")
+            .method(Method::Delete)
+            .need_database(true)
+            .expect_status(Status::NotFound)
+            .expect_json(json!({
+                "error": {
+                    "key": "not_found",
+                    "message": "Not found",
+                    "data": Value::Null
+                }
+            }))
+            .test();
+    }
+
+    /// Updating an adlist passes the new state to the repository
+    #[test]
+    fn update_adlist() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/adlists/2")
+            .method(Method::Put)
+            .body(json!({ "enabled": false }))
+            .mock_provider::<dyn AdlistRepository>(Box::new(move |_| {
+                let mut repo = MockAdlistRepository::new();
+
+                repo.expect_set_enabled()
+                    .with(eq(2), eq(false))
+                    .return_const(Ok(()));
+
+                Ok(Box::new(repo))
+            }))
+            .expect_json(json!({ "status": "success" }))
+            .test();
+    }
+
+    /// Disabled adlists are still returned, along with their state
+    #[test]
+    fn update_adlist_database() {
+        TestBuilder::new()
+            .need_database(true)
+            .step(
+                TestStep::new()
+                    .endpoint("/admin/api/dns/adlists/2")
+                    .method(Method::Put)
+                    .body(json!({ "enabled": false }))
+                    .expect_json(json!({ "status": "success" })),
+            )
+            .step(
+                TestStep::new()
+                    .endpoint("/admin/api/dns/adlists")
+                    .ignore_fields(&["*.date_modified"])
+                    .expect_json(json!([
+                        {
+                            "id": 1,
+                            "address": "https://raw.githubusercontent.com/StevenBlack/hosts/master/hosts",
+                            "enabled": true,
+                            "comment": "Migrated from /etc/pihole/adlists.list",
+                            "date_added": 1_557_712_118
+                        },
+                        {
+                            "id": 2,
+                            "address": "https://mirror1.malwaredomains.com/files/justdomains",
+                            "enabled": false,
+                            "comment": "Migrated from /etc/pihole/adlists.list",
+                            "date_added": 1_557_712_118
+                        }
+                    ])),
+            )
+            .test();
+    }
+
+    /// Updating an adlist which does not exist is a not found error
+    #[test]
+    fn update_not_found() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/adlists/10")
+            .method(Method::Put)
+            .body(json!({ "enabled": true }))
+            .need_database(true)
+            .expect_status(Status::NotFound)
+            .expect_json(json!({
+                "error": {
+                    "key": "not_found",
+                    "message": "Not found",
+                    "data": Value::Null
+                }
+            }))
+            .test();
+    }
+}
@@ -0,0 +1,159 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Endpoints For The Domain Audit Log
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    routes::auth::User,
+    services::{domain_audit::DomainAuditRepository, PiholeModule},
+    util::{reply_data, reply_success, Reply},
+};
+use rocket::serde::json::Json;
+use shaku_rocket::InjectProvided;
+
+/// Represents an API input containing a domain to audit
+#[derive(Deserialize)]
+pub struct AuditInput {
+    domain: String,
+}
+
+/// Get the audited domains
+#[get("/dns/audit")]
+pub fn get_audit(
+    _auth: User,
+    repo: InjectProvided<PiholeModule, dyn DomainAuditRepository>,
+) -> Reply {
+    reply_data(repo.get_all()?)
+}
+
+/// Mark a domain as audited. Auditing a domain which is already audited does
+/// nothing.
+#[post("/dns/audit", data = "<audit_input>")]
+pub fn add_audit(
+    _auth: User,
+    repo: InjectProvided<PiholeModule, dyn DomainAuditRepository>,
+    audit_input: Json<AuditInput>,
+) -> Reply {
+    if !repo.contains(&audit_input.domain)? {
+        repo.add(&audit_input.domain)?;
+    }
+
+    reply_success()
+}
+
+/// Remove a domain from the audit log
+#[delete("/dns/audit/<domain>")]
+pub fn delete_audit(
+    _auth: User,
+    repo: InjectProvided<PiholeModule, dyn DomainAuditRepository>,
+    domain: String,
+) -> Reply {
+    repo.remove(&domain)?;
+    reply_success()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        services::domain_audit::{DomainAuditRepository, MockDomainAuditRepository},
+        testing::{TestBuilder, TestStep},
+    };
+    use mockall::predicate::*;
+    use rocket::http::{Method, Status};
+    use serde_json::Value;
+
+    /// The audited domains are returned from the database
+    #[test]
+    fn get_audit() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/audit")
+            .need_database(true)
+            .expect_json(json!(["audited.domain"]))
+            .test();
+    }
+
+    /// A new domain is added to the audit log
+    #[test]
+    fn add_audit() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/audit")
+            .method(Method::Post)
+            .body(json!({ "domain": "example.com" }))
+            .mock_provider::<dyn DomainAuditRepository>(Box::new(move |_| {
+                let mut repo = MockDomainAuditRepository::new();
+
+                repo.expect_contains()
+                    .with(eq("example.com"))
+                    .return_const(Ok(false));
+                repo.expect_add()
+                    .with(eq("example.com"))
+                    .return_const(Ok(()));
+
+                Ok(Box::new(repo))
+            }))
+            .expect_json(json!({ "status": "success" }))
+            .test();
+    }
+
+    /// Auditing a domain twice only adds it once
+    #[test]
+    fn add_audit_dedupe() {
+        TestBuilder::new()
+            .need_database(true)
+            .step(
+                TestStep::new()
+                    .endpoint("/admin/api/dns/audit")
+                    .method(Method::Post)
+                    .body(json!({ "domain": "audited.domain" }))
+                    .expect_json(json!({ "status": "success" })),
+            )
+            .step(
+                TestStep::new()
+                    .endpoint("/admin/api/dns/audit")
+                    .expect_json(json!(["audited.domain"])),
+            )
+            .test();
+    }
+
+    /// Removed domains are no longer audited
+    #[test]
+    fn delete_audit() {
+        TestBuilder::new()
+            .need_database(true)
+            .step(
+                TestStep::new()
+                    .endpoint("/admin/api/dns/audit/audited.domain")
+                    .method(Method::Delete)
+                    .expect_json(json!({ "status": "success" })),
+            )
+            .step(
+                TestStep::new()
+                    .endpoint("/admin/api/dns/audit")
+                    .expect_json(json!([])),
+            )
+            .test();
+    }
+
+    /// Removing a domain which is not audited is a not found error
+    #[test]
+    fn delete_not_found() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/audit/example.com")
+            .method(Method::Delete)
+            .need_database(true)
+            .expect_status(Status::NotFound)
+            .expect_json(json!({
+                "error": {
+                    "key": "not_found",
+                    "message": "Not found",
+                    "data": Value::Null
+                }
+            }))
+            .test();
+    }
+}
@@ -9,13 +9,14 @@
 // Please see LICENSE file for your rights under this license.
 
 use crate::{
-    routes::auth::User,
+    routes::{auth::User, dns::DomainsInput},
     services::{
-        lists::{List, ListService},
+        lists::{wildcard_regex, List, ListService},
         PiholeModule,
     },
-    util::{reply_success, Reply},
+    util::{reply_data, reply_success, Reply},
 };
+use rocket::serde::json::Json;
 use shaku_rocket::InjectProvided;
 
 /// Delete a domain from the whitelist
@@ -29,14 +30,21 @@ pub fn delete_whitelist(
     reply_success()
 }
 
-/// Delete a domain from the blacklist
-#[delete("/dns/blacklist/<domain>")]
+/// Delete a domain from the blacklist. If `wildcard` is true, the regex
+/// generated for the wildcard is deleted from the regex list instead.
+#[delete("/dns/blacklist/<domain>?<wildcard>")]
 pub fn delete_blacklist(
     _auth: User,
     list_service: InjectProvided<PiholeModule, dyn ListService>,
     domain: String,
+    wildcard: Option<bool>,
 ) -> Reply {
-    list_service.remove(List::Black, &domain)?;
+    if wildcard.unwrap_or(false) {
+        list_service.remove(List::Regex, &wildcard_regex(&domain)?)?;
+    } else {
+        list_service.remove(List::Black, &domain)?;
+    }
+
     reply_success()
 }
 
@@ -51,11 +59,52 @@ pub fn delete_regexlist(
     reply_success()
 }
 
+/// Delete a domain from the regex whitelist
+#[delete("/dns/regexWhitelist/<domain>")]
+pub fn delete_regex_whitelist(
+    _auth: User,
+    list_service: InjectProvided<PiholeModule, dyn ListService>,
+    domain: String,
+) -> Reply {
+    list_service.remove(List::RegexWhite, &domain)?;
+    reply_success()
+}
+
+/// Delete multiple domains from the whitelist
+#[delete("/dns/whitelist/batch", data = "<domains_input>")]
+pub fn delete_whitelist_batch(
+    _auth: User,
+    list_service: InjectProvided<PiholeModule, dyn ListService>,
+    domains_input: Json<DomainsInput>,
+) -> Reply {
+    reply_data(list_service.remove_many(List::White, &domains_input.0.domains)?)
+}
+
+/// Delete multiple domains from the blacklist
+#[delete("/dns/blacklist/batch", data = "<domains_input>")]
+pub fn delete_blacklist_batch(
+    _auth: User,
+    list_service: InjectProvided<PiholeModule, dyn ListService>,
+    domains_input: Json<DomainsInput>,
+) -> Reply {
+    reply_data(list_service.remove_many(List::Black, &domains_input.0.domains)?)
+}
+
+/// Delete multiple domains from the regex list
+#[delete("/dns/regexlist/batch", data = "<domains_input>")]
+pub fn delete_regexlist_batch(
+    _auth: User,
+    list_service: InjectProvided<PiholeModule, dyn ListService>,
+    domains_input: Json<DomainsInput>,
+) -> Reply {
+    reply_data(list_service.remove_many(List::Regex, &domains_input.0.domains)?)
+}
+
 #[cfg(test)]
 mod test {
     use crate::{
-        services::lists::{List, ListService, MockListService},
-        testing::TestBuilder,
+        services::lists::{BatchRemoveResult, List, ListService, MockListService},
+        testing::{write_eom, TestBuilder, TestStep},
     };
     use mockall::predicate::*;
     use rocket::http::Method;
@@ -105,4 +154,125 @@ mod test {
             "^.*example.com$",
         );
     }
+
+    #[test]
+    fn test_delete_regex_whitelist() {
+        delete_test(
+            List::RegexWhite,
+            "/admin/api/dns/regexWhitelist/%5E.%2Aexample.com%24",
+            "^.*example.com$",
+        );
+    }
+
+    /// The domains are passed to the service, and the result is returned
+    #[test]
+    fn delete_blacklist_batch() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/blacklist/batch")
+            .method(Method::Delete)
+            .body(json!({ "domains": ["example.com", "not-found.com"] }))
+            .mock_provider::<dyn ListService>(Box::new(move |_| {
+                let mut service = MockListService::new();
+
+                service
+                    .expect_remove_many()
+                    .withf(|list, domains| {
+                        *list == List::Black && domains == ["example.com", "not-found.com"]
+                    })
+                    .return_const(Ok(BatchRemoveResult {
+                        removed: 1,
+                        not_found: vec!["not-found.com".to_owned()],
+                    }));
+
+                Ok(Box::new(service))
+            }))
+            .expect_json(json!({
+                "removed": 1,
+                "not_found": ["not-found.com"]
+            }))
+            .test();
+    }
+
+    /// The domains are removed from the database, and an empty batch is not
+    /// an error
+    #[test]
+    fn delete_whitelist_batch_database() {
+        TestBuilder::new()
+            .need_database(true)
+            .step(
+                TestStep::new()
+                    .endpoint("/admin/api/dns/whitelist/batch")
+                    .method(Method::Delete)
+                    .body(json!({ "domains": ["test.com", "disabled-white.com"] }))
+                    .expect_json(json!({ "removed": 2, "not_found": [] })),
+            )
+            .step(
+                TestStep::new()
+                    .endpoint("/admin/api/dns/whitelist/batch")
+                    .method(Method::Delete)
+                    .body(json!({ "domains": [] }))
+                    .expect_json(json!({ "removed": 0, "not_found": [] })),
+            )
+            .step(
+                TestStep::new()
+                    .endpoint("/admin/api/dns/whitelist?show_disabled=true")
+                    .expect_json(json!([])),
+            )
+            .test();
+    }
+
+    /// A wildcard added through the blacklist is stored as a regex, and
+    /// deleting it in the same form removes the regex
+    #[test]
+    fn wildcard_round_trip() {
+        let mut data = Vec::new();
+        write_eom(&mut data);
+
+        TestBuilder::new()
+            .need_database(true)
+            .ftl("recompile-regex", data)
+            .step(
+                TestStep::new()
+                    .endpoint("/admin/api/dns/blacklist")
+                    .method(Method::Post)
+                    .body(json!({ "domain": "ads.com", "wildcard": true }))
+                    .expect_json(json!({ "status": "success" })),
+            )
+            .step(
+                TestStep::new()
+                    .endpoint("/admin/api/dns/regexlist")
+                    .ignore_fields(&["*.date_added", "*.date_modified"])
+                    .expect_json(json!([
+                        {
+                            "domain": "(^|\\.)example\\.com$",
+                            "enabled": true,
+                            "comment": null
+                        },
+                        {
+                            "domain": "(\\.|^)ads\\.com$",
+                            "enabled": true,
+                            "comment": "Wildcard for ads.com"
+                        }
+                    ])),
+            )
+            .step(
+                TestStep::new()
+                    .endpoint("/admin/api/dns/blacklist/ads.com?wildcard=true")
+                    .method(Method::Delete)
+                    .expect_json(json!({ "status": "success" })),
+            )
+            .step(
+                TestStep::new()
+                    .endpoint("/admin/api/dns/regexlist")
+                    .ignore_fields(&["*.date_added", "*.date_modified"])
+                    .expect_json(json!([
+                        {
+                            "domain": "(^|\\.)example\\.com$",
+                            "enabled": true,
+                            "comment": null
+                        }
+                    ])),
+            )
+            .test();
+    }
 }
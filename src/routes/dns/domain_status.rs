@@ -0,0 +1,325 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Endpoint For Explaining Why A Domain Is Blocked
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    services::{
+        adlists::AdlistRepository,
+        lists::{List, ListRepository},
+        PiholeModule,
+    },
+    util::{reply_result, Error, Reply},
+};
+use regex::Regex;
+use shaku_rocket::InjectProvided;
+
+/// Explain whether the domain is blocked, and which list entry decided it
+#[get("/dns/status/<domain>")]
+pub fn get_domain_status(
+    list_repo: InjectProvided<PiholeModule, dyn ListRepository>,
+    adlist_repo: InjectProvided<PiholeModule, dyn AdlistRepository>,
+    domain: String,
+) -> Reply {
+    reply_result(find_domain_status(&*list_repo, &*adlist_repo, &domain))
+}
+
+/// The kind of list entry which decided the domain's status
+#[derive(Serialize, Copy, Clone)]
+#[cfg_attr(test, derive(Debug, PartialEq))]
+#[serde(rename_all = "snake_case")]
+pub enum StatusReason {
+    Whitelist,
+    RegexWhitelist,
+    Blacklist,
+    RegexBlacklist,
+    Gravity,
+}
+
+/// Represents the reply structure for a domain's status. `match` is the list
+/// entry which decided the status, or the adlist address for gravity.
+#[derive(Serialize)]
+#[cfg_attr(test, derive(Debug, PartialEq))]
+pub struct DomainStatusReply {
+    pub blocked: bool,
+    pub reason: Option<StatusReason>,
+    #[serde(rename = "match")]
+    pub matched: Option<String>,
+}
+
+impl DomainStatusReply {
+    /// The domain was decided by the entry
+    fn decided(blocked: bool, reason: StatusReason, matched: String) -> Self {
+        DomainStatusReply {
+            blocked,
+            reason: Some(reason),
+            matched: Some(matched),
+        }
+    }
+}
+
+/// Check the lists in the order FTL does: the whitelist, the regex
+/// whitelist, the blacklist, the regex blacklist and then gravity. Disabled
+/// entries are ignored.
+fn find_domain_status(
+    list_repo: &dyn ListRepository,
+    adlist_repo: &dyn AdlistRepository,
+    domain: &str,
+) -> Result<DomainStatusReply, Error> {
+    let domain = List::Black.normalize(&domain.to_lowercase())?;
+
+    if is_enabled_on(list_repo, List::White, &domain)? {
+        return Ok(DomainStatusReply::decided(
+            false,
+            StatusReason::Whitelist,
+            domain,
+        ));
+    }
+
+    if let Some(pattern) = find_regex_match(list_repo, List::RegexWhite, &domain)? {
+        return Ok(DomainStatusReply::decided(
+            false,
+            StatusReason::RegexWhitelist,
+            pattern,
+        ));
+    }
+
+    if is_enabled_on(list_repo, List::Black, &domain)? {
+        return Ok(DomainStatusReply::decided(
+            true,
+            StatusReason::Blacklist,
+            domain,
+        ));
+    }
+
+    if let Some(pattern) = find_regex_match(list_repo, List::Regex, &domain)? {
+        return Ok(DomainStatusReply::decided(
+            true,
+            StatusReason::RegexBlacklist,
+            pattern,
+        ));
+    }
+
+    if let Some(adlist) = adlist_repo.find_domain(&domain)? {
+        return Ok(DomainStatusReply::decided(
+            true,
+            StatusReason::Gravity,
+            adlist.address,
+        ));
+    }
+
+    Ok(DomainStatusReply {
+        blocked: false,
+        reason: None,
+        matched: None,
+    })
+}
+
+/// Check if the domain is on the list and enabled
+fn is_enabled_on(repo: &dyn ListRepository, list: List, domain: &str) -> Result<bool, Error> {
+    Ok(repo
+        .find(list, domain)?
+        .map(|entry| entry.enabled)
+        .unwrap_or(false))
+}
+
+/// Find the first enabled pattern on the regex list which matches the
+/// domain. Stored patterns which do not compile are skipped.
+fn find_regex_match(
+    repo: &dyn ListRepository,
+    list: List,
+    domain: &str,
+) -> Result<Option<String>, Error> {
+    Ok(repo
+        .get(list, false)?
+        .into_iter()
+        .map(|entry| entry.domain)
+        .find(|pattern| {
+            Regex::new(pattern)
+                .map(|regex| regex.is_match(domain))
+                .unwrap_or(false)
+        }))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{find_domain_status, DomainStatusReply, StatusReason};
+    use crate::{
+        services::{
+            adlists::{Adlist, AdlistRepository, MockAdlistRepository},
+            lists::{List, ListEntry, MockListRepository},
+        },
+        testing::TestBuilder,
+    };
+    use mockall::predicate::*;
+
+    /// Create a list entry
+    fn entry(domain: &str, enabled: bool) -> ListEntry {
+        ListEntry {
+            domain: domain.to_owned(),
+            enabled,
+            comment: None,
+            date_added: 1,
+            date_modified: 1,
+        }
+    }
+
+    /// The test gravity database has a single connection, which is taken by
+    /// the list repository, so the database tests mock the adlist repository.
+    /// No domain is found in gravity.
+    fn no_adlists() -> MockAdlistRepository {
+        let mut repo = MockAdlistRepository::new();
+
+        repo.expect_find_domain().return_const(Ok(None));
+
+        repo
+    }
+
+    /// Disabled exact entries are skipped, and the first matching regex
+    /// blocks the domain
+    #[test]
+    fn regex_blacklist() {
+        let mut list_repo = MockListRepository::new();
+        let adlist_repo = MockAdlistRepository::new();
+
+        list_repo
+            .expect_find()
+            .with(eq(List::White), eq("ads.example.com"))
+            .return_const(Ok(Some(entry("ads.example.com", false))));
+        list_repo
+            .expect_find()
+            .with(eq(List::Black), eq("ads.example.com"))
+            .return_const(Ok(None));
+        list_repo
+            .expect_get()
+            .with(eq(List::RegexWhite), eq(false))
+            .return_const(Ok(vec![entry("^example\\.com$", true)]));
+        list_repo
+            .expect_get()
+            .with(eq(List::Regex), eq(false))
+            .return_const(Ok(vec![
+                entry("([a-z", true),
+                entry("example\\.net$", true),
+                entry("^ads\\.", true),
+            ]));
+
+        assert_eq!(
+            find_domain_status(&list_repo, &adlist_repo, "ADS.example.com").unwrap(),
+            DomainStatusReply {
+                blocked: true,
+                reason: Some(StatusReason::RegexBlacklist),
+                matched: Some("^ads\\.".to_owned())
+            }
+        );
+    }
+
+    /// An exact whitelist entry takes priority over gravity
+    #[test]
+    fn whitelist_database() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/status/test.com")
+            .need_database(true)
+            .mock_provider::<dyn AdlistRepository>(Box::new(|_| Ok(Box::new(no_adlists()))))
+            .expect_json(json!({
+                "blocked": false,
+                "reason": "whitelist",
+                "match": "test.com"
+            }))
+            .test();
+    }
+
+    /// A regex whitelist entry takes priority over a blacklist regex
+    #[test]
+    fn regex_whitelist_database() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/status/allowed.example.com")
+            .need_database(true)
+            .mock_provider::<dyn AdlistRepository>(Box::new(|_| Ok(Box::new(no_adlists()))))
+            .expect_json(json!({
+                "blocked": false,
+                "reason": "regex_whitelist",
+                "match": "^allowed\\.example\\.com$"
+            }))
+            .test();
+    }
+
+    /// An exact blacklist entry takes priority over a matching regex
+    #[test]
+    fn blacklist_database() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/status/example.com")
+            .need_database(true)
+            .mock_provider::<dyn AdlistRepository>(Box::new(|_| Ok(Box::new(no_adlists()))))
+            .expect_json(json!({
+                "blocked": true,
+                "reason": "blacklist",
+                "match": "example.com"
+            }))
+            .test();
+    }
+
+    /// Subdomains are blocked by the regex entry
+    #[test]
+    fn regex_blacklist_database() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/status/ads.example.com")
+            .need_database(true)
+            .mock_provider::<dyn AdlistRepository>(Box::new(|_| Ok(Box::new(no_adlists()))))
+            .expect_json(json!({
+                "blocked": true,
+                "reason": "regex_blacklist",
+                "match": "(^|\\.)example\\.com$"
+            }))
+            .test();
+    }
+
+    /// Gravity domains are blocked by the adlist they came from
+    #[test]
+    fn gravity_database() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/status/vra4.com")
+            .need_database(true)
+            .mock_provider::<dyn AdlistRepository>(Box::new(move |_| {
+                let mut repo = MockAdlistRepository::new();
+
+                repo.expect_find_domain()
+                    .with(eq("vra4.com"))
+                    .return_const(Ok(Some(Adlist {
+                        id: 2,
+                        address: "https://mirror1.malwaredomains.com/files/justdomains".to_owned(),
+                        enabled: true,
+                        comment: None,
+                        date_added: 1,
+                        date_modified: 1,
+                    })));
+
+                Ok(Box::new(repo))
+            }))
+            .expect_json(json!({
+                "blocked": true,
+                "reason": "gravity",
+                "match": "https://mirror1.malwaredomains.com/files/justdomains"
+            }))
+            .test();
+    }
+
+    /// Domains which are on no list are not blocked
+    #[test]
+    fn not_blocked_database() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/status/disabled-black.com")
+            .need_database(true)
+            .mock_provider::<dyn AdlistRepository>(Box::new(|_| Ok(Box::new(no_adlists()))))
+            .expect_json(json!({
+                "blocked": false,
+                "reason": null,
+                "match": null
+            }))
+            .test();
+    }
+}
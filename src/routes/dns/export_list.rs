@@ -0,0 +1,180 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Endpoint For Exporting Domain Lists
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    routes::auth::User,
+    services::{
+        lists::{List, ListEntry, ListRepository},
+        PiholeModule,
+    },
+    util::{reply_result, Error, Reply},
+};
+use shaku_rocket::InjectProvided;
+
+/// All of the domain lists, including disabled entries. This is meant to be
+/// fed back into an import, so the format should not change:
+///
+/// ```json
+/// {
+///   "whitelist": [
+///     {
+///       "domain": "example.com",
+///       "enabled": true,
+///       "comment": "A comment, or null",
+///       "date_added": 1557712172,
+///       "date_modified": 1557712172
+///     }
+///   ],
+///   "blacklist": [...],
+///   "regexlist": [...],
+///   "regex_whitelist": [...]
+/// }
+/// ```
+#[derive(Serialize)]
+#[cfg_attr(test, derive(Debug, PartialEq))]
+pub struct ListsExport {
+    pub whitelist: Vec<ListEntry>,
+    pub blacklist: Vec<ListEntry>,
+    pub regexlist: Vec<ListEntry>,
+    pub regex_whitelist: Vec<ListEntry>,
+}
+
+/// Export all of the lists in one document
+#[get("/dns/lists/export")]
+pub fn export_lists(_auth: User, repo: InjectProvided<PiholeModule, dyn ListRepository>) -> Reply {
+    reply_result(get_lists_export(&*repo))
+}
+
+/// Read all of the lists. The repository holds a single database connection,
+/// so all four lists are read using the same connection.
+fn get_lists_export(repo: &dyn ListRepository) -> Result<ListsExport, Error> {
+    Ok(ListsExport {
+        whitelist: repo.get(List::White, true)?,
+        blacklist: repo.get(List::Black, true)?,
+        regexlist: repo.get(List::Regex, true)?,
+        regex_whitelist: repo.get(List::RegexWhite, true)?,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::testing::{TestBuilder, TestStep};
+    use rocket::http::Method;
+
+    /// All lists are exported, including disabled entries
+    #[test]
+    fn export() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/lists/export")
+            .need_database(true)
+            .expect_json(json!({
+                "whitelist": [
+                    {
+                        "domain": "test.com",
+                        "enabled": true,
+                        "comment": null,
+                        "date_added": 1_557_712_172,
+                        "date_modified": 1_557_712_172
+                    },
+                    {
+                        "domain": "disabled-white.com",
+                        "enabled": false,
+                        "comment": null,
+                        "date_added": 1_557_723_854,
+                        "date_modified": 1_557_723_911
+                    }
+                ],
+                "blacklist": [
+                    {
+                        "domain": "example.com",
+                        "enabled": true,
+                        "comment": null,
+                        "date_added": 1_557_712_177,
+                        "date_modified": 1_557_712_177
+                    },
+                    {
+                        "domain": "disabled-black.com",
+                        "enabled": false,
+                        "comment": null,
+                        "date_added": 1_557_723_854,
+                        "date_modified": 1_557_723_864
+                    }
+                ],
+                "regexlist": [
+                    {
+                        "domain": "(^|\\.)example\\.com$",
+                        "enabled": true,
+                        "comment": null,
+                        "date_added": 1_557_712_181,
+                        "date_modified": 1_557_712_181
+                    },
+                    {
+                        "domain": "disabled\\-regex\\.com",
+                        "enabled": false,
+                        "comment": null,
+                        "date_added": 1_557_723_854,
+                        "date_modified": 1_557_723_872
+                    }
+                ],
+                "regex_whitelist": [
+                    {
+                        "domain": "^allowed\\.example\\.com$",
+                        "enabled": true,
+                        "comment": null,
+                        "date_added": 1_557_712_185,
+                        "date_modified": 1_557_712_185
+                    }
+                ]
+            }))
+            .test();
+    }
+
+    /// Entries added through the API are exported with their comments
+    #[test]
+    fn export_added_entry() {
+        TestBuilder::new()
+            .need_database(true)
+            .step(
+                TestStep::new()
+                    .endpoint("/admin/api/dns/blacklist")
+                    .method(Method::Post)
+                    .body(json!({ "domain": "ads.com", "comment": "Ads" }))
+                    .expect_json(json!({ "status": "success" })),
+            )
+            .step(
+                TestStep::new()
+                    .endpoint("/admin/api/dns/lists/export")
+                    .expect_json_contains(json!({
+                        "blacklist": [
+                            {
+                                "domain": "example.com",
+                                "enabled": true,
+                                "comment": null,
+                                "date_added": 1_557_712_177,
+                                "date_modified": 1_557_712_177
+                            },
+                            {
+                                "domain": "disabled-black.com",
+                                "enabled": false,
+                                "comment": null,
+                                "date_added": 1_557_723_854,
+                                "date_modified": 1_557_723_864
+                            },
+                            {
+                                "domain": "ads.com",
+                                "enabled": true,
+                                "comment": "Ads"
+                            }
+                        ]
+                    })),
+            )
+            .test();
+    }
+}
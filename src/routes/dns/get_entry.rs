@@ -0,0 +1,159 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Endpoints For Reading Single Domain List Entries
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    routes::dns::GroupedListEntry,
+    services::{
+        lists::{List, ListService},
+        PiholeModule,
+    },
+    util::{reply_result, Error, Reply},
+};
+use shaku_rocket::InjectProvided;
+
+/// Get a domain on the whitelist
+#[get("/dns/whitelist/<domain>")]
+pub fn get_whitelist_entry(
+    service: InjectProvided<PiholeModule, dyn ListService>,
+    domain: String,
+) -> Reply {
+    reply_result(get_entry(&*service, List::White, &domain))
+}
+
+/// Get a domain on the blacklist
+#[get("/dns/blacklist/<domain>")]
+pub fn get_blacklist_entry(
+    service: InjectProvided<PiholeModule, dyn ListService>,
+    domain: String,
+) -> Reply {
+    reply_result(get_entry(&*service, List::Black, &domain))
+}
+
+/// Get a pattern on the regex list
+#[get("/dns/regexlist/<domain>")]
+pub fn get_regexlist_entry(
+    service: InjectProvided<PiholeModule, dyn ListService>,
+    domain: String,
+) -> Reply {
+    reply_result(get_entry(&*service, List::Regex, &domain))
+}
+
+/// Get a pattern on the regex whitelist
+#[get("/dns/regexWhitelist/<domain>")]
+pub fn get_regex_whitelist_entry(
+    service: InjectProvided<PiholeModule, dyn ListService>,
+    domain: String,
+) -> Reply {
+    reply_result(get_entry(&*service, List::RegexWhite, &domain))
+}
+
+/// Get the entry along with the IDs of its groups
+fn get_entry(
+    service: &dyn ListService,
+    list: List,
+    domain: &str,
+) -> Result<GroupedListEntry, Error> {
+    let entry = service.get_entry(list, domain)?;
+    let groups = service.get_groups(list, &entry.domain)?;
+
+    Ok(GroupedListEntry { entry, groups })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        services::lists::{List, ListEntry, ListService, MockListService},
+        testing::TestBuilder,
+        util::{Error, ErrorKind},
+    };
+    use mockall::predicate::*;
+    use rocket::http::Status;
+    use serde_json::Value;
+
+    /// The entry is returned with its metadata and groups. Regex entries are
+    /// URL decoded.
+    #[test]
+    fn regex_entry() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/regexlist/%5E.%2Aexample%5C.com%24")
+            .mock_provider::<dyn ListService>(Box::new(move |_| {
+                let mut service = MockListService::new();
+
+                service
+                    .expect_get_entry()
+                    .with(eq(List::Regex), eq("^.*example\\.com$"))
+                    .return_const(Ok(ListEntry {
+                        domain: "^.*example\\.com$".to_owned(),
+                        enabled: false,
+                        comment: Some("Ads".to_owned()),
+                        date_added: 1,
+                        date_modified: 2,
+                    }));
+                service
+                    .expect_get_groups()
+                    .with(eq(List::Regex), eq("^.*example\\.com$"))
+                    .return_const(Ok(vec![1]));
+
+                Ok(Box::new(service))
+            }))
+            .expect_json(json!({
+                "domain": "^.*example\\.com$",
+                "enabled": false,
+                "comment": "Ads",
+                "date_added": 1,
+                "date_modified": 2,
+                "groups": [1]
+            }))
+            .test();
+    }
+
+    /// A domain which is not on the list is a not found error
+    #[test]
+    fn not_found() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/whitelist/example.com")
+            .mock_provider::<dyn ListService>(Box::new(move |_| {
+                let mut service = MockListService::new();
+
+                service
+                    .expect_get_entry()
+                    .with(eq(List::White), eq("example.com"))
+                    .return_const(Err(Error::from(ErrorKind::NotFound)));
+
+                Ok(Box::new(service))
+            }))
+            .expect_status(Status::NotFound)
+            .expect_json(json!({
+                "error": {
+                    "key": "not_found",
+                    "message": "Not found",
+                    "data": Value::Null
+                }
+            }))
+            .test();
+    }
+
+    /// The entry is read from the database, including disabled entries
+    #[test]
+    fn entry_database() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/blacklist/disabled-black.com")
+            .need_database(true)
+            .expect_json(json!({
+                "domain": "disabled-black.com",
+                "enabled": false,
+                "comment": null,
+                "date_added": 1_557_723_854,
+                "date_modified": 1_557_723_864,
+                "groups": []
+            }))
+            .test();
+    }
+}
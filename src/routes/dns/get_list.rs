@@ -10,51 +10,481 @@
 
 use crate::{
     services::{
-        lists::{List, ListService},
+        lists::{List, ListEntry, ListFilter, ListQuery, ListService, ListSort},
         PiholeModule,
     },
-    util::{reply_result, Reply},
+    util::{reply_data, reply_result, Error, Reply},
 };
 use shaku_rocket::InjectProvided;
 
-/// Get the Whitelist domains
-#[get("/dns/whitelist")]
-pub fn get_whitelist(service: InjectProvided<PiholeModule, dyn ListService>) -> Reply {
-    reply_result(service.get(List::White))
+/// Get the Whitelist entries
+#[get("/dns/whitelist?<params..>")]
+pub fn get_whitelist(
+    service: InjectProvided<PiholeModule, dyn ListService>,
+    params: ListParams,
+) -> Reply {
+    get_list(&*service, List::White, params)
 }
 
-/// Get the Blacklist domains
-#[get("/dns/blacklist")]
-pub fn get_blacklist(service: InjectProvided<PiholeModule, dyn ListService>) -> Reply {
-    reply_result(service.get(List::Black))
+/// Get the Blacklist entries
+#[get("/dns/blacklist?<params..>")]
+pub fn get_blacklist(
+    service: InjectProvided<PiholeModule, dyn ListService>,
+    params: ListParams,
+) -> Reply {
+    get_list(&*service, List::Black, params)
 }
 
-/// Get the Regex list domains
-#[get("/dns/regexlist")]
-pub fn get_regexlist(service: InjectProvided<PiholeModule, dyn ListService>) -> Reply {
-    reply_result(service.get(List::Regex))
+/// Get the Regex list entries
+#[get("/dns/regexlist?<params..>")]
+pub fn get_regexlist(
+    service: InjectProvided<PiholeModule, dyn ListService>,
+    params: ListParams,
+) -> Reply {
+    get_list(&*service, List::Regex, params)
+}
+
+/// Get the Regex whitelist entries
+#[get("/dns/regexWhitelist?<params..>")]
+pub fn get_regex_whitelist(
+    service: InjectProvided<PiholeModule, dyn ListService>,
+    params: ListParams,
+) -> Reply {
+    get_list(&*service, List::RegexWhite, params)
+}
+
+/// Represents the possible GET parameters on the list endpoints
+#[derive(FromForm, Default)]
+pub struct ListParams {
+    pub limit: Option<usize>,
+    pub offset: Option<usize>,
+    pub show_disabled: Option<bool>,
+    pub with_groups: Option<bool>,
+    pub sort: Option<String>,
+    pub order: Option<String>,
+    pub enabled: Option<bool>,
+    pub comment_contains: Option<String>,
+}
+
+/// A list entry along with the IDs of the groups it is assigned to
+#[derive(Serialize)]
+pub struct GroupedListEntry {
+    #[serde(flatten)]
+    pub entry: ListEntry,
+    pub groups: Vec<i32>,
+}
+
+/// A page of list entries along with their groups
+#[derive(Serialize)]
+pub struct GroupedListPage {
+    pub entries: Vec<GroupedListEntry>,
+    pub total: usize,
+}
+
+/// Get the list entries. If no paging parameters are given, all of the
+/// entries are returned as an array. Otherwise, the requested page is returned
+/// along with the total number of entries. If `with_groups` is true, each
+/// entry includes the IDs of its groups. If `sort` is given, the entries are
+/// sorted by that column in the direction given by `order`. The `enabled` and
+/// `comment_contains` filters are applied before paging, and the total only
+/// counts the matching entries.
+fn get_list(service: &dyn ListService, list: List, params: ListParams) -> Reply {
+    let with_groups = params.with_groups.unwrap_or(false);
+    let query = ListQuery {
+        filter: ListFilter {
+            include_disabled: params.show_disabled.unwrap_or(false),
+            enabled: params.enabled,
+            comment_contains: params.comment_contains,
+        },
+        sort: ListSort::from_params(params.sort.as_deref(), params.order.as_deref())?,
+        limit: params.limit,
+        offset: params.offset.unwrap_or(0),
+    };
+
+    if params.limit.is_none() && params.offset.is_none() {
+        let entries = if query.filter.is_filtering() || query.sort.is_some() {
+            service.query(list, &query)?.entries
+        } else {
+            service.get(list, query.filter.include_disabled)?
+        };
+
+        if with_groups {
+            return reply_result(add_groups(service, list, entries));
+        }
+
+        return reply_data(entries);
+    }
+
+    let page = service.query(list, &query)?;
+
+    if with_groups {
+        return reply_data(GroupedListPage {
+            entries: add_groups(service, list, page.entries)?,
+            total: page.total,
+        });
+    }
+
+    reply_data(page)
+}
+
+/// Attach the IDs of the groups to each of the entries
+fn add_groups(
+    service: &dyn ListService,
+    list: List,
+    entries: Vec<ListEntry>,
+) -> Result<Vec<GroupedListEntry>, Error> {
+    let mut memberships = service.get_memberships(list)?;
+
+    Ok(entries
+        .into_iter()
+        .map(|entry| GroupedListEntry {
+            groups: memberships.remove(&entry.domain).unwrap_or_default(),
+            entry,
+        })
+        .collect())
 }
 
 #[cfg(test)]
 mod test {
     use crate::{
-        services::lists::{List, ListService, MockListService},
+        services::lists::{
+            List, ListEntry, ListFilter, ListPage, ListQuery, ListService, ListSort,
+            MockListService, SortKey, SortOrder,
+        },
         testing::TestBuilder,
     };
     use mockall::predicate::*;
+    use rocket::http::Status;
 
-    /// Test that the domains are returned correctly
+    /// Test that the entries are returned correctly
     fn get_test(list: List, endpoint: &str, domains: Vec<String>) {
+        let entries: Vec<ListEntry> = domains
+            .into_iter()
+            .map(|domain| ListEntry {
+                domain,
+                enabled: true,
+                comment: None,
+                date_added: 1_557_712_172,
+                date_modified: 1_557_712_172,
+            })
+            .collect();
+
         TestBuilder::new()
             .endpoint(endpoint)
-            .expect_json(json!(domains))
+            .expect_json(json!(entries))
+            .mock_provider::<dyn ListService>(Box::new(move |_| {
+                let mut service = MockListService::new();
+
+                service
+                    .expect_get()
+                    .with(eq(list), eq(false))
+                    .return_const(Ok(entries.clone()));
+
+                Ok(Box::new(service))
+            }))
+            .test();
+    }
+
+    /// The entry metadata is included in the output
+    #[test]
+    fn entry_format() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/blacklist")
+            .expect_json(json!([{
+                "domain": "example.com",
+                "enabled": true,
+                "comment": "Ads",
+                "date_added": 1_557_712_177,
+                "date_modified": 1_557_712_177
+            }]))
             .mock_provider::<dyn ListService>(Box::new(move |_| {
                 let mut service = MockListService::new();
 
                 service
                     .expect_get()
-                    .with(eq(list))
-                    .return_const(Ok(domains.clone()));
+                    .with(eq(List::Black), eq(false))
+                    .return_const(Ok(vec![ListEntry {
+                        domain: "example.com".to_owned(),
+                        enabled: true,
+                        comment: Some("Ads".to_owned()),
+                        date_added: 1_557_712_177,
+                        date_modified: 1_557_712_177,
+                    }]));
+
+                Ok(Box::new(service))
+            }))
+            .test();
+    }
+
+    /// The limit and offset parameters return a page of entries with the total
+    #[test]
+    fn paged() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/blacklist?limit=1&offset=2")
+            .expect_json(json!({
+                "entries": [{
+                    "domain": "example.com",
+                    "enabled": true,
+                    "comment": null,
+                    "date_added": 1_557_712_177,
+                    "date_modified": 1_557_712_177
+                }],
+                "total": 3
+            }))
+            .mock_provider::<dyn ListService>(Box::new(move |_| {
+                let mut service = MockListService::new();
+
+                service
+                    .expect_query()
+                    .with(
+                        eq(List::Black),
+                        eq(ListQuery {
+                            limit: Some(1),
+                            offset: 2,
+                            ..ListQuery::default()
+                        }),
+                    )
+                    .return_const(Ok(ListPage {
+                        entries: vec![ListEntry {
+                            domain: "example.com".to_owned(),
+                            enabled: true,
+                            comment: None,
+                            date_added: 1_557_712_177,
+                            date_modified: 1_557_712_177,
+                        }],
+                        total: 3,
+                    }));
+
+                Ok(Box::new(service))
+            }))
+            .test();
+    }
+
+    /// Only giving an offset returns the rest of the entries
+    #[test]
+    fn paged_offset_only() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/whitelist?offset=1")
+            .expect_json(json!({
+                "entries": [],
+                "total": 1
+            }))
+            .mock_provider::<dyn ListService>(Box::new(move |_| {
+                let mut service = MockListService::new();
+
+                service
+                    .expect_query()
+                    .with(
+                        eq(List::White),
+                        eq(ListQuery {
+                            offset: 1,
+                            ..ListQuery::default()
+                        }),
+                    )
+                    .return_const(Ok(ListPage {
+                        entries: Vec::new(),
+                        total: 1,
+                    }));
+
+                Ok(Box::new(service))
+            }))
+            .test();
+    }
+
+    /// The sort parameters sort the page of entries
+    #[test]
+    fn sorted_page() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/blacklist?sort=date_modified&order=desc&limit=1")
+            .expect_json(json!({
+                "entries": [{
+                    "domain": "disabled-black.com",
+                    "enabled": false,
+                    "comment": null,
+                    "date_added": 1_557_723_854,
+                    "date_modified": 1_557_723_864
+                }],
+                "total": 2
+            }))
+            .mock_provider::<dyn ListService>(Box::new(move |_| {
+                let mut service = MockListService::new();
+
+                service
+                    .expect_query()
+                    .with(
+                        eq(List::Black),
+                        eq(ListQuery {
+                            sort: Some(ListSort {
+                                key: SortKey::DateModified,
+                                order: SortOrder::Desc,
+                            }),
+                            limit: Some(1),
+                            ..ListQuery::default()
+                        }),
+                    )
+                    .return_const(Ok(ListPage {
+                        entries: vec![ListEntry {
+                            domain: "disabled-black.com".to_owned(),
+                            enabled: false,
+                            comment: None,
+                            date_added: 1_557_723_854,
+                            date_modified: 1_557_723_864,
+                        }],
+                        total: 2,
+                    }));
+
+                Ok(Box::new(service))
+            }))
+            .test();
+    }
+
+    /// Without paging parameters, all of the sorted entries are returned
+    #[test]
+    fn sorted_database() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/whitelist?sort=domain&show_disabled=true")
+            .need_database(true)
+            .expect_json(json!([
+                {
+                    "domain": "disabled-white.com",
+                    "enabled": false,
+                    "comment": null,
+                    "date_added": 1_557_723_854,
+                    "date_modified": 1_557_723_911
+                },
+                {
+                    "domain": "test.com",
+                    "enabled": true,
+                    "comment": null,
+                    "date_added": 1_557_712_172,
+                    "date_modified": 1_557_712_172
+                }
+            ]))
+            .test();
+    }
+
+    /// The filters are passed to the service along with the paging
+    /// parameters
+    #[test]
+    fn filtered_page() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/blacklist?enabled=false&comment_contains=imported&limit=10")
+            .expect_json(json!({
+                "entries": [{
+                    "domain": "ads.com",
+                    "enabled": false,
+                    "comment": "imported from backup",
+                    "date_added": 1,
+                    "date_modified": 2
+                }],
+                "total": 1
+            }))
+            .mock_provider::<dyn ListService>(Box::new(move |_| {
+                let mut service = MockListService::new();
+
+                service
+                    .expect_query()
+                    .with(
+                        eq(List::Black),
+                        eq(ListQuery {
+                            filter: ListFilter {
+                                include_disabled: false,
+                                enabled: Some(false),
+                                comment_contains: Some("imported".to_owned()),
+                            },
+                            limit: Some(10),
+                            ..ListQuery::default()
+                        }),
+                    )
+                    .return_const(Ok(ListPage {
+                        entries: vec![ListEntry {
+                            domain: "ads.com".to_owned(),
+                            enabled: false,
+                            comment: Some("imported from backup".to_owned()),
+                            date_added: 1,
+                            date_modified: 2,
+                        }],
+                        total: 1,
+                    }));
+
+                Ok(Box::new(service))
+            }))
+            .test();
+    }
+
+    /// Only the disabled entries are returned when filtering by enabled
+    /// state
+    #[test]
+    fn filtered_disabled_database() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/blacklist?enabled=false")
+            .need_database(true)
+            .expect_json(json!([{
+                "domain": "disabled-black.com",
+                "enabled": false,
+                "comment": null,
+                "date_added": 1_557_723_854,
+                "date_modified": 1_557_723_864
+            }]))
+            .test();
+    }
+
+    /// Entries without a comment never match a comment filter
+    #[test]
+    fn filtered_comment_database() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/whitelist?comment_contains=imported&show_disabled=true")
+            .need_database(true)
+            .expect_json(json!([]))
+            .test();
+    }
+
+    /// An unknown sort key is rejected with the valid keys
+    #[test]
+    fn invalid_sort() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/whitelist?sort=id")
+            .mock_provider::<dyn ListService>(Box::new(|_| Ok(Box::new(MockListService::new()))))
+            .expect_status(Status::BadRequest)
+            .expect_json(json!({
+                "error": {
+                    "key": "invalid_parameter",
+                    "message": "Invalid value id for parameter sort",
+                    "data": {
+                        "parameter": "sort",
+                        "value": "id",
+                        "valid": ["domain", "date_added", "date_modified"]
+                    }
+                }
+            }))
+            .test();
+    }
+
+    /// Disabled entries are requested when show_disabled is set
+    #[test]
+    fn show_disabled() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/whitelist?show_disabled=true")
+            .expect_json(json!([{
+                "domain": "disabled-white.com",
+                "enabled": false,
+                "comment": null,
+                "date_added": 1_557_723_854,
+                "date_modified": 1_557_723_911
+            }]))
+            .mock_provider::<dyn ListService>(Box::new(move |_| {
+                let mut service = MockListService::new();
+
+                service
+                    .expect_get()
+                    .with(eq(List::White), eq(true))
+                    .return_const(Ok(vec![ListEntry {
+                        domain: "disabled-white.com".to_owned(),
+                        enabled: false,
+                        comment: None,
+                        date_added: 1_557_723_854,
+                        date_modified: 1_557_723_911,
+                    }]));
 
                 Ok(Box::new(service))
             }))
@@ -87,4 +517,13 @@ mod test {
             vec!["^.*example.com$".to_owned(), "example.net".to_owned()],
         );
     }
+
+    #[test]
+    fn test_get_regex_whitelist() {
+        get_test(
+            List::RegexWhite,
+            "/admin/api/dns/regexWhitelist",
+            vec!["^ads\\.example\\.com$".to_owned()],
+        );
+    }
 }
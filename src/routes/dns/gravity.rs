@@ -0,0 +1,74 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Endpoint For Gravity Statistics
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    env::Env,
+    services::{
+        gravity::{GravityCountCache, GravityRepository},
+        PiholeModule,
+    },
+    util::{reply_data, Reply},
+};
+use shaku_rocket::{Inject, InjectProvided};
+use std::time::Duration;
+
+/// Represents the reply structure for the number of domains in gravity
+#[derive(Serialize)]
+pub struct GravityCount {
+    count: usize,
+}
+
+/// Get the number of domains being blocked by gravity. The count is cached,
+/// since it only changes when gravity runs.
+#[get("/dns/gravity/count")]
+pub fn get_gravity_count(
+    repo: InjectProvided<PiholeModule, dyn GravityRepository>,
+    cache: Inject<PiholeModule, GravityCountCache>,
+    env: Inject<PiholeModule, Env>,
+) -> Reply {
+    let ttl = Duration::from_secs(env.config().general.gravity_count_ttl);
+
+    reply_data(GravityCount {
+        count: cache.get_or_count(ttl, || repo.count())?,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        services::gravity::{GravityRepository, MockGravityRepository},
+        testing::TestBuilder,
+    };
+
+    /// The rows of the seeded gravity table are counted
+    #[test]
+    fn gravity_count() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/gravity/count")
+            .need_database(true)
+            .expect_json(json!({ "count": 10 }))
+            .test();
+    }
+
+    /// A gravity table which is empty or missing has a count of zero
+    #[test]
+    fn gravity_count_empty() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/gravity/count")
+            .mock_provider::<dyn GravityRepository>(Box::new(move |_| {
+                let mut repo = MockGravityRepository::new();
+                repo.expect_count().return_const(Ok(0));
+
+                Ok(Box::new(repo))
+            }))
+            .expect_json(json!({ "count": 0 }))
+            .test();
+    }
+}
@@ -0,0 +1,251 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Endpoints For Managing Groups
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    routes::auth::User,
+    services::{groups::GroupRepository, PiholeModule},
+    util::{reply_data, reply_success, Error, ErrorKind, Reply},
+};
+use rocket::serde::json::Json;
+use shaku_rocket::InjectProvided;
+
+/// Represents an API input containing a new group
+#[derive(Deserialize)]
+pub struct GroupInput {
+    name: String,
+    description: Option<String>,
+}
+
+/// Represents an API input containing the new state of a group
+#[derive(Deserialize)]
+pub struct GroupUpdateInput {
+    name: String,
+    enabled: bool,
+    description: Option<String>,
+}
+
+/// Check that the group name is not blank
+fn validate_name(name: &str) -> Result<(), Error> {
+    if name.trim().is_empty() {
+        Err(Error::from(ErrorKind::BadRequest))
+    } else {
+        Ok(())
+    }
+}
+
+/// Get all of the groups
+#[get("/dns/groups")]
+pub fn get_groups(repo: InjectProvided<PiholeModule, dyn GroupRepository>) -> Reply {
+    reply_data(repo.get()?)
+}
+
+/// Add a group
+#[post("/dns/groups", data = "<group_input>")]
+pub fn add_group(
+    _auth: User,
+    repo: InjectProvided<PiholeModule, dyn GroupRepository>,
+    group_input: Json<GroupInput>,
+) -> Reply {
+    let group_input = group_input.into_inner();
+
+    validate_name(&group_input.name)?;
+    repo.add(&group_input.name, group_input.description)?;
+    reply_success()
+}
+
+/// Update a group
+#[put("/dns/groups/<id>", data = "<update_input>")]
+pub fn update_group(
+    _auth: User,
+    repo: InjectProvided<PiholeModule, dyn GroupRepository>,
+    id: i32,
+    update_input: Json<GroupUpdateInput>,
+) -> Reply {
+    let update_input = update_input.into_inner();
+
+    validate_name(&update_input.name)?;
+    repo.update(
+        id,
+        &update_input.name,
+        update_input.enabled,
+        update_input.description,
+    )?;
+    reply_success()
+}
+
+/// Delete a group. If the group still has members, it is only deleted (along
+/// with its memberships) when `force` is true.
+#[delete("/dns/groups/<id>?<force>")]
+pub fn delete_group(
+    _auth: User,
+    repo: InjectProvided<PiholeModule, dyn GroupRepository>,
+    id: i32,
+    force: Option<bool>,
+) -> Reply {
+    repo.remove(id, force.unwrap_or(false))?;
+    reply_success()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        services::groups::{GroupRepository, MockGroupRepository},
+        testing::{TestBuilder, TestStep},
+    };
+    use mockall::predicate::*;
+    use rocket::http::{Method, Status};
+    use serde_json::Value;
+
+    /// All groups are returned from the database
+    #[test]
+    fn get_groups() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/groups")
+            .need_database(true)
+            .expect_json(json!([
+                {
+                    "id": 1,
+                    "name": "Test",
+                    "enabled": true,
+                    "description": "A test group"
+                }
+            ]))
+            .test();
+    }
+
+    /// Added groups are returned with the existing groups
+    #[test]
+    fn add_group() {
+        TestBuilder::new()
+            .need_database(true)
+            .step(
+                TestStep::new()
+                    .endpoint("/admin/api/dns/groups")
+                    .method(Method::Post)
+                    .body(json!({ "name": "New" }))
+                    .expect_json(json!({ "status": "success" })),
+            )
+            .step(
+                TestStep::new()
+                    .endpoint("/admin/api/dns/groups")
+                    .expect_json(json!([
+                        {
+                            "id": 1,
+                            "name": "Test",
+                            "enabled": true,
+                            "description": "A test group"
+                        },
+                        {
+                            "id": 2,
+                            "name": "New",
+                            "enabled": true,
+                            "description": null
+                        }
+                    ])),
+            )
+            .test();
+    }
+
+    /// Groups must have a name
+    #[test]
+    fn add_blank_name() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/groups")
+            .method(Method::Post)
+            .body(json!({ "name": " " }))
+            .mock_provider::<dyn GroupRepository>(Box::new(move |_| {
+                Ok(Box::new(MockGroupRepository::new()))
+            }))
+            .expect_status(Status::BadRequest)
+            .expect_json(json!({
+                "error": {
+                    "key": "bad_request",
+                    "message": "Bad request",
+                    "data": Value::Null
+                }
+            }))
+            .test();
+    }
+
+    /// The new state of the group is passed to the repository
+    #[test]
+    fn update_group() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/groups/1")
+            .method(Method::Put)
+            .body(json!({ "name": "Renamed", "enabled": false, "description": null }))
+            .mock_provider::<dyn GroupRepository>(Box::new(move |_| {
+                let mut repo = MockGroupRepository::new();
+
+                repo.expect_update()
+                    .with(eq(1), eq("Renamed"), eq(false), eq(None))
+                    .return_const(Ok(()));
+
+                Ok(Box::new(repo))
+            }))
+            .expect_json(json!({ "status": "success" }))
+            .test();
+    }
+
+    /// Deleting a group which still has members is a conflict
+    #[test]
+    fn delete_not_empty() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/groups/1")
+            .method(Method::Delete)
+            .need_database(true)
+            .expect_status(Status::Conflict)
+            .expect_json(json!({
+                "error": {
+                    "key": "group_not_empty",
+                    "message": "Group 1 still has members",
+                    "data": { "id": 1 }
+                }
+            }))
+            .test();
+    }
+
+    /// Forcing the deletion of a group which still has members deletes it
+    #[test]
+    fn delete_force() {
+        TestBuilder::new()
+            .need_database(true)
+            .step(
+                TestStep::new()
+                    .endpoint("/admin/api/dns/groups/1?force=true")
+                    .method(Method::Delete)
+                    .expect_json(json!({ "status": "success" })),
+            )
+            .step(
+                TestStep::new()
+                    .endpoint("/admin/api/dns/groups")
+                    .expect_json(json!([])),
+            )
+            .test();
+    }
+
+    /// Deleting a group which does not exist is a not found error
+    #[test]
+    fn delete_not_found() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/groups/10")
+            .method(Method::Delete)
+            .need_database(true)
+            .expect_status(Status::NotFound)
+            .expect_json(json!({
+                "error": {
+                    "key": "not_found",
+                    "message": "Not found",
+                    "data": Value::Null
+                }
+            }))
+            .test();
+    }
+}
@@ -0,0 +1,231 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Endpoint For Importing Domain Lists
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    routes::auth::User,
+    services::{
+        lists::{ImportMode, ListService, ListsImport},
+        PiholeModule,
+    },
+    util::{reply_data, Reply},
+};
+use rocket::serde::json::Json;
+use shaku_rocket::InjectProvided;
+
+/// Import the lists from a document in the list export format. The `mode`
+/// parameter is either `merge` (the default) or `replace`. Whitelist and
+/// blacklist entries which are on the opposite list are counted as conflicts
+/// unless `force` is true, which moves them.
+#[post("/dns/lists/import?<mode>&<force>", data = "<import>")]
+pub fn import_lists(
+    _auth: User,
+    list_service: InjectProvided<PiholeModule, dyn ListService>,
+    mode: Option<ImportMode>,
+    force: Option<bool>,
+    import: Json<ListsImport>,
+) -> Reply {
+    reply_data(list_service.import(
+        import.into_inner(),
+        mode.unwrap_or(ImportMode::Merge),
+        force.unwrap_or(false),
+    )?)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        services::lists::{
+            ImportCounts, ImportMode, ListService, ListsImport, ListsImportResult, MockListService,
+            NewListEntry,
+        },
+        testing::{TestBuilder, TestStep},
+    };
+    use mockall::predicate::*;
+    use rocket::http::{Method, Status};
+
+    /// The import is passed to the service, and the counts are returned
+    #[test]
+    fn import_replace() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/lists/import?mode=replace")
+            .method(Method::Post)
+            .body(json!({
+                "blacklist": [
+                    { "domain": "example.com", "enabled": false, "comment": "Ads" }
+                ]
+            }))
+            .mock_provider::<dyn ListService>(Box::new(move |_| {
+                let mut service = MockListService::new();
+
+                service
+                    .expect_import()
+                    .with(
+                        eq(ListsImport {
+                            blacklist: vec![NewListEntry {
+                                domain: "example.com".to_owned(),
+                                enabled: false,
+                                comment: Some("Ads".to_owned()),
+                            }],
+                            ..ListsImport::default()
+                        }),
+                        eq(ImportMode::Replace),
+                        eq(false),
+                    )
+                    .return_const(Ok(ListsImportResult {
+                        blacklist: ImportCounts {
+                            inserted: 1,
+                            skipped: 0,
+                            invalid: 0,
+                            conflicts: 0,
+                            moved: 0,
+                        },
+                        ..ListsImportResult::default()
+                    }));
+
+                Ok(Box::new(service))
+            }))
+            .expect_json(json!({
+                "whitelist": {
+                    "inserted": 0,
+                    "skipped": 0,
+                    "invalid": 0,
+                    "conflicts": 0,
+                    "moved": 0
+                },
+                "blacklist": {
+                    "inserted": 1,
+                    "skipped": 0,
+                    "invalid": 0,
+                    "conflicts": 0,
+                    "moved": 0
+                },
+                "regexlist": {
+                    "inserted": 0,
+                    "skipped": 0,
+                    "invalid": 0,
+                    "conflicts": 0,
+                    "moved": 0
+                },
+                "regex_whitelist": {
+                    "inserted": 0,
+                    "skipped": 0,
+                    "invalid": 0,
+                    "conflicts": 0,
+                    "moved": 0
+                }
+            }))
+            .test();
+    }
+
+    /// Forcing the import moves the entries which are on the opposite list
+    #[test]
+    fn import_forced() {
+        TestBuilder::new()
+            .need_database(true)
+            .step(
+                TestStep::new()
+                    .endpoint("/admin/api/dns/lists/import")
+                    .method(Method::Post)
+                    .body(json!({ "whitelist": [{ "domain": "example.com" }] }))
+                    .expect_json_contains(json!({
+                        "whitelist": { "inserted": 0, "conflicts": 1, "moved": 0 }
+                    })),
+            )
+            .step(
+                TestStep::new()
+                    .endpoint("/admin/api/dns/lists/import?force=true")
+                    .method(Method::Post)
+                    .body(json!({ "whitelist": [{ "domain": "example.com" }] }))
+                    .expect_json_contains(json!({
+                        "whitelist": { "inserted": 1, "conflicts": 0, "moved": 1 }
+                    })),
+            )
+            .step(
+                TestStep::new()
+                    .endpoint("/admin/api/dns/blacklist/example.com")
+                    .expect_status(Status::NotFound)
+                    .expect_json_contains(json!({ "error": { "key": "not_found" } })),
+            )
+            .test();
+    }
+
+    /// An exported document can be imported back in merge mode, skipping the
+    /// entries which already exist
+    #[test]
+    fn import_export_round_trip() {
+        TestBuilder::new()
+            .need_database(true)
+            .step(
+                TestStep::new()
+                    .endpoint("/admin/api/dns/lists/import")
+                    .method(Method::Post)
+                    .body(json!({
+                        "whitelist": [
+                            {
+                                "domain": "test.com",
+                                "enabled": true,
+                                "comment": null,
+                                "date_added": 1_557_712_172
+                            },
+                            { "domain": "new.com", "comment": "New" }
+                        ],
+                        "regexlist": [{ "domain": "([a-z" }]
+                    }))
+                    .expect_json(json!({
+                        "whitelist": {
+                            "inserted": 1,
+                            "skipped": 1,
+                            "invalid": 0,
+                            "conflicts": 0,
+                            "moved": 0
+                        },
+                        "blacklist": {
+                            "inserted": 0,
+                            "skipped": 0,
+                            "invalid": 0,
+                            "conflicts": 0,
+                            "moved": 0
+                        },
+                        "regexlist": {
+                            "inserted": 0,
+                            "skipped": 0,
+                            "invalid": 1,
+                            "conflicts": 0,
+                            "moved": 0
+                        },
+                        "regex_whitelist": {
+                            "inserted": 0,
+                            "skipped": 0,
+                            "invalid": 0,
+                            "conflicts": 0,
+                            "moved": 0
+                        }
+                    })),
+            )
+            .step(
+                TestStep::new()
+                    .endpoint("/admin/api/dns/whitelist")
+                    .ignore_fields(&["*.date_added", "*.date_modified"])
+                    .expect_json(json!([
+                        {
+                            "domain": "test.com",
+                            "enabled": true,
+                            "comment": null
+                        },
+                        {
+                            "domain": "new.com",
+                            "enabled": true,
+                            "comment": "New"
+                        }
+                    ])),
+            )
+            .test();
+    }
+}
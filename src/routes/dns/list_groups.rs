@@ -0,0 +1,241 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Endpoints For Assigning List Domains To Groups
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    routes::auth::User,
+    services::{
+        lists::{List, ListService},
+        PiholeModule,
+    },
+    util::{reply_result, reply_success, Reply},
+};
+use rocket::serde::json::Json;
+use shaku_rocket::InjectProvided;
+
+/// Represents an API input containing the groups of a domain
+#[derive(Deserialize)]
+pub struct DomainGroupsInput {
+    groups: Vec<i32>,
+}
+
+/// Get the groups of a domain on the whitelist
+#[get("/dns/whitelist/<domain>/groups")]
+pub fn get_whitelist_groups(
+    list_service: InjectProvided<PiholeModule, dyn ListService>,
+    domain: String,
+) -> Reply {
+    reply_result(list_service.get_groups(List::White, &domain))
+}
+
+/// Get the groups of a domain on the blacklist
+#[get("/dns/blacklist/<domain>/groups")]
+pub fn get_blacklist_groups(
+    list_service: InjectProvided<PiholeModule, dyn ListService>,
+    domain: String,
+) -> Reply {
+    reply_result(list_service.get_groups(List::Black, &domain))
+}
+
+/// Get the groups of a domain on the regex list
+#[get("/dns/regexlist/<domain>/groups")]
+pub fn get_regexlist_groups(
+    list_service: InjectProvided<PiholeModule, dyn ListService>,
+    domain: String,
+) -> Reply {
+    reply_result(list_service.get_groups(List::Regex, &domain))
+}
+
+/// Replace the groups of a domain on the whitelist
+#[put("/dns/whitelist/<domain>/groups", data = "<groups_input>")]
+pub fn set_whitelist_groups(
+    _auth: User,
+    list_service: InjectProvided<PiholeModule, dyn ListService>,
+    domain: String,
+    groups_input: Json<DomainGroupsInput>,
+) -> Reply {
+    list_service.set_groups(List::White, &domain, &groups_input.groups)?;
+    reply_success()
+}
+
+/// Replace the groups of a domain on the blacklist
+#[put("/dns/blacklist/<domain>/groups", data = "<groups_input>")]
+pub fn set_blacklist_groups(
+    _auth: User,
+    list_service: InjectProvided<PiholeModule, dyn ListService>,
+    domain: String,
+    groups_input: Json<DomainGroupsInput>,
+) -> Reply {
+    list_service.set_groups(List::Black, &domain, &groups_input.groups)?;
+    reply_success()
+}
+
+/// Replace the groups of a domain on the regex list
+#[put("/dns/regexlist/<domain>/groups", data = "<groups_input>")]
+pub fn set_regexlist_groups(
+    _auth: User,
+    list_service: InjectProvided<PiholeModule, dyn ListService>,
+    domain: String,
+    groups_input: Json<DomainGroupsInput>,
+) -> Reply {
+    list_service.set_groups(List::Regex, &domain, &groups_input.groups)?;
+    reply_success()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        services::lists::{List, ListService, MockListService},
+        testing::{TestBuilder, TestStep},
+        util::{Error, ErrorKind},
+    };
+    use mockall::predicate::*;
+    use rocket::http::{Method, Status};
+    use serde_json::Value;
+
+    /// Test that the groups are passed to the service
+    fn set_groups_test(list: List, endpoint: &str, domain: &'static str) {
+        TestBuilder::new()
+            .endpoint(endpoint)
+            .method(Method::Put)
+            .body(json!({ "groups": [1, 2] }))
+            .mock_provider::<dyn ListService>(Box::new(move |_| {
+                let mut service = MockListService::new();
+
+                service
+                    .expect_set_groups()
+                    .withf(move |input_list, input_domain, group_ids| {
+                        *input_list == list && input_domain == domain && group_ids == [1, 2]
+                    })
+                    .return_const(Ok(()));
+
+                Ok(Box::new(service))
+            }))
+            .expect_json(json!({ "status": "success" }))
+            .test();
+    }
+
+    #[test]
+    fn set_whitelist_groups() {
+        set_groups_test(
+            List::White,
+            "/admin/api/dns/whitelist/example.com/groups",
+            "example.com",
+        );
+    }
+
+    #[test]
+    fn set_blacklist_groups() {
+        set_groups_test(
+            List::Black,
+            "/admin/api/dns/blacklist/example.com/groups",
+            "example.com",
+        );
+    }
+
+    #[test]
+    fn set_regexlist_groups() {
+        set_groups_test(
+            List::Regex,
+            "/admin/api/dns/regexlist/%5E.%2Aexample.com%24/groups",
+            "^.*example.com$",
+        );
+    }
+
+    /// Assigning a domain to a group which does not exist is rejected
+    #[test]
+    fn set_missing_group() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/blacklist/example.com/groups")
+            .method(Method::Put)
+            .body(json!({ "groups": [10] }))
+            .mock_provider::<dyn ListService>(Box::new(move |_| {
+                let mut service = MockListService::new();
+
+                service
+                    .expect_set_groups()
+                    .return_const(Err(Error::from(ErrorKind::GroupNotFound(10))));
+
+                Ok(Box::new(service))
+            }))
+            .expect_status(Status::BadRequest)
+            .expect_json(json!({
+                "error": {
+                    "key": "group_not_found",
+                    "message": "Group 10 does not exist",
+                    "data": { "id": 10 }
+                }
+            }))
+            .test();
+    }
+
+    /// The groups of a domain are read from the database
+    #[test]
+    fn get_groups_database() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/whitelist/test.com/groups")
+            .need_database(true)
+            .expect_json(json!([]))
+            .test();
+    }
+
+    /// The groups of a domain which is not on the list can not be read
+    #[test]
+    fn get_groups_not_found() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/whitelist/not-found.com/groups")
+            .need_database(true)
+            .expect_status(Status::NotFound)
+            .expect_json(json!({
+                "error": {
+                    "key": "not_found",
+                    "message": "Not found",
+                    "data": Value::Null
+                }
+            }))
+            .test();
+    }
+
+    /// Assigned groups are included in the list when requested
+    #[test]
+    fn list_with_groups() {
+        TestBuilder::new()
+            .need_database(true)
+            .step(
+                TestStep::new()
+                    .endpoint("/admin/api/dns/blacklist/disabled-black.com/groups")
+                    .method(Method::Put)
+                    .body(json!({ "groups": [1] }))
+                    .expect_json(json!({ "status": "success" })),
+            )
+            .step(
+                TestStep::new()
+                    .endpoint("/admin/api/dns/blacklist?show_disabled=true&with_groups=true")
+                    .expect_json(json!([
+                        {
+                            "domain": "example.com",
+                            "enabled": true,
+                            "comment": null,
+                            "date_added": 1_557_712_177,
+                            "date_modified": 1_557_712_177,
+                            "groups": []
+                        },
+                        {
+                            "domain": "disabled-black.com",
+                            "enabled": false,
+                            "comment": null,
+                            "date_added": 1_557_723_854,
+                            "date_modified": 1_557_723_864,
+                            "groups": [1]
+                        }
+                    ])),
+            )
+            .test();
+    }
+}
@@ -0,0 +1,480 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Local CNAME Record Endpoints
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    env::{Env, PiholeFile},
+    routes::{auth::User, dns::local_records::has_local_record},
+    services::{restart::restart_dns, PiholeModule},
+    settings::{read_config, update_config, ValueType},
+    util::{reply_data, reply_success, Error, ErrorKind, Reply},
+};
+use rocket::serde::json::Json;
+use shaku_rocket::Inject;
+use std::collections::{HashMap, HashSet};
+
+/// A local CNAME record, which makes the domain an alias of the target.
+/// Records are stored in a dnsmasq config file as `cname=domain,target`.
+#[derive(Serialize, Deserialize)]
+pub struct LocalCname {
+    domain: String,
+    target: String,
+}
+
+/// A `cname=` line of the config. dnsmasq allows several aliases on one line,
+/// and a TTL after the target.
+struct CnameLine<'a> {
+    aliases: Vec<&'a str>,
+    target: &'a str,
+    ttl: Option<&'a str>,
+}
+
+impl<'a> CnameLine<'a> {
+    /// Parse a line of the config. Comments and other options are not records.
+    fn parse(line: &'a str) -> Option<Self> {
+        let mut parts: Vec<&str> = line
+            .trim()
+            .strip_prefix("cname=")?
+            .split(',')
+            .map(str::trim)
+            .collect();
+
+        let ttl = match parts.last() {
+            Some(ttl) if parts.len() > 2 && ttl.parse::<u32>().is_ok() => parts.pop(),
+            _ => None,
+        };
+        let target = parts.pop()?;
+
+        if parts.is_empty() || target.is_empty() {
+            return None;
+        }
+
+        Some(CnameLine {
+            aliases: parts,
+            target,
+            ttl,
+        })
+    }
+
+    /// Format the record as a line of the config
+    fn to_line(&self) -> String {
+        match self.ttl {
+            Some(ttl) => format!("cname={},{},{}", self.aliases.join(","), self.target, ttl),
+            None => format!("cname={},{}", self.aliases.join(","), self.target),
+        }
+    }
+}
+
+/// Get the local CNAME records, in the order they are in the config
+#[get("/dns/cname")]
+pub fn get_local_cnames(env: Inject<PiholeModule, Env>, _auth: User) -> Reply {
+    let cnames: Vec<LocalCname> = read_config(PiholeFile::CnameConfig, &env)?
+        .iter()
+        .filter_map(|line| CnameLine::parse(line))
+        .flat_map(|record| {
+            let target = record.target;
+
+            record.aliases.into_iter().map(move |alias| LocalCname {
+                domain: alias.to_owned(),
+                target: target.to_owned(),
+            })
+        })
+        .collect();
+
+    reply_data(cnames)
+}
+
+/// Add a local CNAME record. The domain can not already have a CNAME or local
+/// DNS record, and the record can not create a loop of CNAMEs.
+#[post("/dns/cname", data = "<cname>")]
+pub fn add_local_cname(
+    env: Inject<PiholeModule, Env>,
+    _auth: User,
+    cname: Json<LocalCname>,
+) -> Reply {
+    let cname = cname.into_inner();
+    let domain = cname.domain.to_lowercase();
+    let target = cname.target.to_lowercase();
+
+    if !ValueType::Hostname.is_valid(&domain) {
+        return Err(invalid_field("domain", &cname.domain));
+    }
+
+    if !ValueType::Hostname.is_valid(&target) {
+        return Err(invalid_field("target", &cname.target));
+    }
+
+    if domain == target {
+        return Err(invalid_cname(
+            &domain,
+            "The domain can not be its own target",
+        ));
+    }
+
+    update_config(PiholeFile::CnameConfig, &env, |lines| {
+        let targets: HashMap<String, String> = lines
+            .iter()
+            .filter_map(|line| CnameLine::parse(line))
+            .flat_map(|record| {
+                let target = record.target.to_lowercase();
+
+                record
+                    .aliases
+                    .into_iter()
+                    .map(move |alias| (alias.to_lowercase(), target.clone()))
+            })
+            .collect();
+
+        if targets.contains_key(&domain) {
+            return Err(Error::from(ErrorKind::AlreadyExists));
+        }
+
+        if has_local_record(&env, &domain)? {
+            return Err(invalid_cname(
+                &domain,
+                "The domain already has a local DNS record",
+            ));
+        }
+
+        if let Some(chain) = find_loop(&targets, &domain, &target) {
+            return Err(invalid_cname(
+                &domain,
+                &format!("The record would create a loop: {}", chain.join(" -> ")),
+            ));
+        }
+
+        lines.push(format!("cname={},{}", domain, target));
+        Ok(())
+    })?;
+    reload_cnames(&env)?;
+
+    reply_success()
+}
+
+/// Delete the local CNAME record of the domain
+#[delete("/dns/cname/<domain>")]
+pub fn delete_local_cname(env: Inject<PiholeModule, Env>, _auth: User, domain: String) -> Reply {
+    update_config(PiholeFile::CnameConfig, &env, |lines| {
+        let mut found = false;
+        let mut remaining_lines = Vec::new();
+
+        for line in lines.drain(..) {
+            match remove_alias(&line, &domain) {
+                Some(remaining) => {
+                    found = true;
+
+                    // Other aliases on the same line keep their record
+                    if let Some(remaining) = remaining {
+                        remaining_lines.push(remaining);
+                    }
+                }
+                None => remaining_lines.push(line),
+            }
+        }
+
+        if !found {
+            return Err(Error::from(ErrorKind::NotFound));
+        }
+
+        *lines = remaining_lines;
+        Ok(())
+    })?;
+    reload_cnames(&env)?;
+
+    reply_success()
+}
+
+/// Remove the alias from the line, if the line is a record of the alias. The
+/// line is returned without the alias, or `None` if no aliases are left on it.
+fn remove_alias(line: &str, domain: &str) -> Option<Option<String>> {
+    let mut record = CnameLine::parse(line)?;
+    let count = record.aliases.len();
+
+    record
+        .aliases
+        .retain(|alias| !alias.eq_ignore_ascii_case(domain));
+
+    if record.aliases.len() == count {
+        None
+    } else if record.aliases.is_empty() {
+        Some(None)
+    } else {
+        Some(Some(record.to_line()))
+    }
+}
+
+/// Follow the CNAMEs from the target, and get the chain of domains if it leads
+/// back to the domain. Loops which do not include the domain are already in
+/// the config, and are not followed forever.
+fn find_loop(targets: &HashMap<String, String>, domain: &str, target: &str) -> Option<Vec<String>> {
+    let mut chain = vec![domain.to_owned(), target.to_owned()];
+    let mut visited = HashSet::new();
+    let mut current = target;
+
+    while let Some(next) = targets.get(current) {
+        if !visited.insert(current) {
+            return None;
+        }
+
+        chain.push(next.clone());
+
+        if next == domain {
+            return Some(chain);
+        }
+
+        current = next;
+    }
+
+    None
+}
+
+/// Create the error for an invalid field of a CNAME record
+fn invalid_field(field: &str, value: &str) -> Error {
+    Error::from(ErrorKind::InvalidParameter(
+        field.to_owned(),
+        value.to_owned(),
+        Vec::new(),
+    ))
+}
+
+/// Create the error for a CNAME record which can not be added
+fn invalid_cname(domain: &str, reason: &str) -> Error {
+    Error::from(ErrorKind::InvalidCname(
+        domain.to_owned(),
+        reason.to_owned(),
+    ))
+}
+
+/// Restart the DNS server after the config changed, if configured to.
+/// dnsmasq only reads its config files when it starts.
+fn reload_cnames(env: &Env) -> Result<(), Error> {
+    if env.config().general.reload_local_records {
+        restart_dns(env)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{env::PiholeFile, testing::TestBuilder};
+    use rocket::http::{Method, Status};
+
+    /// Send a POST request which is rejected, and check that the config is not
+    /// changed
+    fn test_rejected(
+        config: &str,
+        body: serde_json::Value,
+        status: Status,
+        error: serde_json::Value,
+    ) {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/cname")
+            .method(Method::Post)
+            .file_expect(PiholeFile::CnameConfig, config, config)
+            .file(PiholeFile::CustomList, "192.168.1.10 nas.lan\n")
+            .body(body)
+            .expect_status(status)
+            .expect_json(json!({ "error": error }))
+            .test();
+    }
+
+    /// The records are read in order, skipping comments and other options
+    #[test]
+    fn get() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/cname")
+            .file(
+                PiholeFile::CnameConfig,
+                "# Local CNAMEs\n\
+                 cname=files.lan,nas.lan\n\
+                 local-ttl=2\n\
+                 cname=tv.lan,media.lan,box.lan,300\n",
+            )
+            .expect_json(json!([
+                { "domain": "files.lan", "target": "nas.lan" },
+                { "domain": "tv.lan", "target": "box.lan" },
+                { "domain": "media.lan", "target": "box.lan" }
+            ]))
+            .test();
+    }
+
+    /// A record is appended on its own line, keeping the comments
+    #[test]
+    fn add() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/cname")
+            .method(Method::Post)
+            .file_expect(
+                PiholeFile::CnameConfig,
+                "# Local CNAMEs\n\
+                 cname=files.lan,nas.lan\n",
+                "# Local CNAMEs\n\
+                 cname=files.lan,nas.lan\n\
+                 cname=backup.lan,files.lan\n",
+            )
+            .file(PiholeFile::CustomList, "192.168.1.10 nas.lan\n")
+            .body(json!({ "domain": "Backup.lan", "target": "files.lan" }))
+            .expect_json(json!({ "status": "success" }))
+            .test();
+    }
+
+    /// A domain can not be its own target
+    #[test]
+    fn add_self() {
+        test_rejected(
+            "",
+            json!({ "domain": "nas.lan", "target": "NAS.lan" }),
+            Status::BadRequest,
+            json!({
+                "key": "invalid_cname",
+                "message": "Invalid CNAME record for nas.lan: The domain can not be its own target",
+                "data": {
+                    "domain": "nas.lan",
+                    "reason": "The domain can not be its own target"
+                }
+            }),
+        );
+    }
+
+    /// A record can not point back to itself through other records
+    #[test]
+    fn add_loop() {
+        test_rejected(
+            "cname=a.lan,b.lan\n\
+             cname=b.lan,c.lan\n",
+            json!({ "domain": "c.lan", "target": "a.lan" }),
+            Status::BadRequest,
+            json!({
+                "key": "invalid_cname",
+                "message": "Invalid CNAME record for c.lan: \
+                            The record would create a loop: c.lan -> a.lan -> b.lan -> c.lan",
+                "data": {
+                    "domain": "c.lan",
+                    "reason": "The record would create a loop: c.lan -> a.lan -> b.lan -> c.lan"
+                }
+            }),
+        );
+    }
+
+    /// A chain which does not lead back to the domain is allowed, even if the
+    /// config already has a loop
+    #[test]
+    fn add_chain_with_existing_loop() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/cname")
+            .method(Method::Post)
+            .file_expect(
+                PiholeFile::CnameConfig,
+                "cname=a.lan,b.lan\n\
+                 cname=b.lan,a.lan\n",
+                "cname=a.lan,b.lan\n\
+                 cname=b.lan,a.lan\n\
+                 cname=c.lan,a.lan\n",
+            )
+            .body(json!({ "domain": "c.lan", "target": "a.lan" }))
+            .expect_json(json!({ "status": "success" }))
+            .test();
+    }
+
+    /// A domain can only have one CNAME record
+    #[test]
+    fn add_duplicate() {
+        test_rejected(
+            "cname=files.lan,nas.lan\n",
+            json!({ "domain": "files.lan", "target": "backup.lan" }),
+            Status::Conflict,
+            json!({
+                "key": "already_exists",
+                "message": "Item already exists",
+                "data": null
+            }),
+        );
+    }
+
+    /// A domain with a local DNS record can not also have a CNAME record
+    #[test]
+    fn add_local_record_conflict() {
+        test_rejected(
+            "",
+            json!({ "domain": "nas.lan", "target": "files.lan" }),
+            Status::BadRequest,
+            json!({
+                "key": "invalid_cname",
+                "message": "Invalid CNAME record for nas.lan: \
+                            The domain already has a local DNS record",
+                "data": {
+                    "domain": "nas.lan",
+                    "reason": "The domain already has a local DNS record"
+                }
+            }),
+        );
+    }
+
+    /// Invalid domains are rejected
+    #[test]
+    fn add_invalid_target() {
+        test_rejected(
+            "",
+            json!({ "domain": "files.lan", "target": "my nas" }),
+            Status::BadRequest,
+            json!({
+                "key": "invalid_parameter",
+                "message": "Invalid value my nas for parameter target",
+                "data": {
+                    "parameter": "target",
+                    "value": "my nas",
+                    "valid": []
+                }
+            }),
+        );
+    }
+
+    /// The record of the domain is deleted, and other aliases on the same
+    /// line are kept
+    #[test]
+    fn delete() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/cname/tv.lan")
+            .method(Method::Delete)
+            .file_expect(
+                PiholeFile::CnameConfig,
+                "# Local CNAMEs\n\
+                 cname=files.lan,nas.lan\n\
+                 cname=tv.lan,media.lan,box.lan,300\n",
+                "# Local CNAMEs\n\
+                 cname=files.lan,nas.lan\n\
+                 cname=media.lan,box.lan,300\n",
+            )
+            .expect_json(json!({ "status": "success" }))
+            .test();
+    }
+
+    /// Deleting a domain without a record is an error
+    #[test]
+    fn delete_not_found() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/cname/nas.lan")
+            .method(Method::Delete)
+            .file_expect(
+                PiholeFile::CnameConfig,
+                "cname=files.lan,nas.lan\n",
+                "cname=files.lan,nas.lan\n",
+            )
+            .expect_status(Status::NotFound)
+            .expect_json(json!({
+                "error": {
+                    "key": "not_found",
+                    "message": "Not found",
+                    "data": null
+                }
+            }))
+            .test();
+    }
+}
@@ -0,0 +1,387 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Local DNS Record Endpoints
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    env::{Env, PiholeFile},
+    routes::auth::User,
+    services::{restart::reload_dns, PiholeModule},
+    settings::{read_config, update_config, ValueType},
+    util::{reply_data, reply_success, Error, ErrorKind, Reply},
+};
+use rocket::serde::json::Json;
+use shaku_rocket::Inject;
+use std::net::IpAddr;
+
+/// A local DNS record, which resolves the host to the IP address. Records are
+/// stored in `custom.list`, one `IP hostname` pair per line.
+#[derive(Serialize, Deserialize)]
+pub struct LocalRecord {
+    ip: String,
+    host: String,
+}
+
+/// Get the local DNS records, in the order they are in the file
+#[get("/dns/records")]
+pub fn get_local_records(env: Inject<PiholeModule, Env>, _auth: User) -> Reply {
+    let records: Vec<LocalRecord> = read_config(PiholeFile::CustomList, &env)?
+        .iter()
+        .filter_map(|line| parse_line(line))
+        .flat_map(|(ip, hosts)| {
+            hosts.into_iter().map(move |host| LocalRecord {
+                ip: ip.to_string(),
+                host: host.to_owned(),
+            })
+        })
+        .collect();
+
+    reply_data(records)
+}
+
+/// Add a local DNS record. A host can have one IPv4 and one IPv6 record. The
+/// other lines of the file are kept as they are.
+#[post("/dns/records", data = "<record>")]
+pub fn add_local_record(
+    env: Inject<PiholeModule, Env>,
+    _auth: User,
+    record: Json<LocalRecord>,
+) -> Reply {
+    let record = record.into_inner();
+    let ip = parse_ip(&record.ip)?;
+    let host = record.host.to_lowercase();
+
+    if !ValueType::Hostname.is_valid(&host) {
+        return Err(Error::from(ErrorKind::InvalidParameter(
+            "host".to_owned(),
+            record.host,
+            Vec::new(),
+        )));
+    }
+
+    update_config(PiholeFile::CustomList, &env, |lines| {
+        let duplicate =
+            lines
+                .iter()
+                .filter_map(|line| parse_line(line))
+                .any(|(existing_ip, hosts)| {
+                    existing_ip.is_ipv4() == ip.is_ipv4()
+                        && hosts
+                            .iter()
+                            .any(|existing| existing.eq_ignore_ascii_case(&host))
+                });
+
+        if duplicate {
+            return Err(Error::from(ErrorKind::AlreadyExists));
+        }
+
+        lines.push(format!("{} {}", ip, host));
+        Ok(())
+    })?;
+    reload_records(&env)?;
+
+    reply_success()
+}
+
+/// Delete the local DNS records of the host. If an IP address is given, only
+/// the record with that address is deleted.
+#[delete("/dns/records/<host>?<ip>")]
+pub fn delete_local_record(
+    env: Inject<PiholeModule, Env>,
+    _auth: User,
+    host: String,
+    ip: Option<String>,
+) -> Reply {
+    let ip = ip.as_deref().map(parse_ip).transpose()?;
+
+    update_config(PiholeFile::CustomList, &env, |lines| {
+        let mut found = false;
+        let mut remaining_lines = Vec::new();
+
+        for line in lines.drain(..) {
+            match remove_host(&line, &host, ip) {
+                Some(remaining) => {
+                    found = true;
+
+                    // Other hosts on the same line keep their record
+                    if let Some(remaining) = remaining {
+                        remaining_lines.push(remaining);
+                    }
+                }
+                None => remaining_lines.push(line),
+            }
+        }
+
+        if !found {
+            return Err(Error::from(ErrorKind::NotFound));
+        }
+
+        *lines = remaining_lines;
+        Ok(())
+    })?;
+    reload_records(&env)?;
+
+    reply_success()
+}
+
+/// Check if the host has a local DNS record
+pub(super) fn has_local_record(env: &Env, host: &str) -> Result<bool, Error> {
+    Ok(read_config(PiholeFile::CustomList, env)?
+        .iter()
+        .filter_map(|line| parse_line(line))
+        .any(|(_, hosts)| {
+            hosts
+                .iter()
+                .any(|existing| existing.eq_ignore_ascii_case(host))
+        }))
+}
+
+/// Parse the IP address of a record
+fn parse_ip(ip: &str) -> Result<IpAddr, Error> {
+    ip.parse().map_err(|_| {
+        Error::from(ErrorKind::InvalidParameter(
+            "ip".to_owned(),
+            ip.to_owned(),
+            Vec::new(),
+        ))
+    })
+}
+
+/// Parse a line of the file into the IP address and its hosts. Blank lines,
+/// comments, and malformed lines are not records.
+fn parse_line(line: &str) -> Option<(IpAddr, Vec<&str>)> {
+    let mut words = line.split_whitespace();
+    let ip = words.next()?.parse().ok()?;
+    let hosts: Vec<&str> = words.collect();
+
+    if hosts.is_empty() {
+        None
+    } else {
+        Some((ip, hosts))
+    }
+}
+
+/// Remove the host from the line, if the line is a record of the host and has
+/// the IP address (if given). The line is returned without the host, or `None`
+/// if no hosts are left on it.
+fn remove_host(line: &str, host: &str, ip: Option<IpAddr>) -> Option<Option<String>> {
+    let (line_ip, hosts) = parse_line(line)?;
+
+    if ip.map_or(false, |ip| ip != line_ip)
+        || !hosts
+            .iter()
+            .any(|existing| existing.eq_ignore_ascii_case(host))
+    {
+        return None;
+    }
+
+    let remaining: Vec<&str> = hosts
+        .into_iter()
+        .filter(|existing| !existing.eq_ignore_ascii_case(host))
+        .collect();
+
+    if remaining.is_empty() {
+        Some(None)
+    } else {
+        Some(Some(format!("{} {}", line_ip, remaining.join(" "))))
+    }
+}
+
+/// Reload the DNS server after the records changed, if configured to, so the
+/// records are used immediately
+fn reload_records(env: &Env) -> Result<(), Error> {
+    if env.config().general.reload_local_records {
+        reload_dns(env)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{env::PiholeFile, testing::TestBuilder};
+    use rocket::http::{Method, Status};
+
+    /// The records are read in order, skipping comments and malformed lines
+    #[test]
+    fn get() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/records")
+            .file(
+                PiholeFile::CustomList,
+                "# Local records\n\
+                 192.168.1.10 nas.lan\n\
+                 fd00::10 nas.lan\n\
+                 not-an-ip printer.lan\n\
+                 192.168.1.20 tv.lan media.lan\n",
+            )
+            .expect_json(json!([
+                { "ip": "192.168.1.10", "host": "nas.lan" },
+                { "ip": "fd00::10", "host": "nas.lan" },
+                { "ip": "192.168.1.20", "host": "tv.lan" },
+                { "ip": "192.168.1.20", "host": "media.lan" }
+            ]))
+            .test();
+    }
+
+    /// A missing file has no records
+    #[test]
+    fn get_missing_file() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/records")
+            .expect_json(json!([]))
+            .test();
+    }
+
+    /// A record is appended, keeping the other lines
+    #[test]
+    fn add() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/records")
+            .method(Method::Post)
+            .file_expect(
+                PiholeFile::CustomList,
+                "# Local records\n\
+                 192.168.1.10 nas.lan\n",
+                "# Local records\n\
+                 192.168.1.10 nas.lan\n\
+                 fd00::10 nas.lan\n",
+            )
+            .body(json!({ "ip": "fd00:0::10", "host": "NAS.lan" }))
+            .expect_json(json!({ "status": "success" }))
+            .test();
+    }
+
+    /// A host can only have one record of each address family
+    #[test]
+    fn add_duplicate() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/records")
+            .method(Method::Post)
+            .file_expect(
+                PiholeFile::CustomList,
+                "192.168.1.20 tv.lan media.lan\n",
+                "192.168.1.20 tv.lan media.lan\n",
+            )
+            .body(json!({ "ip": "192.168.1.30", "host": "media.lan" }))
+            .expect_status(Status::Conflict)
+            .expect_json(json!({
+                "error": {
+                    "key": "already_exists",
+                    "message": "Item already exists",
+                    "data": null
+                }
+            }))
+            .test();
+    }
+
+    /// Invalid IP addresses are rejected
+    #[test]
+    fn add_invalid_ip() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/records")
+            .method(Method::Post)
+            .file_expect(PiholeFile::CustomList, "", "")
+            .body(json!({ "ip": "192.168.1.256", "host": "nas.lan" }))
+            .expect_status(Status::BadRequest)
+            .expect_json(json!({
+                "error": {
+                    "key": "invalid_parameter",
+                    "message": "Invalid value 192.168.1.256 for parameter ip",
+                    "data": {
+                        "parameter": "ip",
+                        "value": "192.168.1.256",
+                        "valid": []
+                    }
+                }
+            }))
+            .test();
+    }
+
+    /// Invalid hostnames are rejected
+    #[test]
+    fn add_invalid_host() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/records")
+            .method(Method::Post)
+            .file_expect(PiholeFile::CustomList, "", "")
+            .body(json!({ "ip": "192.168.1.10", "host": "my nas" }))
+            .expect_status(Status::BadRequest)
+            .expect_json(json!({
+                "error": {
+                    "key": "invalid_parameter",
+                    "message": "Invalid value my nas for parameter host",
+                    "data": {
+                        "parameter": "host",
+                        "value": "my nas",
+                        "valid": []
+                    }
+                }
+            }))
+            .test();
+    }
+
+    /// All records of the host are deleted, and other hosts on the same line
+    /// are kept
+    #[test]
+    fn delete() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/records/nas.lan")
+            .method(Method::Delete)
+            .file_expect(
+                PiholeFile::CustomList,
+                "# Local records\n\
+                 192.168.1.10 nas.lan\n\
+                 fd00::10 nas.lan files.lan\n\
+                 192.168.1.20 tv.lan\n",
+                "# Local records\n\
+                 fd00::10 files.lan\n\
+                 192.168.1.20 tv.lan\n",
+            )
+            .expect_json(json!({ "status": "success" }))
+            .test();
+    }
+
+    /// Only the record with the IP address is deleted
+    #[test]
+    fn delete_ip() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/records/nas.lan?ip=fd00::10")
+            .method(Method::Delete)
+            .file_expect(
+                PiholeFile::CustomList,
+                "192.168.1.10 nas.lan\n\
+                 fd00::10 nas.lan\n",
+                "192.168.1.10 nas.lan\n",
+            )
+            .expect_json(json!({ "status": "success" }))
+            .test();
+    }
+
+    /// Deleting a host without records is an error
+    #[test]
+    fn delete_not_found() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/records/nas.lan?ip=192.168.1.20")
+            .method(Method::Delete)
+            .file_expect(
+                PiholeFile::CustomList,
+                "192.168.1.10 nas.lan\n",
+                "192.168.1.10 nas.lan\n",
+            )
+            .expect_status(Status::NotFound)
+            .expect_json(json!({
+                "error": {
+                    "key": "not_found",
+                    "message": "Not found",
+                    "data": null
+                }
+            }))
+            .test();
+    }
+}
@@ -9,9 +9,29 @@
 // Please see LICENSE file for your rights under this license.
 
 mod add_list;
-mod common;
+mod adlists;
+mod audit;
 mod delete_list;
+mod domain_status;
+mod export_list;
+mod get_entry;
 mod get_list;
+mod gravity;
+mod groups;
+mod import_list;
+mod list_groups;
+mod local_cnames;
+mod local_records;
+mod move_list;
+mod regex_tester;
+mod search_list;
 mod status;
+mod summary_list;
+mod update_list;
 
-pub use self::{add_list::*, delete_list::*, get_list::*, status::*};
+pub use self::{
+    add_list::*, adlists::*, audit::*, delete_list::*, domain_status::*, export_list::*,
+    get_entry::*, get_list::*, gravity::*, groups::*, import_list::*, list_groups::*,
+    local_cnames::*, local_records::*, move_list::*, regex_tester::*, search_list::*, status::*,
+    summary_list::*, update_list::*,
+};
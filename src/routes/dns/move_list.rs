@@ -0,0 +1,130 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Endpoint For Moving Domains Between Lists
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    routes::auth::User,
+    services::{
+        lists::{List, ListService},
+        PiholeModule,
+    },
+    util::{reply_success, Reply},
+};
+use rocket::serde::json::Json;
+use shaku_rocket::InjectProvided;
+
+/// Represents an API input containing a domain and the lists to move it
+/// between. The lists are `white`, `black`, `regex`, or `regexwhite`.
+#[derive(Deserialize)]
+pub struct MoveInput {
+    domain: String,
+    from: List,
+    to: List,
+}
+
+/// Move a domain from one list to another
+#[post("/dns/lists/move", data = "<move_input>")]
+pub fn move_domain(
+    _auth: User,
+    list_service: InjectProvided<PiholeModule, dyn ListService>,
+    move_input: Json<MoveInput>,
+) -> Reply {
+    list_service.move_domain(move_input.from, move_input.to, &move_input.domain)?;
+    reply_success()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        services::lists::{List, ListService, MockListService},
+        testing::{TestBuilder, TestStep},
+        util::{Error, ErrorKind},
+    };
+    use mockall::predicate::*;
+    use rocket::http::{Method, Status};
+    use serde_json::Value;
+
+    /// A successful move returns success
+    #[test]
+    fn move_success() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/lists/move")
+            .method(Method::Post)
+            .mock_provider::<dyn ListService>(Box::new(move |_| {
+                let mut service = MockListService::new();
+
+                service
+                    .expect_move_domain()
+                    .with(eq(List::Black), eq(List::White), eq("example.com"))
+                    .return_const(Ok(()));
+
+                Ok(Box::new(service))
+            }))
+            .body(json!({ "domain": "example.com", "from": "black", "to": "white" }))
+            .expect_json(json!({ "status": "success" }))
+            .test();
+    }
+
+    /// A domain which is already on the destination list is a conflict
+    #[test]
+    fn move_conflict() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/lists/move")
+            .method(Method::Post)
+            .mock_provider::<dyn ListService>(Box::new(move |_| {
+                let mut service = MockListService::new();
+
+                service
+                    .expect_move_domain()
+                    .with(eq(List::White), eq(List::Black), eq("example.com"))
+                    .return_const(Err(Error::from(ErrorKind::AlreadyExists)));
+
+                Ok(Box::new(service))
+            }))
+            .body(json!({ "domain": "example.com", "from": "white", "to": "black" }))
+            .expect_status(Status::Conflict)
+            .expect_json(json!({
+                "error": {
+                    "key": "already_exists",
+                    "message": "Item already exists",
+                    "data": Value::Null
+                }
+            }))
+            .test();
+    }
+
+    /// The domain is moved in the gravity database
+    #[test]
+    fn move_database() {
+        TestBuilder::new()
+            .need_database(true)
+            .step(
+                TestStep::new()
+                    .endpoint("/admin/api/dns/lists/move")
+                    .method(Method::Post)
+                    .body(json!({ "domain": "example.com", "from": "black", "to": "white" }))
+                    .expect_json(json!({ "status": "success" })),
+            )
+            .step(
+                TestStep::new()
+                    .endpoint("/admin/api/dns/blacklist")
+                    .expect_json(json!([])),
+            )
+            .step(
+                TestStep::new()
+                    .endpoint("/admin/api/dns/whitelist")
+                    .ignore_fields(&["*.date_added", "*.date_modified"])
+                    .expect_json(json!([
+                        { "domain": "test.com", "enabled": true, "comment": null },
+                        { "domain": "example.com", "enabled": true, "comment": null }
+                    ])),
+            )
+            .test();
+    }
+}
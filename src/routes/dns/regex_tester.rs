@@ -0,0 +1,153 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Endpoint For Testing Regex Patterns
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    services::lists::{compile_regex, List},
+    util::{reply_data, ErrorKind, Reply},
+};
+use rocket::serde::json::Json;
+
+/// Represents an API input containing a pattern and the domains to test it
+/// against
+#[derive(Deserialize)]
+pub struct RegexTestInput {
+    regex: String,
+    domains: Vec<String>,
+}
+
+/// Whether the pattern matched a domain
+#[derive(Serialize)]
+#[cfg_attr(test, derive(Debug, PartialEq))]
+pub struct RegexTestMatch {
+    pub domain: String,
+    pub matched: bool,
+}
+
+/// Represents the reply structure for testing a pattern. If the pattern does
+/// not compile, `reason` explains why and no domains are tested.
+#[derive(Serialize)]
+#[cfg_attr(test, derive(Debug, PartialEq))]
+pub struct RegexTestReply {
+    pub valid: bool,
+    pub reason: Option<String>,
+    pub matches: Vec<RegexTestMatch>,
+}
+
+/// Test a regex pattern against sample domains, without adding it
+#[post("/dns/regexlist/test", data = "<test_input>")]
+pub fn test_regex(test_input: Json<RegexTestInput>) -> Reply {
+    let test_input = test_input.into_inner();
+    reply_data(test_pattern(&test_input.regex, test_input.domains))
+}
+
+/// Compile the pattern the same way regex list entries are validated, and
+/// match it against the domains in the form FTL would see them
+fn test_pattern(pattern: &str, domains: Vec<String>) -> RegexTestReply {
+    let regex = match compile_regex(pattern) {
+        Ok(regex) => regex,
+        Err(e) => {
+            let reason = match e.kind() {
+                ErrorKind::InvalidRegex(_, reason) => reason,
+                kind => kind.to_string(),
+            };
+
+            return RegexTestReply {
+                valid: false,
+                reason: Some(reason),
+                matches: Vec::new(),
+            };
+        }
+    };
+
+    let matches = domains
+        .into_iter()
+        .map(|domain| {
+            let normalized = List::Black
+                .normalize(&domain.to_lowercase())
+                .unwrap_or_else(|_| domain.clone());
+
+            RegexTestMatch {
+                matched: regex.is_match(&normalized),
+                domain,
+            }
+        })
+        .collect();
+
+    RegexTestReply {
+        valid: true,
+        reason: None,
+        matches,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{test_pattern, RegexTestMatch, RegexTestReply};
+    use crate::testing::TestBuilder;
+    use rocket::http::Method;
+
+    /// Each domain is matched against the pattern
+    #[test]
+    fn matches() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/regexlist/test")
+            .method(Method::Post)
+            .body(json!({
+                "regex": "(^|\\.)example\\.com$",
+                "domains": ["example.com", "ads.example.com", "example.net"]
+            }))
+            .expect_json(json!({
+                "valid": true,
+                "reason": null,
+                "matches": [
+                    { "domain": "example.com", "matched": true },
+                    { "domain": "ads.example.com", "matched": true },
+                    { "domain": "example.net", "matched": false }
+                ]
+            }))
+            .test();
+    }
+
+    /// A pattern which does not compile is reported as invalid
+    #[test]
+    fn invalid_pattern() {
+        let reply = test_pattern("([a-z", vec!["example.com".to_owned()]);
+
+        assert!(!reply.valid);
+        assert!(reply.reason.is_some());
+        assert!(reply.matches.is_empty());
+    }
+
+    /// An empty pattern is invalid, since it would match every domain
+    #[test]
+    fn empty_pattern() {
+        assert_eq!(
+            test_pattern("", vec!["example.com".to_owned()]),
+            RegexTestReply {
+                valid: false,
+                reason: Some("The pattern is empty".to_owned()),
+                matches: Vec::new()
+            }
+        );
+    }
+
+    /// Domains are matched in the form FTL sees them, but are returned as
+    /// given
+    #[test]
+    fn normalized_domains() {
+        assert_eq!(
+            test_pattern("^xn--", vec!["MÜNCHEN.de".to_owned()]).matches,
+            vec![RegexTestMatch {
+                domain: "MÜNCHEN.de".to_owned(),
+                matched: true
+            }]
+        );
+    }
+}
@@ -0,0 +1,289 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Endpoint For Searching Domain Lists
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    services::{
+        lists::{List, ListRepository},
+        PiholeModule,
+    },
+    util::{reply_result, Error, ErrorKind, Reply},
+};
+use regex::Regex;
+use shaku_rocket::InjectProvided;
+
+/// Search the lists
+#[get("/dns/lists/search?<params..>")]
+pub fn search_lists(
+    repo: InjectProvided<PiholeModule, dyn ListRepository>,
+    params: SearchParams,
+) -> Reply {
+    reply_result(get_search_results(&*repo, params))
+}
+
+/// Represents the possible GET parameters on `/dns/lists/search`. Exactly
+/// one of them must be given.
+#[derive(FromForm, Default)]
+pub struct SearchParams {
+    /// Find the entries which apply to this domain
+    pub domain: Option<String>,
+    /// Find the entries which contain this text
+    pub query: Option<String>,
+}
+
+/// How a list entry matched the search
+#[derive(Serialize, Copy, Clone)]
+#[cfg_attr(test, derive(Debug, PartialEq))]
+#[serde(rename_all = "lowercase")]
+pub enum MatchType {
+    /// The entry is the searched domain
+    Exact,
+    /// The entry is a regex which matches the searched domain
+    Regex,
+    /// The entry contains the search query
+    Substring,
+}
+
+/// Represents the reply structure for a matching list entry
+#[derive(Serialize)]
+#[cfg_attr(test, derive(Debug, PartialEq))]
+pub struct ListMatch {
+    pub domain: String,
+    #[serde(rename = "match")]
+    pub match_type: MatchType,
+}
+
+/// Represents the reply structure for searching the lists
+#[derive(Serialize)]
+#[cfg_attr(test, derive(Debug, PartialEq))]
+pub struct SearchReply {
+    pub whitelist: Vec<ListMatch>,
+    pub blacklist: Vec<ListMatch>,
+    pub regexlist: Vec<ListMatch>,
+}
+
+/// Search the lists according to the parameters
+fn get_search_results(
+    repo: &dyn ListRepository,
+    params: SearchParams,
+) -> Result<SearchReply, Error> {
+    match (params.domain, params.query) {
+        (Some(domain), None) => Ok(SearchReply {
+            whitelist: find_exact(repo, List::White, &domain)?,
+            blacklist: find_exact(repo, List::Black, &domain)?,
+            regexlist: find_regex(repo, &domain)?,
+        }),
+        (None, Some(query)) => Ok(SearchReply {
+            whitelist: find_substring(repo, List::White, &query)?,
+            blacklist: find_substring(repo, List::Black, &query)?,
+            regexlist: find_substring(repo, List::Regex, &query)?,
+        }),
+        _ => Err(Error::from(ErrorKind::BadRequest)),
+    }
+}
+
+/// Find the entry on the list which is exactly the domain
+fn find_exact(
+    repo: &dyn ListRepository,
+    list: List,
+    domain: &str,
+) -> Result<Vec<ListMatch>, Error> {
+    if repo.contains(list, domain)? {
+        Ok(vec![ListMatch {
+            domain: domain.to_owned(),
+            match_type: MatchType::Exact,
+        }])
+    } else {
+        Ok(Vec::new())
+    }
+}
+
+/// Find the regex entries which match the domain. Stored patterns which do not
+/// compile are skipped.
+fn find_regex(repo: &dyn ListRepository, domain: &str) -> Result<Vec<ListMatch>, Error> {
+    Ok(repo
+        .get(List::Regex, true)?
+        .into_iter()
+        .filter_map(|entry| {
+            let match_type = if entry.domain == domain {
+                MatchType::Exact
+            } else if Regex::new(&entry.domain)
+                .map(|regex| regex.is_match(domain))
+                .unwrap_or(false)
+            {
+                MatchType::Regex
+            } else {
+                return None;
+            };
+
+            Some(ListMatch {
+                domain: entry.domain,
+                match_type,
+            })
+        })
+        .collect())
+}
+
+/// Find the entries on the list which contain the query
+fn find_substring(
+    repo: &dyn ListRepository,
+    list: List,
+    query: &str,
+) -> Result<Vec<ListMatch>, Error> {
+    Ok(repo
+        .search(list, query)?
+        .into_iter()
+        .map(|entry| ListMatch {
+            domain: entry.domain,
+            match_type: MatchType::Substring,
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{get_search_results, ListMatch, MatchType, SearchParams, SearchReply};
+    use crate::{
+        services::lists::{List, ListEntry, MockListRepository},
+        testing::TestBuilder,
+        util::ErrorKind,
+    };
+    use mockall::predicate::*;
+    use rocket::http::Status;
+    use serde_json::Value;
+
+    /// Create an enabled regex entry
+    fn regex_entry(pattern: &str) -> ListEntry {
+        ListEntry {
+            domain: pattern.to_owned(),
+            enabled: true,
+            comment: None,
+            date_added: 1,
+            date_modified: 1,
+        }
+    }
+
+    /// The domain is found on the lists, and regex entries are evaluated
+    #[test]
+    fn domain_search() {
+        let mut repo = MockListRepository::new();
+
+        repo.expect_contains()
+            .with(eq(List::White), eq("ads.example.com"))
+            .return_const(Ok(false));
+        repo.expect_contains()
+            .with(eq(List::Black), eq("ads.example.com"))
+            .return_const(Ok(true));
+        repo.expect_get()
+            .with(eq(List::Regex), eq(true))
+            .return_const(Ok(vec![
+                regex_entry("(^|\\.)example\\.com$"),
+                regex_entry("^ads\\."),
+                regex_entry("ads.example.com"),
+                regex_entry("example\\.net$"),
+                regex_entry("([a-z"),
+            ]));
+
+        let params = SearchParams {
+            domain: Some("ads.example.com".to_owned()),
+            query: None,
+        };
+
+        assert_eq!(
+            get_search_results(&repo, params).unwrap(),
+            SearchReply {
+                whitelist: Vec::new(),
+                blacklist: vec![ListMatch {
+                    domain: "ads.example.com".to_owned(),
+                    match_type: MatchType::Exact
+                }],
+                regexlist: vec![
+                    ListMatch {
+                        domain: "(^|\\.)example\\.com$".to_owned(),
+                        match_type: MatchType::Regex
+                    },
+                    ListMatch {
+                        domain: "^ads\\.".to_owned(),
+                        match_type: MatchType::Regex
+                    },
+                    ListMatch {
+                        domain: "ads.example.com".to_owned(),
+                        match_type: MatchType::Exact
+                    }
+                ]
+            }
+        );
+    }
+
+    /// Giving both parameters, or neither, is a bad request
+    #[test]
+    fn invalid_params() {
+        let repo = MockListRepository::new();
+
+        let both = SearchParams {
+            domain: Some("example.com".to_owned()),
+            query: Some("example".to_owned()),
+        };
+        assert_eq!(
+            get_search_results(&repo, both).unwrap_err().kind(),
+            ErrorKind::BadRequest
+        );
+        assert_eq!(
+            get_search_results(&repo, SearchParams::default())
+                .unwrap_err()
+                .kind(),
+            ErrorKind::BadRequest
+        );
+    }
+
+    /// Searching for a domain uses the gravity database
+    #[test]
+    fn domain_search_database() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/lists/search?domain=example.com")
+            .need_database(true)
+            .expect_json(json!({
+                "whitelist": [],
+                "blacklist": [{ "domain": "example.com", "match": "exact" }],
+                "regexlist": [{ "domain": "(^|\\.)example\\.com$", "match": "regex" }]
+            }))
+            .test();
+    }
+
+    /// Searching for a query returns the entries containing it
+    #[test]
+    fn query_search_database() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/lists/search?query=disabled")
+            .need_database(true)
+            .expect_json(json!({
+                "whitelist": [{ "domain": "disabled-white.com", "match": "substring" }],
+                "blacklist": [{ "domain": "disabled-black.com", "match": "substring" }],
+                "regexlist": [{ "domain": "disabled\\-regex\\.com", "match": "substring" }]
+            }))
+            .test();
+    }
+
+    /// Missing parameters are rejected
+    #[test]
+    fn missing_params() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/lists/search")
+            .need_database(true)
+            .expect_status(Status::BadRequest)
+            .expect_json(json!({
+                "error": {
+                    "key": "bad_request",
+                    "message": "Bad request",
+                    "data": Value::Null
+                }
+            }))
+            .test();
+    }
+}
@@ -10,20 +10,20 @@
 
 use crate::{
     env::{Env, PiholeFile},
-    routes::dns::common::reload_dns,
-    services::PiholeModule,
-    settings::{ConfigEntry, SetupVarsEntry},
+    services::{restart::reload_dns, settings::SettingsRepository, PiholeModule},
+    settings::SetupVarsEntry,
     util::{reply_data, reply_error, reply_success, Error, ErrorKind, Reply},
 };
 use rocket::{serde::json::Json, State};
+use shaku::HasComponent;
 use shaku_rocket::Inject;
-use std::time::Duration;
+use std::{sync::Arc, time::Duration};
 use task_scheduler::Scheduler;
 
 /// Get the DNS blocking status
 #[get("/dns/status")]
-pub fn get_status(env: Inject<PiholeModule, Env>) -> Reply {
-    let status = if SetupVarsEntry::BlockingEnabled.is_true(&env)? {
+pub fn get_status(settings: Inject<PiholeModule, dyn SettingsRepository>) -> Reply {
+    let status = if settings.get_bool(SetupVarsEntry::BlockingEnabled)? {
         "enabled"
     } else {
         "disabled"
@@ -36,12 +36,17 @@ pub fn get_status(env: Inject<PiholeModule, Env>) -> Reply {
 #[post("/dns/status", data = "<data>")]
 pub fn change_status(
     env: Inject<PiholeModule, Env>,
+    module: &State<Box<PiholeModule>>,
     scheduler: &State<Scheduler>,
     data: Json<ChangeStatus>,
 ) -> Reply {
+    // The repository is resolved as an `Arc`, so it can be moved to the
+    // scheduler thread which re-enables blocking
+    let settings: Arc<dyn SettingsRepository> = module.resolve();
+
     match (data.action.as_str(), data.time) {
-        ("enable", None) => enable(&env)?,
-        ("disable", time) => disable(&env, time, Some(scheduler))?,
+        ("enable", None) => enable(&env, &*settings)?,
+        ("disable", time) => disable(&env, settings, time, Some(scheduler))?,
         _ => return reply_error(ErrorKind::BadRequest),
     }
 
@@ -49,9 +54,9 @@ pub fn change_status(
 }
 
 /// Enable blocking
-fn enable(env: &Env) -> Result<(), Error> {
+fn enable(env: &Env, settings: &dyn SettingsRepository) -> Result<(), Error> {
     // Can't enable blocking when it's already enabled
-    if SetupVarsEntry::BlockingEnabled.is_true(&env)? {
+    if settings.get_bool(SetupVarsEntry::BlockingEnabled)? {
         return Err(Error::from(ErrorKind::BadRequest));
     }
 
@@ -65,16 +70,21 @@ fn enable(env: &Env) -> Result<(), Error> {
     }
 
     // Update the blocking status
-    SetupVarsEntry::BlockingEnabled.write("true", env)?;
+    settings.set(SetupVarsEntry::BlockingEnabled, "true")?;
 
     reload_dns(env)
 }
 
 /// Disable blocking. If the time is `None`, then disable permanently.
 /// Otherwise, re-enable after the specified number of seconds.
-fn disable(env: &Env, time: Option<usize>, scheduler: Option<&Scheduler>) -> Result<(), Error> {
+fn disable(
+    env: &Env,
+    settings: Arc<dyn SettingsRepository>,
+    time: Option<usize>,
+    scheduler: Option<&Scheduler>,
+) -> Result<(), Error> {
     // Can't disable blocking when it's already disabled
-    if !SetupVarsEntry::BlockingEnabled.is_true(&env)? {
+    if !settings.get_bool(SetupVarsEntry::BlockingEnabled)? {
         return Err(Error::from(ErrorKind::BadRequest));
     }
 
@@ -82,19 +92,19 @@ fn disable(env: &Env, time: Option<usize>, scheduler: Option<&Scheduler>) -> Res
     if env.file_exists(PiholeFile::Gravity) {
         env.rename_file(PiholeFile::Gravity, PiholeFile::GravityBackup)?;
 
-        // The file will be created and truncated
-        env.write_file(PiholeFile::Gravity, false)?;
+        // The file will be replaced with an empty file
+        env.write_file_atomic(PiholeFile::Gravity, b"")?;
     }
 
     if env.file_exists(PiholeFile::BlackList) {
         env.rename_file(PiholeFile::BlackList, PiholeFile::BlackListBackup)?;
 
-        // The file will be created and truncated
-        env.write_file(PiholeFile::BlackList, false)?;
+        // The file will be replaced with an empty file
+        env.write_file_atomic(PiholeFile::BlackList, b"")?;
     }
 
     // Update the blocking status
-    SetupVarsEntry::BlockingEnabled.write("false", env)?;
+    settings.set(SetupVarsEntry::BlockingEnabled, "false")?;
 
     reload_dns(env)?;
 
@@ -119,7 +129,7 @@ fn disable(env: &Env, time: Option<usize>, scheduler: Option<&Scheduler>) -> Res
                 .after_duration(Duration::from_secs(time as u64), move || {
                     // Handle the result of enabling, so that if it's an error
                     // the thread does not panic
-                    if let Err(e) = enable(&env_copy) {
+                    if let Err(e) = enable(&env_copy, &*settings) {
                         if e.kind() == ErrorKind::BadRequest {
                             // If it was a bad request, blocking was probably
                             // already re-enabled. This is a fairly common
@@ -152,10 +162,26 @@ mod test {
     use super::{disable, enable};
     use crate::{
         env::PiholeFile,
+        services::settings::MockSettingsRepository,
+        settings::SetupVarsEntry,
         testing::{TestBuilder, TestEnvBuilder},
         util::ErrorKind,
     };
+    use mockall::predicate::*;
     use rocket::http::Method;
+    use std::sync::Arc;
+
+    /// Create a settings repository where blocking is enabled or disabled
+    fn blocking_enabled(enabled: bool) -> MockSettingsRepository {
+        let mut settings = MockSettingsRepository::new();
+
+        settings
+            .expect_get_bool()
+            .with(eq(SetupVarsEntry::BlockingEnabled))
+            .return_once(move |_| Ok(enabled));
+
+        settings
+    }
 
     /// Return enabled status if blocking is enabled
     #[test]
@@ -210,12 +236,10 @@ mod test {
     /// Return an error if blocking is enabled and we try to enable it again
     #[test]
     fn action_enable_error() {
-        let env = TestEnvBuilder::new()
-            .file(PiholeFile::SetupVars, "BLOCKING_ENABLED=true")
-            .build();
+        let env = TestEnvBuilder::new().build();
 
         assert_eq!(
-            enable(&env).map_err(|e| e.kind()),
+            enable(&env, &blocking_enabled(true)).map_err(|e| e.kind()),
             Err(ErrorKind::BadRequest)
         );
     }
@@ -243,12 +267,10 @@ mod test {
     /// Return an error if blocking is disabled and we try to disable it again
     #[test]
     fn action_disable_error() {
-        let env = TestEnvBuilder::new()
-            .file(PiholeFile::SetupVars, "BLOCKING_ENABLED=false")
-            .build();
+        let env = TestEnvBuilder::new().build();
 
         assert_eq!(
-            disable(&env, None, None).map_err(|e| e.kind()),
+            disable(&env, Arc::new(blocking_enabled(false)), None, None).map_err(|e| e.kind()),
             Err(ErrorKind::BadRequest)
         );
     }
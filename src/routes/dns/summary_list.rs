@@ -0,0 +1,83 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Endpoint For Summarizing Domain Lists
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    services::{lists::ListRepository, PiholeModule},
+    util::{reply_result, Reply},
+};
+use shaku_rocket::InjectProvided;
+
+/// Get the number of enabled and disabled entries on each list, and the
+/// number of domains in gravity
+#[get("/dns/lists/summary")]
+pub fn get_lists_summary(repo: InjectProvided<PiholeModule, dyn ListRepository>) -> Reply {
+    reply_result(repo.counts())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        services::lists::{EntryCounts, ListCounts, ListRepository, MockListRepository},
+        testing::TestBuilder,
+    };
+
+    /// The counts are read from the database
+    #[test]
+    fn summary() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/lists/summary")
+            .need_database(true)
+            .expect_json(json!({
+                "whitelist": { "enabled": 1, "disabled": 1 },
+                "blacklist": { "enabled": 1, "disabled": 1 },
+                "regexlist": { "enabled": 1, "disabled": 1 },
+                "regex_whitelist": { "enabled": 1, "disabled": 0 },
+                "gravity": 10
+            }))
+            .test();
+    }
+
+    /// If there is no gravity table, the gravity count is null
+    #[test]
+    fn summary_without_gravity() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/lists/summary")
+            .mock_provider::<dyn ListRepository>(Box::new(move |_| {
+                let mut repo = MockListRepository::new();
+
+                repo.expect_counts().return_const(Ok(ListCounts {
+                    whitelist: EntryCounts {
+                        enabled: 42,
+                        disabled: 0,
+                    },
+                    blacklist: EntryCounts {
+                        enabled: 7,
+                        disabled: 1,
+                    },
+                    regexlist: EntryCounts {
+                        enabled: 13,
+                        disabled: 2,
+                    },
+                    regex_whitelist: EntryCounts::default(),
+                    gravity: None,
+                }));
+
+                Ok(Box::new(repo))
+            }))
+            .expect_json(json!({
+                "whitelist": { "enabled": 42, "disabled": 0 },
+                "blacklist": { "enabled": 7, "disabled": 1 },
+                "regexlist": { "enabled": 13, "disabled": 2 },
+                "regex_whitelist": { "enabled": 0, "disabled": 0 },
+                "gravity": null
+            }))
+            .test();
+    }
+}
@@ -0,0 +1,276 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Endpoints For Updating Domains On Lists
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    routes::auth::User,
+    services::{
+        lists::{EntryUpdate, List, ListService},
+        PiholeModule,
+    },
+    util::{reply_success, Reply},
+};
+use rocket::serde::json::Json;
+use shaku_rocket::InjectProvided;
+
+/// Represents an API input containing the new state of a domain
+#[derive(Deserialize)]
+pub struct DomainUpdateInput {
+    enabled: bool,
+}
+
+/// Update a domain on the whitelist
+#[put("/dns/whitelist/<domain>", data = "<update_input>")]
+pub fn update_whitelist(
+    _auth: User,
+    list_service: InjectProvided<PiholeModule, dyn ListService>,
+    domain: String,
+    update_input: Json<DomainUpdateInput>,
+) -> Reply {
+    list_service.set_enabled(List::White, &domain, update_input.enabled)?;
+    reply_success()
+}
+
+/// Update a domain on the blacklist
+#[put("/dns/blacklist/<domain>", data = "<update_input>")]
+pub fn update_blacklist(
+    _auth: User,
+    list_service: InjectProvided<PiholeModule, dyn ListService>,
+    domain: String,
+    update_input: Json<DomainUpdateInput>,
+) -> Reply {
+    list_service.set_enabled(List::Black, &domain, update_input.enabled)?;
+    reply_success()
+}
+
+/// Update a domain on the regex list
+#[put("/dns/regexlist/<domain>", data = "<update_input>")]
+pub fn update_regexlist(
+    _auth: User,
+    list_service: InjectProvided<PiholeModule, dyn ListService>,
+    domain: String,
+    update_input: Json<DomainUpdateInput>,
+) -> Reply {
+    list_service.set_enabled(List::Regex, &domain, update_input.enabled)?;
+    reply_success()
+}
+
+/// Edit an entry on the whitelist
+#[patch("/dns/whitelist/<domain>", data = "<changes>")]
+pub fn edit_whitelist(
+    _auth: User,
+    list_service: InjectProvided<PiholeModule, dyn ListService>,
+    domain: String,
+    changes: Json<EntryUpdate>,
+) -> Reply {
+    list_service.update(List::White, &domain, changes.into_inner())?;
+    reply_success()
+}
+
+/// Edit an entry on the blacklist
+#[patch("/dns/blacklist/<domain>", data = "<changes>")]
+pub fn edit_blacklist(
+    _auth: User,
+    list_service: InjectProvided<PiholeModule, dyn ListService>,
+    domain: String,
+    changes: Json<EntryUpdate>,
+) -> Reply {
+    list_service.update(List::Black, &domain, changes.into_inner())?;
+    reply_success()
+}
+
+/// Edit an entry on the regex list
+#[patch("/dns/regexlist/<domain>", data = "<changes>")]
+pub fn edit_regexlist(
+    _auth: User,
+    list_service: InjectProvided<PiholeModule, dyn ListService>,
+    domain: String,
+    changes: Json<EntryUpdate>,
+) -> Reply {
+    list_service.update(List::Regex, &domain, changes.into_inner())?;
+    reply_success()
+}
+
+/// Edit an entry on the regex whitelist
+#[patch("/dns/regexWhitelist/<domain>", data = "<changes>")]
+pub fn edit_regex_whitelist(
+    _auth: User,
+    list_service: InjectProvided<PiholeModule, dyn ListService>,
+    domain: String,
+    changes: Json<EntryUpdate>,
+) -> Reply {
+    list_service.update(List::RegexWhite, &domain, changes.into_inner())?;
+    reply_success()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        services::lists::{EntryUpdate, List, ListService, MockListService},
+        testing::TestBuilder,
+        util::{Error, ErrorKind},
+    };
+    use mockall::predicate::*;
+    use rocket::http::{Method, Status};
+    use serde_json::Value;
+
+    /// Test that a successful update returns success
+    fn update_test(list: List, endpoint: &str, domain: &'static str, enabled: bool) {
+        TestBuilder::new()
+            .endpoint(endpoint)
+            .method(Method::Put)
+            .mock_provider::<dyn ListService>(Box::new(move |_| {
+                let mut service = MockListService::new();
+
+                service
+                    .expect_set_enabled()
+                    .with(eq(list), eq(domain), eq(enabled))
+                    .return_const(Ok(()));
+
+                Ok(Box::new(service))
+            }))
+            .body(json!({ "enabled": enabled }))
+            .expect_json(json!({ "status": "success" }))
+            .test();
+    }
+
+    #[test]
+    fn update_whitelist() {
+        update_test(
+            List::White,
+            "/admin/api/dns/whitelist/example.com",
+            "example.com",
+            false,
+        );
+    }
+
+    #[test]
+    fn update_blacklist() {
+        update_test(
+            List::Black,
+            "/admin/api/dns/blacklist/example.com",
+            "example.com",
+            true,
+        );
+    }
+
+    #[test]
+    fn update_regexlist() {
+        update_test(
+            List::Regex,
+            "/admin/api/dns/regexlist/%5E.%2Aexample.com%24",
+            "^.*example.com$",
+            false,
+        );
+    }
+
+    /// Updating a domain which is not on the list is a not found error
+    #[test]
+    fn update_not_found() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/blacklist/example.com")
+            .method(Method::Put)
+            .mock_provider::<dyn ListService>(Box::new(move |_| {
+                let mut service = MockListService::new();
+
+                service
+                    .expect_set_enabled()
+                    .with(eq(List::Black), eq("example.com"), eq(false))
+                    .return_const(Err(Error::from(ErrorKind::NotFound)));
+
+                Ok(Box::new(service))
+            }))
+            .body(json!({ "enabled": false }))
+            .expect_status(Status::NotFound)
+            .expect_json(json!({
+                "error": {
+                    "key": "not_found",
+                    "message": "Not found",
+                    "data": Value::Null
+                }
+            }))
+            .test();
+    }
+
+    /// Only the given fields are changed when editing an entry
+    #[test]
+    fn edit_regexlist() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/regexlist/%5Eads%5C.")
+            .method(Method::Patch)
+            .mock_provider::<dyn ListService>(Box::new(move |_| {
+                let mut service = MockListService::new();
+
+                service
+                    .expect_update()
+                    .with(
+                        eq(List::Regex),
+                        eq("^ads\\."),
+                        eq(EntryUpdate {
+                            domain: Some("^ads?\\.".to_owned()),
+                            comment: None,
+                            enabled: None,
+                        }),
+                    )
+                    .return_const(Ok(()));
+
+                Ok(Box::new(service))
+            }))
+            .body(json!({ "domain": "^ads?\\." }))
+            .expect_json(json!({ "status": "success" }))
+            .test();
+    }
+
+    /// Renaming an entry to a domain which is already on the list is a
+    /// conflict
+    #[test]
+    fn edit_conflict() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/whitelist/test.com")
+            .method(Method::Patch)
+            .mock_provider::<dyn ListService>(Box::new(move |_| {
+                let mut service = MockListService::new();
+
+                service
+                    .expect_update()
+                    .with(
+                        eq(List::White),
+                        eq("test.com"),
+                        eq(EntryUpdate {
+                            domain: Some("example.com".to_owned()),
+                            comment: Some("Typo".to_owned()),
+                            enabled: Some(true),
+                        }),
+                    )
+                    .return_const(Err(Error::from(ErrorKind::DomainAlreadyExists(
+                        "example.com".to_owned(),
+                        "whitelist".to_owned(),
+                    ))));
+
+                Ok(Box::new(service))
+            }))
+            .body(json!({
+                "domain": "example.com",
+                "comment": "Typo",
+                "enabled": true
+            }))
+            .expect_status(Status::Conflict)
+            .expect_json(json!({
+                "error": {
+                    "key": "already_exists",
+                    "message": "example.com is already on the whitelist",
+                    "data": {
+                        "domain": "example.com",
+                        "list": "whitelist"
+                    }
+                }
+            }))
+            .test();
+    }
+}
@@ -10,6 +10,7 @@
 
 pub mod auth;
 pub mod dns;
+pub mod network;
 pub mod settings;
 pub mod stats;
 pub mod version;
@@ -0,0 +1,201 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Network Devices Endpoint
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    databases::ftl::{FtlDatabase, FtlDbNetworkDevice},
+    routes::auth::User,
+    services::{mac_vendors::MacVendors, privacy::PrivacyService, PiholeModule},
+    settings::FtlPrivacyLevel,
+    util::{reply_result, Error, ErrorKind, Reply},
+};
+use diesel::{prelude::*, SqliteConnection};
+use failure::ResultExt;
+use shaku_rocket::{Inject, InjectProvided};
+
+/// Get the devices which FTL has seen on the local network, along with the
+/// vendors of their MAC addresses
+#[get("/network/devices")]
+pub fn get_devices(
+    _auth: User,
+    privacy: Inject<PiholeModule, dyn PrivacyService>,
+    vendors: Inject<PiholeModule, MacVendors>,
+    db: InjectProvided<PiholeModule, FtlDatabase>,
+) -> Reply {
+    reply_result(get_network_devices(&db, privacy.privacy_level()?, &vendors))
+}
+
+/// Represents the reply structure for the network devices
+#[derive(Serialize)]
+#[cfg_attr(test, derive(Debug, PartialEq))]
+pub struct NetworkDevicesReply {
+    pub devices: Vec<NetworkDevice>,
+}
+
+/// Represents a device in the FTL network table
+#[derive(Serialize)]
+#[cfg_attr(test, derive(Debug, PartialEq))]
+pub struct NetworkDevice {
+    pub id: i32,
+    pub ip: String,
+    pub hwaddr: String,
+    pub interface: String,
+    pub name: Option<String>,
+    pub first_seen: u64,
+    pub last_query: u64,
+    pub num_queries: usize,
+    pub vendor: Option<String>,
+}
+
+/// Load the devices from the FTL database. The devices are the clients of
+/// the network, so none are shown if the privacy level hides clients.
+fn get_network_devices(
+    db: &FtlDatabase,
+    privacy_level: FtlPrivacyLevel,
+    vendors: &MacVendors,
+) -> Result<NetworkDevicesReply, Error> {
+    use crate::databases::ftl::network::dsl::*;
+
+    // Check if client details are private
+    if !privacy_level.allows(FtlPrivacyLevel::HideDomainsAndClients) {
+        return Ok(NetworkDevicesReply {
+            devices: Vec::new(),
+        });
+    }
+
+    // FTL's vendor column is not used, since vendors are looked up by the API
+    let devices: Vec<FtlDbNetworkDevice> = network
+        .select((
+            id, ip, hwaddr, interface, name, firstSeen, lastQuery, numQueries,
+        ))
+        .order(id)
+        .load(db as &SqliteConnection)
+        .context(ErrorKind::FtlDatabase)?;
+
+    Ok(NetworkDevicesReply {
+        devices: devices
+            .into_iter()
+            .map(|device| NetworkDevice {
+                vendor: vendors.lookup(&device.hwaddr).map(str::to_owned),
+                id: device.id,
+                ip: device.ip,
+                hwaddr: device.hwaddr,
+                interface: device.interface,
+                name: device.name,
+                first_seen: device.first_seen as u64,
+                last_query: device.last_query as u64,
+                num_queries: device.num_queries as usize,
+            })
+            .collect(),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::get_network_devices;
+    use crate::{
+        databases::ftl::{connect_to_ftl_test_db, FtlDatabase},
+        env::PiholeFile,
+        services::mac_vendors::MacVendors,
+        settings::FtlPrivacyLevel,
+        testing::TestBuilder,
+    };
+    use diesel::{insert_into, prelude::*, SqliteConnection};
+
+    /// The test database, with a Raspberry Pi and a device using a randomized
+    /// MAC address added
+    fn test_db() -> FtlDatabase {
+        use crate::databases::ftl::network::dsl::*;
+
+        let db = connect_to_ftl_test_db();
+        insert_into(network)
+            .values(&vec![
+                (
+                    id.eq(2),
+                    ip.eq("10.1.1.2"),
+                    hwaddr.eq("b8:27:eb:01:02:03"),
+                    interface.eq("eth0"),
+                    firstSeen.eq(1_546_832_160),
+                    lastQuery.eq(1_547_002_023),
+                    numQueries.eq(20),
+                ),
+                (
+                    id.eq(3),
+                    ip.eq("10.1.1.3"),
+                    hwaddr.eq("ba:27:eb:01:02:03"),
+                    interface.eq("eth0"),
+                    firstSeen.eq(1_546_832_160),
+                    lastQuery.eq(1_547_002_023),
+                    numQueries.eq(5),
+                ),
+            ])
+            .execute(&db as &SqliteConnection)
+            .unwrap();
+
+        db
+    }
+
+    /// The vendors are found by MAC address, but not for locally administered
+    /// addresses
+    #[test]
+    fn vendors() {
+        let vendors = MacVendors::parse("B827EB\tRaspberry Pi Foundation\n");
+        let reply = get_network_devices(&test_db(), FtlPrivacyLevel::ShowAll, &vendors).unwrap();
+        let device_vendors: Vec<(&str, Option<&str>)> = reply
+            .devices
+            .iter()
+            .map(|device| (device.ip.as_str(), device.vendor.as_deref()))
+            .collect();
+
+        assert_eq!(
+            device_vendors,
+            vec![
+                ("10.1.1.1", None),
+                ("10.1.1.2", Some("Raspberry Pi Foundation")),
+                ("10.1.1.3", None)
+            ]
+        );
+    }
+
+    /// No devices are shown if the privacy level hides clients
+    #[test]
+    fn private() {
+        let vendors = MacVendors::default();
+        let reply =
+            get_network_devices(&test_db(), FtlPrivacyLevel::HideDomainsAndClients, &vendors)
+                .unwrap();
+
+        assert!(reply.devices.is_empty());
+    }
+
+    /// The devices are returned with their vendor
+    #[test]
+    fn endpoint() {
+        TestBuilder::new()
+            .endpoint("/admin/api/network/devices")
+            .need_database(true)
+            .file(PiholeFile::FtlConfig, "")
+            .expect_json(json!({
+                "devices": [
+                    {
+                        "id": 1,
+                        "ip": "10.1.1.1",
+                        "hwaddr": "00:00:00:00:00:00",
+                        "interface": "eth0",
+                        "name": "gateway",
+                        "first_seen": 1_546_832_160,
+                        "last_query": 1_547_002_023,
+                        "num_queries": 6,
+                        "vendor": null
+                    }
+                ]
+            }))
+            .test();
+    }
+}
@@ -10,13 +10,53 @@
 
 use crate::{
     env::Env,
-    util::{Error, ErrorKind},
+    services::restart::RestartService,
+    util::{reply_data, reply_success, Error, ErrorKind, Reply},
 };
 use failure::ResultExt;
 use std::process::{Command, Stdio};
 
-/// Restart the DNS server (via `pihole restartdns`)
-pub fn restart_dns(env: &Env) -> Result<(), Error> {
+/// Reply to a settings update. If the client asked for the settings to be
+/// applied, the DNS server is restarted and the reply reports if the restart
+/// was attempted and if it succeeded. The restart is skipped if nothing
+/// changed or if the API is not allowed to restart the DNS server, and
+/// `skipped` gives the reason (`unchanged` or `disabled`). It is null when the
+/// restart was attempted. The settings have already been written at this
+/// point, so a failed restart does not fail the request.
+pub fn reply_applied(restart: &dyn RestartService, apply: bool, changed: bool) -> Reply {
+    if !apply {
+        return reply_success();
+    }
+
+    let skipped = if !changed {
+        Some("unchanged")
+    } else if !restart.is_enabled() {
+        Some("disabled")
+    } else {
+        None
+    };
+    let attempted = skipped.is_none();
+    let succeeded = attempted
+        && match restart.restart_dns() {
+            Ok(()) => true,
+            Err(e) => {
+                e.print_stacktrace();
+                false
+            }
+        };
+
+    reply_data(json!({
+        "status": "success",
+        "restart": {
+            "attempted": attempted,
+            "succeeded": succeeded,
+            "skipped": skipped
+        }
+    }))
+}
+
+/// Flush the query log (via `pihole -f`)
+pub fn flush_log(env: &Env) -> Result<(), Error> {
     // Don't actually run anything during a test
     if env.is_test() {
         return Ok(());
@@ -24,16 +64,16 @@ pub fn restart_dns(env: &Env) -> Result<(), Error> {
 
     let status = Command::new("sudo")
         .arg("pihole")
-        .arg("restartdns")
+        .arg("-f")
         .stdin(Stdio::null())
         .stdout(Stdio::null())
         .stderr(Stdio::null())
         .status()
-        .context(ErrorKind::RestartDnsError)?;
+        .context(ErrorKind::FlushLogError)?;
 
     if status.success() {
         Ok(())
     } else {
-        Err(Error::from(ErrorKind::RestartDnsError))
+        Err(Error::from(ErrorKind::FlushLogError))
     }
 }
@@ -0,0 +1,335 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Conditional Forwarding Settings
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    routes::{auth::User, settings::common::reply_applied},
+    services::{
+        dnsmasq::DnsmasqConfigGenerator, restart::RestartService, settings::SettingsRepository,
+        PiholeModule,
+    },
+    settings::{network_contains, parse_network, ConfigEntry, SetupVarsEntry},
+    util::{reply_data, Error, ErrorKind, Reply},
+};
+use rocket::serde::json::Json;
+use shaku_rocket::Inject;
+use std::net::IpAddr;
+
+/// The conditional forwarding settings. Queries for the local domain, and
+/// reverse lookups of addresses in the network, are forwarded to the router.
+#[derive(Serialize, Deserialize)]
+pub struct ConditionalForwarding {
+    enabled: bool,
+    /// The network in CIDR notation, such as `192.168.1.0/24`
+    cidr: String,
+    /// The IP address of the router
+    ip: String,
+    /// The local domain, which is optional
+    domain: String,
+    /// Deprecated in favor of the `apply` parameter. If the DNS server should
+    /// be restarted to use the new settings. This is only used when updating
+    /// the settings, and `apply` takes precedence if it is given.
+    #[serde(default, skip_serializing)]
+    restart: bool,
+}
+
+impl ConditionalForwarding {
+    /// Check the settings, and return an error naming the first invalid
+    /// field. The network and router are required when conditional forwarding
+    /// is enabled, and the router must be in the network.
+    fn validate(&self) -> Result<(), Error> {
+        let network = if self.cidr.is_empty() && !self.enabled {
+            None
+        } else {
+            Some(parse_network(&self.cidr).ok_or_else(|| invalid_field("cidr", &self.cidr))?)
+        };
+
+        if !self.ip.is_empty() || self.enabled {
+            let ip: IpAddr = match self.ip.parse() {
+                Ok(ip) if SetupVarsEntry::RevServerTarget.is_valid(&self.ip) => ip,
+                _ => return Err(invalid_field("ip", &self.ip)),
+            };
+
+            if let Some(network) = network {
+                if !network_contains(network, ip) {
+                    return Err(invalid_field("ip", &self.ip));
+                }
+            }
+        }
+
+        if !self.domain.is_empty() && !SetupVarsEntry::RevServerDomain.is_valid(&self.domain) {
+            return Err(invalid_field("domain", &self.domain));
+        }
+
+        Ok(())
+    }
+}
+
+/// Create the error for an invalid conditional forwarding field
+fn invalid_field(field: &str, value: &str) -> Error {
+    Error::from(ErrorKind::InvalidParameter(
+        field.to_owned(),
+        value.to_owned(),
+        Vec::new(),
+    ))
+}
+
+/// Get the conditional forwarding settings
+#[get("/settings/dns/conditional_forwarding")]
+pub fn get_conditional_forwarding(
+    repository: Inject<PiholeModule, dyn SettingsRepository>,
+    _auth: User,
+) -> Reply {
+    reply_data(ConditionalForwarding {
+        enabled: repository.get_bool(SetupVarsEntry::RevServer)?,
+        cidr: repository.get(SetupVarsEntry::RevServerCidr)?,
+        ip: repository.get(SetupVarsEntry::RevServerTarget)?,
+        domain: repository.get(SetupVarsEntry::RevServerDomain)?,
+        restart: false,
+    })
+}
+
+/// Update the conditional forwarding settings and regenerate the dnsmasq
+/// config. All of the settings are checked before any of them are written,
+/// so invalid settings leave SetupVars and the dnsmasq config unchanged. The
+/// DNS server is only restarted if `apply` is set, or if the deprecated
+/// `restart` flag is set in the body without `apply`.
+#[put("/settings/dns/conditional_forwarding?<apply>", data = "<data>")]
+pub fn put_conditional_forwarding(
+    repository: Inject<PiholeModule, dyn SettingsRepository>,
+    dnsmasq: Inject<PiholeModule, dyn DnsmasqConfigGenerator>,
+    restart: Inject<PiholeModule, dyn RestartService>,
+    _auth: User,
+    apply: Option<bool>,
+    data: Json<ConditionalForwarding>,
+) -> Reply {
+    let settings = data.into_inner();
+    settings.validate()?;
+
+    repository.set(SetupVarsEntry::RevServer, &settings.enabled.to_string())?;
+    repository.set(SetupVarsEntry::RevServerCidr, &settings.cidr)?;
+    repository.set(SetupVarsEntry::RevServerTarget, &settings.ip)?;
+    repository.set(SetupVarsEntry::RevServerDomain, &settings.domain)?;
+
+    dnsmasq.generate()?;
+    reply_applied(&*restart, apply.unwrap_or(settings.restart), true)
+}
+
+#[cfg(test)]
+mod test {
+    use super::ConditionalForwarding;
+    use crate::{
+        env::PiholeFile,
+        services::{
+            dnsmasq::{DnsmasqConfigGenerator, MockDnsmasqConfigGenerator},
+            restart::{MockRestartService, RestartService},
+        },
+        testing::TestBuilder,
+        util::ErrorKind,
+    };
+    use rocket::http::{Method, Status};
+
+    fn settings(enabled: bool, cidr: &str, ip: &str, domain: &str) -> ConditionalForwarding {
+        ConditionalForwarding {
+            enabled,
+            cidr: cidr.to_owned(),
+            ip: ip.to_owned(),
+            domain: domain.to_owned(),
+            restart: false,
+        }
+    }
+
+    /// Get the parameter name of the validation error
+    fn invalid_field(settings: ConditionalForwarding) -> String {
+        match settings.validate().unwrap_err().kind() {
+            ErrorKind::InvalidParameter(field, _, _) => field,
+            kind => panic!("Unexpected error {:?}", kind),
+        }
+    }
+
+    /// The settings are read from the REV_SERVER entries
+    #[test]
+    fn get() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/dns/conditional_forwarding")
+            .file(
+                PiholeFile::SetupVars,
+                "REV_SERVER=true\n\
+                 REV_SERVER_CIDR=192.168.1.0/24\n\
+                 REV_SERVER_TARGET=192.168.1.1\n\
+                 REV_SERVER_DOMAIN=lan\n",
+            )
+            .expect_json(json!({
+                "enabled": true,
+                "cidr": "192.168.1.0/24",
+                "ip": "192.168.1.1",
+                "domain": "lan"
+            }))
+            .test();
+    }
+
+    /// Valid settings pass, including disabled settings without a network
+    #[test]
+    fn valid() {
+        assert!(settings(true, "192.168.1.0/24", "192.168.1.1", "lan")
+            .validate()
+            .is_ok());
+        assert!(settings(true, "fd00::/8", "fd00::1", "").validate().is_ok());
+        assert!(settings(false, "", "", "").validate().is_ok());
+    }
+
+    /// Each invalid field is named in the error
+    #[test]
+    fn invalid_fields() {
+        assert_eq!(
+            invalid_field(settings(true, "192.168.1.0", "192.168.1.1", "lan")),
+            "cidr"
+        );
+        assert_eq!(
+            invalid_field(settings(true, "192.168.1.0/24", "192.168.2.1", "lan")),
+            "ip"
+        );
+        assert_eq!(
+            invalid_field(settings(true, "192.168.1.0/24", "fd00::1", "lan")),
+            "ip"
+        );
+        assert_eq!(
+            invalid_field(settings(true, "192.168.1.0/24", "", "lan")),
+            "ip"
+        );
+        assert_eq!(
+            invalid_field(settings(true, "192.168.1.0/24", "192.168.1.1", "my lan")),
+            "domain"
+        );
+    }
+
+    /// SetupVars and the dnsmasq config are both updated
+    #[test]
+    fn put() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/dns/conditional_forwarding")
+            .method(Method::Put)
+            .file_expect(
+                PiholeFile::SetupVars,
+                "PIHOLE_DNS_1=8.8.8.8\n\
+                 REV_SERVER=false\n",
+                "PIHOLE_DNS_1=8.8.8.8\n\
+                 REV_SERVER=true\n\
+                 REV_SERVER_CIDR=192.168.1.0/24\n\
+                 REV_SERVER_TARGET=192.168.1.1\n\
+                 REV_SERVER_DOMAIN=lan\n",
+            )
+            .file_expect(
+                PiholeFile::DnsmasqConfig,
+                "",
+                "################################################################\n\
+                    #       THIS FILE IS AUTOMATICALLY GENERATED BY PI-HOLE.       #\n\
+                    #          ANY CHANGES MADE TO THIS FILE WILL BE LOST.         #\n\
+                    #                                                              #\n\
+                    #  NEW CONFIG SETTINGS MUST BE MADE IN A SEPARATE CONFIG FILE  #\n\
+                    #                OR IN /etc/dnsmasq.conf                       #\n\
+                    ################################################################\n\
+                    \n\
+                    localise-queries\n\
+                    local-ttl=2\n\
+                    cache-size=10000\n\
+                    server=8.8.8.8\n\
+                    addn-hosts=/etc/pihole/gravity.list\n\
+                    addn-hosts=/etc/pihole/black.list\n\
+                    addn-hosts=/etc/pihole/local.list\n\
+                    domain-needed\n\
+                    bogus-priv\n\
+                    local-service\n\
+                    server=/lan/192.168.1.1\n\
+                    rev-server=192.168.1.0/24,192.168.1.1\n",
+            )
+            .body(json!({
+                "enabled": true,
+                "cidr": "192.168.1.0/24",
+                "ip": "192.168.1.1",
+                "domain": "lan"
+            }))
+            .expect_json(json!({
+                "status": "success"
+            }))
+            .test();
+    }
+
+    /// The deprecated `restart` flag in the body still applies the settings
+    #[test]
+    fn put_restart_flag() {
+        let mut dnsmasq = MockDnsmasqConfigGenerator::new();
+        dnsmasq.expect_generate().times(1).returning(|| Ok(()));
+        let mut restart = MockRestartService::new();
+        restart.expect_is_enabled().return_const(true);
+        restart.expect_restart_dns().times(1).returning(|| Ok(()));
+
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/dns/conditional_forwarding")
+            .method(Method::Put)
+            .file_expect(
+                PiholeFile::SetupVars,
+                "REV_SERVER=false\n",
+                "REV_SERVER=true\n\
+                 REV_SERVER_CIDR=192.168.1.0/24\n\
+                 REV_SERVER_TARGET=192.168.1.1\n\
+                 REV_SERVER_DOMAIN=lan\n",
+            )
+            .mock_component::<dyn DnsmasqConfigGenerator>(Box::new(dnsmasq))
+            .mock_component::<dyn RestartService>(Box::new(restart))
+            .body(json!({
+                "enabled": true,
+                "cidr": "192.168.1.0/24",
+                "ip": "192.168.1.1",
+                "domain": "lan",
+                "restart": true
+            }))
+            .expect_json(json!({
+                "status": "success",
+                "restart": {
+                    "attempted": true,
+                    "succeeded": true,
+                    "skipped": null
+                }
+            }))
+            .test();
+    }
+
+    /// Invalid settings are rejected without changing SetupVars
+    #[test]
+    fn put_invalid() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/dns/conditional_forwarding")
+            .method(Method::Put)
+            .file_expect(
+                PiholeFile::SetupVars,
+                "REV_SERVER=false\n",
+                "REV_SERVER=false\n",
+            )
+            .body(json!({
+                "enabled": true,
+                "cidr": "192.168.1.0/24",
+                "ip": "10.0.0.1",
+                "domain": "lan"
+            }))
+            .expect_status(Status::BadRequest)
+            .expect_json(json!({
+                "error": {
+                    "key": "invalid_parameter",
+                    "message": "Invalid value 10.0.0.1 for parameter ip",
+                    "data": {
+                        "parameter": "ip",
+                        "value": "10.0.0.1",
+                        "valid": []
+                    }
+                }
+            }))
+            .test();
+    }
+}
@@ -9,14 +9,21 @@
 // Please see LICENSE file for your rights under this license.
 
 use crate::{
-    env::Env,
-    routes::{auth::User, settings::common::restart_dns},
-    services::PiholeModule,
-    settings::{generate_dnsmasq_config, ConfigEntry, SetupVarsEntry},
-    util::{reply_data, reply_success, Error, ErrorKind, Reply},
+    routes::{auth::User, settings::common::reply_applied},
+    services::{
+        dnsmasq::DnsmasqConfigGenerator, restart::RestartService, settings::SettingsRepository,
+        PiholeModule,
+    },
+    settings::{network_contains, parse_network, ConfigEntry, SetupVarsEntry},
+    util::{reply_data, Error, ErrorKind, Reply},
 };
 use rocket::serde::json::Json;
 use shaku_rocket::Inject;
+use std::net::{IpAddr, Ipv4Addr};
+
+/// The prefix length of the DHCP subnet, if the Pi-hole's IPv4 address does
+/// not have one
+const DEFAULT_DHCP_PREFIX: u32 = 24;
 
 #[derive(Serialize, Deserialize)]
 pub struct DhcpSettings {
@@ -24,15 +31,54 @@ pub struct DhcpSettings {
     ip_start: String,
     ip_end: String,
     router_ip: String,
-    lease_time: usize,
+    lease_time: LeaseTime,
     domain: String,
     ipv6_support: bool,
     rapid_commit: bool,
 }
 
+/// The DHCP lease time, in hours or `"infinite"`. An infinite lease time is
+/// stored as zero hours.
+#[derive(Serialize, Deserialize, Copy, Clone)]
+#[cfg_attr(test, derive(Debug, PartialEq))]
+#[serde(untagged)]
+pub enum LeaseTime {
+    Hours(usize),
+    Infinite(InfiniteLease),
+}
+
+/// The `"infinite"` lease time
+#[derive(Serialize, Deserialize, Copy, Clone)]
+#[cfg_attr(test, derive(Debug, PartialEq))]
+#[serde(rename_all = "lowercase")]
+pub enum InfiniteLease {
+    Infinite,
+}
+
+impl LeaseTime {
+    /// Get the lease time from the hours stored in SetupVars
+    fn from_hours(hours: usize) -> Self {
+        if hours == 0 {
+            LeaseTime::Infinite(InfiniteLease::Infinite)
+        } else {
+            LeaseTime::Hours(hours)
+        }
+    }
+
+    /// Get the hours to store in SetupVars
+    fn hours(self) -> usize {
+        match self {
+            LeaseTime::Hours(hours) => hours,
+            LeaseTime::Infinite(_) => 0,
+        }
+    }
+}
+
 impl DhcpSettings {
-    /// Check if all settings are valid
-    fn is_valid(&self) -> bool {
+    /// Check if all settings are valid. The range must not be backwards, and
+    /// the range and router must be in the same subnet, which has the prefix
+    /// length of the Pi-hole's IPv4 address.
+    fn is_valid(&self, prefix: u32) -> bool {
         // If DHCP is to be turned on, no settings may be empty
         if self.active
             && (self.ip_start.is_empty()
@@ -47,53 +93,95 @@ impl DhcpSettings {
             && SetupVarsEntry::DhcpEnd.is_valid(&self.ip_end)
             && SetupVarsEntry::DhcpRouter.is_valid(&self.router_ip)
             && SetupVarsEntry::PiholeDomain.is_valid(&self.domain)
+            && self.is_range_valid(prefix)
     }
+
+    /// Check that the range is in order and in the subnet of the router. The
+    /// range is not checked if any of the addresses are missing.
+    fn is_range_valid(&self, prefix: u32) -> bool {
+        let parse = |ip: &str| ip.parse::<Ipv4Addr>().ok();
+        let (start, end, router) = match (
+            parse(&self.ip_start),
+            parse(&self.ip_end),
+            parse(&self.router_ip),
+        ) {
+            (Some(start), Some(end), Some(router)) => (start, end, router),
+            _ => return true,
+        };
+        let subnet = (IpAddr::V4(router), prefix);
+
+        start <= end
+            && network_contains(subnet, IpAddr::V4(start))
+            && network_contains(subnet, IpAddr::V4(end))
+    }
+}
+
+/// Get the prefix length of the DHCP subnet from the Pi-hole's IPv4 address,
+/// such as 24 for `192.168.1.10/24`
+fn get_dhcp_prefix(repository: &dyn SettingsRepository) -> Result<u32, Error> {
+    Ok(parse_network(&repository.get(SetupVarsEntry::Ipv4Address)?)
+        .map(|(_, prefix)| prefix)
+        .unwrap_or(DEFAULT_DHCP_PREFIX))
 }
 
 /// Get DHCP Configuration
 #[get("/settings/dhcp")]
-pub fn get_dhcp(env: Inject<PiholeModule, Env>, _auth: User) -> Reply {
+pub fn get_dhcp(repository: Inject<PiholeModule, dyn SettingsRepository>, _auth: User) -> Reply {
     let dhcp_settings = DhcpSettings {
-        active: SetupVarsEntry::DhcpActive.is_true(&env)?,
-        ip_start: SetupVarsEntry::DhcpStart.read(&env)?,
-        ip_end: SetupVarsEntry::DhcpEnd.read(&env)?,
-        router_ip: SetupVarsEntry::DhcpRouter.read(&env)?,
-        lease_time: SetupVarsEntry::DhcpLeasetime.read_as(&env)?,
-        domain: SetupVarsEntry::PiholeDomain.read(&env)?,
-        ipv6_support: SetupVarsEntry::DhcpIpv6.is_true(&env)?,
-        rapid_commit: SetupVarsEntry::DhcpRapidCommit.is_true(&env)?,
+        active: repository.get_bool(SetupVarsEntry::DhcpActive)?,
+        ip_start: repository.get(SetupVarsEntry::DhcpStart)?,
+        ip_end: repository.get(SetupVarsEntry::DhcpEnd)?,
+        router_ip: repository.get(SetupVarsEntry::DhcpRouter)?,
+        lease_time: LeaseTime::from_hours(repository.get_usize(SetupVarsEntry::DhcpLeasetime)?),
+        domain: repository.get(SetupVarsEntry::PiholeDomain)?,
+        ipv6_support: repository.get_bool(SetupVarsEntry::DhcpIpv6)?,
+        rapid_commit: repository.get_bool(SetupVarsEntry::DhcpRapidCommit)?,
     };
 
     reply_data(dhcp_settings)
 }
 
-/// Update DHCP Configuration
-#[put("/settings/dhcp", data = "<data>")]
-pub fn put_dhcp(env: Inject<PiholeModule, Env>, _auth: User, data: Json<DhcpSettings>) -> Reply {
+/// Update DHCP Configuration. The DHCP settings are left out of the
+/// regenerated dnsmasq config if DHCP is disabled. The DNS server is restarted
+/// to use the new settings unless `apply` is false, since this endpoint has
+/// always restarted it.
+#[put("/settings/dhcp?<apply>", data = "<data>")]
+pub fn put_dhcp(
+    repository: Inject<PiholeModule, dyn SettingsRepository>,
+    dnsmasq: Inject<PiholeModule, dyn DnsmasqConfigGenerator>,
+    restart: Inject<PiholeModule, dyn RestartService>,
+    _auth: User,
+    apply: Option<bool>,
+    data: Json<DhcpSettings>,
+) -> Reply {
     let settings: DhcpSettings = data.into_inner();
 
-    if !settings.is_valid() {
+    if !settings.is_valid(get_dhcp_prefix(&*repository)?) {
         return Err(Error::from(ErrorKind::InvalidSettingValue));
     }
 
-    SetupVarsEntry::DhcpActive.write(&settings.active.to_string(), &env)?;
-    SetupVarsEntry::DhcpStart.write(&settings.ip_start, &env)?;
-    SetupVarsEntry::DhcpEnd.write(&settings.ip_end, &env)?;
-    SetupVarsEntry::DhcpRouter.write(&settings.router_ip, &env)?;
-    SetupVarsEntry::DhcpLeasetime.write(&settings.lease_time.to_string(), &env)?;
-    SetupVarsEntry::PiholeDomain.write(&settings.domain, &env)?;
-    SetupVarsEntry::DhcpIpv6.write(&settings.ipv6_support.to_string(), &env)?;
-    SetupVarsEntry::DhcpRapidCommit.write(&settings.rapid_commit.to_string(), &env)?;
-
-    generate_dnsmasq_config(&env)?;
-    restart_dns(&env)?;
-    reply_success()
+    let lease_time = settings.lease_time.hours().to_string();
+    repository.set(SetupVarsEntry::DhcpActive, &settings.active.to_string())?;
+    repository.set(SetupVarsEntry::DhcpStart, &settings.ip_start)?;
+    repository.set(SetupVarsEntry::DhcpEnd, &settings.ip_end)?;
+    repository.set(SetupVarsEntry::DhcpRouter, &settings.router_ip)?;
+    repository.set(SetupVarsEntry::DhcpLeasetime, &lease_time)?;
+    repository.set(SetupVarsEntry::PiholeDomain, &settings.domain)?;
+    repository.set(SetupVarsEntry::DhcpIpv6, &settings.ipv6_support.to_string())?;
+    repository.set(
+        SetupVarsEntry::DhcpRapidCommit,
+        &settings.rapid_commit.to_string(),
+    )?;
+
+    dnsmasq.generate()?;
+    reply_applied(&*restart, apply.unwrap_or(true), true)
 }
 
 #[cfg(test)]
 mod test {
-    use crate::{env::PiholeFile, routes::settings::dhcp::DhcpSettings, testing::TestBuilder};
-    use rocket::http::Method;
+    use super::{DhcpSettings, InfiniteLease, LeaseTime};
+    use crate::{env::PiholeFile, testing::TestBuilder};
+    use rocket::http::{Method, Status};
 
     /// Verify that having active DHCP and missing settings is invalid
     #[test]
@@ -103,13 +191,13 @@ mod test {
             ip_start: "".to_owned(),
             ip_end: "".to_owned(),
             router_ip: "".to_owned(),
-            lease_time: 24,
+            lease_time: LeaseTime::Hours(24),
             domain: "".to_owned(),
             ipv6_support: false,
             rapid_commit: false,
         };
 
-        assert_eq!(settings.is_valid(), false);
+        assert_eq!(settings.is_valid(24), false);
     }
 
     /// Verify that having inactive DHCP and missing settings is valid
@@ -120,13 +208,13 @@ mod test {
             ip_start: "".to_owned(),
             ip_end: "".to_owned(),
             router_ip: "".to_owned(),
-            lease_time: 24,
+            lease_time: LeaseTime::Hours(24),
             domain: "".to_owned(),
             ipv6_support: false,
             rapid_commit: false,
         };
 
-        assert_eq!(settings.is_valid(), true);
+        assert_eq!(settings.is_valid(24), true);
     }
 
     /// Verify that having active DHCP and no missing settings is valid
@@ -137,13 +225,13 @@ mod test {
             ip_start: "192.168.1.50".to_owned(),
             ip_end: "192.168.1.150".to_owned(),
             router_ip: "192.168.1.1".to_owned(),
-            lease_time: 24,
+            lease_time: LeaseTime::Hours(24),
             domain: "lan".to_owned(),
             ipv6_support: false,
             rapid_commit: false,
         };
 
-        assert_eq!(settings.is_valid(), true);
+        assert_eq!(settings.is_valid(24), true);
     }
 
     /// Verify that having invalid settings is invalid
@@ -154,13 +242,13 @@ mod test {
             ip_start: "not an IP".to_owned(),
             ip_end: "not an IP".to_owned(),
             router_ip: "not an IP".to_owned(),
-            lease_time: 24,
+            lease_time: LeaseTime::Hours(24),
             domain: "not a domain".to_owned(),
             ipv6_support: false,
             rapid_commit: false,
         };
 
-        assert_eq!(settings.is_valid(), false);
+        assert_eq!(settings.is_valid(24), false);
     }
 
     /// Basic test for stored settings
@@ -278,7 +366,276 @@ mod test {
                 "rapid_commit": true
             }))
             .expect_json(json!({
-                "status": "success"
+                "status": "success",
+                "restart": {
+                    "attempted": true,
+                    "succeeded": true,
+                    "skipped": null
+                }
+            }))
+            .test();
+    }
+
+    /// Build settings with the range, router, and lease time
+    fn settings(ip_start: &str, ip_end: &str, router_ip: &str) -> DhcpSettings {
+        DhcpSettings {
+            active: true,
+            ip_start: ip_start.to_owned(),
+            ip_end: ip_end.to_owned(),
+            router_ip: router_ip.to_owned(),
+            lease_time: LeaseTime::Hours(24),
+            domain: "lan".to_owned(),
+            ipv6_support: false,
+            rapid_commit: false,
+        }
+    }
+
+    /// The range must not be backwards
+    #[test]
+    fn invalid_if_range_backwards() {
+        assert!(settings("192.168.1.50", "192.168.1.150", "192.168.1.1").is_valid(24));
+        assert!(settings("192.168.1.50", "192.168.1.50", "192.168.1.1").is_valid(24));
+        assert!(!settings("192.168.1.150", "192.168.1.50", "192.168.1.1").is_valid(24));
+    }
+
+    /// The range must be in the subnet of the router
+    #[test]
+    fn invalid_if_range_outside_subnet() {
+        assert!(!settings("192.168.2.50", "192.168.2.150", "192.168.1.1").is_valid(24));
+        assert!(!settings("192.168.1.50", "192.168.2.150", "192.168.1.1").is_valid(24));
+        assert!(settings("192.168.1.50", "192.168.2.150", "192.168.1.1").is_valid(16));
+    }
+
+    /// The lease time is a number of hours or "infinite"
+    #[test]
+    fn lease_time_format() {
+        assert_eq!(
+            serde_json::from_value::<LeaseTime>(json!(12)).unwrap(),
+            LeaseTime::Hours(12)
+        );
+        assert_eq!(
+            serde_json::from_value::<LeaseTime>(json!("infinite")).unwrap(),
+            LeaseTime::Infinite(InfiniteLease::Infinite)
+        );
+        assert!(serde_json::from_value::<LeaseTime>(json!("forever")).is_err());
+        assert!(serde_json::from_value::<LeaseTime>(json!(-1)).is_err());
+        assert_eq!(
+            serde_json::to_value(LeaseTime::Infinite(InfiniteLease::Infinite)).unwrap(),
+            json!("infinite")
+        );
+    }
+
+    /// A lease time of zero hours is shown as infinite
+    #[test]
+    fn get_infinite_lease() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/dhcp")
+            .file(PiholeFile::SetupVars, "DHCP_LEASETIME=0\n")
+            .expect_json(json!({
+                "active": false,
+                "ip_start": "",
+                "ip_end": "",
+                "router_ip": "",
+                "lease_time": "infinite",
+                "domain": "lan",
+                "ipv6_support": false,
+                "rapid_commit": true
+            }))
+            .test();
+    }
+
+    /// Changing the settings of an active DHCP server rewrites its settings.
+    /// The range can be larger than a /24 because the Pi-hole is in a /16.
+    #[test]
+    fn put_modify_dhcp() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/dhcp")
+            .method(Method::Put)
+            .file_expect(
+                PiholeFile::SetupVars,
+                "PIHOLE_DNS_1=8.8.8.8\n\
+                 IPV4_ADDRESS=10.0.0.2/16\n\
+                 DHCP_ACTIVE=true\n\
+                 DHCP_START=10.0.0.50\n\
+                 DHCP_END=10.0.0.150\n\
+                 DHCP_ROUTER=10.0.0.1\n\
+                 DHCP_LEASETIME=24\n\
+                 PIHOLE_DOMAIN=lan\n\
+                 DHCP_IPv6=false\n\
+                 DHCP_rapid_commit=true\n",
+                "PIHOLE_DNS_1=8.8.8.8\n\
+                 IPV4_ADDRESS=10.0.0.2/16\n\
+                 DHCP_ACTIVE=true\n\
+                 DHCP_START=10.0.1.10\n\
+                 DHCP_END=10.0.2.250\n\
+                 DHCP_ROUTER=10.0.0.1\n\
+                 DHCP_LEASETIME=0\n\
+                 PIHOLE_DOMAIN=home\n\
+                 DHCP_IPv6=false\n\
+                 DHCP_rapid_commit=false\n",
+            )
+            .file_expect(
+                PiholeFile::DnsmasqConfig,
+                "",
+                "################################################################\n\
+                 #       THIS FILE IS AUTOMATICALLY GENERATED BY PI-HOLE.       #\n\
+                 #          ANY CHANGES MADE TO THIS FILE WILL BE LOST.         #\n\
+                 #                                                              #\n\
+                 #  NEW CONFIG SETTINGS MUST BE MADE IN A SEPARATE CONFIG FILE  #\n\
+                 #                OR IN /etc/dnsmasq.conf                       #\n\
+                 ################################################################\n\
+                 \n\
+                 localise-queries\n\
+                 local-ttl=2\n\
+                 cache-size=10000\n\
+                 server=8.8.8.8\n\
+                 addn-hosts=/etc/pihole/gravity.list\n\
+                 addn-hosts=/etc/pihole/black.list\n\
+                 addn-hosts=/etc/pihole/local.list\n\
+                 domain-needed\n\
+                 bogus-priv\n\
+                 local-service\n\
+                 dhcp-authoritative\n\
+                 dhcp-leasefile=/etc/pihole/dhcp.leases\n\
+                 dhcp-range=10.0.1.10,10.0.2.250,infinite\n\
+                 dhcp-option=option:router,10.0.0.1\n\
+                 dhcp-name-match=set:hostname-ignore,wpad\n\
+                 dhcp-name-match=set:hostname-ignore,localhost\n\
+                 dhcp-ignore-names=tag:hostname-ignore\n\
+                 domain=home\n",
+            )
+            .body(json!({
+                "active": true,
+                "ip_start": "10.0.1.10",
+                "ip_end": "10.0.2.250",
+                "router_ip": "10.0.0.1",
+                "lease_time": "infinite",
+                "domain": "home",
+                "ipv6_support": false,
+                "rapid_commit": false
+            }))
+            .expect_json(json!({
+                "status": "success",
+                "restart": {
+                    "attempted": true,
+                    "succeeded": true,
+                    "skipped": null
+                }
+            }))
+            .test();
+    }
+
+    /// Disabling DHCP removes the DHCP settings from the dnsmasq config
+    #[test]
+    fn put_disable_dhcp() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/dhcp")
+            .method(Method::Put)
+            .file_expect(
+                PiholeFile::SetupVars,
+                "PIHOLE_DNS_1=8.8.8.8\n\
+                 IPV4_ADDRESS=10.0.0.2/16\n\
+                 DHCP_ACTIVE=true\n\
+                 DHCP_START=10.0.0.50\n\
+                 DHCP_END=10.0.0.150\n\
+                 DHCP_ROUTER=10.0.0.1\n\
+                 DHCP_LEASETIME=24\n\
+                 PIHOLE_DOMAIN=lan\n\
+                 DHCP_IPv6=false\n\
+                 DHCP_rapid_commit=true\n",
+                "PIHOLE_DNS_1=8.8.8.8\n\
+                 IPV4_ADDRESS=10.0.0.2/16\n\
+                 DHCP_ACTIVE=false\n\
+                 DHCP_START=10.0.0.50\n\
+                 DHCP_END=10.0.0.150\n\
+                 DHCP_ROUTER=10.0.0.1\n\
+                 DHCP_LEASETIME=24\n\
+                 PIHOLE_DOMAIN=lan\n\
+                 DHCP_IPv6=false\n\
+                 DHCP_rapid_commit=true\n",
+            )
+            .file_expect(
+                PiholeFile::DnsmasqConfig,
+                "################################################################\n\
+                 #       THIS FILE IS AUTOMATICALLY GENERATED BY PI-HOLE.       #\n\
+                 #          ANY CHANGES MADE TO THIS FILE WILL BE LOST.         #\n\
+                 #                                                              #\n\
+                 #  NEW CONFIG SETTINGS MUST BE MADE IN A SEPARATE CONFIG FILE  #\n\
+                 #                OR IN /etc/dnsmasq.conf                       #\n\
+                 ################################################################\n\
+                 \n\
+                 dhcp-authoritative\n\
+                 dhcp-leasefile=/etc/pihole/dhcp.leases\n\
+                 dhcp-range=10.0.0.50,10.0.0.150,24h\n",
+                "################################################################\n\
+                 #       THIS FILE IS AUTOMATICALLY GENERATED BY PI-HOLE.       #\n\
+                 #          ANY CHANGES MADE TO THIS FILE WILL BE LOST.         #\n\
+                 #                                                              #\n\
+                 #  NEW CONFIG SETTINGS MUST BE MADE IN A SEPARATE CONFIG FILE  #\n\
+                 #                OR IN /etc/dnsmasq.conf                       #\n\
+                 ################################################################\n\
+                 \n\
+                 localise-queries\n\
+                 local-ttl=2\n\
+                 cache-size=10000\n\
+                 server=8.8.8.8\n\
+                 addn-hosts=/etc/pihole/gravity.list\n\
+                 addn-hosts=/etc/pihole/black.list\n\
+                 addn-hosts=/etc/pihole/local.list\n\
+                 domain-needed\n\
+                 bogus-priv\n\
+                 local-service\n",
+            )
+            .body(json!({
+                "active": false,
+                "ip_start": "10.0.0.50",
+                "ip_end": "10.0.0.150",
+                "router_ip": "10.0.0.1",
+                "lease_time": 24,
+                "domain": "lan",
+                "ipv6_support": false,
+                "rapid_commit": true
+            }))
+            .expect_json(json!({
+                "status": "success",
+                "restart": {
+                    "attempted": true,
+                    "succeeded": true,
+                    "skipped": null
+                }
+            }))
+            .test();
+    }
+
+    /// A range outside of the router's subnet is rejected without changing
+    /// SetupVars
+    #[test]
+    fn put_invalid_range() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/dhcp")
+            .method(Method::Put)
+            .file_expect(
+                PiholeFile::SetupVars,
+                "IPV4_ADDRESS=192.168.1.2/24\n",
+                "IPV4_ADDRESS=192.168.1.2/24\n",
+            )
+            .body(json!({
+                "active": true,
+                "ip_start": "192.168.1.50",
+                "ip_end": "192.168.2.150",
+                "router_ip": "192.168.1.1",
+                "lease_time": 24,
+                "domain": "lan",
+                "ipv6_support": false,
+                "rapid_commit": true
+            }))
+            .expect_status(Status::BadRequest)
+            .expect_json(json!({
+                "error": {
+                    "key": "invalid_setting_value",
+                    "message": "Invalid setting value",
+                    "data": null
+                }
             }))
             .test();
     }
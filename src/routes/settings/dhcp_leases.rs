@@ -0,0 +1,273 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Active DHCP Leases
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    env::{Env, PiholeFile},
+    routes::auth::User,
+    services::{restart::restart_dns, PiholeModule},
+    settings::{read_config, update_config},
+    util::{reply_data, reply_success, Error, ErrorKind, Reply},
+};
+use shaku_rocket::Inject;
+use std::{
+    net::IpAddr,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// A DHCP lease given out by dnsmasq
+#[derive(Serialize)]
+#[cfg_attr(test, derive(Debug, PartialEq))]
+pub struct DhcpLease {
+    /// The time the lease expires, or zero if it never expires
+    expires: u64,
+    /// If the lease has expired, but dnsmasq has not removed it yet
+    expired: bool,
+    mac: String,
+    ip: String,
+    hostname: Option<String>,
+    client_id: Option<String>,
+}
+
+impl DhcpLease {
+    /// Parse a line of the leases file, which has the format
+    /// `expires mac ip hostname client_id`. dnsmasq writes `*` for a missing
+    /// hostname or client ID. Other lines, such as the DUID of the server,
+    /// are not leases.
+    fn parse(line: &str, now: u64) -> Option<(IpAddr, Self)> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+
+        if words.len() < 4 {
+            return None;
+        }
+
+        let expires: u64 = words[0].parse().ok()?;
+        let ip: IpAddr = words[2].parse().ok()?;
+        let optional = |word: Option<&&str>| match word {
+            Some(&"*") | None => None,
+            Some(word) => Some((*word).to_owned()),
+        };
+
+        Some((
+            ip,
+            DhcpLease {
+                expires,
+                expired: expires != 0 && expires < now,
+                mac: words[1].to_owned(),
+                ip: ip.to_string(),
+                hostname: optional(words.get(3)),
+                client_id: optional(words.get(4)),
+            },
+        ))
+    }
+}
+
+/// Get the DHCP leases, sorted by IP address. Expired leases are included,
+/// and flagged as expired.
+#[get("/settings/dhcp/leases")]
+pub fn get_dhcp_leases(env: Inject<PiholeModule, Env>, _auth: User) -> Reply {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Time went backwards")
+        .as_secs();
+
+    // dnsmasq only creates the file once DHCP is enabled, so a missing file
+    // has no leases
+    let lines = read_config(PiholeFile::DhcpLeases, &env)?;
+
+    reply_data(get_leases(&lines, now))
+}
+
+/// Delete the lease of the IP address, so the device has to request a new
+/// lease. dnsmasq keeps the leases in memory, so it is restarted to read the
+/// leases file again.
+#[delete("/settings/dhcp/leases/<ip>")]
+pub fn delete_dhcp_lease(env: Inject<PiholeModule, Env>, _auth: User, ip: String) -> Reply {
+    let ip: IpAddr = ip.parse().map_err(|_| {
+        Error::from(ErrorKind::InvalidParameter(
+            "ip".to_owned(),
+            ip.clone(),
+            Vec::new(),
+        ))
+    })?;
+
+    update_config(PiholeFile::DhcpLeases, &env, |lines| {
+        let count = lines.len();
+        lines.retain(|line| DhcpLease::parse(line, 0).map_or(true, |(lease_ip, _)| lease_ip != ip));
+
+        if lines.len() == count {
+            return Err(Error::from(ErrorKind::NotFound));
+        }
+
+        Ok(())
+    })?;
+    restart_dns(&env)?;
+
+    reply_success()
+}
+
+/// Parse the leases, and sort them by IP address
+fn get_leases(lines: &[String], now: u64) -> Vec<DhcpLease> {
+    let mut leases: Vec<(IpAddr, DhcpLease)> = lines
+        .iter()
+        .filter_map(|line| DhcpLease::parse(line, now))
+        .collect();
+
+    leases.sort_by_key(|(ip, _)| *ip);
+    leases.into_iter().map(|(_, lease)| lease).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{get_leases, DhcpLease};
+    use crate::{env::PiholeFile, testing::TestBuilder};
+    use rocket::http::{Method, Status};
+    use std::net::IpAddr;
+
+    /// A leases file with IPv4 and IPv6 leases, and missing hostnames and
+    /// client IDs
+    const LEASES: &str = "4102444800 b8:27:eb:01:02:03 192.168.1.150 pi 01:b8:27:eb:01:02:03\n\
+                          1 aa:bb:cc:dd:ee:ff 192.168.1.20 * *\n\
+                          0 00:11:22:33:44:55 192.168.1.3 nas *\n\
+                          duid 00:01:00:01:2a:3b:4c:5d:b8:27:eb:01:02:03\n\
+                          4102444800 12345678 fd00::150 pi 00:01:00:01:2a:3b:4c:5d:b8:27:eb:01:02:03\n";
+
+    /// Missing values, expired leases, and leases which never expire are
+    /// parsed
+    #[test]
+    fn parse() {
+        assert_eq!(
+            DhcpLease::parse("1000 aa:bb:cc:dd:ee:ff 192.168.1.20 * *", 2000),
+            Some((
+                "192.168.1.20".parse::<IpAddr>().unwrap(),
+                DhcpLease {
+                    expires: 1000,
+                    expired: true,
+                    mac: "aa:bb:cc:dd:ee:ff".to_owned(),
+                    ip: "192.168.1.20".to_owned(),
+                    hostname: None,
+                    client_id: None
+                }
+            ))
+        );
+        assert_eq!(
+            DhcpLease::parse("0 aa:bb:cc:dd:ee:ff 192.168.1.20 tv", 2000).map(|(_, lease)| (
+                lease.expired,
+                lease.hostname,
+                lease.client_id
+            )),
+            Some((false, Some("tv".to_owned()), None))
+        );
+        assert_eq!(DhcpLease::parse("duid 00:01:00:01", 2000), None);
+        assert_eq!(DhcpLease::parse("1000 aa:bb:cc:dd:ee:ff nas", 2000), None);
+    }
+
+    /// The leases are sorted by IP address, with IPv4 before IPv6
+    #[test]
+    fn sorted_by_ip() {
+        let lines: Vec<String> = LEASES.lines().map(str::to_owned).collect();
+        let ips: Vec<String> = get_leases(&lines, 2000)
+            .into_iter()
+            .map(|lease| lease.ip)
+            .collect();
+
+        assert_eq!(
+            ips,
+            vec!["192.168.1.3", "192.168.1.20", "192.168.1.150", "fd00::150"]
+        );
+    }
+
+    /// The leases are returned with the expired leases flagged
+    #[test]
+    fn get() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/dhcp/leases")
+            .file(PiholeFile::DhcpLeases, LEASES)
+            .expect_json(json!([
+                {
+                    "expires": 0,
+                    "expired": false,
+                    "mac": "00:11:22:33:44:55",
+                    "ip": "192.168.1.3",
+                    "hostname": "nas",
+                    "client_id": null
+                },
+                {
+                    "expires": 1,
+                    "expired": true,
+                    "mac": "aa:bb:cc:dd:ee:ff",
+                    "ip": "192.168.1.20",
+                    "hostname": null,
+                    "client_id": null
+                },
+                {
+                    "expires": 4_102_444_800u64,
+                    "expired": false,
+                    "mac": "b8:27:eb:01:02:03",
+                    "ip": "192.168.1.150",
+                    "hostname": "pi",
+                    "client_id": "01:b8:27:eb:01:02:03"
+                },
+                {
+                    "expires": 4_102_444_800u64,
+                    "expired": false,
+                    "mac": "12345678",
+                    "ip": "fd00::150",
+                    "hostname": "pi",
+                    "client_id": "00:01:00:01:2a:3b:4c:5d:b8:27:eb:01:02:03"
+                }
+            ]))
+            .test();
+    }
+
+    /// A missing leases file has no leases
+    #[test]
+    fn get_missing_file() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/dhcp/leases")
+            .expect_json(json!([]))
+            .test();
+    }
+
+    /// The lease line is removed, and the other lines are kept
+    #[test]
+    fn delete() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/dhcp/leases/192.168.1.150")
+            .method(Method::Delete)
+            .file_expect(
+                PiholeFile::DhcpLeases,
+                LEASES,
+                "1 aa:bb:cc:dd:ee:ff 192.168.1.20 * *\n\
+                 0 00:11:22:33:44:55 192.168.1.3 nas *\n\
+                 duid 00:01:00:01:2a:3b:4c:5d:b8:27:eb:01:02:03\n\
+                 4102444800 12345678 fd00::150 pi 00:01:00:01:2a:3b:4c:5d:b8:27:eb:01:02:03\n",
+            )
+            .expect_json(json!({ "status": "success" }))
+            .test();
+    }
+
+    /// Deleting an address without a lease is an error
+    #[test]
+    fn delete_not_found() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/dhcp/leases/192.168.1.4")
+            .method(Method::Delete)
+            .file_expect(PiholeFile::DhcpLeases, LEASES, LEASES)
+            .expect_status(Status::NotFound)
+            .expect_json(json!({
+                "error": {
+                    "key": "not_found",
+                    "message": "Not found",
+                    "data": null
+                }
+            }))
+            .test();
+    }
+}
@@ -0,0 +1,229 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Display Settings Endpoints
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    routes::auth::User,
+    services::{
+        settings::SettingsRepository,
+        system_info::{MemoryUsage, SystemInfoService},
+        PiholeModule,
+    },
+    settings::{ConfigEntry, SetupVarsEntry},
+    util::{reply_data, reply_success, Error, ErrorKind, Reply},
+};
+use rocket::serde::json::Json;
+use shaku_rocket::Inject;
+
+/// The display settings
+#[derive(Deserialize)]
+pub struct DisplaySettings {
+    temperature_unit: String,
+}
+
+/// The current metrics of the host, shown in the footer of the web interface.
+/// Metrics which are not available are null.
+#[derive(Serialize)]
+struct HostInfo {
+    /// The CPU temperature, in the configured unit
+    temperature: Option<f64>,
+    load: Option<[f64; 3]>,
+    memory: Option<MemoryUsage>,
+}
+
+/// Get the display settings, along with the current metrics of the host
+#[get("/settings/display")]
+pub fn get_display(
+    repository: Inject<PiholeModule, dyn SettingsRepository>,
+    system_info: Inject<PiholeModule, dyn SystemInfoService>,
+    _auth: User,
+) -> Reply {
+    let unit = repository.get(SetupVarsEntry::TemperatureUnit)?;
+    let host = HostInfo {
+        temperature: system_info
+            .cpu_temperature()
+            .map(|celsius| convert_temperature(celsius, &unit)),
+        load: system_info.load_average(),
+        memory: system_info.memory_usage(),
+    };
+
+    reply_data(json!({
+        "temperature_unit": unit,
+        "host": host
+    }))
+}
+
+/// Update the display settings
+#[put("/settings/display", data = "<settings>")]
+pub fn put_display(
+    repository: Inject<PiholeModule, dyn SettingsRepository>,
+    _auth: User,
+    settings: Json<DisplaySettings>,
+) -> Reply {
+    let unit = settings.into_inner().temperature_unit;
+
+    if !SetupVarsEntry::TemperatureUnit.is_valid(&unit) {
+        return Err(Error::from(ErrorKind::InvalidParameter(
+            "temperature_unit".to_owned(),
+            unit,
+            vec!["C".to_owned(), "F".to_owned(), "K".to_owned()],
+        )));
+    }
+
+    repository.set(SetupVarsEntry::TemperatureUnit, &unit)?;
+
+    reply_success()
+}
+
+/// Convert the temperature from Celsius to the unit. An unknown unit is kept
+/// as Celsius.
+fn convert_temperature(celsius: f64, unit: &str) -> f64 {
+    match unit {
+        "F" => celsius * 9.0 / 5.0 + 32.0,
+        "K" => celsius + 273.15,
+        _ => celsius,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        env::PiholeFile,
+        services::system_info::{MemoryUsage, MockSystemInfoService, SystemInfoService},
+        testing::TestBuilder,
+    };
+    use rocket::http::{Method, Status};
+
+    /// A mock of the host with all metrics available
+    fn system_info() -> MockSystemInfoService {
+        let mut service = MockSystemInfoService::new();
+
+        service.expect_cpu_temperature().return_const(Some(50.0));
+        service
+            .expect_load_average()
+            .return_const(Some([0.5, 0.25, 0.125]));
+        service
+            .expect_memory_usage()
+            .return_const(Some(MemoryUsage {
+                total: 1_000_000,
+                used: 400_000,
+            }));
+
+        service
+    }
+
+    /// The unit is read from SetupVars, and the temperature is converted to it
+    #[test]
+    fn get() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/display")
+            .file(PiholeFile::SetupVars, "TEMPERATUREUNIT=F\n")
+            .mock_component::<dyn SystemInfoService>(Box::new(system_info()))
+            .expect_json(json!({
+                "temperature_unit": "F",
+                "host": {
+                    "temperature": 122.0,
+                    "load": [0.5, 0.25, 0.125],
+                    "memory": {
+                        "total": 1_000_000,
+                        "used": 400_000
+                    }
+                }
+            }))
+            .test();
+    }
+
+    /// The unit defaults to Celsius
+    #[test]
+    fn get_default() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/display")
+            .file(PiholeFile::SetupVars, "")
+            .mock_component::<dyn SystemInfoService>(Box::new(system_info()))
+            .expect_json(json!({
+                "temperature_unit": "C",
+                "host": {
+                    "temperature": 50.0,
+                    "load": [0.5, 0.25, 0.125],
+                    "memory": {
+                        "total": 1_000_000,
+                        "used": 400_000
+                    }
+                }
+            }))
+            .test();
+    }
+
+    /// Missing metrics, such as the temperature in a VM, are null
+    #[test]
+    fn get_missing_metrics() {
+        let mut service = MockSystemInfoService::new();
+
+        service.expect_cpu_temperature().return_const(None);
+        service.expect_load_average().return_const(None);
+        service.expect_memory_usage().return_const(None);
+
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/display")
+            .file(PiholeFile::SetupVars, "TEMPERATUREUNIT=K\n")
+            .mock_component::<dyn SystemInfoService>(Box::new(service))
+            .expect_json(json!({
+                "temperature_unit": "K",
+                "host": {
+                    "temperature": null,
+                    "load": null,
+                    "memory": null
+                }
+            }))
+            .test();
+    }
+
+    /// The unit is written to SetupVars
+    #[test]
+    fn put() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/display")
+            .method(Method::Put)
+            .file_expect(
+                PiholeFile::SetupVars,
+                "TEMPERATUREUNIT=C\n",
+                "TEMPERATUREUNIT=K\n",
+            )
+            .body(json!({ "temperature_unit": "K" }))
+            .expect_json(json!({ "status": "success" }))
+            .test();
+    }
+
+    /// Units other than C, F, and K are rejected
+    #[test]
+    fn put_invalid() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/display")
+            .method(Method::Put)
+            .file_expect(
+                PiholeFile::SetupVars,
+                "TEMPERATUREUNIT=C\n",
+                "TEMPERATUREUNIT=C\n",
+            )
+            .body(json!({ "temperature_unit": "celsius" }))
+            .expect_status(Status::BadRequest)
+            .expect_json(json!({
+                "error": {
+                    "key": "invalid_parameter",
+                    "message": "Invalid value celsius for parameter temperature_unit",
+                    "data": {
+                        "parameter": "temperature_unit",
+                        "value": "celsius",
+                        "valid": ["C", "F", "K"]
+                    }
+                }
+            }))
+            .test();
+    }
+}
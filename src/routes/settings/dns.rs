@@ -9,11 +9,13 @@
 // Please see LICENSE file for your rights under this license.
 
 use crate::{
-    env::Env,
-    routes::{auth::User, settings::common::restart_dns},
-    services::PiholeModule,
-    settings::{generate_dnsmasq_config, ConfigEntry, SetupVarsEntry, ValueType},
-    util::{reply_data, reply_success, Error, ErrorKind, Reply},
+    routes::{auth::User, settings::common::reply_applied},
+    services::{
+        dnsmasq::DnsmasqConfigGenerator, restart::RestartService, settings::SettingsRepository,
+        PiholeModule,
+    },
+    settings::{ConfigEntry, SetupVarsEntry, Upstream, ValueType},
+    util::{reply_data, Error, ErrorKind, Reply},
 };
 use rocket::serde::json::Json;
 use shaku_rocket::Inject;
@@ -26,13 +28,10 @@ pub struct DnsSettings {
 }
 
 impl DnsSettings {
-    /// Check if all the DNS settings are valid
+    /// Check if the DNS settings are valid. The upstreams are checked
+    /// separately by `parse_upstreams`.
     fn is_valid(&self) -> bool {
-        self.upstream_dns
-            .iter()
-            .all(|dns| SetupVarsEntry::PiholeDns(0).is_valid(dns))
-            && self.options.is_valid()
-            && self.conditional_forwarding.is_valid()
+        self.options.is_valid() && self.conditional_forwarding.is_valid()
     }
 }
 
@@ -84,86 +83,165 @@ impl DnsConditionalForwarding {
     }
 }
 
-/// Get upstream DNS servers
-fn get_upstream_dns(env: &Env) -> Result<Vec<String>, Error> {
-    let mut upstream_dns = Vec::new();
-
-    for num in 1.. {
-        let ip = SetupVarsEntry::PiholeDns(num).read(&env)?;
-
-        if !ip.is_empty() {
-            upstream_dns.push(ip);
-        } else {
-            break;
-        }
-    }
-
-    Ok(upstream_dns)
-}
-
 /// Get DNS Configuration
 #[get("/settings/dns")]
-pub fn get_dns(env: Inject<PiholeModule, Env>, _auth: User) -> Reply {
+pub fn get_dns(repository: Inject<PiholeModule, dyn SettingsRepository>, _auth: User) -> Reply {
     let dns_settings = DnsSettings {
-        upstream_dns: get_upstream_dns(&env)?,
+        upstream_dns: repository.upstreams()?,
         options: DnsOptions {
-            fqdn_required: SetupVarsEntry::DnsFqdnRequired.is_true(&env)?,
-            bogus_priv: SetupVarsEntry::DnsBogusPriv.is_true(&env)?,
-            dnssec: SetupVarsEntry::Dnssec.is_true(&env)?,
-            listening_type: SetupVarsEntry::DnsmasqListening.read(&env)?,
+            fqdn_required: repository.get_bool(SetupVarsEntry::DnsFqdnRequired)?,
+            bogus_priv: repository.get_bool(SetupVarsEntry::DnsBogusPriv)?,
+            dnssec: repository.get_bool(SetupVarsEntry::Dnssec)?,
+            listening_type: repository.get(SetupVarsEntry::DnsmasqListening)?,
         },
         conditional_forwarding: DnsConditionalForwarding {
-            enabled: SetupVarsEntry::ConditionalForwarding.is_true(&env)?,
-            ip: SetupVarsEntry::ConditionalForwardingIp.read(&env)?,
-            domain: SetupVarsEntry::ConditionalForwardingDomain.read(&env)?,
-            cidr: SetupVarsEntry::ConditionalForwardingCIDR.read_as(&env)?,
+            enabled: repository.get_bool(SetupVarsEntry::ConditionalForwarding)?,
+            ip: repository.get(SetupVarsEntry::ConditionalForwardingIp)?,
+            domain: repository.get(SetupVarsEntry::ConditionalForwardingDomain)?,
+            cidr: repository.get_usize(SetupVarsEntry::ConditionalForwardingCIDR)?,
         },
     };
 
     reply_data(dns_settings)
 }
 
-/// Update DNS Configuration
-#[put("/settings/dns", data = "<data>")]
-pub fn put_dns(env: Inject<PiholeModule, Env>, _auth: User, data: Json<DnsSettings>) -> Reply {
+/// Update DNS Configuration. The DNS server is restarted to use the new
+/// settings unless `apply` is false, since this endpoint has always restarted
+/// it.
+#[put("/settings/dns?<apply>", data = "<data>")]
+pub fn put_dns(
+    repository: Inject<PiholeModule, dyn SettingsRepository>,
+    dnsmasq: Inject<PiholeModule, dyn DnsmasqConfigGenerator>,
+    restart: Inject<PiholeModule, dyn RestartService>,
+    _auth: User,
+    apply: Option<bool>,
+    data: Json<DnsSettings>,
+) -> Reply {
     let settings: DnsSettings = data.into_inner();
+    let upstreams = parse_upstreams(&settings.upstream_dns)?;
 
     if !settings.is_valid() {
         return Err(Error::from(ErrorKind::InvalidSettingValue));
     }
 
-    // Delete previous upstream DNS entries
-    SetupVarsEntry::delete_upstream_dns(&env)?;
-
-    // Add new upstream DNS
-    for (i, dns) in settings.upstream_dns.into_iter().enumerate() {
-        SetupVarsEntry::PiholeDns(i + 1).write(&dns, &env)?;
-    }
+    // Replace the previous upstream DNS entries
+    repository.set_upstreams(&upstreams)?;
 
     // Write DNS settings
-    SetupVarsEntry::DnsFqdnRequired.write(&settings.options.fqdn_required.to_string(), &env)?;
-    SetupVarsEntry::DnsBogusPriv.write(&settings.options.bogus_priv.to_string(), &env)?;
-    SetupVarsEntry::Dnssec.write(&settings.options.dnssec.to_string(), &env)?;
-    SetupVarsEntry::DnsmasqListening.write(&settings.options.listening_type, &env)?;
+    let options = &settings.options;
+    repository.set(
+        SetupVarsEntry::DnsFqdnRequired,
+        &options.fqdn_required.to_string(),
+    )?;
+    repository.set(
+        SetupVarsEntry::DnsBogusPriv,
+        &options.bogus_priv.to_string(),
+    )?;
+    repository.set(SetupVarsEntry::Dnssec, &options.dnssec.to_string())?;
+    repository.set(SetupVarsEntry::DnsmasqListening, &options.listening_type)?;
 
     // Write conditional forwarding settings
-    SetupVarsEntry::ConditionalForwarding
-        .write(&settings.conditional_forwarding.enabled.to_string(), &env)?;
-    SetupVarsEntry::ConditionalForwardingIp.write(&settings.conditional_forwarding.ip, &env)?;
-    SetupVarsEntry::ConditionalForwardingDomain
-        .write(&settings.conditional_forwarding.domain, &env)?;
-    SetupVarsEntry::ConditionalForwardingCIDR
-        .write(&settings.conditional_forwarding.cidr.to_string(), &env)?;
-
-    generate_dnsmasq_config(&env)?;
-    restart_dns(&env)?;
-    reply_success()
+    let forwarding = &settings.conditional_forwarding;
+    repository.set(
+        SetupVarsEntry::ConditionalForwarding,
+        &forwarding.enabled.to_string(),
+    )?;
+    repository.set(SetupVarsEntry::ConditionalForwardingIp, &forwarding.ip)?;
+    repository.set(
+        SetupVarsEntry::ConditionalForwardingDomain,
+        &forwarding.domain,
+    )?;
+    repository.set(
+        SetupVarsEntry::ConditionalForwardingCIDR,
+        &forwarding.cidr.to_string(),
+    )?;
+
+    dnsmasq.generate()?;
+    reply_applied(&*restart, apply.unwrap_or(true), true)
+}
+
+/// The upstream DNS servers, in the order they are used
+#[derive(Serialize, Deserialize)]
+pub struct DnsUpstreams {
+    upstreams: Vec<String>,
+    /// Deprecated in favor of the `apply` parameter. If the DNS server should
+    /// be restarted to use the new upstreams. This is only used when updating
+    /// the upstreams, and `apply` takes precedence if it is given.
+    #[serde(default, skip_serializing)]
+    restart: bool,
+}
+
+/// Parse the upstreams and convert them into the format stored in SetupVars
+/// (`127.0.0.1:5353` or `[::1]:5353`). Upstreams can be given in either the
+/// SetupVars format or the `address#port` format used by dnsmasq. The error
+/// names the index of the first invalid upstream.
+fn parse_upstreams(upstreams: &[String]) -> Result<Vec<String>, Error> {
+    upstreams
+        .iter()
+        .enumerate()
+        .map(|(index, upstream)| match upstream.parse::<Upstream>() {
+            Ok(upstream) => Ok(upstream.to_string()),
+            Err(reason) => Err(Error::from(ErrorKind::InvalidUpstream(
+                index,
+                upstream.to_owned(),
+                reason.to_owned(),
+            ))),
+        })
+        .collect()
+}
+
+/// Get the upstream DNS servers
+#[get("/settings/dns/upstreams")]
+pub fn get_dns_upstreams(
+    repository: Inject<PiholeModule, dyn SettingsRepository>,
+    _auth: User,
+) -> Reply {
+    reply_data(DnsUpstreams {
+        upstreams: repository.upstreams()?,
+        restart: false,
+    })
+}
+
+/// Replace the upstream DNS servers. The servers are renumbered in the order
+/// they are given, and the dnsmasq config is regenerated. The DNS server is
+/// only restarted if `apply` is set, or if the deprecated `restart` flag is set
+/// in the body without `apply`.
+#[put("/settings/dns/upstreams?<apply>", data = "<data>")]
+pub fn put_dns_upstreams(
+    repository: Inject<PiholeModule, dyn SettingsRepository>,
+    dnsmasq: Inject<PiholeModule, dyn DnsmasqConfigGenerator>,
+    restart: Inject<PiholeModule, dyn RestartService>,
+    _auth: User,
+    apply: Option<bool>,
+    data: Json<DnsUpstreams>,
+) -> Reply {
+    let data = data.into_inner();
+
+    // Pi-hole can not resolve anything without an upstream
+    if data.upstreams.is_empty() {
+        return Err(Error::from(ErrorKind::InvalidSettingValue));
+    }
+
+    let upstreams = parse_upstreams(&data.upstreams)?;
+
+    repository.set_upstreams(&upstreams)?;
+
+    dnsmasq.generate()?;
+    reply_applied(&*restart, apply.unwrap_or(data.restart), true)
 }
 
 #[cfg(test)]
 mod test {
-    use crate::{env::PiholeFile, testing::TestBuilder};
-    use rocket::http::Method;
+    use super::parse_upstreams;
+    use crate::{
+        env::PiholeFile,
+        services::{
+            dnsmasq::{DnsmasqConfigGenerator, MockDnsmasqConfigGenerator},
+            restart::{MockRestartService, RestartService},
+        },
+        testing::TestBuilder,
+    };
+    use rocket::http::{Method, Status};
 
     /// Basic test for reported settings
     #[test]
@@ -305,9 +383,270 @@ mod test {
                     "listening_type": "local"
                 }
             }))
+            .expect_json(json!({
+                "status": "success",
+                "restart": {
+                    "attempted": true,
+                    "succeeded": true,
+                    "skipped": null
+                }
+            }))
+            .test();
+    }
+
+    /// The DNS server is not restarted if `apply` is false
+    #[test]
+    fn put_dns_without_apply() {
+        let mut dnsmasq = MockDnsmasqConfigGenerator::new();
+        dnsmasq.expect_generate().times(1).returning(|| Ok(()));
+        let mut restart = MockRestartService::new();
+        restart.expect_restart_dns().never();
+
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/dns?apply=false")
+            .method(Method::Put)
+            .file_expect(
+                PiholeFile::SetupVars,
+                "",
+                "PIHOLE_DNS_1=8.8.8.8\n\
+                 DNS_FQDN_REQUIRED=true\n\
+                 DNS_BOGUS_PRIV=true\n\
+                 DNSSEC=false\n\
+                 DNSMASQ_LISTENING=local\n\
+                 CONDITIONAL_FORWARDING=false\n\
+                 CONDITIONAL_FORWARDING_IP=192.168.1.1\n\
+                 CONDITIONAL_FORWARDING_DOMAIN=lan\n\
+                 CONDITIONAL_FORWARDING_CIDR=24\n",
+            )
+            .mock_component::<dyn DnsmasqConfigGenerator>(Box::new(dnsmasq))
+            .mock_component::<dyn RestartService>(Box::new(restart))
+            .body(json!({
+                "upstream_dns": ["8.8.8.8"],
+                "conditional_forwarding": {
+                    "domain": "lan",
+                    "enabled": false,
+                    "ip": "192.168.1.1",
+                    "cidr": 24
+                },
+                "options": {
+                    "bogus_priv": true,
+                    "dnssec": false,
+                    "fqdn_required": true,
+                    "listening_type": "local"
+                }
+            }))
             .expect_json(json!({
                 "status": "success"
             }))
             .test();
     }
+
+    /// The upstreams are read in order until the first missing number
+    #[test]
+    fn get_upstreams() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/dns/upstreams")
+            .file(
+                PiholeFile::SetupVars,
+                "PIHOLE_DNS_1=8.8.8.8\n\
+                 PIHOLE_DNS_2=127.0.0.1:5353\n\
+                 PIHOLE_DNS_4=1.1.1.1\n",
+            )
+            .expect_json(json!({
+                "upstreams": ["8.8.8.8", "127.0.0.1:5353"]
+            }))
+            .test();
+    }
+
+    /// Upstreams with a port in the dnsmasq format are stored in the
+    /// SetupVars format, and the addresses are normalized
+    #[test]
+    fn upstream_ports() {
+        let upstreams: Vec<String> = vec![
+            "8.8.8.8".to_owned(),
+            "127.0.0.1#5353".to_owned(),
+            "::1#5353".to_owned(),
+            "[::1]:5353".to_owned(),
+            "[2606:4700:4700:0:0:0:0:1111]".to_owned(),
+        ];
+
+        assert_eq!(
+            parse_upstreams(&upstreams).unwrap(),
+            vec![
+                "8.8.8.8",
+                "127.0.0.1:5353",
+                "[::1]:5353",
+                "[::1]:5353",
+                "2606:4700:4700::1111"
+            ]
+        );
+    }
+
+    /// The upstreams are renumbered from one, and the dnsmasq config is
+    /// regenerated with them and applied
+    #[test]
+    fn put_upstreams() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/dns/upstreams?apply=true")
+            .method(Method::Put)
+            .file_expect(
+                PiholeFile::SetupVars,
+                "PIHOLE_DNS_1=8.8.8.8\n\
+                 DNSSEC=false\n\
+                 PIHOLE_DNS_3=8.8.4.4\n",
+                "DNSSEC=false\n\
+                 PIHOLE_DNS_1=1.1.1.1\n\
+                 PIHOLE_DNS_2=127.0.0.1:5353\n\
+                 PIHOLE_DNS_3=[2606:4700:4700::1111]:53\n",
+            )
+            .file_expect(
+                PiholeFile::DnsmasqConfig,
+                "",
+                "################################################################\n\
+                    #       THIS FILE IS AUTOMATICALLY GENERATED BY PI-HOLE.       #\n\
+                    #          ANY CHANGES MADE TO THIS FILE WILL BE LOST.         #\n\
+                    #                                                              #\n\
+                    #  NEW CONFIG SETTINGS MUST BE MADE IN A SEPARATE CONFIG FILE  #\n\
+                    #                OR IN /etc/dnsmasq.conf                       #\n\
+                    ################################################################\n\
+                    \n\
+                    localise-queries\n\
+                    local-ttl=2\n\
+                    cache-size=10000\n\
+                    server=1.1.1.1\n\
+                    server=127.0.0.1#5353\n\
+                    server=2606:4700:4700::1111#53\n\
+                    addn-hosts=/etc/pihole/gravity.list\n\
+                    addn-hosts=/etc/pihole/black.list\n\
+                    addn-hosts=/etc/pihole/local.list\n\
+                    domain-needed\n\
+                    bogus-priv\n\
+                    local-service\n",
+            )
+            .body(json!({
+                "upstreams": ["1.1.1.1", "127.0.0.1#5353", "2606:4700:4700::1111#53"]
+            }))
+            .expect_json(json!({
+                "status": "success",
+                "restart": {
+                    "attempted": true,
+                    "succeeded": true,
+                    "skipped": null
+                }
+            }))
+            .test();
+    }
+
+    /// The deprecated `restart` flag in the body still applies the upstreams
+    #[test]
+    fn put_upstreams_restart_flag() {
+        let mut dnsmasq = MockDnsmasqConfigGenerator::new();
+        dnsmasq.expect_generate().times(1).returning(|| Ok(()));
+        let mut restart = MockRestartService::new();
+        restart.expect_is_enabled().return_const(true);
+        restart.expect_restart_dns().times(1).returning(|| Ok(()));
+
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/dns/upstreams")
+            .method(Method::Put)
+            .file_expect(
+                PiholeFile::SetupVars,
+                "PIHOLE_DNS_1=8.8.8.8\n",
+                "PIHOLE_DNS_1=1.1.1.1\n",
+            )
+            .mock_component::<dyn DnsmasqConfigGenerator>(Box::new(dnsmasq))
+            .mock_component::<dyn RestartService>(Box::new(restart))
+            .body(json!({
+                "upstreams": ["1.1.1.1"],
+                "restart": true
+            }))
+            .expect_json(json!({
+                "status": "success",
+                "restart": {
+                    "attempted": true,
+                    "succeeded": true,
+                    "skipped": null
+                }
+            }))
+            .test();
+    }
+
+    /// Invalid upstreams are rejected without changing SetupVars
+    #[test]
+    fn put_invalid_upstream() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/dns/upstreams")
+            .method(Method::Put)
+            .file_expect(
+                PiholeFile::SetupVars,
+                "PIHOLE_DNS_1=8.8.8.8\n",
+                "PIHOLE_DNS_1=8.8.8.8\n",
+            )
+            .body(json!({
+                "upstreams": ["1.1.1.1", "dns.google#53"]
+            }))
+            .expect_status(Status::BadRequest)
+            .expect_json(json!({
+                "error": {
+                    "key": "invalid_upstream",
+                    "message": "Invalid upstream dns.google#53 at index 1: \
+                                Hostnames are not allowed, use an IP address",
+                    "data": {
+                        "index": 1,
+                        "upstream": "dns.google#53",
+                        "reason": "Hostnames are not allowed, use an IP address"
+                    }
+                }
+            }))
+            .test();
+    }
+
+    /// Ports outside of 1 to 65535 are rejected
+    #[test]
+    fn put_invalid_port() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/dns/upstreams")
+            .method(Method::Put)
+            .file_expect(
+                PiholeFile::SetupVars,
+                "PIHOLE_DNS_1=8.8.8.8\n",
+                "PIHOLE_DNS_1=8.8.8.8\n",
+            )
+            .body(json!({
+                "upstreams": ["[2606:4700:4700::1111]#53", "1.1.1.1", "8.8.8.8#65536"]
+            }))
+            .expect_status(Status::BadRequest)
+            .expect_json(json!({
+                "error": {
+                    "key": "invalid_upstream",
+                    "message": "Invalid upstream 8.8.8.8#65536 at index 2: \
+                                The port must be a number from 1 to 65535",
+                    "data": {
+                        "index": 2,
+                        "upstream": "8.8.8.8#65536",
+                        "reason": "The port must be a number from 1 to 65535"
+                    }
+                }
+            }))
+            .test();
+    }
+
+    /// At least one upstream is required
+    #[test]
+    fn put_no_upstreams() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/dns/upstreams")
+            .method(Method::Put)
+            .file(PiholeFile::SetupVars, "PIHOLE_DNS_1=8.8.8.8\n")
+            .body(json!({ "upstreams": [] }))
+            .expect_status(Status::BadRequest)
+            .expect_json(json!({
+                "error": {
+                    "key": "invalid_setting_value",
+                    "message": "Invalid setting value",
+                    "data": null
+                }
+            }))
+            .test();
+    }
 }
@@ -0,0 +1,227 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// DNS Options Settings
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    routes::{auth::User, settings::common::reply_applied},
+    services::{
+        dnsmasq::DnsmasqConfigGenerator, restart::RestartService, settings::SettingsRepository,
+        PiholeModule,
+    },
+    settings::{DnsmasqSettings, SetupVarsEntry},
+    util::{reply_data, Reply},
+};
+use rocket::serde::json::Json;
+use shaku_rocket::Inject;
+
+/// The dnsmasq options which keep local queries from being forwarded
+/// upstream
+#[derive(Serialize, Deserialize)]
+pub struct DnsOptionSettings {
+    /// Do not forward queries for plain names without dots or a domain
+    /// (`domain-needed`)
+    fqdn_required: bool,
+    /// Do not forward reverse lookups of private addresses (`bogus-priv`)
+    bogus_priv: bool,
+}
+
+/// Get the DNS options. Both are on if they are not set in SetupVars.
+#[get("/settings/dns/options")]
+pub fn get_dns_options(
+    repository: Inject<PiholeModule, dyn SettingsRepository>,
+    _auth: User,
+) -> Reply {
+    reply_data(DnsOptionSettings {
+        fqdn_required: repository.get_bool(SetupVarsEntry::DnsFqdnRequired)?,
+        bogus_priv: repository.get_bool(SetupVarsEntry::DnsBogusPriv)?,
+    })
+}
+
+/// Update the DNS options in SetupVars and the dnsmasq config. The dnsmasq
+/// config is written first, so SetupVars is left unchanged if the config is
+/// not managed by Pi-hole. If `apply` is set, the DNS server is restarted if
+/// an option changed.
+#[put("/settings/dns/options?<apply>", data = "<data>")]
+pub fn put_dns_options(
+    repository: Inject<PiholeModule, dyn SettingsRepository>,
+    dnsmasq: Inject<PiholeModule, dyn DnsmasqConfigGenerator>,
+    restart: Inject<PiholeModule, dyn RestartService>,
+    _auth: User,
+    apply: Option<bool>,
+    data: Json<DnsOptionSettings>,
+) -> Reply {
+    let options = data.into_inner();
+    let mut settings = DnsmasqSettings::from_setup_vars(&*repository)?;
+    let changed = settings.fqdn_required != options.fqdn_required
+        || settings.bogus_priv != options.bogus_priv;
+
+    settings.fqdn_required = options.fqdn_required;
+    settings.bogus_priv = options.bogus_priv;
+    dnsmasq.write(&settings)?;
+
+    repository.set(
+        SetupVarsEntry::DnsFqdnRequired,
+        &options.fqdn_required.to_string(),
+    )?;
+    repository.set(
+        SetupVarsEntry::DnsBogusPriv,
+        &options.bogus_priv.to_string(),
+    )?;
+
+    reply_applied(&*restart, apply.unwrap_or(false), changed)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{env::PiholeFile, testing::TestBuilder};
+    use rocket::http::Method;
+
+    /// The generated dnsmasq config, with or without the options
+    fn config(fqdn_required: bool, bogus_priv: bool) -> String {
+        format!(
+            "################################################################\n\
+             #       THIS FILE IS AUTOMATICALLY GENERATED BY PI-HOLE.       #\n\
+             #          ANY CHANGES MADE TO THIS FILE WILL BE LOST.         #\n\
+             #                                                              #\n\
+             #  NEW CONFIG SETTINGS MUST BE MADE IN A SEPARATE CONFIG FILE  #\n\
+             #                OR IN /etc/dnsmasq.conf                       #\n\
+             ################################################################\n\
+             \n\
+             localise-queries\n\
+             local-ttl=2\n\
+             cache-size=10000\n\
+             server=8.8.8.8\n\
+             addn-hosts=/etc/pihole/gravity.list\n\
+             addn-hosts=/etc/pihole/black.list\n\
+             addn-hosts=/etc/pihole/local.list\n\
+             {}{}\
+             local-service\n",
+            if fqdn_required { "domain-needed\n" } else { "" },
+            if bogus_priv { "bogus-priv\n" } else { "" }
+        )
+    }
+
+    /// Turn the options on or off, starting with both on, and check the files
+    /// afterwards
+    fn test_put(fqdn_required: bool, bogus_priv: bool) {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/dns/options")
+            .method(Method::Put)
+            .file_expect(
+                PiholeFile::SetupVars,
+                "PIHOLE_DNS_1=8.8.8.8\n\
+                 DNS_FQDN_REQUIRED=true\n\
+                 DNS_BOGUS_PRIV=true\n",
+                &format!(
+                    "PIHOLE_DNS_1=8.8.8.8\n\
+                     DNS_FQDN_REQUIRED={}\n\
+                     DNS_BOGUS_PRIV={}\n",
+                    fqdn_required, bogus_priv
+                ),
+            )
+            .file_expect(
+                PiholeFile::DnsmasqConfig,
+                &config(true, true),
+                &config(fqdn_required, bogus_priv),
+            )
+            .body(json!({
+                "fqdn_required": fqdn_required,
+                "bogus_priv": bogus_priv
+            }))
+            .expect_json(json!({ "status": "success" }))
+            .test();
+    }
+
+    /// The options are read from SetupVars
+    #[test]
+    fn get() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/dns/options")
+            .file(
+                PiholeFile::SetupVars,
+                "DNS_FQDN_REQUIRED=false\n\
+                 DNS_BOGUS_PRIV=true\n",
+            )
+            .expect_json(json!({
+                "fqdn_required": false,
+                "bogus_priv": true
+            }))
+            .test();
+    }
+
+    /// Both options are on if they are not in SetupVars
+    #[test]
+    fn get_default() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/dns/options")
+            .file(PiholeFile::SetupVars, "PIHOLE_DNS_1=8.8.8.8\n")
+            .expect_json(json!({
+                "fqdn_required": true,
+                "bogus_priv": true
+            }))
+            .test();
+    }
+
+    /// Both options stay on
+    #[test]
+    fn put_both_on() {
+        test_put(true, true);
+    }
+
+    /// Only `domain-needed` is left on
+    #[test]
+    fn put_fqdn_required_only() {
+        test_put(true, false);
+    }
+
+    /// Only `bogus-priv` is left on
+    #[test]
+    fn put_bogus_priv_only() {
+        test_put(false, true);
+    }
+
+    /// Both options are turned off
+    #[test]
+    fn put_both_off() {
+        test_put(false, false);
+    }
+
+    /// Options missing from SetupVars are written, and the change is applied
+    #[test]
+    fn put_missing_apply() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/dns/options?apply=true")
+            .method(Method::Put)
+            .file_expect(
+                PiholeFile::SetupVars,
+                "PIHOLE_DNS_1=8.8.8.8\n",
+                "PIHOLE_DNS_1=8.8.8.8\n\
+                 DNS_FQDN_REQUIRED=true\n\
+                 DNS_BOGUS_PRIV=false\n",
+            )
+            .file_expect(
+                PiholeFile::DnsmasqConfig,
+                &config(true, true),
+                &config(true, false),
+            )
+            .body(json!({
+                "fqdn_required": true,
+                "bogus_priv": false
+            }))
+            .expect_json(json!({
+                "status": "success",
+                "restart": {
+                    "attempted": true,
+                    "succeeded": true,
+                    "skipped": null
+                }
+            }))
+            .test();
+    }
+}
@@ -0,0 +1,285 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// DNSSEC Settings
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    routes::{auth::User, settings::common::reply_applied},
+    services::{
+        dnsmasq::DnsmasqConfigGenerator, restart::RestartService, settings::SettingsRepository,
+        PiholeModule,
+    },
+    settings::{DnsmasqSettings, SetupVarsEntry},
+    util::{reply_data, Error, ErrorKind, Reply},
+};
+use rocket::serde::json::Json;
+use shaku_rocket::Inject;
+
+/// The DNSSEC setting
+#[derive(Serialize, Deserialize)]
+pub struct Dnssec {
+    enabled: bool,
+}
+
+/// Check if DNSSEC is on
+#[get("/settings/dns/dnssec")]
+pub fn get_dnssec(repository: Inject<PiholeModule, dyn SettingsRepository>, _auth: User) -> Reply {
+    reply_data(Dnssec {
+        enabled: repository.get_bool(SetupVarsEntry::Dnssec)?,
+    })
+}
+
+/// Turn DNSSEC on or off in SetupVars and the dnsmasq config. DNSSEC can only
+/// be turned on if there is an upstream DNS server to validate the replies
+/// of. The dnsmasq config is written first, so SetupVars is left unchanged if
+/// the config is not managed by Pi-hole. If `apply` is set, the DNS server is
+/// restarted if the setting changed.
+#[put("/settings/dns/dnssec?<apply>", data = "<data>")]
+pub fn put_dnssec(
+    repository: Inject<PiholeModule, dyn SettingsRepository>,
+    dnsmasq: Inject<PiholeModule, dyn DnsmasqConfigGenerator>,
+    restart: Inject<PiholeModule, dyn RestartService>,
+    _auth: User,
+    apply: Option<bool>,
+    data: Json<Dnssec>,
+) -> Reply {
+    let enabled = data.into_inner().enabled;
+    let mut settings = DnsmasqSettings::from_setup_vars(&*repository)?;
+
+    if enabled && settings.upstreams.is_empty() {
+        return Err(Error::from(ErrorKind::InvalidSettingValue));
+    }
+
+    let changed = settings.dnssec != enabled;
+    settings.dnssec = enabled;
+    dnsmasq.write(&settings)?;
+
+    if changed {
+        repository.set(SetupVarsEntry::Dnssec, &enabled.to_string())?;
+    }
+
+    reply_applied(&*restart, apply.unwrap_or(false), changed)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        env::PiholeFile,
+        services::restart::{MockRestartService, RestartService},
+        testing::TestBuilder,
+        util::{Error, ErrorKind},
+    };
+    use rocket::http::{Method, Status};
+
+    /// The generated dnsmasq config, with or without DNSSEC
+    fn config(dnssec: bool) -> String {
+        format!(
+            "################################################################\n\
+             #       THIS FILE IS AUTOMATICALLY GENERATED BY PI-HOLE.       #\n\
+             #          ANY CHANGES MADE TO THIS FILE WILL BE LOST.         #\n\
+             #                                                              #\n\
+             #  NEW CONFIG SETTINGS MUST BE MADE IN A SEPARATE CONFIG FILE  #\n\
+             #                OR IN /etc/dnsmasq.conf                       #\n\
+             ################################################################\n\
+             \n\
+             localise-queries\n\
+             local-ttl=2\n\
+             cache-size=10000\n\
+             server=8.8.8.8\n\
+             addn-hosts=/etc/pihole/gravity.list\n\
+             addn-hosts=/etc/pihole/black.list\n\
+             addn-hosts=/etc/pihole/local.list\n\
+             domain-needed\n\
+             bogus-priv\n\
+             {}\
+             local-service\n",
+            if dnssec {
+                "dnssec\n\
+                 trust-anchor=.,19036,8,2,49AAC11D7B6F6446702E54A1607371607A1A41855200FD2CE1CDDE32F24E8FB5\n\
+                 trust-anchor=.,20326,8,2,E06D44B80B8F1D39A95C0B0D7C65D08458E880409BBC683457104237C7F8EC8D\n"
+            } else {
+                ""
+            }
+        )
+    }
+
+    /// Send a PUT request, and check the files afterwards
+    fn test_put(enabled: bool, setup_vars: (&str, &str), dnssec: (bool, bool)) {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/dns/dnssec")
+            .method(Method::Put)
+            .file_expect(PiholeFile::SetupVars, setup_vars.0, setup_vars.1)
+            .file_expect(
+                PiholeFile::DnsmasqConfig,
+                &config(dnssec.0),
+                &config(dnssec.1),
+            )
+            .body(json!({ "enabled": enabled }))
+            .expect_json(json!({
+                "status": "success"
+            }))
+            .test();
+    }
+
+    /// The setting is read from SetupVars
+    #[test]
+    fn get() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/dns/dnssec")
+            .file(PiholeFile::SetupVars, "DNSSEC=true\n")
+            .expect_json(json!({ "enabled": true }))
+            .test();
+    }
+
+    /// Turning DNSSEC on adds the options, with each trust anchor once
+    #[test]
+    fn put_on() {
+        test_put(
+            true,
+            (
+                "DNSSEC=false\nPIHOLE_DNS_1=8.8.8.8\n",
+                "PIHOLE_DNS_1=8.8.8.8\nDNSSEC=true\n",
+            ),
+            (false, true),
+        );
+    }
+
+    /// Turning DNSSEC on when it is already on does not change the files
+    #[test]
+    fn put_on_no_op() {
+        test_put(
+            true,
+            (
+                "PIHOLE_DNS_1=8.8.8.8\nDNSSEC=true\n",
+                "PIHOLE_DNS_1=8.8.8.8\nDNSSEC=true\n",
+            ),
+            (true, true),
+        );
+    }
+
+    /// Turning DNSSEC off removes the options
+    #[test]
+    fn put_off() {
+        test_put(
+            false,
+            (
+                "DNSSEC=true\nPIHOLE_DNS_1=8.8.8.8\n",
+                "PIHOLE_DNS_1=8.8.8.8\nDNSSEC=false\n",
+            ),
+            (true, false),
+        );
+    }
+
+    /// Send a PUT request which turns DNSSEC on and asks for it to be applied,
+    /// and check the reported restart
+    fn test_apply(
+        setup_vars: (&str, &str),
+        restart: MockRestartService,
+        attempted: bool,
+        succeeded: bool,
+        skipped: Option<&str>,
+    ) {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/dns/dnssec?apply=true")
+            .method(Method::Put)
+            .file_expect(PiholeFile::SetupVars, setup_vars.0, setup_vars.1)
+            .file_expect(PiholeFile::DnsmasqConfig, &config(false), &config(true))
+            .mock_component::<dyn RestartService>(Box::new(restart))
+            .body(json!({ "enabled": true }))
+            .expect_json(json!({
+                "status": "success",
+                "restart": {
+                    "attempted": attempted,
+                    "succeeded": succeeded,
+                    "skipped": skipped
+                }
+            }))
+            .test();
+    }
+
+    /// The DNS server is restarted when the change is applied
+    #[test]
+    fn put_apply() {
+        let mut restart = MockRestartService::new();
+        restart.expect_is_enabled().return_const(true);
+        restart.expect_restart_dns().times(1).returning(|| Ok(()));
+
+        test_apply(
+            (
+                "DNSSEC=false\nPIHOLE_DNS_1=8.8.8.8\n",
+                "PIHOLE_DNS_1=8.8.8.8\nDNSSEC=true\n",
+            ),
+            restart,
+            true,
+            true,
+            None,
+        );
+    }
+
+    /// A failed restart is reported, but the setting is still written
+    #[test]
+    fn put_apply_failed() {
+        let mut restart = MockRestartService::new();
+        restart.expect_is_enabled().return_const(true);
+        restart
+            .expect_restart_dns()
+            .times(1)
+            .returning(|| Err(Error::from(ErrorKind::RestartDnsError)));
+
+        test_apply(
+            (
+                "DNSSEC=false\nPIHOLE_DNS_1=8.8.8.8\n",
+                "PIHOLE_DNS_1=8.8.8.8\nDNSSEC=true\n",
+            ),
+            restart,
+            true,
+            false,
+            None,
+        );
+    }
+
+    /// The DNS server is not restarted if restarting is turned off in the
+    /// config
+    #[test]
+    fn put_apply_disabled() {
+        let mut restart = MockRestartService::new();
+        restart.expect_is_enabled().return_const(false);
+        restart.expect_restart_dns().never();
+
+        test_apply(
+            (
+                "DNSSEC=false\nPIHOLE_DNS_1=8.8.8.8\n",
+                "PIHOLE_DNS_1=8.8.8.8\nDNSSEC=true\n",
+            ),
+            restart,
+            false,
+            false,
+            Some("disabled"),
+        );
+    }
+
+    /// DNSSEC can not be turned on without an upstream
+    #[test]
+    fn put_on_without_upstream() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/dns/dnssec")
+            .method(Method::Put)
+            .file_expect(PiholeFile::SetupVars, "DNSSEC=false\n", "DNSSEC=false\n")
+            .file_expect(PiholeFile::DnsmasqConfig, "", "")
+            .body(json!({ "enabled": true }))
+            .expect_status(Status::BadRequest)
+            .expect_json(json!({
+                "error": {
+                    "key": "invalid_setting_value",
+                    "message": "Invalid setting value",
+                    "data": null
+                }
+            }))
+            .test();
+    }
+}
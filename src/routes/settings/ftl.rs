@@ -9,32 +9,34 @@
 // Please see LICENSE file for your rights under this license.
 
 use crate::{
-    env::Env,
     routes::auth::User,
-    services::PiholeModule,
-    settings::{ConfigEntry, FtlConfEntry},
+    services::{
+        settings::{parse_setting, SettingsRepository},
+        PiholeModule,
+    },
+    settings::FtlConfEntry,
     util::{reply_data, Reply},
 };
 use shaku_rocket::Inject;
 
 /// Read FTL's settings
 #[get("/settings/ftl")]
-pub fn get_ftl(env: Inject<PiholeModule, Env>, _auth: User) -> Reply {
+pub fn get_ftl(repository: Inject<PiholeModule, dyn SettingsRepository>, _auth: User) -> Reply {
     // if setting is not present, report default
-    let socket_listening = FtlConfEntry::SocketListening.read(&env)?;
-    let query_display = FtlConfEntry::QueryDisplay.read(&env)?;
-    let aaaa_query_analysis = FtlConfEntry::AaaaQueryAnalysis.read(&env)?;
-    let resolve_ipv6 = FtlConfEntry::ResolveIpv6.read(&env)?;
-    let resolve_ipv4 = FtlConfEntry::ResolveIpv4.read(&env)?;
-    let max_db_days: i32 = FtlConfEntry::MaxDbDays.read_as(&env)?;
-    let db_interval: f32 = FtlConfEntry::DbInterval.read_as(&env)?;
-    let db_file = FtlConfEntry::DbFile.read(&env)?;
-    let max_log_age: f32 = FtlConfEntry::MaxLogAge.read_as(&env)?;
-    let ftl_port: usize = FtlConfEntry::FtlPort.read_as(&env)?;
-    let privacy_level: i32 = FtlConfEntry::PrivacyLevel.read_as(&env)?;
-    let ignore_local_host = FtlConfEntry::IgnoreLocalHost.read(&env)?;
-    let blocking_mode = FtlConfEntry::BlockingMode.read(&env)?;
-    let regex_debug_mode = FtlConfEntry::RegexDebugMode.is_true(&env)?;
+    let socket_listening = repository.get_ftl(FtlConfEntry::SocketListening)?;
+    let query_display = repository.get_ftl(FtlConfEntry::QueryDisplay)?;
+    let aaaa_query_analysis = repository.get_ftl(FtlConfEntry::AaaaQueryAnalysis)?;
+    let resolve_ipv6 = repository.get_ftl(FtlConfEntry::ResolveIpv6)?;
+    let resolve_ipv4 = repository.get_ftl(FtlConfEntry::ResolveIpv4)?;
+    let max_db_days: i32 = parse_setting(&repository.get_ftl(FtlConfEntry::MaxDbDays)?)?;
+    let db_interval: f32 = parse_setting(&repository.get_ftl(FtlConfEntry::DbInterval)?)?;
+    let db_file = repository.get_ftl(FtlConfEntry::DbFile)?;
+    let max_log_age: f32 = parse_setting(&repository.get_ftl(FtlConfEntry::MaxLogAge)?)?;
+    let ftl_port: usize = parse_setting(&repository.get_ftl(FtlConfEntry::FtlPort)?)?;
+    let privacy_level: i32 = parse_setting(&repository.get_ftl(FtlConfEntry::PrivacyLevel)?)?;
+    let ignore_local_host = repository.get_ftl(FtlConfEntry::IgnoreLocalHost)?;
+    let blocking_mode = repository.get_ftl(FtlConfEntry::BlockingMode)?;
+    let regex_debug_mode: bool = parse_setting(&repository.get_ftl(FtlConfEntry::RegexDebugMode)?)?;
 
     reply_data(json!({
         "socket_listening": socket_listening,
@@ -37,8 +37,9 @@ pub fn get_ftldb(ftl: Inject<PiholeModule, FtlConnectionType>, _auth: User) -> R
 
 #[cfg(test)]
 mod test {
-    use crate::testing::{write_eom, TestBuilder};
+    use crate::testing::{write_eom, FtlSocketScript, TestBuilder};
     use rmp::encode;
+    use rocket::http::Status;
 
     /// Basic test for reported values
     #[test]
@@ -59,4 +60,43 @@ mod test {
             }))
             .test();
     }
+
+    /// The stats are read from the FTL socket
+    #[test]
+    fn test_get_ftldb_socket() {
+        let mut data = Vec::new();
+        encode::write_i32(&mut data, 1_048_576).unwrap();
+        encode::write_i64(&mut data, 32768).unwrap();
+        encode::write_str(&mut data, "3.0.1").unwrap();
+        write_eom(&mut data);
+
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/ftldb")
+            .ftl_socket_script(FtlSocketScript::new().respond("dbstats", data))
+            .expect_ftl_commands(&["dbstats"])
+            .expect_json(json!({
+                "queries": 1_048_576,
+                "filesize": 32768,
+                "sqlite_version": "3.0.1"
+            }))
+            .test();
+    }
+
+    /// If FTL closes the connection early, an error is returned
+    #[test]
+    fn test_get_ftldb_dropped_connection() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/ftldb")
+            .ftl_socket_script(FtlSocketScript::new().drop_connection("dbstats"))
+            .expect_ftl_commands(&["dbstats"])
+            .expect_status(Status::InternalServerError)
+            .expect_json(json!({
+                "error": {
+                    "key": "ftl_read_error",
+                    "message": "Error reading from FTL",
+                    "data": null
+                }
+            }))
+            .test();
+    }
 }
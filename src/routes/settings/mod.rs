@@ -9,11 +9,24 @@
 // Please see LICENSE file for your rights under this license.
 
 mod common;
+mod conditional_forwarding;
 mod dhcp;
+mod dhcp_leases;
+mod display;
 mod dns;
+mod dns_options;
+mod dnssec;
 mod ftl;
 mod ftldb;
 mod network;
+mod privacy;
+mod query_logging;
+mod rate_limit;
+mod static_leases;
 mod web;
 
-pub use self::{common::*, dhcp::*, dns::*, ftl::*, ftldb::*, network::*, web::*};
+pub use self::{
+    conditional_forwarding::*, dhcp::*, dhcp_leases::*, display::*, dns::*, dns_options::*,
+    dnssec::*, ftl::*, ftldb::*, network::*, privacy::*, query_logging::*, rate_limit::*,
+    static_leases::*, web::*,
+};
@@ -9,10 +9,9 @@
 // Please see LICENSE file for your rights under this license.
 
 use crate::{
-    env::Env,
     routes::auth::User,
-    services::PiholeModule,
-    settings::{ConfigEntry, SetupVarsEntry},
+    services::{settings::SettingsRepository, PiholeModule},
+    settings::SetupVarsEntry,
     util::{reply_data, Reply},
 };
 use shaku_rocket::Inject;
@@ -20,14 +19,14 @@ use std::ffi::OsString;
 
 /// Get Pi-hole local network information
 #[get("/settings/network")]
-pub fn get_network(env: Inject<PiholeModule, Env>, _auth: User) -> Reply {
-    let ipv4_full = SetupVarsEntry::Ipv4Address.read(&env)?;
+pub fn get_network(repository: Inject<PiholeModule, dyn SettingsRepository>, _auth: User) -> Reply {
+    let ipv4_full = repository.get(SetupVarsEntry::Ipv4Address)?;
     let ipv4_address: Vec<&str> = ipv4_full.split('/').collect();
-    let ipv6_full = SetupVarsEntry::Ipv6Address.read(&env)?;
+    let ipv6_full = repository.get(SetupVarsEntry::Ipv6Address)?;
     let ipv6_address: Vec<&str> = ipv6_full.split('/').collect();
 
     reply_data(json!({
-        "interface": SetupVarsEntry::PiholeInterface.read(&env)?,
+        "interface": repository.get(SetupVarsEntry::PiholeInterface)?,
         "ipv4_address": ipv4_address[0],
         "ipv6_address": ipv6_address[0],
         "hostname": hostname::get().unwrap_or_else(|_| OsString::from("unknown"))
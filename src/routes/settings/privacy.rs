@@ -0,0 +1,166 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Privacy Level Settings
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    routes::auth::User,
+    services::{privacy::PrivacyService, PiholeModule},
+    settings::FtlPrivacyLevel,
+    util::{reply_data, reply_success, Error, ErrorKind, Reply},
+};
+use rocket::serde::json::Json;
+use shaku_rocket::Inject;
+
+/// The warning given when the privacy level is lowered
+const LOWERED_WARNING: &str =
+    "Data collected at the previous privacy level is not restored by lowering the level";
+
+/// The privacy level setting. Only levels 0 to 3 can be set through the API.
+#[derive(Serialize, Deserialize)]
+pub struct PrivacySettings {
+    level: u8,
+}
+
+/// Get the privacy level FTL is configured with
+#[get("/settings/privacy")]
+pub fn get_privacy(privacy: Inject<PiholeModule, dyn PrivacyService>, _auth: User) -> Reply {
+    reply_data(PrivacySettings {
+        level: privacy.privacy_level()? as u8,
+    })
+}
+
+/// Set the privacy level in the FTL config. The statistics endpoints read the
+/// level through the same service on every request, so a higher level hides
+/// data immediately. Lowering the level does not restore data which was
+/// already hidden, so a warning is included in the reply.
+#[put("/settings/privacy", data = "<data>")]
+pub fn put_privacy(
+    privacy: Inject<PiholeModule, dyn PrivacyService>,
+    _auth: User,
+    data: Json<PrivacySettings>,
+) -> Reply {
+    let level = parse_level(data.into_inner().level)?;
+    let previous = privacy.privacy_level()?;
+
+    privacy.set_privacy_level(level)?;
+
+    if level < previous {
+        reply_data(json!({
+            "status": "success",
+            "warning": LOWERED_WARNING
+        }))
+    } else {
+        reply_success()
+    }
+}
+
+/// Get the privacy level with the number, if it can be set through the API
+fn parse_level(level: u8) -> Result<FtlPrivacyLevel, Error> {
+    match level.to_string().parse()? {
+        FtlPrivacyLevel::NoStats => Err(Error::from(ErrorKind::InvalidSettingValue)),
+        level => Ok(level),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{env::PiholeFile, testing::TestBuilder};
+    use rocket::http::{Method, Status};
+
+    /// The level is read from the FTL config
+    #[test]
+    fn get() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/privacy")
+            .file(PiholeFile::FtlConfig, "PRIVACYLEVEL=2\n")
+            .expect_json(json!({ "level": 2 }))
+            .test();
+    }
+
+    /// The default level is used if the FTL config does not exist
+    #[test]
+    fn get_default() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/privacy")
+            .expect_json(json!({ "level": 0 }))
+            .test();
+    }
+
+    /// Raising the level replaces the entry without a warning
+    #[test]
+    fn put_raise() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/privacy")
+            .method(Method::Put)
+            .file_expect(
+                PiholeFile::FtlConfig,
+                "PRIVACYLEVEL=1\n\
+                 MAXDBDAYS=30\n",
+                "MAXDBDAYS=30\n\
+                 PRIVACYLEVEL=3\n",
+            )
+            .body(json!({ "level": 3 }))
+            .expect_json(json!({ "status": "success" }))
+            .test();
+    }
+
+    /// Lowering the level includes a warning
+    #[test]
+    fn put_lower() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/privacy")
+            .method(Method::Put)
+            .file_expect(
+                PiholeFile::FtlConfig,
+                "PRIVACYLEVEL=2\n",
+                "PRIVACYLEVEL=0\n",
+            )
+            .body(json!({ "level": 0 }))
+            .expect_json(json!({
+                "status": "success",
+                "warning": super::LOWERED_WARNING
+            }))
+            .test();
+    }
+
+    /// An empty FTL config gets just the privacy level
+    #[test]
+    fn put_empty_config() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/privacy")
+            .method(Method::Put)
+            .file_expect(PiholeFile::FtlConfig, "", "PRIVACYLEVEL=1\n")
+            .body(json!({ "level": 1 }))
+            .expect_json(json!({ "status": "success" }))
+            .test();
+    }
+
+    /// Levels above 3 can not be set
+    #[test]
+    fn put_invalid() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/privacy")
+            .method(Method::Put)
+            .file_expect(
+                PiholeFile::FtlConfig,
+                "PRIVACYLEVEL=1\n",
+                "PRIVACYLEVEL=1\n",
+            )
+            .body(json!({ "level": 4 }))
+            .expect_status(Status::BadRequest)
+            .expect_json(json!({
+                "error": {
+                    "key": "invalid_setting_value",
+                    "message": "Invalid setting value",
+                    "data": null
+                }
+            }))
+            .test();
+    }
+}
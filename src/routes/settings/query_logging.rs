@@ -0,0 +1,254 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Query Logging Settings
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    env::Env,
+    routes::{
+        auth::User,
+        settings::common::{flush_log, reply_applied},
+    },
+    services::{
+        dnsmasq::DnsmasqConfigGenerator, restart::RestartService, settings::SettingsRepository,
+        PiholeModule,
+    },
+    settings::{DnsmasqSettings, SetupVarsEntry},
+    util::{reply_data, Reply},
+};
+use rocket::serde::json::Json;
+use shaku_rocket::Inject;
+
+/// The query logging setting
+#[derive(Serialize, Deserialize)]
+pub struct QueryLogging {
+    enabled: bool,
+    /// If the query log should be flushed when query logging is turned off.
+    /// This is only used when updating the setting.
+    #[serde(default, skip_serializing)]
+    flush: bool,
+}
+
+/// Check if query logging is on
+#[get("/settings/dns/query_logging")]
+pub fn get_query_logging(
+    repository: Inject<PiholeModule, dyn SettingsRepository>,
+    _auth: User,
+) -> Reply {
+    reply_data(QueryLogging {
+        enabled: repository.get_bool(SetupVarsEntry::QueryLogging)?,
+        flush: false,
+    })
+}
+
+/// Turn query logging on or off in SetupVars and the dnsmasq config. The
+/// dnsmasq config is written first, so SetupVars is left unchanged if the
+/// config is not managed by Pi-hole. If `apply` is set, the DNS server is
+/// restarted if the setting changed.
+#[put("/settings/dns/query_logging?<apply>", data = "<data>")]
+pub fn put_query_logging(
+    env: Inject<PiholeModule, Env>,
+    repository: Inject<PiholeModule, dyn SettingsRepository>,
+    dnsmasq: Inject<PiholeModule, dyn DnsmasqConfigGenerator>,
+    restart: Inject<PiholeModule, dyn RestartService>,
+    _auth: User,
+    apply: Option<bool>,
+    data: Json<QueryLogging>,
+) -> Reply {
+    let settings = data.into_inner();
+    let changed = repository.get_bool(SetupVarsEntry::QueryLogging)? != settings.enabled;
+
+    let mut dnsmasq_settings = DnsmasqSettings::from_setup_vars(&*repository)?;
+    dnsmasq_settings.query_logging = settings.enabled;
+    dnsmasq.write(&dnsmasq_settings)?;
+
+    if changed {
+        repository.set(SetupVarsEntry::QueryLogging, &settings.enabled.to_string())?;
+    }
+
+    if !settings.enabled && settings.flush {
+        flush_log(&env)?;
+    }
+
+    reply_applied(&*restart, apply.unwrap_or(false), changed)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        env::PiholeFile,
+        services::settings::{MockSettingsRepository, SettingsRepository},
+        testing::TestBuilder,
+        util::{Error, ErrorKind},
+    };
+    use rocket::http::{Method, Status};
+
+    /// The generated dnsmasq config, with or without query logging
+    fn config(logging: bool) -> String {
+        format!(
+            "################################################################\n\
+             #       THIS FILE IS AUTOMATICALLY GENERATED BY PI-HOLE.       #\n\
+             #          ANY CHANGES MADE TO THIS FILE WILL BE LOST.         #\n\
+             #                                                              #\n\
+             #  NEW CONFIG SETTINGS MUST BE MADE IN A SEPARATE CONFIG FILE  #\n\
+             #                OR IN /etc/dnsmasq.conf                       #\n\
+             ################################################################\n\
+             \n\
+             localise-queries\n\
+             local-ttl=2\n\
+             cache-size=10000\n\
+             server=8.8.8.8\n\
+             addn-hosts=/etc/pihole/gravity.list\n\
+             addn-hosts=/etc/pihole/black.list\n\
+             addn-hosts=/etc/pihole/local.list\n\
+             {}\
+             domain-needed\n\
+             bogus-priv\n\
+             local-service\n",
+            if logging {
+                "log-queries\n\
+                 log-facility=/var/log/pihole.log\n\
+                 log-async\n"
+            } else {
+                ""
+            }
+        )
+    }
+
+    /// Send a PUT request, and check the files afterwards
+    fn test_put(body: serde_json::Value, setup_vars: (&str, &str), logging: (bool, bool)) {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/dns/query_logging")
+            .method(Method::Put)
+            .file_expect(PiholeFile::SetupVars, setup_vars.0, setup_vars.1)
+            .file_expect(
+                PiholeFile::DnsmasqConfig,
+                &config(logging.0),
+                &config(logging.1),
+            )
+            .body(body)
+            .expect_json(json!({
+                "status": "success"
+            }))
+            .test();
+    }
+
+    /// The setting is read from SetupVars
+    #[test]
+    fn get() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/dns/query_logging")
+            .file(PiholeFile::SetupVars, "QUERY_LOGGING=true\n")
+            .expect_json(json!({ "enabled": true }))
+            .test();
+    }
+
+    /// Turning logging on adds the options to the generated config
+    #[test]
+    fn put_on() {
+        test_put(
+            json!({ "enabled": true }),
+            (
+                "PIHOLE_DNS_1=8.8.8.8\nQUERY_LOGGING=false\n",
+                "PIHOLE_DNS_1=8.8.8.8\nQUERY_LOGGING=true\n",
+            ),
+            (false, true),
+        );
+    }
+
+    /// Turning logging off removes the options from the generated config
+    #[test]
+    fn put_off() {
+        test_put(
+            json!({ "enabled": false, "flush": true }),
+            (
+                "QUERY_LOGGING=true\nPIHOLE_DNS_1=8.8.8.8\n",
+                "PIHOLE_DNS_1=8.8.8.8\nQUERY_LOGGING=false\n",
+            ),
+            (true, false),
+        );
+    }
+
+    /// Turning logging on when it is already on does not change the files
+    #[test]
+    fn put_on_no_op() {
+        test_put(
+            json!({ "enabled": true }),
+            (
+                "QUERY_LOGGING=true\nPIHOLE_DNS_1=8.8.8.8\n",
+                "QUERY_LOGGING=true\nPIHOLE_DNS_1=8.8.8.8\n",
+            ),
+            (true, true),
+        );
+    }
+
+    /// Turning logging off when it is already off does not change the files
+    #[test]
+    fn put_off_no_op() {
+        test_put(
+            json!({ "enabled": false }),
+            (
+                "QUERY_LOGGING=false\nPIHOLE_DNS_1=8.8.8.8\n",
+                "QUERY_LOGGING=false\nPIHOLE_DNS_1=8.8.8.8\n",
+            ),
+            (false, false),
+        );
+    }
+
+    /// A dnsmasq config which is not managed by Pi-hole is not overwritten,
+    /// and SetupVars is not changed
+    #[test]
+    fn put_unmanaged_config() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/dns/query_logging")
+            .method(Method::Put)
+            .file_expect(
+                PiholeFile::SetupVars,
+                "QUERY_LOGGING=false\n",
+                "QUERY_LOGGING=false\n",
+            )
+            .file_expect(
+                PiholeFile::DnsmasqConfig,
+                "# My own config\nserver=1.1.1.1\n",
+                "# My own config\nserver=1.1.1.1\n",
+            )
+            .body(json!({ "enabled": true }))
+            .expect_status(Status::Conflict)
+            .expect_json(json!({
+                "error": {
+                    "key": "dnsmasq_config_unmanaged",
+                    "message": "The dnsmasq config is not managed by Pi-hole",
+                    "data": null
+                }
+            }))
+            .test();
+    }
+
+    /// A setting which can not be read is an error
+    #[test]
+    fn get_invalid() {
+        let mut repository = MockSettingsRepository::new();
+
+        repository
+            .expect_get_bool()
+            .return_once(|_| Err(Error::from(ErrorKind::InvalidSettingValue)));
+
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/dns/query_logging")
+            .mock_component::<dyn SettingsRepository>(Box::new(repository))
+            .expect_status(Status::BadRequest)
+            .expect_json(json!({
+                "error": {
+                    "key": "invalid_setting_value",
+                    "message": "Invalid setting value",
+                    "data": null
+                }
+            }))
+            .test();
+    }
+}
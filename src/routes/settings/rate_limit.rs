@@ -0,0 +1,185 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Rate Limit Settings
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    routes::{auth::User, settings::common::reply_applied},
+    services::{
+        restart::RestartService,
+        settings::{parse_setting, SettingsRepository},
+        PiholeModule,
+    },
+    settings::{FtlConfEntry, RateLimit},
+    util::{reply_data, Error, ErrorKind, Reply},
+};
+use rocket::serde::json::Json;
+use shaku_rocket::Inject;
+
+/// Get the rate limit FTL is configured with
+#[get("/settings/dns/rate_limit")]
+pub fn get_rate_limit(
+    repository: Inject<PiholeModule, dyn SettingsRepository>,
+    _auth: User,
+) -> Reply {
+    let rate_limit: RateLimit = parse_setting(&repository.get_ftl(FtlConfEntry::RateLimit)?)?;
+
+    reply_data(rate_limit)
+}
+
+/// Set the rate limit in the FTL config. FTL only reads the limit when it
+/// starts, so if `apply` is set, the DNS server is restarted if the limit
+/// changed.
+#[put("/settings/dns/rate_limit?<apply>", data = "<data>")]
+pub fn put_rate_limit(
+    repository: Inject<PiholeModule, dyn SettingsRepository>,
+    restart: Inject<PiholeModule, dyn RestartService>,
+    _auth: User,
+    apply: Option<bool>,
+    data: Json<RateLimit>,
+) -> Reply {
+    let rate_limit = data.into_inner();
+
+    if !rate_limit.is_valid() {
+        return Err(Error::from(ErrorKind::InvalidSettingValue));
+    }
+
+    let value = rate_limit.to_string();
+    let changed = repository.get_ftl(FtlConfEntry::RateLimit)? != value;
+
+    if changed {
+        repository.set_ftl(FtlConfEntry::RateLimit, &value)?;
+    }
+
+    reply_applied(&*restart, apply.unwrap_or(false), changed)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{env::PiholeFile, testing::TestBuilder};
+    use rocket::http::{Method, Status};
+
+    /// Send a PUT request, and check the FTL config afterwards
+    fn test_put(body: serde_json::Value, before: &str, after: &str) {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/dns/rate_limit")
+            .method(Method::Put)
+            .file_expect(PiholeFile::FtlConfig, before, after)
+            .body(body)
+            .expect_json(json!({ "status": "success" }))
+            .test();
+    }
+
+    /// Send a PUT request with an invalid rate limit, and check the FTL config
+    /// is not changed
+    fn test_put_invalid(body: serde_json::Value) {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/dns/rate_limit")
+            .method(Method::Put)
+            .file_expect(
+                PiholeFile::FtlConfig,
+                "RATE_LIMIT=1000/60\n",
+                "RATE_LIMIT=1000/60\n",
+            )
+            .body(body)
+            .expect_status(Status::BadRequest)
+            .expect_json(json!({
+                "error": {
+                    "key": "invalid_setting_value",
+                    "message": "Invalid setting value",
+                    "data": null
+                }
+            }))
+            .test();
+    }
+
+    /// The rate limit is read from the FTL config
+    #[test]
+    fn get() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/dns/rate_limit")
+            .file(PiholeFile::FtlConfig, "RATE_LIMIT=500/30\n")
+            .expect_json(json!({ "queries": 500, "seconds": 30 }))
+            .test();
+    }
+
+    /// FTL's default is used if the rate limit is not set
+    #[test]
+    fn get_default() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/dns/rate_limit")
+            .file(PiholeFile::FtlConfig, "")
+            .expect_json(json!({ "queries": 1000, "seconds": 60 }))
+            .test();
+    }
+
+    /// The rate limit is written as `queries/seconds`
+    #[test]
+    fn put() {
+        test_put(
+            json!({ "queries": 500, "seconds": 30 }),
+            "PRIVACYLEVEL=0\nRATE_LIMIT=1000/60\n",
+            "PRIVACYLEVEL=0\nRATE_LIMIT=500/30\n",
+        );
+    }
+
+    /// Rate limiting can be turned off with `0/0`
+    #[test]
+    fn put_disabled() {
+        test_put(
+            json!({ "queries": 0, "seconds": 0 }),
+            "RATE_LIMIT=1000/60\n",
+            "RATE_LIMIT=0/0\n",
+        );
+    }
+
+    /// Setting the current rate limit does not change the config
+    #[test]
+    fn put_unchanged() {
+        test_put(
+            json!({ "queries": 1000, "seconds": 60 }),
+            "RATE_LIMIT=1000/60\n",
+            "RATE_LIMIT=1000/60\n",
+        );
+    }
+
+    /// Applying an unchanged rate limit does not restart the DNS server
+    #[test]
+    fn put_apply_unchanged() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/dns/rate_limit?apply=true")
+            .method(Method::Put)
+            .file_expect(
+                PiholeFile::FtlConfig,
+                "RATE_LIMIT=1000/60\n",
+                "RATE_LIMIT=1000/60\n",
+            )
+            .body(json!({ "queries": 1000, "seconds": 60 }))
+            .expect_json(json!({
+                "status": "success",
+                "restart": {
+                    "attempted": false,
+                    "succeeded": false,
+                    "skipped": "unchanged"
+                }
+            }))
+            .test();
+    }
+
+    /// A zero interval is only allowed when rate limiting is off
+    #[test]
+    fn put_zero_seconds() {
+        test_put_invalid(json!({ "queries": 1000, "seconds": 0 }));
+    }
+
+    /// A zero query count is only allowed when rate limiting is off
+    #[test]
+    fn put_zero_queries() {
+        test_put_invalid(json!({ "queries": 0, "seconds": 60 }));
+    }
+}
@@ -0,0 +1,405 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Static DHCP Lease Settings
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    env::{Env, PiholeFile},
+    routes::auth::User,
+    services::{restart::restart_dns, settings::SettingsRepository, PiholeModule},
+    settings::{read_config, update_config, SetupVarsEntry, ValueType},
+    util::{reply_data, reply_success, Error, ErrorKind, Reply},
+};
+use rocket::serde::json::Json;
+use shaku_rocket::Inject;
+use std::net::Ipv4Addr;
+
+/// The warning given when a lease is added outside of the DHCP range
+const OUTSIDE_RANGE_WARNING: &str = "The IP address is outside of the DHCP range";
+
+/// A static DHCP lease, which always gives the device with the MAC address the
+/// same IP address. Leases are stored in a dnsmasq config file as
+/// `dhcp-host=MAC,IP,hostname`.
+#[derive(Serialize, Deserialize)]
+pub struct StaticLease {
+    mac: String,
+    ip: String,
+    /// The hostname is optional, and empty if it is not set
+    #[serde(default)]
+    hostname: String,
+    /// If the IP address may be outside of the DHCP range. This is only used
+    /// when adding a lease.
+    #[serde(default, skip_serializing)]
+    outside_range: bool,
+}
+
+impl StaticLease {
+    /// Parse a `dhcp-host=` line of the config. dnsmasq does not require an
+    /// order, so each value is recognized by its format. Lines without a MAC
+    /// address are not leases which the API manages.
+    fn parse(line: &str) -> Option<Self> {
+        let (key, values) = line.split_at(line.find('=')?);
+
+        if key.trim() != "dhcp-host" {
+            return None;
+        }
+
+        let mut mac = None;
+        let mut ip = None;
+        let mut hostname = None;
+
+        for value in values[1..].split(',').map(str::trim) {
+            if mac.is_none() && ValueType::MacAddress.is_valid(value) {
+                mac = Some(value.to_lowercase());
+            } else if ip.is_none() && value.parse::<Ipv4Addr>().is_ok() {
+                ip = Some(value.to_owned());
+            } else if hostname.is_none() && ValueType::Hostname.is_valid(value) {
+                hostname = Some(value.to_owned());
+            }
+        }
+
+        Some(StaticLease {
+            mac: mac?,
+            ip: ip.unwrap_or_default(),
+            hostname: hostname.unwrap_or_default(),
+            outside_range: false,
+        })
+    }
+
+    /// Format the lease as a line of the config
+    fn to_line(&self) -> String {
+        if self.hostname.is_empty() {
+            format!("dhcp-host={},{}", self.mac, self.ip)
+        } else {
+            format!("dhcp-host={},{},{}", self.mac, self.ip, self.hostname)
+        }
+    }
+}
+
+/// Get the static DHCP leases, in the order they are in the config
+#[get("/settings/dhcp/static_leases")]
+pub fn get_static_leases(env: Inject<PiholeModule, Env>, _auth: User) -> Reply {
+    let leases: Vec<StaticLease> = read_config(PiholeFile::StaticDhcpConfig, &env)?
+        .iter()
+        .filter_map(|line| StaticLease::parse(line))
+        .collect();
+
+    reply_data(leases)
+}
+
+/// Add a static DHCP lease. Neither the MAC address nor the IP address can
+/// already have a lease. The IP address must be inside the DHCP range, unless
+/// `outside_range` is set, in which case a warning is included in the reply.
+/// The lines of the config are kept as they are, and the lease is appended.
+#[post("/settings/dhcp/static_leases", data = "<lease>")]
+pub fn add_static_lease(
+    env: Inject<PiholeModule, Env>,
+    repository: Inject<PiholeModule, dyn SettingsRepository>,
+    _auth: User,
+    lease: Json<StaticLease>,
+) -> Reply {
+    let mut lease = lease.into_inner();
+
+    if !ValueType::MacAddress.is_valid(&lease.mac) {
+        return Err(invalid_field("mac", &lease.mac));
+    }
+
+    let ip: Ipv4Addr = lease
+        .ip
+        .parse()
+        .map_err(|_| invalid_field("ip", &lease.ip))?;
+
+    if !lease.hostname.is_empty() && !ValueType::Hostname.is_valid(&lease.hostname) {
+        return Err(invalid_field("hostname", &lease.hostname));
+    }
+
+    let inside_range = is_in_dhcp_range(&*repository, ip)?;
+
+    if !inside_range && !lease.outside_range {
+        return Err(invalid_field("ip", &lease.ip));
+    }
+
+    lease.mac = lease.mac.to_lowercase();
+    lease.ip = ip.to_string();
+
+    update_config(PiholeFile::StaticDhcpConfig, &env, |lines| {
+        for existing in lines.iter().filter_map(|line| StaticLease::parse(line)) {
+            if existing.mac == lease.mac {
+                return Err(Error::from(ErrorKind::LeaseAlreadyExists(existing.mac)));
+            }
+
+            if existing.ip == lease.ip {
+                return Err(Error::from(ErrorKind::LeaseAlreadyExists(existing.ip)));
+            }
+        }
+
+        lines.push(lease.to_line());
+        Ok(())
+    })?;
+    restart_dns(&env)?;
+
+    if inside_range {
+        reply_success()
+    } else {
+        reply_data(json!({
+            "status": "success",
+            "warning": OUTSIDE_RANGE_WARNING
+        }))
+    }
+}
+
+/// Delete the static DHCP lease of the MAC address. The other lines of the
+/// config are kept as they are.
+#[delete("/settings/dhcp/static_leases/<mac>")]
+pub fn delete_static_lease(env: Inject<PiholeModule, Env>, _auth: User, mac: String) -> Reply {
+    update_config(PiholeFile::StaticDhcpConfig, &env, |lines| {
+        let count = lines.len();
+        lines.retain(|line| {
+            StaticLease::parse(line).map_or(true, |lease| !lease.mac.eq_ignore_ascii_case(&mac))
+        });
+
+        if lines.len() == count {
+            return Err(Error::from(ErrorKind::NotFound));
+        }
+
+        Ok(())
+    })?;
+    restart_dns(&env)?;
+
+    reply_success()
+}
+
+/// Check if the IP address is inside the DHCP range. If the range is not set,
+/// every address is inside it.
+fn is_in_dhcp_range(repository: &dyn SettingsRepository, ip: Ipv4Addr) -> Result<bool, Error> {
+    let start = repository
+        .get(SetupVarsEntry::DhcpStart)?
+        .parse::<Ipv4Addr>();
+    let end = repository.get(SetupVarsEntry::DhcpEnd)?.parse::<Ipv4Addr>();
+
+    Ok(match (start, end) {
+        (Ok(start), Ok(end)) => start <= ip && ip <= end,
+        _ => true,
+    })
+}
+
+/// Create the error for an invalid field of a lease
+fn invalid_field(field: &str, value: &str) -> Error {
+    Error::from(ErrorKind::InvalidParameter(
+        field.to_owned(),
+        value.to_owned(),
+        Vec::new(),
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{env::PiholeFile, testing::TestBuilder};
+    use rocket::http::{Method, Status};
+
+    /// The DHCP range used by the tests
+    const SETUP_VARS: &str = "DHCP_START=192.168.1.100\nDHCP_END=192.168.1.200\n";
+
+    /// A config written by hand, with odd spacing and other options
+    const HAND_WRITTEN: &str = "# Static leases\n\
+                                dhcp-host = B8:27:EB:01:02:03 , 192.168.1.150 ,pi\n\
+                                dhcp-host=192.168.1.160,aa:bb:cc:dd:ee:ff\n\
+                                dhcp-option=option:ntp-server,192.168.1.1\n\
+                                dhcp-host=printer\n";
+
+    /// Send a POST request which is rejected, and check that the config is not
+    /// changed
+    fn test_rejected(body: serde_json::Value, status: Status, error: serde_json::Value) {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/dhcp/static_leases")
+            .method(Method::Post)
+            .file(PiholeFile::SetupVars, SETUP_VARS)
+            .file_expect(PiholeFile::StaticDhcpConfig, HAND_WRITTEN, HAND_WRITTEN)
+            .body(body)
+            .expect_status(status)
+            .expect_json(json!({ "error": error }))
+            .test();
+    }
+
+    /// The leases are read in order, whatever the spacing and order of the
+    /// values
+    #[test]
+    fn get() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/dhcp/static_leases")
+            .file(PiholeFile::StaticDhcpConfig, HAND_WRITTEN)
+            .expect_json(json!([
+                { "mac": "b8:27:eb:01:02:03", "ip": "192.168.1.150", "hostname": "pi" },
+                { "mac": "aa:bb:cc:dd:ee:ff", "ip": "192.168.1.160", "hostname": "" }
+            ]))
+            .test();
+    }
+
+    /// A lease is appended, and the hand written lines are kept as they are
+    #[test]
+    fn add() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/dhcp/static_leases")
+            .method(Method::Post)
+            .file(PiholeFile::SetupVars, SETUP_VARS)
+            .file_expect(
+                PiholeFile::StaticDhcpConfig,
+                HAND_WRITTEN,
+                &format!(
+                    "{}dhcp-host=00:11:22:33:44:55,192.168.1.170,nas\n",
+                    HAND_WRITTEN
+                ),
+            )
+            .body(json!({ "mac": "00:11:22:33:44:55", "ip": "192.168.1.170", "hostname": "nas" }))
+            .expect_json(json!({ "status": "success" }))
+            .test();
+    }
+
+    /// The hostname is optional
+    #[test]
+    fn add_without_hostname() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/dhcp/static_leases")
+            .method(Method::Post)
+            .file(PiholeFile::SetupVars, SETUP_VARS)
+            .file_expect(
+                PiholeFile::StaticDhcpConfig,
+                "",
+                "dhcp-host=00:11:22:33:44:55,192.168.1.170\n",
+            )
+            .body(json!({ "mac": "00:11:22:33:44:55", "ip": "192.168.1.170" }))
+            .expect_json(json!({ "status": "success" }))
+            .test();
+    }
+
+    /// A lease outside of the DHCP range is added with a warning if it is
+    /// deliberate
+    #[test]
+    fn add_outside_range() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/dhcp/static_leases")
+            .method(Method::Post)
+            .file(PiholeFile::SetupVars, SETUP_VARS)
+            .file_expect(
+                PiholeFile::StaticDhcpConfig,
+                "",
+                "dhcp-host=00:11:22:33:44:55,192.168.1.20\n",
+            )
+            .body(json!({
+                "mac": "00:11:22:33:44:55",
+                "ip": "192.168.1.20",
+                "outside_range": true
+            }))
+            .expect_json(json!({
+                "status": "success",
+                "warning": super::OUTSIDE_RANGE_WARNING
+            }))
+            .test();
+    }
+
+    /// A lease outside of the DHCP range is rejected if it is not deliberate
+    #[test]
+    fn add_outside_range_rejected() {
+        test_rejected(
+            json!({ "mac": "00:11:22:33:44:55", "ip": "192.168.1.20" }),
+            Status::BadRequest,
+            json!({
+                "key": "invalid_parameter",
+                "message": "Invalid value 192.168.1.20 for parameter ip",
+                "data": {
+                    "parameter": "ip",
+                    "value": "192.168.1.20",
+                    "valid": []
+                }
+            }),
+        );
+    }
+
+    /// MAC addresses must be colon separated
+    #[test]
+    fn add_invalid_mac() {
+        test_rejected(
+            json!({ "mac": "00-11-22-33-44-55", "ip": "192.168.1.170" }),
+            Status::BadRequest,
+            json!({
+                "key": "invalid_parameter",
+                "message": "Invalid value 00-11-22-33-44-55 for parameter mac",
+                "data": {
+                    "parameter": "mac",
+                    "value": "00-11-22-33-44-55",
+                    "valid": []
+                }
+            }),
+        );
+    }
+
+    /// A MAC address can only have one lease
+    #[test]
+    fn add_duplicate_mac() {
+        test_rejected(
+            json!({ "mac": "AA:BB:CC:DD:EE:FF", "ip": "192.168.1.170" }),
+            Status::Conflict,
+            json!({
+                "key": "already_exists",
+                "message": "aa:bb:cc:dd:ee:ff is already assigned to a static lease",
+                "data": { "assigned": "aa:bb:cc:dd:ee:ff" }
+            }),
+        );
+    }
+
+    /// An IP address can only have one lease
+    #[test]
+    fn add_duplicate_ip() {
+        test_rejected(
+            json!({ "mac": "00:11:22:33:44:55", "ip": "192.168.1.150" }),
+            Status::Conflict,
+            json!({
+                "key": "already_exists",
+                "message": "192.168.1.150 is already assigned to a static lease",
+                "data": { "assigned": "192.168.1.150" }
+            }),
+        );
+    }
+
+    /// The lease is deleted by MAC address, and the other lines are kept as
+    /// they are
+    #[test]
+    fn delete() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/dhcp/static_leases/b8:27:eb:01:02:03")
+            .method(Method::Delete)
+            .file_expect(
+                PiholeFile::StaticDhcpConfig,
+                HAND_WRITTEN,
+                "# Static leases\n\
+                 dhcp-host=192.168.1.160,aa:bb:cc:dd:ee:ff\n\
+                 dhcp-option=option:ntp-server,192.168.1.1\n\
+                 dhcp-host=printer\n",
+            )
+            .expect_json(json!({ "status": "success" }))
+            .test();
+    }
+
+    /// Deleting a MAC address without a lease is an error
+    #[test]
+    fn delete_not_found() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/dhcp/static_leases/00:11:22:33:44:55")
+            .method(Method::Delete)
+            .file_expect(PiholeFile::StaticDhcpConfig, HAND_WRITTEN, HAND_WRITTEN)
+            .expect_status(Status::NotFound)
+            .expect_json(json!({
+                "error": {
+                    "key": "not_found",
+                    "message": "Not found",
+                    "data": null
+                }
+            }))
+            .test();
+    }
+}
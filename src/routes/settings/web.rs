@@ -9,9 +9,8 @@
 // Please see LICENSE file for your rights under this license.
 
 use crate::{
-    env::Env,
     routes::auth::User,
-    services::PiholeModule,
+    services::{settings::SettingsRepository, PiholeModule},
     settings::{ConfigEntry, SetupVarsEntry},
     util::{reply_data, reply_success, Error, ErrorKind, Reply},
 };
@@ -20,10 +19,10 @@ use shaku_rocket::Inject;
 
 /// Get web interface settings
 #[get("/settings/web")]
-pub fn get_web(env: Inject<PiholeModule, Env>) -> Reply {
+pub fn get_web(repository: Inject<PiholeModule, dyn SettingsRepository>) -> Reply {
     let settings = WebSettings {
-        layout: SetupVarsEntry::WebLayout.read(&env)?,
-        language: SetupVarsEntry::WebLanguage.read(&env)?,
+        layout: repository.get(SetupVarsEntry::WebLayout)?,
+        language: repository.get(SetupVarsEntry::WebLanguage)?,
     };
 
     reply_data(settings)
@@ -31,15 +30,19 @@ pub fn get_web(env: Inject<PiholeModule, Env>) -> Reply {
 
 /// Update web interface settings
 #[put("/settings/web", data = "<settings>")]
-pub fn put_web(_auth: User, env: Inject<PiholeModule, Env>, settings: Json<WebSettings>) -> Reply {
+pub fn put_web(
+    _auth: User,
+    repository: Inject<PiholeModule, dyn SettingsRepository>,
+    settings: Json<WebSettings>,
+) -> Reply {
     let settings = settings.into_inner();
 
     if !settings.is_valid() {
         return Err(Error::from(ErrorKind::InvalidSettingValue));
     }
 
-    SetupVarsEntry::WebLayout.write(&settings.layout, &env)?;
-    SetupVarsEntry::WebLanguage.write(&settings.language, &env)?;
+    repository.set(SetupVarsEntry::WebLayout, &settings.layout)?;
+    repository.set(SetupVarsEntry::WebLanguage, &settings.language)?;
 
     reply_success()
 }
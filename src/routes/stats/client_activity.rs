@@ -0,0 +1,282 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Client Activity Endpoint
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    env::Env,
+    ftl::{FtlMemory, FtlRedactor},
+    routes::{
+        auth::User,
+        stats::{
+            clients::{filter_ftl_clients, ClientParams},
+            common::get_current_over_time_slot,
+            over_time_history::effective_interval,
+            time_zone::BucketTimeZone,
+        },
+    },
+    services::{
+        exclusions::{ExclusionService, Exclusions},
+        privacy::PrivacyService,
+        PiholeModule,
+    },
+    settings::FtlPrivacyLevel,
+    util::{reply_result, Error, Reply},
+};
+use rocket::State;
+use shaku_rocket::Inject;
+use std::{
+    collections::{HashMap, HashSet},
+    ptr,
+};
+
+pub use client_activity as route;
+
+/// Get the number of distinct active clients over time. If an interval (in
+/// seconds) is given, the FTL slots are grouped into buckets of that size,
+/// aligned to the `timezone` or `utc_offset` like the over time history.
+#[get("/stats/clients/activity?<interval>&<timezone>&<utc_offset>")]
+#[allow(clippy::too_many_arguments)]
+pub fn client_activity(
+    _auth: User,
+    ftl_memory: &State<FtlMemory>,
+    env: Inject<PiholeModule, Env>,
+    privacy: Inject<PiholeModule, dyn PrivacyService>,
+    exclusions: Inject<PiholeModule, dyn ExclusionService>,
+    interval: Option<usize>,
+    timezone: Option<String>,
+    utc_offset: Option<i32>,
+) -> Reply {
+    reply_result(get_client_activity(
+        ftl_memory,
+        privacy.redactor()?,
+        &*exclusions.exclusions()?,
+        effective_interval(interval),
+        BucketTimeZone::from_params(timezone.as_deref(), utc_offset, &env)?,
+    ))
+}
+
+/// Represents the reply structure for the client activity, along with the
+/// interval of the data and the current UTC offset of the buckets in seconds
+#[derive(Serialize)]
+#[cfg_attr(test, derive(Debug, PartialEq))]
+pub struct ClientActivityReply {
+    pub interval: usize,
+    pub utc_offset: i32,
+    pub activity: Vec<ClientActivityItem>,
+}
+
+/// Represents the number of distinct clients which made at least one query in
+/// a bucket
+#[derive(Serialize)]
+#[cfg_attr(test, derive(Debug, PartialEq))]
+pub struct ClientActivityItem {
+    pub timestamp: u64,
+    pub clients: usize,
+}
+
+/// Count the distinct clients in each bucket. FTL only keeps query counts per
+/// client and slot, so the in-memory queries are used to find which clients
+/// were active. Hidden and excluded clients are not counted.
+fn get_client_activity(
+    ftl_memory: &FtlMemory,
+    redactor: FtlRedactor,
+    exclusions: &Exclusions,
+    interval: usize,
+    time_zone: BucketTimeZone,
+) -> Result<ClientActivityReply, Error> {
+    let utc_offset = time_zone.current_offset();
+
+    // Check if client details are private
+    if !redactor.allows(FtlPrivacyLevel::HideDomainsAndClients) {
+        return Ok(ClientActivityReply {
+            interval,
+            utc_offset,
+            activity: Vec::new(),
+        });
+    }
+
+    let lock = ftl_memory.lock()?;
+    let counters = ftl_memory.counters(&lock)?;
+    let over_time = ftl_memory.over_time(&lock)?;
+    let queries = ftl_memory.queries(&lock)?;
+    let ftl_clients = ftl_memory.clients(&lock)?;
+
+    // Find the indices of the clients which are allowed to be counted
+    let clients = filter_ftl_clients(
+        ftl_memory,
+        &lock,
+        &ftl_clients,
+        redactor,
+        exclusions,
+        ClientParams::default(),
+    )?;
+    let allowed_clients: HashSet<usize> = ftl_clients
+        .iter()
+        .enumerate()
+        .filter(|(_, client)| clients.iter().any(|allowed| ptr::eq(*allowed, *client)))
+        .map(|(i, _)| i)
+        .collect();
+
+    // Find the distinct clients of each slot. The queries store the index of
+    // their over time slot.
+    let mut slot_clients: HashMap<usize, HashSet<usize>> = HashMap::new();
+
+    for query in queries.iter().take(counters.total_queries as usize) {
+        let client_id = query.client_id as usize;
+
+        if redactor.hides_query_client(query) || !allowed_clients.contains(&client_id) {
+            continue;
+        }
+
+        slot_clients
+            .entry(query.time_index as usize)
+            .or_default()
+            .insert(client_id);
+    }
+
+    let slots = over_time
+        .iter()
+        // Take all of the slots including the current slot
+        .take(get_current_over_time_slot(&over_time) + 1)
+        .enumerate()
+        // Skip the overTime slots without any data
+        .skip_while(|(_, time)| time.total_queries <= 0 && time.blocked_queries <= 0)
+        .map(|(i, time)| {
+            (
+                time.timestamp as u64,
+                slot_clients.remove(&i).unwrap_or_default(),
+            )
+        });
+
+    let buckets = time_zone.group_slots(slots, interval, |bucket_clients, active| {
+        bucket_clients.extend(active)
+    });
+
+    Ok(ClientActivityReply {
+        interval,
+        utc_offset,
+        activity: buckets
+            .into_iter()
+            .map(|(timestamp, clients)| ClientActivityItem {
+                timestamp,
+                clients: clients.len(),
+            })
+            .collect(),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        env::PiholeFile,
+        ftl::{FtlMemory, FtlQueryStatus},
+        testing::{TestBuilder, TestFtlMemoryBuilder},
+    };
+
+    /// Three slots. 10.1.1.1 is active in the first two slots, 10.1.1.2 in
+    /// the first and last slots, and 10.1.1.3 only in the last slot.
+    fn test_memory() -> FtlMemory {
+        let mut builder = TestFtlMemoryBuilder::new();
+        let client1 = builder.client("10.1.1.1", "laptop");
+        let client2 = builder.unnamed_client("10.1.1.2");
+        let client3 = builder.unnamed_client("10.1.1.3");
+        let upstream = builder.upstream("8.8.8.8", "google-public-dns-a.google.com");
+
+        for _ in 0..3 {
+            builder.query(
+                1000,
+                "example.com",
+                client1,
+                FtlQueryStatus::Forward,
+                Some(upstream),
+            );
+        }
+        builder.query(1000, "ads.com", client2, FtlQueryStatus::Gravity, None);
+        builder.query(1600, "example.com", client1, FtlQueryStatus::Cache, None);
+        builder.query(2200, "ads.com", client2, FtlQueryStatus::Gravity, None);
+        builder.query(2200, "example.com", client3, FtlQueryStatus::Cache, None);
+
+        builder.build()
+    }
+
+    /// Each slot counts the clients which made a query in it, once per client
+    #[test]
+    fn default_params() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/clients/activity")
+            .ftl_memory(test_memory())
+            .file(PiholeFile::SetupVars, "")
+            .file(PiholeFile::FtlConfig, "")
+            .expect_json(json!({
+                "interval": 600,
+                "utc_offset": 0,
+                "activity": [
+                    { "timestamp": 900, "clients": 2 },
+                    { "timestamp": 1500, "clients": 1 },
+                    { "timestamp": 2100, "clients": 2 }
+                ]
+            }))
+            .test();
+    }
+
+    /// Excluded clients are not counted
+    #[test]
+    fn excluded_client() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/clients/activity")
+            .ftl_memory(test_memory())
+            .file(PiholeFile::SetupVars, "API_EXCLUDE_CLIENTS=10.1.1.3")
+            .file(PiholeFile::FtlConfig, "")
+            .expect_json(json!({
+                "interval": 600,
+                "utc_offset": 0,
+                "activity": [
+                    { "timestamp": 900, "clients": 2 },
+                    { "timestamp": 1500, "clients": 1 },
+                    { "timestamp": 2100, "clients": 1 }
+                ]
+            }))
+            .test();
+    }
+
+    /// Clients active in several grouped slots are only counted once
+    #[test]
+    fn interval() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/clients/activity?interval=1800")
+            .ftl_memory(test_memory())
+            .file(PiholeFile::SetupVars, "API_EXCLUDE_CLIENTS=10.1.1.3")
+            .file(PiholeFile::FtlConfig, "")
+            .expect_json(json!({
+                "interval": 1800,
+                "utc_offset": 0,
+                "activity": [
+                    { "timestamp": 900, "clients": 2 },
+                    { "timestamp": 2700, "clients": 1 }
+                ]
+            }))
+            .test();
+    }
+
+    /// No activity is shown if the privacy level hides clients
+    #[test]
+    fn private() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/clients/activity")
+            .ftl_memory(test_memory())
+            .file(PiholeFile::SetupVars, "")
+            .file(PiholeFile::FtlConfig, "PRIVACYLEVEL=2")
+            .expect_json(json!({
+                "interval": 600,
+                "utc_offset": 0,
+                "activity": []
+            }))
+            .test();
+    }
+}
@@ -0,0 +1,420 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Client Detail Endpoint
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    ftl::{ClientReply, FtlMemory, FtlQueryType, FtlRedactor},
+    routes::{
+        auth::User,
+        stats::{
+            clients::{filter_ftl_clients, ClientParams},
+            common::get_current_over_time_slot,
+            query_types::QueryTypeReply,
+            top_domains::TopDomainItemReply,
+            top_sort::TopSort,
+        },
+    },
+    services::{
+        exclusions::{ExclusionService, Exclusions},
+        privacy::PrivacyService,
+        PiholeModule,
+    },
+    settings::{FtlPrivacyLevel, HIDDEN_DOMAIN},
+    util::{reply_result, Error, Reply},
+};
+use rocket::State;
+use shaku_rocket::Inject;
+use std::{collections::HashMap, ptr};
+
+pub use client_detail as route;
+
+/// The number of domains in each of the top lists
+const TOP_DOMAINS_LIMIT: usize = 10;
+
+/// Get the statistics of a single client, by its IP address or name
+#[get("/stats/clients/<client>")]
+pub fn client_detail(
+    _auth: User,
+    ftl_memory: &State<FtlMemory>,
+    privacy: Inject<PiholeModule, dyn PrivacyService>,
+    exclusions: Inject<PiholeModule, dyn ExclusionService>,
+    client: String,
+) -> Reply {
+    reply_result(get_client_detail(
+        ftl_memory,
+        privacy.redactor()?,
+        &*exclusions.exclusions()?,
+        &client,
+    ))
+}
+
+/// Represents the reply structure for the statistics of a client. If several
+/// clients have the requested name, their statistics are combined.
+#[derive(Serialize)]
+#[cfg_attr(test, derive(Debug, PartialEq))]
+pub struct ClientDetailReply {
+    pub clients: Vec<ClientReply>,
+    pub total_queries: usize,
+    pub blocked_queries: usize,
+    pub top_domains: Vec<TopDomainItemReply>,
+    pub top_blocked: Vec<TopDomainItemReply>,
+    pub query_types: Vec<QueryTypeReply>,
+    pub over_time: Vec<ClientOverTimeItem>,
+}
+
+/// Represents the number of queries of the client in an overTime slot
+#[derive(Serialize)]
+#[cfg_attr(test, derive(Debug, PartialEq))]
+pub struct ClientOverTimeItem {
+    pub timestamp: u64,
+    pub count: usize,
+}
+
+/// Find the clients with the IP address or name, and combine their
+/// statistics. Unknown, hidden, and excluded clients get an empty reply.
+fn get_client_detail(
+    ftl_memory: &FtlMemory,
+    redactor: FtlRedactor,
+    exclusions: &Exclusions,
+    client: &str,
+) -> Result<ClientDetailReply, Error> {
+    let lock = ftl_memory.lock()?;
+    let counters = ftl_memory.counters(&lock)?;
+    let strings = ftl_memory.strings(&lock)?;
+    let over_time = ftl_memory.over_time(&lock)?;
+    let queries = ftl_memory.queries(&lock)?;
+    let domains = ftl_memory.domains(&lock)?;
+    let ftl_clients = ftl_memory.clients(&lock)?;
+
+    // Clients which are hidden by the privacy level or excluded can not be
+    // found. Inactive clients are included, so they get an empty structure
+    // along with their name.
+    let allowed_clients = filter_ftl_clients(
+        ftl_memory,
+        &lock,
+        &ftl_clients,
+        redactor,
+        exclusions,
+        ClientParams {
+            inactive: Some(true),
+        },
+    )?;
+
+    // Find the indices of the matching clients. The queries store the index
+    // of their client.
+    let client_ids: Vec<usize> = ftl_clients
+        .iter()
+        .enumerate()
+        .filter(|(_, ftl_client)| {
+            allowed_clients
+                .iter()
+                .any(|allowed| ptr::eq(*allowed, *ftl_client))
+        })
+        .filter(|(_, ftl_client)| {
+            ftl_client.get_ip(&strings) == client
+                || ftl_client
+                    .get_name(&strings)
+                    .map_or(false, |name| name.eq_ignore_ascii_case(client))
+        })
+        .map(|(i, _)| i)
+        .collect();
+    let matched_clients: Vec<_> = client_ids.iter().map(|&i| &ftl_clients[i]).collect();
+
+    // Count the domains and query types of the client's queries
+    let mut domain_counts: HashMap<usize, (usize, usize)> = HashMap::new();
+    let mut type_counts: HashMap<FtlQueryType, usize> = HashMap::new();
+    let show_domains = redactor.allows(FtlPrivacyLevel::HideDomains);
+
+    for query in queries.iter().take(counters.total_queries as usize) {
+        let query_privacy = redactor.query_level(query);
+
+        if !client_ids.contains(&(query.client_id as usize)) || query_privacy.hides_clients() {
+            continue;
+        }
+
+        *type_counts.entry(query.query_type).or_default() += 1;
+
+        if !show_domains || query_privacy.hides_domains() {
+            continue;
+        }
+
+        let (permitted, blocked) = domain_counts.entry(query.domain_id as usize).or_default();
+        if query.is_blocked() {
+            *blocked += 1;
+        } else {
+            *permitted += 1;
+        }
+    }
+
+    // Build the top lists, without hidden or excluded domains
+    let sort = TopSort::from_params(None, None, None)?;
+    let top_list = |blocked: bool| {
+        let mut items: Vec<TopDomainItemReply> = domain_counts
+            .iter()
+            .filter_map(|(&domain_id, &(permitted, blocked_count))| {
+                let count = if blocked { blocked_count } else { permitted };
+                let domain = domains.get(domain_id)?.get_domain(&strings);
+
+                if count == 0 || domain == HIDDEN_DOMAIN || exclusions.excludes_domain(domain) {
+                    return None;
+                }
+
+                Some(TopDomainItemReply {
+                    domain: domain.to_owned(),
+                    count,
+                })
+            })
+            .collect();
+
+        items.sort_by(|a, b| sort.compare((a.count, &a.domain), (b.count, &b.domain)));
+        items.truncate(TOP_DOMAINS_LIMIT);
+        items
+    };
+    let top_domains = top_list(false);
+    let top_blocked = top_list(true);
+
+    let query_types = if matched_clients.is_empty() {
+        Vec::new()
+    } else {
+        FtlQueryType::variants()
+            .iter()
+            .map(|&variant| QueryTypeReply {
+                name: variant.get_name(),
+                count: type_counts.get(&variant).copied().unwrap_or_default(),
+            })
+            .collect()
+    };
+
+    // Sum the client's queries in each overTime slot
+    let over_time: Vec<ClientOverTimeItem> = if matched_clients.is_empty() {
+        Vec::new()
+    } else {
+        over_time
+            .iter()
+            // Take all of the slots including the current slot
+            .take(get_current_over_time_slot(&over_time) + 1)
+            .enumerate()
+            // Skip the overTime slots without any data
+            .skip_while(|(_, time)| time.total_queries <= 0 && time.blocked_queries <= 0)
+            .map(|(i, time)| ClientOverTimeItem {
+                timestamp: time.timestamp as u64,
+                count: matched_clients
+                    .iter()
+                    .map(|client| *client.over_time.get(i).unwrap_or(&0) as usize)
+                    .sum(),
+            })
+            .collect()
+    };
+
+    Ok(ClientDetailReply {
+        clients: matched_clients
+            .iter()
+            .map(|client| client.as_reply(&strings))
+            .collect(),
+        total_queries: matched_clients
+            .iter()
+            .map(|client| client.query_count as usize)
+            .sum(),
+        blocked_queries: matched_clients
+            .iter()
+            .map(|client| client.blocked_count as usize)
+            .sum(),
+        top_domains,
+        top_blocked,
+        query_types,
+        over_time,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::get_client_detail;
+    use crate::{
+        env::PiholeFile,
+        ftl::{FtlMemory, FtlQueryStatus, FtlQueryType, FtlRedactor},
+        services::exclusions::Exclusions,
+        settings::FtlPrivacyLevel,
+        testing::{TestBuilder, TestFtlMemoryBuilder},
+    };
+
+    /// Two clients. The laptop has permitted, blocked, and cached queries
+    /// over two slots, and the other client has a single query.
+    fn test_memory() -> FtlMemory {
+        let mut builder = TestFtlMemoryBuilder::new();
+        let laptop = builder.client("10.1.1.1", "laptop");
+        let phone = builder.unnamed_client("10.1.1.2");
+        let upstream = builder.upstream("8.8.8.8", "google-public-dns-a.google.com");
+
+        for _ in 0..2 {
+            builder.query(
+                1000,
+                "example.com",
+                laptop,
+                FtlQueryStatus::Forward,
+                Some(upstream),
+            );
+        }
+        builder.query(1000, "ads.com", laptop, FtlQueryStatus::Gravity, None);
+        builder.typed_query(
+            1600,
+            FtlQueryType::AAAA,
+            "github.com",
+            laptop,
+            FtlQueryStatus::Cache,
+            None,
+        );
+        builder.query(1600, "example.org", phone, FtlQueryStatus::Cache, None);
+
+        builder.build()
+    }
+
+    /// All of the statistics of the client are combined
+    #[test]
+    fn by_ip() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/clients/10.1.1.1")
+            .ftl_memory(test_memory())
+            .file(PiholeFile::SetupVars, "")
+            .file(PiholeFile::FtlConfig, "")
+            .expect_json(json!({
+                "clients": [{ "name": "laptop", "ip": "10.1.1.1" }],
+                "total_queries": 4,
+                "blocked_queries": 1,
+                "top_domains": [
+                    { "domain": "example.com", "count": 2 },
+                    { "domain": "github.com", "count": 1 }
+                ],
+                "top_blocked": [{ "domain": "ads.com", "count": 1 }],
+                "query_types": [
+                    { "name": "A", "count": 3 },
+                    { "name": "AAAA", "count": 1 },
+                    { "name": "ANY", "count": 0 },
+                    { "name": "SRV", "count": 0 },
+                    { "name": "SOA", "count": 0 },
+                    { "name": "PTR", "count": 0 },
+                    { "name": "TXT", "count": 0 }
+                ],
+                "over_time": [
+                    { "timestamp": 900, "count": 3 },
+                    { "timestamp": 1500, "count": 1 }
+                ]
+            }))
+            .test();
+    }
+
+    /// Clients can also be found by their name, ignoring case
+    #[test]
+    fn by_name() {
+        let memory = test_memory();
+        let exclusions = Exclusions::new(Vec::new(), Vec::new());
+
+        assert_eq!(
+            get_client_detail(
+                &memory,
+                FtlRedactor::new(FtlPrivacyLevel::ShowAll),
+                &exclusions,
+                "LAPTOP"
+            )
+            .unwrap(),
+            get_client_detail(
+                &memory,
+                FtlRedactor::new(FtlPrivacyLevel::ShowAll),
+                &exclusions,
+                "10.1.1.1"
+            )
+            .unwrap()
+        );
+    }
+
+    /// Unknown clients get an empty structure
+    #[test]
+    fn unknown_client() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/clients/10.9.9.9")
+            .ftl_memory(test_memory())
+            .file(PiholeFile::SetupVars, "")
+            .file(PiholeFile::FtlConfig, "")
+            .expect_json(json!({
+                "clients": [],
+                "total_queries": 0,
+                "blocked_queries": 0,
+                "top_domains": [],
+                "top_blocked": [],
+                "query_types": [],
+                "over_time": []
+            }))
+            .test();
+    }
+
+    /// Excluded clients are treated as unknown clients
+    #[test]
+    fn excluded_client() {
+        let exclusions = Exclusions::new(Vec::new(), vec!["laptop".to_owned()]);
+        let reply = get_client_detail(
+            &test_memory(),
+            FtlRedactor::new(FtlPrivacyLevel::ShowAll),
+            &exclusions,
+            "10.1.1.1",
+        )
+        .unwrap();
+
+        assert!(reply.clients.is_empty());
+        assert_eq!(reply.total_queries, 0);
+        assert!(reply.top_domains.is_empty());
+        assert!(reply.over_time.is_empty());
+    }
+
+    /// Excluded domains are not in the top lists
+    #[test]
+    fn excluded_domain() {
+        let exclusions = Exclusions::new(vec!["github.com".to_owned()], Vec::new());
+        let reply = get_client_detail(
+            &test_memory(),
+            FtlRedactor::new(FtlPrivacyLevel::ShowAll),
+            &exclusions,
+            "10.1.1.1",
+        )
+        .unwrap();
+
+        let top_domains: Vec<&str> = reply
+            .top_domains
+            .iter()
+            .map(|item| item.domain.as_str())
+            .collect();
+        assert_eq!(top_domains, vec!["example.com"]);
+    }
+
+    /// If the privacy level hides domains, the counts are still shown but the
+    /// top lists are empty. If it hides clients, the client can not be found.
+    #[test]
+    fn privacy() {
+        let exclusions = Exclusions::new(Vec::new(), Vec::new());
+        let hide_domains = get_client_detail(
+            &test_memory(),
+            FtlRedactor::new(FtlPrivacyLevel::HideDomains),
+            &exclusions,
+            "10.1.1.1",
+        )
+        .unwrap();
+        let hide_clients = get_client_detail(
+            &test_memory(),
+            FtlRedactor::new(FtlPrivacyLevel::HideDomainsAndClients),
+            &exclusions,
+            "10.1.1.1",
+        )
+        .unwrap();
+
+        assert_eq!(hide_domains.total_queries, 4);
+        assert_eq!(hide_domains.over_time.len(), 2);
+        assert!(hide_domains.top_domains.is_empty());
+        assert!(hide_domains.top_blocked.is_empty());
+        assert!(hide_clients.clients.is_empty());
+        assert_eq!(hide_clients.total_queries, 0);
+    }
+}
@@ -10,13 +10,18 @@
 
 use crate::{
     env::Env,
-    ftl::{ClientReply, FtlClient, FtlMemory, ShmLockGuard},
+    ftl::{ClientReply, FtlClient, FtlMemory, FtlRedactor, ShmLockGuard},
     routes::{
         auth::User,
-        stats::common::{remove_excluded_clients, remove_hidden_clients},
+        stats::common::{get_client_name, remove_excluded_clients},
     },
-    services::PiholeModule,
-    settings::{ConfigEntry, FtlConfEntry, FtlPrivacyLevel},
+    services::{
+        exclusions::{ExclusionService, Exclusions},
+        hostnames::HostnameCache,
+        privacy::PrivacyService,
+        PiholeModule,
+    },
+    settings::FtlPrivacyLevel,
     util::{reply_result, Error, Reply},
 };
 use rocket::State;
@@ -30,21 +35,37 @@ pub fn clients(
     _auth: User,
     ftl_memory: &State<FtlMemory>,
     env: Inject<PiholeModule, Env>,
+    hostnames: Inject<PiholeModule, HostnameCache>,
+    privacy: Inject<PiholeModule, dyn PrivacyService>,
+    exclusions: Inject<PiholeModule, dyn ExclusionService>,
     params: ClientParams,
 ) -> Reply {
-    reply_result(get_clients(ftl_memory, &env, params))
+    let redactor = privacy.redactor()?;
+    let exclusions = exclusions.exclusions()?;
+
+    reply_result(get_clients(
+        ftl_memory,
+        &env,
+        &hostnames,
+        redactor,
+        &exclusions,
+        params,
+    ))
 }
 
 /// The possible GET parameters for `/stats/clients`
 #[derive(FromForm, Default)]
 pub struct ClientParams {
-    inactive: Option<bool>,
+    pub inactive: Option<bool>,
 }
 
 /// Get client data for API output according to the parameters
 fn get_clients(
     ftl_memory: &FtlMemory,
     env: &Env,
+    hostnames: &HostnameCache,
+    redactor: FtlRedactor,
+    exclusions: &Exclusions,
     params: ClientParams,
 ) -> Result<Vec<ClientReply>, Error> {
     let lock = ftl_memory.lock()?;
@@ -52,9 +73,12 @@ fn get_clients(
     let clients = ftl_memory.clients(&lock)?;
 
     Ok(
-        filter_ftl_clients(ftl_memory, &lock, &clients, env, params)?
+        filter_ftl_clients(ftl_memory, &lock, &clients, redactor, exclusions, params)?
             .iter()
-            .map(|client| client.as_reply(&strings))
+            .map(|client| ClientReply {
+                name: get_client_name(client, &strings, env, hostnames),
+                ip: client.get_ip(&strings).to_owned(),
+            })
             .collect::<Vec<ClientReply>>(),
     )
 }
@@ -65,13 +89,12 @@ pub fn filter_ftl_clients<'a>(
     ftl_memory: &'a FtlMemory,
     lock: &ShmLockGuard<'a>,
     clients: &'a [FtlClient],
-    env: &Env,
+    redactor: FtlRedactor,
+    exclusions: &Exclusions,
     params: ClientParams,
 ) -> Result<Vec<&'a FtlClient>, Error> {
     // Check if client details are private
-    if FtlConfEntry::PrivacyLevel.read_as::<FtlPrivacyLevel>(&env)?
-        >= FtlPrivacyLevel::HideDomainsAndClients
-    {
+    if !redactor.allows(FtlPrivacyLevel::HideDomainsAndClients) {
         return Ok(Vec::new());
     }
 
@@ -85,8 +108,8 @@ pub fn filter_ftl_clients<'a>(
         .collect();
 
     // Ignore hidden and excluded clients
-    remove_hidden_clients(&mut clients, &strings);
-    remove_excluded_clients(&mut clients, &env, &strings)?;
+    redactor.retain_clients(&mut clients, &strings);
+    remove_excluded_clients(&mut clients, exclusions, &strings);
 
     // Ignore inactive clients by default (retain active clients)
     if !params.inactive.unwrap_or(false) {
@@ -100,43 +123,28 @@ pub fn filter_ftl_clients<'a>(
 mod test {
     use crate::{
         env::PiholeFile,
-        ftl::{FtlClient, FtlCounters, FtlMemory, FtlSettings},
-        testing::TestBuilder,
+        ftl::{FtlClient, FtlCounters, FtlMemory, FtlQueryStatus, FtlSettings},
+        testing::{TestBuilder, TestFtlMemoryBuilder},
     };
     use std::collections::HashMap;
 
     /// There are 6 clients, two inactive, one hidden, and two with names.
     fn test_data() -> FtlMemory {
-        let mut strings = HashMap::new();
-        strings.insert(1, "10.1.1.1".to_owned());
-        strings.insert(2, "client1".to_owned());
-        strings.insert(3, "10.1.1.2".to_owned());
-        strings.insert(4, "10.1.1.3".to_owned());
-        strings.insert(5, "client3".to_owned());
-        strings.insert(6, "10.1.1.4".to_owned());
-        strings.insert(7, "10.1.1.5".to_owned());
-        strings.insert(8, "0.0.0.0".to_owned());
-
-        FtlMemory::Test {
-            clients: vec![
-                FtlClient::new(1, 0, 1, Some(2)),
-                FtlClient::new(1, 0, 3, None),
-                FtlClient::new(1, 0, 4, Some(5)),
-                FtlClient::new(1, 0, 6, None),
-                FtlClient::new(0, 0, 7, None),
-                FtlClient::new(0, 0, 8, None),
-            ],
-            domains: Vec::new(),
-            over_time: Vec::new(),
-            strings,
-            upstreams: Vec::new(),
-            queries: Vec::new(),
-            counters: FtlCounters {
-                total_clients: 6,
-                ..FtlCounters::default()
-            },
-            settings: FtlSettings::default(),
+        let mut builder = TestFtlMemoryBuilder::new();
+        let active_clients = [
+            builder.client("10.1.1.1", "client1"),
+            builder.unnamed_client("10.1.1.2"),
+            builder.client("10.1.1.3", "client3"),
+            builder.unnamed_client("10.1.1.4"),
+        ];
+        builder.unnamed_client("10.1.1.5");
+        builder.unnamed_client("0.0.0.0");
+
+        for (i, client) in active_clients.iter().enumerate() {
+            builder.query(i, "domain.com", *client, FtlQueryStatus::Cache, None);
         }
+
+        builder.build()
     }
 
     /// The default behavior lists all active clients
@@ -149,9 +157,9 @@ mod test {
             .file(PiholeFile::FtlConfig, "")
             .expect_json(json!([
                 { "name": "client1", "ip": "10.1.1.1" },
-                { "name": "",        "ip": "10.1.1.2" },
+                { "name": null,      "ip": "10.1.1.2" },
                 { "name": "client3", "ip": "10.1.1.3" },
-                { "name": "",        "ip": "10.1.1.4" }
+                { "name": null,      "ip": "10.1.1.4" }
             ]))
             .test();
     }
@@ -177,10 +185,10 @@ mod test {
             .ftl_memory(test_data())
             .expect_json(json!([
                 { "name": "client1", "ip": "10.1.1.1" },
-                { "name": "",        "ip": "10.1.1.2" },
+                { "name": null,      "ip": "10.1.1.2" },
                 { "name": "client3", "ip": "10.1.1.3" },
-                { "name": "",        "ip": "10.1.1.4" },
-                { "name": "",        "ip": "10.1.1.5" }
+                { "name": null,      "ip": "10.1.1.4" },
+                { "name": null,      "ip": "10.1.1.5" }
             ]))
             .test();
     }
@@ -198,7 +206,46 @@ mod test {
             .file(PiholeFile::FtlConfig, "")
             .expect_json(json!([
                 { "name": "client1", "ip": "10.1.1.1" },
-                { "name": "",        "ip": "10.1.1.4" }
+                { "name": null,      "ip": "10.1.1.4" }
+            ]))
+            .test();
+    }
+
+    /// Clients without a name, including clients whose name is missing from
+    /// the strings, have a null name
+    #[test]
+    fn missing_names() {
+        let mut strings = HashMap::new();
+        strings.insert(1, "10.1.1.1".to_owned());
+        strings.insert(2, "client1".to_owned());
+        strings.insert(3, "10.1.1.2".to_owned());
+        strings.insert(4, "10.1.1.3".to_owned());
+
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/clients")
+            .ftl_memory(FtlMemory::Test {
+                clients: vec![
+                    FtlClient::new(1, 0, 1, Some(2)),
+                    FtlClient::new(1, 0, 3, None),
+                    FtlClient::new(1, 0, 4, Some(5)),
+                ],
+                domains: Vec::new(),
+                over_time: Vec::new(),
+                strings,
+                upstreams: Vec::new(),
+                queries: Vec::new(),
+                counters: FtlCounters {
+                    total_clients: 3,
+                    ..FtlCounters::default()
+                },
+                settings: FtlSettings::default(),
+            })
+            .file(PiholeFile::SetupVars, "")
+            .file(PiholeFile::FtlConfig, "")
+            .expect_json(json!([
+                { "name": "client1", "ip": "10.1.1.1" },
+                { "name": null,      "ip": "10.1.1.2" },
+                { "name": null,      "ip": "10.1.1.3" }
             ]))
             .test();
     }
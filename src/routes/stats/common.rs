@@ -11,101 +11,79 @@
 use crate::{
     env::Env,
     ftl::{FtlClient, FtlDomain, FtlOverTime, FtlStrings, OVERTIME_SLOTS},
-    settings::{ConfigEntry, SetupVarsEntry},
+    services::{exclusions::Exclusions, hostnames::HostnameCache, settings::SettingsRepository},
+    settings::SetupVarsEntry,
     util::Error,
 };
-use std::{
-    collections::HashSet,
-    time::{SystemTime, UNIX_EPOCH},
-};
-
-/// The designated hidden domain
-pub const HIDDEN_DOMAIN: &str = "hidden";
-
-/// The designated hidden client IP address
-pub const HIDDEN_CLIENT: &str = "0.0.0.0";
+use std::time::{SystemTime, UNIX_EPOCH};
 
-/// Remove clients from the `clients` vector if they show up in
-/// [`SetupVarsEntry::ApiExcludeClients`].
-///
-/// [`SetupVarsEntry::ApiExcludeClients`]:
-/// ../../../settings/entries/enum.SetupVarsEntry.html#variant.ApiExcludeClients
+/// Remove clients from the `clients` vector if they are excluded by their IP
+/// address or name
 pub fn remove_excluded_clients(
     clients: &mut Vec<&FtlClient>,
-    env: &Env,
+    exclusions: &Exclusions,
     strings: &FtlStrings,
-) -> Result<(), Error> {
-    let excluded_clients = get_excluded_clients(env)?;
-    let excluded_clients: HashSet<&str> = excluded_clients.iter().map(String::as_str).collect();
-
-    if !excluded_clients.is_empty() {
+) {
+    if !exclusions.is_empty() {
         // Only retain clients which do not appear in the exclusion list
         clients.retain(|client| {
-            let ip = client.get_ip(strings);
-            let name = client.get_name(strings).unwrap_or_default().to_lowercase();
-
-            !excluded_clients.contains(&ip) && !excluded_clients.contains(&name.as_str())
+            !exclusions.excludes_client(client.get_ip(strings), client.get_name(strings))
         })
     }
-
-    Ok(())
 }
 
 /// Get the clients from [`SetupVarsEntry::ApiExcludeClients`] in lowercase.
 ///
 /// [`SetupVarsEntry::ApiExcludeClients`]:
 /// ../../../settings/entries/enum.SetupVarsEntry.html#variant.ApiExcludeClients
-pub fn get_excluded_clients(env: &Env) -> Result<Vec<String>, Error> {
-    Ok(SetupVarsEntry::ApiExcludeClients
-        .read_list(env)?
+pub fn get_excluded_clients(settings: &dyn SettingsRepository) -> Result<Vec<String>, Error> {
+    Ok(settings
+        .get_list(SetupVarsEntry::ApiExcludeClients)?
         .into_iter()
         .map(|s| s.to_lowercase())
         .collect())
 }
 
-/// Remove domains from the `domains` vector if they show up in
-/// [`SetupVarsEntry::ApiExcludeDomains`].
-///
-/// [`SetupVarsEntry::ApiExcludeDomains`]:
-/// ../../../settings/entries/enum.SetupVarsEntry.html#variant.ApiExcludeDomains
+/// Remove domains from the `domains` vector if they or a parent domain are
+/// excluded
 pub fn remove_excluded_domains(
     domains: &mut Vec<&FtlDomain>,
-    env: &Env,
+    exclusions: &Exclusions,
     strings: &FtlStrings,
-) -> Result<(), Error> {
-    let excluded_domains: Vec<String> = get_excluded_domains(env)?;
-    let excluded_domains: HashSet<&str> = excluded_domains.iter().map(String::as_str).collect();
-
-    if !excluded_domains.is_empty() {
+) {
+    if !exclusions.is_empty() {
         // Only retain domains which do not appear in the exclusion list
-        domains.retain(|domain| !excluded_domains.contains(&domain.get_domain(strings)));
+        domains.retain(|domain| !exclusions.excludes_domain(domain.get_domain(strings)));
     }
-
-    Ok(())
 }
 
 /// Get the domains from [`SetupVarsEntry::ApiExcludeDomains`] in lowercase.
 ///
 /// [`SetupVarsEntry::ApiExcludeDomains`]:
 /// ../../../settings/entries/enum.SetupVarsEntry.html#variant.ApiExcludeDomains
-pub fn get_excluded_domains(env: &Env) -> Result<Vec<String>, Error> {
-    Ok(SetupVarsEntry::ApiExcludeDomains
-        .read_list(env)?
+pub fn get_excluded_domains(settings: &dyn SettingsRepository) -> Result<Vec<String>, Error> {
+    Ok(settings
+        .get_list(SetupVarsEntry::ApiExcludeDomains)?
         .into_iter()
         .map(|s| s.to_lowercase())
         .collect())
 }
 
-/// Remove clients from the `clients` vector if they are marked as hidden due
-/// to the privacy level.
-pub fn remove_hidden_clients(clients: &mut Vec<&FtlClient>, strings: &FtlStrings) {
-    clients.retain(|client| client.get_ip(strings) != HIDDEN_CLIENT);
-}
-
-/// Remove domains from the `domains` vector if they are marked as hidden due
-/// to the privacy level.
-pub fn remove_hidden_domains(domains: &mut Vec<&FtlDomain>, strings: &FtlStrings) {
-    domains.retain(|domain| domain.get_domain(strings) != HIDDEN_DOMAIN);
+/// Get the name of the client. If FTL has not named the client and
+/// `reverse_dns_clients` is enabled, the name from reverse DNS is used.
+pub fn get_client_name(
+    client: &FtlClient,
+    strings: &FtlStrings,
+    env: &Env,
+    hostnames: &HostnameCache,
+) -> Option<String> {
+    client.get_name(strings).map(str::to_owned).or_else(|| {
+        if env.config().general.reverse_dns_clients {
+            hostnames.get(client.get_ip(strings))
+        } else {
+            None
+        }
+    })
 }
 
 /// Get the current overTime slot index, based on the current time. If all of
@@ -133,16 +111,12 @@ pub fn get_current_over_time_slot(over_time: &[FtlOverTime]) -> usize {
 
 #[cfg(test)]
 mod tests {
-    use super::{
-        remove_excluded_clients, remove_excluded_domains, remove_hidden_clients,
-        remove_hidden_domains,
-    };
+    use super::{remove_excluded_clients, remove_excluded_domains};
     use crate::{
-        env::PiholeFile,
         ftl::{
             FtlClient, FtlCounters, FtlDomain, FtlMemory, FtlRegexMatch, FtlSettings, ShmLockGuard,
         },
-        testing::TestEnvBuilder,
+        services::exclusions::Exclusions,
     };
     use std::collections::HashMap;
 
@@ -181,13 +155,10 @@ mod tests {
     #[test]
     fn excluded_clients() {
         let ftl_memory = test_data();
-
-        let env = TestEnvBuilder::new()
-            .file(
-                PiholeFile::SetupVars,
-                "API_EXCLUDE_CLIENTS=10.1.1.2,client1",
-            )
-            .build();
+        let exclusions = Exclusions::new(
+            Vec::new(),
+            vec!["10.1.1.2".to_owned(), "client1".to_owned()],
+        );
 
         let lock_guard = ShmLockGuard::Test;
         let clients = ftl_memory.clients(&lock_guard).unwrap();
@@ -195,10 +166,9 @@ mod tests {
 
         remove_excluded_clients(
             &mut clients,
-            &env,
+            &exclusions,
             &ftl_memory.strings(&lock_guard).unwrap(),
-        )
-        .unwrap();
+        );
 
         assert_eq!(clients, vec![&FtlClient::new(0, 0, 4, None)]);
     }
@@ -207,13 +177,10 @@ mod tests {
     #[test]
     fn excluded_domains() {
         let ftl_memory = test_data();
-
-        let env = TestEnvBuilder::new()
-            .file(
-                PiholeFile::SetupVars,
-                "API_EXCLUDE_DOMAINS=google.com,example.com",
-            )
-            .build();
+        let exclusions = Exclusions::new(
+            vec!["google.com".to_owned(), "example.com".to_owned()],
+            Vec::new(),
+        );
 
         let lock_guard = ShmLockGuard::Test;
         let domains = ftl_memory.domains(&lock_guard).unwrap();
@@ -221,10 +188,9 @@ mod tests {
 
         remove_excluded_domains(
             &mut clients,
-            &env,
+            &exclusions,
             &ftl_memory.strings(&lock_guard).unwrap(),
-        )
-        .unwrap();
+        );
 
         assert_eq!(
             clients,
@@ -234,36 +200,4 @@ mod tests {
             ]
         );
     }
-
-    /// Clients marked as hidden are removed
-    #[test]
-    fn hidden_clients() {
-        let ftl_memory = test_data();
-        let lock_guard = ShmLockGuard::Test;
-
-        let clients = ftl_memory.clients(&lock_guard).unwrap();
-        let mut clients: Vec<&FtlClient> = clients.iter().collect();
-        let mut clients_clone = clients.clone();
-        clients_clone.remove(2);
-
-        remove_hidden_clients(&mut clients, &ftl_memory.strings(&lock_guard).unwrap());
-
-        assert_eq!(clients, clients_clone);
-    }
-
-    /// Domains marked as hidden are removed
-    #[test]
-    fn hidden_domains() {
-        let ftl_memory = test_data();
-        let lock_guard = ShmLockGuard::Test;
-
-        let domains = ftl_memory.domains(&lock_guard).unwrap();
-        let mut domains: Vec<&FtlDomain> = domains.iter().collect();
-        let mut domains_clone = domains.clone();
-        domains_clone.remove(2);
-
-        remove_hidden_domains(&mut domains, &ftl_memory.strings(&lock_guard).unwrap());
-
-        assert_eq!(domains, domains_clone);
-    }
 }
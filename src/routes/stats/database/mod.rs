@@ -10,6 +10,7 @@
 
 pub mod over_time_clients_db;
 pub mod over_time_history_db;
+pub mod query_db;
 pub mod query_types_db;
 pub mod summary_db;
 pub mod top_clients_db;
@@ -10,18 +10,17 @@
 
 use crate::{
     databases::ftl::FtlDatabase,
-    env::Env,
     ftl::ClientReply,
     routes::{
         auth::User,
         stats::{
-            common::{get_excluded_clients, HIDDEN_CLIENT},
+            common::get_excluded_clients,
             database::over_time_history_db::align_from_until,
             over_time_clients::{OverTimeClientItem, OverTimeClients},
         },
     },
-    services::PiholeModule,
-    settings::ValueType,
+    services::{settings::SettingsRepository, PiholeModule},
+    settings::{ValueType, HIDDEN_CLIENT},
     util::{reply_result, Error, ErrorKind, Reply},
 };
 use diesel::{dsl::sql, prelude::*, sql_types::BigInt, SqliteConnection};
@@ -39,14 +38,14 @@ pub fn over_time_clients_db(
     interval: Option<usize>,
     _auth: User,
     db: InjectProvided<PiholeModule, FtlDatabase>,
-    env: Inject<PiholeModule, Env>,
+    settings: Inject<PiholeModule, dyn SettingsRepository>,
 ) -> Reply {
     reply_result(over_time_clients_db_impl(
         from,
         until,
         interval.unwrap_or(600),
         &db as &SqliteConnection,
-        &env,
+        &*settings,
     ))
 }
 
@@ -56,12 +55,12 @@ fn over_time_clients_db_impl(
     until: u64,
     interval: usize,
     db: &SqliteConnection,
-    env: &Env,
+    settings: &dyn SettingsRepository,
 ) -> Result<OverTimeClients, Error> {
     let (from, until) = align_from_until(from, until, interval as u64)?;
 
     // Load the clients (names or IP addresses)
-    let client_identifiers = get_client_identifiers(from, until, db, env)?;
+    let client_identifiers = get_client_identifiers(from, until, db, settings)?;
 
     // Build the timestamp -> client query data map
     let mut over_time_data: HashMap<u64, Vec<usize>> = (from..until)
@@ -102,13 +101,13 @@ fn over_time_clients_db_impl(
             {
                 // If the identifier is an IP address, use it as the client IP
                 ClientReply {
-                    name: "".to_owned(),
+                    name: None,
                     ip: client_identifier,
                 }
             } else {
                 // If the identifier is not an IP address, use it as the name
                 ClientReply {
-                    name: client_identifier,
+                    name: Some(client_identifier),
                     ip: "".to_owned(),
                 }
             }
@@ -124,12 +123,12 @@ fn get_client_identifiers(
     from: u64,
     until: u64,
     db: &SqliteConnection,
-    env: &Env,
+    settings: &dyn SettingsRepository,
 ) -> Result<Vec<String>, Error> {
     use crate::databases::ftl::queries::dsl::*;
 
     // Find clients which should not be used
-    let mut ignored_clients = get_excluded_clients(env)?;
+    let mut ignored_clients = get_excluded_clients(settings)?;
     ignored_clients.push(HIDDEN_CLIENT.to_owned());
 
     let client_identifiers = queries
@@ -199,11 +198,11 @@ mod test {
         let expected = OverTimeClients {
             clients: vec![
                 ClientReply {
-                    name: "".to_owned(),
+                    name: None,
                     ip: "127.0.0.1".to_owned(),
                 },
                 ClientReply {
-                    name: "".to_owned(),
+                    name: None,
                     ip: "10.1.1.1".to_owned(),
                 },
             ],
@@ -224,11 +223,11 @@ mod test {
         };
 
         let db = connect_to_ftl_test_db();
-        let env = TestEnvBuilder::new()
+        let settings = TestEnvBuilder::new()
             .file(PiholeFile::SetupVars, "")
-            .build();
+            .build_settings();
         let actual =
-            over_time_clients_db_impl(FROM_TIMESTAMP, UNTIL_TIMESTAMP, INTERVAL, &db, &env)
+            over_time_clients_db_impl(FROM_TIMESTAMP, UNTIL_TIMESTAMP, INTERVAL, &db, &settings)
                 .unwrap();
 
         assert_eq!(actual, expected);
@@ -241,10 +240,11 @@ mod test {
         let expected = vec!["127.0.0.1".to_owned(), "10.1.1.1".to_owned()];
 
         let db = connect_to_ftl_test_db();
-        let env = TestEnvBuilder::new()
+        let settings = TestEnvBuilder::new()
             .file(PiholeFile::SetupVars, "")
-            .build();
-        let actual = get_client_identifiers(FROM_TIMESTAMP, UNTIL_TIMESTAMP, &db, &env).unwrap();
+            .build_settings();
+        let actual =
+            get_client_identifiers(FROM_TIMESTAMP, UNTIL_TIMESTAMP, &db, &settings).unwrap();
 
         assert_eq!(actual, expected);
     }
@@ -255,10 +255,11 @@ mod test {
         let expected = vec!["127.0.0.1".to_owned()];
 
         let db = connect_to_ftl_test_db();
-        let env = TestEnvBuilder::new()
+        let settings = TestEnvBuilder::new()
             .file(PiholeFile::SetupVars, "API_EXCLUDE_CLIENTS=10.1.1.1")
-            .build();
-        let actual = get_client_identifiers(FROM_TIMESTAMP, UNTIL_TIMESTAMP, &db, &env).unwrap();
+            .build_settings();
+        let actual =
+            get_client_identifiers(FROM_TIMESTAMP, UNTIL_TIMESTAMP, &db, &settings).unwrap();
 
         assert_eq!(actual, expected);
     }
@@ -22,6 +22,14 @@ use std::collections::HashMap;
 
 pub use over_time_history_db as route;
 
+/// The smallest interval which can be used for time ranges longer than
+/// `MAX_SHORT_INTERVAL_RANGE`
+const MIN_LONG_RANGE_INTERVAL: u64 = 600;
+
+/// The longest time range (one week) which can use an interval smaller than
+/// `MIN_LONG_RANGE_INTERVAL`
+const MAX_SHORT_INTERVAL_RANGE: u64 = 7 * 86400;
+
 /// Get the query history over time from the database
 /// (separated into blocked and not blocked)
 #[get("/stats/database/overTime/history?<from>&<until>&<interval>")]
@@ -73,8 +81,9 @@ fn over_time_history_db_impl(
 }
 
 /// Align `from` and `until` with the interval. Also check that the time
-/// interval is increasing from `from` to `until`. If it is not, an error is
-/// returned.
+/// interval is increasing from `from` to `until`, and that the interval is
+/// not so small that there would be too many slots. If either check fails, an
+/// error is returned.
 pub fn align_from_until(from: u64, until: u64, interval: u64) -> Result<(u64, u64), Error> {
     let is_range_increasing = from < until;
 
@@ -83,6 +92,12 @@ pub fn align_from_until(from: u64, until: u64, interval: u64) -> Result<(u64, u6
         return Err(Error::from(ErrorKind::BadRequest));
     }
 
+    if interval == 0
+        || (interval < MIN_LONG_RANGE_INTERVAL && until - from > MAX_SHORT_INTERVAL_RANGE)
+    {
+        return Err(Error::from(ErrorKind::InvalidInterval(interval)));
+    }
+
     // Align timestamps with the interval
     let from = from - (from % interval);
     let until = until - (until % interval) + interval;
@@ -156,9 +171,13 @@ fn get_blocked_intervals(
 
 #[cfg(test)]
 mod test {
-    use super::{get_blocked_intervals, get_total_intervals, over_time_history_db_impl};
+    use super::{
+        align_from_until, get_blocked_intervals, get_total_intervals, over_time_history_db_impl,
+    };
     use crate::{
-        databases::ftl::connect_to_ftl_test_db, routes::stats::over_time_history::OverTimeItem,
+        databases::ftl::{connect_to_ftl_test_db, insert_test_queries},
+        routes::stats::over_time_history::OverTimeItem,
+        util::ErrorKind,
     };
     use std::collections::HashMap;
 
@@ -220,4 +239,65 @@ mod test {
 
         assert_eq!(actual, expected);
     }
+
+    /// Queries are bucketed by day, including the queries on the edges of each
+    /// day and the whole day containing `until`
+    #[test]
+    fn daily_intervals() {
+        let expected = vec![
+            OverTimeItem {
+                timestamp: 907_200,
+                total_queries: 2,
+                blocked_queries: 1,
+            },
+            OverTimeItem {
+                timestamp: 993_600,
+                total_queries: 1,
+                blocked_queries: 0,
+            },
+            OverTimeItem {
+                timestamp: 1_080_000,
+                total_queries: 2,
+                blocked_queries: 1,
+            },
+        ];
+
+        let db = connect_to_ftl_test_db();
+        insert_test_queries(
+            &db,
+            &[
+                (864_000, 2, "github.com", "10.0.0.2"),
+                (950_399, 1, "ads.example.com", "10.0.0.2"),
+                (950_400, 2, "github.com", "10.0.0.2"),
+                (1_036_900, 1, "ads.example.com", "10.0.0.2"),
+                (1_037_000, 3, "github.com", "10.0.0.2"),
+            ],
+        );
+        let actual = over_time_history_db_impl(864_000, 1_036_850, 86400, &db).unwrap();
+
+        assert_eq!(actual, expected);
+    }
+
+    /// Small intervals are rejected for time ranges longer than a week
+    #[test]
+    fn small_interval_long_range() {
+        assert_eq!(
+            align_from_until(0, 8 * 86400, 300).unwrap_err().kind(),
+            ErrorKind::InvalidInterval(300)
+        );
+        assert_eq!(align_from_until(0, 86400, 300).unwrap(), (0, 86400 + 300));
+        assert_eq!(
+            align_from_until(0, 8 * 86400, 600).unwrap(),
+            (0, 8 * 86400 + 600)
+        );
+    }
+
+    /// An interval of zero is rejected
+    #[test]
+    fn zero_interval() {
+        assert_eq!(
+            align_from_until(0, 600, 0).unwrap_err().kind(),
+            ErrorKind::InvalidInterval(0)
+        );
+    }
 }
@@ -0,0 +1,375 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Query Search Endpoint - DB Version
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    databases::ftl::FtlDatabase,
+    routes::{
+        auth::User,
+        stats::history::{
+            database::load_queries_from_database, HistoryCursor, HistoryParams, HistoryReply,
+            QueryReply,
+        },
+    },
+    services::{privacy::PrivacyService, settings::SettingsRepository, PiholeModule},
+    settings::FtlPrivacyLevel,
+    util::{reply_result, Error, ErrorKind, Reply},
+};
+use shaku_rocket::{Inject, InjectProvided};
+
+pub use query_db as route;
+
+/// The longest time range (31 days) which can be searched at once
+const MAX_TIME_RANGE: u64 = 31 * 86400;
+
+/// The default number of queries to return
+const DEFAULT_LIMIT: usize = 100;
+
+/// The most queries which can be returned at once
+const MAX_LIMIT: usize = 1000;
+
+/// Search the queries in the database by domain
+#[get("/stats/database/query?<params..>")]
+pub fn query_db(
+    _auth: User,
+    settings: Inject<PiholeModule, dyn SettingsRepository>,
+    privacy: Inject<PiholeModule, dyn PrivacyService>,
+    params: QueryDbParams,
+    db: InjectProvided<PiholeModule, FtlDatabase>,
+) -> Reply {
+    reply_result(query_db_impl(
+        &*settings,
+        privacy.privacy_level()?,
+        &db,
+        params,
+    ))
+}
+
+/// Represents the possible GET parameters on `/stats/database/query`. The
+/// time range is required, because the database can hold years of queries.
+#[derive(FromForm, Default)]
+pub struct QueryDbParams {
+    pub from: u64,
+    pub until: u64,
+    /// Only include queries with a domain containing this
+    pub domain: Option<String>,
+    pub client: Option<String>,
+    /// Comma separated status names or numbers, like the history
+    pub status: Option<String>,
+    pub blocked: Option<bool>,
+    /// The cursor returned with the previous page, in Base64
+    pub cursor: Option<String>,
+    pub limit: Option<usize>,
+}
+
+/// Search the queries in the database. The results have the same format as
+/// the history, including the cursor to get the next page.
+fn query_db_impl(
+    settings: &dyn SettingsRepository,
+    privacy_level: FtlPrivacyLevel,
+    db: &FtlDatabase,
+    params: QueryDbParams,
+) -> Result<HistoryReply, Error> {
+    // The timestamps should increase from "from" to "until"
+    if params.from > params.until {
+        return Err(Error::from(ErrorKind::BadRequest));
+    }
+
+    if params.until - params.from > MAX_TIME_RANGE {
+        return Err(Error::from(ErrorKind::InvalidTimeRange(MAX_TIME_RANGE)));
+    }
+
+    let cursor = params
+        .cursor
+        .as_deref()
+        .map(HistoryCursor::from_base64)
+        .transpose()?;
+
+    // Check if query details are private
+    if !privacy_level.allows(FtlPrivacyLevel::Maximum) {
+        return Ok(HistoryReply {
+            history: Vec::new(),
+            cursor: None,
+        });
+    }
+
+    let limit = params.limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT);
+    let history_params = HistoryParams {
+        from: Some(params.from),
+        until: Some(params.until),
+        domain: params.domain,
+        client: params.client.into_iter().collect(),
+        status: params.status,
+        blocked: params.blocked,
+        limit: Some(limit),
+        ..HistoryParams::default()
+    };
+
+    let (queries, cursor) = load_queries_from_database(
+        db,
+        cursor.and_then(|cursor| cursor.db_id),
+        &history_params,
+        settings,
+        limit,
+    )?;
+
+    // The database does not store the privacy level of each query, so only
+    // the configured level is used
+    let history = queries
+        .into_iter()
+        .map(|query| QueryReply::from(query).redact(privacy_level))
+        .collect();
+    let cursor = cursor.map(|cursor| cursor.as_base64()).transpose()?;
+
+    Ok(HistoryReply { history, cursor })
+}
+
+#[cfg(test)]
+mod test {
+    use super::{query_db_impl, QueryDbParams, MAX_TIME_RANGE};
+    use crate::{
+        databases::ftl::{connect_to_ftl_test_db, insert_test_queries, FtlDatabase},
+        env::PiholeFile,
+        routes::stats::history::HistoryReply,
+        services::settings::SettingsRepositoryImpl,
+        settings::FtlPrivacyLevel,
+        testing::{TestBuilder, TestEnvBuilder},
+        util::ErrorKind,
+    };
+    use rocket::http::Status;
+
+    const FROM: u64 = 300_000;
+    const UNTIL: u64 = FROM + 86400;
+
+    /// A test database with queries just outside and exactly on the edges of
+    /// the time range
+    fn test_db() -> FtlDatabase {
+        let db = connect_to_ftl_test_db();
+        insert_test_queries(
+            &db,
+            &[
+                (FROM as i32 - 1, 2, "facebook.com", "10.0.0.1"),
+                (FROM as i32, 2, "www.facebook.com", "10.0.0.1"),
+                (FROM as i32 + 40_000, 1, "facebook.com", "10.0.0.2"),
+                (FROM as i32 + 40_001, 2, "github.com", "10.0.0.1"),
+                (UNTIL as i32, 3, "graph.facebook.com", "10.0.0.2"),
+                (UNTIL as i32 + 1, 2, "facebook.com", "10.0.0.1"),
+            ],
+        );
+
+        db
+    }
+
+    fn test_settings() -> SettingsRepositoryImpl {
+        TestEnvBuilder::new()
+            .file(PiholeFile::SetupVars, "")
+            .build_settings()
+    }
+
+    /// Get the timestamp and domain of each query in the reply
+    fn timestamps_and_domains(reply: &HistoryReply) -> Vec<(u64, &str)> {
+        reply
+            .history
+            .iter()
+            .map(|query| (query.timestamp, query.domain.as_str()))
+            .collect()
+    }
+
+    /// Matching queries in the time range are returned, most recent first,
+    /// including the queries on the edges of the range
+    #[test]
+    fn domain_search() {
+        let params = QueryDbParams {
+            from: FROM,
+            until: UNTIL,
+            domain: Some("facebook".to_owned()),
+            ..QueryDbParams::default()
+        };
+
+        let reply = query_db_impl(
+            &test_settings(),
+            FtlPrivacyLevel::ShowAll,
+            &test_db(),
+            params,
+        )
+        .unwrap();
+
+        assert_eq!(
+            timestamps_and_domains(&reply),
+            vec![
+                (UNTIL, "graph.facebook.com"),
+                (FROM + 40_000, "facebook.com"),
+                (FROM, "www.facebook.com")
+            ]
+        );
+        assert_eq!(reply.cursor, None);
+    }
+
+    /// The status and client filters are applied along with the domain search
+    #[test]
+    fn status_and_client_filters() {
+        let db = test_db();
+        let blocked = QueryDbParams {
+            from: FROM,
+            until: UNTIL,
+            domain: Some("facebook".to_owned()),
+            blocked: Some(true),
+            ..QueryDbParams::default()
+        };
+        let client = QueryDbParams {
+            from: FROM,
+            until: UNTIL,
+            domain: Some("facebook".to_owned()),
+            client: Some("10.0.0.1".to_owned()),
+            ..QueryDbParams::default()
+        };
+
+        let blocked =
+            query_db_impl(&test_settings(), FtlPrivacyLevel::ShowAll, &db, blocked).unwrap();
+        let client =
+            query_db_impl(&test_settings(), FtlPrivacyLevel::ShowAll, &db, client).unwrap();
+
+        assert_eq!(
+            timestamps_and_domains(&blocked),
+            vec![(FROM + 40_000, "facebook.com")]
+        );
+        assert_eq!(
+            timestamps_and_domains(&client),
+            vec![(FROM, "www.facebook.com")]
+        );
+    }
+
+    /// The limit is applied by the database, and a cursor is returned to get
+    /// the rest of the queries
+    #[test]
+    fn limit() {
+        let db = test_db();
+        let params = QueryDbParams {
+            from: FROM,
+            until: UNTIL,
+            domain: Some("facebook".to_owned()),
+            limit: Some(2),
+            ..QueryDbParams::default()
+        };
+
+        let reply = query_db_impl(&test_settings(), FtlPrivacyLevel::ShowAll, &db, params).unwrap();
+        assert_eq!(reply.history.len(), 2);
+
+        let params = QueryDbParams {
+            from: FROM,
+            until: UNTIL,
+            domain: Some("facebook".to_owned()),
+            cursor: reply.cursor,
+            ..QueryDbParams::default()
+        };
+        let next_page =
+            query_db_impl(&test_settings(), FtlPrivacyLevel::ShowAll, &db, params).unwrap();
+
+        assert_eq!(
+            timestamps_and_domains(&next_page),
+            vec![(FROM, "www.facebook.com")]
+        );
+        assert_eq!(next_page.cursor, None);
+    }
+
+    /// The domains are hidden if the privacy level hides them
+    #[test]
+    fn privacy_hides_domains() {
+        let params = QueryDbParams {
+            from: FROM,
+            until: UNTIL,
+            domain: Some("github".to_owned()),
+            ..QueryDbParams::default()
+        };
+
+        let reply = query_db_impl(
+            &test_settings(),
+            FtlPrivacyLevel::HideDomains,
+            &test_db(),
+            params,
+        )
+        .unwrap();
+
+        assert_eq!(
+            timestamps_and_domains(&reply),
+            vec![(FROM + 40_001, "hidden")]
+        );
+    }
+
+    /// A time range of exactly 31 days is allowed, but not any longer
+    #[test]
+    fn max_time_range() {
+        let db = test_db();
+        let params = |until| QueryDbParams {
+            from: FROM,
+            until,
+            domain: Some("facebook".to_owned()),
+            ..QueryDbParams::default()
+        };
+
+        assert!(query_db_impl(
+            &test_settings(),
+            FtlPrivacyLevel::ShowAll,
+            &db,
+            params(FROM + MAX_TIME_RANGE)
+        )
+        .is_ok());
+        assert_eq!(
+            query_db_impl(
+                &test_settings(),
+                FtlPrivacyLevel::ShowAll,
+                &db,
+                params(FROM + MAX_TIME_RANGE + 1)
+            )
+            .unwrap_err()
+            .kind(),
+            ErrorKind::InvalidTimeRange(MAX_TIME_RANGE)
+        );
+    }
+
+    /// A time range which is too long is rejected with a 400
+    #[test]
+    fn time_range_too_long() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/database/query?domain=facebook&from=0&until=2678401")
+            .need_database(true)
+            .file(PiholeFile::FtlConfig, "")
+            .expect_status(Status::BadRequest)
+            .expect_json(json!({
+                "error": {
+                    "key": "invalid_time_range",
+                    "message": "Time range is longer than 2678400 seconds",
+                    "data": { "max_range": 2_678_400 }
+                }
+            }))
+            .test();
+    }
+
+    /// A time range which goes backwards is rejected
+    #[test]
+    fn backwards_time_range() {
+        let params = QueryDbParams {
+            from: UNTIL,
+            until: FROM,
+            ..QueryDbParams::default()
+        };
+
+        assert_eq!(
+            query_db_impl(
+                &test_settings(),
+                FtlPrivacyLevel::ShowAll,
+                &test_db(),
+                params
+            )
+            .unwrap_err()
+            .kind(),
+            ErrorKind::BadRequest
+        );
+    }
+}
@@ -10,7 +10,6 @@
 
 use crate::{
     databases::ftl::FtlDatabase,
-    env::Env,
     ftl::{FtlQueryStatus, FtlQueryType, BLOCKED_STATUSES},
     routes::{
         auth::User,
@@ -19,8 +18,8 @@ use crate::{
             summary::{ReplyTypes, Summary, TotalQueries},
         },
     },
-    services::PiholeModule,
-    settings::{ConfigEntry, SetupVarsEntry},
+    services::{settings::SettingsRepository, PiholeModule},
+    settings::SetupVarsEntry,
     util::{reply_result, Error, ErrorKind, Reply},
 };
 use diesel::prelude::*;
@@ -34,13 +33,13 @@ pub fn get_summary_db(
     until: u64,
     _auth: User,
     db: InjectProvided<PiholeModule, FtlDatabase>,
-    env: Inject<PiholeModule, Env>,
+    settings: Inject<PiholeModule, dyn SettingsRepository>,
 ) -> Reply {
     reply_result(get_summary_impl(
         from,
         until,
         &db as &SqliteConnection,
-        &env,
+        &*settings,
     ))
 }
 
@@ -51,7 +50,7 @@ fn get_summary_impl(
     from: u64,
     until: u64,
     db: &SqliteConnection,
-    env: &Env,
+    settings: &dyn SettingsRepository,
 ) -> Result<Summary, Error> {
     let query_type_counts = get_query_type_counts(db, from, until)?;
 
@@ -105,7 +104,7 @@ fn get_summary_impl(
         // TODO: use real client values when we can accurately determine the number of clients
         total_clients: 0,
         active_clients: 0,
-        status: if SetupVarsEntry::BlockingEnabled.is_true(&env)? {
+        status: if settings.get_bool(SetupVarsEntry::BlockingEnabled)? {
             "enabled"
         } else {
             "disabled"
@@ -215,10 +214,11 @@ mod test {
         };
 
         let db = connect_to_ftl_test_db();
-        let env = TestEnvBuilder::new()
+        let settings = TestEnvBuilder::new()
             .file(PiholeFile::SetupVars, "")
-            .build();
-        let actual_summary = get_summary_impl(FROM_TIMESTAMP, UNTIL_TIMESTAMP, &db, &env).unwrap();
+            .build_settings();
+        let actual_summary =
+            get_summary_impl(FROM_TIMESTAMP, UNTIL_TIMESTAMP, &db, &settings).unwrap();
 
         assert_eq!(actual_summary, expected_summary);
     }
@@ -10,12 +10,11 @@
 
 use crate::{
     databases::ftl::FtlDatabase,
-    env::Env,
     ftl::BLOCKED_STATUSES,
     routes::{
         auth::User,
         stats::{
-            common::{get_excluded_clients, HIDDEN_CLIENT},
+            common::get_excluded_clients,
             database::{
                 query_types_db::get_query_type_counts, summary_db::get_blocked_query_count,
             },
@@ -25,8 +24,8 @@ use crate::{
             },
         },
     },
-    services::PiholeModule,
-    settings::ValueType,
+    services::{privacy::PrivacyService, settings::SettingsRepository, PiholeModule},
+    settings::{FtlPrivacyLevel, ValueType, HIDDEN_CLIENT},
     util::{reply_result, Error, ErrorKind, Reply},
 };
 use diesel::{dsl::sql, prelude::*, sql_types::BigInt};
@@ -39,14 +38,18 @@ pub use top_clients_db as route;
 #[get("/stats/database/top_clients?<from>&<until>&<params..>")]
 pub fn top_clients_db(
     _auth: User,
-    env: Inject<PiholeModule, Env>,
+    settings: Inject<PiholeModule, dyn SettingsRepository>,
+    privacy: Inject<PiholeModule, dyn PrivacyService>,
     db: InjectProvided<PiholeModule, FtlDatabase>,
     from: u64,
     until: u64,
     params: TopClientParams,
 ) -> Reply {
+    let privacy_level = privacy.privacy_level()?;
+
     reply_result(top_clients_db_impl(
-        &env,
+        &*settings,
+        privacy_level,
         &db as &SqliteConnection,
         from,
         until,
@@ -56,7 +59,8 @@ pub fn top_clients_db(
 
 /// Get the top clients
 fn top_clients_db_impl(
-    env: &Env,
+    settings: &dyn SettingsRepository,
+    privacy_level: FtlPrivacyLevel,
     db: &SqliteConnection,
     from: u64,
     until: u64,
@@ -75,13 +79,13 @@ fn top_clients_db_impl(
     } as usize;
 
     // Check if the client details are private
-    if let Some(reply) = check_privacy_level_top_clients(env, blocked, total_count)? {
+    if let Some(reply) = check_privacy_level_top_clients(privacy_level, blocked, total_count) {
         // We can not share any of the clients, so use the reply returned by the
         // function
         return Ok(reply);
     }
 
-    let ignored_clients = get_ignored_clients(env)?;
+    let ignored_clients = get_ignored_clients(settings)?;
 
     // Fetch the top clients and map into the reply structure
     let top_clients: Vec<TopClientItemReply> =
@@ -93,14 +97,14 @@ fn top_clients_db_impl(
                 {
                     // If the identifier is an IP address, use it as the client IP
                     TopClientItemReply {
-                        name: "".to_owned(),
+                        name: None,
                         ip: client_identifier,
                         count: count as usize,
                     }
                 } else {
                     // If the identifier is not an IP address, use it as the name
                     TopClientItemReply {
-                        name: client_identifier,
+                        name: Some(client_identifier),
                         ip: "".to_owned(),
                         count: count as usize,
                     }
@@ -125,9 +129,9 @@ fn top_clients_db_impl(
 }
 
 /// Get the list of clients to ignore
-fn get_ignored_clients(env: &Env) -> Result<Vec<String>, Error> {
+fn get_ignored_clients(settings: &dyn SettingsRepository) -> Result<Vec<String>, Error> {
     // Ignore clients excluded via SetupVars
-    let mut ignored_clients = get_excluded_clients(env)?;
+    let mut ignored_clients = get_excluded_clients(settings)?;
 
     // Ignore the hidden client IP (due to privacy level)
     ignored_clients.push(HIDDEN_CLIENT.to_owned());
@@ -189,9 +193,10 @@ fn execute_top_clients_query(
 mod test {
     use super::top_clients_db_impl;
     use crate::{
-        databases::ftl::connect_to_ftl_test_db,
+        databases::ftl::{connect_to_ftl_test_db, insert_test_queries},
         env::PiholeFile,
         routes::stats::top_clients::{TopClientItemReply, TopClientParams, TopClientsReply},
+        settings::FtlPrivacyLevel,
         testing::TestEnvBuilder,
     };
 
@@ -204,12 +209,12 @@ mod test {
         let expected = TopClientsReply {
             top_clients: vec![
                 TopClientItemReply {
-                    name: "".to_owned(),
+                    name: None,
                     ip: "127.0.0.1".to_owned(),
                     count: 93,
                 },
                 TopClientItemReply {
-                    name: "".to_owned(),
+                    name: None,
                     ip: "10.1.1.1".to_owned(),
                     count: 1,
                 },
@@ -219,13 +224,20 @@ mod test {
         };
 
         let db = connect_to_ftl_test_db();
-        let env = TestEnvBuilder::new()
+        let settings = TestEnvBuilder::new()
             .file(PiholeFile::SetupVars, "")
             .file(PiholeFile::FtlConfig, "")
-            .build();
+            .build_settings();
         let params = TopClientParams::default();
-        let actual =
-            top_clients_db_impl(&env, &db, FROM_TIMESTAMP, UNTIL_TIMESTAMP, params).unwrap();
+        let actual = top_clients_db_impl(
+            &settings,
+            FtlPrivacyLevel::ShowAll,
+            &db,
+            FROM_TIMESTAMP,
+            UNTIL_TIMESTAMP,
+            params,
+        )
+        .unwrap();
 
         assert_eq!(actual, expected);
     }
@@ -241,16 +253,23 @@ mod test {
         };
 
         let db = connect_to_ftl_test_db();
-        let env = TestEnvBuilder::new()
+        let settings = TestEnvBuilder::new()
             .file(PiholeFile::SetupVars, "")
             .file(PiholeFile::FtlConfig, "")
-            .build();
+            .build_settings();
         let params = TopClientParams {
             blocked: Some(true),
             ..TopClientParams::default()
         };
-        let actual =
-            top_clients_db_impl(&env, &db, FROM_TIMESTAMP, UNTIL_TIMESTAMP, params).unwrap();
+        let actual = top_clients_db_impl(
+            &settings,
+            FtlPrivacyLevel::ShowAll,
+            &db,
+            FROM_TIMESTAMP,
+            UNTIL_TIMESTAMP,
+            params,
+        )
+        .unwrap();
 
         assert_eq!(actual, expected);
     }
@@ -260,7 +279,7 @@ mod test {
     fn limit() {
         let expected = TopClientsReply {
             top_clients: vec![TopClientItemReply {
-                name: "".to_owned(),
+                name: None,
                 ip: "127.0.0.1".to_owned(),
                 count: 93,
             }],
@@ -269,16 +288,23 @@ mod test {
         };
 
         let db = connect_to_ftl_test_db();
-        let env = TestEnvBuilder::new()
+        let settings = TestEnvBuilder::new()
             .file(PiholeFile::SetupVars, "")
             .file(PiholeFile::FtlConfig, "")
-            .build();
+            .build_settings();
         let params = TopClientParams {
             limit: Some(1),
             ..TopClientParams::default()
         };
-        let actual =
-            top_clients_db_impl(&env, &db, FROM_TIMESTAMP, UNTIL_TIMESTAMP, params).unwrap();
+        let actual = top_clients_db_impl(
+            &settings,
+            FtlPrivacyLevel::ShowAll,
+            &db,
+            FROM_TIMESTAMP,
+            UNTIL_TIMESTAMP,
+            params,
+        )
+        .unwrap();
 
         assert_eq!(actual, expected);
     }
@@ -289,12 +315,12 @@ mod test {
         let expected = TopClientsReply {
             top_clients: vec![
                 TopClientItemReply {
-                    name: "".to_owned(),
+                    name: None,
                     ip: "10.1.1.1".to_owned(),
                     count: 1,
                 },
                 TopClientItemReply {
-                    name: "".to_owned(),
+                    name: None,
                     ip: "127.0.0.1".to_owned(),
                     count: 93,
                 },
@@ -304,16 +330,23 @@ mod test {
         };
 
         let db = connect_to_ftl_test_db();
-        let env = TestEnvBuilder::new()
+        let settings = TestEnvBuilder::new()
             .file(PiholeFile::SetupVars, "")
             .file(PiholeFile::FtlConfig, "")
-            .build();
+            .build_settings();
         let params = TopClientParams {
             ascending: Some(true),
             ..TopClientParams::default()
         };
-        let actual =
-            top_clients_db_impl(&env, &db, FROM_TIMESTAMP, UNTIL_TIMESTAMP, params).unwrap();
+        let actual = top_clients_db_impl(
+            &settings,
+            FtlPrivacyLevel::ShowAll,
+            &db,
+            FROM_TIMESTAMP,
+            UNTIL_TIMESTAMP,
+            params,
+        )
+        .unwrap();
 
         assert_eq!(actual, expected);
     }
@@ -328,12 +361,19 @@ mod test {
         };
 
         let db = connect_to_ftl_test_db();
-        let env = TestEnvBuilder::new()
+        let settings = TestEnvBuilder::new()
             .file(PiholeFile::FtlConfig, "PRIVACYLEVEL=2")
-            .build();
+            .build_settings();
         let params = TopClientParams::default();
-        let actual =
-            top_clients_db_impl(&env, &db, FROM_TIMESTAMP, UNTIL_TIMESTAMP, params).unwrap();
+        let actual = top_clients_db_impl(
+            &settings,
+            FtlPrivacyLevel::HideDomainsAndClients,
+            &db,
+            FROM_TIMESTAMP,
+            UNTIL_TIMESTAMP,
+            params,
+        )
+        .unwrap();
 
         assert_eq!(actual, expected);
     }
@@ -349,15 +389,22 @@ mod test {
         };
 
         let db = connect_to_ftl_test_db();
-        let env = TestEnvBuilder::new()
+        let settings = TestEnvBuilder::new()
             .file(PiholeFile::FtlConfig, "PRIVACYLEVEL=2")
-            .build();
+            .build_settings();
         let params = TopClientParams {
             blocked: Some(true),
             ..TopClientParams::default()
         };
-        let actual =
-            top_clients_db_impl(&env, &db, FROM_TIMESTAMP, UNTIL_TIMESTAMP, params).unwrap();
+        let actual = top_clients_db_impl(
+            &settings,
+            FtlPrivacyLevel::HideDomainsAndClients,
+            &db,
+            FROM_TIMESTAMP,
+            UNTIL_TIMESTAMP,
+            params,
+        )
+        .unwrap();
 
         assert_eq!(actual, expected);
     }
@@ -367,7 +414,7 @@ mod test {
     fn excluded_clients() {
         let expected = TopClientsReply {
             top_clients: vec![TopClientItemReply {
-                name: "".to_owned(),
+                name: None,
                 ip: "10.1.1.1".to_owned(),
                 count: 1,
             }],
@@ -376,13 +423,73 @@ mod test {
         };
 
         let db = connect_to_ftl_test_db();
-        let env = TestEnvBuilder::new()
+        let settings = TestEnvBuilder::new()
             .file(PiholeFile::SetupVars, "API_EXCLUDE_CLIENTS=127.0.0.1")
             .file(PiholeFile::FtlConfig, "")
-            .build();
+            .build_settings();
         let params = TopClientParams::default();
-        let actual =
-            top_clients_db_impl(&env, &db, FROM_TIMESTAMP, UNTIL_TIMESTAMP, params).unwrap();
+        let actual = top_clients_db_impl(
+            &settings,
+            FtlPrivacyLevel::ShowAll,
+            &db,
+            FROM_TIMESTAMP,
+            UNTIL_TIMESTAMP,
+            params,
+        )
+        .unwrap();
+
+        assert_eq!(actual, expected);
+    }
+
+    /// Blocked clients are grouped and ordered by the database, and only
+    /// queries in the time range are counted
+    #[test]
+    fn blocked_seeded() {
+        let expected = TopClientsReply {
+            top_clients: vec![
+                TopClientItemReply {
+                    name: None,
+                    ip: "10.0.0.2".to_owned(),
+                    count: 2,
+                },
+                TopClientItemReply {
+                    name: None,
+                    ip: "10.0.0.3".to_owned(),
+                    count: 1,
+                },
+            ],
+            total_queries: None,
+            blocked_queries: Some(3),
+        };
+
+        let db = connect_to_ftl_test_db();
+        insert_test_queries(
+            &db,
+            &[
+                (200_000, 1, "ads.example.com", "10.0.0.2"),
+                (200_100, 5, "ads.example.com", "10.0.0.3"),
+                (200_200, 4, "tracker.example.com", "10.0.0.2"),
+                (200_300, 2, "github.com", "10.0.0.3"),
+                (400_000, 1, "late.example.com", "10.0.0.4"),
+            ],
+        );
+        let settings = TestEnvBuilder::new()
+            .file(PiholeFile::SetupVars, "")
+            .file(PiholeFile::FtlConfig, "")
+            .build_settings();
+        let params = TopClientParams {
+            blocked: Some(true),
+            ..TopClientParams::default()
+        };
+        let actual = top_clients_db_impl(
+            &settings,
+            FtlPrivacyLevel::ShowAll,
+            &db,
+            200_000,
+            300_000,
+            params,
+        )
+        .unwrap();
 
         assert_eq!(actual, expected);
     }
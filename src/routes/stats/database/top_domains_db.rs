@@ -10,12 +10,11 @@
 
 use crate::{
     databases::ftl::FtlDatabase,
-    env::Env,
     ftl::BLOCKED_STATUSES,
     routes::{
         auth::User,
         stats::{
-            common::{get_excluded_domains, HIDDEN_DOMAIN},
+            common::get_excluded_domains,
             database::{
                 query_types_db::get_query_type_counts, summary_db::get_blocked_query_count,
             },
@@ -25,7 +24,11 @@ use crate::{
             },
         },
     },
-    services::{domain_audit::DomainAuditRepository, PiholeModule},
+    services::{
+        domain_audit::DomainAuditRepository, privacy::PrivacyService, settings::SettingsRepository,
+        PiholeModule,
+    },
+    settings::{FtlPrivacyLevel, HIDDEN_DOMAIN},
     util::{reply_result, Error, ErrorKind, Reply},
 };
 use diesel::{dsl::sql, prelude::*, sql_types::BigInt, sqlite::SqliteConnection};
@@ -36,17 +39,22 @@ pub use top_domains_db as route;
 
 /// Return the top domains
 #[get("/stats/database/top_domains?<from>&<until>&<params..>")]
+#[allow(clippy::too_many_arguments)]
 pub fn top_domains_db(
     _auth: User,
-    env: Inject<PiholeModule, Env>,
+    settings: Inject<PiholeModule, dyn SettingsRepository>,
+    privacy: Inject<PiholeModule, dyn PrivacyService>,
     db: InjectProvided<PiholeModule, FtlDatabase>,
     from: u64,
     until: u64,
     params: TopDomainParams,
     domain_audit: InjectProvided<PiholeModule, dyn DomainAuditRepository>,
 ) -> Reply {
+    let privacy_level = privacy.privacy_level()?;
+
     reply_result(top_domains_db_impl(
-        &env,
+        &*settings,
+        privacy_level,
         &db as &SqliteConnection,
         from,
         until,
@@ -57,7 +65,8 @@ pub fn top_domains_db(
 
 /// Return the top domains
 fn top_domains_db_impl(
-    env: &Env,
+    settings: &dyn SettingsRepository,
+    privacy_level: FtlPrivacyLevel,
     db: &SqliteConnection,
     from: u64,
     until: u64,
@@ -71,7 +80,7 @@ fn top_domains_db_impl(
     let blocked = params.blocked.unwrap_or(false);
 
     // Check if we are allowed to share the top domains
-    if let Some(reply) = check_query_log_show_top_domains(env, blocked)? {
+    if let Some(reply) = check_query_log_show_top_domains(settings, blocked)? {
         // We can not share any of the domains, so use the reply returned by the
         // function
         return Ok(reply);
@@ -85,14 +94,14 @@ fn top_domains_db_impl(
     } as usize;
 
     // Check if the domain details are private
-    if let Some(reply) = check_privacy_level_top_domains(env, blocked, total_count)? {
+    if let Some(reply) = check_privacy_level_top_domains(privacy_level, blocked, total_count) {
         // We can not share any of the domains, so use the reply returned by the
         // function
         return Ok(reply);
     }
 
     // Find domains which should not be considered
-    let ignored_domains = get_ignored_domains(env, audit, domain_audit)?;
+    let ignored_domains = get_ignored_domains(settings, audit, domain_audit)?;
 
     // Fetch the top domains and map into the reply structure
     let top_domains: Vec<TopDomainItemReply> =
@@ -123,12 +132,12 @@ fn top_domains_db_impl(
 /// Get the list of domains to ignore. If the audit flag is true, audited
 /// domains are ignored (only show unaudited domains).
 fn get_ignored_domains(
-    env: &Env,
+    settings: &dyn SettingsRepository,
     audit: bool,
     domain_audit: &dyn DomainAuditRepository,
 ) -> Result<Vec<String>, Error> {
     // Ignore domains excluded via SetupVars
-    let mut ignored_domains = get_excluded_domains(env)?;
+    let mut ignored_domains = get_excluded_domains(settings)?;
 
     // Ignore the hidden domain (due to privacy level)
     ignored_domains.push(HIDDEN_DOMAIN.to_owned());
@@ -194,10 +203,11 @@ fn execute_top_domains_query(
 mod test {
     use super::top_domains_db_impl;
     use crate::{
-        databases::ftl::connect_to_ftl_test_db,
+        databases::ftl::{connect_to_ftl_test_db, insert_test_queries},
         env::PiholeFile,
         routes::stats::top_domains::{TopDomainItemReply, TopDomainParams, TopDomainsReply},
         services::domain_audit::MockDomainAuditRepository,
+        settings::FtlPrivacyLevel,
         testing::TestEnvBuilder,
     };
 
@@ -256,13 +266,14 @@ mod test {
         };
 
         let db = connect_to_ftl_test_db();
-        let env = TestEnvBuilder::new()
+        let settings = TestEnvBuilder::new()
             .file(PiholeFile::SetupVars, "")
             .file(PiholeFile::FtlConfig, "")
-            .build();
+            .build_settings();
         let params = TopDomainParams::default();
         let actual = top_domains_db_impl(
-            &env,
+            &settings,
+            FtlPrivacyLevel::ShowAll,
             &*db,
             FROM_TIMESTAMP,
             UNTIL_TIMESTAMP,
@@ -293,16 +304,17 @@ mod test {
         };
 
         let db = connect_to_ftl_test_db();
-        let env = TestEnvBuilder::new()
+        let settings = TestEnvBuilder::new()
             .file(PiholeFile::SetupVars, "")
             .file(PiholeFile::FtlConfig, "")
-            .build();
+            .build_settings();
         let params = TopDomainParams {
             limit: Some(2),
             ..TopDomainParams::default()
         };
         let actual = top_domains_db_impl(
-            &env,
+            &settings,
+            FtlPrivacyLevel::ShowAll,
             &*db,
             FROM_TIMESTAMP,
             UNTIL_TIMESTAMP,
@@ -326,16 +338,17 @@ mod test {
         };
 
         let db = connect_to_ftl_test_db();
-        let env = TestEnvBuilder::new()
+        let settings = TestEnvBuilder::new()
             .file(PiholeFile::SetupVars, "")
             .file(PiholeFile::FtlConfig, "")
-            .build();
+            .build_settings();
         let params = TopDomainParams {
             blocked: Some(true),
             ..TopDomainParams::default()
         };
         let actual = top_domains_db_impl(
-            &env,
+            &settings,
+            FtlPrivacyLevel::ShowAll,
             &*db,
             FROM_TIMESTAMP,
             UNTIL_TIMESTAMP,
@@ -367,17 +380,18 @@ mod test {
         };
 
         let db = connect_to_ftl_test_db();
-        let env = TestEnvBuilder::new()
+        let settings = TestEnvBuilder::new()
             .file(PiholeFile::SetupVars, "")
             .file(PiholeFile::FtlConfig, "")
-            .build();
+            .build_settings();
         let params = TopDomainParams {
             ascending: Some(true),
             limit: Some(2),
             ..TopDomainParams::default()
         };
         let actual = top_domains_db_impl(
-            &env,
+            &settings,
+            FtlPrivacyLevel::ShowAll,
             &*db,
             FROM_TIMESTAMP,
             UNTIL_TIMESTAMP,
@@ -409,10 +423,10 @@ mod test {
         };
 
         let db = connect_to_ftl_test_db();
-        let env = TestEnvBuilder::new()
+        let settings = TestEnvBuilder::new()
             .file(PiholeFile::SetupVars, "")
             .file(PiholeFile::FtlConfig, "")
-            .build();
+            .build_settings();
         let params = TopDomainParams {
             audit: Some(true),
             limit: Some(2),
@@ -424,7 +438,8 @@ mod test {
             .return_const(Ok(vec!["1.ubuntu.pool.ntp.org".to_owned()]));
 
         let actual = top_domains_db_impl(
-            &env,
+            &settings,
+            FtlPrivacyLevel::ShowAll,
             &*db,
             FROM_TIMESTAMP,
             UNTIL_TIMESTAMP,
@@ -455,13 +470,13 @@ mod test {
         };
 
         let db = connect_to_ftl_test_db();
-        let env = TestEnvBuilder::new()
+        let settings = TestEnvBuilder::new()
             .file(
                 PiholeFile::SetupVars,
                 "API_EXCLUDE_DOMAINS=1.ubuntu.pool.ntp.org",
             )
             .file(PiholeFile::FtlConfig, "")
-            .build();
+            .build_settings();
         let params = TopDomainParams {
             audit: Some(true),
             limit: Some(2),
@@ -471,7 +486,8 @@ mod test {
         domain_audit.expect_get_all().return_const(Ok(Vec::new()));
 
         let actual = top_domains_db_impl(
-            &env,
+            &settings,
+            FtlPrivacyLevel::ShowAll,
             &*db,
             FROM_TIMESTAMP,
             UNTIL_TIMESTAMP,
@@ -482,4 +498,56 @@ mod test {
 
         assert_eq!(actual, expected);
     }
+
+    /// Blocked domains are grouped and ordered by the database, and only
+    /// queries in the time range are counted
+    #[test]
+    fn blocked_seeded() {
+        let expected = TopDomainsReply {
+            top_domains: vec![
+                TopDomainItemReply {
+                    domain: "ads.example.com".to_owned(),
+                    count: 2,
+                },
+                TopDomainItemReply {
+                    domain: "tracker.example.com".to_owned(),
+                    count: 1,
+                },
+            ],
+            total_queries: None,
+            blocked_queries: Some(3),
+        };
+
+        let db = connect_to_ftl_test_db();
+        insert_test_queries(
+            &db,
+            &[
+                (200_000, 1, "ads.example.com", "10.0.0.2"),
+                (200_100, 5, "ads.example.com", "10.0.0.3"),
+                (200_200, 4, "tracker.example.com", "10.0.0.2"),
+                (200_300, 2, "github.com", "10.0.0.3"),
+                (400_000, 1, "late.example.com", "10.0.0.4"),
+            ],
+        );
+        let settings = TestEnvBuilder::new()
+            .file(PiholeFile::SetupVars, "")
+            .file(PiholeFile::FtlConfig, "")
+            .build_settings();
+        let params = TopDomainParams {
+            blocked: Some(true),
+            ..TopDomainParams::default()
+        };
+        let actual = top_domains_db_impl(
+            &settings,
+            FtlPrivacyLevel::ShowAll,
+            &*db,
+            200_000,
+            300_000,
+            params,
+            &MockDomainAuditRepository::new(),
+        )
+        .unwrap();
+
+        assert_eq!(actual, expected);
+    }
 }
@@ -61,16 +61,18 @@ fn upstreams_db_impl(
     let mut upstreams = Vec::with_capacity(upstream_counts.len() + 1);
 
     // Add blocklist and cache upstreams
-    upstreams.push(UpstreamItemReply {
-        name: "blocklist".to_owned(),
-        ip: "blocklist".to_owned(),
-        count: blocked_count,
-    });
-    upstreams.push(UpstreamItemReply {
-        name: "cache".to_owned(),
-        ip: "cache".to_owned(),
-        count: cached_count,
-    });
+    upstreams.push(UpstreamItemReply::new(
+        "blocklist".to_owned(),
+        "blocklist".to_owned(),
+        blocked_count,
+        total_queries,
+    ));
+    upstreams.push(UpstreamItemReply::new(
+        "cache".to_owned(),
+        "cache".to_owned(),
+        cached_count,
+        total_queries,
+    ));
 
     // Convert the upstreams into the reply structs
     let mut upstream_counts: Vec<UpstreamItemReply> = upstream_counts
@@ -78,11 +80,7 @@ fn upstreams_db_impl(
         .filter_map(|(ip, count)| {
             // Ignore the blocked and cached queries. These have already
             // been added above
-            ip.map(|ip| UpstreamItemReply {
-                name: "".to_owned(),
-                ip,
-                count: count as usize,
-            })
+            ip.map(|ip| UpstreamItemReply::new("".to_owned(), ip, count as usize, total_queries))
         })
         .collect();
 
@@ -146,21 +144,25 @@ mod test {
                     name: "blocklist".to_owned(),
                     ip: "blocklist".to_owned(),
                     count: 0,
+                    percentage: 0.0,
                 },
                 UpstreamItemReply {
                     name: "cache".to_owned(),
                     ip: "cache".to_owned(),
                     count: 28,
+                    percentage: 2800.0 / 94.0,
                 },
                 UpstreamItemReply {
                     name: "".to_owned(),
                     ip: "8.8.4.4".to_owned(),
                     count: 22,
+                    percentage: 2200.0 / 94.0,
                 },
                 UpstreamItemReply {
                     name: "".to_owned(),
                     ip: "8.8.8.8".to_owned(),
                     count: 4,
+                    percentage: 400.0 / 94.0,
                 },
             ],
             total_queries: 94,
@@ -0,0 +1,143 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Raw FTL Counters Endpoint
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    ftl::{FtlCounters, FtlMemory},
+    routes::auth::User,
+    util::{reply_result, Error, Reply},
+};
+use rocket::State;
+
+pub use ftl_counters as route;
+
+/// Get the raw FTL counters and the sizes of the shared memory arrays, for
+/// diagnostics. There are no domains or clients in the reply, so nothing is
+/// redacted.
+#[get("/stats/ftl")]
+pub fn ftl_counters(_auth: User, ftl_memory: &State<FtlMemory>) -> Reply {
+    reply_result(get_ftl_counters(ftl_memory))
+}
+
+/// Represents the reply structure for the raw FTL counters
+#[derive(Serialize)]
+pub struct FtlCountersReply {
+    pub counters: FtlCounters,
+    pub sizes: FtlMemorySizes,
+}
+
+/// The number of entries in each of the shared memory arrays, and the size of
+/// the string memory in bytes
+#[derive(Serialize)]
+pub struct FtlMemorySizes {
+    pub domains: usize,
+    pub clients: usize,
+    pub upstreams: usize,
+    pub queries: usize,
+    pub strings: usize,
+}
+
+/// Read the counters and memory sizes while holding the lock, so they are
+/// consistent with each other
+fn get_ftl_counters(ftl_memory: &FtlMemory) -> Result<FtlCountersReply, Error> {
+    let lock = ftl_memory.lock()?;
+
+    Ok(FtlCountersReply {
+        counters: **ftl_memory.counters(&lock)?,
+        sizes: FtlMemorySizes {
+            domains: ftl_memory.domains(&lock)?.len(),
+            clients: ftl_memory.clients(&lock)?.len(),
+            upstreams: ftl_memory.upstreams(&lock)?.len(),
+            queries: ftl_memory.queries(&lock)?.len(),
+            strings: ftl_memory.strings(&lock)?.len(),
+        },
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        ftl::{
+            FtlClient, FtlCounters, FtlDomain, FtlMemory, FtlRegexMatch, FtlSettings, FtlUpstream,
+        },
+        testing::TestBuilder,
+    };
+    use std::collections::HashMap;
+
+    /// The counters are returned with their field names, along with the sizes
+    /// of the shared memory
+    #[test]
+    fn counters() {
+        let mut strings = HashMap::new();
+        strings.insert(1, "10.1.1.1".to_owned());
+        strings.insert(10, "example.com".to_owned());
+        strings.insert(22, "8.8.8.8".to_owned());
+
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/ftl")
+            .ftl_memory(FtlMemory::Test {
+                clients: vec![FtlClient::new(3, 1, 1, None)],
+                domains: vec![
+                    FtlDomain::new(3, 1, 10, FtlRegexMatch::NotBlocked),
+                    FtlDomain::new(0, 0, 10, FtlRegexMatch::NotBlocked),
+                ],
+                over_time: Vec::new(),
+                strings,
+                upstreams: vec![FtlUpstream::new(2, 0, 22, None)],
+                queries: Vec::new(),
+                counters: FtlCounters {
+                    total_queries: 3,
+                    blocked_queries: 1,
+                    cached_queries: 0,
+                    total_upstreams: 1,
+                    total_clients: 1,
+                    total_domains: 1,
+                    query_capacity: 10_000,
+                    gravity_size: 120_000,
+                    query_type_counters: [2, 1, 0, 0, 0, 0, 0],
+                    forwarded_queries: 2,
+                    reply_count_ip: 2,
+                    ..FtlCounters::default()
+                },
+                settings: FtlSettings::default(),
+            })
+            .expect_json(json!({
+                "counters": {
+                    "total_queries": 3,
+                    "blocked_queries": 1,
+                    "cached_queries": 0,
+                    "unknown_queries": 0,
+                    "total_upstreams": 1,
+                    "total_clients": 1,
+                    "total_domains": 1,
+                    "query_capacity": 10_000,
+                    "upstream_capacity": 0,
+                    "client_capacity": 0,
+                    "domain_capacity": 0,
+                    "string_capacity": 0,
+                    "gravity_size": 120_000,
+                    "query_type_counters": [2, 1, 0, 0, 0, 0, 0],
+                    "forwarded_queries": 2,
+                    "reply_count_nodata": 0,
+                    "reply_count_nxdomain": 0,
+                    "reply_count_cname": 0,
+                    "reply_count_ip": 2,
+                    "reply_count_domain": 0
+                },
+                "sizes": {
+                    "domains": 2,
+                    "clients": 1,
+                    "upstreams": 1,
+                    "queries": 0,
+                    "strings": 30
+                }
+            }))
+            .test();
+    }
+}
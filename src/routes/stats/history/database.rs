@@ -10,12 +10,12 @@
 
 use crate::{
     databases::ftl::{queries, FtlDatabase, FtlDbQuery},
-    env::Env,
     routes::stats::history::{
         endpoints::{HistoryCursor, HistoryParams},
         filters::*,
         skip_to_cursor::skip_to_cursor_db,
     },
+    services::settings::SettingsRepository,
     util::{Error, ErrorKind},
 };
 use diesel::{prelude::*, sqlite::Sqlite};
@@ -34,7 +34,7 @@ pub fn load_queries_from_database(
     db: &FtlDatabase,
     start_id: Option<i64>,
     params: &HistoryParams,
-    env: &Env,
+    settings: &dyn SettingsRepository,
     limit: usize,
 ) -> Result<(Vec<FtlDbQuery>, Option<HistoryCursor>), Error> {
     // Use the Diesel DSL of this table for easy querying
@@ -56,14 +56,14 @@ pub fn load_queries_from_database(
     let db_query = filter_time_from_db(db_query, params);
     let db_query = filter_time_until_db(db_query, params);
     let db_query = filter_domain_db(db_query, params);
-    let db_query = filter_client_db(db_query, params);
+    let db_query = filter_client_db(db_query, params)?;
     let db_query = filter_upstream_db(db_query, params);
-    let db_query = filter_query_type_db(db_query, params);
-    let db_query = filter_status_db(db_query, params);
+    let db_query = filter_query_type_db(db_query, params)?;
+    let db_query = filter_status_db(db_query, params)?;
     let db_query = filter_blocked_db(db_query, params);
-    let db_query = filter_excluded_domains_db(db_query, env)?;
-    let db_query = filter_excluded_clients_db(db_query, env)?;
-    let db_query = filter_setup_vars_setting_db(db_query, env)?;
+    let db_query = filter_excluded_domains_db(db_query, settings)?;
+    let db_query = filter_excluded_clients_db(db_query, settings)?;
+    let db_query = filter_setup_vars_setting_db(db_query, settings)?;
 
     // Execute the query and load the results
     let mut results: Vec<FtlDbQuery> = execute_query(db, db_query)?;
@@ -111,15 +111,15 @@ mod test {
     /// Queries are ordered by id, descending
     #[test]
     fn order_by_id() {
-        let env = TestEnvBuilder::new()
+        let settings = TestEnvBuilder::new()
             .file(PiholeFile::SetupVars, "")
-            .build();
+            .build_settings();
 
         let (queries, cursor) = load_queries_from_database(
             &connect_to_ftl_test_db(),
             Some(2),
             &HistoryParams::default(),
-            &env,
+            &settings,
             100,
         )
         .unwrap();
@@ -132,9 +132,9 @@ mod test {
     /// The max number of queries returned is specified by the limit
     #[test]
     fn limit() {
-        let env = TestEnvBuilder::new()
+        let settings = TestEnvBuilder::new()
             .file(PiholeFile::SetupVars, "")
-            .build();
+            .build_settings();
         let expected_cursor = Some(HistoryCursor {
             id: None,
             db_id: Some(1),
@@ -144,7 +144,7 @@ mod test {
             &connect_to_ftl_test_db(),
             Some(3),
             &HistoryParams::default(),
-            &env,
+            &settings,
             2,
         )
         .unwrap();
@@ -152,4 +152,50 @@ mod test {
         assert_eq!(queries.len(), 2);
         assert_eq!(cursor, expected_cursor);
     }
+
+    /// The client filter is applied by the database
+    #[test]
+    fn client_filter() {
+        let settings = TestEnvBuilder::new()
+            .file(PiholeFile::SetupVars, "")
+            .build_settings();
+        let params = HistoryParams {
+            client: vec!["10.1.1.1".to_owned()],
+            ..HistoryParams::default()
+        };
+
+        let (queries, cursor) =
+            load_queries_from_database(&connect_to_ftl_test_db(), None, &params, &settings, 100)
+                .unwrap();
+
+        assert_eq!(cursor, None);
+        assert_eq!(queries.len(), 1);
+        assert_eq!(queries[0].id, 14);
+    }
+
+    /// The domain and status filters are applied by the database, along with
+    /// the limit
+    #[test]
+    fn domain_status_filter() {
+        let settings = TestEnvBuilder::new()
+            .file(PiholeFile::SetupVars, "")
+            .build_settings();
+        let params = HistoryParams {
+            domain: Some("github".to_owned()),
+            status: Some("cached".to_owned()),
+            ..HistoryParams::default()
+        };
+        let expected_cursor = Some(HistoryCursor {
+            id: None,
+            db_id: Some(11),
+        });
+
+        let (queries, cursor) =
+            load_queries_from_database(&connect_to_ftl_test_db(), None, &params, &settings, 3)
+                .unwrap();
+        let ids: Vec<i32> = queries.iter().map(|query| query.id).collect();
+
+        assert_eq!(ids, vec![35, 34, 12]);
+        assert_eq!(cursor, expected_cursor);
+    }
 }
@@ -10,33 +10,39 @@
 
 use crate::{
     databases::ftl::FtlDatabase,
-    env::Env,
-    ftl::{FtlDnssecType, FtlMemory, FtlQueryReplyType, FtlQueryStatus, FtlQueryType},
+    ftl::{FtlDnssecType, FtlMemory, FtlQueryReplyType},
     routes::{auth::User, stats::history::get_history::get_history},
-    services::PiholeModule,
-    util::{reply_result, Error, ErrorKind, Reply},
+    services::{privacy::PrivacyService, settings::SettingsRepository, PiholeModule},
+    settings::{FtlPrivacyLevel, HIDDEN_CLIENT, HIDDEN_DOMAIN},
+    util::{Error, ErrorKind},
 };
 use base64::{decode, encode};
 use failure::ResultExt;
 use rocket::{
-    form,
-    form::{FromFormField, ValueField},
-    State,
+    futures::stream,
+    http::ContentType,
+    response::{self, stream::ByteStream, Responder},
+    Request, State,
 };
 use shaku_rocket::{Inject, InjectProvided};
+use std::iter;
 
 pub use history as route;
 
-/// Get the query history according to the specified parameters
+/// Get the query history according to the specified parameters. The reply is
+/// streamed, because it can hold a whole day of queries.
 #[get("/stats/history?<params..>")]
 pub fn history(
     _auth: User,
     ftl_memory: &State<FtlMemory>,
-    env: Inject<PiholeModule, Env>,
+    settings: Inject<PiholeModule, dyn SettingsRepository>,
+    privacy: Inject<PiholeModule, dyn PrivacyService>,
     params: HistoryParams,
     db: InjectProvided<PiholeModule, FtlDatabase>,
-) -> Reply {
-    reply_result(get_history(ftl_memory, &env, params, &db))
+) -> Result<HistoryReply, Error> {
+    let privacy_level = privacy.privacy_level()?;
+
+    get_history(ftl_memory, &*settings, privacy_level, params, &db)
 }
 
 /// The structure returned by the history endpoint
@@ -46,6 +52,37 @@ pub struct HistoryReply {
     pub cursor: Option<String>,
 }
 
+impl HistoryReply {
+    /// Serialize the reply into chunks of JSON, one query per chunk, so the
+    /// whole reply is never held in memory as JSON
+    pub fn json_chunks(self) -> impl Iterator<Item = Vec<u8>> {
+        let HistoryReply { history, cursor } = self;
+
+        // The cursor is either null or a Base64 string, so it always
+        // serializes
+        let closing = format!("],\"cursor\":{}}}", serde_json::to_string(&cursor).unwrap());
+
+        iter::once(b"{\"history\":[".to_vec())
+            .chain(history.into_iter().enumerate().map(|(i, query)| {
+                let mut chunk = if i == 0 { Vec::new() } else { b",".to_vec() };
+
+                // Queries only hold strings and numbers, so they always
+                // serialize
+                serde_json::to_writer(&mut chunk, &query).unwrap();
+                chunk
+            }))
+            .chain(iter::once(closing.into_bytes()))
+    }
+}
+
+impl<'r> Responder<'r, 'r> for HistoryReply {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'r> {
+        let chunks = ByteStream(stream::iter(self.json_chunks()));
+
+        (ContentType::JSON, chunks).respond_to(request)
+    }
+}
+
 /// The structure of queries returned by the history endpoint
 #[derive(Serialize, PartialEq, Debug)]
 pub struct QueryReply {
@@ -54,22 +91,44 @@ pub struct QueryReply {
     pub status: u8,
     pub domain: String,
     pub client: String,
-    pub dnssec: u8,
+    /// The name of the client, if it is known and not private
+    pub client_name: Option<String>,
+    pub dnssec: &'static str,
     pub reply: u8,
     pub response_time: u32,
 }
 
+impl QueryReply {
+    /// Hide the domain and client if the privacy level requires it
+    pub fn redact(mut self, privacy_level: FtlPrivacyLevel) -> Self {
+        if privacy_level.hides_domains() {
+            self.domain = HIDDEN_DOMAIN.to_owned();
+        }
+
+        if privacy_level.hides_clients() {
+            self.client = HIDDEN_CLIENT.to_owned();
+            self.client_name = None;
+        }
+
+        self
+    }
+}
+
 /// Represents the possible GET parameters on `/stats/history`
 #[derive(FromForm)]
 pub struct HistoryParams {
-    pub cursor: Option<HistoryCursor>,
+    /// The cursor returned with the previous page, in Base64
+    pub cursor: Option<String>,
     pub from: Option<u64>,
     pub until: Option<u64>,
     pub domain: Option<String>,
-    pub client: Option<String>,
+    /// Clients to search for, which can be given more than once
+    pub client: Vec<String>,
     pub upstream: Option<String>,
-    pub query_type: Option<FtlQueryType>,
-    pub status: Option<FtlQueryStatus>,
+    /// Comma separated query type names or numbers
+    pub query_type: Option<String>,
+    /// Comma separated status names or numbers, see `FtlQueryStatus::from_name`
+    pub status: Option<String>,
     pub blocked: Option<bool>,
     pub dnssec: Option<FtlDnssecType>,
     pub reply: Option<FtlQueryReplyType>,
@@ -83,7 +142,7 @@ impl Default for HistoryParams {
             from: None,
             until: None,
             domain: None,
-            client: None,
+            client: Vec::new(),
             upstream: None,
             query_type: None,
             status: None,
@@ -110,20 +169,162 @@ impl HistoryCursor {
 
         Ok(encode(&bytes))
     }
+
+    /// Parse the cursor from its Base64 representation. The cursor must
+    /// reference either a query ID or a database ID.
+    pub fn from_base64(cursor: &str) -> Result<Self, Error> {
+        let invalid = || Error::from(ErrorKind::InvalidCursor(cursor.to_owned()));
+
+        let decoded = decode(cursor).map_err(|_| invalid())?;
+        let parsed: HistoryCursor = serde_json::from_slice(&decoded).map_err(|_| invalid())?;
+
+        if parsed.id.is_none() && parsed.db_id.is_none() {
+            return Err(invalid());
+        }
+
+        Ok(parsed)
+    }
 }
 
-impl<'v> FromFormField<'v> for HistoryCursor {
-    fn from_value(field: ValueField<'v>) -> form::Result<'v, Self> {
-        // Decode from Base64
-        let decoded = decode(field.value)
-            .context(ErrorKind::BadRequest)
-            .map_err(|e| form::Error::validation(e.get_context().to_string()))?;
+#[cfg(test)]
+mod test {
+    use super::{HistoryCursor, HistoryParams, QueryReply};
+    use crate::{
+        databases::ftl::connect_to_ftl_test_db,
+        env::PiholeFile,
+        routes::stats::history::{get_history::get_history, testing::test_memory_with_queries},
+        settings::FtlPrivacyLevel,
+        testing::{TestBuilder, TestEnvBuilder},
+    };
+    use rocket::http::Status;
+
+    /// Database queries are redacted according to the privacy level
+    #[test]
+    fn redact() {
+        let query = || QueryReply {
+            timestamp: 177_180,
+            r#type: 6,
+            status: 2,
+            domain: "4.4.8.8.in-addr.arpa".to_owned(),
+            client: "127.0.0.1".to_owned(),
+            client_name: Some("localhost".to_owned()),
+            dnssec: "unknown",
+            reply: 0,
+            response_time: 0,
+        };
+
+        assert_eq!(query().redact(FtlPrivacyLevel::ShowAll), query());
+        assert_eq!(
+            query().redact(FtlPrivacyLevel::HideDomains),
+            QueryReply {
+                domain: "hidden".to_owned(),
+                ..query()
+            }
+        );
+        assert_eq!(
+            query().redact(FtlPrivacyLevel::HideDomainsAndClients),
+            QueryReply {
+                domain: "hidden".to_owned(),
+                client: "0.0.0.0".to_owned(),
+                client_name: None,
+                ..query()
+            }
+        );
+    }
+
+    /// A malformed cursor is rejected with a 400
+    #[test]
+    fn invalid_cursor() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/history?cursor=e30%3D")
+            .need_database(true)
+            .expect_status(Status::BadRequest)
+            .expect_json(json!({
+                "error": {
+                    "key": "invalid_cursor",
+                    "message": "Invalid cursor e30=",
+                    "data": { "cursor": "e30=" }
+                }
+            }))
+            .test();
+    }
+
+    /// A filter without a value is rejected with a 400
+    #[test]
+    fn empty_filter() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/history?client=192.168.1.11&client=")
+            .file(PiholeFile::SetupVars, "")
+            .file(PiholeFile::FtlConfig, "")
+            .need_database(true)
+            .expect_status(Status::BadRequest)
+            .expect_json(json!({
+                "error": {
+                    "key": "invalid_parameter",
+                    "message": "Invalid value  for parameter client",
+                    "data": {
+                        "parameter": "client",
+                        "value": "",
+                        "valid": []
+                    }
+                }
+            }))
+            .test();
+    }
+
+    /// The streamed reply has the same fields as the reply structure
+    #[test]
+    fn streamed_reply() {
+        let cursor = HistoryCursor {
+            id: Some(2),
+            db_id: None,
+        };
+
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/history?limit=1")
+            .ftl_memory(test_memory_with_queries(3))
+            .file(PiholeFile::SetupVars, "")
+            .file(PiholeFile::FtlConfig, "")
+            .need_database(true)
+            .expect_json_contains(json!({
+                "history": [{
+                    "timestamp": 3,
+                    "domain": "domain1.com",
+                    "client": "client1",
+                    "client_name": "client1"
+                }],
+                "cursor": cursor.as_base64().unwrap()
+            }))
+            .test();
+    }
+
+    /// A day of queries is streamed as valid JSON, with every query in it
+    #[test]
+    fn large_reply() {
+        let count = 50_000;
+        let settings = TestEnvBuilder::new()
+            .file(PiholeFile::SetupVars, "")
+            .file(PiholeFile::FtlConfig, "")
+            .build_settings();
+        let params = HistoryParams {
+            limit: Some(count),
+            ..HistoryParams::default()
+        };
+
+        let reply = get_history(
+            &test_memory_with_queries(count),
+            &settings,
+            FtlPrivacyLevel::ShowAll,
+            params,
+            &connect_to_ftl_test_db(),
+        )
+        .unwrap();
+        let expected = serde_json::to_value(&reply).unwrap();
 
-        // Deserialize from JSON
-        let cursor = serde_json::from_slice(&decoded)
-            .context(ErrorKind::BadRequest)
-            .map_err(|e| form::Error::validation(e.get_context().to_string()))?;
+        let streamed: Vec<u8> = reply.json_chunks().flatten().collect();
+        let streamed: serde_json::Value = serde_json::from_slice(&streamed).unwrap();
 
-        Ok(cursor)
+        assert_eq!(streamed["history"].as_array().unwrap().len(), count);
+        assert_eq!(streamed, expected);
     }
 }
@@ -12,65 +12,101 @@ use crate::{
     databases::ftl::queries,
     ftl::{FtlMemory, FtlQuery, ShmLockGuard},
     routes::stats::history::endpoints::HistoryParams,
-    util::Error,
+    util::{Error, ErrorKind},
 };
-use diesel::{prelude::*, sqlite::Sqlite};
+use diesel::{expression::BoxableExpression, prelude::*, sql_types::Bool, sqlite::Sqlite};
 use std::{collections::HashSet, iter};
 
-/// Only show queries of the specified client
+/// Only show queries of the specified clients. A query matches if its client
+/// IP or name contains any of the client filters.
 pub fn filter_client<'a>(
     queries_iter: Box<dyn Iterator<Item = &'a FtlQuery> + 'a>,
     params: &HistoryParams,
     ftl_memory: &FtlMemory,
     ftl_lock: &ShmLockGuard<'a>,
 ) -> Result<Box<dyn Iterator<Item = &'a FtlQuery> + 'a>, Error> {
-    if let Some(ref client_filter) = params.client {
-        // Find the matching clients. If none are found, return an empty
-        // iterator because no query can match the client requested
-        let counters = ftl_memory.counters(ftl_lock)?;
-        let strings = ftl_memory.strings(ftl_lock)?;
-        let clients = ftl_memory.clients(ftl_lock)?;
-        let client_ids: HashSet<usize> = clients
-            .iter()
-            .take(counters.total_clients as usize)
-            .enumerate()
-            .filter_map(|(i, client)| {
-                let ip = client.get_ip(&strings);
-                let name = client.get_name(&strings).unwrap_or_default();
-
-                if ip.contains(client_filter) || name.contains(client_filter) {
-                    Some(i)
-                } else {
-                    None
-                }
-            })
-            .collect();
-
-        if !client_ids.is_empty() {
-            Ok(Box::new(queries_iter.filter(move |query| {
-                client_ids.contains(&(query.client_id as usize))
-            })))
-        } else {
-            Ok(Box::new(iter::empty()))
-        }
+    if params.client.is_empty() {
+        return Ok(queries_iter);
+    }
+
+    check_client_filters(&params.client)?;
+
+    // Find the matching clients. If none are found, return an empty iterator
+    // because no query can match the clients requested
+    let counters = ftl_memory.counters(ftl_lock)?;
+    let strings = ftl_memory.strings(ftl_lock)?;
+    let clients = ftl_memory.clients(ftl_lock)?;
+    let client_ids: HashSet<usize> = clients
+        .iter()
+        .take(counters.total_clients as usize)
+        .enumerate()
+        .filter_map(|(i, client)| {
+            let ip = client.get_ip(&strings);
+            let name = client.get_name(&strings).unwrap_or_default();
+
+            if params
+                .client
+                .iter()
+                .any(|filter| ip.contains(filter) || name.contains(filter))
+            {
+                Some(i)
+            } else {
+                None
+            }
+        })
+        .collect();
+
+    if !client_ids.is_empty() {
+        Ok(Box::new(queries_iter.filter(move |query| {
+            client_ids.contains(&(query.client_id as usize))
+        })))
     } else {
-        Ok(queries_iter)
+        Ok(Box::new(iter::empty()))
     }
 }
 
-/// Only show queries of the specified client in database results
+/// Only show queries of the specified clients in database results
 pub fn filter_client_db<'a>(
     db_query: queries::BoxedQuery<'a, Sqlite>,
     params: &HistoryParams,
-) -> queries::BoxedQuery<'a, Sqlite> {
+) -> Result<queries::BoxedQuery<'a, Sqlite>, Error> {
     // Use the Diesel DSL of this table for easy querying
     use self::queries::dsl::*;
 
-    if let Some(ref search_client) = params.client {
-        db_query.filter(client.like(format!("%{}%", search_client)))
-    } else {
-        db_query
+    check_client_filters(&params.client)?;
+
+    let mut patterns = params
+        .client
+        .iter()
+        .map(|search_client| format!("%{}%", search_client));
+
+    let first = match patterns.next() {
+        Some(first) => first,
+        None => return Ok(db_query),
+    };
+
+    // Match any of the clients
+    let first: Box<dyn BoxableExpression<queries, Sqlite, SqlType = Bool>> =
+        Box::new(client.like(first));
+    let condition = patterns.fold(first, |condition, pattern| {
+        Box::new(condition.or(client.like(pattern)))
+    });
+
+    Ok(db_query.filter(condition))
+}
+
+/// An empty client filter would match every client, which is almost certainly
+/// not what was intended, so it is rejected
+fn check_client_filters(filters: &[String]) -> Result<(), Error> {
+    if filters.iter().any(|filter| filter.is_empty()) {
+        return Err(Error::from(ErrorKind::InvalidParameter(
+            "client".to_owned(),
+            String::new(),
+            Vec::new(),
+        )));
     }
+
+    Ok(())
 }
 
 #[cfg(test)]
@@ -84,6 +120,7 @@ mod test {
             endpoints::HistoryParams,
             testing::{test_memory, test_queries},
         },
+        util::ErrorKind,
     };
     use diesel::prelude::*;
 
@@ -95,7 +132,7 @@ mod test {
         let filtered_queries: Vec<&FtlQuery> = filter_client(
             Box::new(queries.iter()),
             &HistoryParams {
-                client: Some("192.168.1.10".to_owned()),
+                client: vec!["192.168.1.10".to_owned()],
                 ..HistoryParams::default()
             },
             &test_memory(),
@@ -116,7 +153,7 @@ mod test {
         let filtered_queries: Vec<&FtlQuery> = filter_client(
             Box::new(queries.iter()),
             &HistoryParams {
-                client: Some(".10".to_owned()),
+                client: vec![".10".to_owned()],
                 ..HistoryParams::default()
             },
             &test_memory(),
@@ -136,7 +173,7 @@ mod test {
         let filtered_queries: Vec<&FtlQuery> = filter_client(
             Box::new(queries.iter()),
             &HistoryParams {
-                client: Some("client1".to_owned()),
+                client: vec!["client1".to_owned()],
                 ..HistoryParams::default()
             },
             &test_memory(),
@@ -157,7 +194,7 @@ mod test {
         let filtered_queries: Vec<&FtlQuery> = filter_client(
             Box::new(queries.iter()),
             &HistoryParams {
-                client: Some("t1".to_owned()),
+                client: vec!["t1".to_owned()],
                 ..HistoryParams::default()
             },
             &test_memory(),
@@ -176,14 +213,88 @@ mod test {
         use crate::databases::ftl::queries::dsl::*;
 
         let params = HistoryParams {
-            client: Some("10.1".to_owned()),
+            client: vec!["10.1".to_owned()],
             ..HistoryParams::default()
         };
 
-        let db_query = filter_client_db(queries.into_boxed(), &params);
+        let db_query = filter_client_db(queries.into_boxed(), &params).unwrap();
         let filtered_queries = execute_query(&connect_to_ftl_test_db(), db_query).unwrap();
 
         assert_eq!(filtered_queries.len(), 1);
         assert_eq!(filtered_queries[0].client, "10.1.1.1");
     }
+
+    /// Queries of any of the clients are returned
+    #[test]
+    fn multiple_clients() {
+        let queries = test_queries();
+        let expected_queries = vec![
+            &queries[0],
+            &queries[1],
+            &queries[2],
+            &queries[6],
+            &queries[7],
+        ];
+        let filtered_queries: Vec<&FtlQuery> = filter_client(
+            Box::new(queries.iter()),
+            &HistoryParams {
+                client: vec!["client1".to_owned(), "192.168.1.12".to_owned()],
+                ..HistoryParams::default()
+            },
+            &test_memory(),
+            &ShmLockGuard::Test,
+        )
+        .unwrap()
+        .collect();
+
+        assert_eq!(filtered_queries, expected_queries);
+    }
+
+    /// Queries of any of the clients are returned. This is a database filter.
+    #[test]
+    fn multiple_clients_database() {
+        use crate::databases::ftl::queries::dsl::*;
+
+        let db = connect_to_ftl_test_db();
+        let count = |clients: &[&str]| {
+            let params = HistoryParams {
+                client: clients.iter().map(|&c| c.to_owned()).collect(),
+                ..HistoryParams::default()
+            };
+            let db_query = filter_client_db(queries.into_boxed(), &params).unwrap();
+
+            execute_query(&db, db_query).unwrap().len()
+        };
+
+        assert_eq!(
+            count(&["10.1", "127.0"]),
+            count(&["10.1"]) + count(&["127.0"])
+        );
+        assert!(count(&["127.0"]) > 0);
+    }
+
+    /// An empty client is rejected, even along with other clients
+    #[test]
+    fn empty_client() {
+        let queries = test_queries();
+        let params = HistoryParams {
+            client: vec!["client1".to_owned(), String::new()],
+            ..HistoryParams::default()
+        };
+
+        let result = filter_client(
+            Box::new(queries.iter()),
+            &params,
+            &test_memory(),
+            &ShmLockGuard::Test,
+        );
+
+        assert_eq!(
+            result.map(|_| ()).unwrap_err().kind(),
+            ErrorKind::InvalidParameter("client".to_owned(), String::new(), Vec::new())
+        );
+        assert!(
+            filter_client_db(crate::databases::ftl::queries::table.into_boxed(), &params).is_err()
+        );
+    }
 }
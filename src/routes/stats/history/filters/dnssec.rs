@@ -10,13 +10,14 @@
 
 use crate::{ftl::FtlQuery, routes::stats::history::endpoints::HistoryParams};
 
-/// Only show queries of the specified DNSSEC type
+/// Only show queries of the specified DNSSEC type. The types are compared by
+/// name, so queries without a DNSSEC type are also unknown.
 pub fn filter_dnssec<'a>(
     queries_iter: Box<dyn Iterator<Item = &'a FtlQuery> + 'a>,
     params: &HistoryParams,
 ) -> Box<dyn Iterator<Item = &'a FtlQuery> + 'a> {
     if let Some(dnssec) = params.dnssec {
-        Box::new(queries_iter.filter(move |query| query.dnssec_type == dnssec))
+        Box::new(queries_iter.filter(move |query| query.dnssec_type.name() == dnssec.name()))
     } else {
         queries_iter
     }
@@ -46,4 +47,27 @@ mod test {
 
         assert_eq!(filtered_queries, expected_queries);
     }
+
+    /// Queries without a DNSSEC type are unknown
+    #[test]
+    fn unknown() {
+        let queries = test_queries();
+        let expected_queries = vec![
+            &queries[4],
+            &queries[5],
+            &queries[6],
+            &queries[7],
+            &queries[8],
+        ];
+        let filtered_queries: Vec<&FtlQuery> = filter_dnssec(
+            Box::new(queries.iter()),
+            &HistoryParams {
+                dnssec: Some(FtlDnssecType::Unknown),
+                ..HistoryParams::default()
+            },
+        )
+        .collect();
+
+        assert_eq!(filtered_queries, expected_queries);
+    }
 }
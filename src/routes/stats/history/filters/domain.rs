@@ -25,6 +25,9 @@ pub fn filter_domain<'a>(
     ftl_lock: &ShmLockGuard<'a>,
 ) -> Result<Box<dyn Iterator<Item = &'a FtlQuery> + 'a>, Error> {
     if let Some(ref domain_filter) = params.domain {
+        // Domains are case-insensitive
+        let domain_filter = domain_filter.to_ascii_lowercase();
+
         // Find the matching domains. If none are found, return an empty
         // iterator because no query can match the domain requested
         let counters = ftl_memory.counters(ftl_lock)?;
@@ -35,7 +38,11 @@ pub fn filter_domain<'a>(
             .take(counters.total_domains as usize)
             .enumerate()
             .filter_map(|(i, domain)| {
-                if domain.get_domain(&strings).contains(domain_filter) {
+                if domain
+                    .get_domain(&strings)
+                    .to_ascii_lowercase()
+                    .contains(&domain_filter)
+                {
                     Some(i)
                 } else {
                     None
@@ -75,11 +82,14 @@ mod test {
     use super::{filter_domain, filter_domain_db};
     use crate::{
         databases::ftl::connect_to_ftl_test_db,
-        ftl::{FtlQuery, ShmLockGuard},
+        ftl::{FtlMemory, FtlQuery, FtlSettings, ShmLockGuard},
         routes::stats::history::{
             database::execute_query,
             endpoints::HistoryParams,
-            testing::{test_memory, test_queries},
+            testing::{
+                test_clients, test_counters, test_domains, test_memory, test_queries, test_strings,
+                test_upstreams,
+            },
         },
     };
     use diesel::prelude::*;
@@ -125,6 +135,42 @@ mod test {
         assert_eq!(filtered_queries, expected_queries);
     }
 
+    /// The substring matching is case-insensitive, and does not match other
+    /// domains
+    #[test]
+    fn case_insensitive() {
+        let mut strings = test_strings();
+        strings.insert(1, "ad.doubleclick.net".to_owned());
+        strings.insert(2, "example.com".to_owned());
+
+        let ftl_memory = FtlMemory::Test {
+            clients: test_clients(),
+            counters: test_counters(),
+            domains: test_domains(),
+            over_time: Vec::new(),
+            strings,
+            queries: test_queries(),
+            upstreams: test_upstreams(),
+            settings: FtlSettings::default(),
+        };
+
+        let queries = test_queries();
+        let expected_queries = vec![&queries[0], &queries[1], &queries[2], &queries[4]];
+        let filtered_queries: Vec<&FtlQuery> = filter_domain(
+            Box::new(queries.iter()),
+            &HistoryParams {
+                domain: Some("DoubleClick".to_owned()),
+                ..HistoryParams::default()
+            },
+            &ftl_memory,
+            &ShmLockGuard::Test,
+        )
+        .unwrap()
+        .collect();
+
+        assert_eq!(filtered_queries, expected_queries);
+    }
+
     /// Only queries with domains similar to the input are returned. This is a
     /// database filter.
     #[test]
@@ -10,9 +10,9 @@
 
 use crate::{
     databases::ftl::queries,
-    env::Env,
     ftl::{FtlMemory, FtlQuery, ShmLockGuard},
-    settings::{ConfigEntry, SetupVarsEntry},
+    services::settings::SettingsRepository,
+    settings::SetupVarsEntry,
     util::Error,
 };
 use diesel::{prelude::*, sqlite::Sqlite};
@@ -21,13 +21,13 @@ use std::collections::HashSet;
 /// Apply the `SetupVarsEntry::ApiExcludeClients` setting
 pub fn filter_excluded_clients<'a>(
     queries_iter: Box<dyn Iterator<Item = &'a FtlQuery> + 'a>,
-    env: &Env,
+    settings: &dyn SettingsRepository,
     ftl_memory: &FtlMemory,
     ftl_lock: &ShmLockGuard<'a>,
 ) -> Result<Box<dyn Iterator<Item = &'a FtlQuery> + 'a>, Error> {
     // Get the excluded clients list
-    let excluded_clients: Vec<String> = SetupVarsEntry::ApiExcludeClients
-        .read_list(env)?
+    let excluded_clients: Vec<String> = settings
+        .get_list(SetupVarsEntry::ApiExcludeClients)?
         .into_iter()
         .map(|s| s.to_lowercase())
         .collect();
@@ -72,14 +72,14 @@ pub fn filter_excluded_clients<'a>(
 /// Apply the `SetupVarsEntry::ApiExcludeClients` setting to database queries
 pub fn filter_excluded_clients_db<'a>(
     db_query: queries::BoxedQuery<'a, Sqlite>,
-    env: &Env,
+    settings: &dyn SettingsRepository,
 ) -> Result<queries::BoxedQuery<'a, Sqlite>, Error> {
     // Use the Diesel DSL of this table for easy querying
     use self::queries::dsl::*;
 
     // Get the excluded clients list
-    let excluded_clients: HashSet<String> = SetupVarsEntry::ApiExcludeClients
-        .read_list(env)?
+    let excluded_clients: HashSet<String> = settings
+        .get_list(SetupVarsEntry::ApiExcludeClients)?
         .into_iter()
         .map(|s| s.to_lowercase())
         .collect();
@@ -109,14 +109,14 @@ mod tests {
     /// No queries should be filtered out if `API_EXCLUDE_CLIENTS` is empty
     #[test]
     fn clients_empty() {
-        let env = TestEnvBuilder::new()
+        let settings = TestEnvBuilder::new()
             .file(PiholeFile::SetupVars, "API_EXCLUDE_CLIENTS=")
-            .build();
+            .build_settings();
         let queries = test_queries();
         let expected_queries: Vec<&FtlQuery> = queries.iter().collect();
         let filtered_queries: Vec<&FtlQuery> = filter_excluded_clients(
             Box::new(queries.iter()),
-            &env,
+            &settings,
             &test_memory(),
             &ShmLockGuard::Test,
         )
@@ -130,9 +130,9 @@ mod tests {
     /// removed
     #[test]
     fn clients() {
-        let env = TestEnvBuilder::new()
+        let settings = TestEnvBuilder::new()
             .file(PiholeFile::SetupVars, "API_EXCLUDE_CLIENTS=192.168.1.11")
-            .build();
+            .build_settings();
         let queries = test_queries();
         let expected_queries = vec![
             &queries[0],
@@ -144,7 +144,7 @@ mod tests {
         ];
         let filtered_queries: Vec<&FtlQuery> = filter_excluded_clients(
             Box::new(queries.iter()),
-            &env,
+            &settings,
             &test_memory(),
             &ShmLockGuard::Test,
         )
@@ -160,11 +160,11 @@ mod tests {
     fn clients_db() {
         use crate::databases::ftl::queries::dsl::*;
 
-        let env = TestEnvBuilder::new()
+        let settings = TestEnvBuilder::new()
             .file(PiholeFile::SetupVars, "API_EXCLUDE_CLIENTS=127.0.0.1")
-            .build();
+            .build_settings();
 
-        let db_query = filter_excluded_clients_db(queries.into_boxed(), &env).unwrap();
+        let db_query = filter_excluded_clients_db(queries.into_boxed(), &settings).unwrap();
         let filtered_queries = execute_query(&connect_to_ftl_test_db(), db_query).unwrap();
 
         assert_eq!(filtered_queries.len(), 1);
@@ -10,9 +10,9 @@
 
 use crate::{
     databases::ftl::queries,
-    env::Env,
     ftl::{FtlMemory, FtlQuery, ShmLockGuard},
-    settings::{ConfigEntry, SetupVarsEntry},
+    services::settings::SettingsRepository,
+    settings::SetupVarsEntry,
     util::Error,
 };
 use diesel::{prelude::*, sqlite::Sqlite};
@@ -21,13 +21,13 @@ use std::collections::HashSet;
 /// Apply the `SetupVarsEntry::ApiExcludeDomains` setting
 pub fn filter_excluded_domains<'a>(
     queries_iter: Box<dyn Iterator<Item = &'a FtlQuery> + 'a>,
-    env: &Env,
+    settings: &dyn SettingsRepository,
     ftl_memory: &FtlMemory,
     ftl_lock: &ShmLockGuard<'a>,
 ) -> Result<Box<dyn Iterator<Item = &'a FtlQuery> + 'a>, Error> {
     // Get the excluded domains list
-    let excluded_domains: Vec<String> = SetupVarsEntry::ApiExcludeDomains
-        .read_list(env)?
+    let excluded_domains: Vec<String> = settings
+        .get_list(SetupVarsEntry::ApiExcludeDomains)?
         .into_iter()
         .map(|s| s.to_lowercase())
         .collect();
@@ -69,14 +69,14 @@ pub fn filter_excluded_domains<'a>(
 /// Apply the `SetupVarsEntry::ApiExcludeDomains` setting to database queries
 pub fn filter_excluded_domains_db<'a>(
     db_query: queries::BoxedQuery<'a, Sqlite>,
-    env: &Env,
+    settings: &dyn SettingsRepository,
 ) -> Result<queries::BoxedQuery<'a, Sqlite>, Error> {
     // Use the Diesel DSL of this table for easy querying
     use self::queries::dsl::*;
 
     // Get the excluded domains list
-    let excluded_domains: HashSet<String> = SetupVarsEntry::ApiExcludeDomains
-        .read_list(env)?
+    let excluded_domains: HashSet<String> = settings
+        .get_list(SetupVarsEntry::ApiExcludeDomains)?
         .into_iter()
         .map(|s| s.to_lowercase())
         .collect();
@@ -106,14 +106,14 @@ mod tests {
     /// No queries should be filtered out if `API_EXCLUDE_DOMAINS` is empty
     #[test]
     fn domains_empty() {
-        let env = TestEnvBuilder::new()
+        let settings = TestEnvBuilder::new()
             .file(PiholeFile::SetupVars, "API_EXCLUDE_DOMAINS=")
-            .build();
+            .build_settings();
         let queries = test_queries();
         let expected_queries: Vec<&FtlQuery> = queries.iter().collect();
         let filtered_queries: Vec<&FtlQuery> = filter_excluded_domains(
             Box::new(queries.iter()),
-            &env,
+            &settings,
             &test_memory(),
             &ShmLockGuard::Test,
         )
@@ -127,15 +127,15 @@ mod tests {
     /// removed
     #[test]
     fn domains() {
-        let env = TestEnvBuilder::new()
+        let settings = TestEnvBuilder::new()
             .file(PiholeFile::SetupVars, "API_EXCLUDE_DOMAINS=domain2.com")
-            .build();
+            .build_settings();
         let queries = test_queries();
         let expected_queries: Vec<&FtlQuery> =
             queries.iter().filter(|query| query.id != 4).collect();
         let filtered_queries: Vec<&FtlQuery> = filter_excluded_domains(
             Box::new(queries.iter()),
-            &env,
+            &settings,
             &test_memory(),
             &ShmLockGuard::Test,
         )
@@ -151,14 +151,14 @@ mod tests {
     fn domains_db() {
         use crate::databases::ftl::queries::dsl::*;
 
-        let env = TestEnvBuilder::new()
+        let settings = TestEnvBuilder::new()
             .file(
                 PiholeFile::SetupVars,
                 "API_EXCLUDE_DOMAINS=0.ubuntu.pool.ntp.org,1.ubuntu.pool.ntp.org",
             )
-            .build();
+            .build_settings();
 
-        let db_query = filter_excluded_domains_db(queries.into_boxed(), &env).unwrap();
+        let db_query = filter_excluded_domains_db(queries.into_boxed(), &settings).unwrap();
         let filtered_queries = execute_query(&connect_to_ftl_test_db(), db_query).unwrap();
 
         for query in filtered_queries {
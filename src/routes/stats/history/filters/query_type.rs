@@ -9,34 +9,50 @@
 // Please see LICENSE file for your rights under this license.
 
 use crate::{
-    databases::ftl::queries, ftl::FtlQuery, routes::stats::history::endpoints::HistoryParams,
+    databases::ftl::queries,
+    ftl::{FtlQuery, FtlQueryType},
+    routes::stats::history::endpoints::HistoryParams,
+    util::Error,
 };
 use diesel::{prelude::*, sqlite::Sqlite};
+use std::collections::HashSet;
 
-/// Only show queries with the specified query type
+/// Only show queries with one of the specified query types
 pub fn filter_query_type<'a>(
     queries_iter: Box<dyn Iterator<Item = &'a FtlQuery> + 'a>,
     params: &HistoryParams,
-) -> Box<dyn Iterator<Item = &'a FtlQuery> + 'a> {
-    if let Some(query_type) = params.query_type {
-        Box::new(queries_iter.filter(move |query| query.query_type == query_type))
+) -> Result<Box<dyn Iterator<Item = &'a FtlQuery> + 'a>, Error> {
+    if let Some(ref query_type_list) = params.query_type {
+        let query_types: HashSet<FtlQueryType> = FtlQueryType::parse_list(query_type_list)?
+            .into_iter()
+            .collect();
+
+        Ok(Box::new(queries_iter.filter(move |query| {
+            query_types.contains(&query.query_type)
+        })))
     } else {
-        queries_iter
+        Ok(queries_iter)
     }
 }
 
-/// Only show queries with the specified query type in database results
+/// Only show queries with one of the specified query types in database
+/// results
 pub fn filter_query_type_db<'a>(
     db_query: queries::BoxedQuery<'a, Sqlite>,
     params: &HistoryParams,
-) -> queries::BoxedQuery<'a, Sqlite> {
+) -> Result<queries::BoxedQuery<'a, Sqlite>, Error> {
     // Use the Diesel DSL of this table for easy querying
     use self::queries::dsl::*;
 
-    if let Some(search_query_type) = params.query_type {
-        db_query.filter(query_type.eq(search_query_type as i32))
+    if let Some(ref query_type_list) = params.query_type {
+        let query_types: Vec<i32> = FtlQueryType::parse_list(query_type_list)?
+            .into_iter()
+            .map(|search_query_type| search_query_type as i32)
+            .collect();
+
+        Ok(db_query.filter(query_type.eq_any(query_types)))
     } else {
-        db_query
+        Ok(db_query)
     }
 }
 
@@ -49,6 +65,7 @@ mod test {
         routes::stats::history::{
             database::execute_query, endpoints::HistoryParams, testing::test_queries,
         },
+        util::ErrorKind,
     };
     use diesel::prelude::*;
 
@@ -60,32 +77,81 @@ mod test {
         let filtered_queries: Vec<&FtlQuery> = filter_query_type(
             Box::new(queries.iter()),
             &HistoryParams {
-                query_type: Some(FtlQueryType::A),
+                query_type: Some("A".to_owned()),
                 ..HistoryParams::default()
             },
         )
+        .unwrap()
         .collect();
 
         assert_eq!(filtered_queries, expected_queries);
     }
 
+    /// Queries with any of the query types in the list are returned
+    #[test]
+    fn query_type_list() {
+        let queries = test_queries();
+        let expected_queries = vec![
+            &queries[0],
+            &queries[2],
+            &queries[3],
+            &queries[6],
+            &queries[8],
+        ];
+        let filtered_queries: Vec<&FtlQuery> = filter_query_type(
+            Box::new(queries.iter()),
+            &HistoryParams {
+                query_type: Some("a,PTR".to_owned()),
+                ..HistoryParams::default()
+            },
+        )
+        .unwrap()
+        .collect();
+
+        assert_eq!(filtered_queries, expected_queries);
+    }
+
+    /// An empty query type list is rejected
+    #[test]
+    fn empty_query_type() {
+        let queries = test_queries();
+        let result = filter_query_type(
+            Box::new(queries.iter()),
+            &HistoryParams {
+                query_type: Some(String::new()),
+                ..HistoryParams::default()
+            },
+        );
+
+        match result.map(|_| ()).unwrap_err().kind() {
+            ErrorKind::InvalidParameter(parameter, value, _) => {
+                assert_eq!(parameter, "query_type");
+                assert_eq!(value, "");
+            }
+            kind => panic!("Unexpected error {:?}", kind),
+        }
+    }
+
     /// Only queries with the input query type are returned. This is a database
     /// filter.
     #[test]
     fn database() {
         use crate::databases::ftl::queries::dsl::*;
 
-        let expected_query_type = FtlQueryType::PTR;
         let params = HistoryParams {
-            query_type: Some(expected_query_type),
+            query_type: Some("PTR,AAAA".to_owned()),
             ..HistoryParams::default()
         };
 
-        let db_query = filter_query_type_db(queries.into_boxed(), &params);
+        let db_query = filter_query_type_db(queries.into_boxed(), &params).unwrap();
         let filtered_queries = execute_query(&connect_to_ftl_test_db(), db_query).unwrap();
 
+        assert!(!filtered_queries.is_empty());
         for query in filtered_queries {
-            assert_eq!(query.query_type, expected_query_type as i32);
+            assert!(
+                query.query_type == FtlQueryType::PTR as i32
+                    || query.query_type == FtlQueryType::AAAA as i32
+            );
         }
     }
 }
@@ -10,9 +10,9 @@
 
 use crate::{
     databases::ftl::queries,
-    env::Env,
     ftl::{FtlQuery, BLOCKED_STATUSES},
-    settings::{ConfigEntry, SetupVarsEntry},
+    services::settings::SettingsRepository,
+    settings::SetupVarsEntry,
     util::Error,
 };
 use diesel::{prelude::*, sqlite::Sqlite};
@@ -22,9 +22,11 @@ use std::iter;
 /// `blockedonly`, etc).
 pub fn filter_setup_vars_setting<'a>(
     queries_iter: Box<dyn Iterator<Item = &'a FtlQuery> + 'a>,
-    env: &Env,
+    settings: &dyn SettingsRepository,
 ) -> Result<Box<dyn Iterator<Item = &'a FtlQuery> + 'a>, Error> {
-    Ok(match SetupVarsEntry::ApiQueryLogShow.read(env)?.as_str() {
+    let setting = settings.get(SetupVarsEntry::ApiQueryLogShow)?;
+
+    Ok(match setting.as_str() {
         "permittedonly" => Box::new(queries_iter.filter(|query| !query.is_blocked())),
         "blockedonly" => Box::new(queries_iter.filter(|query| query.is_blocked())),
         "nothing" => Box::new(iter::empty()),
@@ -36,12 +38,14 @@ pub fn filter_setup_vars_setting<'a>(
 /// `blockedonly`, etc) to database results.
 pub fn filter_setup_vars_setting_db<'a>(
     db_query: queries::BoxedQuery<'a, Sqlite>,
-    env: &Env,
+    settings: &dyn SettingsRepository,
 ) -> Result<queries::BoxedQuery<'a, Sqlite>, Error> {
     // Use the Diesel DSL of this table for easy querying
     use self::queries::dsl::*;
 
-    Ok(match SetupVarsEntry::ApiQueryLogShow.read(env)?.as_str() {
+    let setting = settings.get(SetupVarsEntry::ApiQueryLogShow)?;
+
+    Ok(match setting.as_str() {
         "permittedonly" => db_query.filter(status.ne_all(&BLOCKED_STATUSES)),
         "blockedonly" => db_query.filter(status.eq_any(&BLOCKED_STATUSES)),
         "nothing" => db_query.limit(0),
@@ -64,12 +68,12 @@ mod test {
     /// No queries should be shown if `API_QUERY_LOG_SHOW` equals `nothing`
     #[test]
     fn setting_is_nothing() {
-        let env = TestEnvBuilder::new()
+        let settings = TestEnvBuilder::new()
             .file(PiholeFile::SetupVars, "API_QUERY_LOG_SHOW=nothing")
-            .build();
+            .build_settings();
         let queries = test_queries();
         let filtered_queries: Vec<&FtlQuery> =
-            filter_setup_vars_setting(Box::new(queries.iter()), &env)
+            filter_setup_vars_setting(Box::new(queries.iter()), &settings)
                 .unwrap()
                 .collect();
 
@@ -80,9 +84,9 @@ mod test {
     /// `permittedonly`
     #[test]
     fn setting_is_permitted() {
-        let env = TestEnvBuilder::new()
+        let settings = TestEnvBuilder::new()
             .file(PiholeFile::SetupVars, "API_QUERY_LOG_SHOW=permittedonly")
-            .build();
+            .build_settings();
         let queries = test_queries();
         let expected_queries: Vec<&FtlQuery> = vec![
             &queries[0],
@@ -92,7 +96,7 @@ mod test {
             &queries[8],
         ];
         let filtered_queries: Vec<&FtlQuery> =
-            filter_setup_vars_setting(Box::new(queries.iter()), &env)
+            filter_setup_vars_setting(Box::new(queries.iter()), &settings)
                 .unwrap()
                 .collect();
 
@@ -103,14 +107,14 @@ mod test {
     /// `blockedonly`
     #[test]
     fn setting_is_blocked() {
-        let env = TestEnvBuilder::new()
+        let settings = TestEnvBuilder::new()
             .file(PiholeFile::SetupVars, "API_QUERY_LOG_SHOW=blockedonly")
-            .build();
+            .build_settings();
         let queries = test_queries();
         let expected_queries: Vec<&FtlQuery> =
             vec![&queries[3], &queries[5], &queries[6], &queries[7]];
         let filtered_queries: Vec<&FtlQuery> =
-            filter_setup_vars_setting(Box::new(queries.iter()), &env)
+            filter_setup_vars_setting(Box::new(queries.iter()), &settings)
                 .unwrap()
                 .collect();
 
@@ -123,11 +127,11 @@ mod test {
     fn setting_is_nothing_db() {
         use crate::databases::ftl::queries::dsl::*;
 
-        let env = TestEnvBuilder::new()
+        let settings = TestEnvBuilder::new()
             .file(PiholeFile::SetupVars, "API_QUERY_LOG_SHOW=nothing")
-            .build();
+            .build_settings();
 
-        let db_query = filter_setup_vars_setting_db(queries.into_boxed(), &env).unwrap();
+        let db_query = filter_setup_vars_setting_db(queries.into_boxed(), &settings).unwrap();
         let filtered_queries = execute_query(&connect_to_ftl_test_db(), db_query).unwrap();
 
         assert_eq!(filtered_queries.len(), 0);
@@ -139,11 +143,11 @@ mod test {
     fn setting_is_permitted_db() {
         use crate::databases::ftl::queries::dsl::*;
 
-        let env = TestEnvBuilder::new()
+        let settings = TestEnvBuilder::new()
             .file(PiholeFile::SetupVars, "API_QUERY_LOG_SHOW=permittedonly")
-            .build();
+            .build_settings();
 
-        let db_query = filter_setup_vars_setting_db(queries.into_boxed(), &env).unwrap();
+        let db_query = filter_setup_vars_setting_db(queries.into_boxed(), &settings).unwrap();
         let filtered_queries = execute_query(&connect_to_ftl_test_db(), db_query).unwrap();
 
         for query in filtered_queries {
@@ -157,11 +161,11 @@ mod test {
     fn setting_is_blocked_db() {
         use crate::databases::ftl::queries::dsl::*;
 
-        let env = TestEnvBuilder::new()
+        let settings = TestEnvBuilder::new()
             .file(PiholeFile::SetupVars, "API_QUERY_LOG_SHOW=blockedonly")
-            .build();
+            .build_settings();
 
-        let db_query = filter_setup_vars_setting_db(queries.into_boxed(), &env).unwrap();
+        let db_query = filter_setup_vars_setting_db(queries.into_boxed(), &settings).unwrap();
         let filtered_queries = execute_query(&connect_to_ftl_test_db(), db_query).unwrap();
 
         for query in filtered_queries {
@@ -9,34 +9,46 @@
 // Please see LICENSE file for your rights under this license.
 
 use crate::{
-    databases::ftl::queries, ftl::FtlQuery, routes::stats::history::endpoints::HistoryParams,
+    databases::ftl::queries,
+    ftl::{FtlQuery, FtlQueryStatus},
+    routes::stats::history::endpoints::HistoryParams,
+    util::Error,
 };
 use diesel::{prelude::*, sqlite::Sqlite};
 
-/// Only show queries with the specific status
+/// Only show queries with one of the specified statuses
 pub fn filter_status<'a>(
     queries_iter: Box<dyn Iterator<Item = &'a FtlQuery> + 'a>,
     params: &HistoryParams,
-) -> Box<dyn Iterator<Item = &'a FtlQuery> + 'a> {
-    if let Some(status) = params.status {
-        Box::new(queries_iter.filter(move |query| query.status == status))
+) -> Result<Box<dyn Iterator<Item = &'a FtlQuery> + 'a>, Error> {
+    if let Some(ref status_list) = params.status {
+        let statuses = FtlQueryStatus::parse_list(status_list)?;
+
+        Ok(Box::new(
+            queries_iter.filter(move |query| statuses.contains(&query.status)),
+        ))
     } else {
-        queries_iter
+        Ok(queries_iter)
     }
 }
 
-/// Only show queries with the specific status in database results
+/// Only show queries with one of the specified statuses in database results
 pub fn filter_status_db<'a>(
     db_query: queries::BoxedQuery<'a, Sqlite>,
     params: &HistoryParams,
-) -> queries::BoxedQuery<'a, Sqlite> {
+) -> Result<queries::BoxedQuery<'a, Sqlite>, Error> {
     // Use the Diesel DSL of this table for easy querying
     use self::queries::dsl::*;
 
-    if let Some(search_status) = params.status {
-        db_query.filter(status.eq(search_status as i32))
+    if let Some(ref status_list) = params.status {
+        let statuses: Vec<i32> = FtlQueryStatus::parse_list(status_list)?
+            .into_iter()
+            .map(|search_status| search_status as i32)
+            .collect();
+
+        Ok(db_query.filter(status.eq_any(statuses)))
     } else {
-        db_query
+        Ok(db_query)
     }
 }
 
@@ -49,6 +61,7 @@ mod test {
         routes::stats::history::{
             database::execute_query, endpoints::HistoryParams, testing::test_queries,
         },
+        util::ErrorKind,
     };
     use diesel::prelude::*;
 
@@ -60,15 +73,130 @@ mod test {
         let filtered_queries: Vec<&FtlQuery> = filter_status(
             Box::new(queries.iter()),
             &HistoryParams {
-                status: Some(FtlQueryStatus::Gravity),
+                status: Some("1".to_owned()),
                 ..HistoryParams::default()
             },
         )
+        .unwrap()
         .collect();
 
         assert_eq!(filtered_queries, expected_queries);
     }
 
+    /// Status names select groups of statuses, and can be combined with each
+    /// other and with status numbers
+    #[test]
+    fn multiple_statuses() {
+        let queries = test_queries();
+        let expected_queries = vec![&queries[3], &queries[4], &queries[5], &queries[6]];
+        let filtered_queries: Vec<&FtlQuery> = filter_status(
+            Box::new(queries.iter()),
+            &HistoryParams {
+                status: Some("cached,4, 5,1".to_owned()),
+                ..HistoryParams::default()
+            },
+        )
+        .unwrap()
+        .collect();
+
+        assert_eq!(filtered_queries, expected_queries);
+    }
+
+    /// The names of single statuses can be combined
+    #[test]
+    fn single_status_names() {
+        let queries = test_queries();
+        let expected_queries = vec![&queries[3], &queries[5], &queries[6]];
+        let filtered_queries: Vec<&FtlQuery> = filter_status(
+            Box::new(queries.iter()),
+            &HistoryParams {
+                status: Some("gravity,regex,blacklist".to_owned()),
+                ..HistoryParams::default()
+            },
+        )
+        .unwrap()
+        .collect();
+
+        assert_eq!(filtered_queries, expected_queries);
+    }
+
+    /// Blocked and permitted queries are split by status
+    #[test]
+    fn blocked_permitted() {
+        let queries = test_queries();
+        let blocked_queries = vec![&queries[3], &queries[5], &queries[6], &queries[7]];
+        let permitted_queries = vec![
+            &queries[0],
+            &queries[1],
+            &queries[2],
+            &queries[4],
+            &queries[8],
+        ];
+
+        fn filter<'a>(queries: &'a [FtlQuery], status: &str) -> Vec<&'a FtlQuery> {
+            filter_status(
+                Box::new(queries.iter()),
+                &HistoryParams {
+                    status: Some(status.to_owned()),
+                    ..HistoryParams::default()
+                },
+            )
+            .unwrap()
+            .collect()
+        }
+
+        assert_eq!(filter(&queries, "blocked"), blocked_queries);
+        assert_eq!(filter(&queries, "permitted"), permitted_queries);
+    }
+
+    /// Unknown status names are rejected with the valid values
+    #[test]
+    fn unknown_status() {
+        let queries = test_queries();
+        let error = filter_status(
+            Box::new(queries.iter()),
+            &HistoryParams {
+                status: Some("cached,denied".to_owned()),
+                ..HistoryParams::default()
+            },
+        )
+        .map(|_| ())
+        .unwrap_err();
+
+        assert_eq!(
+            error.kind(),
+            ErrorKind::InvalidParameter(
+                "status".to_owned(),
+                "denied".to_owned(),
+                vec![
+                    "blocked",
+                    "permitted",
+                    "cached",
+                    "forwarded",
+                    "unknown",
+                    "gravity",
+                    "regex",
+                    "blacklist",
+                    "external_blocked_ip",
+                    "external_blocked_null",
+                    "external_blocked_nxra",
+                    "0",
+                    "1",
+                    "2",
+                    "3",
+                    "4",
+                    "5",
+                    "6",
+                    "7",
+                    "8"
+                ]
+                .into_iter()
+                .map(str::to_owned)
+                .collect()
+            )
+        );
+    }
+
     /// Only queries with the input query status are returned. This is a
     /// database filter.
     #[test]
@@ -77,11 +205,11 @@ mod test {
 
         let expected_status = FtlQueryStatus::Forward;
         let params = HistoryParams {
-            status: Some(expected_status),
+            status: Some("forwarded".to_owned()),
             ..HistoryParams::default()
         };
 
-        let db_query = filter_status_db(queries.into_boxed(), &params);
+        let db_query = filter_status_db(queries.into_boxed(), &params).unwrap();
         let filtered_queries = execute_query(&connect_to_ftl_test_db(), db_query).unwrap();
 
         for query in filtered_queries {
@@ -12,29 +12,37 @@ use crate::{
     databases::ftl::queries, ftl::FtlQuery, routes::stats::history::endpoints::HistoryParams,
 };
 use diesel::{prelude::*, sqlite::Sqlite};
+use std::cmp::Ordering;
+
+/// Get the queries between the `from` and `until` timestamps (inclusive).
+/// FTL stores the queries in the order they were made, so the edges of the
+/// range are found with a binary search instead of checking every query.
+pub fn filter_time_range<'a>(queries: &'a [FtlQuery], params: &HistoryParams) -> &'a [FtlQuery] {
+    let start = match params.from {
+        Some(from) => first_query_after(queries, |timestamp| timestamp < from),
+        None => 0,
+    };
+    let end = match params.until {
+        Some(until) => first_query_after(queries, |timestamp| timestamp <= until),
+        None => queries.len(),
+    };
 
-/// Filter out queries before the `from` timestamp
-pub fn filter_time_from<'a>(
-    queries_iter: Box<dyn Iterator<Item = &'a FtlQuery> + 'a>,
-    params: &HistoryParams,
-) -> Box<dyn Iterator<Item = &'a FtlQuery> + 'a> {
-    if let Some(from) = params.from {
-        Box::new(queries_iter.filter(move |query| query.timestamp as u64 >= from))
-    } else {
-        queries_iter
-    }
+    // The range is empty if it is backwards
+    &queries[start..end.max(start)]
 }
 
-/// Filter out queries after the `until` timestamp
-pub fn filter_time_until<'a>(
-    queries_iter: Box<dyn Iterator<Item = &'a FtlQuery> + 'a>,
-    params: &HistoryParams,
-) -> Box<dyn Iterator<Item = &'a FtlQuery> + 'a> {
-    if let Some(until) = params.until {
-        Box::new(queries_iter.filter(move |query| query.timestamp as u64 <= until))
-    } else {
-        queries_iter
-    }
+/// Find the index of the first query which is not `before` the time, or the
+/// number of queries if all of them are
+fn first_query_after(queries: &[FtlQuery], before: impl Fn(u64) -> bool) -> usize {
+    queries
+        .binary_search_by(|query| {
+            if before(query.timestamp as u64) {
+                Ordering::Less
+            } else {
+                Ordering::Greater
+            }
+        })
+        .unwrap_err()
 }
 
 /// Filter out queries before the `from` timestamp in database results
@@ -69,7 +77,7 @@ pub fn filter_time_until_db<'a>(
 
 #[cfg(test)]
 mod test {
-    use super::{filter_time_from, filter_time_from_db, filter_time_until, filter_time_until_db};
+    use super::{filter_time_from_db, filter_time_range, filter_time_until_db};
     use crate::{
         databases::ftl::connect_to_ftl_test_db,
         ftl::FtlQuery,
@@ -84,13 +92,14 @@ mod test {
     fn from() {
         let queries = test_queries();
         let expected_queries: Vec<&FtlQuery> = queries.iter().skip(4).collect();
-        let filtered_queries: Vec<&FtlQuery> = filter_time_from(
-            Box::new(queries.iter()),
+        let filtered_queries: Vec<&FtlQuery> = filter_time_range(
+            &queries,
             &HistoryParams {
                 from: Some(263_584),
                 ..HistoryParams::default()
             },
         )
+        .iter()
         .collect();
 
         assert_eq!(filtered_queries, expected_queries);
@@ -101,18 +110,59 @@ mod test {
     fn until() {
         let queries = test_queries();
         let expected_queries: Vec<&FtlQuery> = queries.iter().take(5).collect();
-        let filtered_queries: Vec<&FtlQuery> = filter_time_until(
-            Box::new(queries.iter()),
+        let filtered_queries: Vec<&FtlQuery> = filter_time_range(
+            &queries,
             &HistoryParams {
                 until: Some(263_584),
                 ..HistoryParams::default()
             },
         )
+        .iter()
         .collect();
 
         assert_eq!(filtered_queries, expected_queries);
     }
 
+    /// Queries with the same timestamp as an edge of the range are included
+    #[test]
+    fn range() {
+        let queries = test_queries();
+        let expected_queries: Vec<&FtlQuery> = queries.iter().skip(2).take(5).collect();
+        let filtered_queries: Vec<&FtlQuery> = filter_time_range(
+            &queries,
+            &HistoryParams {
+                from: Some(263_583),
+                until: Some(263_585),
+                ..HistoryParams::default()
+            },
+        )
+        .iter()
+        .collect();
+
+        assert_eq!(filtered_queries, expected_queries);
+    }
+
+    /// Ranges outside of the queries, or between two queries, are empty
+    #[test]
+    fn empty_range() {
+        let queries = test_queries();
+        let range = |from, until| {
+            filter_time_range(
+                &queries,
+                &HistoryParams {
+                    from: Some(from),
+                    until: Some(until),
+                    ..HistoryParams::default()
+                },
+            )
+            .len()
+        };
+
+        assert_eq!(range(0, 263_580), 0);
+        assert_eq!(range(263_588, 300_000), 0);
+        assert_eq!(range(263_585, 263_584), 0);
+    }
+
     /// Only queries newer than `from` are returned. This is a database filter.
     #[test]
     fn from_db() {
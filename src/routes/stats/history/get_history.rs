@@ -16,23 +16,38 @@ use super::{
 };
 use crate::{
     databases::ftl::FtlDatabase,
-    env::Env,
     ftl::{FtlMemory, FtlQuery},
     routes::stats::history::{database::load_queries_from_database, HistoryReply, QueryReply},
-    settings::{ConfigEntry, FtlConfEntry, FtlPrivacyLevel},
-    util::Error,
+    services::settings::SettingsRepository,
+    settings::FtlPrivacyLevel,
+    util::{Error, ErrorKind},
 };
 use std::time::{SystemTime, UNIX_EPOCH};
 
 /// Get the query history according to the specified parameters
 pub fn get_history(
     ftl_memory: &FtlMemory,
-    env: &Env,
+    settings: &dyn SettingsRepository,
+    privacy_level: FtlPrivacyLevel,
     params: HistoryParams,
     db: &FtlDatabase,
 ) -> Result<HistoryReply, Error> {
+    // Parse the cursor first, so a malformed cursor is always rejected
+    let cursor = params
+        .cursor
+        .as_deref()
+        .map(HistoryCursor::from_base64)
+        .transpose()?;
+
+    // The timestamps should increase from "from" to "until"
+    if let (Some(from), Some(until)) = (params.from, params.until) {
+        if from > until {
+            return Err(Error::from(ErrorKind::BadRequest));
+        }
+    }
+
     // Check if query details are private
-    if FtlConfEntry::PrivacyLevel.read_as::<FtlPrivacyLevel>(env)? >= FtlPrivacyLevel::Maximum {
+    if !privacy_level.allows(FtlPrivacyLevel::Maximum) {
         return Ok(HistoryReply {
             history: Vec::new(),
             cursor: None,
@@ -58,35 +73,30 @@ pub fn get_history(
     // combinations of modifiers to the iterator and not worry about the real
     // type.
 
-    // Start making an iterator by getting valid query references (FTL allocates
-    // more than it uses).
-    let queries_iter = Box::new(
-        queries
-            .iter()
-            // Get the most recent queries first
-            .rev()
-            // Skip the uninitialized queries
-            .skip(queries.len() - counters.total_queries as usize),
-    );
+    // Get the valid queries (FTL allocates more than it uses) in the time
+    // range
+    let queries = &queries[..queries.len().min(counters.total_queries as usize)];
+    let queries = filter_time_range(queries, &params);
+
+    // Start making an iterator, with the most recent queries first
+    let queries_iter = Box::new(queries.iter().rev());
 
     // If there is a cursor, skip to the referenced query
-    let queries_iter = skip_to_cursor(queries_iter, &params);
+    let queries_iter = skip_to_cursor(queries_iter, cursor);
 
     // Apply filters
     let queries_iter = filter_private_queries(queries_iter);
-    let queries_iter = filter_setup_vars_setting(queries_iter, env)?;
-    let queries_iter = filter_time_from(queries_iter, &params);
-    let queries_iter = filter_time_until(queries_iter, &params);
-    let queries_iter = filter_query_type(queries_iter, &params);
+    let queries_iter = filter_setup_vars_setting(queries_iter, settings)?;
+    let queries_iter = filter_query_type(queries_iter, &params)?;
     let queries_iter = filter_upstream(queries_iter, &params, ftl_memory, &lock)?;
     let queries_iter = filter_domain(queries_iter, &params, ftl_memory, &lock)?;
     let queries_iter = filter_client(queries_iter, &params, ftl_memory, &lock)?;
-    let queries_iter = filter_status(queries_iter, &params);
+    let queries_iter = filter_status(queries_iter, &params)?;
     let queries_iter = filter_blocked(queries_iter, &params);
     let queries_iter = filter_dnssec(queries_iter, &params);
     let queries_iter = filter_reply(queries_iter, &params);
-    let queries_iter = filter_excluded_domains(queries_iter, env, ftl_memory, &lock)?;
-    let queries_iter = filter_excluded_clients(queries_iter, env, ftl_memory, &lock)?;
+    let queries_iter = filter_excluded_domains(queries_iter, settings, ftl_memory, &lock)?;
+    let queries_iter = filter_excluded_clients(queries_iter, settings, ftl_memory, &lock)?;
 
     // Get the limit
     let limit = params.limit.unwrap_or(100);
@@ -123,11 +133,15 @@ pub fn get_history(
     // queries in the database.
     let last_db_id = history
         .last()
+        // Queries which are not in the database yet have a database ID of 0.
+        // If the last query is not in the database, none of the queries we
+        // found are, so the search starts with the most recent queries.
+        .filter(|query| query.database_id != 0)
         // Subtract one from the database ID so that the database search starts
         // with the next query instead of the last one we found
         .map(|query| query.database_id - 1)
         // If no queries were found, then use the cursor's database ID
-        .or_else(|| params.cursor.map(|cursor| cursor.db_id).unwrap_or(None));
+        .or_else(|| cursor.and_then(|cursor| cursor.db_id));
 
     // Map the queries into the output format
     let history: Vec<QueryReply> = history
@@ -135,7 +149,7 @@ pub fn get_history(
             // Only take up to the limit this time, not including the last query,
             // because it was just used to get the cursor
             .take(limit)
-            .map(map_query_to_json(ftl_memory, &lock)?)
+            .map(map_query_to_json(ftl_memory, &lock, privacy_level)?)
             .collect();
 
     // If there are not enough queries to reach the limit (next cursor is null),
@@ -146,10 +160,15 @@ pub fn get_history(
         && !is_within_24_hours(params.from, params.until)
     {
         // Load queries from the database
-        let (db_queries, cursor) = load_queries_from_database(db, last_db_id, &params, env, limit)?;
+        let (db_queries, cursor) =
+            load_queries_from_database(db, last_db_id, &params, settings, limit)?;
 
-        // Map the queries into JSON
-        let db_queries = db_queries.into_iter().map(Into::into);
+        // Map the queries into JSON, hiding the details the privacy level
+        // requires. The database does not store the privacy level of each
+        // query, so only the configured level is used.
+        let db_queries = db_queries
+            .into_iter()
+            .map(|query| QueryReply::from(query).redact(privacy_level));
 
         // Update the cursor
         next_cursor = cursor.map(|cursor| cursor.as_base64().unwrap());
@@ -194,14 +213,19 @@ mod test {
     use crate::{
         databases::ftl::connect_to_ftl_test_db,
         env::PiholeFile,
-        ftl::ShmLockGuard,
+        ftl::{FtlMemory, FtlSettings, ShmLockGuard},
         routes::stats::history::{
             get_history::get_history,
             map_query_to_json::map_query_to_json,
-            testing::{test_memory, test_queries},
-            HistoryParams, HistoryReply, QueryReply,
+            testing::{
+                test_clients, test_counters, test_domains, test_memory, test_memory_with_queries,
+                test_queries, test_strings, test_upstreams,
+            },
+            HistoryCursor, HistoryParams, HistoryReply, QueryReply,
         },
+        settings::FtlPrivacyLevel,
         testing::TestEnvBuilder,
+        util::ErrorKind,
     };
 
     /// The default behavior lists the first 100 non-private queries sorted by
@@ -217,13 +241,16 @@ mod test {
         let history: Vec<QueryReply> = expected_queries
             .iter()
             .rev()
-            .map(map_query_to_json(&ftl_memory, &ShmLockGuard::Test).unwrap())
+            .map(
+                map_query_to_json(&ftl_memory, &ShmLockGuard::Test, FtlPrivacyLevel::ShowAll)
+                    .unwrap(),
+            )
             .collect();
 
-        let env = TestEnvBuilder::new()
+        let settings = TestEnvBuilder::new()
             .file(PiholeFile::SetupVars, "")
             .file(PiholeFile::FtlConfig, "")
-            .build();
+            .build_settings();
 
         let expected = HistoryReply {
             history,
@@ -232,7 +259,8 @@ mod test {
 
         let actual = get_history(
             &ftl_memory,
-            &env,
+            &settings,
+            FtlPrivacyLevel::ShowAll,
             HistoryParams::default(),
             &connect_to_ftl_test_db(),
         )
@@ -254,13 +282,16 @@ mod test {
             .iter()
             .rev()
             .take(5)
-            .map(map_query_to_json(&ftl_memory, &ShmLockGuard::Test).unwrap())
+            .map(
+                map_query_to_json(&ftl_memory, &ShmLockGuard::Test, FtlPrivacyLevel::ShowAll)
+                    .unwrap(),
+            )
             .collect();
 
-        let env = TestEnvBuilder::new()
+        let settings = TestEnvBuilder::new()
             .file(PiholeFile::SetupVars, "")
             .file(PiholeFile::FtlConfig, "")
-            .build();
+            .build_settings();
 
         let params = HistoryParams {
             limit: Some(5),
@@ -272,7 +303,14 @@ mod test {
             cursor: Some("eyJpZCI6bnVsbCwiZGJfaWQiOjk3fQ==".to_owned()),
         };
 
-        let actual = get_history(&ftl_memory, &env, params, &connect_to_ftl_test_db()).unwrap();
+        let actual = get_history(
+            &ftl_memory,
+            &settings,
+            FtlPrivacyLevel::ShowAll,
+            params,
+            &connect_to_ftl_test_db(),
+        )
+        .unwrap();
 
         assert_eq!(actual, expected);
     }
@@ -280,9 +318,9 @@ mod test {
     /// Maximum privacy shows no queries
     #[test]
     fn privacy_max() {
-        let env = TestEnvBuilder::new()
+        let settings = TestEnvBuilder::new()
             .file(PiholeFile::FtlConfig, "PRIVACYLEVEL=3")
-            .build();
+            .build_settings();
 
         let expected = HistoryReply {
             history: Vec::new(),
@@ -291,7 +329,8 @@ mod test {
 
         let actual = get_history(
             &test_memory(),
-            &env,
+            &settings,
+            FtlPrivacyLevel::Maximum,
             HistoryParams::default(),
             &connect_to_ftl_test_db(),
         )
@@ -310,7 +349,8 @@ mod test {
                 status: 2,
                 domain: "4.4.8.8.in-addr.arpa".to_owned(),
                 client: "127.0.0.1".to_owned(),
-                dnssec: 5,
+                client_name: None,
+                dnssec: "unknown",
                 reply: 0,
                 response_time: 0,
             },
@@ -320,16 +360,17 @@ mod test {
                 status: 3,
                 domain: "1.1.1.10.in-addr.arpa".to_owned(),
                 client: "127.0.0.1".to_owned(),
-                dnssec: 5,
+                client_name: None,
+                dnssec: "unknown",
                 reply: 0,
                 response_time: 0,
             },
         ];
 
-        let env = TestEnvBuilder::new()
+        let settings = TestEnvBuilder::new()
             .file(PiholeFile::SetupVars, "")
             .file(PiholeFile::FtlConfig, "")
-            .build();
+            .build_settings();
 
         let params = HistoryParams {
             from: Some(177_180),
@@ -342,8 +383,503 @@ mod test {
             cursor: None,
         };
 
-        let actual = get_history(&test_memory(), &env, params, &connect_to_ftl_test_db()).unwrap();
+        let actual = get_history(
+            &test_memory(),
+            &settings,
+            FtlPrivacyLevel::ShowAll,
+            params,
+            &connect_to_ftl_test_db(),
+        )
+        .unwrap();
+
+        assert_eq!(actual, expected);
+    }
+
+    /// Following the cursors pages through all of the queries exactly once,
+    /// even when new queries arrive between pages
+    #[test]
+    fn pagination() {
+        let settings = TestEnvBuilder::new()
+            .file(PiholeFile::SetupVars, "")
+            .file(PiholeFile::FtlConfig, "")
+            .build_settings();
+        let db = connect_to_ftl_test_db();
+
+        let mut timestamps = Vec::new();
+        let mut cursor = None;
+        let mut query_count = 300;
+
+        loop {
+            let params = HistoryParams {
+                cursor: cursor.take(),
+                limit: Some(70),
+                ..HistoryParams::default()
+            };
+            let reply = get_history(
+                &test_memory_with_queries(query_count),
+                &settings,
+                FtlPrivacyLevel::ShowAll,
+                params,
+                &db,
+            )
+            .unwrap();
+
+            timestamps.extend(reply.history.iter().map(|query| query.timestamp));
+
+            match reply.cursor {
+                Some(next_cursor) => cursor = Some(next_cursor),
+                None => break,
+            }
+
+            // New queries arrive before the next page is loaded
+            query_count += 5;
+        }
+
+        let expected: Vec<u64> = (1..=300).rev().collect();
+
+        assert_eq!(timestamps, expected);
+    }
+
+    /// The cursor references the first query of the next page
+    #[test]
+    fn pagination_cursor() {
+        let settings = TestEnvBuilder::new()
+            .file(PiholeFile::SetupVars, "")
+            .file(PiholeFile::FtlConfig, "")
+            .build_settings();
+
+        let params = HistoryParams {
+            limit: Some(100),
+            ..HistoryParams::default()
+        };
+        let reply = get_history(
+            &test_memory_with_queries(300),
+            &settings,
+            FtlPrivacyLevel::ShowAll,
+            params,
+            &connect_to_ftl_test_db(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            HistoryCursor::from_base64(&reply.cursor.unwrap()).unwrap(),
+            HistoryCursor {
+                id: Some(200),
+                db_id: None
+            }
+        );
+    }
+
+    /// Malformed cursors are rejected
+    #[test]
+    fn invalid_cursor() {
+        let settings = TestEnvBuilder::new()
+            .file(PiholeFile::SetupVars, "")
+            .file(PiholeFile::FtlConfig, "")
+            .build_settings();
+
+        // Not Base64, not a cursor object, and a cursor without any IDs
+        for cursor in &["not base64!", "MQ==", "e30="] {
+            let params = HistoryParams {
+                cursor: Some((*cursor).to_owned()),
+                ..HistoryParams::default()
+            };
+            let error = get_history(
+                &test_memory_with_queries(300),
+                &settings,
+                FtlPrivacyLevel::ShowAll,
+                params,
+                &connect_to_ftl_test_db(),
+            )
+            .unwrap_err();
+
+            assert_eq!(error.kind(), ErrorKind::InvalidCursor((*cursor).to_owned()));
+        }
+    }
+
+    /// A time window only returns the queries inside of it, along with a
+    /// cursor for the rest of the window
+    #[test]
+    fn time_window() {
+        let settings = TestEnvBuilder::new()
+            .file(PiholeFile::SetupVars, "")
+            .file(PiholeFile::FtlConfig, "")
+            .build_settings();
+        let params = HistoryParams {
+            from: Some(100),
+            until: Some(199),
+            limit: Some(50),
+            ..HistoryParams::default()
+        };
+
+        let reply = get_history(
+            &test_memory_with_queries(300),
+            &settings,
+            FtlPrivacyLevel::ShowAll,
+            params,
+            &connect_to_ftl_test_db(),
+        )
+        .unwrap();
+        let timestamps: Vec<u64> = reply.history.iter().map(|query| query.timestamp).collect();
+
+        assert_eq!(timestamps, (150..=199).rev().collect::<Vec<u64>>());
+        assert_eq!(
+            reply.cursor,
+            Some(
+                HistoryCursor {
+                    id: Some(149),
+                    db_id: None
+                }
+                .as_base64()
+                .unwrap()
+            )
+        );
+    }
+
+    /// A time window which goes backwards is rejected
+    #[test]
+    fn backwards_time_window() {
+        let settings = TestEnvBuilder::new()
+            .file(PiholeFile::SetupVars, "")
+            .file(PiholeFile::FtlConfig, "")
+            .build_settings();
+        let params = HistoryParams {
+            from: Some(200),
+            until: Some(100),
+            ..HistoryParams::default()
+        };
+
+        let error = get_history(
+            &test_memory_with_queries(300),
+            &settings,
+            FtlPrivacyLevel::ShowAll,
+            params,
+            &connect_to_ftl_test_db(),
+        )
+        .unwrap_err();
+
+        assert_eq!(error.kind(), ErrorKind::BadRequest);
+    }
+
+    /// The client filter can be combined with the limit and cursor
+    #[test]
+    fn client_pagination() {
+        let ftl_memory = test_memory();
+        let queries = test_queries();
+        let settings = TestEnvBuilder::new()
+            .file(PiholeFile::SetupVars, "")
+            .file(PiholeFile::FtlConfig, "")
+            .build_settings();
+        let db = connect_to_ftl_test_db();
+        let lock = ShmLockGuard::Test;
+        let to_json = map_query_to_json(&ftl_memory, &lock, FtlPrivacyLevel::ShowAll).unwrap();
+
+        let params = HistoryParams {
+            client: vec!["192.168.1.11".to_owned()],
+            limit: Some(2),
+            ..HistoryParams::default()
+        };
+        let first_page = get_history(
+            &ftl_memory,
+            &settings,
+            FtlPrivacyLevel::ShowAll,
+            params,
+            &db,
+        )
+        .unwrap();
+
+        assert_eq!(
+            first_page.history,
+            vec![to_json(&queries[5]), to_json(&queries[4])]
+        );
+
+        let params = HistoryParams {
+            client: vec!["192.168.1.11".to_owned()],
+            limit: Some(2),
+            cursor: first_page.cursor,
+            ..HistoryParams::default()
+        };
+        let second_page = get_history(
+            &ftl_memory,
+            &settings,
+            FtlPrivacyLevel::ShowAll,
+            params,
+            &db,
+        )
+        .unwrap();
+
+        assert_eq!(
+            second_page,
+            HistoryReply {
+                history: vec![to_json(&queries[3])],
+                cursor: None
+            }
+        );
+    }
+
+    /// An unknown client gives no queries instead of an error
+    #[test]
+    fn unknown_client() {
+        let settings = TestEnvBuilder::new()
+            .file(PiholeFile::SetupVars, "")
+            .file(PiholeFile::FtlConfig, "")
+            .build_settings();
+
+        let params = HistoryParams {
+            client: vec!["192.168.1.42".to_owned()],
+            ..HistoryParams::default()
+        };
+
+        let expected = HistoryReply {
+            history: Vec::new(),
+            cursor: None,
+        };
+
+        let actual = get_history(
+            &test_memory(),
+            &settings,
+            FtlPrivacyLevel::ShowAll,
+            params,
+            &connect_to_ftl_test_db(),
+        )
+        .unwrap();
+
+        assert_eq!(actual, expected);
+    }
+
+    /// Several clients can be combined with lists of statuses and query types
+    #[test]
+    fn multiple_values() {
+        let ftl_memory = test_memory();
+        let queries = test_queries();
+        let settings = TestEnvBuilder::new()
+            .file(PiholeFile::SetupVars, "")
+            .file(PiholeFile::FtlConfig, "")
+            .build_settings();
+        let lock = ShmLockGuard::Test;
+        let to_json = map_query_to_json(&ftl_memory, &lock, FtlPrivacyLevel::ShowAll).unwrap();
+
+        let params = HistoryParams {
+            client: vec!["192.168.1.11".to_owned(), "192.168.1.12".to_owned()],
+            status: Some("gravity,regex,blacklist".to_owned()),
+            query_type: Some("A,PTR".to_owned()),
+            ..HistoryParams::default()
+        };
+        let reply = get_history(
+            &ftl_memory,
+            &settings,
+            FtlPrivacyLevel::ShowAll,
+            params,
+            &connect_to_ftl_test_db(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            reply.history,
+            vec![to_json(&queries[6]), to_json(&queries[3])]
+        );
+    }
+
+    /// The domain filter can be combined with the client and status filters
+    #[test]
+    fn domain_client_status() {
+        let ftl_memory = test_memory();
+        let queries = test_queries();
+        let settings = TestEnvBuilder::new()
+            .file(PiholeFile::SetupVars, "")
+            .file(PiholeFile::FtlConfig, "")
+            .build_settings();
+        let lock = ShmLockGuard::Test;
+        let to_json = map_query_to_json(&ftl_memory, &lock, FtlPrivacyLevel::ShowAll).unwrap();
+
+        let params = HistoryParams {
+            domain: Some("DOMAIN1".to_owned()),
+            client: vec!["192.168.1.11".to_owned()],
+            status: Some("cached".to_owned()),
+            ..HistoryParams::default()
+        };
+
+        let expected = HistoryReply {
+            history: vec![to_json(&queries[4])],
+            cursor: None,
+        };
+
+        let actual = get_history(
+            &ftl_memory,
+            &settings,
+            FtlPrivacyLevel::ShowAll,
+            params,
+            &connect_to_ftl_test_db(),
+        )
+        .unwrap();
+
+        assert_eq!(actual, expected);
+    }
+
+    /// When the time range covers both the in-memory queries and the database,
+    /// the database queries follow the in-memory queries without duplicates
+    #[test]
+    fn database_and_memory() {
+        let ftl_memory = test_memory();
+        let mut expected_queries = test_queries();
+
+        // The private query should be ignored
+        expected_queries.remove(8);
+
+        let lock = ShmLockGuard::Test;
+        let mut history: Vec<QueryReply> = expected_queries
+            .iter()
+            .rev()
+            .map(map_query_to_json(&ftl_memory, &lock, FtlPrivacyLevel::ShowAll).unwrap())
+            .collect();
+        history.push(QueryReply {
+            timestamp: 177_180,
+            r#type: 6,
+            status: 2,
+            domain: "4.4.8.8.in-addr.arpa".to_owned(),
+            client: "127.0.0.1".to_owned(),
+            client_name: None,
+            dnssec: "unknown",
+            reply: 0,
+            response_time: 0,
+        });
+        history.push(QueryReply {
+            timestamp: 177_180,
+            r#type: 6,
+            status: 3,
+            domain: "1.1.1.10.in-addr.arpa".to_owned(),
+            client: "127.0.0.1".to_owned(),
+            client_name: None,
+            dnssec: "unknown",
+            reply: 0,
+            response_time: 0,
+        });
+
+        let settings = TestEnvBuilder::new()
+            .file(PiholeFile::SetupVars, "")
+            .file(PiholeFile::FtlConfig, "")
+            .build_settings();
+
+        let params = HistoryParams {
+            from: Some(177_180),
+            ..HistoryParams::default()
+        };
+
+        let expected = HistoryReply {
+            history,
+            cursor: None,
+        };
+
+        let actual = get_history(
+            &ftl_memory,
+            &settings,
+            FtlPrivacyLevel::ShowAll,
+            params,
+            &connect_to_ftl_test_db(),
+        )
+        .unwrap();
 
         assert_eq!(actual, expected);
     }
+
+    /// If none of the in-memory queries which were found are in the database,
+    /// the database search starts with the most recent queries
+    #[test]
+    fn database_after_unsaved_queries() {
+        let mut strings = test_strings();
+        strings.insert(5, "4.4.8.8.in-addr.arpa".to_owned());
+
+        let ftl_memory = FtlMemory::Test {
+            clients: test_clients(),
+            counters: test_counters(),
+            domains: test_domains(),
+            over_time: Vec::new(),
+            strings,
+            queries: test_queries(),
+            upstreams: test_upstreams(),
+            settings: FtlSettings::default(),
+        };
+        let queries = test_queries();
+
+        let settings = TestEnvBuilder::new()
+            .file(PiholeFile::SetupVars, "")
+            .file(PiholeFile::FtlConfig, "")
+            .build_settings();
+
+        let params = HistoryParams {
+            from: Some(177_180),
+            domain: Some("4.4.8.8".to_owned()),
+            ..HistoryParams::default()
+        };
+
+        let actual = get_history(
+            &ftl_memory,
+            &settings,
+            FtlPrivacyLevel::ShowAll,
+            params,
+            &connect_to_ftl_test_db(),
+        )
+        .unwrap();
+        let lock = ShmLockGuard::Test;
+        let to_json = map_query_to_json(&ftl_memory, &lock, FtlPrivacyLevel::ShowAll).unwrap();
+
+        assert_eq!(
+            actual.history,
+            vec![
+                to_json(&queries[7]),
+                QueryReply {
+                    timestamp: 177_180,
+                    r#type: 6,
+                    status: 2,
+                    domain: "4.4.8.8.in-addr.arpa".to_owned(),
+                    client: "127.0.0.1".to_owned(),
+                    client_name: None,
+                    dnssec: "unknown",
+                    reply: 0,
+                    response_time: 0,
+                }
+            ]
+        );
+    }
+
+    /// The configured privacy level hides the domains and clients of every
+    /// query, and the maximum level hides the queries entirely
+    #[test]
+    fn privacy_levels() {
+        let ftl_memory = test_memory();
+        let settings = TestEnvBuilder::new()
+            .file(PiholeFile::SetupVars, "")
+            .file(PiholeFile::FtlConfig, "")
+            .build_settings();
+        let levels = [
+            FtlPrivacyLevel::ShowAll,
+            FtlPrivacyLevel::HideDomains,
+            FtlPrivacyLevel::HideDomainsAndClients,
+            FtlPrivacyLevel::Maximum,
+        ];
+
+        for &privacy_level in &levels {
+            let actual = get_history(
+                &ftl_memory,
+                &settings,
+                privacy_level,
+                HistoryParams::default(),
+                &connect_to_ftl_test_db(),
+            )
+            .unwrap();
+
+            if privacy_level == FtlPrivacyLevel::Maximum {
+                assert!(actual.history.is_empty());
+                continue;
+            }
+
+            // The private query is always ignored
+            assert_eq!(actual.history.len(), 8);
+
+            for query in actual.history {
+                assert_eq!(query.domain == "hidden", privacy_level.hides_domains());
+                assert_eq!(query.client == "0.0.0.0", privacy_level.hides_clients());
+            }
+        }
+    }
 }
@@ -10,42 +10,60 @@
 
 use crate::{
     ftl::{FtlMemory, FtlQuery, ShmLockGuard},
-    routes::stats::{
-        common::{HIDDEN_CLIENT, HIDDEN_DOMAIN},
-        history::QueryReply,
-    },
-    settings::FtlPrivacyLevel,
+    routes::stats::history::QueryReply,
+    settings::{FtlPrivacyLevel, HIDDEN_CLIENT, HIDDEN_DOMAIN},
     util::Error,
 };
 
 /// Create a function to map `FtlQuery` structs to JSON `Value` structs. The
-/// queries' privacy levels will be taken into account when exposing their data.
+/// stricter of the configured privacy level and the query's privacy level
+/// will be taken into account when exposing its data.
 pub fn map_query_to_json<'a>(
     ftl_memory: &'a FtlMemory,
     ftl_lock: &ShmLockGuard<'a>,
+    privacy_level: FtlPrivacyLevel,
 ) -> Result<impl Fn(&FtlQuery) -> QueryReply + 'a, Error> {
     let domains = ftl_memory.domains(ftl_lock)?;
     let clients = ftl_memory.clients(ftl_lock)?;
     let strings = ftl_memory.strings(ftl_lock)?;
 
     Ok(move |query: &FtlQuery| {
+        let privacy_level = privacy_level.stricter(query.privacy_level);
+
         // Get the domain depending on the privacy level
-        let domain = if query.privacy_level < FtlPrivacyLevel::HideDomains {
-            domains[query.domain_id as usize].get_domain(&strings)
-        } else {
+        let domain = if privacy_level.hides_domains() {
             HIDDEN_DOMAIN
+        } else {
+            domains
+                .get(query.domain_id as usize)
+                .map(|domain| domain.get_domain(&strings))
+                .unwrap_or_default()
         };
 
-        // Get the client depending on the privacy level
-        let client = if query.privacy_level < FtlPrivacyLevel::HideDomainsAndClients {
-            let client = clients[query.client_id as usize];
+        // The client is only missing if shared memory is inconsistent
+        let ftl_client = clients.get(query.client_id as usize);
 
+        // Get the client depending on the privacy level
+        let client = if privacy_level.hides_clients() {
+            HIDDEN_CLIENT
+        } else {
             // Try to get the client name first, but if it doesn't exist use the IP
-            client
-                .get_name(&strings)
-                .unwrap_or_else(|| client.get_ip(&strings))
+            ftl_client
+                .map(|client| {
+                    client
+                        .get_name(&strings)
+                        .unwrap_or_else(|| client.get_ip(&strings))
+                })
+                .unwrap_or_default()
+        };
+
+        // Get the client name, which is only known if the client is not private
+        let client_name = if privacy_level.hides_clients() {
+            None
         } else {
-            HIDDEN_CLIENT
+            ftl_client
+                .and_then(|client| client.get_name(&strings))
+                .map(str::to_owned)
         };
 
         // Check if response was received (response time should be smaller than 30min)
@@ -61,7 +79,8 @@ pub fn map_query_to_json<'a>(
             status: query.status as u8,
             domain: domain.to_owned(),
             client: client.to_owned(),
-            dnssec: query.dnssec_type as u8,
+            client_name,
+            dnssec: query.dnssec_type.name(),
             reply: query.reply_type as u8,
             response_time,
         }
@@ -85,7 +104,8 @@ mod test {
     fn test_map_query_to_json() {
         let query = test_queries()[0];
         let ftl_memory = test_memory();
-        let map_function = map_query_to_json(&ftl_memory, &ShmLockGuard::Test).unwrap();
+        let map_function =
+            map_query_to_json(&ftl_memory, &ShmLockGuard::Test, FtlPrivacyLevel::ShowAll).unwrap();
         let mapped_query = map_function(&query);
 
         assert_eq!(
@@ -96,7 +116,8 @@ mod test {
                 status: 2,
                 domain: "domain1.com".to_owned(),
                 client: "client1".to_owned(),
-                dnssec: 1,
+                client_name: Some("client1".to_owned()),
+                dnssec: "secure",
                 reply: 3,
                 response_time: 1
             }
@@ -108,7 +129,8 @@ mod test {
     fn private_domains() {
         let mut query = test_queries()[0];
         let ftl_memory = test_memory();
-        let map_function = map_query_to_json(&ftl_memory, &ShmLockGuard::Test).unwrap();
+        let map_function =
+            map_query_to_json(&ftl_memory, &ShmLockGuard::Test, FtlPrivacyLevel::ShowAll).unwrap();
 
         query.privacy_level = FtlPrivacyLevel::HideDomains;
         let mapped_query = map_function(&query);
@@ -121,7 +143,8 @@ mod test {
                 status: 2,
                 domain: "hidden".to_owned(),
                 client: "client1".to_owned(),
-                dnssec: 1,
+                client_name: Some("client1".to_owned()),
+                dnssec: "secure",
                 reply: 3,
                 response_time: 1
             }
@@ -133,7 +156,8 @@ mod test {
     fn private_clients() {
         let mut query = test_queries()[0];
         let ftl_memory = test_memory();
-        let map_function = map_query_to_json(&ftl_memory, &ShmLockGuard::Test).unwrap();
+        let map_function =
+            map_query_to_json(&ftl_memory, &ShmLockGuard::Test, FtlPrivacyLevel::ShowAll).unwrap();
 
         query.privacy_level = FtlPrivacyLevel::HideDomainsAndClients;
         let mapped_query = map_function(&query);
@@ -146,10 +170,67 @@ mod test {
                 status: 2,
                 domain: "hidden".to_owned(),
                 client: "0.0.0.0".to_owned(),
-                dnssec: 1,
+                client_name: None,
+                dnssec: "secure",
                 reply: 3,
                 response_time: 1
             }
         );
     }
+
+    /// Clients without a name use the IP as the client and have no name
+    #[test]
+    fn unnamed_client() {
+        let query = test_queries()[3];
+        let ftl_memory = test_memory();
+        let map_function =
+            map_query_to_json(&ftl_memory, &ShmLockGuard::Test, FtlPrivacyLevel::ShowAll).unwrap();
+        let mapped_query = map_function(&query);
+
+        assert_eq!(mapped_query.client, "192.168.1.11");
+        assert_eq!(mapped_query.client_name, None);
+    }
+
+    /// DNSSEC types are named, and queries without a DNSSEC type are unknown
+    #[test]
+    fn dnssec_names() {
+        let ftl_memory = test_memory();
+        let map_function =
+            map_query_to_json(&ftl_memory, &ShmLockGuard::Test, FtlPrivacyLevel::ShowAll).unwrap();
+        let dnssec: Vec<&str> = test_queries()
+            .iter()
+            .take(6)
+            .map(|query| map_function(query).dnssec)
+            .collect();
+
+        assert_eq!(
+            dnssec,
+            vec![
+                "secure",
+                "insecure",
+                "bogus",
+                "abandoned",
+                "unknown",
+                "unknown"
+            ]
+        );
+    }
+
+    /// Domain and client IDs past the end of shared memory are mapped to empty
+    /// strings instead of panicking
+    #[test]
+    fn id_past_end() {
+        let mut query = test_queries()[0];
+        let ftl_memory = test_memory();
+        let map_function =
+            map_query_to_json(&ftl_memory, &ShmLockGuard::Test, FtlPrivacyLevel::ShowAll).unwrap();
+
+        query.domain_id = 99;
+        query.client_id = -1;
+        let mapped_query = map_function(&query);
+
+        assert_eq!(mapped_query.domain, "");
+        assert_eq!(mapped_query.client, "");
+        assert_eq!(mapped_query.client_name, None);
+    }
 }
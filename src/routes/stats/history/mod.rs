@@ -8,9 +8,9 @@
 // This file is copyright under the latest version of the EUPL.
 // Please see LICENSE file for your rights under this license.
 
-mod database;
+pub mod database;
 mod endpoints;
-mod filters;
+pub mod filters;
 mod get_history;
 mod map_query_to_json;
 mod skip_to_cursor;
@@ -9,16 +9,16 @@
 // Please see LICENSE file for your rights under this license.
 
 use crate::{
-    databases::ftl::queries, ftl::FtlQuery, routes::stats::history::endpoints::HistoryParams,
+    databases::ftl::queries, ftl::FtlQuery, routes::stats::history::endpoints::HistoryCursor,
 };
 use diesel::{prelude::*, sqlite::Sqlite};
 
 /// Skip iteration until the query which corresponds to the cursor.
 pub fn skip_to_cursor<'a>(
     queries_iter: Box<dyn Iterator<Item = &'a FtlQuery> + 'a>,
-    params: &HistoryParams,
+    cursor: Option<HistoryCursor>,
 ) -> Box<dyn Iterator<Item = &'a FtlQuery> + 'a> {
-    if let Some(cursor) = params.cursor {
+    if let Some(cursor) = cursor {
         if let Some(id) = cursor.id {
             Box::new(queries_iter.skip_while(move |query| query.id as i32 != id))
         } else if let Some(db_id) = cursor.db_id {
@@ -55,9 +55,7 @@ mod test {
         databases::ftl::{connect_to_ftl_test_db, FtlDbQuery},
         ftl::FtlQuery,
         routes::stats::history::{
-            database::execute_query,
-            endpoints::{HistoryCursor, HistoryParams},
-            testing::test_queries,
+            database::execute_query, endpoints::HistoryCursor, testing::test_queries,
         },
     };
     use diesel::prelude::*;
@@ -69,13 +67,10 @@ mod test {
         let expected_queries: Vec<&FtlQuery> = queries.iter().skip(7).collect();
         let filtered_queries: Vec<&FtlQuery> = skip_to_cursor(
             Box::new(queries.iter()),
-            &HistoryParams {
-                cursor: Some(HistoryCursor {
-                    id: Some(8),
-                    db_id: None,
-                }),
-                ..HistoryParams::default()
-            },
+            Some(HistoryCursor {
+                id: Some(8),
+                db_id: None,
+            }),
         )
         .collect();
 
@@ -89,13 +84,10 @@ mod test {
         let expected_queries: Vec<&FtlQuery> = queries.iter().skip(4).collect();
         let filtered_queries: Vec<&FtlQuery> = skip_to_cursor(
             Box::new(queries.iter()),
-            &HistoryParams {
-                cursor: Some(HistoryCursor {
-                    id: None,
-                    db_id: Some(99),
-                }),
-                ..HistoryParams::default()
-            },
+            Some(HistoryCursor {
+                id: None,
+                db_id: Some(99),
+            }),
         )
         .collect();
 
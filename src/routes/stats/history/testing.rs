@@ -64,8 +64,33 @@ pub fn test_memory() -> FtlMemory {
     }
 }
 
+/// Creates an `FtlMemory` struct with many simple queries, for testing
+/// pagination. The queries have IDs and timestamps from 1 to `count`, and none
+/// of them are in the database.
+pub fn test_memory_with_queries(count: usize) -> FtlMemory {
+    let queries = (1..=count as i32)
+        .map(|id| query!(id, 0, A, Forward, 0, 0, 0, id as libc::time_t, ShowAll))
+        .collect();
+
+    FtlMemory::Test {
+        clients: test_clients(),
+        counters: FtlCounters {
+            total_queries: count as i32,
+            ..test_counters()
+        },
+        domains: test_domains(),
+        over_time: Vec::new(),
+        strings: test_strings(),
+        queries,
+        upstreams: test_upstreams(),
+        settings: FtlSettings::default(),
+    }
+}
+
 /// 9 queries. Query 9 is private. Last two are not in the database. Query 1
-/// has a DNSSEC type of Secure and a reply type of CNAME. The database
+/// has a DNSSEC type of Secure and a reply type of CNAME. Queries 2 to 5 have
+/// DNSSEC types of Insecure, Bogus, Abandoned, and Unknown, and the rest have
+/// no DNSSEC type. The database
 /// timestamps end at 177180, so the in memory queries start at 263581
 /// (24 hours after). The database ids end at 94, so the in memory database IDs
 /// start at 95.
@@ -82,7 +107,7 @@ pub fn test_memory() -> FtlMemory {
 /// | 8  |   0 | AAAA | ExternalB. | 4      | 2      | 1        | 263586    |
 /// | 9  |   0 | A    | Forward    | 5      | 3      | 0        | 263587    |
 pub fn test_queries() -> Vec<FtlQuery> {
-    vec![
+    let mut queries = vec![
         FtlQuery {
             magic: MAGIC_BYTE,
             id: 1,
@@ -108,7 +133,14 @@ pub fn test_queries() -> Vec<FtlQuery> {
         query!(7, 101, A, Blacklist, 3, 2, 0, 263_585, ShowAll),
         query!(8, 0, AAAA, ExternalBlockIp, 4, 2, 1, 263_586, ShowAll),
         query!(9, 0, A, Forward, 5, 3, 0, 263_587, Maximum),
-    ]
+    ];
+
+    queries[1].dnssec_type = FtlDnssecType::Insecure;
+    queries[2].dnssec_type = FtlDnssecType::Bogus;
+    queries[3].dnssec_type = FtlDnssecType::Abandoned;
+    queries[4].dnssec_type = FtlDnssecType::Unknown;
+
+    queries
 }
 
 /// The counters necessary for the history tests.
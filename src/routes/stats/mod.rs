@@ -8,15 +8,28 @@
 // This file is copyright under the latest version of the EUPL.
 // Please see LICENSE file for your rights under this license.
 
+pub mod client_activity;
+pub mod client_detail;
 pub mod clients;
 pub mod common;
 pub mod database;
+pub mod ftl_counters;
 pub mod history;
 pub mod over_time_clients;
 pub mod over_time_history;
+pub mod over_time_query_types;
+pub mod over_time_upstreams;
+pub mod query_statuses;
 pub mod query_types;
+pub mod recent_activity;
 pub mod recent_blocked;
+pub mod reply_times;
+pub mod reply_types;
 pub mod summary;
+pub mod time_zone;
+pub mod top_blocked;
 pub mod top_clients;
 pub mod top_domains;
+pub mod top_sort;
+pub mod upstream_response_times;
 pub mod upstreams;
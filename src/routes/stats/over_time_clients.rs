@@ -9,7 +9,6 @@
 // Please see LICENSE file for your rights under this license.
 
 use crate::{
-    env::Env,
     ftl::{ClientReply, FtlMemory},
     routes::{
         auth::User,
@@ -18,8 +17,8 @@ use crate::{
             common::get_current_over_time_slot,
         },
     },
-    services::PiholeModule,
-    settings::{ConfigEntry, FtlConfEntry, FtlPrivacyLevel},
+    services::{exclusions::ExclusionService, privacy::PrivacyService, PiholeModule},
+    settings::FtlPrivacyLevel,
     util::{reply_data, Reply},
 };
 use rocket::State;
@@ -33,12 +32,12 @@ pub use over_time_clients as route;
 pub fn over_time_clients(
     _auth: User,
     ftl_memory: &State<FtlMemory>,
-    env: Inject<PiholeModule, Env>,
+    privacy: Inject<PiholeModule, dyn PrivacyService>,
+    exclusions: Inject<PiholeModule, dyn ExclusionService>,
 ) -> Reply {
     // Check if client details are private
-    if FtlConfEntry::PrivacyLevel.read_as::<FtlPrivacyLevel>(&env)?
-        >= FtlPrivacyLevel::HideDomainsAndClients
-    {
+    let redactor = privacy.redactor()?;
+    if !redactor.allows(FtlPrivacyLevel::HideDomainsAndClients) {
         return reply_data(OverTimeClients {
             over_time: Vec::new(),
             clients: Vec::new(),
@@ -56,7 +55,8 @@ pub fn over_time_clients(
         ftl_memory,
         &lock,
         &ftl_clients,
-        &env,
+        redactor,
+        &*exclusions.exclusions()?,
         ClientParams::default(),
     )?;
 
@@ -133,8 +133,8 @@ pub struct OverTimeClients {
 mod test {
     use crate::{
         env::PiholeFile,
-        ftl::{FtlClient, FtlCounters, FtlMemory, FtlOverTime, FtlSettings},
-        testing::TestBuilder,
+        ftl::{FtlClient, FtlCounters, FtlMemory, FtlOverTime, FtlQueryStatus, FtlSettings},
+        testing::{TestBuilder, TestFtlMemoryBuilder},
     };
     use std::collections::HashMap;
 
@@ -190,10 +190,10 @@ mod test {
             .file(PiholeFile::FtlConfig, "")
             .expect_json(json!({
                 "clients": [
-                    { "name": "",        "ip": "10.1.1.2" },
+                    { "name": null,      "ip": "10.1.1.2" },
                     { "name": "client3", "ip": "10.1.1.3" },
-                    { "name": "",        "ip": "10.1.1.4" },
-                    { "name": "",        "ip": "10.1.1.5" }
+                    { "name": null,      "ip": "10.1.1.4" },
+                    { "name": null,      "ip": "10.1.1.5" }
                 ],
                 "over_time": [
                     { "timestamp": 1, "data": [1, 1, 0, 0] },
@@ -203,4 +203,94 @@ mod test {
             }))
             .test();
     }
+
+    /// Three clients with queries spread over three overTime slots. The first
+    /// client has a name.
+    fn builder_data() -> FtlMemory {
+        let mut builder = TestFtlMemoryBuilder::new();
+        let alpha = builder.client("10.1.1.1", "alpha");
+        let beta = builder.unnamed_client("10.1.1.2");
+        let gamma = builder.unnamed_client("10.1.1.3");
+        let queries = [
+            (1_000_200, alpha),
+            (1_000_250, alpha),
+            (1_000_300, gamma),
+            (1_000_800, beta),
+            (1_000_900, gamma),
+            (1_001_400, gamma),
+        ];
+
+        for &(timestamp, client) in &queries {
+            builder.query(
+                timestamp,
+                "example.com",
+                client,
+                FtlQueryStatus::Cache,
+                None,
+            );
+        }
+
+        builder.build()
+    }
+
+    /// Each count in a slot belongs to the client at the same index in the
+    /// client list
+    #[test]
+    fn client_alignment() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/overTime/clients")
+            .ftl_memory(builder_data())
+            .file(PiholeFile::SetupVars, "")
+            .file(PiholeFile::FtlConfig, "")
+            .expect_json(json!({
+                "clients": [
+                    { "name": "alpha", "ip": "10.1.1.1" },
+                    { "name": null,    "ip": "10.1.1.2" },
+                    { "name": null,    "ip": "10.1.1.3" }
+                ],
+                "over_time": [
+                    { "timestamp": 1_000_500, "data": [2, 0, 1] },
+                    { "timestamp": 1_001_100, "data": [0, 1, 1] },
+                    { "timestamp": 1_001_700, "data": [0, 0, 1] }
+                ]
+            }))
+            .test();
+    }
+
+    /// Excluding a client removes both it and its column of counts
+    #[test]
+    fn excluded_client_alignment() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/overTime/clients")
+            .ftl_memory(builder_data())
+            .file(PiholeFile::SetupVars, "API_EXCLUDE_CLIENTS=10.1.1.2")
+            .file(PiholeFile::FtlConfig, "")
+            .expect_json(json!({
+                "clients": [
+                    { "name": "alpha", "ip": "10.1.1.1" },
+                    { "name": null,    "ip": "10.1.1.3" }
+                ],
+                "over_time": [
+                    { "timestamp": 1_000_500, "data": [2, 1] },
+                    { "timestamp": 1_001_100, "data": [0, 1] },
+                    { "timestamp": 1_001_700, "data": [0, 1] }
+                ]
+            }))
+            .test();
+    }
+
+    /// Privacy level 2 does not show any clients or overTime data
+    #[test]
+    fn privacy() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/overTime/clients")
+            .ftl_memory(builder_data())
+            .file(PiholeFile::SetupVars, "")
+            .file(PiholeFile::FtlConfig, "PRIVACYLEVEL=2")
+            .expect_json(json!({
+                "clients": [],
+                "over_time": []
+            }))
+            .test();
+    }
 }
@@ -9,17 +9,42 @@
 // Please see LICENSE file for your rights under this license.
 
 use crate::{
-    ftl::FtlMemory,
-    routes::stats::common::get_current_over_time_slot,
+    env::Env,
+    ftl::{FtlMemory, OVERTIME_INTERVAL},
+    routes::stats::{common::get_current_over_time_slot, time_zone::BucketTimeZone},
+    services::PiholeModule,
     util::{reply_data, Reply},
 };
-use rocket::State;
+use rocket::{
+    form::{self, FromFormField, ValueField},
+    State,
+};
+use shaku_rocket::Inject;
 
 pub use over_time_history as route;
 
-/// Get the query history over time (separated into blocked and not blocked)
-#[get("/stats/overTime/history")]
-pub fn over_time_history(ftl_memory: &State<FtlMemory>) -> Reply {
+/// The largest interval the over time data can be grouped into (one day)
+const MAX_INTERVAL: usize = 86_400;
+
+/// Get the query history over time (separated into blocked and not blocked).
+/// If an interval (in seconds) is given, the FTL slots are grouped into
+/// buckets of that size. With `format=percentage`, the percentage of blocked
+/// queries is returned instead, optionally smoothed over `smooth` buckets.
+/// Buckets are aligned to the `timezone` (IANA name) or `utc_offset` (in
+/// seconds), see `BucketTimeZone`.
+#[get("/stats/overTime/history?<interval>&<format>&<smooth>&<timezone>&<utc_offset>")]
+pub fn over_time_history(
+    ftl_memory: &State<FtlMemory>,
+    env: Inject<PiholeModule, Env>,
+    interval: Option<usize>,
+    format: Option<OverTimeFormat>,
+    smooth: Option<usize>,
+    timezone: Option<String>,
+    utc_offset: Option<i32>,
+) -> Reply {
+    let interval = effective_interval(interval);
+    let time_zone = BucketTimeZone::from_params(timezone.as_deref(), utc_offset, &env)?;
+    let utc_offset = time_zone.current_offset();
     let lock = ftl_memory.lock()?;
     let over_time = ftl_memory.over_time(&lock)?;
 
@@ -39,7 +64,153 @@ pub fn over_time_history(ftl_memory: &State<FtlMemory>) -> Reply {
         })
         .collect();
 
-    reply_data(over_time_data)
+    let over_time = group_slots(over_time_data, interval, time_zone);
+
+    match format.unwrap_or(OverTimeFormat::Counts) {
+        OverTimeFormat::Counts => reply_data(OverTimeHistoryReply {
+            interval,
+            utc_offset,
+            over_time,
+        }),
+        OverTimeFormat::Percentage => {
+            let smooth = smooth.unwrap_or(1).max(1);
+            let percentages = moving_average(&block_percentages(&over_time), smooth);
+
+            reply_data(OverTimePercentageReply {
+                interval,
+                utc_offset,
+                smooth,
+                over_time: over_time
+                    .iter()
+                    .zip(percentages)
+                    .map(|(bucket, percentage)| OverTimePercentageItem {
+                        timestamp: bucket.timestamp,
+                        percentage,
+                    })
+                    .collect(),
+            })
+        }
+    }
+}
+
+/// The format of the over time data
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum OverTimeFormat {
+    /// The total and blocked query counts
+    Counts,
+    /// The percentage of queries which were blocked
+    Percentage,
+}
+
+impl<'v> FromFormField<'v> for OverTimeFormat {
+    fn from_value(field: ValueField<'v>) -> form::Result<'v, Self> {
+        match field.value {
+            "counts" => Ok(OverTimeFormat::Counts),
+            "percentage" => Ok(OverTimeFormat::Percentage),
+            _ => Err(form::Error::validation("Unknown over time format").into()),
+        }
+    }
+}
+
+/// Get the interval which will be used for the requested interval. The
+/// interval is clamped between the FTL slot interval and one day, and rounded
+/// down to a whole number of FTL slots.
+pub fn effective_interval(interval: Option<usize>) -> usize {
+    let interval = interval
+        .unwrap_or(OVERTIME_INTERVAL)
+        .clamp(OVERTIME_INTERVAL, MAX_INTERVAL);
+
+    interval - interval % OVERTIME_INTERVAL
+}
+
+/// Group the FTL slots into buckets of the interval. Bucket edges are aligned
+/// to multiples of the interval in the time zone, and the bucket timestamps
+/// are centered in the bucket. Slots are not modified if the interval is the
+/// FTL slot interval.
+fn group_slots(
+    slots: Vec<OverTimeItem>,
+    interval: usize,
+    time_zone: BucketTimeZone,
+) -> Vec<OverTimeItem> {
+    time_zone
+        .group_slots(
+            slots.into_iter().map(|slot| (slot.timestamp, slot)),
+            interval,
+            |bucket, slot| {
+                bucket.total_queries += slot.total_queries;
+                bucket.blocked_queries += slot.blocked_queries;
+            },
+        )
+        .into_iter()
+        .map(|(timestamp, bucket)| OverTimeItem {
+            timestamp,
+            ..bucket
+        })
+        .collect()
+}
+
+/// Get the percentage of queries which were blocked in each bucket. Buckets
+/// without any queries have no percentage, instead of a percentage of zero.
+fn block_percentages(buckets: &[OverTimeItem]) -> Vec<Option<f64>> {
+    buckets
+        .iter()
+        .map(|bucket| {
+            if bucket.total_queries == 0 {
+                None
+            } else {
+                let percentage =
+                    bucket.blocked_queries as f64 * 100.0 / bucket.total_queries as f64;
+
+                Some(percentage.min(100.0))
+            }
+        })
+        .collect()
+}
+
+/// Average each value with the values before it, over a window of `window`
+/// values. Empty values stay empty and are left out of the averages of the
+/// other values. At the start of the series, the window only includes the
+/// values which exist.
+fn moving_average(values: &[Option<f64>], window: usize) -> Vec<Option<f64>> {
+    (0..values.len())
+        .map(|i| {
+            // Empty values stay empty, so gaps are not filled in
+            values[i]?;
+
+            let start = (i + 1).saturating_sub(window);
+            let window_values: Vec<f64> = values[start..=i].iter().filter_map(|&v| v).collect();
+
+            Some(window_values.iter().sum::<f64>() / window_values.len() as f64)
+        })
+        .collect()
+}
+
+/// The over time data, along with the interval of the data and the current
+/// offset of the bucket time zone from UTC, in seconds
+#[derive(Serialize)]
+pub struct OverTimeHistoryReply {
+    pub interval: usize,
+    pub utc_offset: i32,
+    pub over_time: Vec<OverTimeItem>,
+}
+
+/// The blocked percentage over time, along with the interval of the data and
+/// the current offset of the bucket time zone from UTC in seconds, and the
+/// number of buckets it was smoothed over
+#[derive(Serialize)]
+pub struct OverTimePercentageReply {
+    pub interval: usize,
+    pub utc_offset: i32,
+    pub smooth: usize,
+    pub over_time: Vec<OverTimePercentageItem>,
+}
+
+#[derive(Serialize)]
+pub struct OverTimePercentageItem {
+    pub timestamp: u64,
+    /// The percentage of queries which were blocked, or `None` if there were
+    /// no queries
+    pub percentage: Option<f64>,
 }
 
 #[derive(Serialize)]
@@ -52,10 +223,13 @@ pub struct OverTimeItem {
 
 #[cfg(test)]
 mod test {
+    use super::{block_percentages, effective_interval, group_slots, moving_average, OverTimeItem};
     use crate::{
         ftl::{FtlCounters, FtlMemory, FtlOverTime, FtlSettings},
+        routes::stats::time_zone::BucketTimeZone,
         testing::TestBuilder,
     };
+    use rocket::http::Status;
     use std::collections::HashMap;
 
     /// Data for testing over_time_history
@@ -85,11 +259,278 @@ mod test {
         TestBuilder::new()
             .endpoint("/admin/api/stats/overTime/history")
             .ftl_memory(test_data())
-            .expect_json(json!([
-                { "timestamp": 1, "total_queries": 1, "blocked_queries": 0 },
-                { "timestamp": 2, "total_queries": 1, "blocked_queries": 1 },
-                { "timestamp": 3, "total_queries": 0, "blocked_queries": 1 }
-            ]))
+            .expect_json(json!({
+                "interval": 600,
+                "utc_offset": 0,
+                "over_time": [
+                    { "timestamp": 1, "total_queries": 1, "blocked_queries": 0 },
+                    { "timestamp": 2, "total_queries": 1, "blocked_queries": 1 },
+                    { "timestamp": 3, "total_queries": 0, "blocked_queries": 1 }
+                ]
+            }))
+            .test();
+    }
+
+    /// The slots are grouped into buckets of the interval
+    #[test]
+    fn interval() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/overTime/history?interval=3600")
+            .ftl_memory(test_data())
+            .expect_json(json!({
+                "interval": 3600,
+                "utc_offset": 0,
+                "over_time": [
+                    { "timestamp": 1800, "total_queries": 2, "blocked_queries": 2 }
+                ]
+            }))
+            .test();
+    }
+
+    /// The UTC offset is shown in the reply
+    #[test]
+    fn utc_offset() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/overTime/history?interval=3600&utc_offset=-3600")
+            .ftl_memory(test_data())
+            .expect_json(json!({
+                "interval": 3600,
+                "utc_offset": -3600,
+                "over_time": [
+                    { "timestamp": 1800, "total_queries": 2, "blocked_queries": 2 }
+                ]
+            }))
+            .test();
+    }
+
+    /// An unknown time zone is rejected with a 400
+    #[test]
+    fn unknown_time_zone() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/overTime/history?timezone=Mars/Olympus_Mons")
+            .ftl_memory(test_data())
+            .expect_status(Status::BadRequest)
+            .expect_json(json!({
+                "error": {
+                    "key": "invalid_parameter",
+                    "message": "Invalid value Mars/Olympus_Mons for parameter timezone",
+                    "data": {
+                        "parameter": "timezone",
+                        "value": "Mars/Olympus_Mons",
+                        "valid": []
+                    }
+                }
+            }))
             .test();
     }
+
+    /// Short, long, and unaligned intervals are adjusted
+    #[test]
+    fn interval_bounds() {
+        assert_eq!(effective_interval(None), 600);
+        assert_eq!(effective_interval(Some(60)), 600);
+        assert_eq!(effective_interval(Some(1000)), 600);
+        assert_eq!(effective_interval(Some(1900)), 1800);
+        assert_eq!(effective_interval(Some(604_800)), 86_400);
+    }
+
+    /// Both total and blocked queries are summed in each bucket, and bucket
+    /// edges are aligned to the interval
+    #[test]
+    fn group_by_interval() {
+        // Slot timestamps are in the middle of the ten minute slots
+        let slots: Vec<OverTimeItem> = (0..7)
+            .map(|i| OverTimeItem {
+                timestamp: 1200 + 600 * i + 300,
+                total_queries: i as usize + 1,
+                blocked_queries: i as usize % 2,
+            })
+            .collect();
+
+        assert_eq!(
+            group_slots(slots, 1800, BucketTimeZone::Fixed(0)),
+            vec![
+                // 0 to 1800, which only has the 1200 to 1800 slot
+                OverTimeItem {
+                    timestamp: 900,
+                    total_queries: 1,
+                    blocked_queries: 0
+                },
+                // 1800 to 3600
+                OverTimeItem {
+                    timestamp: 2700,
+                    total_queries: 2 + 3 + 4,
+                    blocked_queries: 2
+                },
+                // 3600 to 5400
+                OverTimeItem {
+                    timestamp: 4500,
+                    total_queries: 5 + 6 + 7,
+                    blocked_queries: 1
+                }
+            ]
+        );
+    }
+
+    /// Bucket edges are aligned to the interval in local time
+    #[test]
+    fn group_with_offset() {
+        let slots: Vec<OverTimeItem> = (0..7)
+            .map(|i| OverTimeItem {
+                timestamp: 1200 + 600 * i + 300,
+                total_queries: i as usize + 1,
+                blocked_queries: i as usize % 2,
+            })
+            .collect();
+
+        assert_eq!(
+            group_slots(slots, 1800, BucketTimeZone::Fixed(900)),
+            vec![
+                // 900 to 2700
+                OverTimeItem {
+                    timestamp: 1800,
+                    total_queries: 1 + 2,
+                    blocked_queries: 1
+                },
+                // 2700 to 4500
+                OverTimeItem {
+                    timestamp: 3600,
+                    total_queries: 3 + 4 + 5,
+                    blocked_queries: 1
+                },
+                // 4500 to 6300
+                OverTimeItem {
+                    timestamp: 5400,
+                    total_queries: 6 + 7,
+                    blocked_queries: 1
+                }
+            ]
+        );
+    }
+
+    /// Slots are unchanged with the default interval
+    #[test]
+    fn group_default_interval() {
+        let slots = vec![
+            OverTimeItem {
+                timestamp: 300,
+                total_queries: 4,
+                blocked_queries: 1,
+            },
+            OverTimeItem {
+                timestamp: 900,
+                total_queries: 2,
+                blocked_queries: 2,
+            },
+        ];
+
+        assert_eq!(
+            group_slots(slots, 600, BucketTimeZone::Fixed(0)),
+            vec![
+                OverTimeItem {
+                    timestamp: 300,
+                    total_queries: 4,
+                    blocked_queries: 1
+                },
+                OverTimeItem {
+                    timestamp: 900,
+                    total_queries: 2,
+                    blocked_queries: 2
+                }
+            ]
+        );
+    }
+
+    /// The percentage of blocked queries is returned for each slot, and
+    /// slots without queries are null
+    #[test]
+    fn percentage() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/overTime/history?format=percentage")
+            .ftl_memory(test_data())
+            .expect_json(json!({
+                "interval": 600,
+                "utc_offset": 0,
+                "smooth": 1,
+                "over_time": [
+                    { "timestamp": 1, "percentage": 0.0 },
+                    { "timestamp": 2, "percentage": 100.0 },
+                    { "timestamp": 3, "percentage": null }
+                ]
+            }))
+            .test();
+    }
+
+    /// The percentages are averaged over the smoothing window
+    #[test]
+    fn percentage_smooth() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/overTime/history?format=percentage&smooth=2")
+            .ftl_memory(test_data())
+            .expect_json(json!({
+                "interval": 600,
+                "utc_offset": 0,
+                "smooth": 2,
+                "over_time": [
+                    { "timestamp": 1, "percentage": 0.0 },
+                    { "timestamp": 2, "percentage": 50.0 },
+                    { "timestamp": 3, "percentage": null }
+                ]
+            }))
+            .test();
+    }
+
+    /// The percentage is calculated from the blocked and total queries, and
+    /// empty buckets have no percentage
+    #[test]
+    fn percentages_math() {
+        let buckets = vec![
+            OverTimeItem {
+                timestamp: 300,
+                total_queries: 4,
+                blocked_queries: 1,
+            },
+            OverTimeItem {
+                timestamp: 900,
+                total_queries: 0,
+                blocked_queries: 0,
+            },
+            OverTimeItem {
+                timestamp: 1500,
+                total_queries: 3,
+                blocked_queries: 3,
+            },
+        ];
+
+        assert_eq!(
+            block_percentages(&buckets),
+            vec![Some(25.0), None, Some(100.0)]
+        );
+    }
+
+    /// The window shrinks at the start of the series, and empty values are
+    /// skipped without making the gaps disappear
+    #[test]
+    fn moving_average_edges() {
+        let values = vec![Some(10.0), Some(20.0), None, Some(60.0), Some(30.0)];
+
+        assert_eq!(
+            moving_average(&values, 3),
+            vec![Some(10.0), Some(15.0), None, Some(40.0), Some(45.0)]
+        );
+    }
+
+    /// A window of one slot does not change the values, and a window larger
+    /// than the series averages everything before each value
+    #[test]
+    fn moving_average_window_sizes() {
+        let values = vec![Some(10.0), None, Some(40.0)];
+
+        assert_eq!(moving_average(&values, 1), values);
+        assert_eq!(
+            moving_average(&values, 10),
+            vec![Some(10.0), None, Some(25.0)]
+        );
+        assert_eq!(moving_average(&[], 3), Vec::<Option<f64>>::new());
+    }
 }
@@ -0,0 +1,231 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Query Types Over Time Endpoint
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    env::Env,
+    ftl::{FtlMemory, FtlQueryType},
+    routes::{
+        auth::User,
+        stats::{
+            common::get_current_over_time_slot, over_time_history::effective_interval,
+            time_zone::BucketTimeZone,
+        },
+    },
+    services::PiholeModule,
+    util::{reply_result, Error, Reply},
+};
+use rocket::State;
+use shaku_rocket::Inject;
+
+pub use over_time_query_types as route;
+
+/// Get the queries of each query type over time. If an interval (in seconds)
+/// is given, the FTL slots are grouped into buckets of that size, aligned to
+/// the `timezone` or `utc_offset` like the over time history.
+#[get("/stats/query_types/overTime?<interval>&<timezone>&<utc_offset>")]
+pub fn over_time_query_types(
+    _auth: User,
+    ftl_memory: &State<FtlMemory>,
+    env: Inject<PiholeModule, Env>,
+    interval: Option<usize>,
+    timezone: Option<String>,
+    utc_offset: Option<i32>,
+) -> Reply {
+    reply_result(get_over_time_query_types(
+        ftl_memory,
+        effective_interval(interval),
+        BucketTimeZone::from_params(timezone.as_deref(), utc_offset, &env)?,
+    ))
+}
+
+/// Represents the reply format for the query types over time endpoint. The
+/// data of each item is indexed according to the query types.
+#[derive(Serialize)]
+#[cfg_attr(test, derive(Debug, PartialEq))]
+pub struct OverTimeQueryTypes {
+    pub interval: usize,
+    pub utc_offset: i32,
+    pub over_time: Vec<OverTimeQueryTypeItem>,
+    pub query_types: Vec<String>,
+}
+
+/// Represents an overTime query type item, which holds time and query type
+/// data for an overTime interval
+#[derive(Serialize)]
+#[cfg_attr(test, derive(Debug, PartialEq))]
+pub struct OverTimeQueryTypeItem {
+    pub timestamp: u64,
+    pub data: Vec<usize>,
+}
+
+/// Count the queries of each type in each bucket. The in-memory queries are
+/// counted in a single pass, using the index of their over time slot.
+fn get_over_time_query_types(
+    ftl_memory: &FtlMemory,
+    interval: usize,
+    time_zone: BucketTimeZone,
+) -> Result<OverTimeQueryTypes, Error> {
+    let lock = ftl_memory.lock()?;
+    let counters = ftl_memory.counters(&lock)?;
+    let over_time = ftl_memory.over_time(&lock)?;
+    let queries = ftl_memory.queries(&lock)?;
+
+    let query_types = FtlQueryType::variants();
+    let slot_count = get_current_over_time_slot(&over_time) + 1;
+    let mut slot_counts: Vec<Vec<usize>> = vec![vec![0; query_types.len()]; slot_count];
+
+    for query in queries.iter().take(counters.total_queries as usize) {
+        let column = match query_types
+            .iter()
+            .position(|&query_type| query_type == query.query_type)
+        {
+            Some(column) => column,
+            None => continue,
+        };
+
+        if let Some(count) = slot_counts
+            .get_mut(query.time_index as usize)
+            .and_then(|counts| counts.get_mut(column))
+        {
+            *count += 1;
+        }
+    }
+
+    let slots = over_time
+        .iter()
+        // Take all of the slots including the current slot
+        .take(slot_count)
+        .zip(slot_counts)
+        // Skip the overTime slots without any data
+        .skip_while(|(time, _)| time.total_queries <= 0 && time.blocked_queries <= 0)
+        .map(|(time, data)| (time.timestamp as u64, data));
+
+    let buckets = time_zone.group_slots(slots, interval, |bucket, data| {
+        for (total, count) in bucket.iter_mut().zip(data) {
+            *total += count;
+        }
+    });
+
+    Ok(OverTimeQueryTypes {
+        interval,
+        utc_offset: time_zone.current_offset(),
+        over_time: buckets
+            .into_iter()
+            .map(|(timestamp, data)| OverTimeQueryTypeItem { timestamp, data })
+            .collect(),
+        query_types: query_types
+            .iter()
+            .map(|query_type| query_type.get_name())
+            .collect(),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        ftl::{FtlMemory, FtlQueryStatus, FtlQueryType},
+        testing::{TestBuilder, TestFtlMemoryBuilder},
+    };
+
+    /// A, AAAA, and PTR queries spread over three slots
+    fn test_memory() -> FtlMemory {
+        let mut builder = TestFtlMemoryBuilder::new();
+        let client = builder.unnamed_client("10.1.1.1");
+        let upstream = builder.upstream("8.8.8.8", "google-public-dns-a.google.com");
+
+        builder.typed_query(
+            1000,
+            FtlQueryType::A,
+            "example.com",
+            client,
+            FtlQueryStatus::Forward,
+            Some(upstream),
+        );
+        builder.typed_query(
+            1000,
+            FtlQueryType::AAAA,
+            "example.com",
+            client,
+            FtlQueryStatus::Cache,
+            None,
+        );
+        builder.typed_query(
+            1000,
+            FtlQueryType::A,
+            "ads.com",
+            client,
+            FtlQueryStatus::Gravity,
+            None,
+        );
+        builder.typed_query(
+            1600,
+            FtlQueryType::PTR,
+            "1.1.1.10.in-addr.arpa",
+            client,
+            FtlQueryStatus::Cache,
+            None,
+        );
+        builder.typed_query(
+            2200,
+            FtlQueryType::AAAA,
+            "example.org",
+            client,
+            FtlQueryStatus::Forward,
+            Some(upstream),
+        );
+        builder.typed_query(
+            2200,
+            FtlQueryType::PTR,
+            "8.8.8.8.in-addr.arpa",
+            client,
+            FtlQueryStatus::Forward,
+            Some(upstream),
+        );
+
+        builder.build()
+    }
+
+    /// The queries of each slot are counted by type
+    #[test]
+    fn default_params() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/query_types/overTime")
+            .ftl_memory(test_memory())
+            .expect_json(json!({
+                "interval": 600,
+                "utc_offset": 0,
+                "over_time": [
+                    { "timestamp": 900, "data": [2, 1, 0, 0, 0, 0, 0] },
+                    { "timestamp": 1500, "data": [0, 0, 0, 0, 0, 1, 0] },
+                    { "timestamp": 2100, "data": [0, 1, 0, 0, 0, 1, 0] }
+                ],
+                "query_types": ["A", "AAAA", "ANY", "SRV", "SOA", "PTR", "TXT"]
+            }))
+            .test();
+    }
+
+    /// The slots are summed into buckets of the interval
+    #[test]
+    fn interval() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/query_types/overTime?interval=1800")
+            .ftl_memory(test_memory())
+            .expect_json(json!({
+                "interval": 1800,
+                "utc_offset": 0,
+                "over_time": [
+                    { "timestamp": 900, "data": [2, 1, 0, 0, 0, 1, 0] },
+                    { "timestamp": 2700, "data": [0, 1, 0, 0, 0, 1, 0] }
+                ],
+                "query_types": ["A", "AAAA", "ANY", "SRV", "SOA", "PTR", "TXT"]
+            }))
+            .test();
+    }
+}
@@ -0,0 +1,260 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Upstreams Over Time Endpoint
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    env::Env,
+    ftl::{FtlMemory, FtlQueryStatus},
+    routes::{
+        auth::User,
+        stats::{
+            common::get_current_over_time_slot, over_time_history::effective_interval,
+            time_zone::BucketTimeZone,
+        },
+    },
+    services::PiholeModule,
+    util::{reply_result, Error, Reply},
+};
+use rocket::State;
+use shaku_rocket::Inject;
+
+pub use over_time_upstreams as route;
+
+/// The number of pseudo-upstreams (blocklist and cache) before the real
+/// upstreams
+const PSEUDO_UPSTREAMS: usize = 2;
+
+/// Get the queries of each upstream over time. The blocklist and cache are
+/// included as pseudo-upstreams, like the upstreams endpoint. If an interval
+/// (in seconds) is given, the FTL slots are grouped into buckets of that size,
+/// aligned to the `timezone` or `utc_offset` like the over time history.
+#[get("/stats/upstreams/overTime?<interval>&<timezone>&<utc_offset>")]
+pub fn over_time_upstreams(
+    _auth: User,
+    ftl_memory: &State<FtlMemory>,
+    env: Inject<PiholeModule, Env>,
+    interval: Option<usize>,
+    timezone: Option<String>,
+    utc_offset: Option<i32>,
+) -> Reply {
+    reply_result(get_over_time_upstreams(
+        ftl_memory,
+        effective_interval(interval),
+        BucketTimeZone::from_params(timezone.as_deref(), utc_offset, &env)?,
+    ))
+}
+
+/// Represents the reply format for the upstreams over time endpoint. The data
+/// of each item is indexed according to the upstreams.
+#[derive(Serialize)]
+#[cfg_attr(test, derive(Debug, PartialEq))]
+pub struct OverTimeUpstreams {
+    pub interval: usize,
+    pub utc_offset: i32,
+    pub over_time: Vec<OverTimeUpstreamItem>,
+    pub upstreams: Vec<OverTimeUpstream>,
+}
+
+/// Represents an overTime upstream item, which holds time and upstream data
+/// for an overTime interval
+#[derive(Serialize)]
+#[cfg_attr(test, derive(Debug, PartialEq))]
+pub struct OverTimeUpstreamItem {
+    pub timestamp: u64,
+    pub data: Vec<usize>,
+}
+
+/// Represents an upstream (or pseudo-upstream) in the overTime data
+#[derive(Serialize)]
+#[cfg_attr(test, derive(Debug, PartialEq))]
+pub struct OverTimeUpstream {
+    pub name: String,
+    pub ip: String,
+}
+
+/// Count the queries of each upstream in each bucket. FTL does not keep
+/// upstream counts per slot, so the in-memory queries are counted in a single
+/// pass, using the index of their over time slot.
+fn get_over_time_upstreams(
+    ftl_memory: &FtlMemory,
+    interval: usize,
+    time_zone: BucketTimeZone,
+) -> Result<OverTimeUpstreams, Error> {
+    let lock = ftl_memory.lock()?;
+    let counters = ftl_memory.counters(&lock)?;
+    let over_time = ftl_memory.over_time(&lock)?;
+    let queries = ftl_memory.queries(&lock)?;
+    let ftl_upstreams = ftl_memory.upstreams(&lock)?;
+    let strings = ftl_memory.strings(&lock)?;
+
+    let ftl_upstreams =
+        &ftl_upstreams[..ftl_upstreams.len().min(counters.total_upstreams as usize)];
+    let columns = ftl_upstreams.len() + PSEUDO_UPSTREAMS;
+    let slot_count = get_current_over_time_slot(&over_time) + 1;
+    let mut slot_counts: Vec<Vec<usize>> = vec![vec![0; columns]; slot_count];
+
+    for query in queries.iter().take(counters.total_queries as usize) {
+        let column = if query.is_blocked() {
+            0
+        } else if query.status == FtlQueryStatus::Cache {
+            1
+        } else if query.status == FtlQueryStatus::Forward {
+            PSEUDO_UPSTREAMS + query.upstream_id as usize
+        } else {
+            // Queries which have not been answered yet are not counted
+            continue;
+        };
+
+        if let Some(count) = slot_counts
+            .get_mut(query.time_index as usize)
+            .and_then(|counts| counts.get_mut(column))
+        {
+            *count += 1;
+        }
+    }
+
+    let slots = over_time
+        .iter()
+        // Take all of the slots including the current slot
+        .take(slot_count)
+        .zip(slot_counts)
+        // Skip the overTime slots without any data
+        .skip_while(|(time, _)| time.total_queries <= 0 && time.blocked_queries <= 0)
+        .map(|(time, data)| (time.timestamp as u64, data));
+
+    let buckets = time_zone.group_slots(slots, interval, |bucket, data| {
+        for (total, count) in bucket.iter_mut().zip(data) {
+            *total += count;
+        }
+    });
+
+    let mut upstreams = Vec::with_capacity(columns);
+    upstreams.push(OverTimeUpstream {
+        name: "blocklist".to_owned(),
+        ip: "blocklist".to_owned(),
+    });
+    upstreams.push(OverTimeUpstream {
+        name: "cache".to_owned(),
+        ip: "cache".to_owned(),
+    });
+    upstreams.extend(ftl_upstreams.iter().map(|upstream| OverTimeUpstream {
+        name: upstream.get_name(&strings).unwrap_or_default().to_owned(),
+        ip: upstream.get_ip(&strings).to_owned(),
+    }));
+
+    Ok(OverTimeUpstreams {
+        interval,
+        utc_offset: time_zone.current_offset(),
+        over_time: buckets
+            .into_iter()
+            .map(|(timestamp, data)| OverTimeUpstreamItem { timestamp, data })
+            .collect(),
+        upstreams,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        ftl::{FtlMemory, FtlQueryStatus},
+        testing::{TestBuilder, TestFtlMemoryBuilder},
+    };
+
+    /// Two upstreams, with blocked and cached queries mixed in over three
+    /// slots, and one query which has not been answered yet
+    fn test_memory() -> FtlMemory {
+        let mut builder = TestFtlMemoryBuilder::new();
+        let client = builder.unnamed_client("10.1.1.1");
+        let upstream1 = builder.upstream("8.8.8.8", "google-public-dns-a.google.com");
+        let upstream2 = builder.upstream("1.1.1.1", "");
+
+        builder.query(
+            1000,
+            "example.com",
+            client,
+            FtlQueryStatus::Forward,
+            Some(upstream1),
+        );
+        builder.query(
+            1000,
+            "example.org",
+            client,
+            FtlQueryStatus::Forward,
+            Some(upstream2),
+        );
+        builder.query(1000, "ads.com", client, FtlQueryStatus::Gravity, None);
+        builder.query(1600, "example.com", client, FtlQueryStatus::Cache, None);
+        builder.query(1600, "ads.com", client, FtlQueryStatus::Blacklist, None);
+        builder.query(
+            2200,
+            "example.com",
+            client,
+            FtlQueryStatus::Forward,
+            Some(upstream1),
+        );
+        builder.query(
+            2200,
+            "example.net",
+            client,
+            FtlQueryStatus::Forward,
+            Some(upstream1),
+        );
+        builder.query(2200, "slow.com", client, FtlQueryStatus::Unknown, None);
+
+        builder.build()
+    }
+
+    /// The queries of each slot are counted by upstream, after the blocklist
+    /// and cache
+    #[test]
+    fn default_params() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/upstreams/overTime")
+            .ftl_memory(test_memory())
+            .expect_json(json!({
+                "interval": 600,
+                "utc_offset": 0,
+                "over_time": [
+                    { "timestamp": 900, "data": [1, 0, 1, 1] },
+                    { "timestamp": 1500, "data": [1, 1, 0, 0] },
+                    { "timestamp": 2100, "data": [0, 0, 2, 0] }
+                ],
+                "upstreams": [
+                    { "name": "blocklist", "ip": "blocklist" },
+                    { "name": "cache", "ip": "cache" },
+                    { "name": "google-public-dns-a.google.com", "ip": "8.8.8.8" },
+                    { "name": "", "ip": "1.1.1.1" }
+                ]
+            }))
+            .test();
+    }
+
+    /// The slots are summed into buckets of the interval
+    #[test]
+    fn interval() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/upstreams/overTime?interval=1800")
+            .ftl_memory(test_memory())
+            .expect_json(json!({
+                "interval": 1800,
+                "utc_offset": 0,
+                "over_time": [
+                    { "timestamp": 900, "data": [2, 1, 1, 1] },
+                    { "timestamp": 2700, "data": [0, 0, 2, 0] }
+                ],
+                "upstreams": [
+                    { "name": "blocklist", "ip": "blocklist" },
+                    { "name": "cache", "ip": "cache" },
+                    { "name": "google-public-dns-a.google.com", "ip": "8.8.8.8" },
+                    { "name": "", "ip": "1.1.1.1" }
+                ]
+            }))
+            .test();
+    }
+}
@@ -0,0 +1,157 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Query Statuses Endpoint
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    ftl::{FtlMemory, FtlQueryStatus},
+    routes::auth::User,
+    util::{reply_result, Error, Reply},
+};
+use rocket::State;
+
+pub use query_statuses as route;
+
+/// Get the query statuses
+#[get("/stats/query_statuses")]
+pub fn query_statuses(_auth: User, ftl_memory: &State<FtlMemory>) -> Reply {
+    reply_result(query_statuses_impl(ftl_memory))
+}
+
+/// Get the query statuses. The counters only separate blocked, cached, and
+/// forwarded queries, so the in-memory queries are counted instead.
+fn query_statuses_impl(ftl_memory: &FtlMemory) -> Result<QueryStatusesReply, Error> {
+    let lock = ftl_memory.lock()?;
+    let counters = ftl_memory.counters(&lock)?;
+    let queries = ftl_memory.queries(&lock)?;
+
+    let mut counts = [0usize; FtlQueryStatus::ExternalBlockNxdomainRa as usize + 1];
+    let total = counters.total_queries as usize;
+
+    for query in queries.iter().take(total) {
+        counts[query.status as usize] += 1;
+    }
+
+    let query_statuses = FtlQueryStatus::variants()
+        .iter()
+        .map(|&variant| QueryStatusReply::new(variant, counts[variant as usize], total))
+        .collect();
+
+    Ok(QueryStatusesReply {
+        query_statuses,
+        total,
+    })
+}
+
+/// Represents the reply structure for the query statuses and their total
+#[derive(Serialize)]
+#[cfg_attr(test, derive(Debug, PartialEq))]
+pub struct QueryStatusesReply {
+    pub query_statuses: Vec<QueryStatusReply>,
+    pub total: usize,
+}
+
+/// Represents the reply structure for returning query status data
+#[derive(Serialize)]
+#[cfg_attr(test, derive(Debug, PartialEq))]
+pub struct QueryStatusReply {
+    pub name: &'static str,
+    pub count: usize,
+    /// The percentage of all queries which have this status
+    pub percentage: f64,
+}
+
+impl QueryStatusReply {
+    /// Create the reply for a status, calculating its percentage of the total
+    /// queries
+    pub fn new(status: FtlQueryStatus, count: usize, total_queries: usize) -> Self {
+        let percentage = if total_queries == 0 {
+            0.0
+        } else {
+            (count * 100) as f64 / total_queries as f64
+        };
+
+        QueryStatusReply {
+            name: status.get_name(),
+            count,
+            percentage,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        ftl::FtlQueryStatus,
+        testing::{TestBuilder, TestFtlMemoryBuilder},
+    };
+
+    /// Queries are counted by status, with the percentage of the total
+    #[test]
+    fn query_statuses() {
+        let mut builder = TestFtlMemoryBuilder::new();
+        let client = builder.unnamed_client("10.1.1.1");
+        let upstream = builder.upstream("8.8.8.8", "google-public-dns-a.google.com");
+        let statuses = [
+            FtlQueryStatus::Forward,
+            FtlQueryStatus::Forward,
+            FtlQueryStatus::Forward,
+            FtlQueryStatus::Cache,
+            FtlQueryStatus::Gravity,
+            FtlQueryStatus::Gravity,
+            FtlQueryStatus::Wildcard,
+            FtlQueryStatus::Blacklist,
+        ];
+
+        for (i, &status) in statuses.iter().enumerate() {
+            builder.query(1000 + i, "example.com", client, status, Some(upstream));
+        }
+
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/query_statuses")
+            .ftl_memory(builder.build())
+            .expect_json(json!({
+                "query_statuses": [
+                    { "name": "unknown", "count": 0, "percentage": 0.0 },
+                    { "name": "gravity", "count": 2, "percentage": 25.0 },
+                    { "name": "forwarded", "count": 3, "percentage": 37.5 },
+                    { "name": "cached", "count": 1, "percentage": 12.5 },
+                    { "name": "regex", "count": 1, "percentage": 12.5 },
+                    { "name": "blacklist", "count": 1, "percentage": 12.5 },
+                    { "name": "external_blocked_ip", "count": 0, "percentage": 0.0 },
+                    { "name": "external_blocked_null", "count": 0, "percentage": 0.0 },
+                    { "name": "external_blocked_nxra", "count": 0, "percentage": 0.0 }
+                ],
+                "total": 8
+            }))
+            .test();
+    }
+
+    /// With no queries, the percentages are zero
+    #[test]
+    fn no_queries() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/query_statuses")
+            .ftl_memory(TestFtlMemoryBuilder::new().build())
+            .expect_json(json!({
+                "query_statuses": [
+                    { "name": "unknown", "count": 0, "percentage": 0.0 },
+                    { "name": "gravity", "count": 0, "percentage": 0.0 },
+                    { "name": "forwarded", "count": 0, "percentage": 0.0 },
+                    { "name": "cached", "count": 0, "percentage": 0.0 },
+                    { "name": "regex", "count": 0, "percentage": 0.0 },
+                    { "name": "blacklist", "count": 0, "percentage": 0.0 },
+                    { "name": "external_blocked_ip", "count": 0, "percentage": 0.0 },
+                    { "name": "external_blocked_null", "count": 0, "percentage": 0.0 },
+                    { "name": "external_blocked_nxra", "count": 0, "percentage": 0.0 }
+                ],
+                "total": 0
+            }))
+            .test();
+    }
+}
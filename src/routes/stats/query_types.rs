@@ -24,17 +24,38 @@ pub fn query_types(_auth: User, ftl_memory: &State<FtlMemory>) -> Reply {
 }
 
 /// Get the query types
-fn query_types_impl(ftl_memory: &FtlMemory) -> Result<Vec<QueryTypeReply>, Error> {
+fn query_types_impl(ftl_memory: &FtlMemory) -> Result<QueryTypesReply, Error> {
     let lock = ftl_memory.lock()?;
     let counters = ftl_memory.counters(&lock)?;
 
-    Ok(FtlQueryType::variants()
+    let query_types: Vec<QueryTypeReply> = FtlQueryType::variants()
         .iter()
         .map(|&variant| QueryTypeReply {
             name: variant.get_name(),
             count: counters.query_type(variant),
         })
-        .collect())
+        .collect();
+
+    // Queries of types which the API does not know about are only counted in
+    // the total
+    let total = counters.total_queries as usize;
+    let known: usize = query_types.iter().map(|query_type| query_type.count).sum();
+
+    Ok(QueryTypesReply {
+        query_types,
+        other: total.saturating_sub(known),
+        total,
+    })
+}
+
+/// Represents the reply structure for the query types and their total
+#[derive(Serialize)]
+#[cfg_attr(test, derive(Debug, PartialEq))]
+pub struct QueryTypesReply {
+    pub query_types: Vec<QueryTypeReply>,
+    /// The number of queries with a type which is not listed
+    pub other: usize,
+    pub total: usize,
 }
 
 /// Represents the reply structure for returning query type data
@@ -50,7 +71,8 @@ mod test {
     use super::query_types_impl;
     use crate::{
         ftl::{FtlCounters, FtlMemory, FtlSettings},
-        routes::stats::query_types::QueryTypeReply,
+        routes::stats::query_types::{QueryTypeReply, QueryTypesReply},
+        testing::TestBuilder,
     };
     use std::collections::HashMap;
 
@@ -74,7 +96,7 @@ mod test {
     /// Simple test to validate output
     #[test]
     fn query_types() {
-        let expected = vec![
+        let query_types = vec![
             QueryTypeReply {
                 name: "A".to_owned(),
                 count: 2,
@@ -105,8 +127,50 @@ mod test {
             },
         ];
 
+        let expected = QueryTypesReply {
+            query_types,
+            other: 0,
+            total: 10,
+        };
+
         let actual = query_types_impl(&test_data()).unwrap();
 
         assert_eq!(actual, expected);
     }
+
+    /// Every type is included even with a count of zero, and queries of
+    /// unknown types are counted as other
+    #[test]
+    fn query_types_json() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/query_types")
+            .ftl_memory(FtlMemory::Test {
+                counters: FtlCounters {
+                    query_type_counters: [5, 3, 0, 0, 0, 1, 0],
+                    total_queries: 11,
+                    ..FtlCounters::default()
+                },
+                domains: Vec::new(),
+                over_time: Vec::new(),
+                strings: HashMap::new(),
+                upstreams: Vec::new(),
+                queries: Vec::new(),
+                clients: Vec::new(),
+                settings: FtlSettings::default(),
+            })
+            .expect_json(json!({
+                "query_types": [
+                    { "name": "A", "count": 5 },
+                    { "name": "AAAA", "count": 3 },
+                    { "name": "ANY", "count": 0 },
+                    { "name": "SRV", "count": 0 },
+                    { "name": "SOA", "count": 0 },
+                    { "name": "PTR", "count": 1 },
+                    { "name": "TXT", "count": 0 }
+                ],
+                "other": 2,
+                "total": 11
+            }))
+            .test();
+    }
 }
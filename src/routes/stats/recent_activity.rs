@@ -0,0 +1,360 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Recent Activity Endpoint
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    ftl::{FtlMemory, FtlQuery, FtlRedactor},
+    routes::auth::User,
+    services::{privacy::PrivacyService, PiholeModule},
+    util::{reply_result, Error, Reply},
+};
+use rocket::State;
+use shaku_rocket::Inject;
+use std::{
+    collections::HashMap,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+pub use recent_activity as route;
+
+/// The windows of recent activity, in minutes
+const RECENT_WINDOWS: [u64; 3] = [1, 5, 15];
+
+/// Get the query rates over the last few minutes
+#[get("/stats/recent_activity?<params..>")]
+pub fn recent_activity(
+    _auth: User,
+    ftl_memory: &State<FtlMemory>,
+    privacy: Inject<PiholeModule, dyn PrivacyService>,
+    params: RecentActivityParams,
+) -> Reply {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Time went backwards")
+        .as_secs();
+
+    reply_result(get_recent_activity(
+        ftl_memory,
+        privacy.redactor()?,
+        params,
+        now,
+    ))
+}
+
+/// Represents the possible GET parameters on `/stats/recent_activity`
+#[derive(FromForm, Default)]
+pub struct RecentActivityParams {
+    /// Include the client with the most queries in each window
+    pub top_client: Option<bool>,
+}
+
+/// Represents the reply structure for the recent activity
+#[derive(Serialize)]
+#[cfg_attr(test, derive(Debug, PartialEq))]
+pub struct RecentActivityReply {
+    pub recent_activity: Vec<RecentWindowReply>,
+}
+
+/// Represents the reply structure for the activity in one window
+#[derive(Serialize)]
+#[cfg_attr(test, derive(Debug, PartialEq))]
+pub struct RecentWindowReply {
+    pub minutes: u64,
+    pub queries: usize,
+    pub blocked: usize,
+    /// Queries per minute
+    pub rate: f64,
+    /// Blocked queries per minute
+    pub blocked_rate: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_client: Option<RecentClientReply>,
+}
+
+/// Represents the reply structure for the top client of a window
+#[derive(Serialize)]
+#[cfg_attr(test, derive(Debug, PartialEq))]
+pub struct RecentClientReply {
+    pub name: Option<String>,
+    pub ip: String,
+    pub count: usize,
+}
+
+/// The counts of queries in a window, while the queries are being scanned
+#[derive(Default)]
+struct WindowCounts {
+    queries: usize,
+    blocked: usize,
+    clients: HashMap<usize, usize>,
+}
+
+/// Get the query rates over the windows ending at `now`. The queries are
+/// stored in order, so only the tail of the queries which is inside the
+/// longest window is scanned.
+fn get_recent_activity(
+    ftl_memory: &FtlMemory,
+    redactor: FtlRedactor,
+    params: RecentActivityParams,
+    now: u64,
+) -> Result<RecentActivityReply, Error> {
+    let top_client = params.top_client.unwrap_or(false);
+
+    let lock = ftl_memory.lock()?;
+    let counters = ftl_memory.counters(&lock)?;
+    let queries = ftl_memory.queries(&lock)?;
+
+    let mut windows: Vec<WindowCounts> = RECENT_WINDOWS
+        .iter()
+        .map(|_| WindowCounts::default())
+        .collect();
+    let longest_window = RECENT_WINDOWS[RECENT_WINDOWS.len() - 1];
+
+    let recent_queries = queries
+        .iter()
+        .take(counters.total_queries as usize)
+        .rev()
+        .take_while(|query| is_in_window(query, longest_window, now));
+
+    for query in recent_queries {
+        // Only count the client if the privacy level allows showing it
+        let client_visible = top_client && !redactor.hides_query_client(query);
+
+        for (window, &minutes) in windows.iter_mut().zip(RECENT_WINDOWS.iter()) {
+            if !is_in_window(query, minutes, now) {
+                continue;
+            }
+
+            window.queries += 1;
+
+            if query.is_blocked() {
+                window.blocked += 1;
+            }
+
+            if client_visible {
+                *window.clients.entry(query.client_id as usize).or_insert(0) += 1;
+            }
+        }
+    }
+
+    let clients = ftl_memory.clients(&lock)?;
+    let strings = ftl_memory.strings(&lock)?;
+
+    let recent_activity = windows
+        .into_iter()
+        .zip(RECENT_WINDOWS.iter())
+        .map(|(window, &minutes)| {
+            // Break ties with the client which FTL saw first
+            let top_client = window
+                .clients
+                .iter()
+                .max_by(|(a_id, a_count), (b_id, b_count)| {
+                    a_count.cmp(b_count).then(b_id.cmp(a_id))
+                })
+                .and_then(|(&id, &count)| {
+                    let reply = clients.get(id)?.as_reply(&strings);
+
+                    Some(RecentClientReply {
+                        name: reply.name,
+                        ip: reply.ip,
+                        count,
+                    })
+                });
+
+            RecentWindowReply {
+                minutes,
+                queries: window.queries,
+                blocked: window.blocked,
+                rate: window.queries as f64 / minutes as f64,
+                blocked_rate: window.blocked as f64 / minutes as f64,
+                top_client,
+            }
+        })
+        .collect();
+
+    Ok(RecentActivityReply { recent_activity })
+}
+
+/// Check if the query was made within the last `minutes` minutes before `now`
+fn is_in_window(query: &FtlQuery, minutes: u64, now: u64) -> bool {
+    query.timestamp as u64 > now.saturating_sub(minutes * 60)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        env::PiholeFile,
+        ftl::{FtlMemory, FtlQueryStatus},
+        testing::{TestBuilder, TestFtlMemoryBuilder},
+    };
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    /// Queries spread so each window has a different rate. 10.1.1.1 made most
+    /// of the queries in the last five minutes, and 10.1.1.2 most of the older
+    /// ones.
+    fn test_memory() -> FtlMemory {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as usize;
+        let mut builder = TestFtlMemoryBuilder::new();
+        let client1 = builder.client("10.1.1.1", "laptop");
+        let client2 = builder.unnamed_client("10.1.1.2");
+        let upstream = builder.upstream("8.8.8.8", "google-public-dns-a.google.com");
+
+        // Too old for any window
+        builder.query(
+            now - 3600,
+            "old.com",
+            client1,
+            FtlQueryStatus::Gravity,
+            None,
+        );
+
+        // Last 15 minutes
+        for _ in 0..5 {
+            builder.query(
+                now - 600,
+                "example.com",
+                client2,
+                FtlQueryStatus::Forward,
+                Some(upstream),
+            );
+        }
+
+        // Last 5 minutes
+        builder.query(now - 120, "ads.com", client2, FtlQueryStatus::Gravity, None);
+        builder.query(now - 120, "ads.com", client2, FtlQueryStatus::Gravity, None);
+        builder.query(
+            now - 120,
+            "example.com",
+            client1,
+            FtlQueryStatus::Cache,
+            None,
+        );
+
+        // Last minute
+        builder.query(
+            now - 10,
+            "ads.com",
+            client1,
+            FtlQueryStatus::Blacklist,
+            None,
+        );
+        builder.query(now - 5, "example.com", client1, FtlQueryStatus::Cache, None);
+
+        builder.build()
+    }
+
+    /// The rates of each window are calculated from the queries inside it
+    #[test]
+    fn rates() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/recent_activity")
+            .ftl_memory(test_memory())
+            .file(PiholeFile::FtlConfig, "")
+            .expect_json(json!({
+                "recent_activity": [
+                    {
+                        "minutes": 1,
+                        "queries": 2,
+                        "blocked": 1,
+                        "rate": 2.0,
+                        "blocked_rate": 1.0
+                    },
+                    {
+                        "minutes": 5,
+                        "queries": 5,
+                        "blocked": 3,
+                        "rate": 1.0,
+                        "blocked_rate": 0.6
+                    },
+                    {
+                        "minutes": 15,
+                        "queries": 10,
+                        "blocked": 3,
+                        "rate": 10.0 / 15.0,
+                        "blocked_rate": 0.2
+                    }
+                ]
+            }))
+            .test();
+    }
+
+    /// The top client of each window is included when requested
+    #[test]
+    fn top_client() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/recent_activity?top_client=true")
+            .ftl_memory(test_memory())
+            .file(PiholeFile::FtlConfig, "")
+            .expect_json(json!({
+                "recent_activity": [
+                    {
+                        "minutes": 1,
+                        "queries": 2,
+                        "blocked": 1,
+                        "rate": 2.0,
+                        "blocked_rate": 1.0,
+                        "top_client": { "name": "laptop", "ip": "10.1.1.1", "count": 2 }
+                    },
+                    {
+                        "minutes": 5,
+                        "queries": 5,
+                        "blocked": 3,
+                        "rate": 1.0,
+                        "blocked_rate": 0.6,
+                        "top_client": { "name": "laptop", "ip": "10.1.1.1", "count": 3 }
+                    },
+                    {
+                        "minutes": 15,
+                        "queries": 10,
+                        "blocked": 3,
+                        "rate": 10.0 / 15.0,
+                        "blocked_rate": 0.2,
+                        "top_client": { "name": null, "ip": "10.1.1.2", "count": 7 }
+                    }
+                ]
+            }))
+            .test();
+    }
+
+    /// The top client is not shown if the privacy level hides clients
+    #[test]
+    fn top_client_private() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/recent_activity?top_client=true")
+            .ftl_memory(test_memory())
+            .file(PiholeFile::FtlConfig, "PRIVACYLEVEL=2")
+            .expect_json(json!({
+                "recent_activity": [
+                    {
+                        "minutes": 1,
+                        "queries": 2,
+                        "blocked": 1,
+                        "rate": 2.0,
+                        "blocked_rate": 1.0
+                    },
+                    {
+                        "minutes": 5,
+                        "queries": 5,
+                        "blocked": 3,
+                        "rate": 1.0,
+                        "blocked_rate": 0.6
+                    },
+                    {
+                        "minutes": 15,
+                        "queries": 10,
+                        "blocked": 3,
+                        "rate": 10.0 / 15.0,
+                        "blocked_rate": 0.2
+                    }
+                ]
+            }))
+            .test();
+    }
+}
@@ -9,11 +9,10 @@
 // Please see LICENSE file for your rights under this license.
 
 use crate::{
-    env::Env,
-    ftl::FtlMemory,
+    ftl::{FtlMemory, FtlRedactor},
     routes::auth::User,
-    services::PiholeModule,
-    settings::{ConfigEntry, FtlConfEntry, FtlPrivacyLevel},
+    services::{privacy::PrivacyService, PiholeModule},
+    settings::FtlPrivacyLevel,
     util::{reply_data, Reply},
 };
 use rocket::State;
@@ -21,28 +20,45 @@ use shaku_rocket::Inject;
 
 pub use recent_blocked as route;
 
-/// Get the `num` most recently blocked domains
+/// Get the `limit` most recently blocked domains
 #[get("/stats/recent_blocked?<params..>")]
 pub fn recent_blocked(
     _auth: User,
     ftl_memory: &State<FtlMemory>,
-    env: Inject<PiholeModule, Env>,
+    privacy: Inject<PiholeModule, dyn PrivacyService>,
     params: RecentBlockedParams,
 ) -> Reply {
-    get_recent_blocked(ftl_memory, &env, params.num.unwrap_or(1))
+    get_recent_blocked(
+        ftl_memory,
+        privacy.redactor()?,
+        params.limit.or(params.num).unwrap_or(1),
+    )
 }
 
-/// Represents the possible GET parameters on `/stats/recent_blocked`
+/// Get the most recently blocked domain and when it was blocked. This is
+/// polled by the dashboard, so only the tail of the queries is scanned.
+#[get("/stats/recent_blocked/latest")]
+pub fn recent_blocked_latest(
+    _auth: User,
+    ftl_memory: &State<FtlMemory>,
+    privacy: Inject<PiholeModule, dyn PrivacyService>,
+) -> Reply {
+    get_latest_blocked(ftl_memory, privacy.redactor()?)
+}
+
+/// Represents the possible GET parameters on `/stats/recent_blocked`. `num` is
+/// the old name of `limit`.
 #[derive(FromForm)]
 pub struct RecentBlockedParams {
+    limit: Option<usize>,
     num: Option<usize>,
 }
 
-/// Get `num`-many most recently blocked domains
-pub fn get_recent_blocked(ftl_memory: &FtlMemory, env: &Env, num: usize) -> Reply {
-    // Check if client details are private
-    if FtlConfEntry::PrivacyLevel.read_as::<FtlPrivacyLevel>(&env)? >= FtlPrivacyLevel::HideDomains
-    {
+/// Get `num`-many most recently blocked domains. Like the history, domains are
+/// replaced with a placeholder if the privacy level hides them.
+pub fn get_recent_blocked(ftl_memory: &FtlMemory, redactor: FtlRedactor, num: usize) -> Reply {
+    // Nothing is shown with the maximum privacy level
+    if !redactor.allows(FtlPrivacyLevel::Maximum) {
         return reply_data([0; 0]);
     }
 
@@ -57,105 +73,96 @@ pub fn get_recent_blocked(ftl_memory: &FtlMemory, env: &Env, num: usize) -> Repl
         // Get the most recent queries first
         .rev()
         // Skip the uninitialized queries
-        .skip(queries.len() - counters.total_queries as usize)
+        .skip(queries.len().saturating_sub(counters.total_queries as usize))
         // Only get blocked queries
         .filter(|query| query.is_blocked())
-        // Get up to num queries
+        // Get up to num queries. The iterator is lazy, so the scan stops once
+        // enough queries are found.
         .take(num)
-        // Only return the domain
-        .map(|query| domains[query.domain_id as usize].get_domain(&strings))
+        // Only return the domain, unless it is private
+        .map(|query| redactor.query_domain(query, &domains, &strings))
         .collect();
 
     reply_data(recent_blocked)
 }
 
+/// Represents the reply structure for the most recently blocked domain
+#[derive(Serialize)]
+#[cfg_attr(test, derive(Debug, PartialEq))]
+pub struct LatestBlockedReply<'a> {
+    pub domain: &'a str,
+    pub timestamp: u64,
+}
+
+/// Get the most recently blocked domain. If nothing has been blocked, or the
+/// privacy level hides everything, the reply is empty.
+pub fn get_latest_blocked(ftl_memory: &FtlMemory, redactor: FtlRedactor) -> Reply {
+    // Nothing is shown with the maximum privacy level
+    if !redactor.allows(FtlPrivacyLevel::Maximum) {
+        return reply_data(json!({}));
+    }
+
+    let lock = ftl_memory.lock()?;
+    let counters = ftl_memory.counters(&lock)?;
+
+    // Don't scan all of the queries if none of them were blocked
+    if counters.blocked_queries <= 0 {
+        return reply_data(json!({}));
+    }
+
+    let queries = ftl_memory.queries(&lock)?;
+    let strings = ftl_memory.strings(&lock)?;
+    let domains = ftl_memory.domains(&lock)?;
+
+    let latest = queries
+        .iter()
+        .take(counters.total_queries as usize)
+        .rev()
+        .find(|query| query.is_blocked());
+
+    match latest {
+        Some(query) => reply_data(LatestBlockedReply {
+            domain: redactor.query_domain(query, &domains, &strings),
+            timestamp: query.timestamp as u64,
+        }),
+        None => reply_data(json!({})),
+    }
+}
+
 #[cfg(test)]
 mod test {
     use crate::{
         env::PiholeFile,
-        ftl::{
-            FtlCounters, FtlDnssecType, FtlDomain, FtlMemory, FtlQuery, FtlQueryReplyType,
-            FtlQueryStatus, FtlQueryType, FtlRegexMatch, FtlSettings, MAGIC_BYTE,
-        },
-        settings::FtlPrivacyLevel,
-        testing::TestBuilder,
+        ftl::{FtlMemory, FtlQueryStatus},
+        testing::{TestBuilder, TestFtlMemoryBuilder},
     };
-    use std::collections::HashMap;
-
-    /// Shorthand for making `FtlQuery` structs
-    macro_rules! query {
-        ($id:expr, $status:ident, $domain:expr) => {
-            FtlQuery {
-                magic: MAGIC_BYTE,
-                id: $id,
-                database_id: 0,
-                timestamp: 1,
-                time_index: 1,
-                response_time: 1,
-                domain_id: $domain,
-                client_id: 0,
-                upstream_id: 0,
-                query_type: FtlQueryType::A,
-                status: FtlQueryStatus::$status,
-                reply_type: FtlQueryReplyType::IP,
-                dnssec_type: FtlDnssecType::Unspecified,
-                is_complete: true,
-                privacy_level: FtlPrivacyLevel::ShowAll,
-            }
-        };
-    }
-
-    /// 6 queries, 4 blocked
-    fn test_queries() -> Vec<FtlQuery> {
-        vec![
-            query!(1, Forward, 0),
-            query!(2, Gravity, 1),
-            query!(3, Blacklist, 2),
-            query!(4, Wildcard, 3),
-            query!(5, ExternalBlockIp, 4),
-            query!(6, Cache, 0),
-        ]
-    }
-
-    /// 5 domain, 4 blocked, 1 regex blocked, 1 not blocked
-    fn test_domains() -> Vec<FtlDomain> {
-        vec![
-            FtlDomain::new(2, 0, 1, FtlRegexMatch::NotBlocked),
-            FtlDomain::new(1, 1, 2, FtlRegexMatch::NotBlocked),
-            FtlDomain::new(1, 1, 3, FtlRegexMatch::NotBlocked),
-            FtlDomain::new(1, 1, 4, FtlRegexMatch::Blocked),
-            FtlDomain::new(1, 1, 5, FtlRegexMatch::NotBlocked),
-        ]
-    }
-
-    /// Strings used in the test data
-    fn test_strings() -> HashMap<usize, String> {
-        let mut strings = HashMap::new();
-        strings.insert(1, "domain1.com".to_owned());
-        strings.insert(2, "domain2.com".to_owned());
-        strings.insert(3, "domain3.com".to_owned());
-        strings.insert(4, "domain4.com".to_owned());
-        strings.insert(5, "domain5.com".to_owned());
-
-        strings
-    }
-
-    /// Creates an `FtlMemory` struct from the other test data functions
+
+    /// 6 queries, 4 blocked (1 by regex), over 5 domains
     fn test_memory() -> FtlMemory {
-        FtlMemory::Test {
-            queries: test_queries(),
-            domains: test_domains(),
-            over_time: Vec::new(),
-            strings: test_strings(),
-            clients: Vec::new(),
-            upstreams: Vec::new(),
-            counters: FtlCounters {
-                total_queries: 6,
-                total_domains: 5,
-                ..FtlCounters::default()
-            },
-            settings: FtlSettings::default(),
-        }
+        let mut builder = TestFtlMemoryBuilder::new();
+        let client = builder.unnamed_client("10.1.1.1");
+        let upstream = builder.upstream("8.8.8.8", "google-public-dns-a.google.com");
+
+        builder.query(
+            1,
+            "domain1.com",
+            client,
+            FtlQueryStatus::Forward,
+            Some(upstream),
+        );
+        builder.query(2, "domain2.com", client, FtlQueryStatus::Gravity, None);
+        builder.query(3, "domain3.com", client, FtlQueryStatus::Blacklist, None);
+        builder.query(4, "domain4.com", client, FtlQueryStatus::Wildcard, None);
+        builder.query(
+            5,
+            "domain5.com",
+            client,
+            FtlQueryStatus::ExternalBlockIp,
+            None,
+        );
+        builder.query(6, "domain1.com", client, FtlQueryStatus::Cache, None);
+
+        builder.build()
     }
 
     /// The default behavior shows one most recently blocked domain
@@ -196,4 +203,146 @@ mod test {
             ]))
             .test();
     }
+
+    /// The `limit` parameter is the same as `num`. Domains which are blocked
+    /// more than once are returned each time.
+    #[test]
+    fn limit_interleaved() {
+        let mut builder = TestFtlMemoryBuilder::new();
+        let client = builder.unnamed_client("10.1.1.1");
+        let upstream = builder.upstream("8.8.8.8", "google-public-dns-a.google.com");
+
+        builder.query(1, "ads.com", client, FtlQueryStatus::Gravity, None);
+        builder.query(
+            2,
+            "news.com",
+            client,
+            FtlQueryStatus::Forward,
+            Some(upstream),
+        );
+        builder.query(3, "ads.com", client, FtlQueryStatus::Gravity, None);
+        builder.query(4, "news.com", client, FtlQueryStatus::Cache, None);
+        builder.query(5, "tracker.com", client, FtlQueryStatus::Blacklist, None);
+        builder.query(
+            6,
+            "news.com",
+            client,
+            FtlQueryStatus::Forward,
+            Some(upstream),
+        );
+        builder.query(7, "ads.com", client, FtlQueryStatus::Gravity, None);
+
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/recent_blocked?limit=3")
+            .ftl_memory(builder.build())
+            .file(PiholeFile::FtlConfig, "")
+            .expect_json(json!(["ads.com", "tracker.com", "ads.com"]))
+            .test();
+    }
+
+    /// Domains are hidden if the privacy level hides domains
+    #[test]
+    fn privacy_hides_domains() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/recent_blocked?limit=2")
+            .ftl_memory(test_memory())
+            .file(PiholeFile::FtlConfig, "PRIVACYLEVEL=1")
+            .expect_json(json!(["hidden", "hidden"]))
+            .test();
+    }
+
+    /// Nothing is shown with the maximum privacy level
+    #[test]
+    fn privacy_maximum() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/recent_blocked?limit=2")
+            .ftl_memory(test_memory())
+            .file(PiholeFile::FtlConfig, "PRIVACYLEVEL=3")
+            .expect_json(json!([]))
+            .test();
+    }
+
+    /// A domain ID past the end of shared memory is returned as an empty
+    /// string instead of panicking
+    #[test]
+    fn domain_past_end() {
+        let mut ftl_memory = test_memory();
+
+        if let FtlMemory::Test { queries, .. } = &mut ftl_memory {
+            queries[4].domain_id = 99;
+        }
+
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/recent_blocked?limit=2")
+            .ftl_memory(ftl_memory)
+            .file(PiholeFile::FtlConfig, "")
+            .expect_json(json!(["", "domain4.com"]))
+            .test();
+    }
+
+    /// A query counter past the end of shared memory does not panic
+    #[test]
+    fn counter_past_end() {
+        let mut ftl_memory = test_memory();
+
+        if let FtlMemory::Test { counters, .. } = &mut ftl_memory {
+            counters.total_queries = 100;
+        }
+
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/recent_blocked")
+            .ftl_memory(ftl_memory)
+            .file(PiholeFile::FtlConfig, "")
+            .expect_json(json!(["domain5.com"]))
+            .test();
+    }
+
+    /// The latest endpoint shows the most recently blocked domain and when it
+    /// was blocked
+    #[test]
+    fn latest() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/recent_blocked/latest")
+            .ftl_memory(test_memory())
+            .file(PiholeFile::FtlConfig, "")
+            .expect_json(json!({ "domain": "domain5.com", "timestamp": 5 }))
+            .test();
+    }
+
+    /// The latest domain is hidden if the privacy level hides domains
+    #[test]
+    fn latest_privacy_hides_domains() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/recent_blocked/latest")
+            .ftl_memory(test_memory())
+            .file(PiholeFile::FtlConfig, "PRIVACYLEVEL=1")
+            .expect_json(json!({ "domain": "hidden", "timestamp": 5 }))
+            .test();
+    }
+
+    /// The latest reply is empty if nothing has been blocked
+    #[test]
+    fn latest_nothing_blocked() {
+        let mut builder = TestFtlMemoryBuilder::new();
+        let client = builder.unnamed_client("10.1.1.1");
+        builder.query(1, "domain1.com", client, FtlQueryStatus::Cache, None);
+
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/recent_blocked/latest")
+            .ftl_memory(builder.build())
+            .file(PiholeFile::FtlConfig, "")
+            .expect_json(json!({}))
+            .test();
+    }
+
+    /// The latest reply is empty with the maximum privacy level
+    #[test]
+    fn latest_privacy_maximum() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/recent_blocked/latest")
+            .ftl_memory(test_memory())
+            .file(PiholeFile::FtlConfig, "PRIVACYLEVEL=3")
+            .expect_json(json!({}))
+            .test();
+    }
 }
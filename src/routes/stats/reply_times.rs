@@ -0,0 +1,260 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Reply Times Endpoint
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    ftl::{FtlMemory, FtlQueryStatus},
+    routes::{
+        auth::User,
+        stats::history::{
+            filters::{filter_blocked, filter_client},
+            HistoryParams,
+        },
+    },
+    util::{reply_result, Error, Reply},
+};
+use rocket::State;
+
+pub use reply_times as route;
+
+/// The upper bounds of the reply time buckets, in milliseconds. The last
+/// bucket has no upper bound.
+const BUCKET_BOUNDS: [u64; 4] = [1, 10, 50, 200];
+
+/// Get a histogram of the reply times
+#[get("/stats/reply_times?<params..>")]
+pub fn reply_times(_auth: User, ftl_memory: &State<FtlMemory>, params: ReplyTimesParams) -> Reply {
+    reply_result(get_reply_times(ftl_memory, params))
+}
+
+/// Represents the possible GET parameters on `/stats/reply_times`
+#[derive(FromForm, Default)]
+pub struct ReplyTimesParams {
+    /// Only include queries of clients matching this, like the history
+    pub client: Option<String>,
+    /// Include blocked and cached queries, which have synthetic reply times
+    pub all: Option<bool>,
+}
+
+/// Represents the reply structure for the reply time histogram
+#[derive(Serialize)]
+#[cfg_attr(test, derive(Debug, PartialEq))]
+pub struct ReplyTimesReply {
+    pub buckets: Vec<ReplyTimeBucket>,
+    pub total: usize,
+    /// The average reply time in milliseconds, if there are any replies
+    pub average: Option<f64>,
+}
+
+/// Represents a bucket of reply times, from `min` (inclusive) to `max`
+/// (exclusive) milliseconds
+#[derive(Serialize)]
+#[cfg_attr(test, derive(Debug, PartialEq))]
+pub struct ReplyTimeBucket {
+    pub min: u64,
+    pub max: Option<u64>,
+    pub count: usize,
+}
+
+/// Count the reply times of the queries in each bucket. Queries without a
+/// reply are not counted.
+fn get_reply_times(
+    ftl_memory: &FtlMemory,
+    params: ReplyTimesParams,
+) -> Result<ReplyTimesReply, Error> {
+    let all = params.all.unwrap_or(false);
+    let history_params = HistoryParams {
+        client: params.client.into_iter().collect(),
+        blocked: if all { None } else { Some(false) },
+        ..HistoryParams::default()
+    };
+
+    let lock = ftl_memory.lock()?;
+    let counters = ftl_memory.counters(&lock)?;
+    let queries = ftl_memory.queries(&lock)?;
+
+    let queries_iter = Box::new(queries.iter().take(counters.total_queries as usize));
+    let queries_iter = filter_blocked(queries_iter, &history_params);
+    let queries_iter = filter_client(queries_iter, &history_params, ftl_memory, &lock)?;
+
+    let mut counts = [0usize; BUCKET_BOUNDS.len() + 1];
+    let mut total = 0;
+    let mut sum = 0;
+
+    for query in queries_iter {
+        if !all && query.status == FtlQueryStatus::Cache {
+            continue;
+        }
+
+        let reply_time = match query.reply_time() {
+            Some(reply_time) => reply_time,
+            None => continue,
+        };
+
+        counts[bucket_index(reply_time)] += 1;
+        total += 1;
+        sum += reply_time;
+    }
+
+    let buckets = counts
+        .iter()
+        .enumerate()
+        .map(|(i, &count)| ReplyTimeBucket {
+            min: if i == 0 { 0 } else { BUCKET_BOUNDS[i - 1] },
+            max: BUCKET_BOUNDS.get(i).copied(),
+            count,
+        })
+        .collect();
+
+    // The reply times are in units of 1/10 milliseconds
+    let average = if total == 0 {
+        None
+    } else {
+        Some(sum as f64 / total as f64 / 10.0)
+    };
+
+    Ok(ReplyTimesReply {
+        buckets,
+        total,
+        average,
+    })
+}
+
+/// Get the index of the bucket for a reply time in units of 1/10
+/// milliseconds
+fn bucket_index(reply_time: u64) -> usize {
+    BUCKET_BOUNDS
+        .iter()
+        .position(|&bound| reply_time < bound * 10)
+        .unwrap_or(BUCKET_BOUNDS.len())
+}
+
+#[cfg(test)]
+mod test {
+    use super::bucket_index;
+    use crate::{
+        ftl::{FtlMemory, FtlQueryStatus},
+        testing::{TestBuilder, TestFtlMemoryBuilder},
+    };
+
+    /// Forwarded queries with reply times in every bucket, from two clients,
+    /// along with blocked, cached, and unanswered queries
+    fn test_memory() -> FtlMemory {
+        let mut builder = TestFtlMemoryBuilder::new();
+        let client1 = builder.client("10.1.1.1", "laptop");
+        let client2 = builder.unnamed_client("10.1.1.2");
+        let upstream = builder.upstream("8.8.8.8", "google-public-dns-a.google.com");
+
+        let reply_times = [
+            (client1, 5),
+            (client1, 10),
+            (client1, 99),
+            (client2, 100),
+            (client2, 499),
+            (client2, 500),
+            (client2, 1999),
+            (client1, 2000),
+            (client2, 0),
+        ];
+
+        for (i, &(client, reply_time)) in reply_times.iter().enumerate() {
+            let query = builder.query(
+                1000 + i,
+                "example.com",
+                client,
+                FtlQueryStatus::Forward,
+                Some(upstream),
+            );
+            builder.response_time(query, reply_time);
+        }
+
+        let blocked = builder.query(2000, "ads.com", client1, FtlQueryStatus::Gravity, None);
+        builder.response_time(blocked, 1);
+        let cached = builder.query(2001, "example.com", client2, FtlQueryStatus::Cache, None);
+        builder.response_time(cached, 2);
+
+        builder.build()
+    }
+
+    /// Forwarded queries are counted in their buckets, and the blocked,
+    /// cached, and unanswered queries are not counted
+    #[test]
+    fn default_params() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/reply_times")
+            .ftl_memory(test_memory())
+            .expect_json(json!({
+                "buckets": [
+                    { "min": 0, "max": 1, "count": 1 },
+                    { "min": 1, "max": 10, "count": 2 },
+                    { "min": 10, "max": 50, "count": 2 },
+                    { "min": 50, "max": 200, "count": 2 },
+                    { "min": 200, "max": null, "count": 1 }
+                ],
+                "total": 8,
+                "average": 65.15
+            }))
+            .test();
+    }
+
+    /// Blocked and cached queries are counted if requested
+    #[test]
+    fn all() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/reply_times?all=true")
+            .ftl_memory(test_memory())
+            .expect_json(json!({
+                "buckets": [
+                    { "min": 0, "max": 1, "count": 3 },
+                    { "min": 1, "max": 10, "count": 2 },
+                    { "min": 10, "max": 50, "count": 2 },
+                    { "min": 50, "max": 200, "count": 2 },
+                    { "min": 200, "max": null, "count": 1 }
+                ],
+                "total": 10,
+                "average": 52.15
+            }))
+            .test();
+    }
+
+    /// Only the queries of matching clients are counted
+    #[test]
+    fn client() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/reply_times?client=laptop")
+            .ftl_memory(test_memory())
+            .expect_json(json!({
+                "buckets": [
+                    { "min": 0, "max": 1, "count": 1 },
+                    { "min": 1, "max": 10, "count": 2 },
+                    { "min": 10, "max": 50, "count": 0 },
+                    { "min": 50, "max": 200, "count": 0 },
+                    { "min": 200, "max": null, "count": 1 }
+                ],
+                "total": 4,
+                "average": 52.85
+            }))
+            .test();
+    }
+
+    /// Reply times on a bucket boundary go in the higher bucket
+    #[test]
+    fn bucket_boundaries() {
+        assert_eq!(bucket_index(0), 0);
+        assert_eq!(bucket_index(9), 0);
+        assert_eq!(bucket_index(10), 1);
+        assert_eq!(bucket_index(99), 1);
+        assert_eq!(bucket_index(100), 2);
+        assert_eq!(bucket_index(499), 2);
+        assert_eq!(bucket_index(500), 3);
+        assert_eq!(bucket_index(1999), 3);
+        assert_eq!(bucket_index(2000), 4);
+        assert_eq!(bucket_index(u64::MAX), 4);
+    }
+}
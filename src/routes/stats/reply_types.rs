@@ -0,0 +1,157 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Reply Types Endpoint
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    ftl::{FtlMemory, FtlQueryReplyType},
+    routes::auth::User,
+    util::{reply_result, Error, Reply},
+};
+use rocket::State;
+
+pub use reply_types as route;
+
+/// Get the reply types
+#[get("/stats/reply_types")]
+pub fn reply_types(_auth: User, ftl_memory: &State<FtlMemory>) -> Reply {
+    reply_result(reply_types_impl(ftl_memory))
+}
+
+/// Get the reply types. FTL only keeps counters for some of the reply types,
+/// so the in-memory queries are counted instead.
+fn reply_types_impl(ftl_memory: &FtlMemory) -> Result<ReplyTypesReply, Error> {
+    let lock = ftl_memory.lock()?;
+    let counters = ftl_memory.counters(&lock)?;
+    let queries = ftl_memory.queries(&lock)?;
+
+    let mut counts = [0usize; FtlQueryReplyType::OTHER as usize + 1];
+    let total = counters.total_queries as usize;
+
+    for query in queries.iter().take(total) {
+        counts[query.reply_type as usize] += 1;
+    }
+
+    let reply_types: Vec<ReplyTypeReply> = FtlQueryReplyType::variants()
+        .iter()
+        .map(|&variant| ReplyTypeReply {
+            name: variant.get_name(),
+            count: counts[variant as usize],
+        })
+        .collect();
+
+    // Queries without a reply yet, or with a reply type which the API does
+    // not know about, are counted as other
+    let known: usize = reply_types.iter().map(|reply_type| reply_type.count).sum();
+
+    Ok(ReplyTypesReply {
+        reply_types,
+        other: total.saturating_sub(known),
+        total,
+    })
+}
+
+/// Represents the reply structure for the reply types and their total
+#[derive(Serialize)]
+#[cfg_attr(test, derive(Debug, PartialEq))]
+pub struct ReplyTypesReply {
+    pub reply_types: Vec<ReplyTypeReply>,
+    /// The number of queries with a reply type which is not listed
+    pub other: usize,
+    pub total: usize,
+}
+
+/// Represents the reply structure for returning reply type data
+#[derive(Serialize)]
+#[cfg_attr(test, derive(Debug, PartialEq))]
+pub struct ReplyTypeReply {
+    pub name: &'static str,
+    pub count: usize,
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        ftl::{FtlQueryReplyType, FtlQueryStatus},
+        testing::{TestBuilder, TestFtlMemoryBuilder},
+    };
+
+    /// Queries are counted by reply type, and queries without a known reply
+    /// type are counted as other
+    #[test]
+    fn reply_types() {
+        let mut builder = TestFtlMemoryBuilder::new();
+        let client = builder.unnamed_client("10.1.1.1");
+        let upstream = builder.upstream("8.8.8.8", "google-public-dns-a.google.com");
+        let replies = [
+            FtlQueryReplyType::IP,
+            FtlQueryReplyType::IP,
+            FtlQueryReplyType::CNAME,
+            FtlQueryReplyType::NXDOMAIN,
+            FtlQueryReplyType::NODATA,
+            FtlQueryReplyType::SERVFAIL,
+            FtlQueryReplyType::Unknown,
+            FtlQueryReplyType::OTHER,
+        ];
+
+        for (i, &reply) in replies.iter().enumerate() {
+            let query = builder.query(
+                1000 + i,
+                "example.com",
+                client,
+                FtlQueryStatus::Forward,
+                Some(upstream),
+            );
+            builder.reply_type(query, reply);
+        }
+
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/reply_types")
+            .ftl_memory(builder.build())
+            .expect_json(json!({
+                "reply_types": [
+                    { "name": "NODATA", "count": 1 },
+                    { "name": "NXDOMAIN", "count": 1 },
+                    { "name": "CNAME", "count": 1 },
+                    { "name": "IP", "count": 2 },
+                    { "name": "DOMAIN", "count": 0 },
+                    { "name": "RRNAME", "count": 0 },
+                    { "name": "SERVFAIL", "count": 1 },
+                    { "name": "REFUSED", "count": 0 },
+                    { "name": "NOTIMP", "count": 0 }
+                ],
+                "other": 2,
+                "total": 8
+            }))
+            .test();
+    }
+
+    /// Every reply type is included when there are no queries
+    #[test]
+    fn no_queries() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/reply_types")
+            .ftl_memory(TestFtlMemoryBuilder::new().build())
+            .expect_json(json!({
+                "reply_types": [
+                    { "name": "NODATA", "count": 0 },
+                    { "name": "NXDOMAIN", "count": 0 },
+                    { "name": "CNAME", "count": 0 },
+                    { "name": "IP", "count": 0 },
+                    { "name": "DOMAIN", "count": 0 },
+                    { "name": "RRNAME", "count": 0 },
+                    { "name": "SERVFAIL", "count": 0 },
+                    { "name": "REFUSED", "count": 0 },
+                    { "name": "NOTIMP", "count": 0 }
+                ],
+                "other": 0,
+                "total": 0
+            }))
+            .test();
+    }
+}
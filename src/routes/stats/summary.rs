@@ -9,10 +9,9 @@
 // Please see LICENSE file for your rights under this license.
 
 use crate::{
-    env::Env,
     ftl::{FtlMemory, FtlQueryType},
-    services::PiholeModule,
-    settings::{ConfigEntry, FtlConfEntry, FtlPrivacyLevel, SetupVarsEntry},
+    services::{privacy::PrivacyService, settings::SettingsRepository, PiholeModule},
+    settings::{FtlPrivacyLevel, SetupVarsEntry},
     util::{reply_data, Reply},
 };
 use rocket::State;
@@ -20,19 +19,26 @@ use shaku_rocket::Inject;
 
 /// Get the summary data
 #[get("/stats/summary")]
-pub fn get_summary(ftl_memory: &State<FtlMemory>, env: Inject<PiholeModule, Env>) -> Reply {
+pub fn get_summary(
+    ftl_memory: &State<FtlMemory>,
+    settings: Inject<PiholeModule, dyn SettingsRepository>,
+    privacy: Inject<PiholeModule, dyn PrivacyService>,
+) -> Reply {
     let lock = ftl_memory.lock()?;
     let counters = ftl_memory.counters(&lock)?;
 
+    // Calculate the percentage as a float, since multiplying the counter could
+    // overflow on a busy server
     let percent_blocked = if counters.total_queries == 0 {
         0.0
     } else {
-        (counters.blocked_queries * 100) as f64 / counters.total_queries as f64
+        counters.blocked_queries as f64 * 100.0 / counters.total_queries as f64
     };
 
     let (total_clients, active_clients) = {
-        if FtlConfEntry::PrivacyLevel.read_as::<FtlPrivacyLevel>(&env)?
-            >= FtlPrivacyLevel::HideDomainsAndClients
+        if !privacy
+            .privacy_level()?
+            .allows(FtlPrivacyLevel::HideDomainsAndClients)
         {
             // If clients are supposed to be hidden, pretend there are no clients
             (0, 0)
@@ -58,7 +64,7 @@ pub fn get_summary(ftl_memory: &State<FtlMemory>, env: Inject<PiholeModule, Env>
         }
     };
 
-    let status = if SetupVarsEntry::BlockingEnabled.is_true(&env)? {
+    let status = if settings.get_bool(SetupVarsEntry::BlockingEnabled)? {
         "enabled"
     } else {
         "disabled"
@@ -264,4 +270,79 @@ mod test {
             }))
             .test();
     }
+
+    /// With no queries, the blocked percentage is zero
+    #[test]
+    fn no_queries() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/summary")
+            .ftl_memory(FtlMemory::Test {
+                clients: Vec::new(),
+                domains: Vec::new(),
+                over_time: Vec::new(),
+                strings: HashMap::new(),
+                upstreams: Vec::new(),
+                queries: Vec::new(),
+                counters: FtlCounters::default(),
+                settings: FtlSettings::default(),
+            })
+            .file(PiholeFile::SetupVars, "BLOCKING_ENABLED=true")
+            .file(PiholeFile::FtlConfig, "")
+            .expect_json(json!({
+                "gravity_size": 0,
+                "total_queries": {
+                    "A": 0,
+                    "AAAA": 0,
+                    "ANY": 0,
+                    "SRV": 0,
+                    "SOA": 0,
+                    "PTR": 0,
+                    "TXT": 0
+                },
+                "blocked_queries": 0,
+                "percent_blocked": 0.0,
+                "unique_domains": 0,
+                "forwarded_queries": 0,
+                "cached_queries": 0,
+                "reply_types": {
+                    "IP": 0,
+                    "CNAME": 0,
+                    "DOMAIN": 0,
+                    "NODATA": 0,
+                    "NXDOMAIN": 0
+                },
+                "total_clients": 0,
+                "active_clients": 0,
+                "status": "enabled"
+            }))
+            .test();
+    }
+
+    /// The blocked percentage is correct for large query counts
+    #[test]
+    fn many_queries() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/summary")
+            .ftl_memory(FtlMemory::Test {
+                clients: Vec::new(),
+                domains: Vec::new(),
+                over_time: Vec::new(),
+                strings: HashMap::new(),
+                upstreams: Vec::new(),
+                queries: Vec::new(),
+                counters: FtlCounters {
+                    total_queries: 100_000_000,
+                    blocked_queries: 25_000_000,
+                    ..FtlCounters::default()
+                },
+                settings: FtlSettings::default(),
+            })
+            .file(PiholeFile::SetupVars, "BLOCKING_ENABLED=true")
+            .file(PiholeFile::FtlConfig, "")
+            .expect_json_contains(json!({
+                "blocked_queries": 25_000_000,
+                "percent_blocked": 25.0
+            }))
+            .test();
+    }
 }
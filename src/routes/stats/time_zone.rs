@@ -0,0 +1,346 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Over Time Bucket Time Zones
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    env::Env,
+    ftl::OVERTIME_INTERVAL,
+    util::{Error, ErrorKind},
+};
+use chrono::{NaiveDateTime, Offset, TimeZone};
+use chrono_tz::Tz;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The largest offset from UTC which is accepted, in seconds (14 hours)
+const MAX_UTC_OFFSET: i32 = 14 * 3600;
+
+/// The time zone which over time buckets are aligned to, so buckets can start
+/// at local midnight or on local hours
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum BucketTimeZone {
+    /// A fixed offset from UTC in seconds
+    Fixed(i32),
+    /// An IANA time zone, where the offset can change with daylight saving
+    Named(Tz),
+}
+
+/// A bucket which a slot belongs to
+#[cfg_attr(test, derive(Debug, PartialEq))]
+pub struct Bucket {
+    /// The local time of the start of the bucket, which identifies it
+    pub local_start: i64,
+    /// The timestamp in the middle of the bucket
+    pub timestamp: u64,
+}
+
+impl BucketTimeZone {
+    /// Get the time zone from the `timezone` (IANA name) or `utc_offset` (in
+    /// seconds) parameters. Without either parameter, the time zone in the
+    /// config is used, and otherwise UTC.
+    pub fn from_params(
+        timezone: Option<&str>,
+        utc_offset: Option<i32>,
+        env: &Env,
+    ) -> Result<Self, Error> {
+        match (timezone, utc_offset) {
+            (Some(_), Some(_)) => Err(Error::from(ErrorKind::BadRequest)),
+            (Some(timezone), None) => Self::from_name(timezone).ok_or_else(|| {
+                Error::from(ErrorKind::InvalidParameter(
+                    "timezone".to_owned(),
+                    timezone.to_owned(),
+                    Vec::new(),
+                ))
+            }),
+            (None, Some(offset)) if offset.abs() <= MAX_UTC_OFFSET => {
+                Ok(BucketTimeZone::Fixed(offset))
+            }
+            (None, Some(offset)) => Err(Error::from(ErrorKind::InvalidParameter(
+                "utc_offset".to_owned(),
+                offset.to_string(),
+                Vec::new(),
+            ))),
+            (None, None) => Ok(env
+                .config()
+                .general
+                .timezone
+                .as_deref()
+                .and_then(Self::from_name)
+                .unwrap_or(BucketTimeZone::Fixed(0))),
+        }
+    }
+
+    /// Get the time zone with an IANA name, such as `Europe/Berlin`
+    pub fn from_name(name: &str) -> Option<Self> {
+        name.parse().ok().map(BucketTimeZone::Named)
+    }
+
+    /// Get the offset from UTC in seconds at the timestamp
+    pub fn offset_at(self, timestamp: u64) -> i32 {
+        match self {
+            BucketTimeZone::Fixed(offset) => offset,
+            BucketTimeZone::Named(tz) => tz
+                .offset_from_utc_datetime(&NaiveDateTime::from_timestamp(timestamp as i64, 0))
+                .fix()
+                .local_minus_utc(),
+        }
+    }
+
+    /// Get the offset from UTC in seconds at the current time, which is shown
+    /// in the replies
+    pub fn current_offset(self) -> i32 {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or_default();
+
+        self.offset_at(now)
+    }
+
+    /// Find the bucket of the interval which the timestamp is in. Bucket edges
+    /// are aligned to multiples of the interval in local time, so day buckets
+    /// start at local midnight.
+    ///
+    /// Slots should be grouped by the local start of their bucket. On days
+    /// with a daylight saving transition, this means a day bucket covers the
+    /// whole local day of 23 or 25 hours. When the clocks go back, slots in the
+    /// repeated local time fall into the same bucket as the slots before them
+    /// if the bucket is still open (such as an hour bucket of two hours), and
+    /// otherwise start a new bucket. Skipped local times have no bucket. The
+    /// bucket timestamp is found with the offset of the first slot in it, so
+    /// buckets never overlap and their timestamps always increase.
+    pub fn bucket(self, timestamp: u64, interval: u64) -> Bucket {
+        let offset = i64::from(self.offset_at(timestamp));
+        let local = timestamp as i64 + offset;
+        let local_start = local - local.rem_euclid(interval as i64);
+        let start = (local_start - offset).max(0) as u64;
+
+        Bucket {
+            local_start,
+            timestamp: start + interval / 2,
+        }
+    }
+
+    /// Group the slots into buckets of the interval. The slots are given in
+    /// order as their timestamp and data, and the data of the slots in a
+    /// bucket is combined with `merge`. The buckets are returned as their
+    /// timestamp and data. Slots keep their timestamp if the interval is the
+    /// FTL slot interval.
+    pub fn group_slots<T>(
+        self,
+        slots: impl IntoIterator<Item = (u64, T)>,
+        interval: usize,
+        mut merge: impl FnMut(&mut T, T),
+    ) -> Vec<(u64, T)> {
+        let mut buckets: Vec<(i64, u64, T)> = Vec::new();
+
+        for (timestamp, data) in slots {
+            let (local_start, timestamp) = if interval == OVERTIME_INTERVAL {
+                (timestamp as i64, timestamp)
+            } else {
+                let bucket = self.bucket(timestamp, interval as u64);
+                (bucket.local_start, bucket.timestamp)
+            };
+
+            // The slots are in order, so a slot is either in the last bucket or
+            // starts a new bucket
+            match buckets.last_mut() {
+                Some((last_start, _, bucket)) if *last_start == local_start => merge(bucket, data),
+                _ => buckets.push((local_start, timestamp, data)),
+            }
+        }
+
+        buckets
+            .into_iter()
+            .map(|(_, timestamp, data)| (timestamp, data))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Bucket, BucketTimeZone};
+    use crate::{
+        env::{Config, Env},
+        util::ErrorKind,
+    };
+    use std::collections::HashMap;
+
+    /// 2021-10-31 00:00 UTC, the day the clocks in Berlin go back from +2 to
+    /// +1 at 01:00 UTC
+    const FALL_BACK_DAY: u64 = 1_635_638_400;
+
+    /// 2021-03-28 00:00 UTC, the day the clocks in Berlin go forward from +1
+    /// to +2 at 01:00 UTC
+    const SPRING_FORWARD_DAY: u64 = 1_616_889_600;
+
+    /// Group the slots of ten minutes in the time range into buckets of the
+    /// interval, and count the slots in each bucket
+    fn group(
+        time_zone: BucketTimeZone,
+        from: u64,
+        until: u64,
+        interval: usize,
+    ) -> Vec<(u64, usize)> {
+        time_zone.group_slots(
+            (from..until)
+                .step_by(600)
+                .map(|timestamp| (timestamp + 300, 1)),
+            interval,
+            |count, slot| *count += slot,
+        )
+    }
+
+    /// UTC buckets are aligned to multiples of the interval
+    #[test]
+    fn utc() {
+        assert_eq!(
+            BucketTimeZone::Fixed(0).bucket(4000, 1800),
+            Bucket {
+                local_start: 3600,
+                timestamp: 4500
+            }
+        );
+    }
+
+    /// Buckets with a fixed offset start on local hours and days, including
+    /// offsets which are not whole hours
+    #[test]
+    fn fixed_offset() {
+        // 01:05 UTC is 06:35 at +05:30, so the local hour started at 00:30 UTC
+        assert_eq!(
+            BucketTimeZone::Fixed(19_800).bucket(3900, 3600),
+            Bucket {
+                local_start: 21_600,
+                timestamp: 1800 + 1800
+            }
+        );
+        // 01:00 UTC on the second day is 21:00 on the first day at -04:00, so
+        // the local day started at 04:00 UTC on the first day
+        assert_eq!(
+            BucketTimeZone::Fixed(-14_400).bucket(90_000, 86_400),
+            Bucket {
+                local_start: 0,
+                timestamp: 14_400 + 43_200
+            }
+        );
+    }
+
+    /// Day buckets start at local midnight
+    #[test]
+    fn local_midnight() {
+        let berlin = BucketTimeZone::from_name("Europe/Berlin").unwrap();
+
+        // Noon UTC in summer, so the day started at 22:00 UTC the day before
+        assert_eq!(
+            berlin
+                .bucket(FALL_BACK_DAY - 86_400 + 43_200, 86_400)
+                .timestamp,
+            FALL_BACK_DAY - 86_400 - 7200 + 43_200
+        );
+    }
+
+    /// The day the clocks go back has a bucket of 25 hours, and the buckets
+    /// around it do not overlap
+    #[test]
+    fn long_day() {
+        let berlin = BucketTimeZone::from_name("Europe/Berlin").unwrap();
+        let from = FALL_BACK_DAY - 86_400 - 7200;
+
+        assert_eq!(
+            group(berlin, from, from + 3 * 86_400 + 3600, 86_400),
+            vec![
+                (from + 43_200, 144),
+                (from + 86_400 + 43_200, 150),
+                (from + 86_400 + 90_000 + 43_200, 144)
+            ]
+        );
+    }
+
+    /// The day the clocks go forward has a bucket of 23 hours
+    #[test]
+    fn short_day() {
+        let berlin = BucketTimeZone::from_name("Europe/Berlin").unwrap();
+        let from = SPRING_FORWARD_DAY - 3600;
+
+        assert_eq!(
+            group(berlin, from, from + 86_400 - 3600, 86_400),
+            vec![(from + 43_200, 138)]
+        );
+    }
+
+    /// When the clocks go back, the repeated hour is in the same hour bucket
+    /// as the hour before it, so the buckets do not overlap
+    #[test]
+    fn repeated_hour() {
+        let berlin = BucketTimeZone::from_name("Europe/Berlin").unwrap();
+
+        // 02:00 to 03:00 local time happens at 00:00 and 01:00 UTC
+        assert_eq!(
+            group(berlin, FALL_BACK_DAY - 3600, FALL_BACK_DAY + 7200, 3600),
+            vec![(FALL_BACK_DAY - 3600 + 1800, 6), (FALL_BACK_DAY + 1800, 12)]
+        );
+    }
+
+    /// An unknown time zone name is rejected
+    #[test]
+    fn unknown_time_zone() {
+        let env = Env::Test(Config::default(), HashMap::new());
+
+        assert_eq!(
+            BucketTimeZone::from_params(Some("Mars/Olympus_Mons"), None, &env)
+                .unwrap_err()
+                .kind(),
+            ErrorKind::InvalidParameter(
+                "timezone".to_owned(),
+                "Mars/Olympus_Mons".to_owned(),
+                Vec::new()
+            )
+        );
+    }
+
+    /// The time zone and offset can not both be given, and the offset must be
+    /// within 14 hours of UTC
+    #[test]
+    fn invalid_params() {
+        let env = Env::Test(Config::default(), HashMap::new());
+
+        assert_eq!(
+            BucketTimeZone::from_params(Some("UTC"), Some(0), &env)
+                .unwrap_err()
+                .kind(),
+            ErrorKind::BadRequest
+        );
+        assert_eq!(
+            BucketTimeZone::from_params(None, Some(50_401), &env)
+                .unwrap_err()
+                .kind(),
+            ErrorKind::InvalidParameter("utc_offset".to_owned(), "50401".to_owned(), Vec::new())
+        );
+    }
+
+    /// The config time zone is used without any parameters
+    #[test]
+    fn config_default() {
+        let mut config = Config::default();
+        config.general.timezone = Some("Asia/Kolkata".to_owned());
+        let env = Env::Test(config, HashMap::new());
+
+        assert_eq!(
+            BucketTimeZone::from_params(None, None, &env)
+                .unwrap()
+                .offset_at(0),
+            19_800
+        );
+        assert_eq!(
+            BucketTimeZone::from_params(None, None, &Env::Test(Config::default(), HashMap::new()))
+                .unwrap(),
+            BucketTimeZone::Fixed(0)
+        );
+    }
+}
@@ -0,0 +1,225 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Top Blocked Domains Endpoint
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    ftl::FtlMemory,
+    routes::{
+        auth::User,
+        stats::top_domains::{get_top_domains, TopDomainParams},
+    },
+    services::{
+        domain_audit::DomainAuditRepository, exclusions::ExclusionService, privacy::PrivacyService,
+        settings::SettingsRepository, PiholeModule,
+    },
+    util::{reply_result, Reply},
+};
+use rocket::State;
+use shaku_rocket::{Inject, InjectProvided};
+
+pub use top_blocked as route;
+
+/// Return the top blocked domains, along with the total number of blocked
+/// queries. This is the same as `/stats/top_domains?blocked=true`.
+#[get("/stats/top_blocked?<params..>")]
+pub fn top_blocked(
+    _auth: User,
+    ftl_memory: &State<FtlMemory>,
+    settings: Inject<PiholeModule, dyn SettingsRepository>,
+    privacy: Inject<PiholeModule, dyn PrivacyService>,
+    exclusions: Inject<PiholeModule, dyn ExclusionService>,
+    params: TopBlockedParams,
+    domain_audit: InjectProvided<PiholeModule, dyn DomainAuditRepository>,
+) -> Reply {
+    let params = TopDomainParams {
+        limit: params.limit,
+        audit: params.audit,
+        ascending: params.ascending,
+        blocked: Some(true),
+        sort: params.sort,
+        order: params.order,
+    };
+
+    let redactor = privacy.redactor()?;
+    let exclusions = exclusions.exclusions()?;
+
+    reply_result(get_top_domains(
+        ftl_memory,
+        &*settings,
+        redactor,
+        &exclusions,
+        params,
+        &*domain_audit,
+    ))
+}
+
+/// Represents the possible GET parameters for top blocked domains requests
+#[derive(FromForm, Default)]
+pub struct TopBlockedParams {
+    pub limit: Option<usize>,
+    pub audit: Option<bool>,
+    pub ascending: Option<bool>,
+    pub sort: Option<String>,
+    pub order: Option<String>,
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        env::PiholeFile,
+        ftl::{FtlCounters, FtlDomain, FtlMemory, FtlRegexMatch, FtlSettings},
+        services::domain_audit::{DomainAuditRepository, MockDomainAuditRepository},
+        testing::TestBuilder,
+    };
+    use std::collections::HashMap;
+
+    /// Four blocked domains (one hidden) and one permitted domain
+    fn test_data() -> FtlMemory {
+        let mut strings = HashMap::new();
+        strings.insert(1, "example.com".to_owned());
+        strings.insert(2, "hidden".to_owned());
+        strings.insert(3, "github.com".to_owned());
+        strings.insert(4, "ads.example.net".to_owned());
+        strings.insert(5, "tracker.example.org".to_owned());
+
+        FtlMemory::Test {
+            domains: vec![
+                FtlDomain::new(10, 4, 1, FtlRegexMatch::Unknown),
+                FtlDomain::new(4, 4, 2, FtlRegexMatch::Unknown),
+                FtlDomain::new(20, 0, 3, FtlRegexMatch::Unknown),
+                FtlDomain::new(12, 12, 4, FtlRegexMatch::Unknown),
+                FtlDomain::new(7, 7, 5, FtlRegexMatch::Unknown),
+            ],
+            clients: Vec::new(),
+            over_time: Vec::new(),
+            strings,
+            upstreams: Vec::new(),
+            queries: Vec::new(),
+            counters: FtlCounters {
+                total_queries: 53,
+                blocked_queries: 27,
+                total_domains: 5,
+                ..FtlCounters::default()
+            },
+            settings: FtlSettings::default(),
+        }
+    }
+
+    /// Blocked domains are sorted by their blocked count, not their total
+    /// count, and hidden or unblocked domains are not shown
+    #[test]
+    fn default_params() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/top_blocked")
+            .ftl_memory(test_data())
+            .mock_provider::<dyn DomainAuditRepository>(Box::new(|_| {
+                Ok(Box::new(MockDomainAuditRepository::new()))
+            }))
+            .file(PiholeFile::SetupVars, "")
+            .file(PiholeFile::FtlConfig, "")
+            .expect_json(json!({
+                "top_domains": [
+                    { "domain": "ads.example.net", "count": 12 },
+                    { "domain": "tracker.example.org", "count": 7 },
+                    { "domain": "example.com", "count": 4 }
+                ],
+                "blocked_queries": 27
+            }))
+            .test();
+    }
+
+    /// Don't show more domains than the limit
+    #[test]
+    fn limit() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/top_blocked?limit=2")
+            .ftl_memory(test_data())
+            .mock_provider::<dyn DomainAuditRepository>(Box::new(|_| {
+                Ok(Box::new(MockDomainAuditRepository::new()))
+            }))
+            .file(PiholeFile::SetupVars, "")
+            .file(PiholeFile::FtlConfig, "")
+            .expect_json(json!({
+                "top_domains": [
+                    { "domain": "ads.example.net", "count": 12 },
+                    { "domain": "tracker.example.org", "count": 7 }
+                ],
+                "blocked_queries": 27
+            }))
+            .test();
+    }
+
+    /// Audited domains are not shown when only unaudited domains are
+    /// requested
+    #[test]
+    fn audit() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/top_blocked?audit=true")
+            .ftl_memory(test_data())
+            .mock_provider::<dyn DomainAuditRepository>(Box::new(|_| {
+                let mut domain_audit = MockDomainAuditRepository::new();
+
+                domain_audit
+                    .expect_get_all()
+                    .return_const(Ok(vec!["ads.example.net".to_owned()]));
+
+                Ok(Box::new(domain_audit))
+            }))
+            .file(PiholeFile::SetupVars, "")
+            .file(PiholeFile::FtlConfig, "")
+            .expect_json(json!({
+                "top_domains": [
+                    { "domain": "tracker.example.org", "count": 7 },
+                    { "domain": "example.com", "count": 4 }
+                ],
+                "blocked_queries": 27
+            }))
+            .test();
+    }
+
+    /// If domains are hidden by the privacy level, only the blocked total is
+    /// shown
+    #[test]
+    fn privacy_level_hides_domains() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/top_blocked")
+            .ftl_memory(test_data())
+            .mock_provider::<dyn DomainAuditRepository>(Box::new(|_| {
+                Ok(Box::new(MockDomainAuditRepository::new()))
+            }))
+            .file(PiholeFile::SetupVars, "")
+            .file(PiholeFile::FtlConfig, "PRIVACYLEVEL=1")
+            .expect_json(json!({
+                "top_domains": [],
+                "blocked_queries": 27
+            }))
+            .test();
+    }
+
+    /// Excluding a domain also excludes its subdomains, and the exclusions are
+    /// applied before the limit
+    #[test]
+    fn excluded_subdomains() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/top_blocked?limit=1")
+            .ftl_memory(test_data())
+            .mock_provider::<dyn DomainAuditRepository>(Box::new(|_| {
+                Ok(Box::new(MockDomainAuditRepository::new()))
+            }))
+            .file(PiholeFile::SetupVars, "API_EXCLUDE_DOMAINS=example.net")
+            .file(PiholeFile::FtlConfig, "")
+            .expect_json(json!({
+                "top_domains": [
+                    { "domain": "tracker.example.org", "count": 7 }
+                ],
+                "blocked_queries": 27
+            }))
+            .test();
+    }
+}
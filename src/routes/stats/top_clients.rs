@@ -10,13 +10,21 @@
 
 use crate::{
     env::Env,
-    ftl::{FtlClient, FtlMemory},
+    ftl::{FtlClient, FtlMemory, FtlRedactor},
     routes::{
         auth::User,
-        stats::common::{remove_excluded_clients, remove_hidden_clients},
+        stats::{
+            common::{get_client_name, remove_excluded_clients},
+            top_sort::{TopSort, TopSortKey},
+        },
     },
-    services::PiholeModule,
-    settings::{ConfigEntry, FtlConfEntry, FtlPrivacyLevel},
+    services::{
+        exclusions::{ExclusionService, Exclusions},
+        hostnames::HostnameCache,
+        privacy::PrivacyService,
+        PiholeModule,
+    },
+    settings::FtlPrivacyLevel,
     util::{reply_result, Error, Reply},
 };
 use rocket::State;
@@ -30,9 +38,22 @@ pub fn top_clients(
     _auth: User,
     ftl_memory: &State<FtlMemory>,
     env: Inject<PiholeModule, Env>,
+    hostnames: Inject<PiholeModule, HostnameCache>,
+    privacy: Inject<PiholeModule, dyn PrivacyService>,
+    exclusions: Inject<PiholeModule, dyn ExclusionService>,
     params: TopClientParams,
 ) -> Reply {
-    reply_result(get_top_clients(ftl_memory, &env, params))
+    let redactor = privacy.redactor()?;
+    let exclusions = exclusions.exclusions()?;
+
+    reply_result(get_top_clients(
+        ftl_memory,
+        &env,
+        &hostnames,
+        redactor,
+        &exclusions,
+        params,
+    ))
 }
 
 /// Represents the possible GET parameters on `/stats/top_clients`
@@ -42,6 +63,10 @@ pub struct TopClientParams {
     pub inactive: Option<bool>,
     pub ascending: Option<bool>,
     pub blocked: Option<bool>,
+    /// Sort by `count` (default) or `name`
+    pub sort: Option<String>,
+    /// Sort in `asc` or `desc` order, overriding `ascending`
+    pub order: Option<String>,
 }
 
 /// Represents the reply structure for top (blocked) clients
@@ -59,7 +84,7 @@ pub struct TopClientsReply {
 #[derive(Serialize)]
 #[cfg_attr(test, derive(Debug, PartialEq))]
 pub struct TopClientItemReply {
-    pub name: String,
+    pub name: Option<String>,
     pub ip: String,
     pub count: usize,
 }
@@ -68,13 +93,20 @@ pub struct TopClientItemReply {
 fn get_top_clients(
     ftl_memory: &FtlMemory,
     env: &Env,
+    hostnames: &HostnameCache,
+    redactor: FtlRedactor,
+    exclusions: &Exclusions,
     params: TopClientParams,
 ) -> Result<TopClientsReply, Error> {
     // Resolve the parameters
     let limit = params.limit.unwrap_or(10);
     let inactive = params.inactive.unwrap_or(false);
-    let ascending = params.ascending.unwrap_or(false);
     let blocked = params.blocked.unwrap_or(false);
+    let sort = TopSort::from_params(
+        params.sort.as_deref(),
+        params.order.as_deref(),
+        params.ascending,
+    )?;
 
     let lock = ftl_memory.lock()?;
     let counters = ftl_memory.counters(&lock)?;
@@ -86,7 +118,7 @@ fn get_top_clients(
     } as usize;
 
     // Check if the client details are private
-    if let Some(reply) = check_privacy_level_top_clients(env, blocked, total_count)? {
+    if let Some(reply) = check_privacy_level_top_clients(redactor.level(), blocked, total_count) {
         // We can not share any of the clients, so use the reply returned by the
         // function
         return Ok(reply);
@@ -110,38 +142,71 @@ fn get_top_clients(
         }
     }
 
-    // Remove excluded and hidden clients
-    remove_excluded_clients(&mut clients, env, &strings)?;
-    remove_hidden_clients(&mut clients, &strings);
+    // Remove excluded and private clients
+    remove_excluded_clients(&mut clients, exclusions, &strings);
+    redactor.retain_clients(&mut clients, &strings);
 
-    // Sort the clients (descending by default)
-    match (ascending, blocked) {
-        (false, false) => clients.sort_by(|a, b| b.query_count.cmp(&a.query_count)),
-        (true, false) => clients.sort_by(|a, b| a.query_count.cmp(&b.query_count)),
-        (false, true) => clients.sort_by(|a, b| b.blocked_count.cmp(&a.blocked_count)),
-        (true, true) => clients.sort_by(|a, b| a.blocked_count.cmp(&b.blocked_count)),
-    }
+    let client_count = |client: &FtlClient| {
+        let count = if blocked {
+            client.blocked_count
+        } else {
+            client.query_count
+        };
 
-    // Take into account the limit
-    if limit < clients.len() {
-        clients.truncate(limit);
-    }
+        count as usize
+    };
+    let to_reply = |client: &FtlClient| TopClientItemReply {
+        name: get_client_name(client, &strings, env, hostnames),
+        ip: client.get_ip(&strings).to_owned(),
+        count: client_count(client),
+    };
 
-    // Map the clients into the output format
-    let top_clients: Vec<TopClientItemReply> = clients
-        .into_iter()
-        .map(|client| {
-            let name = client.get_name(&strings).unwrap_or_default().to_owned();
-            let ip = client.get_ip(&strings).to_owned();
-            let count = if blocked {
-                client.blocked_count
-            } else {
-                client.query_count
-            } as usize;
-
-            TopClientItemReply { name, ip, count }
-        })
-        .collect();
+    let top_clients: Vec<TopClientItemReply> = match sort.key {
+        // Sort by count before the limit, so only the names of the clients
+        // which are shown have to be resolved. Ties are broken by the name
+        // FTL knows, or the IP.
+        TopSortKey::Count => {
+            clients.sort_by(|a, b| {
+                sort.compare(
+                    (
+                        client_count(a),
+                        a.get_name(&strings).unwrap_or_else(|| a.get_ip(&strings)),
+                    ),
+                    (
+                        client_count(b),
+                        b.get_name(&strings).unwrap_or_else(|| b.get_ip(&strings)),
+                    ),
+                )
+            });
+
+            // Take into account the limit
+            if limit < clients.len() {
+                clients.truncate(limit);
+            }
+
+            clients.into_iter().map(to_reply).collect()
+        }
+        // Sort by name after resolving the names, falling back to the IP for
+        // clients without a name
+        TopSortKey::Name => {
+            let mut top_clients: Vec<TopClientItemReply> =
+                clients.into_iter().map(to_reply).collect();
+
+            top_clients.sort_by(|a, b| {
+                sort.compare(
+                    (a.count, a.name.as_deref().unwrap_or(&a.ip)),
+                    (b.count, b.name.as_deref().unwrap_or(&b.ip)),
+                )
+            });
+
+            // Take into account the limit
+            if limit < top_clients.len() {
+                top_clients.truncate(limit);
+            }
+
+            top_clients
+        }
+    };
 
     // Output format changes when getting top blocked clients
     if blocked {
@@ -162,29 +227,27 @@ fn get_top_clients(
 /// Check the privacy level to see if clients are allowed to be shared. If not,
 /// then only return the relevant count (total or blocked queries).
 pub fn check_privacy_level_top_clients(
-    env: &Env,
+    privacy_level: FtlPrivacyLevel,
     blocked: bool,
     count: usize,
-) -> Result<Option<TopClientsReply>, Error> {
-    if FtlConfEntry::PrivacyLevel.read_as::<FtlPrivacyLevel>(&env)?
-        >= FtlPrivacyLevel::HideDomainsAndClients
-    {
+) -> Option<TopClientsReply> {
+    if !privacy_level.allows(FtlPrivacyLevel::HideDomainsAndClients) {
         return if blocked {
-            Ok(Some(TopClientsReply {
+            Some(TopClientsReply {
                 top_clients: Vec::new(),
                 total_queries: None,
                 blocked_queries: Some(count),
-            }))
+            })
         } else {
-            Ok(Some(TopClientsReply {
+            Some(TopClientsReply {
                 top_clients: Vec::new(),
                 total_queries: Some(count),
                 blocked_queries: None,
-            }))
+            })
         };
     }
 
-    Ok(None)
+    None
 }
 
 #[cfg(test)]
@@ -194,6 +257,7 @@ mod test {
         ftl::{FtlClient, FtlCounters, FtlMemory, FtlSettings},
         testing::TestBuilder,
     };
+    use rocket::http::Status;
     use std::collections::HashMap;
 
     /// There are 6 clients, two inactive, one hidden, and two with names.
@@ -242,9 +306,9 @@ mod test {
             .file(PiholeFile::FtlConfig, "")
             .expect_json(json!({
                 "top_clients": [
-                    { "name": "",        "ip": "10.1.1.4", "count": 40 },
+                    { "name": null,      "ip": "10.1.1.4", "count": 40 },
                     { "name": "client1", "ip": "10.1.1.1", "count": 30 },
-                    { "name": "",        "ip": "10.1.1.2", "count": 20 },
+                    { "name": null,      "ip": "10.1.1.2", "count": 20 },
                     { "name": "client3", "ip": "10.1.1.3", "count": 10 }
                 ],
                 "total_queries": 100
@@ -264,7 +328,7 @@ mod test {
             .expect_json(json!({
                 "top_clients": [
                     { "name": "client1", "ip": "10.1.1.1", "count": 10 },
-                    { "name": "",        "ip": "10.1.1.2", "count": 5 }
+                    { "name": null,      "ip": "10.1.1.2", "count": 5 }
                 ],
                 "blocked_queries": 15
             }))
@@ -281,7 +345,7 @@ mod test {
             .file(PiholeFile::FtlConfig, "")
             .expect_json(json!({
                 "top_clients": [
-                    { "name": "",        "ip": "10.1.1.4", "count": 40 },
+                    { "name": null,      "ip": "10.1.1.4", "count": 40 },
                     { "name": "client1", "ip": "10.1.1.1", "count": 30 }
                 ],
                 "total_queries": 100
@@ -300,9 +364,27 @@ mod test {
             .expect_json(json!({
                 "top_clients": [
                     { "name": "client3", "ip": "10.1.1.3", "count": 10 },
-                    { "name": "",        "ip": "10.1.1.2", "count": 20 },
+                    { "name": null,      "ip": "10.1.1.2", "count": 20 },
                     { "name": "client1", "ip": "10.1.1.1", "count": 30 },
-                    { "name": "",        "ip": "10.1.1.4", "count": 40 }
+                    { "name": null,      "ip": "10.1.1.4", "count": 40 }
+                ],
+                "total_queries": 100
+            }))
+            .test();
+    }
+
+    /// Privacy level 1 only hides domains, so clients are still shown
+    #[test]
+    fn privacy_hide_domains() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/top_clients?limit=2")
+            .ftl_memory(test_data())
+            .file(PiholeFile::SetupVars, "")
+            .file(PiholeFile::FtlConfig, "PRIVACYLEVEL=1")
+            .expect_json(json!({
+                "top_clients": [
+                    { "name": null,      "ip": "10.1.1.4", "count": 40 },
+                    { "name": "client1", "ip": "10.1.1.1", "count": 30 }
                 ],
                 "total_queries": 100
             }))
@@ -348,11 +430,11 @@ mod test {
             .file(PiholeFile::FtlConfig, "")
             .expect_json(json!({
                 "top_clients": [
-                    { "name": "",        "ip": "10.1.1.4", "count": 40 },
+                    { "name": null,      "ip": "10.1.1.4", "count": 40 },
                     { "name": "client1", "ip": "10.1.1.1", "count": 30 },
-                    { "name": "",        "ip": "10.1.1.2", "count": 20 },
+                    { "name": null,      "ip": "10.1.1.2", "count": 20 },
                     { "name": "client3", "ip": "10.1.1.3", "count": 10 },
-                    { "name": "",        "ip": "10.1.1.5", "count":  0 }
+                    { "name": null,      "ip": "10.1.1.5", "count":  0 }
                 ],
                 "total_queries": 100
             }))
@@ -372,11 +454,136 @@ mod test {
             .file(PiholeFile::FtlConfig, "")
             .expect_json(json!({
                 "top_clients": [
-                    { "name": "",        "ip": "10.1.1.4", "count": 40 },
+                    { "name": null,      "ip": "10.1.1.4", "count": 40 },
                     { "name": "client1", "ip": "10.1.1.1", "count": 30 }
                 ],
                 "total_queries": 100
             }))
             .test();
     }
+
+    /// Excluded clients are removed before the limit is applied, so the
+    /// limit is still filled with visible clients
+    #[test]
+    fn excluded_clients_limit() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/top_clients?limit=2")
+            .ftl_memory(test_data())
+            .file(PiholeFile::SetupVars, "API_EXCLUDE_CLIENTS=10.1.1.4")
+            .file(PiholeFile::FtlConfig, "")
+            .expect_json(json!({
+                "top_clients": [
+                    { "name": "client1", "ip": "10.1.1.1", "count": 30 },
+                    { "name": null,      "ip": "10.1.1.2", "count": 20 }
+                ],
+                "total_queries": 100
+            }))
+            .test();
+    }
+
+    /// Clients with the same count are sorted by name, or IP if they have no
+    /// name
+    #[test]
+    fn count_ties() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/top_clients?blocked=true&inactive=true")
+            .ftl_memory(test_data())
+            .file(PiholeFile::SetupVars, "")
+            .file(PiholeFile::FtlConfig, "")
+            .expect_json(json!({
+                "top_clients": [
+                    { "name": "client1", "ip": "10.1.1.1", "count": 10 },
+                    { "name": null,      "ip": "10.1.1.2", "count": 5 },
+                    { "name": null,      "ip": "10.1.1.4", "count": 0 },
+                    { "name": null,      "ip": "10.1.1.5", "count": 0 },
+                    { "name": "client3", "ip": "10.1.1.3", "count": 0 }
+                ],
+                "blocked_queries": 15
+            }))
+            .test();
+    }
+
+    /// Ties are still sorted by name ascending when the counts are ascending
+    #[test]
+    fn count_ties_ascending() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/top_clients?blocked=true&inactive=true&order=asc")
+            .ftl_memory(test_data())
+            .file(PiholeFile::SetupVars, "")
+            .file(PiholeFile::FtlConfig, "")
+            .expect_json(json!({
+                "top_clients": [
+                    { "name": null,      "ip": "10.1.1.4", "count": 0 },
+                    { "name": null,      "ip": "10.1.1.5", "count": 0 },
+                    { "name": "client3", "ip": "10.1.1.3", "count": 0 },
+                    { "name": null,      "ip": "10.1.1.2", "count": 5 },
+                    { "name": "client1", "ip": "10.1.1.1", "count": 10 }
+                ],
+                "blocked_queries": 15
+            }))
+            .test();
+    }
+
+    /// Clients are sorted by name before the limit is applied, using the IP
+    /// of clients without a name
+    #[test]
+    fn sort_by_name() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/top_clients?sort=name&limit=3")
+            .ftl_memory(test_data())
+            .file(PiholeFile::SetupVars, "")
+            .file(PiholeFile::FtlConfig, "")
+            .expect_json(json!({
+                "top_clients": [
+                    { "name": null,      "ip": "10.1.1.2", "count": 20 },
+                    { "name": null,      "ip": "10.1.1.4", "count": 40 },
+                    { "name": "client1", "ip": "10.1.1.1", "count": 30 }
+                ],
+                "total_queries": 100
+            }))
+            .test();
+    }
+
+    /// Clients can be sorted by name in descending order
+    #[test]
+    fn sort_by_name_descending() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/top_clients?sort=name&order=desc")
+            .ftl_memory(test_data())
+            .file(PiholeFile::SetupVars, "")
+            .file(PiholeFile::FtlConfig, "")
+            .expect_json(json!({
+                "top_clients": [
+                    { "name": "client3", "ip": "10.1.1.3", "count": 10 },
+                    { "name": "client1", "ip": "10.1.1.1", "count": 30 },
+                    { "name": null,      "ip": "10.1.1.4", "count": 40 },
+                    { "name": null,      "ip": "10.1.1.2", "count": 20 }
+                ],
+                "total_queries": 100
+            }))
+            .test();
+    }
+
+    /// An unknown order is rejected with the valid orders
+    #[test]
+    fn invalid_order() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/top_clients?order=up")
+            .ftl_memory(test_data())
+            .file(PiholeFile::SetupVars, "")
+            .file(PiholeFile::FtlConfig, "")
+            .expect_status(Status::BadRequest)
+            .expect_json(json!({
+                "error": {
+                    "key": "invalid_parameter",
+                    "message": "Invalid value up for parameter order",
+                    "data": {
+                        "parameter": "order",
+                        "value": "up",
+                        "valid": ["asc", "desc"]
+                    }
+                }
+            }))
+            .test();
+    }
 }
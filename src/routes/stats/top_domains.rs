@@ -9,14 +9,19 @@
 // Please see LICENSE file for your rights under this license.
 
 use crate::{
-    env::Env,
-    ftl::{FtlDomain, FtlMemory},
+    ftl::{FtlDomain, FtlMemory, FtlRedactor},
     routes::{
         auth::User,
-        stats::common::{remove_excluded_domains, remove_hidden_domains},
+        stats::{common::remove_excluded_domains, top_sort::TopSort},
     },
-    services::{domain_audit::DomainAuditRepository, PiholeModule},
-    settings::{ConfigEntry, FtlConfEntry, FtlPrivacyLevel, SetupVarsEntry},
+    services::{
+        domain_audit::DomainAuditRepository,
+        exclusions::{ExclusionService, Exclusions},
+        privacy::PrivacyService,
+        settings::SettingsRepository,
+        PiholeModule,
+    },
+    settings::{FtlPrivacyLevel, SetupVarsEntry},
     util::{reply_result, Error, Reply},
 };
 use rocket::State;
@@ -30,11 +35,23 @@ pub use top_domains as route;
 pub fn top_domains(
     _auth: User,
     ftl_memory: &State<FtlMemory>,
-    env: Inject<PiholeModule, Env>,
+    settings: Inject<PiholeModule, dyn SettingsRepository>,
+    privacy: Inject<PiholeModule, dyn PrivacyService>,
+    exclusions: Inject<PiholeModule, dyn ExclusionService>,
     params: TopDomainParams,
     domain_audit: InjectProvided<PiholeModule, dyn DomainAuditRepository>,
 ) -> Reply {
-    reply_result(get_top_domains(ftl_memory, &env, params, &*domain_audit))
+    let redactor = privacy.redactor()?;
+    let exclusions = exclusions.exclusions()?;
+
+    reply_result(get_top_domains(
+        ftl_memory,
+        &*settings,
+        redactor,
+        &exclusions,
+        params,
+        &*domain_audit,
+    ))
 }
 
 /// Represents the possible GET parameters for top (blocked) domains requests
@@ -44,6 +61,10 @@ pub struct TopDomainParams {
     pub audit: Option<bool>,
     pub ascending: Option<bool>,
     pub blocked: Option<bool>,
+    /// Sort by `count` (default) or `name`
+    pub sort: Option<String>,
+    /// Sort in `asc` or `desc` order, overriding `ascending`
+    pub order: Option<String>,
 }
 
 /// Represents the reply structure for top (blocked) domains
@@ -66,23 +87,29 @@ pub struct TopDomainItemReply {
 }
 
 /// Get the top domains (blocked or not)
-fn get_top_domains(
+pub fn get_top_domains(
     ftl_memory: &FtlMemory,
-    env: &Env,
+    settings: &dyn SettingsRepository,
+    redactor: FtlRedactor,
+    exclusions: &Exclusions,
     params: TopDomainParams,
     domain_audit: &dyn DomainAuditRepository,
 ) -> Result<TopDomainsReply, Error> {
     // Resolve the parameters
     let limit = params.limit.unwrap_or(10);
     let audit = params.audit.unwrap_or(false);
-    let ascending = params.ascending.unwrap_or(false);
     let blocked = params.blocked.unwrap_or(false);
+    let sort = TopSort::from_params(
+        params.sort.as_deref(),
+        params.order.as_deref(),
+        params.ascending,
+    )?;
 
     let lock = ftl_memory.lock()?;
     let counters = ftl_memory.counters(&lock)?;
 
     // Check if we are allowed to share the top domains
-    if let Some(reply) = check_query_log_show_top_domains(env, blocked)? {
+    if let Some(reply) = check_query_log_show_top_domains(settings, blocked)? {
         // We can not share any of the domains, so use the reply returned by the
         // function
         return Ok(reply);
@@ -95,7 +122,7 @@ fn get_top_domains(
     } as usize;
 
     // Check if the domain details are private
-    if let Some(reply) = check_privacy_level_top_domains(env, blocked, total_count)? {
+    if let Some(reply) = check_privacy_level_top_domains(redactor.level(), blocked, total_count) {
         // We can not share any of the domains, so use the reply returned by the
         // function
         return Ok(reply);
@@ -110,9 +137,9 @@ fn get_top_domains(
         .take(counters.total_domains as usize)
         .collect();
 
-    // Remove excluded and hidden domains
-    remove_excluded_domains(&mut domains, env, &strings)?;
-    remove_hidden_domains(&mut domains, &strings);
+    // Remove excluded and private domains
+    remove_excluded_domains(&mut domains, exclusions, &strings);
+    redactor.retain_domains(&mut domains, &strings);
 
     // Remove domains with a count of 0
     if blocked {
@@ -129,17 +156,24 @@ fn get_top_domains(
         domains.retain(|domain| !audited_domains.contains(domain.get_domain(&strings)));
     }
 
-    // Sort the domains (descending by default)
-    match (ascending, blocked) {
-        (false, false) => domains.sort_by(|a, b| {
-            (b.query_count - b.blocked_count).cmp(&(a.query_count - a.blocked_count))
-        }),
-        (true, false) => domains.sort_by(|a, b| {
-            (a.query_count - a.blocked_count).cmp(&(b.query_count - b.blocked_count))
-        }),
-        (false, true) => domains.sort_by(|a, b| b.blocked_count.cmp(&a.blocked_count)),
-        (true, true) => domains.sort_by(|a, b| a.blocked_count.cmp(&b.blocked_count)),
-    }
+    let domain_count = |domain: &FtlDomain| {
+        let count = if blocked {
+            domain.blocked_count
+        } else {
+            domain.query_count - domain.blocked_count
+        };
+
+        count as usize
+    };
+
+    // Sort the domains (descending by count by default). The domain names do
+    // not need to be resolved, so both sorts happen before the limit.
+    domains.sort_by(|a, b| {
+        sort.compare(
+            (domain_count(a), a.get_domain(&strings)),
+            (domain_count(b), b.get_domain(&strings)),
+        )
+    });
 
     // Take into account the limit
     if limit < domains.len() {
@@ -149,18 +183,9 @@ fn get_top_domains(
     // Map the domains into the output format
     let top_domains: Vec<TopDomainItemReply> = domains
         .iter()
-        .map(|domain| {
-            let name = domain.get_domain(&strings).to_owned();
-            let count = if blocked {
-                domain.blocked_count
-            } else {
-                domain.query_count - domain.blocked_count
-            } as usize;
-
-            TopDomainItemReply {
-                domain: name,
-                count,
-            }
+        .map(|domain| TopDomainItemReply {
+            domain: domain.get_domain(&strings).to_owned(),
+            count: domain_count(domain),
         })
         .collect();
 
@@ -185,10 +210,10 @@ fn get_top_domains(
 /// equals `permittedonly` but top blocked domains are requested) then a reply
 /// is returned which should be used as the endpoint reply.
 pub fn check_query_log_show_top_domains(
-    env: &Env,
+    settings: &dyn SettingsRepository,
     blocked: bool,
 ) -> Result<Option<TopDomainsReply>, Error> {
-    let display_setting = SetupVarsEntry::ApiQueryLogShow.read(env)?;
+    let display_setting = settings.get(SetupVarsEntry::ApiQueryLogShow)?;
 
     if display_setting == "nothing"
         || (display_setting == "permittedonly" && blocked)
@@ -215,28 +240,27 @@ pub fn check_query_log_show_top_domains(
 /// Check the privacy level to see if domains are allowed to be shared. If not,
 /// then only return the relevant count (total or blocked queries).
 pub fn check_privacy_level_top_domains(
-    env: &Env,
+    privacy_level: FtlPrivacyLevel,
     blocked: bool,
     count: usize,
-) -> Result<Option<TopDomainsReply>, Error> {
-    if FtlConfEntry::PrivacyLevel.read_as::<FtlPrivacyLevel>(&env)? >= FtlPrivacyLevel::HideDomains
-    {
+) -> Option<TopDomainsReply> {
+    if !privacy_level.allows(FtlPrivacyLevel::HideDomains) {
         if blocked {
-            return Ok(Some(TopDomainsReply {
+            return Some(TopDomainsReply {
                 top_domains: Vec::new(),
                 total_queries: None,
                 blocked_queries: Some(count),
-            }));
+            });
         } else {
-            return Ok(Some(TopDomainsReply {
+            return Some(TopDomainsReply {
                 top_domains: Vec::new(),
                 total_queries: Some(count),
                 blocked_queries: None,
-            }));
+            });
         }
     }
 
-    Ok(None)
+    None
 }
 
 #[cfg(test)]
@@ -323,6 +347,80 @@ mod test {
             .test();
     }
 
+    /// The limit also applies to blocked domains, keeping the most blocked
+    #[test]
+    fn blocked_limit() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/top_domains?blocked=true&limit=1")
+            .ftl_memory(test_data())
+            .mock_provider::<dyn DomainAuditRepository>(Box::new(|_| {
+                Ok(Box::new(MockDomainAuditRepository::new()))
+            }))
+            .file(PiholeFile::SetupVars, "")
+            .file(PiholeFile::FtlConfig, "")
+            .expect_json(json!({
+                "top_domains": [
+                    { "domain": "example.com", "count": 10 }
+                ],
+                "blocked_queries": 21
+            }))
+            .test();
+    }
+
+    /// A limit of zero shows no domains, but still shows the total
+    #[test]
+    fn limit_zero() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/top_domains?limit=0")
+            .ftl_memory(test_data())
+            .mock_provider::<dyn DomainAuditRepository>(Box::new(|_| {
+                Ok(Box::new(MockDomainAuditRepository::new()))
+            }))
+            .file(PiholeFile::SetupVars, "")
+            .file(PiholeFile::FtlConfig, "")
+            .expect_json(json!({
+                "top_domains": [],
+                "total_queries": 39
+            }))
+            .test();
+    }
+
+    /// If domains are hidden by the privacy level, only the total is shown
+    #[test]
+    fn privacy_level_hides_domains() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/top_domains")
+            .ftl_memory(test_data())
+            .mock_provider::<dyn DomainAuditRepository>(Box::new(|_| {
+                Ok(Box::new(MockDomainAuditRepository::new()))
+            }))
+            .file(PiholeFile::SetupVars, "")
+            .file(PiholeFile::FtlConfig, "PRIVACYLEVEL=1")
+            .expect_json(json!({
+                "top_domains": [],
+                "total_queries": 39
+            }))
+            .test();
+    }
+
+    /// If only blocked queries may be shown, no permitted domains are shown
+    #[test]
+    fn query_log_show_blocked_only() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/top_domains")
+            .ftl_memory(test_data())
+            .mock_provider::<dyn DomainAuditRepository>(Box::new(|_| {
+                Ok(Box::new(MockDomainAuditRepository::new()))
+            }))
+            .file(PiholeFile::SetupVars, "API_QUERY_LOG_SHOW=blockedonly")
+            .file(PiholeFile::FtlConfig, "")
+            .expect_json(json!({
+                "top_domains": [],
+                "total_queries": 0
+            }))
+            .test();
+    }
+
     /// Show blocked domains, but no hidden, inactive, or completely unblocked
     /// domains
     #[test]
@@ -413,4 +511,46 @@ mod test {
             }))
             .test();
     }
+
+    /// Domains can be sorted by name, ascending by default
+    #[test]
+    fn sort_by_name() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/top_domains?sort=name")
+            .ftl_memory(test_data())
+            .mock_provider::<dyn DomainAuditRepository>(Box::new(|_| {
+                Ok(Box::new(MockDomainAuditRepository::new()))
+            }))
+            .file(PiholeFile::SetupVars, "")
+            .file(PiholeFile::FtlConfig, "")
+            .expect_json(json!({
+                "top_domains": [
+                    { "domain": "example.net", "count": 1 },
+                    { "domain": "github.com", "count": 20 }
+                ],
+                "total_queries": 39
+            }))
+            .test();
+    }
+
+    /// Blocked domains can be sorted by name in descending order, and the
+    /// limit is applied after sorting
+    #[test]
+    fn blocked_sort_by_name_descending() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/top_domains?blocked=true&sort=name&order=desc&limit=1")
+            .ftl_memory(test_data())
+            .mock_provider::<dyn DomainAuditRepository>(Box::new(|_| {
+                Ok(Box::new(MockDomainAuditRepository::new()))
+            }))
+            .file(PiholeFile::SetupVars, "")
+            .file(PiholeFile::FtlConfig, "")
+            .expect_json(json!({
+                "top_domains": [
+                    { "domain": "example.net", "count": 9 }
+                ],
+                "blocked_queries": 21
+            }))
+            .test();
+    }
 }
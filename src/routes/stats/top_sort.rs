@@ -0,0 +1,181 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Top List Sorting
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::util::{Error, ErrorKind};
+use std::cmp::Ordering;
+
+/// The values accepted by the `sort` parameter
+const SORT_KEYS: &[&str] = &["count", "name"];
+
+/// The values accepted by the `order` parameter
+const SORT_ORDERS: &[&str] = &["asc", "desc"];
+
+/// The value which top list items are sorted by
+#[cfg_attr(test, derive(Debug))]
+#[derive(Copy, Clone, PartialEq)]
+pub enum TopSortKey {
+    Count,
+    Name,
+}
+
+/// The direction top list items are sorted in
+#[cfg_attr(test, derive(Debug))]
+#[derive(Copy, Clone, PartialEq)]
+pub enum TopSortOrder {
+    Asc,
+    Desc,
+}
+
+/// How to sort the items of a top list
+#[cfg_attr(test, derive(Debug))]
+#[derive(Copy, Clone, PartialEq)]
+pub struct TopSort {
+    pub key: TopSortKey,
+    pub order: TopSortOrder,
+}
+
+impl TopSort {
+    /// Parse the `sort` and `order` parameters. Items are sorted by count by
+    /// default. Without an `order` parameter, the older `ascending` parameter
+    /// is used, and otherwise counts are sorted descending and names
+    /// ascending.
+    pub fn from_params(
+        sort: Option<&str>,
+        order: Option<&str>,
+        ascending: Option<bool>,
+    ) -> Result<Self, Error> {
+        let key = match sort {
+            None | Some("count") => TopSortKey::Count,
+            Some("name") => TopSortKey::Name,
+            Some(sort) => return Err(invalid_parameter("sort", sort, SORT_KEYS)),
+        };
+
+        let order = match (order, ascending) {
+            (Some("asc"), _) => TopSortOrder::Asc,
+            (Some("desc"), _) => TopSortOrder::Desc,
+            (Some(order), _) => return Err(invalid_parameter("order", order, SORT_ORDERS)),
+            (None, Some(true)) => TopSortOrder::Asc,
+            (None, Some(false)) => TopSortOrder::Desc,
+            (None, None) => match key {
+                TopSortKey::Count => TopSortOrder::Desc,
+                TopSortKey::Name => TopSortOrder::Asc,
+            },
+        };
+
+        Ok(TopSort { key, order })
+    }
+
+    /// Compare two items by their count and name. Items with the same count
+    /// are always ordered by name ascending, so the results are deterministic.
+    pub fn compare(self, a: (usize, &str), b: (usize, &str)) -> Ordering {
+        let (a_count, a_name) = a;
+        let (b_count, b_name) = b;
+
+        let ordering = match self.key {
+            TopSortKey::Count => a_count.cmp(&b_count),
+            TopSortKey::Name => a_name.cmp(b_name),
+        };
+        let ordering = match self.order {
+            TopSortOrder::Asc => ordering,
+            TopSortOrder::Desc => ordering.reverse(),
+        };
+
+        ordering.then_with(|| a_name.cmp(b_name))
+    }
+}
+
+/// Create the error for a parameter value which is not one of the valid
+/// values
+fn invalid_parameter(parameter: &str, value: &str, valid: &[&str]) -> Error {
+    Error::from(ErrorKind::InvalidParameter(
+        parameter.to_owned(),
+        value.to_owned(),
+        valid.iter().map(|value| (*value).to_owned()).collect(),
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{TopSort, TopSortKey, TopSortOrder};
+    use crate::util::ErrorKind;
+    use std::cmp::Ordering;
+
+    /// The default order depends on the sort key, and an explicit order
+    /// overrides `ascending`
+    #[test]
+    fn parse() {
+        assert_eq!(
+            TopSort::from_params(None, None, None).unwrap(),
+            TopSort {
+                key: TopSortKey::Count,
+                order: TopSortOrder::Desc
+            }
+        );
+        assert_eq!(
+            TopSort::from_params(Some("name"), None, None).unwrap(),
+            TopSort {
+                key: TopSortKey::Name,
+                order: TopSortOrder::Asc
+            }
+        );
+        assert_eq!(
+            TopSort::from_params(Some("count"), None, Some(true)).unwrap(),
+            TopSort {
+                key: TopSortKey::Count,
+                order: TopSortOrder::Asc
+            }
+        );
+        assert_eq!(
+            TopSort::from_params(None, Some("desc"), Some(true)).unwrap(),
+            TopSort {
+                key: TopSortKey::Count,
+                order: TopSortOrder::Desc
+            }
+        );
+    }
+
+    /// Unknown values are rejected along with the valid values
+    #[test]
+    fn invalid() {
+        assert_eq!(
+            TopSort::from_params(Some("ip"), None, None)
+                .unwrap_err()
+                .kind(),
+            ErrorKind::InvalidParameter(
+                "sort".to_owned(),
+                "ip".to_owned(),
+                vec!["count".to_owned(), "name".to_owned()]
+            )
+        );
+        assert_eq!(
+            TopSort::from_params(Some("name"), Some("up"), None)
+                .unwrap_err()
+                .kind(),
+            ErrorKind::InvalidParameter(
+                "order".to_owned(),
+                "up".to_owned(),
+                vec!["asc".to_owned(), "desc".to_owned()]
+            )
+        );
+    }
+
+    /// Ties in the count are broken by name ascending, in either order
+    #[test]
+    fn ties() {
+        for &order in &[TopSortOrder::Asc, TopSortOrder::Desc] {
+            let sort = TopSort {
+                key: TopSortKey::Count,
+                order,
+            };
+
+            assert_eq!(sort.compare((5, "a.com"), (5, "b.com")), Ordering::Less);
+        }
+    }
+}
@@ -0,0 +1,285 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Upstream Response Times Endpoint
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    ftl::{FtlMemory, FtlQueryStatus},
+    routes::auth::User,
+    util::{reply_result, Error, Reply},
+};
+use rocket::State;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub use upstream_response_times as route;
+
+/// The default window of queries to use, in minutes
+const DEFAULT_WINDOW: u64 = 60;
+
+/// The longest window of queries which can be used (one day), in minutes
+const MAX_WINDOW: u64 = 1440;
+
+/// Get the response time statistics of each upstream over the last `minutes`
+/// minutes
+#[get("/stats/upstreams/response_times?<minutes>")]
+pub fn upstream_response_times(
+    _auth: User,
+    ftl_memory: &State<FtlMemory>,
+    minutes: Option<u64>,
+) -> Reply {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Time went backwards")
+        .as_secs();
+
+    reply_result(get_response_times(ftl_memory, minutes, now))
+}
+
+/// Represents the reply structure for the upstream response times
+#[derive(Serialize)]
+#[cfg_attr(test, derive(Debug, PartialEq))]
+pub struct ResponseTimesReply {
+    /// The window of queries which was used, in minutes
+    pub minutes: u64,
+    pub upstreams: Vec<UpstreamResponseTimes>,
+}
+
+/// Represents the response time statistics of an upstream, in milliseconds.
+/// They are `None` if the upstream did not answer any queries in the window.
+#[derive(Serialize)]
+#[cfg_attr(test, derive(Debug, PartialEq))]
+pub struct UpstreamResponseTimes {
+    pub name: String,
+    pub ip: String,
+    /// The number of replies the statistics were calculated from
+    pub count: usize,
+    pub min: Option<f64>,
+    pub median: Option<f64>,
+    pub p95: Option<f64>,
+    pub average: Option<f64>,
+}
+
+/// Get the response time statistics of each upstream. The recent queries are
+/// scanned once to group the response times by upstream, and then each
+/// upstream's response times are sorted once to find the percentiles.
+fn get_response_times(
+    ftl_memory: &FtlMemory,
+    minutes: Option<u64>,
+    now: u64,
+) -> Result<ResponseTimesReply, Error> {
+    let minutes = minutes.unwrap_or(DEFAULT_WINDOW).clamp(1, MAX_WINDOW);
+    let window_start = now.saturating_sub(minutes * 60);
+
+    let lock = ftl_memory.lock()?;
+    let counters = ftl_memory.counters(&lock)?;
+    let queries = ftl_memory.queries(&lock)?;
+    let upstreams = ftl_memory.upstreams(&lock)?;
+    let strings = ftl_memory.strings(&lock)?;
+
+    let upstreams = &upstreams[..upstreams.len().min(counters.total_upstreams as usize)];
+    let mut response_times: Vec<Vec<u64>> = vec![Vec::new(); upstreams.len()];
+
+    // The queries are stored in order, so only the tail inside the window is
+    // scanned
+    let recent_queries = queries
+        .iter()
+        .take(counters.total_queries as usize)
+        .rev()
+        .take_while(|query| query.timestamp as u64 > window_start);
+
+    for query in recent_queries {
+        if query.status != FtlQueryStatus::Forward {
+            continue;
+        }
+
+        if let (Some(times), Some(reply_time)) = (
+            response_times.get_mut(query.upstream_id as usize),
+            query.reply_time(),
+        ) {
+            times.push(reply_time);
+        }
+    }
+
+    let upstreams = upstreams
+        .iter()
+        .zip(response_times)
+        .map(|(upstream, mut times)| {
+            times.sort_unstable();
+
+            let average = if times.is_empty() {
+                None
+            } else {
+                Some(times.iter().sum::<u64>() as f64 / times.len() as f64)
+            };
+
+            UpstreamResponseTimes {
+                name: upstream.get_name(&strings).unwrap_or_default().to_owned(),
+                ip: upstream.get_ip(&strings).to_owned(),
+                count: times.len(),
+                min: times.first().copied().map(to_milliseconds),
+                median: percentile(&times, 50).map(to_milliseconds),
+                p95: percentile(&times, 95).map(to_milliseconds),
+                average: average.map(|average| average / 10.0),
+            }
+        })
+        .collect();
+
+    Ok(ResponseTimesReply { minutes, upstreams })
+}
+
+/// Get the `percent` percentile of the sorted values, using the nearest-rank
+/// method. There is no percentile if there are no values.
+fn percentile(sorted: &[u64], percent: usize) -> Option<u64> {
+    if sorted.is_empty() {
+        return None;
+    }
+
+    // The rank is the percentage of the values, rounded up
+    let rank = (percent * sorted.len() + 99) / 100;
+
+    Some(sorted[rank.max(1) - 1])
+}
+
+/// Convert a response time from FTL's units of 1/10 ms to milliseconds
+fn to_milliseconds(response_time: u64) -> f64 {
+    response_time as f64 / 10.0
+}
+
+#[cfg(test)]
+mod test {
+    use super::percentile;
+    use crate::{
+        ftl::{FtlMemory, FtlQueryStatus},
+        testing::{TestBuilder, TestFtlMemoryBuilder},
+    };
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    /// One upstream with five replies in the last hour, and one which only
+    /// answered before the window
+    fn test_memory() -> FtlMemory {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as usize;
+        let mut builder = TestFtlMemoryBuilder::new();
+        let client = builder.unnamed_client("10.1.1.1");
+        let google = builder.upstream("8.8.8.8", "google-public-dns-a.google.com");
+        let cloudflare = builder.upstream("1.1.1.1", "one.one.one.one");
+
+        let old = builder.query(
+            now - 7200,
+            "example.com",
+            client,
+            FtlQueryStatus::Forward,
+            Some(cloudflare),
+        );
+        builder.response_time(old, 50);
+
+        for (i, &response_time) in [100, 300, 200, 400, 1000].iter().enumerate() {
+            let query = builder.query(
+                now - 600 + i,
+                "example.com",
+                client,
+                FtlQueryStatus::Forward,
+                Some(google),
+            );
+            builder.response_time(query, response_time);
+        }
+
+        // Queries which were not forwarded, or had no reply, are ignored
+        builder.query(now - 60, "example.com", client, FtlQueryStatus::Cache, None);
+        let unanswered = builder.query(
+            now - 30,
+            "example.com",
+            client,
+            FtlQueryStatus::Forward,
+            Some(google),
+        );
+        builder.response_time(unanswered, 0);
+
+        builder.build()
+    }
+
+    /// The statistics are calculated from the replies in the window, and
+    /// upstreams without replies have no statistics
+    #[test]
+    fn response_times() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/upstreams/response_times")
+            .ftl_memory(test_memory())
+            .expect_json(json!({
+                "minutes": 60,
+                "upstreams": [
+                    {
+                        "name": "google-public-dns-a.google.com",
+                        "ip": "8.8.8.8",
+                        "count": 5,
+                        "min": 10.0,
+                        "median": 30.0,
+                        "p95": 100.0,
+                        "average": 40.0
+                    },
+                    {
+                        "name": "one.one.one.one",
+                        "ip": "1.1.1.1",
+                        "count": 0,
+                        "min": null,
+                        "median": null,
+                        "p95": null,
+                        "average": null
+                    }
+                ]
+            }))
+            .test();
+    }
+
+    /// A longer window includes older replies
+    #[test]
+    fn longer_window() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/upstreams/response_times?minutes=180")
+            .ftl_memory(test_memory())
+            .expect_json(json!({
+                "minutes": 180,
+                "upstreams": [
+                    {
+                        "name": "google-public-dns-a.google.com",
+                        "ip": "8.8.8.8",
+                        "count": 5,
+                        "min": 10.0,
+                        "median": 30.0,
+                        "p95": 100.0,
+                        "average": 40.0
+                    },
+                    {
+                        "name": "one.one.one.one",
+                        "ip": "1.1.1.1",
+                        "count": 1,
+                        "min": 5.0,
+                        "median": 5.0,
+                        "p95": 5.0,
+                        "average": 5.0
+                    }
+                ]
+            }))
+            .test();
+    }
+
+    /// Percentiles use the nearest rank
+    #[test]
+    fn nearest_rank() {
+        let values: Vec<u64> = (1..=20).collect();
+
+        assert_eq!(percentile(&values, 50), Some(10));
+        assert_eq!(percentile(&values, 95), Some(19));
+        assert_eq!(percentile(&values, 100), Some(20));
+        assert_eq!(percentile(&values, 0), Some(1));
+        assert_eq!(percentile(&[7], 95), Some(7));
+        assert_eq!(percentile(&[], 50), None);
+    }
+}
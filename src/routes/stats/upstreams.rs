@@ -17,13 +17,16 @@ use rocket::State;
 
 pub use upstreams as route;
 
-/// Get the upstreams
-#[get("/stats/upstreams")]
-pub fn upstreams(_auth: User, ftl_memory: &State<FtlMemory>) -> Reply {
+/// Get the upstreams. The blocklist and cache are included as
+/// pseudo-upstreams, so the percentages add up to 100%. If there is a limit,
+/// it only applies to the real upstreams.
+#[get("/stats/upstreams?<limit>")]
+pub fn upstreams(_auth: User, ftl_memory: &State<FtlMemory>, limit: Option<usize>) -> Reply {
     let lock = ftl_memory.lock()?;
     let ftl_upstreams = ftl_memory.upstreams(&lock)?;
     let strings = ftl_memory.strings(&lock)?;
     let counters = ftl_memory.counters(&lock)?;
+    let total_queries = counters.total_queries as usize;
 
     // Get an array of valid upstream references (FTL allocates more than it uses)
     let mut ftl_upstreams: Vec<&FtlUpstream> = ftl_upstreams
@@ -36,36 +39,41 @@ pub fn upstreams(_auth: User, ftl_memory: &State<FtlMemory>) -> Reply {
     // Sort the upstreams (descending by count)
     ftl_upstreams.sort_by(|a, b| b.query_count.cmp(&a.query_count));
 
+    // Take into account the limit
+    if let Some(limit) = limit {
+        if limit < ftl_upstreams.len() {
+            ftl_upstreams.truncate(limit);
+        }
+    }
+
     let mut upstreams: Vec<UpstreamItemReply> = Vec::with_capacity(ftl_upstreams.len() + 2);
 
     // Add blocklist and cache upstreams
-    upstreams.push(UpstreamItemReply {
-        name: "blocklist".to_owned(),
-        ip: "blocklist".to_owned(),
-        count: counters.blocked_queries as usize,
-    });
-    upstreams.push(UpstreamItemReply {
-        name: "cache".to_owned(),
-        ip: "cache".to_owned(),
-        count: counters.cached_queries as usize,
-    });
+    upstreams.push(UpstreamItemReply::new(
+        "blocklist".to_owned(),
+        "blocklist".to_owned(),
+        counters.blocked_queries as usize,
+        total_queries,
+    ));
+    upstreams.push(UpstreamItemReply::new(
+        "cache".to_owned(),
+        "cache".to_owned(),
+        counters.cached_queries as usize,
+        total_queries,
+    ));
 
     // Map the upstreams into the output format
     upstreams.extend(ftl_upstreams.into_iter().map(|upstream| {
         let ip = upstream.get_ip(&strings).to_owned();
         let name = upstream.get_name(&strings).unwrap_or_default().to_owned();
 
-        UpstreamItemReply {
-            name,
-            ip,
-            count: upstream.query_count as usize,
-        }
+        UpstreamItemReply::new(name, ip, upstream.query_count as usize, total_queries)
     }));
 
     reply_data(UpstreamsReply {
         upstreams,
         forwarded_queries: counters.forwarded_queries as usize,
-        total_queries: counters.total_queries as usize,
+        total_queries,
     })
 }
 
@@ -76,6 +84,27 @@ pub struct UpstreamItemReply {
     pub name: String,
     pub ip: String,
     pub count: usize,
+    /// The percentage of all queries which went to this upstream
+    pub percentage: f64,
+}
+
+impl UpstreamItemReply {
+    /// Create the reply for an upstream, calculating its share of the total
+    /// queries
+    pub fn new(name: String, ip: String, count: usize, total_queries: usize) -> Self {
+        let percentage = if total_queries == 0 {
+            0.0
+        } else {
+            (count * 100) as f64 / total_queries as f64
+        };
+
+        UpstreamItemReply {
+            name,
+            ip,
+            count,
+            percentage,
+        }
+    }
 }
 
 /// Represents the reply structure for upstreams endpoints
@@ -137,11 +166,21 @@ mod test {
             })
             .expect_json(json!({
                 "upstreams": [
-                    { "name": "blocklist", "ip": "blocklist", "count": 0 },
-                    { "name": "cache", "ip": "cache", "count": 0 },
-                    { "name": "google-public-dns-a.google.com", "ip": "8.8.8.8", "count": 10 },
-                    { "name": "google-public-dns-b.google.com", "ip": "8.8.4.4", "count": 4 },
-                    { "name": "", "ip": "1.1.1.1", "count": 3 }
+                    { "name": "blocklist", "ip": "blocklist", "count": 0, "percentage": 0.0 },
+                    { "name": "cache", "ip": "cache", "count": 0, "percentage": 0.0 },
+                    {
+                        "name": "google-public-dns-a.google.com",
+                        "ip": "8.8.8.8",
+                        "count": 10,
+                        "percentage": 1000.0 / 17.0
+                    },
+                    {
+                        "name": "google-public-dns-b.google.com",
+                        "ip": "8.8.4.4",
+                        "count": 4,
+                        "percentage": 400.0 / 17.0
+                    },
+                    { "name": "", "ip": "1.1.1.1", "count": 3, "percentage": 300.0 / 17.0 }
                 ],
                 "total_queries": 17,
                 "forwarded_queries": 17
@@ -176,15 +215,69 @@ mod test {
             })
             .expect_json(json!({
                 "upstreams": [
-                    { "name": "blocklist", "ip": "blocklist", "count": 1 },
-                    { "name": "cache", "ip": "cache", "count": 1 },
-                    { "name": "google-public-dns-a.google.com", "ip": "8.8.8.8", "count": 10 },
-                    { "name": "google-public-dns-b.google.com", "ip": "8.8.4.4", "count": 4 },
-                    { "name": "", "ip": "1.1.1.1", "count": 3 }
+                    { "name": "blocklist", "ip": "blocklist", "count": 1, "percentage": 100.0 / 19.0 },
+                    { "name": "cache", "ip": "cache", "count": 1, "percentage": 100.0 / 19.0 },
+                    {
+                        "name": "google-public-dns-a.google.com",
+                        "ip": "8.8.8.8",
+                        "count": 10,
+                        "percentage": 1000.0 / 19.0
+                    },
+                    {
+                        "name": "google-public-dns-b.google.com",
+                        "ip": "8.8.4.4",
+                        "count": 4,
+                        "percentage": 400.0 / 19.0
+                    },
+                    { "name": "", "ip": "1.1.1.1", "count": 3, "percentage": 300.0 / 19.0 }
                 ],
                 "total_queries": 19,
                 "forwarded_queries": 17
             }))
             .test();
     }
+
+    /// Upstreams without queries are not shown, and the limit only applies
+    /// to the real upstreams
+    #[test]
+    fn limit_and_zero_count() {
+        let (mut upstreams, mut strings) = test_upstream_data();
+        strings.insert(6, "9.9.9.9".to_owned());
+        upstreams.push(FtlUpstream::new(0, 0, 6, None));
+
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/upstreams?limit=1")
+            .ftl_memory(FtlMemory::Test {
+                upstreams,
+                strings,
+                counters: FtlCounters {
+                    total_upstreams: 4,
+                    total_queries: 20,
+                    forwarded_queries: 17,
+                    blocked_queries: 2,
+                    cached_queries: 1,
+                    ..FtlCounters::default()
+                },
+                clients: Vec::new(),
+                domains: Vec::new(),
+                over_time: Vec::new(),
+                queries: Vec::new(),
+                settings: FtlSettings::default(),
+            })
+            .expect_json(json!({
+                "upstreams": [
+                    { "name": "blocklist", "ip": "blocklist", "count": 2, "percentage": 10.0 },
+                    { "name": "cache", "ip": "cache", "count": 1, "percentage": 5.0 },
+                    {
+                        "name": "google-public-dns-a.google.com",
+                        "ip": "8.8.8.8",
+                        "count": 10,
+                        "percentage": 50.0
+                    }
+                ],
+                "total_queries": 20,
+                "forwarded_queries": 17
+            }))
+            .test();
+    }
 }
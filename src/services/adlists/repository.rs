@@ -0,0 +1,610 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Adlist Database Repository
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    databases::gravity::GravityDatabase,
+    util::{Error, ErrorKind},
+};
+use diesel::{
+    delete,
+    dsl::{exists, sql},
+    insert_into,
+    prelude::*,
+    result::{DatabaseErrorKind, Error as DieselError},
+    select,
+    sql_types::{Bool, Integer, Nullable},
+    update,
+};
+use failure::{Fail, ResultExt};
+use shaku::Provider;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// An adlist which gravity downloads domains from, along with its metadata
+#[cfg_attr(test, derive(Debug))]
+#[derive(Queryable, Serialize, Clone, PartialEq)]
+pub struct Adlist {
+    pub id: i32,
+    pub address: String,
+    pub enabled: bool,
+    pub comment: Option<String>,
+    pub date_added: i32,
+    pub date_modified: i32,
+}
+
+/// The result of the last time gravity downloaded an adlist. The fields are
+/// `None` if the gravity database does not record them or gravity has not
+/// run since the adlist was added.
+#[cfg_attr(test, derive(Debug))]
+#[derive(Serialize, Clone, PartialEq)]
+pub struct AdlistStatus {
+    /// When gravity last updated the adlist's domains
+    pub date_updated: Option<i32>,
+    /// The number of domains gravity pulled from the adlist
+    pub number_of_domains: Option<i32>,
+    /// If the last download of the adlist failed
+    pub fetch_failed: Option<bool>,
+}
+
+impl AdlistStatus {
+    /// Create a status with no information, for databases which do not
+    /// record the status of adlists
+    fn unknown() -> Self {
+        AdlistStatus {
+            date_updated: None,
+            number_of_domains: None,
+            fetch_failed: None,
+        }
+    }
+}
+
+/// Describes interactions with the adlist data store
+#[cfg_attr(test, mockall::automock)]
+pub trait AdlistRepository: Send {
+    /// Get all of the adlists, including disabled adlists
+    fn get(&self) -> Result<Vec<Adlist>, Error>;
+
+    /// Check if there is an adlist with the address
+    fn contains(&self, address: &str) -> Result<bool, Error>;
+
+    /// Add an enabled adlist with an optional comment. An `AlreadyExists`
+    /// error is returned if there is already an adlist with the address.
+    fn add(&self, address: &str, comment: Option<String>) -> Result<(), Error>;
+
+    /// Remove the adlist with the ID, along with its group memberships and
+    /// its domains in gravity. A `NotFound` error is returned if there is no
+    /// such adlist.
+    fn remove(&self, id: i32) -> Result<(), Error>;
+
+    /// Enable or disable the adlist with the ID. A `NotFound` error is
+    /// returned if there is no such adlist.
+    fn set_enabled(&self, id: i32, enabled: bool) -> Result<(), Error>;
+
+    /// Count the domains which gravity downloaded from the adlist with the
+    /// ID. A `NotFound` error is returned if there is no such adlist.
+    fn domain_count(&self, id: i32) -> Result<usize, Error>;
+
+    /// Find the first adlist which gravity downloaded the domain from, if
+    /// the domain is in gravity
+    fn find_domain(&self, domain: &str) -> Result<Option<Adlist>, Error>;
+
+    /// Get the result of the last gravity download of the adlist with the
+    /// ID. A `NotFound` error is returned if there is no such adlist.
+    fn status(&self, id: i32) -> Result<AdlistStatus, Error>;
+}
+
+/// The implementation of `AdlistRepository`
+#[derive(Provider)]
+#[shaku(interface = AdlistRepository)]
+pub struct AdlistRepositoryImpl {
+    #[shaku(provide)]
+    db: Box<GravityDatabase>,
+}
+
+impl AdlistRepository for AdlistRepositoryImpl {
+    fn get(&self) -> Result<Vec<Adlist>, Error> {
+        use crate::databases::gravity::adlist::dsl::*;
+        let db = &self.db as &SqliteConnection;
+
+        adlist
+            .select((id, address, enabled, comment, date_added, date_modified))
+            .order(id)
+            .load(db)
+            .context(ErrorKind::GravityDatabase)
+            .map_err(Error::from)
+    }
+
+    fn contains(&self, input_address: &str) -> Result<bool, Error> {
+        use crate::databases::gravity::adlist::dsl::*;
+        let db = &self.db as &SqliteConnection;
+
+        select(exists(adlist.filter(address.eq(input_address))))
+            .get_result(db)
+            .context(ErrorKind::GravityDatabase)
+            .map_err(Error::from)
+    }
+
+    fn add(&self, input_address: &str, input_comment: Option<String>) -> Result<(), Error> {
+        use crate::databases::gravity::adlist::dsl::*;
+        let db = &self.db as &SqliteConnection;
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i32;
+
+        insert_into(adlist)
+            .values(&(
+                address.eq(input_address),
+                enabled.eq(true),
+                comment.eq(input_comment),
+                date_added.eq(now),
+                date_modified.eq(now),
+            ))
+            .execute(db)
+            .map_err(|e| match e {
+                DieselError::DatabaseError(DatabaseErrorKind::UniqueViolation, _) => {
+                    Error::from(ErrorKind::AlreadyExists)
+                }
+                e => Error::from(e.context(ErrorKind::GravityDatabase)),
+            })?;
+
+        Ok(())
+    }
+
+    fn remove(&self, input_id: i32) -> Result<(), Error> {
+        use crate::databases::gravity::{adlist, adlist_by_group, gravity};
+        let db = &self.db as &SqliteConnection;
+
+        let has_gravity_adlists = has_gravity_adlist_column(db)?;
+
+        // The group memberships and gravity domains reference the adlist, so
+        // they are deleted first. Older gravity tables do not record the
+        // adlist of each domain, so their domains are left for gravity.
+        db.transaction::<_, DieselError, _>(|| {
+            delete(adlist_by_group::table.filter(adlist_by_group::adlist_id.eq(input_id)))
+                .execute(db)?;
+
+            if has_gravity_adlists {
+                delete(gravity::table.filter(gravity::adlist_id.eq(input_id))).execute(db)?;
+            }
+
+            match delete(adlist::table.filter(adlist::id.eq(input_id))).execute(db)? {
+                0 => Err(DieselError::NotFound),
+                _ => Ok(()),
+            }
+        })
+        .map_err(|e| match e {
+            DieselError::NotFound => Error::from(ErrorKind::NotFound),
+            e => Error::from(e.context(ErrorKind::GravityDatabase)),
+        })
+    }
+
+    fn set_enabled(&self, input_id: i32, input_enabled: bool) -> Result<(), Error> {
+        use crate::databases::gravity::adlist::dsl::*;
+        let db = &self.db as &SqliteConnection;
+
+        let updated = update(adlist.filter(id.eq(input_id)))
+            .set(enabled.eq(input_enabled))
+            .execute(db)
+            .context(ErrorKind::GravityDatabase)?;
+
+        if updated == 0 {
+            return Err(Error::from(ErrorKind::NotFound));
+        }
+
+        Ok(())
+    }
+
+    fn domain_count(&self, input_id: i32) -> Result<usize, Error> {
+        use crate::databases::gravity::{adlist, gravity};
+        let db = &self.db as &SqliteConnection;
+
+        let adlist_exists: bool = select(exists(adlist::table.filter(adlist::id.eq(input_id))))
+            .get_result(db)
+            .context(ErrorKind::GravityDatabase)?;
+
+        if !adlist_exists {
+            return Err(Error::from(ErrorKind::NotFound));
+        }
+
+        // The domains can not be counted if the gravity table does not record
+        // their adlist, or does not exist yet, so the count is zero
+        if !has_gravity_adlist_column(db)? {
+            return Ok(0);
+        }
+
+        // Adlists which have not been downloaded by gravity yet have no
+        // domains in the gravity table, so their count is zero
+        let domain_count: i64 = gravity::table
+            .filter(gravity::adlist_id.eq(input_id))
+            .count()
+            .get_result(db)
+            .context(ErrorKind::GravityDatabase)?;
+
+        Ok(domain_count as usize)
+    }
+
+    fn find_domain(&self, input_domain: &str) -> Result<Option<Adlist>, Error> {
+        use crate::databases::gravity::{adlist, gravity};
+        let db = &self.db as &SqliteConnection;
+
+        // Without the adlist of each domain, no adlist can be found
+        if !has_gravity_adlist_column(db)? {
+            return Ok(None);
+        }
+
+        adlist::table
+            .inner_join(gravity::table)
+            .filter(gravity::domain.eq(input_domain))
+            .select((
+                adlist::id,
+                adlist::address,
+                adlist::enabled,
+                adlist::comment,
+                adlist::date_added,
+                adlist::date_modified,
+            ))
+            .order(adlist::id)
+            .first(db)
+            .optional()
+            .context(ErrorKind::GravityDatabase)
+            .map_err(Error::from)
+    }
+
+    fn status(&self, input_id: i32) -> Result<AdlistStatus, Error> {
+        use crate::databases::gravity::adlist::dsl::*;
+        let db = &self.db as &SqliteConnection;
+
+        // Older gravity databases do not have the status columns, so the
+        // status is unknown instead of an error
+        if !has_status_columns(db)? {
+            return if select(exists(adlist.filter(id.eq(input_id))))
+                .get_result(db)
+                .context(ErrorKind::GravityDatabase)?
+            {
+                Ok(AdlistStatus::unknown())
+            } else {
+                Err(Error::from(ErrorKind::NotFound))
+            };
+        }
+
+        let row: Option<(Option<i32>, i32, i32)> = adlist
+            .filter(id.eq(input_id))
+            .select(sql::<(Nullable<Integer>, Integer, Integer)>(
+                "date_updated, number, status",
+            ))
+            .first(db)
+            .optional()
+            .context(ErrorKind::GravityDatabase)?;
+        let (updated, number, status) = row.ok_or(ErrorKind::NotFound)?;
+
+        // Gravity uses status 1 for a download, 2 for an unchanged list, and
+        // 3 or 4 for a failed download, depending on if a cached copy was
+        // used. Status 0 means gravity has not run since the adlist was added.
+        let fetch_failed = match status {
+            0 => None,
+            1 | 2 => Some(false),
+            _ => Some(true),
+        };
+
+        Ok(AdlistStatus {
+            date_updated: updated,
+            number_of_domains: if status == 0 { None } else { Some(number) },
+            fetch_failed,
+        })
+    }
+}
+
+/// Check if the adlist table has the columns which record the result of the
+/// last gravity download
+fn has_status_columns(db: &SqliteConnection) -> Result<bool, Error> {
+    select(sql::<Bool>(
+        "EXISTS(SELECT 1 FROM pragma_table_info('adlist') WHERE name = 'status')",
+    ))
+    .get_result(db)
+    .context(ErrorKind::GravityDatabase)
+    .map_err(Error::from)
+}
+
+/// Check if the gravity table records the adlist of each domain. Older
+/// gravity tables only have the domains, and the table does not exist until
+/// gravity has run.
+fn has_gravity_adlist_column(db: &SqliteConnection) -> Result<bool, Error> {
+    select(sql::<Bool>(
+        "EXISTS(SELECT 1 FROM pragma_table_info('gravity') WHERE name = 'adlist_id')",
+    ))
+    .get_result(db)
+    .context(ErrorKind::GravityDatabase)
+    .map_err(Error::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Adlist, AdlistRepository, AdlistRepositoryImpl, AdlistStatus};
+    use crate::{
+        databases::gravity::{connect_to_gravity_test_db, connect_to_gravity_v5_test_db},
+        util::ErrorKind,
+    };
+    use diesel::{connection::SimpleConnection, SqliteConnection};
+
+    /// All adlists are retrieved in order of their IDs
+    #[test]
+    fn get() {
+        let db = connect_to_gravity_test_db();
+        let repo = AdlistRepositoryImpl { db };
+
+        assert_eq!(
+            repo.get().unwrap(),
+            vec![
+                Adlist {
+                    id: 1,
+                    address: "https://raw.githubusercontent.com/StevenBlack/hosts/master/hosts"
+                        .to_owned(),
+                    enabled: true,
+                    comment: Some("Migrated from /etc/pihole/adlists.list".to_owned()),
+                    date_added: 1_557_712_118,
+                    date_modified: 1_557_712_118
+                },
+                Adlist {
+                    id: 2,
+                    address: "https://mirror1.malwaredomains.com/files/justdomains".to_owned(),
+                    enabled: true,
+                    comment: Some("Migrated from /etc/pihole/adlists.list".to_owned()),
+                    date_added: 1_557_712_118,
+                    date_modified: 1_557_712_118
+                },
+            ]
+        );
+    }
+
+    /// Existing adlists are found, and other addresses are not
+    #[test]
+    fn contains() {
+        let db = connect_to_gravity_test_db();
+        let repo = AdlistRepositoryImpl { db };
+
+        assert!(repo
+            .contains("https://mirror1.malwaredomains.com/files/justdomains")
+            .unwrap());
+        assert!(!repo.contains("https://example.com/hosts").unwrap());
+    }
+
+    /// After adding, the adlist is enabled and has the comment
+    #[test]
+    fn add_success() {
+        let db = connect_to_gravity_test_db();
+        let repo = AdlistRepositoryImpl { db };
+
+        repo.add("https://example.com/hosts", Some("Example".to_owned()))
+            .unwrap();
+
+        let added = repo
+            .get()
+            .unwrap()
+            .into_iter()
+            .find(|adlist| adlist.address == "https://example.com/hosts")
+            .unwrap();
+        assert!(added.enabled);
+        assert_eq!(added.comment, Some("Example".to_owned()));
+        assert_eq!(added.date_added, added.date_modified);
+    }
+
+    /// Adding an adlist which already exists is an `AlreadyExists` error
+    #[test]
+    fn add_duplicate() {
+        let db = connect_to_gravity_test_db();
+        let repo = AdlistRepositoryImpl { db };
+
+        assert_eq!(
+            repo.add("https://mirror1.malwaredomains.com/files/justdomains", None)
+                .unwrap_err()
+                .kind(),
+            ErrorKind::AlreadyExists
+        );
+    }
+
+    /// After removing, the adlist and its gravity domains are no longer in
+    /// the database
+    #[test]
+    fn remove_success() {
+        let db = connect_to_gravity_v5_test_db();
+        let repo = AdlistRepositoryImpl { db };
+
+        repo.remove(2).unwrap();
+
+        assert!(!repo
+            .contains("https://mirror1.malwaredomains.com/files/justdomains")
+            .unwrap());
+        assert_eq!(repo.find_domain("vra4.com").unwrap(), None);
+        assert_eq!(repo.domain_count(1).unwrap(), 6);
+    }
+
+    /// Adlists are removed from databases which do not record the adlist of
+    /// each gravity domain
+    #[test]
+    fn remove_legacy_schema() {
+        let db = connect_to_gravity_test_db();
+        let repo = AdlistRepositoryImpl { db };
+
+        repo.remove(2).unwrap();
+
+        assert!(!repo
+            .contains("https://mirror1.malwaredomains.com/files/justdomains")
+            .unwrap());
+    }
+
+    /// Removing an adlist which does not exist is a `NotFound` error
+    #[test]
+    fn remove_not_found() {
+        let db = connect_to_gravity_test_db();
+        let repo = AdlistRepositoryImpl { db };
+
+        assert_eq!(repo.remove(10).unwrap_err().kind(), ErrorKind::NotFound);
+    }
+
+    /// Disabling an adlist keeps it in the database
+    #[test]
+    fn set_enabled_success() {
+        let db = connect_to_gravity_test_db();
+        let repo = AdlistRepositoryImpl { db };
+
+        repo.set_enabled(2, false).unwrap();
+
+        let adlists = repo.get().unwrap();
+        assert_eq!(adlists.len(), 2);
+        assert!(adlists[0].enabled);
+        assert!(!adlists[1].enabled);
+    }
+
+    /// The domains in gravity are counted for each adlist
+    #[test]
+    fn domain_count() {
+        let db = connect_to_gravity_v5_test_db();
+        let repo = AdlistRepositoryImpl { db };
+
+        assert_eq!(repo.domain_count(1).unwrap(), 6);
+        assert_eq!(repo.domain_count(2).unwrap(), 4);
+    }
+
+    /// An adlist which gravity has not downloaded yet has no domains
+    #[test]
+    fn domain_count_not_downloaded() {
+        let db = connect_to_gravity_v5_test_db();
+        let repo = AdlistRepositoryImpl { db };
+
+        repo.add("https://example.com/hosts", None).unwrap();
+
+        assert_eq!(repo.domain_count(3).unwrap(), 0);
+    }
+
+    /// Counting the domains of an adlist which does not exist is a
+    /// `NotFound` error
+    #[test]
+    fn domain_count_not_found() {
+        let db = connect_to_gravity_v5_test_db();
+        let repo = AdlistRepositoryImpl { db };
+
+        assert_eq!(
+            repo.domain_count(10).unwrap_err().kind(),
+            ErrorKind::NotFound
+        );
+    }
+
+    /// The domains can not be counted if gravity does not record their
+    /// adlist, so the count is zero
+    #[test]
+    fn domain_count_legacy_schema() {
+        let db = connect_to_gravity_test_db();
+        let repo = AdlistRepositoryImpl { db };
+
+        assert_eq!(repo.domain_count(2).unwrap(), 0);
+        assert_eq!(
+            repo.domain_count(10).unwrap_err().kind(),
+            ErrorKind::NotFound
+        );
+    }
+
+    /// The count is zero if gravity has not created its table yet
+    #[test]
+    fn domain_count_without_table() {
+        let db = connect_to_gravity_v5_test_db();
+        (&db as &SqliteConnection)
+            .batch_execute("DROP TABLE gravity")
+            .unwrap();
+        let repo = AdlistRepositoryImpl { db };
+
+        assert_eq!(repo.domain_count(1).unwrap(), 0);
+        assert_eq!(repo.find_domain("vra4.com").unwrap(), None);
+    }
+
+    /// Updating an adlist which does not exist is a `NotFound` error
+    #[test]
+    fn set_enabled_not_found() {
+        let db = connect_to_gravity_test_db();
+        let repo = AdlistRepositoryImpl { db };
+
+        assert_eq!(
+            repo.set_enabled(10, false).unwrap_err().kind(),
+            ErrorKind::NotFound
+        );
+    }
+
+    /// The adlist which a domain in gravity came from is found
+    #[test]
+    fn find_domain() {
+        let db = connect_to_gravity_v5_test_db();
+        let repo = AdlistRepositoryImpl { db };
+
+        assert_eq!(
+            repo.find_domain("vra4.com")
+                .unwrap()
+                .map(|adlist| adlist.id),
+            Some(2)
+        );
+        assert_eq!(repo.find_domain("example.com").unwrap(), None);
+    }
+
+    /// No adlist is found if gravity does not record the adlist of each
+    /// domain
+    #[test]
+    fn find_domain_legacy_schema() {
+        let db = connect_to_gravity_test_db();
+        let repo = AdlistRepositoryImpl { db };
+
+        assert_eq!(repo.find_domain("vra4.com").unwrap(), None);
+    }
+
+    /// The status of the last download is read from the adlist table
+    #[test]
+    fn status() {
+        let db = connect_to_gravity_v5_test_db();
+        let repo = AdlistRepositoryImpl { db };
+
+        assert_eq!(
+            repo.status(1).unwrap(),
+            AdlistStatus {
+                date_updated: Some(1_557_712_200),
+                number_of_domains: Some(6),
+                fetch_failed: Some(false)
+            }
+        );
+        assert_eq!(repo.status(2).unwrap().fetch_failed, Some(true));
+    }
+
+    /// An adlist which gravity has not downloaded yet has an unknown status
+    #[test]
+    fn status_not_downloaded() {
+        let db = connect_to_gravity_v5_test_db();
+        let repo = AdlistRepositoryImpl { db };
+
+        repo.add("https://example.com/hosts", None).unwrap();
+
+        assert_eq!(repo.status(3).unwrap(), AdlistStatus::unknown());
+    }
+
+    /// Databases without the status columns have an unknown status
+    #[test]
+    fn status_legacy_schema() {
+        let db = connect_to_gravity_test_db();
+        let repo = AdlistRepositoryImpl { db };
+
+        assert_eq!(repo.status(1).unwrap(), AdlistStatus::unknown());
+        assert_eq!(repo.status(10).unwrap_err().kind(), ErrorKind::NotFound);
+    }
+
+    /// Getting the status of an adlist which does not exist is a `NotFound`
+    /// error
+    #[test]
+    fn status_not_found() {
+        let db = connect_to_gravity_v5_test_db();
+        let repo = AdlistRepositoryImpl { db };
+
+        assert_eq!(repo.status(10).unwrap_err().kind(), ErrorKind::NotFound);
+    }
+}
@@ -0,0 +1,67 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Adlist Address Validation
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::util::{Error, ErrorKind};
+
+/// The URL schemes which gravity is able to download adlists from
+const SUPPORTED_SCHEMES: [&str; 3] = ["http://", "https://", "file://"];
+
+/// Check that the adlist address uses a supported scheme and has something
+/// after the scheme
+pub fn validate_address(address: &str) -> Result<(), Error> {
+    let valid = SUPPORTED_SCHEMES
+        .iter()
+        .any(|scheme| match address.get(..scheme.len()) {
+            Some(prefix) if prefix.eq_ignore_ascii_case(scheme) => {
+                let rest = &address[scheme.len()..];
+                !rest.is_empty() && !rest.chars().any(char::is_whitespace)
+            }
+            _ => false,
+        });
+
+    if valid {
+        Ok(())
+    } else {
+        Err(Error::from(ErrorKind::InvalidAdlist(address.to_owned())))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::validate_address;
+    use crate::util::ErrorKind;
+
+    /// Addresses with a supported scheme are accepted
+    #[test]
+    fn valid() {
+        assert!(validate_address("https://example.com/hosts").is_ok());
+        assert!(validate_address("http://example.com/hosts").is_ok());
+        assert!(validate_address("HTTPS://example.com/hosts").is_ok());
+        assert!(validate_address("file:///etc/pihole/hosts").is_ok());
+    }
+
+    /// Addresses without a supported scheme, or with nothing after the
+    /// scheme, are rejected
+    #[test]
+    fn invalid() {
+        for address in &[
+            "example.com/hosts",
+            "ftp://example.com/hosts",
+            "https://",
+            "https://example.com/my hosts",
+            "",
+        ] {
+            assert_eq!(
+                validate_address(address).unwrap_err().kind(),
+                ErrorKind::InvalidAdlist((*address).to_owned())
+            );
+        }
+    }
+}
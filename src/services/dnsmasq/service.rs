@@ -0,0 +1,52 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Dnsmasq Config Generator Service
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    env::Env,
+    services::settings::SettingsRepository,
+    settings::{write_dnsmasq_config, DnsmasqSettings},
+    util::Error,
+};
+use shaku::{Component, Interface};
+use std::sync::Arc;
+
+/// Describes generating the Pi-hole dnsmasq config. Settings endpoints update
+/// SetupVars and then regenerate the whole config, so the two files can not
+/// drift apart.
+#[cfg_attr(test, mockall::automock)]
+pub trait DnsmasqConfigGenerator: Interface {
+    /// Render the config from the settings and write it. A config which is
+    /// not managed by Pi-hole is never overwritten.
+    fn write(&self, settings: &DnsmasqSettings) -> Result<(), Error>;
+
+    /// Render the config from the settings in SetupVars and write it
+    fn generate(&self) -> Result<(), Error>;
+}
+
+/// The implementation of `DnsmasqConfigGenerator`, which writes the config
+/// through the environment
+#[derive(Component)]
+#[shaku(interface = DnsmasqConfigGenerator)]
+pub struct DnsmasqConfigGeneratorImpl {
+    #[shaku(inject)]
+    env: Arc<Env>,
+    #[shaku(inject)]
+    settings: Arc<dyn SettingsRepository>,
+}
+
+impl DnsmasqConfigGenerator for DnsmasqConfigGeneratorImpl {
+    fn write(&self, settings: &DnsmasqSettings) -> Result<(), Error> {
+        write_dnsmasq_config(settings, &self.env)
+    }
+
+    fn generate(&self) -> Result<(), Error> {
+        self.write(&DnsmasqSettings::from_setup_vars(&*self.settings)?)
+    }
+}
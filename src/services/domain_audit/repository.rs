@@ -12,13 +12,13 @@ use crate::{
     databases::gravity::GravityDatabase,
     util::{Error, ErrorKind},
 };
-use diesel::{expression::exists::exists, insert_into, prelude::*, select};
+use diesel::{delete, expression::exists::exists, insert_into, prelude::*, select};
 use failure::ResultExt;
 use shaku::Provider;
 
 /// Describes interactions with the domain audit data store
 #[cfg_attr(test, mockall::automock)]
-pub trait DomainAuditRepository {
+pub trait DomainAuditRepository: Send {
     /// Check if the domain is contained in the audit table
     fn contains(&self, domain: &str) -> Result<bool, Error>;
 
@@ -27,6 +27,10 @@ pub trait DomainAuditRepository {
 
     /// Add a domain to the audit table
     fn add(&self, domain: &str) -> Result<(), Error>;
+
+    /// Remove a domain from the audit table. A `NotFound` error is returned
+    /// if the domain is not in the table.
+    fn remove(&self, domain: &str) -> Result<(), Error>;
 }
 
 /// The implementation of `DomainAuditRepository`
@@ -70,6 +74,21 @@ impl DomainAuditRepository for DomainAuditRepositoryImpl {
 
         Ok(())
     }
+
+    fn remove(&self, input_domain: &str) -> Result<(), Error> {
+        use crate::databases::gravity::domain_audit::dsl::*;
+        let db = &self.db as &SqliteConnection;
+
+        let deleted = delete(domain_audit.filter(domain.eq(input_domain)))
+            .execute(db)
+            .context(ErrorKind::GravityDatabase)?;
+
+        if deleted == 0 {
+            return Err(Error::from(ErrorKind::NotFound));
+        }
+
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -77,6 +96,7 @@ mod tests {
     use crate::{
         databases::gravity::connect_to_gravity_test_db,
         services::domain_audit::{DomainAuditRepository, DomainAuditRepositoryImpl},
+        util::ErrorKind,
     };
 
     /// If the audit table contains the domain, true will be returned
@@ -116,4 +136,27 @@ mod tests {
 
         assert_eq!(repo.contains("new.audited.domain").unwrap(), true);
     }
+
+    /// After removing, the database will not contain the domain
+    #[test]
+    fn remove_success() {
+        let db = connect_to_gravity_test_db();
+        let repo = DomainAuditRepositoryImpl { db };
+
+        repo.remove("audited.domain").unwrap();
+
+        assert_eq!(repo.contains("audited.domain").unwrap(), false);
+    }
+
+    /// Removing a domain which is not audited is a `NotFound` error
+    #[test]
+    fn remove_not_found() {
+        let db = connect_to_gravity_test_db();
+        let repo = DomainAuditRepositoryImpl { db };
+
+        assert_eq!(
+            repo.remove("not.audited.domain").unwrap_err().kind(),
+            ErrorKind::NotFound
+        );
+    }
 }
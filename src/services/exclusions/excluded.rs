@@ -0,0 +1,89 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Excluded Domains And Clients
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use std::collections::HashSet;
+
+/// The domains and clients which are left out of the top lists, from the
+/// `API_EXCLUDE_DOMAINS` and `API_EXCLUDE_CLIENTS` settings
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Exclusions {
+    domains: HashSet<String>,
+    clients: HashSet<String>,
+}
+
+impl Exclusions {
+    /// Create the exclusions from the excluded domains and clients. They are
+    /// compared in lowercase.
+    pub fn new(domains: Vec<String>, clients: Vec<String>) -> Self {
+        Exclusions {
+            domains: domains.iter().map(|s| s.to_lowercase()).collect(),
+            clients: clients.iter().map(|s| s.to_lowercase()).collect(),
+        }
+    }
+
+    /// Check if there are no exclusions
+    pub fn is_empty(&self) -> bool {
+        self.domains.is_empty() && self.clients.is_empty()
+    }
+
+    /// Check if the domain is excluded. Excluding a domain also excludes its
+    /// subdomains, so `google.com` excludes `www.google.com`.
+    pub fn excludes_domain(&self, domain: &str) -> bool {
+        let domain = domain.to_lowercase();
+        let mut suffix = domain.as_str();
+
+        loop {
+            if self.domains.contains(suffix) {
+                return true;
+            }
+
+            match suffix.find('.') {
+                Some(index) => suffix = &suffix[index + 1..],
+                None => return false,
+            }
+        }
+    }
+
+    /// Check if the client is excluded by its IP address or hostname
+    pub fn excludes_client(&self, ip: &str, name: Option<&str>) -> bool {
+        self.clients.contains(&ip.to_lowercase())
+            || name.map_or(false, |name| self.clients.contains(&name.to_lowercase()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Exclusions;
+
+    /// Domains are excluded along with their subdomains
+    #[test]
+    fn domain_suffix() {
+        let exclusions = Exclusions::new(vec!["google.com".to_owned()], Vec::new());
+
+        assert!(exclusions.excludes_domain("google.com"));
+        assert!(exclusions.excludes_domain("www.google.com"));
+        assert!(exclusions.excludes_domain("WWW.Google.COM"));
+        assert!(!exclusions.excludes_domain("notgoogle.com"));
+        assert!(!exclusions.excludes_domain("google.com.au"));
+        assert!(!exclusions.excludes_domain("com"));
+    }
+
+    /// Clients are excluded by exact IP address or hostname
+    #[test]
+    fn client_exact() {
+        let exclusions =
+            Exclusions::new(Vec::new(), vec!["10.1.1.1".to_owned(), "Laptop".to_owned()]);
+
+        assert!(exclusions.excludes_client("10.1.1.1", None));
+        assert!(exclusions.excludes_client("10.1.1.2", Some("laptop")));
+        assert!(!exclusions.excludes_client("10.1.1.10", None));
+        assert!(!exclusions.excludes_client("10.1.1.2", Some("laptop.lan")));
+    }
+}
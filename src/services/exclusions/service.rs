@@ -0,0 +1,153 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Exclusion Service
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    env::{Env, FileStamp, PiholeFile},
+    services::{exclusions::Exclusions, settings::SettingsRepository},
+    settings::SetupVarsEntry,
+    util::Error,
+};
+use shaku::{Component, Interface};
+use std::sync::{Arc, Mutex};
+
+/// Describes reading the domains and clients which are excluded from the top
+/// lists
+#[cfg_attr(test, mockall::automock)]
+pub trait ExclusionService: Interface {
+    /// Get the excluded domains and clients
+    fn exclusions(&self) -> Result<Arc<Exclusions>, Error>;
+}
+
+/// The implementation of `ExclusionService`, which reads the exclusions from
+/// SetupVars. They are cached until SetupVars changes.
+#[derive(Component)]
+#[shaku(interface = ExclusionService)]
+pub struct ExclusionServiceImpl {
+    #[shaku(inject)]
+    env: Arc<Env>,
+    #[shaku(inject)]
+    settings: Arc<dyn SettingsRepository>,
+    #[shaku(default)]
+    cached: Mutex<Option<(FileStamp, Arc<Exclusions>)>>,
+}
+
+impl ExclusionServiceImpl {
+    /// Read the exclusions from SetupVars
+    fn read(&self) -> Result<Exclusions, Error> {
+        Ok(Exclusions::new(
+            self.settings.get_list(SetupVarsEntry::ApiExcludeDomains)?,
+            self.settings.get_list(SetupVarsEntry::ApiExcludeClients)?,
+        ))
+    }
+}
+
+impl ExclusionService for ExclusionServiceImpl {
+    fn exclusions(&self) -> Result<Arc<Exclusions>, Error> {
+        // If the file can not be found, the cache can not be checked
+        let stamp = match self.env.file_stamp(PiholeFile::SetupVars) {
+            Some(stamp) => stamp,
+            None => return Ok(Arc::new(self.read()?)),
+        };
+        let mut cached = self.cached.lock().unwrap();
+
+        if let Some((cached_stamp, exclusions)) = cached.as_ref() {
+            if *cached_stamp == stamp {
+                return Ok(Arc::clone(exclusions));
+            }
+        }
+
+        let exclusions = Arc::new(self.read()?);
+        *cached = Some((stamp, Arc::clone(&exclusions)));
+        Ok(exclusions)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ExclusionService, ExclusionServiceImpl};
+    use crate::{
+        env::{Env, FileStamp, PiholeFile},
+        services::{exclusions::Exclusions, settings::SettingsRepositoryImpl},
+        testing::TestEnvBuilder,
+    };
+    use std::sync::{Arc, Mutex};
+
+    /// Create a service with SetupVars and the cached exclusions
+    fn service(
+        setup_vars: &str,
+        cached: impl FnOnce(&Env) -> Option<(FileStamp, Arc<Exclusions>)>,
+    ) -> ExclusionServiceImpl {
+        let env = Arc::new(
+            TestEnvBuilder::new()
+                .file(PiholeFile::SetupVars, setup_vars)
+                .build(),
+        );
+
+        ExclusionServiceImpl {
+            cached: Mutex::new(cached(&env)),
+            settings: Arc::new(SettingsRepositoryImpl::new(Arc::clone(&env))),
+            env,
+        }
+    }
+
+    /// The exclusions are read from SetupVars
+    #[test]
+    fn read() {
+        let service = service(
+            "API_EXCLUDE_DOMAINS=google.com,Example.com\n\
+             API_EXCLUDE_CLIENTS=10.1.1.1",
+            |_| None,
+        );
+
+        assert_eq!(
+            *service.exclusions().unwrap(),
+            Exclusions::new(
+                vec!["google.com".to_owned(), "example.com".to_owned()],
+                vec!["10.1.1.1".to_owned()]
+            )
+        );
+    }
+
+    /// The cached exclusions are used while SetupVars does not change
+    #[test]
+    fn cached() {
+        let cached = Arc::new(Exclusions::new(vec!["cached.com".to_owned()], Vec::new()));
+        let service = service("API_EXCLUDE_DOMAINS=google.com", |env| {
+            Some((
+                env.file_stamp(PiholeFile::SetupVars).unwrap(),
+                Arc::clone(&cached),
+            ))
+        });
+
+        assert_eq!(service.exclusions().unwrap(), cached);
+    }
+
+    /// The exclusions are read again once SetupVars changes, even if its
+    /// modification time is the same
+    #[test]
+    fn modified() {
+        let service = service("API_EXCLUDE_DOMAINS=google.com", |env| {
+            let stamp = env.file_stamp(PiholeFile::SetupVars).unwrap();
+
+            Some((
+                FileStamp {
+                    len: stamp.len + 1,
+                    ..stamp
+                },
+                Arc::new(Exclusions::new(vec!["cached.com".to_owned()], Vec::new())),
+            ))
+        });
+
+        assert_eq!(
+            *service.exclusions().unwrap(),
+            Exclusions::new(vec!["google.com".to_owned()], Vec::new())
+        );
+    }
+}
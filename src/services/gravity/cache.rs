@@ -0,0 +1,93 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Gravity Count Cache
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::util::Error;
+use shaku::{Component, Module, ModuleBuildContext};
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// Caches the number of domains in gravity, which only changes when gravity
+/// runs but is expensive to count
+#[derive(Default)]
+pub struct GravityCountCache {
+    cached: Mutex<Option<(Instant, usize)>>,
+}
+
+impl<M: Module> Component<M> for GravityCountCache {
+    type Interface = Self;
+    type Parameters = ();
+
+    fn build(_: &mut ModuleBuildContext<M>, _: ()) -> Box<Self> {
+        Box::new(GravityCountCache::default())
+    }
+}
+
+impl GravityCountCache {
+    /// Get the cached count if it is younger than `ttl`. Otherwise, count
+    /// again with `count` and cache the result. Errors are not cached.
+    pub fn get_or_count(
+        &self,
+        ttl: Duration,
+        count: impl FnOnce() -> Result<usize, Error>,
+    ) -> Result<usize, Error> {
+        let mut cached = self.cached.lock().unwrap();
+
+        if let Some((counted_at, value)) = *cached {
+            if counted_at.elapsed() < ttl {
+                return Ok(value);
+            }
+        }
+
+        let value = count()?;
+        *cached = Some((Instant::now(), value));
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::GravityCountCache;
+    use crate::util::{Error, ErrorKind};
+    use std::time::Duration;
+
+    /// The count is reused until it expires
+    #[test]
+    fn cached() {
+        let cache = GravityCountCache::default();
+        let ttl = Duration::from_secs(60);
+
+        assert_eq!(cache.get_or_count(ttl, || Ok(10)).unwrap(), 10);
+        assert_eq!(cache.get_or_count(ttl, || Ok(20)).unwrap(), 10);
+        assert_eq!(
+            cache
+                .get_or_count(Duration::from_secs(0), || Ok(20))
+                .unwrap(),
+            20
+        );
+    }
+
+    /// Errors are returned and not cached
+    #[test]
+    fn error_not_cached() {
+        let cache = GravityCountCache::default();
+        let ttl = Duration::from_secs(60);
+
+        assert_eq!(
+            cache
+                .get_or_count(ttl, || Err(Error::from(ErrorKind::GravityDatabase)))
+                .unwrap_err()
+                .kind(),
+            ErrorKind::GravityDatabase
+        );
+        assert_eq!(cache.get_or_count(ttl, || Ok(10)).unwrap(), 10);
+    }
+}
@@ -0,0 +1,78 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Gravity Database Repository
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    databases::gravity::GravityDatabase,
+    util::{Error, ErrorKind},
+};
+use diesel::{dsl::count_star, prelude::*, result::Error as DieselError};
+use failure::Fail;
+use shaku::Provider;
+
+/// Describes interactions with the domains downloaded by gravity
+#[cfg_attr(test, mockall::automock)]
+pub trait GravityRepository: Send {
+    /// Count the rows in the gravity table. If the table does not exist
+    /// because gravity has not run yet, the count is zero.
+    fn count(&self) -> Result<usize, Error>;
+}
+
+/// The implementation of `GravityRepository`
+#[derive(Provider)]
+#[shaku(interface = GravityRepository)]
+pub struct GravityRepositoryImpl {
+    #[shaku(provide)]
+    db: Box<GravityDatabase>,
+}
+
+impl GravityRepository for GravityRepositoryImpl {
+    fn count(&self) -> Result<usize, Error> {
+        use crate::databases::gravity::gravity::dsl::*;
+        let db = &self.db as &SqliteConnection;
+
+        match gravity.select(count_star()).get_result::<i64>(db) {
+            Ok(row_count) => Ok(row_count as usize),
+            Err(DieselError::DatabaseError(_, ref info))
+                if info.message().starts_with("no such table") =>
+            {
+                Ok(0)
+            }
+            Err(e) => Err(Error::from(e.context(ErrorKind::GravityDatabase))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{GravityRepository, GravityRepositoryImpl};
+    use crate::databases::gravity::connect_to_gravity_test_db;
+    use diesel::{connection::SimpleConnection, SqliteConnection};
+
+    /// Every row of the gravity table is counted
+    #[test]
+    fn count() {
+        let db = connect_to_gravity_test_db();
+        let repo = GravityRepositoryImpl { db };
+
+        assert_eq!(repo.count().unwrap(), 10);
+    }
+
+    /// The count is zero if gravity has not created its table yet
+    #[test]
+    fn count_without_table() {
+        let db = connect_to_gravity_test_db();
+        (&db as &SqliteConnection)
+            .batch_execute("DROP TABLE gravity")
+            .unwrap();
+        let repo = GravityRepositoryImpl { db };
+
+        assert_eq!(repo.count().unwrap(), 0);
+    }
+}
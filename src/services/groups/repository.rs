@@ -0,0 +1,331 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Group Database Repository
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    databases::gravity::GravityDatabase,
+    util::{Error, ErrorKind},
+};
+use diesel::{delete, insert_into, prelude::*, result::Error as DieselError, update};
+use failure::{Fail, ResultExt};
+use shaku::Provider;
+
+/// A group which list domains and adlists can be assigned to
+#[cfg_attr(test, derive(Debug))]
+#[derive(Queryable, Serialize, Clone, PartialEq)]
+pub struct Group {
+    pub id: i32,
+    pub name: String,
+    pub enabled: bool,
+    pub description: Option<String>,
+}
+
+/// Describes interactions with the group data store
+#[cfg_attr(test, mockall::automock)]
+pub trait GroupRepository: Send {
+    /// Get all of the groups, including disabled groups
+    fn get(&self) -> Result<Vec<Group>, Error>;
+
+    /// Add an enabled group with an optional description
+    fn add(&self, name: &str, description: Option<String>) -> Result<(), Error>;
+
+    /// Replace the name, enabled state, and description of the group with the
+    /// ID. A `NotFound` error is returned if there is no such group.
+    fn update(
+        &self,
+        id: i32,
+        name: &str,
+        enabled: bool,
+        description: Option<String>,
+    ) -> Result<(), Error>;
+
+    /// Count the list domains and adlists which are assigned to the group
+    fn member_count(&self, id: i32) -> Result<usize, Error>;
+
+    /// Remove the group with the ID. If the group still has members, a
+    /// `GroupNotEmpty` error is returned unless `force` is true, in which case
+    /// the memberships are removed along with the group. A `NotFound` error
+    /// is returned if there is no such group.
+    fn remove(&self, id: i32, force: bool) -> Result<(), Error>;
+}
+
+/// The implementation of `GroupRepository`
+#[derive(Provider)]
+#[shaku(interface = GroupRepository)]
+pub struct GroupRepositoryImpl {
+    #[shaku(provide)]
+    db: Box<GravityDatabase>,
+}
+
+impl GroupRepository for GroupRepositoryImpl {
+    fn get(&self) -> Result<Vec<Group>, Error> {
+        use crate::databases::gravity::group::dsl::*;
+        let db = &self.db as &SqliteConnection;
+
+        group
+            .select((id, name, enabled, description))
+            .order(id)
+            .load(db)
+            .context(ErrorKind::GravityDatabase)
+            .map_err(Error::from)
+    }
+
+    fn add(&self, input_name: &str, input_description: Option<String>) -> Result<(), Error> {
+        use crate::databases::gravity::group::dsl::*;
+        let db = &self.db as &SqliteConnection;
+
+        insert_into(group)
+            .values(&(
+                name.eq(input_name),
+                enabled.eq(true),
+                description.eq(input_description),
+            ))
+            .execute(db)
+            .context(ErrorKind::GravityDatabase)?;
+
+        Ok(())
+    }
+
+    fn update(
+        &self,
+        input_id: i32,
+        input_name: &str,
+        input_enabled: bool,
+        input_description: Option<String>,
+    ) -> Result<(), Error> {
+        use crate::databases::gravity::group::dsl::*;
+        let db = &self.db as &SqliteConnection;
+
+        let updated = update(group.filter(id.eq(input_id)))
+            .set((
+                name.eq(input_name),
+                enabled.eq(input_enabled),
+                description.eq(input_description),
+            ))
+            .execute(db)
+            .context(ErrorKind::GravityDatabase)?;
+
+        if updated == 0 {
+            return Err(Error::from(ErrorKind::NotFound));
+        }
+
+        Ok(())
+    }
+
+    fn member_count(&self, input_id: i32) -> Result<usize, Error> {
+        use crate::databases::gravity::{
+            adlist_by_group, blacklist_by_group, regex_by_group, regex_whitelist_by_group,
+            whitelist_by_group,
+        };
+        let db = &self.db as &SqliteConnection;
+
+        let counts: Vec<i64> = vec![
+            whitelist_by_group::table
+                .filter(whitelist_by_group::group_id.eq(input_id))
+                .count()
+                .get_result::<i64>(db),
+            blacklist_by_group::table
+                .filter(blacklist_by_group::group_id.eq(input_id))
+                .count()
+                .get_result::<i64>(db),
+            regex_by_group::table
+                .filter(regex_by_group::group_id.eq(input_id))
+                .count()
+                .get_result::<i64>(db),
+            regex_whitelist_by_group::table
+                .filter(regex_whitelist_by_group::group_id.eq(input_id))
+                .count()
+                .get_result::<i64>(db),
+            adlist_by_group::table
+                .filter(adlist_by_group::group_id.eq(input_id))
+                .count()
+                .get_result::<i64>(db),
+        ]
+        .into_iter()
+        .collect::<Result<_, _>>()
+        .context(ErrorKind::GravityDatabase)?;
+
+        Ok(counts.into_iter().sum::<i64>() as usize)
+    }
+
+    fn remove(&self, input_id: i32, force: bool) -> Result<(), Error> {
+        use crate::databases::gravity::{
+            adlist_by_group, blacklist_by_group, group, regex_by_group, regex_whitelist_by_group,
+            whitelist_by_group,
+        };
+        let db = &self.db as &SqliteConnection;
+
+        if !force && self.member_count(input_id)? > 0 {
+            return Err(Error::from(ErrorKind::GroupNotEmpty(input_id)));
+        }
+
+        // The memberships reference the group, so they are deleted first
+        db.transaction::<_, DieselError, _>(|| {
+            delete(whitelist_by_group::table.filter(whitelist_by_group::group_id.eq(input_id)))
+                .execute(db)?;
+            delete(blacklist_by_group::table.filter(blacklist_by_group::group_id.eq(input_id)))
+                .execute(db)?;
+            delete(regex_by_group::table.filter(regex_by_group::group_id.eq(input_id)))
+                .execute(db)?;
+            delete(
+                regex_whitelist_by_group::table
+                    .filter(regex_whitelist_by_group::group_id.eq(input_id)),
+            )
+            .execute(db)?;
+            delete(adlist_by_group::table.filter(adlist_by_group::group_id.eq(input_id)))
+                .execute(db)?;
+
+            match delete(group::table.filter(group::id.eq(input_id))).execute(db)? {
+                0 => Err(DieselError::NotFound),
+                _ => Ok(()),
+            }
+        })
+        .map_err(|e| match e {
+            DieselError::NotFound => Error::from(ErrorKind::NotFound),
+            e => Error::from(e.context(ErrorKind::GravityDatabase)),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Group, GroupRepository, GroupRepositoryImpl};
+    use crate::{databases::gravity::connect_to_gravity_test_db, util::ErrorKind};
+
+    /// The test group, which the second adlist is assigned to
+    fn test_group() -> Group {
+        Group {
+            id: 1,
+            name: "Test".to_owned(),
+            enabled: true,
+            description: Some("A test group".to_owned()),
+        }
+    }
+
+    /// All groups are retrieved in order of their IDs
+    #[test]
+    fn get() {
+        let db = connect_to_gravity_test_db();
+        let repo = GroupRepositoryImpl { db };
+
+        assert_eq!(repo.get().unwrap(), vec![test_group()]);
+    }
+
+    /// After adding, the group is enabled and has the description
+    #[test]
+    fn add_success() {
+        let db = connect_to_gravity_test_db();
+        let repo = GroupRepositoryImpl { db };
+
+        repo.add("New", Some("A new group".to_owned())).unwrap();
+
+        assert_eq!(
+            repo.get().unwrap(),
+            vec![
+                test_group(),
+                Group {
+                    id: 2,
+                    name: "New".to_owned(),
+                    enabled: true,
+                    description: Some("A new group".to_owned())
+                }
+            ]
+        );
+    }
+
+    /// Updating a group replaces all of its fields
+    #[test]
+    fn update_success() {
+        let db = connect_to_gravity_test_db();
+        let repo = GroupRepositoryImpl { db };
+
+        repo.update(1, "Renamed", false, None).unwrap();
+
+        assert_eq!(
+            repo.get().unwrap(),
+            vec![Group {
+                id: 1,
+                name: "Renamed".to_owned(),
+                enabled: false,
+                description: None
+            }]
+        );
+    }
+
+    /// Updating a group which does not exist is a `NotFound` error
+    #[test]
+    fn update_not_found() {
+        let db = connect_to_gravity_test_db();
+        let repo = GroupRepositoryImpl { db };
+
+        assert_eq!(
+            repo.update(10, "Missing", true, None).unwrap_err().kind(),
+            ErrorKind::NotFound
+        );
+    }
+
+    /// The members of a group are counted
+    #[test]
+    fn member_count() {
+        let db = connect_to_gravity_test_db();
+        let repo = GroupRepositoryImpl { db };
+
+        assert_eq!(repo.member_count(1).unwrap(), 1);
+        assert_eq!(repo.member_count(10).unwrap(), 0);
+    }
+
+    /// Removing a group which still has members is refused
+    #[test]
+    fn remove_not_empty() {
+        let db = connect_to_gravity_test_db();
+        let repo = GroupRepositoryImpl { db };
+
+        assert_eq!(
+            repo.remove(1, false).unwrap_err().kind(),
+            ErrorKind::GroupNotEmpty(1)
+        );
+        assert_eq!(repo.get().unwrap(), vec![test_group()]);
+    }
+
+    /// Forcing the removal of a group removes its memberships as well
+    #[test]
+    fn remove_force() {
+        let db = connect_to_gravity_test_db();
+        let repo = GroupRepositoryImpl { db };
+
+        repo.remove(1, true).unwrap();
+
+        assert_eq!(repo.get().unwrap(), Vec::new());
+        assert_eq!(repo.member_count(1).unwrap(), 0);
+    }
+
+    /// A group without members can be removed without forcing it
+    #[test]
+    fn remove_empty() {
+        let db = connect_to_gravity_test_db();
+        let repo = GroupRepositoryImpl { db };
+
+        repo.add("New", None).unwrap();
+        repo.remove(2, false).unwrap();
+
+        assert_eq!(repo.get().unwrap(), vec![test_group()]);
+    }
+
+    /// Removing a group which does not exist is a `NotFound` error
+    #[test]
+    fn remove_not_found() {
+        let db = connect_to_gravity_test_db();
+        let repo = GroupRepositoryImpl { db };
+
+        assert_eq!(
+            repo.remove(10, false).unwrap_err().kind(),
+            ErrorKind::NotFound
+        );
+    }
+}
@@ -0,0 +1,139 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Reverse DNS Hostname Cache
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use shaku::{Component, Module, ModuleBuildContext};
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::{Arc, Mutex},
+    thread,
+};
+
+/// The most addresses which are cached. The cache is cleared when it is full.
+const MAX_CACHED_ADDRESSES: usize = 1024;
+
+/// Resolves the hostnames of addresses with reverse DNS. Lookups are done in
+/// the background so requests are never blocked on DNS: the first request for
+/// an address starts the lookup and gets no name, and later requests get the
+/// cached result.
+#[derive(Default)]
+pub struct HostnameCache {
+    /// The resolved hostnames. Addresses which are being looked up, or which
+    /// have no hostname, are cached as `None`.
+    names: Arc<Mutex<HashMap<IpAddr, Option<String>>>>,
+}
+
+impl<M: Module> Component<M> for HostnameCache {
+    type Interface = Self;
+    type Parameters = ();
+
+    fn build(_: &mut ModuleBuildContext<M>, _: ()) -> Box<Self> {
+        Box::new(HostnameCache::default())
+    }
+}
+
+impl HostnameCache {
+    /// Get the cached hostname of the address, starting a reverse DNS lookup
+    /// if the address has not been looked up yet
+    pub fn get(&self, address: &str) -> Option<String> {
+        self.get_or_resolve(address, reverse_lookup)
+    }
+
+    /// Get the cached hostname of the address, starting a lookup with
+    /// `resolve` in the background if the address has not been looked up yet
+    fn get_or_resolve(
+        &self,
+        address: &str,
+        resolve: fn(IpAddr) -> Option<String>,
+    ) -> Option<String> {
+        let ip: IpAddr = address.parse().ok()?;
+        let mut names = self.names.lock().unwrap();
+
+        if let Some(name) = names.get(&ip) {
+            return name.clone();
+        }
+
+        if names.len() >= MAX_CACHED_ADDRESSES {
+            names.clear();
+        }
+
+        // Mark the address as looked up, so only one lookup is started
+        names.insert(ip, None);
+
+        let names = Arc::clone(&self.names);
+        thread::spawn(move || {
+            let name = resolve(ip);
+            names.lock().unwrap().insert(ip, name);
+        });
+
+        None
+    }
+}
+
+/// Look up the hostname of the address. If there is no hostname, `None` is
+/// returned instead of the address itself.
+fn reverse_lookup(ip: IpAddr) -> Option<String> {
+    dns_lookup::lookup_addr(&ip)
+        .ok()
+        .filter(|name| name.parse::<IpAddr>().is_err())
+}
+
+#[cfg(test)]
+mod test {
+    use super::HostnameCache;
+    use std::{net::IpAddr, thread, time::Duration};
+
+    fn resolve(ip: IpAddr) -> Option<String> {
+        if ip.to_string() == "10.1.1.1" {
+            Some("client.lan".to_owned())
+        } else {
+            None
+        }
+    }
+
+    /// Wait for the background lookup of the address to finish
+    fn wait_for_lookup(cache: &HostnameCache, address: &str) {
+        let ip: IpAddr = address.parse().unwrap();
+
+        for _ in 0..100 {
+            if cache.names.lock().unwrap().get(&ip) != Some(&None) {
+                return;
+            }
+
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    /// The first request starts the lookup without waiting for it, and later
+    /// requests get the hostname
+    #[test]
+    fn resolved_in_background() {
+        let cache = HostnameCache::default();
+
+        assert_eq!(cache.get_or_resolve("10.1.1.1", resolve), None);
+        wait_for_lookup(&cache, "10.1.1.1");
+        assert_eq!(
+            cache.get_or_resolve("10.1.1.1", resolve),
+            Some("client.lan".to_owned())
+        );
+    }
+
+    /// Addresses without a hostname, and values which are not addresses, have
+    /// no name
+    #[test]
+    fn no_hostname() {
+        let cache = HostnameCache::default();
+
+        assert_eq!(cache.get_or_resolve("10.1.1.2", resolve), None);
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(cache.get_or_resolve("10.1.1.2", resolve), None);
+        assert_eq!(cache.get_or_resolve("client", resolve), None);
+    }
+}
@@ -0,0 +1,94 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// List Entry Filtering
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+/// Restricts which entries of a list are returned. The filters are applied by
+/// the database, and all of them must match.
+#[cfg_attr(test, derive(Debug))]
+#[derive(Clone, PartialEq, Default)]
+pub struct ListFilter {
+    /// Include disabled entries if `enabled` is not given
+    pub include_disabled: bool,
+    /// Only include entries with this enabled state
+    pub enabled: Option<bool>,
+    /// Only include entries whose comment contains this text
+    pub comment_contains: Option<String>,
+}
+
+impl ListFilter {
+    /// Check if the filter does more than include or exclude disabled
+    /// entries
+    pub fn is_filtering(&self) -> bool {
+        self.enabled.is_some() || self.comment_contains.is_some()
+    }
+
+    /// Get the values of the `enabled` column which should be selected
+    pub fn enabled_states(&self) -> Vec<bool> {
+        match self.enabled {
+            Some(enabled) => vec![enabled],
+            None if self.include_disabled => vec![true, false],
+            None => vec![true],
+        }
+    }
+
+    /// Get the `LIKE` pattern which matches comments containing the text,
+    /// using `\` as the escape character
+    pub fn comment_pattern(&self) -> Option<String> {
+        self.comment_contains
+            .as_ref()
+            .map(|text| contains_pattern(text))
+    }
+}
+
+/// Get the `LIKE` pattern which matches values containing the text. The
+/// `LIKE` wildcards are escaped with `\` so they are matched literally.
+pub fn contains_pattern(text: &str) -> String {
+    format!(
+        "%{}%",
+        text.replace('\\', "\\\\")
+            .replace('%', "\\%")
+            .replace('_', "\\_")
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::ListFilter;
+
+    /// An explicit enabled state overrides `include_disabled`
+    #[test]
+    fn enabled_states() {
+        let filter = ListFilter {
+            include_disabled: false,
+            enabled: Some(false),
+            comment_contains: None,
+        };
+
+        assert_eq!(filter.enabled_states(), vec![false]);
+        assert_eq!(
+            ListFilter {
+                include_disabled: true,
+                ..ListFilter::default()
+            }
+            .enabled_states(),
+            vec![true, false]
+        );
+    }
+
+    /// Wildcards in the comment text are matched literally
+    #[test]
+    fn comment_pattern() {
+        let filter = ListFilter {
+            comment_contains: Some("100%_sure".to_owned()),
+            ..ListFilter::default()
+        };
+
+        assert_eq!(filter.comment_pattern(), Some("%100\\%\\_sure%".to_owned()));
+    }
+}
@@ -8,23 +8,144 @@
 // This file is copyright under the latest version of the EUPL.
 // Please see LICENSE file for your rights under this license.
 
-use crate::settings::ValueType;
+use crate::{
+    services::lists::{validate_domain, validate_regex},
+    util::{Error, ErrorKind},
+};
 
-/// Represents the various Pi-hole domain lists
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+/// Represents the various Pi-hole domain lists. `Regex` is the regex
+/// blacklist.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum List {
     White,
     Black,
     Regex,
+    RegexWhite,
 }
 
 impl List {
+    /// Get the name of the list, for use in messages
+    pub fn name(self) -> &'static str {
+        match self {
+            List::White => "whitelist",
+            List::Black => "blacklist",
+            List::Regex => "regex list",
+            List::RegexWhite => "regex whitelist",
+        }
+    }
+
+    /// Check if the entries of the list are regex patterns
+    pub fn is_regex(self) -> bool {
+        self == List::Regex || self == List::RegexWhite
+    }
+
+    /// Get the list which has the opposite effect on the same exact domains.
+    /// A domain should not be on both the whitelist and the blacklist.
+    pub fn opposite(self) -> Option<List> {
+        match self {
+            List::White => Some(List::Black),
+            List::Black => Some(List::White),
+            List::Regex | List::RegexWhite => None,
+        }
+    }
+
+    /// Get the value of the `type` column for entries of the list in the
+    /// unified `domainlist` table
+    pub fn domainlist_type(self) -> i32 {
+        match self {
+            List::White => 0,
+            List::Black => 1,
+            List::RegexWhite => 2,
+            List::Regex => 3,
+        }
+    }
+
+    /// Lowercase the domain, since domains are case-insensitive. Regex
+    /// entries are returned unchanged.
+    pub fn fold_case(self, domain: &str) -> String {
+        if self.is_regex() {
+            domain.to_owned()
+        } else {
+            domain.to_ascii_lowercase()
+        }
+    }
+
+    /// Convert the domain to the form FTL uses: lowercase, with
+    /// internationalized domains converted to punycode. Regex entries are
+    /// returned unchanged.
+    pub fn normalize(self, domain: &str) -> Result<String, Error> {
+        if self.is_regex() || domain.is_ascii() {
+            return Ok(self.fold_case(domain));
+        }
+
+        idna::domain_to_ascii(domain)
+            .map_err(|_| Error::from(ErrorKind::InvalidIdn(domain.to_owned())))
+    }
+
     /// Check if the list accepts the domain as valid
     pub fn accepts(self, domain: &str) -> bool {
+        self.validate(domain).is_ok()
+    }
+
+    /// Check if the list accepts the domain as valid, returning an error
+    /// describing the problem if it does not
+    pub fn validate(self, domain: &str) -> Result<(), Error> {
         match self {
-            List::Regex => ValueType::Regex.is_valid(domain),
-            // Allow hostnames to be white/blacklist-ed
-            _ => ValueType::Hostname.is_valid(domain),
+            List::Regex | List::RegexWhite => validate_regex(domain),
+            List::White | List::Black => validate_domain(domain),
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::List;
+    use crate::util::ErrorKind;
+
+    /// Internationalized domains are converted to punycode
+    #[test]
+    fn normalize_idn() {
+        assert_eq!(
+            List::White.normalize("münchen.de").unwrap(),
+            "xn--mnchen-3ya.de"
+        );
+        assert_eq!(
+            List::Black.normalize("xn--mnchen-3ya.de").unwrap(),
+            "xn--mnchen-3ya.de"
+        );
+    }
+
+    /// Domains are lowercased
+    #[test]
+    fn normalize_case() {
+        assert_eq!(
+            List::Black.normalize("Ads.Example.COM").unwrap(),
+            "ads.example.com"
+        );
+        assert_eq!(
+            List::White.normalize("MÜNCHEN.de").unwrap(),
+            "xn--mnchen-3ya.de"
+        );
+    }
+
+    /// Regex entries are not converted
+    #[test]
+    fn normalize_regex() {
+        assert_eq!(List::Regex.normalize("^münchen").unwrap(), "^münchen");
+        assert_eq!(List::RegexWhite.normalize("^münchen").unwrap(), "^münchen");
+        assert_eq!(List::Regex.normalize("^Ads\\.").unwrap(), "^Ads\\.");
+    }
+
+    /// Invalid internationalized domains are rejected
+    #[test]
+    fn normalize_invalid() {
+        assert_eq!(
+            List::White
+                .normalize("bad\u{fffd}domain.com")
+                .unwrap_err()
+                .kind(),
+            ErrorKind::InvalidIdn("bad\u{fffd}domain.com".to_owned())
+        );
+    }
+}
@@ -8,8 +8,15 @@
 // This file is copyright under the latest version of the EUPL.
 // Please see LICENSE file for your rights under this license.
 
+mod filter;
 mod list;
+mod query;
 mod repository;
 mod service;
+mod sort;
+mod validator;
+mod wildcard;
 
-pub use self::{list::*, repository::*, service::*};
+pub use self::{
+    filter::*, list::*, query::*, repository::*, service::*, sort::*, validator::*, wildcard::*,
+};
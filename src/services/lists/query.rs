@@ -0,0 +1,46 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// List Entry Query Options
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::services::lists::{ListFilter, ListSort};
+
+/// Selects a page of list entries. The filter is applied before the entries
+/// are sorted and paged.
+#[cfg_attr(test, derive(Debug))]
+#[derive(Clone, PartialEq, Default)]
+pub struct ListQuery {
+    /// Restricts which entries are returned
+    pub filter: ListFilter,
+    /// Sort the entries, or keep them in insertion order if `None`
+    pub sort: Option<ListSort>,
+    /// Return at most this many entries, or all remaining entries if `None`
+    pub limit: Option<usize>,
+    /// Skip this many entries
+    pub offset: usize,
+}
+
+impl ListQuery {
+    /// Get the `ORDER BY` clause for the query
+    pub fn order_clause(&self) -> String {
+        self.sort
+            .map(ListSort::order_clause)
+            .unwrap_or_else(|| "id ASC".to_owned())
+    }
+
+    /// Get the `LIMIT` value for the query. SQLite treats a negative limit as
+    /// no limit.
+    pub fn sql_limit(&self) -> i64 {
+        self.limit.map(|limit| limit as i64).unwrap_or(-1)
+    }
+
+    /// Get the `OFFSET` value for the query
+    pub fn sql_offset(&self) -> i64 {
+        self.offset as i64
+    }
+}
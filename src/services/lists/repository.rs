@@ -9,31 +9,421 @@
 // Please see LICENSE file for your rights under this license.
 
 use crate::{
-    databases::gravity::GravityDatabase,
-    services::lists::List,
+    databases::gravity::{GravityDatabase, GravitySchema},
+    services::lists::{contains_pattern, List, ListFilter, ListQuery},
     util::{Error, ErrorKind},
 };
-use diesel::{delete, dsl::exists, insert_into, prelude::*, select};
-use failure::ResultExt;
+use diesel::{
+    delete,
+    dsl::{exists, sql},
+    insert_into,
+    prelude::*,
+    result::{DatabaseErrorKind, Error as DieselError},
+    select,
+    sql_types::{BigInt, Text},
+    update,
+};
+use failure::{Fail, ResultExt};
 use shaku::Provider;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A domain on one of the lists, along with its metadata
+#[cfg_attr(test, derive(Debug))]
+#[derive(Queryable, Serialize, Clone, PartialEq)]
+pub struct ListEntry {
+    pub domain: String,
+    pub enabled: bool,
+    pub comment: Option<String>,
+    pub date_added: i32,
+    pub date_modified: i32,
+}
+
+/// A domain to be imported into one of the lists, along with its metadata
+#[cfg_attr(test, derive(Debug))]
+#[derive(Deserialize, Clone, PartialEq)]
+pub struct NewListEntry {
+    pub domain: String,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    pub comment: Option<String>,
+}
+
+/// Changes to make to an existing entry. Fields which are not given are
+/// left unchanged.
+#[cfg_attr(test, derive(Debug))]
+#[derive(Deserialize, Clone, PartialEq, Default)]
+pub struct EntryUpdate {
+    /// The new domain or pattern of the entry
+    pub domain: Option<String>,
+    pub comment: Option<String>,
+    pub enabled: Option<bool>,
+}
+
+/// The number of enabled and disabled entries in a list
+#[cfg_attr(test, derive(Debug))]
+#[derive(Serialize, Copy, Clone, PartialEq, Default)]
+pub struct EntryCounts {
+    pub enabled: usize,
+    pub disabled: usize,
+}
+
+impl EntryCounts {
+    /// Collect the number of entries with each enabled state
+    fn from_states(states: Vec<(bool, i64)>) -> Self {
+        states
+            .into_iter()
+            .fold(EntryCounts::default(), |mut counts, (enabled, count)| {
+                if enabled {
+                    counts.enabled += count as usize;
+                } else {
+                    counts.disabled += count as usize;
+                }
+                counts
+            })
+    }
+}
+
+/// The number of entries in each list, along with the number of domains in
+/// gravity
+#[cfg_attr(test, derive(Debug))]
+#[derive(Serialize, Clone, PartialEq)]
+pub struct ListCounts {
+    pub whitelist: EntryCounts,
+    pub blacklist: EntryCounts,
+    pub regexlist: EntryCounts,
+    pub regex_whitelist: EntryCounts,
+    /// This is `None` if the gravity table does not exist
+    pub gravity: Option<usize>,
+}
+
+/// Imported entries are enabled unless specified otherwise
+fn default_enabled() -> bool {
+    true
+}
 
 /// Describes interactions with the list data store
 #[cfg_attr(test, mockall::automock)]
 pub trait ListRepository: Send {
-    /// Get all of the domains in the list
-    fn get(&self, list: List) -> Result<Vec<String>, Error>;
+    /// Get all of the entries in the list. Disabled entries are only
+    /// included if `include_disabled` is true.
+    fn get(&self, list: List, include_disabled: bool) -> Result<Vec<ListEntry>, Error>;
 
-    /// Check if the list contains the domain
+    /// Get the page of entries in the list selected by the query
+    fn query(&self, list: List, options: &ListQuery) -> Result<Vec<ListEntry>, Error>;
+
+    /// Count the entries in the list which match the filter
+    fn count_filtered(&self, list: List, filter: &ListFilter) -> Result<usize, Error>;
+
+    /// Count the entries in the list
+    fn count(&self, list: List, include_disabled: bool) -> Result<usize, Error>;
+
+    /// Count the enabled and disabled entries in each list, and the domains
+    /// in gravity
+    fn counts(&self) -> Result<ListCounts, Error>;
+
+    /// Get the entries in the list whose domain contains the query, including
+    /// disabled entries
+    fn search(&self, list: List, query: &str) -> Result<Vec<ListEntry>, Error>;
+
+    /// Check if the list contains the domain, whether or not it is enabled
     fn contains(&self, list: List, domain: &str) -> Result<bool, Error>;
 
-    /// Add the domain to the list
-    fn add(&self, list: List, domain: &str) -> Result<(), Error>;
+    /// Get the domain's entry on the list, if it is on the list
+    fn find(&self, list: List, domain: &str) -> Result<Option<ListEntry>, Error>;
+
+    /// Add the domain to the list, with an optional comment
+    fn add(&self, list: List, domain: &str, comment: Option<String>) -> Result<(), Error>;
+
+    /// Add the domains to the list in a single transaction. If one insert
+    /// fails, none of the domains are added.
+    fn add_many(&self, list: List, domains: &[String]) -> Result<(), Error>;
+
+    /// Add the entries to their lists in a single transaction. If `replace`
+    /// is true, the existing entries of each of the given lists are deleted
+    /// first. If anything fails, none of the lists are changed.
+    fn import(&self, lists: &[(List, Vec<NewListEntry>)], replace: bool) -> Result<(), Error>;
 
-    /// Remove the domain from the list
+    /// Lowercase the domains on the list in a single transaction, so that
+    /// databases written before domains were lowercased converge. Entries
+    /// which become duplicates are removed. Regex lists are not changed. The
+    /// number of changed entries is returned.
+    fn normalize_case(&self, list: List) -> Result<usize, Error>;
+
+    /// Remove the domain from the list, along with its group memberships
     fn remove(&self, list: List, domain: &str) -> Result<(), Error>;
+
+    /// Remove the domains from the list in a single transaction, along with
+    /// their group memberships. The domains which were on the list are
+    /// returned.
+    fn remove_many(&self, list: List, domains: &[String]) -> Result<Vec<String>, Error>;
+
+    /// Move the domain from one list to another in a single transaction,
+    /// keeping its enabled state and comment but not its group memberships.
+    /// A `NotFound` error is returned
+    /// if the domain is not on the source list, and a `DomainAlreadyExists`
+    /// error is returned if it is already on the destination list.
+    fn move_domain(&self, from: List, to: List, domain: &str) -> Result<(), Error>;
+
+    /// Enable or disable the domain on the list. A `NotFound` error is
+    /// returned if the domain is not on the list.
+    fn set_enabled(&self, list: List, domain: &str, enabled: bool) -> Result<(), Error>;
+
+    /// Apply the changes to the domain's entry on the list, keeping its date
+    /// added and group memberships. The date modified is updated. A
+    /// `NotFound` error is returned if the domain is not on the list, and a
+    /// `DomainAlreadyExists` error is returned if the new domain is already
+    /// on the list.
+    fn update(&self, list: List, domain: &str, changes: &EntryUpdate) -> Result<(), Error>;
+
+    /// Get the IDs of the groups which the domain on the list is assigned
+    /// to. A `NotFound` error is returned if the domain is not on the list.
+    fn get_groups(&self, list: List, domain: &str) -> Result<Vec<i32>, Error>;
+
+    /// Replace the groups which the domain on the list is assigned to in a
+    /// single transaction. A `NotFound` error is returned if the domain is not
+    /// on the list, and a `GroupNotFound` error is returned if one of the
+    /// groups does not exist.
+    fn set_groups(&self, list: List, domain: &str, group_ids: &[i32]) -> Result<(), Error>;
+
+    /// Get the group memberships of all of the domains on the list, as pairs
+    /// of domain and group ID
+    fn get_memberships(&self, list: List) -> Result<Vec<(String, i32)>, Error>;
+
+    /// Run the operations on the repository inside a single transaction. If
+    /// the operations return an error, all of their changes are rolled back
+    /// and the error is returned.
+    fn with_transaction(&self, operations: &mut dyn ListOperations) -> Result<(), Error>;
+}
+
+/// The operations which `ListRepository::with_transaction` runs inside a
+/// transaction. This is implemented by closures taking the repository.
+pub trait ListOperations {
+    /// Run the operations on the repository
+    fn run(&mut self, repo: &dyn ListRepository) -> Result<(), Error>;
+}
+
+impl<F: FnMut(&dyn ListRepository) -> Result<(), Error>> ListOperations for F {
+    fn run(&mut self, repo: &dyn ListRepository) -> Result<(), Error> {
+        self(repo)
+    }
+}
+
+/// Get the current Unix timestamp, which is used for the date columns of new
+/// entries
+fn current_timestamp() -> i32 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i32
+}
+
+/// Convert an error from inserting the domain into the list. If the domain is
+/// already on the list, an error saying so is returned.
+fn insert_error(error: DieselError, list: List, domain: &str) -> Error {
+    match error {
+        DieselError::DatabaseError(DatabaseErrorKind::UniqueViolation, _) => Error::from(
+            ErrorKind::DomainAlreadyExists(domain.to_owned(), list.name().to_owned()),
+        ),
+        error => Error::from(error.context(ErrorKind::GravityDatabase)),
+    }
+}
+
+/// Get the values of the `enabled` column which should be selected
+fn enabled_states(include_disabled: bool) -> Vec<bool> {
+    if include_disabled {
+        vec![true, false]
+    } else {
+        vec![true]
+    }
+}
+
+/// Run the body with the tables which store the list. `$table` is the table
+/// of the entries, and `$entries` selects the entries of the list from it.
+/// `$by_group` is the table of the group memberships, and `$by_group_entry`
+/// is its column referencing the entry.
+///
+/// Older databases have tables for each list. Newer databases keep every
+/// list in the `domainlist` table, where the `type` column says which list an
+/// entry is on, so the body works with both schemas.
+macro_rules! with_list_tables {
+    (
+        $schema:expr,
+        $list:expr,
+        |$table:ident, $entries:ident, $by_group:ident, $by_group_entry:ident| $body:expr
+    ) => {{
+        let list: List = $list;
+
+        match $schema {
+            GravitySchema::Domainlist => {
+                #[allow(unused_imports)]
+                use crate::databases::gravity::{
+                    domainlist as $table, domainlist_by_group as $by_group,
+                    domainlist_by_group::domainlist_id as $by_group_entry,
+                };
+                #[allow(unused_variables)]
+                let $entries = $table::table.filter($table::type_.eq(list.domainlist_type()));
+
+                $body
+            }
+            GravitySchema::Legacy => {
+                with_legacy_tables!(list, |$table, $entries, $by_group, $by_group_entry| $body)
+            }
+        }
+    }};
+}
+
+/// Run the body with the tables which store the list in older databases,
+/// which have tables for each list. See `with_list_tables`.
+macro_rules! with_legacy_tables {
+    (
+        $list:expr,
+        |$table:ident, $entries:ident, $by_group:ident, $by_group_entry:ident| $body:expr
+    ) => {
+        match $list {
+            List::White => with_legacy_tables!(
+                @tables whitelist, whitelist_by_group, whitelist_id,
+                |$table, $entries, $by_group, $by_group_entry| $body
+            ),
+            List::Black => with_legacy_tables!(
+                @tables blacklist, blacklist_by_group, blacklist_id,
+                |$table, $entries, $by_group, $by_group_entry| $body
+            ),
+            List::Regex => with_legacy_tables!(
+                @tables regex, regex_by_group, regex_id,
+                |$table, $entries, $by_group, $by_group_entry| $body
+            ),
+            List::RegexWhite => with_legacy_tables!(
+                @tables regex_whitelist, regex_whitelist_by_group, regex_whitelist_id,
+                |$table, $entries, $by_group, $by_group_entry| $body
+            ),
+        }
+    };
+    (
+        @tables $table_name:ident, $by_group_name:ident, $entry_column:ident,
+        |$table:ident, $entries:ident, $by_group:ident, $by_group_entry:ident| $body:expr
+    ) => {{
+        #[allow(unused_imports)]
+        use crate::databases::gravity::{
+            $by_group_name as $by_group, $by_group_name::$entry_column as $by_group_entry,
+            $table_name as $table,
+        };
+        #[allow(unused_variables)]
+        let $entries = $table::table;
+
+        $body
+    }};
+}
+
+/// The columns of the table which make up a `ListEntry`
+macro_rules! entry_columns {
+    ($table:ident) => {
+        (
+            $table::domain,
+            $table::enabled,
+            $table::comment,
+            $table::date_added,
+            $table::date_modified,
+        )
+    };
+}
+
+/// Get the ID of the domain's entry on the list
+fn entry_id(
+    db: &SqliteConnection,
+    schema: GravitySchema,
+    list: List,
+    input_domain: &str,
+) -> QueryResult<i32> {
+    with_list_tables!(schema, list, |table, entries, by_group, by_group_entry| {
+        entries
+            .select(table::id)
+            .filter(table::domain.eq(input_domain))
+            .first(db)
+    })
+}
+
+/// Delete the group memberships of the domains on the list. The memberships
+/// reference the list entry, so this must be done before deleting the entry.
+fn delete_memberships(
+    db: &SqliteConnection,
+    schema: GravitySchema,
+    list: List,
+    input_domains: &[String],
+) -> QueryResult<usize> {
+    with_list_tables!(schema, list, |table, entries, by_group, by_group_entry| {
+        delete(
+            by_group::table.filter(
+                by_group_entry.eq_any(
+                    entries
+                        .select(table::id)
+                        .filter(table::domain.eq_any(input_domains)),
+                ),
+            ),
+        )
+        .execute(db)
+    })
+}
+
+/// Delete all of the entries on the list, along with their group memberships
+fn clear_list(db: &SqliteConnection, schema: GravitySchema, list: List) -> QueryResult<usize> {
+    with_list_tables!(schema, list, |table, entries, by_group, by_group_entry| {
+        delete(by_group::table.filter(by_group_entry.eq_any(entries.select(table::id))))
+            .execute(db)?;
+        delete(entries).execute(db)
+    })
+}
+
+/// Add the entries to the list. The dates of the entries are set to now.
+fn insert_entries(
+    db: &SqliteConnection,
+    schema: GravitySchema,
+    list: List,
+    new_entries: &[NewListEntry],
+) -> QueryResult<usize> {
+    let now = current_timestamp();
+
+    match schema {
+        GravitySchema::Domainlist => {
+            use crate::databases::gravity::domainlist;
+            let values: Vec<_> = new_entries
+                .iter()
+                .map(|entry| {
+                    (
+                        domainlist::type_.eq(list.domainlist_type()),
+                        domainlist::domain.eq(&entry.domain),
+                        domainlist::enabled.eq(entry.enabled),
+                        domainlist::comment.eq(&entry.comment),
+                        domainlist::date_added.eq(now),
+                        domainlist::date_modified.eq(now),
+                    )
+                })
+                .collect();
+
+            insert_into(domainlist::table).values(&values).execute(db)
+        }
+        GravitySchema::Legacy => {
+            with_legacy_tables!(list, |table, entries, by_group, by_group_entry| {
+                let values: Vec<_> = new_entries
+                    .iter()
+                    .map(|entry| {
+                        (
+                            table::domain.eq(&entry.domain),
+                            table::enabled.eq(entry.enabled),
+                            table::comment.eq(&entry.comment),
+                            table::date_added.eq(now),
+                            table::date_modified.eq(now),
+                        )
+                    })
+                    .collect();
+
+                insert_into(table::table).values(&values).execute(db)
+            })
+        }
+    }
 }
 
-/// The implementation of `ListRepository`
+/// The implementation of `ListRepository`. The lists are stored according to
+/// the schema of the database.
 #[derive(Provider)]
 #[shaku(interface = ListRepository)]
 pub struct ListRepositoryImpl {
@@ -41,142 +431,560 @@ pub struct ListRepositoryImpl {
     db: Box<GravityDatabase>,
 }
 
+impl ListRepositoryImpl {
+    /// Create a repository using the connection
+    pub fn new(db: Box<GravityDatabase>) -> Self {
+        ListRepositoryImpl { db }
+    }
+
+    /// Count the enabled and disabled entries in the list
+    fn entry_counts(&self, list: List) -> Result<EntryCounts, Error> {
+        let db = &self.db as &SqliteConnection;
+        let schema = self.db.schema;
+
+        with_list_tables!(schema, list, |table, entries, by_group, by_group_entry| {
+            entries
+                .group_by(table::enabled)
+                .select((table::enabled, sql::<BigInt>("COUNT(*)")))
+                .load(db)
+        })
+        .map(EntryCounts::from_states)
+        .context(ErrorKind::GravityDatabase)
+        .map_err(Error::from)
+    }
+}
+
 impl ListRepository for ListRepositoryImpl {
-    fn get(&self, list: List) -> Result<Vec<String>, Error> {
+    fn get(&self, list: List, include_disabled: bool) -> Result<Vec<ListEntry>, Error> {
         let db = &self.db as &SqliteConnection;
+        let schema = self.db.schema;
+
+        with_list_tables!(schema, list, |table, entries, by_group, by_group_entry| {
+            entries
+                .select(entry_columns!(table))
+                .filter(table::enabled.eq_any(enabled_states(include_disabled)))
+                .order(table::id)
+                .load(db)
+        })
+        .context(ErrorKind::GravityDatabase)
+        .map_err(Error::from)
+    }
 
-        match list {
-            List::White => {
-                use crate::databases::gravity::whitelist::dsl::*;
-                whitelist.select(domain).filter(enabled.eq(true)).load(db)
+    fn query(&self, list: List, options: &ListQuery) -> Result<Vec<ListEntry>, Error> {
+        let db = &self.db as &SqliteConnection;
+        let schema = self.db.schema;
+        let filter = &options.filter;
+        let comment_pattern = filter.comment_pattern();
+
+        with_list_tables!(schema, list, |table, entries, by_group, by_group_entry| {
+            let mut query = entries
+                .select(entry_columns!(table))
+                .filter(table::enabled.eq_any(filter.enabled_states()))
+                .into_boxed();
+            if let Some(ref pattern) = comment_pattern {
+                query = query.filter(table::comment.like(pattern).escape('\\'));
             }
-            List::Black => {
-                use crate::databases::gravity::blacklist::dsl::*;
-                blacklist.select(domain).filter(enabled.eq(true)).load(db)
+
+            query
+                .order(sql::<Text>(&options.order_clause()))
+                .limit(options.sql_limit())
+                .offset(options.sql_offset())
+                .load(db)
+        })
+        .context(ErrorKind::GravityDatabase)
+        .map_err(Error::from)
+    }
+
+    fn count_filtered(&self, list: List, filter: &ListFilter) -> Result<usize, Error> {
+        let db = &self.db as &SqliteConnection;
+        let schema = self.db.schema;
+        let comment_pattern = filter.comment_pattern();
+
+        with_list_tables!(schema, list, |table, entries, by_group, by_group_entry| {
+            let mut query = entries
+                .filter(table::enabled.eq_any(filter.enabled_states()))
+                .count()
+                .into_boxed();
+            if let Some(ref pattern) = comment_pattern {
+                query = query.filter(table::comment.like(pattern).escape('\\'));
             }
-            List::Regex => {
-                use crate::databases::gravity::regex::dsl::*;
-                regex.select(domain).filter(enabled.eq(true)).load(db)
+
+            query.get_result::<i64>(db)
+        })
+        .map(|count| count as usize)
+        .context(ErrorKind::GravityDatabase)
+        .map_err(Error::from)
+    }
+
+    fn count(&self, list: List, include_disabled: bool) -> Result<usize, Error> {
+        let db = &self.db as &SqliteConnection;
+        let schema = self.db.schema;
+
+        with_list_tables!(schema, list, |table, entries, by_group, by_group_entry| {
+            entries
+                .filter(table::enabled.eq_any(enabled_states(include_disabled)))
+                .count()
+                .get_result::<i64>(db)
+        })
+        .map(|count| count as usize)
+        .context(ErrorKind::GravityDatabase)
+        .map_err(Error::from)
+    }
+
+    fn counts(&self) -> Result<ListCounts, Error> {
+        use crate::databases::gravity::gravity;
+        let db = &self.db as &SqliteConnection;
+
+        // A domain can be in gravity once for each adlist it is on. The table
+        // does not exist until gravity has been set up.
+        let gravity_count = match gravity::table
+            .select(sql::<diesel::sql_types::BigInt>("COUNT(DISTINCT domain)"))
+            .get_result::<i64>(db)
+        {
+            Ok(count) => Some(count as usize),
+            Err(DieselError::DatabaseError(_, ref info))
+                if info.message().starts_with("no such table") =>
+            {
+                None
             }
-        }
+            Err(e) => return Err(Error::from(e.context(ErrorKind::GravityDatabase))),
+        };
+
+        Ok(ListCounts {
+            whitelist: self.entry_counts(List::White)?,
+            blacklist: self.entry_counts(List::Black)?,
+            regexlist: self.entry_counts(List::Regex)?,
+            regex_whitelist: self.entry_counts(List::RegexWhite)?,
+            gravity: gravity_count,
+        })
+    }
+
+    fn search(&self, list: List, query: &str) -> Result<Vec<ListEntry>, Error> {
+        let db = &self.db as &SqliteConnection;
+        let schema = self.db.schema;
+
+        let pattern = contains_pattern(query);
+
+        with_list_tables!(schema, list, |table, entries, by_group, by_group_entry| {
+            entries
+                .select(entry_columns!(table))
+                .filter(table::domain.like(&pattern).escape('\\'))
+                .order(table::id)
+                .load(db)
+        })
         .context(ErrorKind::GravityDatabase)
         .map_err(Error::from)
     }
 
     fn contains(&self, list: List, input_domain: &str) -> Result<bool, Error> {
         let db = &self.db as &SqliteConnection;
+        let schema = self.db.schema;
+        let input_domain = &list.fold_case(input_domain);
 
-        match list {
-            List::White => {
-                use crate::databases::gravity::whitelist::dsl::*;
-                select(exists(
-                    whitelist
-                        .filter(enabled.eq(true))
-                        .filter(domain.eq(input_domain)),
-                ))
-                .get_result(db)
-            }
-            List::Black => {
-                use crate::databases::gravity::blacklist::dsl::*;
-                select(exists(
-                    blacklist
-                        .filter(enabled.eq(true))
-                        .filter(domain.eq(input_domain)),
-                ))
-                .get_result(db)
-            }
-            List::Regex => {
-                use crate::databases::gravity::regex::dsl::*;
-                select(exists(
-                    regex
-                        .filter(enabled.eq(true))
-                        .filter(domain.eq(input_domain)),
-                ))
-                .get_result(db)
+        with_list_tables!(schema, list, |table, entries, by_group, by_group_entry| {
+            select(exists(entries.filter(table::domain.eq(input_domain)))).get_result(db)
+        })
+        .context(ErrorKind::GravityDatabase)
+        .map_err(Error::from)
+    }
+
+    fn find(&self, list: List, input_domain: &str) -> Result<Option<ListEntry>, Error> {
+        let db = &self.db as &SqliteConnection;
+        let schema = self.db.schema;
+
+        with_list_tables!(schema, list, |table, entries, by_group, by_group_entry| {
+            entries
+                .select(entry_columns!(table))
+                .filter(table::domain.eq(input_domain))
+                .first(db)
+                .optional()
+        })
+        .context(ErrorKind::GravityDatabase)
+        .map_err(Error::from)
+    }
+
+    fn add(
+        &self,
+        list: List,
+        input_domain: &str,
+        input_comment: Option<String>,
+    ) -> Result<(), Error> {
+        let db = &self.db as &SqliteConnection;
+        let schema = self.db.schema;
+        let new_entry = NewListEntry {
+            domain: input_domain.to_owned(),
+            enabled: true,
+            comment: input_comment,
+        };
+
+        insert_entries(db, schema, list, &[new_entry])
+            .map_err(|e| insert_error(e, list, input_domain))?;
+
+        Ok(())
+    }
+
+    fn add_many(&self, list: List, input_domains: &[String]) -> Result<(), Error> {
+        let db = &self.db as &SqliteConnection;
+        let schema = self.db.schema;
+        let new_entries: Vec<NewListEntry> = input_domains
+            .iter()
+            .map(|input_domain| NewListEntry {
+                domain: input_domain.to_owned(),
+                enabled: true,
+                comment: None,
+            })
+            .collect();
+
+        db.transaction(|| insert_entries(db, schema, list, &new_entries))
+            .context(ErrorKind::GravityDatabase)?;
+
+        Ok(())
+    }
+
+    fn import(&self, lists: &[(List, Vec<NewListEntry>)], replace: bool) -> Result<(), Error> {
+        let db = &self.db as &SqliteConnection;
+        let schema = self.db.schema;
+
+        db.transaction::<_, DieselError, _>(|| {
+            for (list, new_entries) in lists {
+                if replace {
+                    clear_list(db, schema, *list)?;
+                }
+
+                insert_entries(db, schema, *list, new_entries)?;
             }
+
+            Ok(())
+        })
+        .context(ErrorKind::GravityDatabase)?;
+
+        Ok(())
+    }
+
+    fn normalize_case(&self, list: List) -> Result<usize, Error> {
+        if list.is_regex() {
+            return Ok(0);
         }
+
+        let mut changed = 0;
+
+        self.with_transaction(&mut |repo: &dyn ListRepository| {
+            changed = 0;
+
+            for entry in repo.get(list, true)? {
+                let lowercase = list.fold_case(&entry.domain);
+                if lowercase == entry.domain {
+                    continue;
+                }
+
+                // Keep the entry which is already lowercase. Removing by the
+                // exact domain avoids removing the lowercase entry instead.
+                if repo.find(list, &lowercase)?.is_some() {
+                    repo.remove_many(list, &[entry.domain])?;
+                } else {
+                    repo.update(
+                        list,
+                        &entry.domain,
+                        &EntryUpdate {
+                            domain: Some(lowercase),
+                            ..EntryUpdate::default()
+                        },
+                    )?;
+                }
+
+                changed += 1;
+            }
+
+            Ok(())
+        })?;
+
+        Ok(changed)
+    }
+
+    fn remove(&self, list: List, input_domain: &str) -> Result<(), Error> {
+        let input_domain = list.fold_case(input_domain);
+
+        self.remove_many(list, &[input_domain])?;
+
+        Ok(())
+    }
+
+    fn remove_many(&self, list: List, input_domains: &[String]) -> Result<Vec<String>, Error> {
+        let db = &self.db as &SqliteConnection;
+        let schema = self.db.schema;
+
+        db.transaction::<_, DieselError, _>(|| {
+            let removed =
+                with_list_tables!(schema, list, |table, entries, by_group, by_group_entry| {
+                    entries
+                        .select(table::domain)
+                        .filter(table::domain.eq_any(input_domains))
+                        .order(table::id)
+                        .load(db)
+                })?;
+
+            delete_memberships(db, schema, list, input_domains)?;
+            with_list_tables!(schema, list, |table, entries, by_group, by_group_entry| {
+                delete(entries.filter(table::domain.eq_any(input_domains))).execute(db)
+            })?;
+
+            Ok(removed)
+        })
         .context(ErrorKind::GravityDatabase)
         .map_err(Error::from)
     }
 
-    fn add(&self, list: List, input_domain: &str) -> Result<(), Error> {
+    fn move_domain(&self, from: List, to: List, input_domain: &str) -> Result<(), Error> {
         let db = &self.db as &SqliteConnection;
+        let schema = self.db.schema;
 
-        match list {
-            List::White => {
-                use crate::databases::gravity::whitelist::dsl::*;
-                insert_into(whitelist)
-                    .values(&(domain.eq(input_domain), enabled.eq(true)))
-                    .execute(db)
-            }
-            List::Black => {
-                use crate::databases::gravity::blacklist::dsl::*;
-                insert_into(blacklist)
-                    .values(&(domain.eq(input_domain), enabled.eq(true)))
+        db.transaction(|| {
+            // Take the entry off of the source list. If it is not there, this
+            // fails with a not found error. The group memberships belong to
+            // the source list, so they are not moved.
+            delete_memberships(db, schema, from, &[input_domain.to_owned()])?;
+            let (enabled, comment) =
+                with_list_tables!(schema, from, |table, entries, by_group, by_group_entry| {
+                    let entry = entries
+                        .select((table::enabled, table::comment))
+                        .filter(table::domain.eq(input_domain))
+                        .first(db)?;
+                    delete(entries.filter(table::domain.eq(input_domain))).execute(db)?;
+                    entry
+                });
+
+            // Put it on the destination list. If it is already there, this
+            // fails with a unique violation and the delete is rolled back.
+            let new_entry = NewListEntry {
+                domain: input_domain.to_owned(),
+                enabled,
+                comment,
+            };
+            insert_entries(db, schema, to, &[new_entry])
+        })
+        .map_err(|e| match e {
+            DieselError::NotFound => Error::from(ErrorKind::NotFound),
+            e => insert_error(e, to, input_domain),
+        })?;
+
+        Ok(())
+    }
+
+    fn set_enabled(
+        &self,
+        list: List,
+        input_domain: &str,
+        input_enabled: bool,
+    ) -> Result<(), Error> {
+        let db = &self.db as &SqliteConnection;
+        let schema = self.db.schema;
+
+        let updated =
+            with_list_tables!(schema, list, |table, entries, by_group, by_group_entry| {
+                update(entries.filter(table::domain.eq(input_domain)))
+                    .set(table::enabled.eq(input_enabled))
                     .execute(db)
-            }
-            List::Regex => {
-                use crate::databases::gravity::regex::dsl::*;
-                insert_into(regex)
-                    .values(&(domain.eq(input_domain), enabled.eq(true)))
+            })
+            .context(ErrorKind::GravityDatabase)?;
+
+        if updated == 0 {
+            return Err(Error::from(ErrorKind::NotFound));
+        }
+
+        Ok(())
+    }
+
+    fn update(&self, list: List, input_domain: &str, changes: &EntryUpdate) -> Result<(), Error> {
+        let db = &self.db as &SqliteConnection;
+        let schema = self.db.schema;
+        let now = current_timestamp();
+
+        let updated =
+            with_list_tables!(schema, list, |table, entries, by_group, by_group_entry| {
+                update(entries.filter(table::domain.eq(input_domain)))
+                    .set((
+                        changes
+                            .domain
+                            .as_ref()
+                            .map(|new_domain| table::domain.eq(new_domain)),
+                        changes
+                            .comment
+                            .as_ref()
+                            .map(|new_comment| table::comment.eq(new_comment)),
+                        changes
+                            .enabled
+                            .map(|new_enabled| table::enabled.eq(new_enabled)),
+                        table::date_modified.eq(now),
+                    ))
                     .execute(db)
-            }
+            })
+            .map_err(|e| {
+                insert_error(e, list, changes.domain.as_deref().unwrap_or(input_domain))
+            })?;
+
+        if updated == 0 {
+            return Err(Error::from(ErrorKind::NotFound));
         }
-        .context(ErrorKind::GravityDatabase)?;
 
         Ok(())
     }
 
-    fn remove(&self, list: List, input_domain: &str) -> Result<(), Error> {
+    fn get_groups(&self, list: List, input_domain: &str) -> Result<Vec<i32>, Error> {
         let db = &self.db as &SqliteConnection;
+        let schema = self.db.schema;
 
-        match list {
-            List::White => {
-                use crate::databases::gravity::whitelist::dsl::*;
-                delete(
-                    whitelist
-                        .filter(enabled.eq(true))
-                        .filter(domain.eq(input_domain)),
-                )
-                .execute(db)
-            }
-            List::Black => {
-                use crate::databases::gravity::blacklist::dsl::*;
-                delete(
-                    blacklist
-                        .filter(enabled.eq(true))
-                        .filter(domain.eq(input_domain)),
-                )
-                .execute(db)
-            }
-            List::Regex => {
-                use crate::databases::gravity::regex::dsl::*;
-                delete(
-                    regex
-                        .filter(enabled.eq(true))
-                        .filter(domain.eq(input_domain)),
-                )
-                .execute(db)
-            }
+        let entry = entry_id(db, schema, list, input_domain)
+            .optional()
+            .context(ErrorKind::GravityDatabase)?
+            .ok_or_else(|| Error::from(ErrorKind::NotFound))?;
+
+        with_list_tables!(schema, list, |table, entries, by_group, by_group_entry| {
+            by_group::table
+                .select(by_group::group_id)
+                .filter(by_group_entry.eq(entry))
+                .order(by_group::group_id)
+                .load(db)
+        })
+        .context(ErrorKind::GravityDatabase)
+        .map_err(Error::from)
+    }
+
+    fn set_groups(&self, list: List, input_domain: &str, group_ids: &[i32]) -> Result<(), Error> {
+        let db = &self.db as &SqliteConnection;
+        let schema = self.db.schema;
+
+        let mut group_ids = group_ids.to_vec();
+        group_ids.sort_unstable();
+        group_ids.dedup();
+
+        // Make sure all of the groups exist before changing anything
+        let existing_ids: Vec<i32> = {
+            use crate::databases::gravity::group::dsl::*;
+            group
+                .select(id)
+                .filter(id.eq_any(&group_ids))
+                .load(db)
+                .context(ErrorKind::GravityDatabase)?
+        };
+        if let Some(missing_id) = group_ids.iter().find(|id| !existing_ids.contains(*id)) {
+            return Err(Error::from(ErrorKind::GroupNotFound(*missing_id)));
         }
-        .context(ErrorKind::GravityDatabase)?;
+
+        db.transaction(|| {
+            let entry = entry_id(db, schema, list, input_domain)?;
+
+            with_list_tables!(schema, list, |table, entries, by_group, by_group_entry| {
+                delete(by_group::table.filter(by_group_entry.eq(entry))).execute(db)?;
+
+                let values: Vec<_> = group_ids
+                    .iter()
+                    .map(|input_group| {
+                        (by_group_entry.eq(entry), by_group::group_id.eq(input_group))
+                    })
+                    .collect();
+                insert_into(by_group::table).values(&values).execute(db)
+            })
+        })
+        .map_err(|e| match e {
+            DieselError::NotFound => Error::from(ErrorKind::NotFound),
+            e => Error::from(e.context(ErrorKind::GravityDatabase)),
+        })?;
 
         Ok(())
     }
+
+    fn get_memberships(&self, list: List) -> Result<Vec<(String, i32)>, Error> {
+        let db = &self.db as &SqliteConnection;
+        let schema = self.db.schema;
+
+        with_list_tables!(schema, list, |table, entries, by_group, by_group_entry| {
+            entries
+                .inner_join(by_group::table)
+                .select((table::domain, by_group::group_id))
+                .order(by_group::group_id)
+                .load(db)
+        })
+        .context(ErrorKind::GravityDatabase)
+        .map_err(Error::from)
+    }
+
+    fn with_transaction(&self, operations: &mut dyn ListOperations) -> Result<(), Error> {
+        let db = &self.db as &SqliteConnection;
+        let mut operation_error = None;
+
+        // The repository methods all use the same connection, so they run
+        // inside this transaction (nested transactions become savepoints)
+        let result = db.transaction::<_, DieselError, _>(|| {
+            operations.run(self).map_err(|e| {
+                operation_error = Some(e);
+                DieselError::RollbackTransaction
+            })
+        });
+
+        match (result, operation_error) {
+            (Ok(()), _) => Ok(()),
+            (Err(_), Some(e)) => Err(e),
+            (Err(e), None) => Err(Error::from(e.context(ErrorKind::GravityDatabase))),
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{ListRepository, ListRepositoryImpl};
-    use crate::{databases::gravity::connect_to_gravity_test_db, services::lists::List};
+    use super::{
+        current_timestamp, EntryCounts, EntryUpdate, ListCounts, ListEntry, ListRepository,
+        ListRepositoryImpl, NewListEntry,
+    };
+    use crate::{
+        databases::gravity::connect_to_gravity_test_db,
+        services::lists::{List, ListFilter, ListQuery, ListSort, SortKey, SortOrder},
+        util::ErrorKind,
+    };
+
+    /// Get the domains on the list
+    fn get_domains(repo: &ListRepositoryImpl, list: List) -> Vec<String> {
+        repo.get(list, false)
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.domain)
+            .collect()
+    }
 
-    /// Assert that the list of domains retrieved from the database equals the
+    /// Assert that the list of entries retrieved from the database equals the
     /// expected list
-    fn get_test(list: List, expected_domains: Vec<String>) {
+    fn get_test(list: List, expected_entries: Vec<ListEntry>) {
+        let db = connect_to_gravity_test_db();
+        let repo = ListRepositoryImpl::new(db);
+
+        let actual_entries = repo.get(list, false).unwrap();
+
+        assert_eq!(actual_entries, expected_entries);
+    }
+
+    /// Assert that the page of domains retrieved from the database equals
+    /// the expected domains
+    fn query_paged_test(
+        list: List,
+        limit: Option<usize>,
+        offset: usize,
+        expected_domains: Vec<&str>,
+    ) {
         let db = connect_to_gravity_test_db();
-        let repo = ListRepositoryImpl { db };
+        let repo = ListRepositoryImpl::new(db);
+        let domains = vec!["a.com".to_owned(), "b.com".to_owned()];
+        repo.add_many(list, &domains).unwrap();
+        let query = ListQuery {
+            limit,
+            offset,
+            ..ListQuery::default()
+        };
 
-        let actual_domains = repo.get(list).unwrap();
+        let actual_domains: Vec<String> = repo
+            .query(list, &query)
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.domain)
+            .collect();
 
         assert_eq!(actual_domains, expected_domains);
     }
@@ -184,7 +992,7 @@ mod tests {
     /// Assert that the list contains the given domain
     fn contains_test(list: List, domain: &str) {
         let db = connect_to_gravity_test_db();
-        let repo = ListRepositoryImpl { db };
+        let repo = ListRepositoryImpl::new(db);
 
         assert!(repo.contains(list, domain).unwrap())
     }
@@ -192,41 +1000,311 @@ mod tests {
     /// Assert that adding a domain not already on the list works
     fn add_test(list: List, domain: &str) {
         let db = connect_to_gravity_test_db();
-        let repo = ListRepositoryImpl { db };
+        let repo = ListRepositoryImpl::new(db);
 
         // Make sure it doesn't exist already
-        let initial_domains = repo.get(list).unwrap();
+        let initial_domains = get_domains(&repo, list);
         assert!(!initial_domains.contains(&domain.to_owned()));
 
-        repo.add(list, domain).unwrap();
+        repo.add(list, domain, None).unwrap();
 
         // Make sure it was added
-        let domains = repo.get(list).unwrap();
+        let domains = get_domains(&repo, list);
         assert!(domains.contains(&domain.to_owned()));
     }
 
     /// Assert that deleting a domain from the list works
     fn delete_test(list: List, domain: &str) {
         let db = connect_to_gravity_test_db();
-        let repo = ListRepositoryImpl { db };
+        let repo = ListRepositoryImpl::new(db);
 
         // Make sure the domain is on the list
-        let domains = repo.get(list).unwrap();
+        let domains = get_domains(&repo, list);
         assert!(domains.contains(&domain.to_owned()));
 
         repo.remove(list, domain).unwrap();
 
         // Make sure it was removed
-        let domains = repo.get(list).unwrap();
+        let domains = get_domains(&repo, list);
         assert!(!domains.contains(&domain.to_owned()));
     }
 
-    /// Getting the lists should return the expected domains
+    /// Getting the lists should return the expected entries
     #[test]
     fn get() {
-        get_test(List::White, vec!["test.com".to_owned()]);
-        get_test(List::Black, vec!["example.com".to_owned()]);
-        get_test(List::Regex, vec!["(^|\\.)example\\.com$".to_owned()]);
+        get_test(
+            List::White,
+            vec![ListEntry {
+                domain: "test.com".to_owned(),
+                enabled: true,
+                comment: None,
+                date_added: 1_557_712_172,
+                date_modified: 1_557_712_172,
+            }],
+        );
+        get_test(
+            List::Black,
+            vec![ListEntry {
+                domain: "example.com".to_owned(),
+                enabled: true,
+                comment: None,
+                date_added: 1_557_712_177,
+                date_modified: 1_557_712_177,
+            }],
+        );
+        get_test(
+            List::Regex,
+            vec![ListEntry {
+                domain: "(^|\\.)example\\.com$".to_owned(),
+                enabled: true,
+                comment: None,
+                date_added: 1_557_712_181,
+                date_modified: 1_557_712_181,
+            }],
+        );
+    }
+
+    /// Disabled entries are included when requested
+    #[test]
+    fn get_disabled() {
+        let db = connect_to_gravity_test_db();
+        let repo = ListRepositoryImpl::new(db);
+
+        let entries = repo.get(List::White, true).unwrap();
+
+        assert_eq!(
+            entries
+                .iter()
+                .map(|entry| (entry.domain.as_str(), entry.enabled))
+                .collect::<Vec<_>>(),
+            vec![("test.com", true), ("disabled-white.com", false)]
+        );
+    }
+
+    /// The limit and offset select a page of the list
+    #[test]
+    fn query_paged() {
+        query_paged_test(List::Black, Some(2), 0, vec!["example.com", "a.com"]);
+        query_paged_test(List::Black, Some(2), 1, vec!["a.com", "b.com"]);
+        query_paged_test(List::Black, Some(2), 3, vec![]);
+        query_paged_test(List::White, None, 1, vec!["a.com", "b.com"]);
+    }
+
+    /// Sorted entries are ordered by the column, and then by ID so that
+    /// pages are stable
+    #[test]
+    fn query_sorted() {
+        let db = connect_to_gravity_test_db();
+        let repo = ListRepositoryImpl::new(db);
+        repo.add(List::Black, "ads.com", None).unwrap();
+        let query = ListQuery {
+            filter: ListFilter {
+                include_disabled: true,
+                ..ListFilter::default()
+            },
+            sort: Some(ListSort {
+                key: SortKey::DateAdded,
+                order: SortOrder::Desc,
+            }),
+            limit: Some(2),
+            offset: 1,
+        };
+
+        let domains: Vec<String> = repo
+            .query(List::Black, &query)
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.domain)
+            .collect();
+
+        assert_eq!(domains, vec!["disabled-black.com", "example.com"]);
+    }
+
+    /// The filters are combined, and the count only includes the matching
+    /// entries
+    #[test]
+    fn query_filtered() {
+        let db = connect_to_gravity_test_db();
+        let repo = ListRepositoryImpl::new(db);
+        repo.add(List::Black, "ads.com", Some("Imported list".to_owned()))
+            .unwrap();
+        repo.add(List::Black, "tracker.com", Some("Imported list".to_owned()))
+            .unwrap();
+        repo.add(List::Black, "other.com", Some("Manual".to_owned()))
+            .unwrap();
+        repo.set_enabled(List::Black, "tracker.com", false).unwrap();
+        let query = ListQuery {
+            filter: ListFilter {
+                include_disabled: false,
+                enabled: Some(false),
+                comment_contains: Some("imported".to_owned()),
+            },
+            ..ListQuery::default()
+        };
+
+        let domains: Vec<String> = repo
+            .query(List::Black, &query)
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.domain)
+            .collect();
+
+        assert_eq!(domains, vec!["tracker.com"]);
+        assert_eq!(repo.count_filtered(List::Black, &query.filter).unwrap(), 1);
+    }
+
+    /// Updating an entry keeps its date added and groups, and bumps its date
+    /// modified
+    #[test]
+    fn update() {
+        let db = connect_to_gravity_test_db();
+        let repo = ListRepositoryImpl::new(db);
+        repo.set_groups(List::Black, "disabled-black.com", &[1])
+            .unwrap();
+
+        repo.update(
+            List::Black,
+            "disabled-black.com",
+            &EntryUpdate {
+                domain: Some("renamed-black.com".to_owned()),
+                comment: Some("Fixed typo".to_owned()),
+                enabled: Some(true),
+            },
+        )
+        .unwrap();
+
+        let entry = repo
+            .find(List::Black, "renamed-black.com")
+            .unwrap()
+            .unwrap();
+        assert!(entry.enabled);
+        assert_eq!(entry.comment, Some("Fixed typo".to_owned()));
+        assert_eq!(entry.date_added, 1_557_723_854);
+        assert!(entry.date_modified >= current_timestamp() - 1);
+        assert_eq!(
+            repo.get_groups(List::Black, "renamed-black.com").unwrap(),
+            vec![1]
+        );
+    }
+
+    /// Renaming an entry to a domain which is already on the list is an
+    /// error, and updating a missing entry is not found
+    #[test]
+    fn update_errors() {
+        let db = connect_to_gravity_test_db();
+        let repo = ListRepositoryImpl::new(db);
+
+        assert_eq!(
+            repo.update(
+                List::Black,
+                "disabled-black.com",
+                &EntryUpdate {
+                    domain: Some("example.com".to_owned()),
+                    ..EntryUpdate::default()
+                },
+            )
+            .unwrap_err()
+            .kind(),
+            ErrorKind::DomainAlreadyExists("example.com".to_owned(), "blacklist".to_owned())
+        );
+        assert_eq!(
+            repo.update(List::Black, "missing.com", &EntryUpdate::default())
+                .unwrap_err()
+                .kind(),
+            ErrorKind::NotFound
+        );
+    }
+
+    /// Looking up and removing a domain ignores its case
+    #[test]
+    fn case_insensitive() {
+        let db = connect_to_gravity_test_db();
+        let repo = ListRepositoryImpl::new(db);
+
+        assert!(repo.contains(List::Black, "EXAMPLE.com").unwrap());
+        repo.remove(List::Black, "Example.Com").unwrap();
+        assert!(!repo.contains(List::Black, "example.com").unwrap());
+    }
+
+    /// Mixed case entries are lowercased, and the ones which duplicate an
+    /// existing entry are removed
+    #[test]
+    fn normalize_case() {
+        let db = connect_to_gravity_test_db();
+        let repo = ListRepositoryImpl::new(db);
+        repo.add(List::Black, "Example.com", None).unwrap();
+        repo.add(List::Black, "Ads.COM", None).unwrap();
+        repo.add(List::Black, "ADS.com", None).unwrap();
+
+        assert_eq!(repo.normalize_case(List::Black).unwrap(), 3);
+        assert_eq!(
+            repo.get(List::Black, true)
+                .unwrap()
+                .into_iter()
+                .map(|entry| entry.domain)
+                .collect::<Vec<_>>(),
+            vec!["example.com", "disabled-black.com", "ads.com"]
+        );
+        assert_eq!(repo.normalize_case(List::Black).unwrap(), 0);
+    }
+
+    /// Disabled entries are not counted
+    #[test]
+    fn count() {
+        let db = connect_to_gravity_test_db();
+        let repo = ListRepositoryImpl::new(db);
+
+        assert_eq!(repo.count(List::White, false).unwrap(), 1);
+        assert_eq!(repo.count(List::Black, false).unwrap(), 1);
+        assert_eq!(repo.count(List::Regex, false).unwrap(), 1);
+    }
+
+    /// Disabled entries are counted when requested
+    #[test]
+    fn count_disabled() {
+        let db = connect_to_gravity_test_db();
+        let repo = ListRepositoryImpl::new(db);
+
+        assert_eq!(repo.count(List::White, true).unwrap(), 2);
+        assert_eq!(repo.count(List::Black, true).unwrap(), 2);
+        assert_eq!(repo.count(List::Regex, true).unwrap(), 2);
+    }
+
+    /// Assert that searching the list returns the expected domains
+    fn search_test(list: List, query: &str, expected_domains: Vec<&str>) {
+        let db = connect_to_gravity_test_db();
+        let repo = ListRepositoryImpl::new(db);
+
+        let actual_domains: Vec<String> = repo
+            .search(list, query)
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.domain)
+            .collect();
+
+        assert_eq!(actual_domains, expected_domains);
+    }
+
+    /// Searching returns the entries containing the query, including disabled
+    /// entries
+    #[test]
+    fn search() {
+        search_test(List::White, "test", vec!["test.com"]);
+        search_test(
+            List::Black,
+            ".com",
+            vec!["example.com", "disabled-black.com"],
+        );
+        search_test(List::Regex, "example", vec!["(^|\\.)example\\.com$"]);
+        search_test(List::Black, "not-found", vec![]);
+    }
+
+    /// LIKE wildcards and backslashes in the query are matched literally
+    #[test]
+    fn search_escaped() {
+        search_test(List::White, "%", vec![]);
+        search_test(List::White, "_", vec![]);
+        search_test(List::Regex, "\\-", vec!["disabled\\-regex\\.com"]);
     }
 
     /// Assert that checking for an existing domain works
@@ -245,6 +1323,185 @@ mod tests {
         add_test(List::Regex, "regex.com");
     }
 
+    /// Adding a domain which is already on the list is an already exists
+    /// error
+    #[test]
+    fn add_duplicate() {
+        let db = connect_to_gravity_test_db();
+        let repo = ListRepositoryImpl::new(db);
+
+        let error = repo.add(List::Black, "example.com", None).unwrap_err();
+
+        assert_eq!(
+            error.kind(),
+            ErrorKind::DomainAlreadyExists("example.com".to_owned(), "blacklist".to_owned())
+        );
+    }
+
+    /// The comment is stored along with the domain
+    #[test]
+    fn add_with_comment() {
+        let db = connect_to_gravity_test_db();
+        let repo = ListRepositoryImpl::new(db);
+
+        repo.add(List::Black, "blacklist.com", Some("Ads".to_owned()))
+            .unwrap();
+
+        let entry = repo
+            .get(List::Black, false)
+            .unwrap()
+            .into_iter()
+            .find(|entry| entry.domain == "blacklist.com")
+            .unwrap();
+        assert_eq!(entry.comment, Some("Ads".to_owned()));
+    }
+
+    /// The dates of a new entry are set to the current time
+    #[test]
+    fn add_sets_dates() {
+        let db = connect_to_gravity_test_db();
+        let repo = ListRepositoryImpl::new(db);
+        let before = current_timestamp();
+
+        repo.add(List::White, "whitelist.com", None).unwrap();
+
+        let entry = repo
+            .get(List::White, false)
+            .unwrap()
+            .into_iter()
+            .find(|entry| entry.domain == "whitelist.com")
+            .unwrap();
+        assert!(entry.date_added >= before);
+        assert_eq!(entry.date_modified, entry.date_added);
+    }
+
+    /// Adding multiple domains adds all of them
+    #[test]
+    fn add_many_new() {
+        let db = connect_to_gravity_test_db();
+        let repo = ListRepositoryImpl::new(db);
+        let domains = vec!["a.com".to_owned(), "b.com".to_owned()];
+
+        repo.add_many(List::Black, &domains).unwrap();
+
+        assert_eq!(
+            get_domains(&repo, List::Black),
+            vec![
+                "example.com".to_owned(),
+                "a.com".to_owned(),
+                "b.com".to_owned()
+            ]
+        );
+    }
+
+    /// If one of the domains can not be added, none of them are added
+    #[test]
+    fn add_many_rollback() {
+        let db = connect_to_gravity_test_db();
+        let repo = ListRepositoryImpl::new(db);
+        let domains = vec!["a.com".to_owned(), "example.com".to_owned()];
+
+        assert!(repo.add_many(List::Black, &domains).is_err());
+        assert_eq!(
+            get_domains(&repo, List::Black),
+            vec!["example.com".to_owned()]
+        );
+    }
+
+    /// Create an enabled entry to import
+    fn new_entry(domain: &str) -> NewListEntry {
+        NewListEntry {
+            domain: domain.to_owned(),
+            enabled: true,
+            comment: None,
+        }
+    }
+
+    /// Importing in merge mode keeps the existing entries
+    #[test]
+    fn import_merge() {
+        let db = connect_to_gravity_test_db();
+        let repo = ListRepositoryImpl::new(db);
+
+        repo.import(
+            &[
+                (List::White, vec![new_entry("a.com")]),
+                (List::Black, vec![new_entry("b.com"), new_entry("c.com")]),
+            ],
+            false,
+        )
+        .unwrap();
+
+        assert_eq!(
+            get_domains(&repo, List::White),
+            vec!["test.com".to_owned(), "a.com".to_owned()]
+        );
+        assert_eq!(
+            get_domains(&repo, List::Black),
+            vec![
+                "example.com".to_owned(),
+                "b.com".to_owned(),
+                "c.com".to_owned()
+            ]
+        );
+    }
+
+    /// Importing in replace mode deletes the existing entries, including
+    /// disabled entries
+    #[test]
+    fn import_replace() {
+        let db = connect_to_gravity_test_db();
+        let repo = ListRepositoryImpl::new(db);
+        let disabled_entry = NewListEntry {
+            domain: "b.com".to_owned(),
+            enabled: false,
+            comment: Some("Disabled".to_owned()),
+        };
+
+        repo.import(
+            &[(List::Black, vec![new_entry("a.com"), disabled_entry])],
+            true,
+        )
+        .unwrap();
+
+        let entries: Vec<(String, bool, Option<String>)> = repo
+            .get(List::Black, true)
+            .unwrap()
+            .into_iter()
+            .map(|entry| (entry.domain, entry.enabled, entry.comment))
+            .collect();
+        assert_eq!(
+            entries,
+            vec![
+                ("a.com".to_owned(), true, None),
+                ("b.com".to_owned(), false, Some("Disabled".to_owned()))
+            ]
+        );
+
+        // Lists which were not imported are left alone
+        assert_eq!(repo.count(List::White, true).unwrap(), 2);
+    }
+
+    /// If one of the inserts fails, the lists are not wiped
+    #[test]
+    fn import_rollback() {
+        let db = connect_to_gravity_test_db();
+        let repo = ListRepositoryImpl::new(db);
+
+        assert!(repo
+            .import(
+                &[
+                    (List::White, vec![new_entry("a.com")]),
+                    (List::Black, vec![new_entry("b.com"), new_entry("b.com")]),
+                ],
+                true,
+            )
+            .is_err());
+
+        assert_eq!(repo.count(List::White, true).unwrap(), 2);
+        assert_eq!(repo.count(List::Black, true).unwrap(), 2);
+    }
+
     /// Deleting existing domains from the lists should work
     #[test]
     fn delete_existing() {
@@ -252,4 +1509,648 @@ mod tests {
         delete_test(List::Black, "example.com");
         delete_test(List::Regex, "(^|\\.)example\\.com$");
     }
+
+    /// Disabled domains are still on the list
+    #[test]
+    fn contains_disabled() {
+        contains_test(List::White, "disabled-white.com");
+        contains_test(List::Black, "disabled-black.com");
+        contains_test(List::Regex, "disabled\\-regex\\.com");
+    }
+
+    /// Disabling a domain hides it from the list without deleting it
+    #[test]
+    fn set_enabled_disable() {
+        let db = connect_to_gravity_test_db();
+        let repo = ListRepositoryImpl::new(db);
+
+        repo.set_enabled(List::Black, "example.com", false).unwrap();
+
+        assert!(get_domains(&repo, List::Black).is_empty());
+        assert!(repo.contains(List::Black, "example.com").unwrap());
+    }
+
+    /// Enabling a disabled domain adds it back to the list
+    #[test]
+    fn set_enabled_enable() {
+        let db = connect_to_gravity_test_db();
+        let repo = ListRepositoryImpl::new(db);
+
+        repo.set_enabled(List::White, "disabled-white.com", true)
+            .unwrap();
+
+        assert_eq!(
+            get_domains(&repo, List::White),
+            vec!["test.com".to_owned(), "disabled-white.com".to_owned()]
+        );
+    }
+
+    /// Moving a domain takes it off of the source list and puts it on the
+    /// destination list with the same state
+    #[test]
+    fn move_domain() {
+        let db = connect_to_gravity_test_db();
+        let repo = ListRepositoryImpl::new(db);
+
+        repo.move_domain(List::Black, List::White, "disabled-black.com")
+            .unwrap();
+
+        assert_eq!(repo.count(List::Black, true).unwrap(), 1);
+        let entry = repo
+            .get(List::White, true)
+            .unwrap()
+            .into_iter()
+            .find(|entry| entry.domain == "disabled-black.com")
+            .unwrap();
+        assert!(!entry.enabled);
+    }
+
+    /// Moving a domain which is not on the source list is a not found error
+    #[test]
+    fn move_domain_not_found() {
+        let db = connect_to_gravity_test_db();
+        let repo = ListRepositoryImpl::new(db);
+
+        let error = repo
+            .move_domain(List::Black, List::White, "not-found.com")
+            .unwrap_err();
+
+        assert_eq!(error.kind(), ErrorKind::NotFound);
+        assert_eq!(repo.count(List::White, true).unwrap(), 2);
+    }
+
+    /// If the domain is already on the destination list, the insert fails and
+    /// the domain is not removed from the source list
+    #[test]
+    fn move_domain_rollback() {
+        let db = connect_to_gravity_test_db();
+        let repo = ListRepositoryImpl::new(db);
+        repo.add(List::White, "example.com", None).unwrap();
+
+        let error = repo
+            .move_domain(List::Black, List::White, "example.com")
+            .unwrap_err();
+
+        assert_eq!(
+            error.kind(),
+            ErrorKind::DomainAlreadyExists("example.com".to_owned(), "whitelist".to_owned())
+        );
+        assert!(repo.contains(List::Black, "example.com").unwrap());
+        assert!(repo.contains(List::White, "example.com").unwrap());
+    }
+
+    /// Changing a domain which is not on the list is a not found error
+    #[test]
+    fn set_enabled_not_found() {
+        let db = connect_to_gravity_test_db();
+        let repo = ListRepositoryImpl::new(db);
+
+        let error = repo
+            .set_enabled(List::Regex, "not-found.com", true)
+            .unwrap_err();
+
+        assert_eq!(error.kind(), ErrorKind::NotFound);
+    }
+
+    /// The groups of a domain are replaced, and can be read back
+    #[test]
+    fn set_groups() {
+        let db = connect_to_gravity_test_db();
+        let repo = ListRepositoryImpl::new(db);
+
+        assert_eq!(
+            repo.get_groups(List::Black, "example.com").unwrap(),
+            Vec::<i32>::new()
+        );
+
+        repo.set_groups(List::Black, "example.com", &[1, 1])
+            .unwrap();
+        assert_eq!(
+            repo.get_groups(List::Black, "example.com").unwrap(),
+            vec![1]
+        );
+        assert_eq!(
+            repo.get_memberships(List::Black).unwrap(),
+            vec![("example.com".to_owned(), 1)]
+        );
+
+        repo.set_groups(List::Black, "example.com", &[]).unwrap();
+        assert_eq!(
+            repo.get_groups(List::Black, "example.com").unwrap(),
+            Vec::<i32>::new()
+        );
+    }
+
+    /// Assigning a domain to a group which does not exist changes nothing
+    #[test]
+    fn set_groups_missing_group() {
+        let db = connect_to_gravity_test_db();
+        let repo = ListRepositoryImpl::new(db);
+        repo.set_groups(List::White, "test.com", &[1]).unwrap();
+
+        let error = repo.set_groups(List::White, "test.com", &[10]).unwrap_err();
+
+        assert_eq!(error.kind(), ErrorKind::GroupNotFound(10));
+        assert_eq!(repo.get_groups(List::White, "test.com").unwrap(), vec![1]);
+    }
+
+    /// The groups of a domain which is not on the list can not be read or
+    /// changed
+    #[test]
+    fn groups_not_found() {
+        let db = connect_to_gravity_test_db();
+        let repo = ListRepositoryImpl::new(db);
+
+        assert_eq!(
+            repo.get_groups(List::Regex, "not-found.com")
+                .unwrap_err()
+                .kind(),
+            ErrorKind::NotFound
+        );
+        assert_eq!(
+            repo.set_groups(List::Regex, "not-found.com", &[1])
+                .unwrap_err()
+                .kind(),
+            ErrorKind::NotFound
+        );
+    }
+
+    /// Removing a domain removes its group memberships as well
+    #[test]
+    fn remove_with_groups() {
+        let db = connect_to_gravity_test_db();
+        let repo = ListRepositoryImpl::new(db);
+        repo.set_groups(List::Regex, "disabled\\-regex\\.com", &[1])
+            .unwrap();
+
+        repo.remove(List::Regex, "disabled\\-regex\\.com").unwrap();
+
+        assert!(!repo
+            .contains(List::Regex, "disabled\\-regex\\.com")
+            .unwrap());
+        assert_eq!(repo.get_memberships(List::Regex).unwrap(), Vec::new());
+    }
+
+    /// The entries are counted by their enabled state, and gravity is
+    /// counted by distinct domains
+    #[test]
+    fn counts() {
+        let db = connect_to_gravity_test_db();
+        let repo = ListRepositoryImpl::new(db);
+        repo.add(List::Black, "ads.com", None).unwrap();
+
+        assert_eq!(
+            repo.counts().unwrap(),
+            ListCounts {
+                whitelist: EntryCounts {
+                    enabled: 1,
+                    disabled: 1
+                },
+                blacklist: EntryCounts {
+                    enabled: 2,
+                    disabled: 1
+                },
+                regexlist: EntryCounts {
+                    enabled: 1,
+                    disabled: 1
+                },
+                regex_whitelist: EntryCounts {
+                    enabled: 1,
+                    disabled: 0
+                },
+                gravity: Some(10)
+            }
+        );
+    }
+
+    /// The domains on the list are removed and returned, and other domains
+    /// are ignored
+    #[test]
+    fn remove_many() {
+        let db = connect_to_gravity_test_db();
+        let repo = ListRepositoryImpl::new(db);
+        repo.set_groups(List::Black, "example.com", &[1]).unwrap();
+
+        let removed = repo
+            .remove_many(
+                List::Black,
+                &[
+                    "example.com".to_owned(),
+                    "disabled-black.com".to_owned(),
+                    "not-found.com".to_owned(),
+                ],
+            )
+            .unwrap();
+
+        assert_eq!(removed, vec!["example.com", "disabled-black.com"]);
+        assert_eq!(repo.count(List::Black, true).unwrap(), 0);
+        assert_eq!(repo.get_memberships(List::Black).unwrap(), Vec::new());
+    }
+
+    /// Removing no domains changes nothing
+    #[test]
+    fn remove_many_empty() {
+        let db = connect_to_gravity_test_db();
+        let repo = ListRepositoryImpl::new(db);
+
+        assert_eq!(
+            repo.remove_many(List::White, &[]).unwrap(),
+            Vec::<String>::new()
+        );
+        assert_eq!(repo.count(List::White, true).unwrap(), 2);
+    }
+
+    /// The entry of a domain is found with its metadata, and missing domains
+    /// are not found
+    #[test]
+    fn find() {
+        let db = connect_to_gravity_test_db();
+        let repo = ListRepositoryImpl::new(db);
+
+        assert_eq!(
+            repo.find(List::White, "disabled-white.com").unwrap(),
+            Some(ListEntry {
+                domain: "disabled-white.com".to_owned(),
+                enabled: false,
+                comment: None,
+                date_added: 1_557_723_854,
+                date_modified: 1_557_723_911
+            })
+        );
+        assert_eq!(repo.find(List::White, "not-found.com").unwrap(), None);
+    }
+
+    /// If a later operation in a transaction fails, the earlier operations
+    /// are rolled back and the error is returned
+    #[test]
+    fn with_transaction_rollback() {
+        let db = connect_to_gravity_test_db();
+        let repo = ListRepositoryImpl::new(db);
+
+        let error = repo
+            .with_transaction(&mut |repo: &dyn ListRepository| {
+                repo.add(List::Black, "new.com", None)?;
+                repo.add(List::Black, "example.com", None)
+            })
+            .unwrap_err();
+
+        assert_eq!(
+            error.kind(),
+            ErrorKind::DomainAlreadyExists("example.com".to_owned(), "blacklist".to_owned())
+        );
+        assert_eq!(
+            get_domains(&repo, List::Black),
+            vec!["example.com".to_owned()]
+        );
+    }
+
+    /// The operations in a successful transaction are committed
+    #[test]
+    fn with_transaction_commit() {
+        let db = connect_to_gravity_test_db();
+        let repo = ListRepositoryImpl::new(db);
+
+        repo.with_transaction(&mut |repo: &dyn ListRepository| {
+            repo.add(List::Black, "new.com", None)?;
+            repo.remove(List::White, "test.com")
+        })
+        .unwrap();
+
+        assert_eq!(
+            get_domains(&repo, List::Black),
+            vec!["example.com".to_owned(), "new.com".to_owned()]
+        );
+        assert!(!repo.contains(List::White, "test.com").unwrap());
+    }
+
+    /// The lists in databases which keep every list in the `domainlist` table
+    mod domainlist {
+        use crate::{
+            databases::gravity::{
+                connect_to_gravity_test_db, connect_to_gravity_v5_test_db, GravitySchema,
+            },
+            services::lists::{
+                EntryCounts, List, ListCounts, ListEntry, ListFilter, ListQuery, ListRepository,
+                ListRepositoryImpl, NewListEntry,
+            },
+            util::ErrorKind,
+        };
+
+        /// Create a repository using the unified schema
+        fn v5_repo() -> ListRepositoryImpl {
+            ListRepositoryImpl::new(connect_to_gravity_v5_test_db())
+        }
+
+        /// Get the domains on the list
+        fn get_domains(repo: &ListRepositoryImpl, list: List) -> Vec<String> {
+            repo.get(list, true)
+                .unwrap()
+                .into_iter()
+                .map(|entry| entry.domain)
+                .collect()
+        }
+
+        /// The schema is detected from the tables in the database
+        #[test]
+        fn detect_schema() {
+            assert_eq!(
+                GravitySchema::detect(&connect_to_gravity_test_db()).unwrap(),
+                GravitySchema::Legacy
+            );
+            assert_eq!(
+                GravitySchema::detect(&connect_to_gravity_v5_test_db()).unwrap(),
+                GravitySchema::Domainlist
+            );
+        }
+
+        /// Each list only contains the entries of its type
+        #[test]
+        fn get() {
+            let repo = v5_repo();
+
+            assert_eq!(
+                repo.get(List::White, false).unwrap(),
+                vec![ListEntry {
+                    domain: "test.com".to_owned(),
+                    enabled: true,
+                    comment: None,
+                    date_added: 1_557_712_172,
+                    date_modified: 1_557_712_172
+                }]
+            );
+            assert_eq!(
+                get_domains(&repo, List::Black),
+                vec!["example.com", "disabled-black.com"]
+            );
+            assert_eq!(
+                get_domains(&repo, List::Regex),
+                vec!["(^|\\.)example\\.com$", "disabled\\-regex\\.com"]
+            );
+            assert_eq!(
+                get_domains(&repo, List::RegexWhite),
+                vec!["^allowed\\.example\\.com$"]
+            );
+        }
+
+        /// Querying, counting and searching are limited to the list
+        #[test]
+        fn query_count_search() {
+            let repo = v5_repo();
+
+            let query = ListQuery {
+                filter: ListFilter {
+                    include_disabled: true,
+                    ..ListFilter::default()
+                },
+                limit: Some(1),
+                offset: 1,
+                ..ListQuery::default()
+            };
+
+            assert_eq!(
+                repo.query(List::Black, &query)
+                    .unwrap()
+                    .into_iter()
+                    .map(|entry| entry.domain)
+                    .collect::<Vec<_>>(),
+                vec!["disabled-black.com"]
+            );
+            assert_eq!(repo.count(List::White, true).unwrap(), 2);
+            assert_eq!(repo.count(List::White, false).unwrap(), 1);
+            assert_eq!(
+                repo.search(List::Black, "example")
+                    .unwrap()
+                    .into_iter()
+                    .map(|entry| entry.domain)
+                    .collect::<Vec<_>>(),
+                vec!["example.com"]
+            );
+        }
+
+        /// The same domain can be on more than one list
+        #[test]
+        fn add_to_other_list() {
+            let repo = v5_repo();
+            repo.add(List::White, "example.com", Some("Allowed".to_owned()))
+                .unwrap();
+
+            assert!(repo.contains(List::White, "example.com").unwrap());
+            assert!(repo.contains(List::Black, "example.com").unwrap());
+            assert_eq!(
+                repo.find(List::White, "example.com")
+                    .unwrap()
+                    .unwrap()
+                    .comment,
+                Some("Allowed".to_owned())
+            );
+        }
+
+        /// Adding a domain which is already on the list is an error
+        #[test]
+        fn add_duplicate() {
+            let repo = v5_repo();
+
+            assert_eq!(
+                repo.add(List::Black, "example.com", None)
+                    .unwrap_err()
+                    .kind(),
+                ErrorKind::DomainAlreadyExists("example.com".to_owned(), "blacklist".to_owned())
+            );
+        }
+
+        /// Removing a domain only removes it from the given list
+        #[test]
+        fn remove() {
+            let repo = v5_repo();
+            repo.add(List::White, "example.com", None).unwrap();
+            repo.set_groups(List::Black, "example.com", &[1]).unwrap();
+
+            repo.remove(List::Black, "example.com").unwrap();
+
+            assert!(!repo.contains(List::Black, "example.com").unwrap());
+            assert!(repo.contains(List::White, "example.com").unwrap());
+            assert_eq!(repo.get_memberships(List::Black).unwrap(), Vec::new());
+        }
+
+        /// The domains on the list are removed and returned
+        #[test]
+        fn remove_many() {
+            let repo = v5_repo();
+
+            assert_eq!(
+                repo.remove_many(
+                    List::Black,
+                    &["example.com".to_owned(), "test.com".to_owned()]
+                )
+                .unwrap(),
+                vec!["example.com"]
+            );
+            assert!(repo.contains(List::White, "test.com").unwrap());
+        }
+
+        /// Moving a domain keeps its enabled state and comment, but not its
+        /// groups
+        #[test]
+        fn move_domain() {
+            let repo = v5_repo();
+            repo.set_groups(List::Black, "disabled-black.com", &[1])
+                .unwrap();
+
+            repo.move_domain(List::Black, List::White, "disabled-black.com")
+                .unwrap();
+
+            assert!(!repo.contains(List::Black, "disabled-black.com").unwrap());
+            let entry = repo
+                .find(List::White, "disabled-black.com")
+                .unwrap()
+                .unwrap();
+            assert!(!entry.enabled);
+            assert_eq!(
+                repo.get_groups(List::White, "disabled-black.com").unwrap(),
+                Vec::<i32>::new()
+            );
+        }
+
+        /// Moving a domain which is already on the destination list is an error,
+        /// and does not change either list
+        #[test]
+        fn move_domain_duplicate() {
+            let repo = v5_repo();
+            repo.add(List::White, "example.com", None).unwrap();
+            repo.set_groups(List::Black, "example.com", &[1]).unwrap();
+
+            assert_eq!(
+                repo.move_domain(List::Black, List::White, "example.com")
+                    .unwrap_err()
+                    .kind(),
+                ErrorKind::DomainAlreadyExists("example.com".to_owned(), "whitelist".to_owned())
+            );
+            assert_eq!(
+                repo.get_groups(List::Black, "example.com").unwrap(),
+                vec![1]
+            );
+        }
+
+        /// Moving a domain which is not on the source list is a not found error
+        #[test]
+        fn move_domain_not_found() {
+            let repo = v5_repo();
+
+            assert_eq!(
+                repo.move_domain(List::White, List::Black, "example.com")
+                    .unwrap_err()
+                    .kind(),
+                ErrorKind::NotFound
+            );
+        }
+
+        /// Entries can be enabled and disabled, and missing entries are not
+        /// found
+        #[test]
+        fn set_enabled() {
+            let repo = v5_repo();
+            repo.set_enabled(List::White, "disabled-white.com", true)
+                .unwrap();
+
+            assert_eq!(repo.count(List::White, false).unwrap(), 2);
+            assert_eq!(
+                repo.set_enabled(List::White, "example.com", true)
+                    .unwrap_err()
+                    .kind(),
+                ErrorKind::NotFound
+            );
+        }
+
+        /// Groups are assigned to the entry on the given list
+        #[test]
+        fn set_groups() {
+            let repo = v5_repo();
+            repo.set_groups(List::Regex, "(^|\\.)example\\.com$", &[1])
+                .unwrap();
+
+            assert_eq!(
+                repo.get_memberships(List::Regex).unwrap(),
+                vec![("(^|\\.)example\\.com$".to_owned(), 1)]
+            );
+            assert_eq!(repo.get_memberships(List::Black).unwrap(), Vec::new());
+            assert_eq!(
+                repo.set_groups(List::White, "example.com", &[1])
+                    .unwrap_err()
+                    .kind(),
+                ErrorKind::NotFound
+            );
+        }
+
+        /// Replacing a list on import leaves the other lists alone
+        #[test]
+        fn import_replace() {
+            let repo = v5_repo();
+            repo.add_many(List::Regex, &["^ads\\.".to_owned()]).unwrap();
+
+            repo.import(
+                &[(
+                    List::Black,
+                    vec![NewListEntry {
+                        domain: "imported.com".to_owned(),
+                        enabled: true,
+                        comment: None,
+                    }],
+                )],
+                true,
+            )
+            .unwrap();
+
+            assert_eq!(get_domains(&repo, List::Black), vec!["imported.com"]);
+            assert_eq!(repo.count(List::Regex, true).unwrap(), 3);
+            assert_eq!(repo.count(List::White, true).unwrap(), 2);
+        }
+
+        /// The entries are counted by list and enabled state
+        #[test]
+        fn counts() {
+            let repo = v5_repo();
+
+            assert_eq!(
+                repo.counts().unwrap(),
+                ListCounts {
+                    whitelist: EntryCounts {
+                        enabled: 1,
+                        disabled: 1
+                    },
+                    blacklist: EntryCounts {
+                        enabled: 1,
+                        disabled: 1
+                    },
+                    regexlist: EntryCounts {
+                        enabled: 1,
+                        disabled: 1
+                    },
+                    regex_whitelist: EntryCounts {
+                        enabled: 1,
+                        disabled: 0
+                    },
+                    gravity: Some(10)
+                }
+            );
+        }
+
+        /// Changes made in a failed transaction are rolled back
+        #[test]
+        fn with_transaction_rollback() {
+            let repo = v5_repo();
+
+            let error = repo
+                .with_transaction(&mut |repo: &dyn ListRepository| {
+                    repo.add(List::Black, "new.com", None)?;
+                    repo.add(List::Black, "example.com", None)
+                })
+                .unwrap_err();
+
+            assert_eq!(
+                error.kind(),
+                ErrorKind::DomainAlreadyExists("example.com".to_owned(), "blacklist".to_owned())
+            );
+            assert!(!repo.contains(List::Black, "new.com").unwrap());
+        }
+    }
 }
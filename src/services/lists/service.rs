@@ -11,12 +11,17 @@
 use crate::{
     env::Env,
     ftl::FtlConnectionType,
-    services::lists::{List, ListRepository},
+    services::{
+        lists::{EntryUpdate, List, ListEntry, ListQuery, ListRepository, NewListEntry},
+        webhooks::{ListAction, ListEvent, WebhookService},
+    },
     util::{Error, ErrorKind},
 };
 use failure::ResultExt;
+use rocket::form::{self, FromFormField, ValueField};
 use shaku::Provider;
 use std::{
+    collections::HashMap,
     process::{Command, Stdio},
     sync::Arc,
 };
@@ -25,15 +30,183 @@ use std::{
 /// and regexlist)
 #[cfg_attr(test, mockall::automock)]
 pub trait ListService: Send {
-    /// Add a domain to the list and update FTL and other lists accordingly.
-    /// Example: when adding to the whitelist, remove from the blacklist.
-    fn add(&self, list: List, domain: &str) -> Result<(), Error>;
+    /// Add a domain to the list and update FTL. If the domain is on the
+    /// opposite list (the blacklist for the whitelist, and vice versa), a
+    /// `DomainOnOppositeList` error is returned, unless `force` is true. Then
+    /// the domain is removed from the opposite list in the same transaction,
+    /// and the opposite list is returned.
+    fn add(
+        &self,
+        list: List,
+        domain: &str,
+        comment: Option<String>,
+        force: bool,
+    ) -> Result<Option<List>, Error>;
+
+    /// Add multiple domains to the list, updating FTL and other lists once
+    /// for all of them. Domains which are invalid or already on the list are
+    /// skipped and reported in the result. Domains which are on the opposite
+    /// list are reported as conflicts, unless `force` is true. Then they are
+    /// removed from the opposite list in the same transaction as the add.
+    fn add_many(
+        &self,
+        list: List,
+        domains: &[String],
+        force: bool,
+    ) -> Result<BatchAddResult, Error>;
+
+    /// Import entries into the lists and update FTL once for all of them.
+    /// Entries which are invalid or duplicates are skipped and counted in
+    /// the result. Whitelist and blacklist entries which are on the opposite
+    /// list are counted as conflicts, unless `force` is true. Then they are
+    /// removed from the opposite list in the same transaction as the import.
+    /// Domains which are on both lists of the import are always conflicts.
+    fn import(
+        &self,
+        import: ListsImport,
+        mode: ImportMode,
+        force: bool,
+    ) -> Result<ListsImportResult, Error>;
 
     /// Remove a domain from the list and update FTL
     fn remove(&self, list: List, domain: &str) -> Result<(), Error>;
 
-    /// Get all of the domains in the list
-    fn get(&self, list: List) -> Result<Vec<String>, Error>;
+    /// Remove multiple domains from the list, updating FTL once for all of
+    /// them. Domains which are not on the list are reported in the result.
+    fn remove_many(&self, list: List, domains: &[String]) -> Result<BatchRemoveResult, Error>;
+
+    /// Move a domain from one list to another and update FTL. The move is
+    /// atomic, so the domain is always on one of the lists.
+    fn move_domain(&self, from: List, to: List, domain: &str) -> Result<(), Error>;
+
+    /// Enable or disable a domain on the list and update FTL. Disabled
+    /// domains keep their metadata but are not used for blocking.
+    fn set_enabled(&self, list: List, domain: &str, enabled: bool) -> Result<(), Error>;
+
+    /// Get all of the entries in the list. Disabled entries are only
+    /// included if `include_disabled` is true.
+    fn get(&self, list: List, include_disabled: bool) -> Result<Vec<ListEntry>, Error>;
+
+    /// Get the domain's entry on the list, whether or not it is enabled. A
+    /// `NotFound` error is returned if the domain is not on the list.
+    fn get_entry(&self, list: List, domain: &str) -> Result<ListEntry, Error>;
+
+    /// Get the page of entries in the list selected by the query, along with
+    /// the total number of entries which match its filter
+    fn query(&self, list: List, options: &ListQuery) -> Result<ListPage, Error>;
+
+    /// Edit the domain's entry on the list in place, keeping its groups, and
+    /// update FTL
+    fn update(&self, list: List, domain: &str, changes: EntryUpdate) -> Result<(), Error>;
+
+    /// Get the IDs of the groups which the domain on the list is assigned to
+    fn get_groups(&self, list: List, domain: &str) -> Result<Vec<i32>, Error>;
+
+    /// Replace the groups which the domain on the list is assigned to and
+    /// update FTL
+    fn set_groups(&self, list: List, domain: &str, group_ids: &[i32]) -> Result<(), Error>;
+
+    /// Get the IDs of the groups which each domain on the list is assigned
+    /// to. Domains without any groups are not included.
+    fn get_memberships(&self, list: List) -> Result<HashMap<String, Vec<i32>>, Error>;
+}
+
+/// The outcome of adding multiple domains to a list
+#[cfg_attr(test, derive(Debug, PartialEq))]
+#[derive(Serialize, Clone, Default)]
+pub struct BatchAddResult {
+    /// Domains which were added to the list
+    pub added: Vec<String>,
+    /// Domains which were already on the list
+    pub already_exists: Vec<String>,
+    /// Domains which are not valid for the list
+    pub invalid: Vec<String>,
+    /// Domains which are on the opposite list, and were not added because
+    /// the add was not forced
+    pub conflicts: Vec<String>,
+    /// Domains which were added to the list and removed from the opposite
+    /// list
+    pub moved: Vec<String>,
+}
+
+/// The outcome of removing multiple domains from a list
+#[cfg_attr(test, derive(Debug, PartialEq))]
+#[derive(Serialize, Clone, Default)]
+pub struct BatchRemoveResult {
+    /// The number of domains which were removed from the list
+    pub removed: usize,
+    /// Domains which were not on the list
+    pub not_found: Vec<String>,
+}
+
+/// How imported entries are combined with the existing entries
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ImportMode {
+    /// Keep the existing entries, skipping imported entries which already
+    /// exist
+    Merge,
+    /// Delete the existing entries before importing
+    Replace,
+}
+
+impl<'v> FromFormField<'v> for ImportMode {
+    fn from_value(field: ValueField<'v>) -> form::Result<'v, Self> {
+        match field.value {
+            "merge" => Ok(ImportMode::Merge),
+            "replace" => Ok(ImportMode::Replace),
+            _ => Err(form::Error::validation("Unknown import mode").into()),
+        }
+    }
+}
+
+/// The entries to import into each list. This has the same format as a list
+/// export. Missing lists are treated as empty.
+#[cfg_attr(test, derive(Debug, PartialEq))]
+#[derive(Deserialize, Clone, Default)]
+pub struct ListsImport {
+    #[serde(default)]
+    pub whitelist: Vec<NewListEntry>,
+    #[serde(default)]
+    pub blacklist: Vec<NewListEntry>,
+    #[serde(default)]
+    pub regexlist: Vec<NewListEntry>,
+    #[serde(default)]
+    pub regex_whitelist: Vec<NewListEntry>,
+}
+
+/// The number of entries which were imported into a list, or were not
+#[cfg_attr(test, derive(Debug, PartialEq))]
+#[derive(Serialize, Clone, Default)]
+pub struct ImportCounts {
+    /// Entries which were added to the list
+    pub inserted: usize,
+    /// Entries which were already on the list, or repeated in the import
+    pub skipped: usize,
+    /// Entries which are not valid for the list
+    pub invalid: usize,
+    /// Entries which are on the opposite list, and were not imported
+    pub conflicts: usize,
+    /// Entries which were imported and removed from the opposite list
+    pub moved: usize,
+}
+
+/// The outcome of importing entries into the lists
+#[cfg_attr(test, derive(Debug, PartialEq))]
+#[derive(Serialize, Clone, Default)]
+pub struct ListsImportResult {
+    pub whitelist: ImportCounts,
+    pub blacklist: ImportCounts,
+    pub regexlist: ImportCounts,
+    pub regex_whitelist: ImportCounts,
+}
+
+/// A page of list entries
+#[cfg_attr(test, derive(Debug, PartialEq))]
+#[derive(Serialize, Clone)]
+pub struct ListPage {
+    pub entries: Vec<ListEntry>,
+    /// The total number of entries in the list
+    pub total: usize,
 }
 
 /// The implementation of `ListService`
@@ -46,93 +219,504 @@ pub struct ListServiceImpl {
     env: Arc<Env>,
     #[shaku(inject)]
     ftl: Arc<FtlConnectionType>,
+    #[shaku(inject)]
+    webhooks: Arc<dyn WebhookService>,
 }
 
 impl ListService for ListServiceImpl {
-    fn add(&self, list: List, domain: &str) -> Result<(), Error> {
-        match list {
-            List::White => {
-                // We need to add it to the whitelist and remove it from the
-                // blacklist
-                self.add_raw(List::White, domain)?;
-                self.try_remove_raw(List::Black, domain)?;
-
-                // Since we haven't hit an error yet, reload gravity
-                reload_gravity(List::White, &self.env)
-            }
-            List::Black => {
-                // We need to add it to the blacklist and remove it from the
-                // whitelist
-                self.add_raw(List::Black, domain)?;
-                self.try_remove_raw(List::White, domain)?;
-
-                // Since we haven't hit an error yet, reload gravity
-                reload_gravity(List::Black, &self.env)
-            }
-            List::Regex => {
+    fn add(
+        &self,
+        list: List,
+        domain: &str,
+        comment: Option<String>,
+        force: bool,
+    ) -> Result<Option<List>, Error> {
+        let domain = &list.normalize(domain)?;
+
+        let opposite = match list.opposite() {
+            Some(opposite) => opposite,
+            None => {
                 // We only need to add it to the regex list
-                self.add_raw(List::Regex, domain)?;
+                self.add_raw(list, domain, comment)?;
 
                 // Since we haven't hit an error yet, tell FTL to recompile
                 // regex
-                self.ftl.connect("recompile-regex")?.expect_eom()
+                self.apply_change(list)?;
+                self.notify(list, ListAction::Add, Some(domain));
+                return Ok(None);
             }
+        };
+
+        self.check_addable(list, domain)?;
+
+        if !self.repo.contains(opposite, domain)? {
+            self.repo.add(list, domain, comment)?;
+
+            // Since we haven't hit an error yet, reload gravity
+            self.apply_change(list)?;
+            self.notify(list, ListAction::Add, Some(domain));
+            return Ok(None);
+        }
+
+        if !force {
+            return Err(Error::from(ErrorKind::DomainOnOppositeList(
+                domain.to_owned(),
+                opposite.name().to_owned(),
+            )));
         }
+
+        // Move the domain so that it is never on both lists or neither list
+        self.repo
+            .with_transaction(&mut |repo: &dyn ListRepository| {
+                repo.add(list, domain, comment.clone())?;
+                repo.remove(opposite, domain)
+            })?;
+
+        // Both lists changed, so reload gravity for each of them
+        self.apply_change(list)?;
+        self.apply_change(opposite)?;
+        self.notify(opposite, ListAction::Remove, Some(domain));
+        self.notify(list, ListAction::Add, Some(domain));
+
+        Ok(Some(opposite))
     }
 
-    fn remove(&self, list: List, domain: &str) -> Result<(), Error> {
-        match list {
-            List::White => {
-                self.remove_raw(List::White, domain)?;
-                reload_gravity(List::White, &self.env)
+    fn add_many(
+        &self,
+        list: List,
+        domains: &[String],
+        force: bool,
+    ) -> Result<BatchAddResult, Error> {
+        let mut result = BatchAddResult::default();
+        let opposite = list.opposite();
+
+        // Sort out which domains can be added
+        for domain in domains {
+            let domain = match list.normalize(domain) {
+                Ok(domain) if list.accepts(&domain) => domain,
+                _ => {
+                    result.invalid.push(domain.to_owned());
+                    continue;
+                }
+            };
+
+            if result.added.contains(&domain) || self.repo.contains(list, &domain)? {
+                result.already_exists.push(domain);
+                continue;
+            }
+
+            let on_opposite = match opposite {
+                Some(opposite) => self.repo.contains(opposite, &domain)?,
+                None => false,
+            };
+
+            if !on_opposite {
+                result.added.push(domain);
+            } else if force {
+                result.moved.push(domain.clone());
+                result.added.push(domain);
+            } else {
+                result.conflicts.push(domain);
+            }
+        }
+
+        if result.added.is_empty() {
+            return Ok(result);
+        }
+
+        match opposite {
+            Some(opposite) if !result.moved.is_empty() => {
+                // Move the domains so that they are never on both lists or
+                // neither list
+                self.repo
+                    .with_transaction(&mut |repo: &dyn ListRepository| {
+                        repo.add_many(list, &result.added)?;
+                        repo.remove_many(opposite, &result.moved).map(|_| ())
+                    })?;
+
+                self.apply_change(list)?;
+                self.apply_change(opposite)?;
+                self.notify(opposite, ListAction::Remove, None);
+            }
+            _ => {
+                self.repo.add_many(list, &result.added)?;
+                self.apply_change(list)?;
+            }
+        }
+
+        self.notify(list, ListAction::Add, None);
+        Ok(result)
+    }
+
+    fn import(
+        &self,
+        import: ListsImport,
+        mode: ImportMode,
+        force: bool,
+    ) -> Result<ListsImportResult, Error> {
+        let (mut whitelist, mut whitelist_counts) =
+            self.filter_import(List::White, import.whitelist, mode)?;
+        let (mut blacklist, mut blacklist_counts) =
+            self.filter_import(List::Black, import.blacklist, mode)?;
+        let (regexlist, regexlist_counts) =
+            self.filter_import(List::Regex, import.regexlist, mode)?;
+        let (regex_whitelist, regex_whitelist_counts) =
+            self.filter_import(List::RegexWhite, import.regex_whitelist, mode)?;
+
+        // Take out the whitelist and blacklist entries which would be on both
+        // lists after the import
+        let imported_white: Vec<String> = whitelist.iter().map(|e| e.domain.clone()).collect();
+        let imported_black: Vec<String> = blacklist.iter().map(|e| e.domain.clone()).collect();
+        let moved_from_black = self.filter_import_conflicts(
+            List::White,
+            &mut whitelist,
+            &mut whitelist_counts,
+            &imported_black,
+            mode,
+            force,
+        )?;
+        let moved_from_white = self.filter_import_conflicts(
+            List::Black,
+            &mut blacklist,
+            &mut blacklist_counts,
+            &imported_white,
+            mode,
+            force,
+        )?;
+
+        let lists = vec![
+            (List::White, whitelist),
+            (List::Black, blacklist),
+            (List::Regex, regexlist),
+            (List::RegexWhite, regex_whitelist),
+        ];
+        let moves = [
+            (List::White, moved_from_white),
+            (List::Black, moved_from_black),
+        ];
+        let replace = mode == ImportMode::Replace;
+
+        if moves.iter().all(|(_, domains)| domains.is_empty()) {
+            self.repo.import(&lists, replace)?;
+        } else {
+            // Move the domains so that they are never on both lists or
+            // neither list
+            self.repo
+                .with_transaction(&mut |repo: &dyn ListRepository| {
+                    for (list, domains) in &moves {
+                        repo.remove_many(*list, domains)?;
+                    }
+
+                    repo.import(&lists, replace)
+                })?;
+        }
+
+        // Update FTL for the lists which changed
+        for (list, entries) in &lists {
+            let moved_from = moves
+                .iter()
+                .any(|(from, domains)| from == list && !domains.is_empty());
+
+            if mode == ImportMode::Merge && entries.is_empty() && !moved_from {
+                continue;
             }
-            List::Black => {
-                self.remove_raw(List::Black, domain)?;
-                reload_gravity(List::Black, &self.env)
+
+            self.apply_change(*list)?;
+
+            if moved_from {
+                self.notify(*list, ListAction::Remove, None);
             }
-            List::Regex => {
-                self.remove_raw(List::Regex, domain)?;
-                self.ftl.connect("recompile-regex")?.expect_eom()
+
+            if mode == ImportMode::Replace || !entries.is_empty() {
+                self.notify(*list, ListAction::Import, None);
             }
         }
+
+        Ok(ListsImportResult {
+            whitelist: whitelist_counts,
+            blacklist: blacklist_counts,
+            regexlist: regexlist_counts,
+            regex_whitelist: regex_whitelist_counts,
+        })
     }
 
-    fn get(&self, list: List) -> Result<Vec<String>, Error> {
-        self.repo.get(list)
+    fn remove(&self, list: List, domain: &str) -> Result<(), Error> {
+        let domain = &list.normalize(domain)?;
+
+        self.remove_raw(list, domain)?;
+        self.apply_change(list)?;
+
+        self.notify(list, ListAction::Remove, Some(domain));
+        Ok(())
     }
-}
 
-impl ListServiceImpl {
-    /// Simply add a domain to the list
-    fn add_raw(&self, list: List, domain: &str) -> Result<(), Error> {
+    fn remove_many(&self, list: List, domains: &[String]) -> Result<BatchRemoveResult, Error> {
+        // Domains which can not be normalized are not on the list
+        let normalized: Vec<Option<String>> = domains
+            .iter()
+            .map(|domain| list.normalize(domain).ok())
+            .collect();
+        let to_remove: Vec<String> = normalized.iter().flatten().cloned().collect();
+
+        let removed = if to_remove.is_empty() {
+            Vec::new()
+        } else {
+            self.repo.remove_many(list, &to_remove)?
+        };
+
+        let not_found = domains
+            .iter()
+            .zip(normalized)
+            .filter(|(_, normalized)| match normalized {
+                Some(domain) => !removed.contains(domain),
+                None => true,
+            })
+            .map(|(domain, _)| domain.to_owned())
+            .collect();
+
+        if !removed.is_empty() {
+            self.apply_change(list)?;
+
+            self.notify(list, ListAction::Remove, None);
+        }
+
+        Ok(BatchRemoveResult {
+            removed: removed.len(),
+            not_found,
+        })
+    }
+
+    fn move_domain(&self, from: List, to: List, domain: &str) -> Result<(), Error> {
+        if from == to {
+            return Err(Error::from(ErrorKind::BadRequest));
+        }
+
+        // The domain is stored in the form used by the source list, and it
+        // must be valid for the destination list
+        let domain = &from.normalize(domain)?;
+        to.validate(domain)?;
+
+        self.repo.move_domain(from, to, domain)?;
+
+        // Both lists changed, so update FTL for each of them
+        for list in &[from, to] {
+            self.apply_change(*list)?;
+        }
+
+        self.notify(from, ListAction::Remove, Some(domain));
+        self.notify(to, ListAction::Add, Some(domain));
+        Ok(())
+    }
+
+    fn set_enabled(&self, list: List, domain: &str, enabled: bool) -> Result<(), Error> {
+        let domain = &list.normalize(domain)?;
+
         // Check if it's a valid domain before doing anything
         if !list.accepts(domain) {
             return Err(Error::from(ErrorKind::InvalidDomain));
         }
 
+        self.repo.set_enabled(list, domain, enabled)?;
+
+        self.apply_change(list)?;
+
+        let action = if enabled {
+            ListAction::Enable
+        } else {
+            ListAction::Disable
+        };
+        self.notify(list, action, Some(domain));
+        Ok(())
+    }
+
+    fn update(&self, list: List, domain: &str, changes: EntryUpdate) -> Result<(), Error> {
+        let domain = &list.normalize(domain)?;
+
+        // A new domain is checked and stored the same way as an added domain
+        let new_domain = match changes.domain {
+            Some(ref new_domain) => {
+                let new_domain = list.normalize(new_domain)?;
+                list.validate(&new_domain)?;
+                Some(new_domain)
+            }
+            None => None,
+        };
+        let changes = EntryUpdate {
+            domain: new_domain,
+            ..changes
+        };
+
+        self.repo.update(list, domain, &changes)?;
+
+        self.apply_change(list)?;
+
+        self.notify(list, ListAction::Update, Some(domain));
+        Ok(())
+    }
+
+    fn get(&self, list: List, include_disabled: bool) -> Result<Vec<ListEntry>, Error> {
+        self.repo.get(list, include_disabled)
+    }
+
+    fn get_entry(&self, list: List, domain: &str) -> Result<ListEntry, Error> {
+        let domain = &list.normalize(domain)?;
+
+        self.repo
+            .find(list, domain)?
+            .ok_or_else(|| Error::from(ErrorKind::NotFound))
+    }
+
+    fn query(&self, list: List, options: &ListQuery) -> Result<ListPage, Error> {
+        Ok(ListPage {
+            entries: self.repo.query(list, options)?,
+            total: self.repo.count_filtered(list, &options.filter)?,
+        })
+    }
+
+    fn get_groups(&self, list: List, domain: &str) -> Result<Vec<i32>, Error> {
+        let domain = &list.normalize(domain)?;
+        self.repo.get_groups(list, domain)
+    }
+
+    fn set_groups(&self, list: List, domain: &str, group_ids: &[i32]) -> Result<(), Error> {
+        let domain = &list.normalize(domain)?;
+
+        self.repo.set_groups(list, domain, group_ids)?;
+
+        self.apply_change(list)?;
+
+        // Changing the groups changes which clients the entry applies to
+        self.notify(list, ListAction::Update, Some(domain));
+        Ok(())
+    }
+
+    fn get_memberships(&self, list: List) -> Result<HashMap<String, Vec<i32>>, Error> {
+        let mut memberships = HashMap::new();
+
+        for (domain, group_id) in self.repo.get_memberships(list)? {
+            memberships
+                .entry(domain)
+                .or_insert_with(Vec::new)
+                .push(group_id);
+        }
+
+        Ok(memberships)
+    }
+}
+
+impl ListServiceImpl {
+    /// Notify the webhook of a successful change to the list
+    fn notify(&self, list: List, action: ListAction, domain: Option<&str>) {
+        self.webhooks.notify(ListEvent::new(list, action, domain));
+    }
+
+    /// Tell FTL about the changes to the list. Gravity is reloaded for the
+    /// exact lists, and the regexes are recompiled for the regex lists.
+    fn apply_change(&self, list: List) -> Result<(), Error> {
+        match list {
+            List::White | List::Black => reload_gravity(list, &self.env),
+            List::Regex | List::RegexWhite => self.ftl.connect("recompile-regex")?.expect_eom(),
+        }
+    }
+
+    /// Simply add a domain to the list
+    fn add_raw(&self, list: List, domain: &str, comment: Option<String>) -> Result<(), Error> {
+        self.check_addable(list, domain)?;
+        self.repo.add(list, domain, comment)
+    }
+
+    /// Check that the domain is valid for the list and is not already on it
+    fn check_addable(&self, list: List, domain: &str) -> Result<(), Error> {
+        // Check if it's a valid domain before doing anything
+        list.validate(domain)?;
+
         // Check if the domain is already in the list
         if self.repo.contains(list, domain)? {
-            return Err(Error::from(ErrorKind::AlreadyExists));
+            return Err(Error::from(ErrorKind::DomainAlreadyExists(
+                domain.to_owned(),
+                list.name().to_owned(),
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Sort out which of the imported entries should be added to the list.
+    /// In merge mode, entries which are already on the list are skipped.
+    fn filter_import(
+        &self,
+        list: List,
+        entries: Vec<NewListEntry>,
+        mode: ImportMode,
+    ) -> Result<(Vec<NewListEntry>, ImportCounts), Error> {
+        let mut accepted: Vec<NewListEntry> = Vec::new();
+        let mut counts = ImportCounts::default();
+
+        for mut entry in entries {
+            entry.domain = match list.normalize(&entry.domain) {
+                Ok(domain) => domain,
+                Err(_) => {
+                    counts.invalid += 1;
+                    continue;
+                }
+            };
+
+            if !list.accepts(&entry.domain) {
+                counts.invalid += 1;
+            } else if accepted.iter().any(|other| other.domain == entry.domain)
+                || (mode == ImportMode::Merge && self.repo.contains(list, &entry.domain)?)
+            {
+                counts.skipped += 1;
+            } else {
+                accepted.push(entry);
+            }
         }
 
-        self.repo.add(list, domain)
+        counts.inserted = accepted.len();
+        Ok((accepted, counts))
     }
 
-    /// Try to remove a domain from the list, but it is not an error if the
-    /// domain does not exist
-    fn try_remove_raw(&self, list: List, domain: &str) -> Result<(), Error> {
-        match self.remove_raw(list, domain) {
-            // Pass through successful results
-            Ok(_) => Ok(()),
-            Err(e) => {
-                // Ignore NotFound errors
-                if e.kind() == ErrorKind::NotFound {
-                    Ok(())
+    /// Take the imported entries which would also be on the opposite list
+    /// out of `entries`, counting them as conflicts. Domains which are in the
+    /// imported opposite list are always conflicts. In merge mode, domains
+    /// which are on the opposite list in the database are conflicts unless
+    /// `force` is true. Then they are kept, and returned so that they can be
+    /// removed from the opposite list.
+    fn filter_import_conflicts(
+        &self,
+        list: List,
+        entries: &mut Vec<NewListEntry>,
+        counts: &mut ImportCounts,
+        imported_opposite: &[String],
+        mode: ImportMode,
+        force: bool,
+    ) -> Result<Vec<String>, Error> {
+        let opposite = match list.opposite() {
+            Some(opposite) => opposite,
+            None => return Ok(Vec::new()),
+        };
+        let mut accepted = Vec::with_capacity(entries.len());
+        let mut moved = Vec::new();
+
+        for entry in entries.drain(..) {
+            if imported_opposite.contains(&entry.domain) {
+                counts.conflicts += 1;
+            } else if mode == ImportMode::Merge && self.repo.contains(opposite, &entry.domain)? {
+                if force {
+                    moved.push(entry.domain.clone());
+                    accepted.push(entry);
                 } else {
-                    Err(e)
+                    counts.conflicts += 1;
                 }
+            } else {
+                accepted.push(entry);
             }
         }
+
+        *entries = accepted;
+        counts.inserted = entries.len();
+        counts.moved = moved.len();
+        Ok(moved)
     }
 
     /// Simply remove a domain from the list
@@ -188,12 +772,28 @@ mod test {
     use super::List;
     use crate::{
         ftl::FtlConnectionType,
-        services::lists::{ListService, ListServiceImpl, MockListRepository},
+        services::{
+            lists::{
+                BatchAddResult, BatchRemoveResult, EntryUpdate, ImportCounts, ImportMode,
+                ListEntry, ListFilter, ListPage, ListQuery, ListService, ListServiceImpl,
+                ListsImport, ListsImportResult, MockListRepository, NewListEntry,
+            },
+            webhooks::{ListAction, MockWebhookService, WebhookService},
+        },
         testing::{write_eom, TestEnvBuilder},
+        util::ErrorKind,
     };
     use mockall::predicate::*;
     use std::{collections::HashMap, sync::Arc};
 
+    /// Get a webhook service which accepts any event
+    fn get_webhooks() -> Arc<dyn WebhookService> {
+        let mut webhooks = MockWebhookService::new();
+        webhooks.expect_notify().return_const(());
+
+        Arc::new(webhooks)
+    }
+
     fn get_ftl() -> FtlConnectionType {
         let mut data = Vec::new();
         let mut command_map = HashMap::new();
@@ -204,23 +804,31 @@ mod test {
         FtlConnectionType::Test(command_map)
     }
 
-    /// Test getting the domains for a list
+    /// Test getting the entries for a list
     fn get_test(list: List, domain: &str) {
         let env = TestEnvBuilder::new().build();
         let ftl = get_ftl();
         let mut repo = MockListRepository::new();
+        let entries = vec![ListEntry {
+            domain: domain.to_owned(),
+            enabled: true,
+            comment: None,
+            date_added: 1,
+            date_modified: 1,
+        }];
 
         repo.expect_get()
-            .with(eq(list))
-            .return_const(Ok(vec![domain.to_owned()]));
+            .with(eq(list), eq(false))
+            .return_const(Ok(entries.clone()));
 
         let service = ListServiceImpl {
             repo: Box::new(repo),
             env: Arc::new(env),
             ftl: Arc::new(ftl),
+            webhooks: get_webhooks(),
         };
 
-        assert_eq!(service.get(list).unwrap(), vec![domain.to_owned()]);
+        assert_eq!(service.get(list, false).unwrap(), entries);
     }
 
     /// Test successfully deleting a domain from a list
@@ -240,11 +848,40 @@ mod test {
             repo: Box::new(repo),
             env: Arc::new(env),
             ftl: Arc::new(ftl),
+            webhooks: get_webhooks(),
         };
 
         service.remove(list, domain).unwrap();
     }
 
+    /// A domain which is not on the list is a not found error, and the
+    /// domain is looked up in punycode
+    #[test]
+    fn get_entry_not_found() {
+        let env = TestEnvBuilder::new().build();
+        let ftl = get_ftl();
+        let mut repo = MockListRepository::new();
+
+        repo.expect_find()
+            .with(eq(List::Black), eq("xn--mnchen-3ya.de"))
+            .return_const(Ok(None));
+
+        let service = ListServiceImpl {
+            repo: Box::new(repo),
+            env: Arc::new(env),
+            ftl: Arc::new(ftl),
+            webhooks: get_webhooks(),
+        };
+
+        assert_eq!(
+            service
+                .get_entry(List::Black, "münchen.de")
+                .unwrap_err()
+                .kind(),
+            ErrorKind::NotFound
+        );
+    }
+
     /// The lists are retrieved correctly
     #[test]
     fn get() {
@@ -253,82 +890,903 @@ mod test {
         get_test(List::Regex, "regex.com");
     }
 
-    /// Adding a domain to the whitelist works when the domain does not exist
-    /// in either the whitelist or blacklist
+    /// A page of entries is returned with the count of entries matching the
+    /// filter
     #[test]
-    fn add_whitelist() {
+    fn query() {
         let env = TestEnvBuilder::new().build();
         let ftl = get_ftl();
         let mut repo = MockListRepository::new();
+        let entries = vec![ListEntry {
+            domain: "example.com".to_owned(),
+            enabled: true,
+            comment: None,
+            date_added: 1,
+            date_modified: 1,
+        }];
 
-        repo.expect_contains()
-            .with(eq(List::White), eq("example.com"))
-            .return_const(Ok(false));
-        repo.expect_add()
-            .with(eq(List::White), eq("example.com"))
-            .return_const(Ok(()));
-        repo.expect_contains()
-            .with(eq(List::Black), eq("example.com"))
-            .return_const(Ok(false));
+        let query = ListQuery {
+            limit: Some(1),
+            offset: 2,
+            ..ListQuery::default()
+        };
+
+        repo.expect_query()
+            .with(eq(List::Black), eq(query.clone()))
+            .return_const(Ok(entries.clone()));
+        repo.expect_count_filtered()
+            .with(eq(List::Black), eq(ListFilter::default()))
+            .return_const(Ok(5));
 
         let service = ListServiceImpl {
             repo: Box::new(repo),
             env: Arc::new(env),
             ftl: Arc::new(ftl),
+            webhooks: get_webhooks(),
         };
 
-        service.add(List::White, "example.com").unwrap();
+        assert_eq!(
+            service.query(List::Black, &query).unwrap(),
+            ListPage { entries, total: 5 }
+        );
     }
 
-    /// Adding a domain to the blacklist works when the domain does not exist
-    /// in either the whitelist or blacklist
+    /// Moving a domain between lists uses the repository
     #[test]
-    fn add_blacklist() {
+    fn move_domain() {
         let env = TestEnvBuilder::new().build();
         let ftl = get_ftl();
         let mut repo = MockListRepository::new();
 
-        repo.expect_contains()
-            .with(eq(List::Black), eq("example.com"))
-            .return_const(Ok(false));
-        repo.expect_add()
-            .with(eq(List::Black), eq("example.com"))
+        repo.expect_move_domain()
+            .with(eq(List::Black), eq(List::Regex), eq("example.com"))
             .return_const(Ok(()));
-        repo.expect_contains()
-            .with(eq(List::White), eq("example.com"))
-            .return_const(Ok(false));
 
         let service = ListServiceImpl {
             repo: Box::new(repo),
             env: Arc::new(env),
             ftl: Arc::new(ftl),
+            webhooks: get_webhooks(),
         };
 
-        service.add(List::Black, "example.com").unwrap();
+        service
+            .move_domain(List::Black, List::Regex, "example.com")
+            .unwrap();
     }
 
-    /// Adding a domain to the regex list works when the domain does not already
-    /// exist in the regex list
+    /// Moving a domain to the list it is already on is a bad request
     #[test]
-    fn add_regexlist() {
+    fn move_domain_same_list() {
+        let env = TestEnvBuilder::new().build();
+        let ftl = get_ftl();
+        let repo = MockListRepository::new();
+
+        let service = ListServiceImpl {
+            repo: Box::new(repo),
+            env: Arc::new(env),
+            ftl: Arc::new(ftl),
+            webhooks: get_webhooks(),
+        };
+
+        let error = service
+            .move_domain(List::White, List::White, "example.com")
+            .unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::BadRequest);
+    }
+
+    /// Moving a regex which is not a valid domain to the whitelist is an error
+    #[test]
+    fn move_domain_invalid() {
+        let env = TestEnvBuilder::new().build();
+        let ftl = get_ftl();
+        let repo = MockListRepository::new();
+
+        let service = ListServiceImpl {
+            repo: Box::new(repo),
+            env: Arc::new(env),
+            ftl: Arc::new(ftl),
+            webhooks: get_webhooks(),
+        };
+
+        let error = service
+            .move_domain(List::Regex, List::White, "^ads\\.")
+            .unwrap_err();
+        assert_eq!(
+            error.kind(),
+            ErrorKind::InvalidHostname("^ads\\.".to_owned(), "domain ends with a dot".to_owned())
+        );
+    }
+
+    /// The new domain of an update is normalized before it is stored
+    #[test]
+    fn update() {
         let env = TestEnvBuilder::new().build();
         let ftl = get_ftl();
         let mut repo = MockListRepository::new();
 
-        repo.expect_contains()
-            .with(eq(List::Regex), eq("example.com"))
-            .return_const(Ok(false));
-        repo.expect_add()
-            .with(eq(List::Regex), eq("example.com"))
+        repo.expect_update()
+            .with(
+                eq(List::Black),
+                eq("example.com"),
+                eq(EntryUpdate {
+                    domain: Some("xn--mnchen-3ya.de".to_owned()),
+                    comment: Some("Renamed".to_owned()),
+                    enabled: None,
+                }),
+            )
             .return_const(Ok(()));
 
         let service = ListServiceImpl {
             repo: Box::new(repo),
             env: Arc::new(env),
             ftl: Arc::new(ftl),
+            webhooks: get_webhooks(),
+        };
+
+        service
+            .update(
+                List::Black,
+                "example.com",
+                EntryUpdate {
+                    domain: Some("münchen.de".to_owned()),
+                    comment: Some("Renamed".to_owned()),
+                    enabled: None,
+                },
+            )
+            .unwrap();
+    }
+
+    /// Renaming an entry to an invalid pattern is an error
+    #[test]
+    fn update_invalid() {
+        let env = TestEnvBuilder::new().build();
+        let ftl = get_ftl();
+        let repo = MockListRepository::new();
+
+        let service = ListServiceImpl {
+            repo: Box::new(repo),
+            env: Arc::new(env),
+            ftl: Arc::new(ftl),
+            webhooks: get_webhooks(),
+        };
+
+        let error = service
+            .update(
+                List::Regex,
+                "^ads\\.",
+                EntryUpdate {
+                    domain: Some("([a-z".to_owned()),
+                    ..EntryUpdate::default()
+                },
+            )
+            .unwrap_err();
+        assert_eq!(error.key(), "invalid_regex");
+    }
+
+    /// Disabling a domain updates the repository
+    #[test]
+    fn set_enabled() {
+        let env = TestEnvBuilder::new().build();
+        let ftl = get_ftl();
+        let mut repo = MockListRepository::new();
+
+        repo.expect_set_enabled()
+            .with(eq(List::Regex), eq("example.com"), eq(false))
+            .return_const(Ok(()));
+
+        let service = ListServiceImpl {
+            repo: Box::new(repo),
+            env: Arc::new(env),
+            ftl: Arc::new(ftl),
+            webhooks: get_webhooks(),
+        };
+
+        service
+            .set_enabled(List::Regex, "example.com", false)
+            .unwrap();
+    }
+
+    /// Setting the groups of a regex recompiles the regex list and notifies
+    /// the webhook of the update
+    #[test]
+    fn set_groups() {
+        let env = TestEnvBuilder::new().build();
+        let ftl = get_ftl();
+        let mut repo = MockListRepository::new();
+        let mut webhooks = MockWebhookService::new();
+
+        repo.expect_set_groups()
+            .withf(|list, domain, group_ids| {
+                *list == List::Regex && domain == "example.com" && group_ids == [1, 2]
+            })
+            .return_const(Ok(()));
+        webhooks
+            .expect_notify()
+            .withf(|event| {
+                event.list == List::Regex
+                    && event.action == ListAction::Update
+                    && event.domain == Some("example.com".to_owned())
+            })
+            .times(1)
+            .return_const(());
+
+        let service = ListServiceImpl {
+            repo: Box::new(repo),
+            env: Arc::new(env),
+            ftl: Arc::new(ftl),
+            webhooks: Arc::new(webhooks),
+        };
+
+        service
+            .set_groups(List::Regex, "example.com", &[1, 2])
+            .unwrap();
+    }
+
+    /// The memberships are grouped by domain
+    #[test]
+    fn get_memberships() {
+        let env = TestEnvBuilder::new().build();
+        let ftl = get_ftl();
+        let mut repo = MockListRepository::new();
+
+        repo.expect_get_memberships()
+            .with(eq(List::Black))
+            .return_const(Ok(vec![
+                ("a.com".to_owned(), 1),
+                ("b.com".to_owned(), 1),
+                ("a.com".to_owned(), 2),
+            ]));
+
+        let service = ListServiceImpl {
+            repo: Box::new(repo),
+            env: Arc::new(env),
+            ftl: Arc::new(ftl),
+            webhooks: get_webhooks(),
+        };
+
+        let mut expected = HashMap::new();
+        expected.insert("a.com".to_owned(), vec![1, 2]);
+        expected.insert("b.com".to_owned(), vec![1]);
+
+        assert_eq!(service.get_memberships(List::Black).unwrap(), expected);
+    }
+
+    /// Changing an invalid domain is an error and does not touch the
+    /// repository
+    #[test]
+    fn set_enabled_invalid() {
+        let env = TestEnvBuilder::new().build();
+        let ftl = get_ftl();
+        let repo = MockListRepository::new();
+
+        let service = ListServiceImpl {
+            repo: Box::new(repo),
+            env: Arc::new(env),
+            ftl: Arc::new(ftl),
+            webhooks: get_webhooks(),
+        };
+
+        let error = service
+            .set_enabled(List::White, "invalid domain", true)
+            .unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidDomain);
+    }
+
+    /// Adding a domain to the whitelist works when the domain does not exist
+    /// in either the whitelist or blacklist
+    #[test]
+    fn add_whitelist() {
+        let env = TestEnvBuilder::new().build();
+        let ftl = get_ftl();
+        let mut repo = MockListRepository::new();
+
+        repo.expect_contains()
+            .with(eq(List::White), eq("example.com"))
+            .return_const(Ok(false));
+        repo.expect_add()
+            .with(eq(List::White), eq("example.com"), eq(None))
+            .return_const(Ok(()));
+        repo.expect_contains()
+            .with(eq(List::Black), eq("example.com"))
+            .return_const(Ok(false));
+
+        let service = ListServiceImpl {
+            repo: Box::new(repo),
+            env: Arc::new(env),
+            ftl: Arc::new(ftl),
+            webhooks: get_webhooks(),
+        };
+
+        service
+            .add(List::White, "example.com", None, false)
+            .unwrap();
+    }
+
+    /// Adding a domain to the blacklist works when the domain does not exist
+    /// in either the whitelist or blacklist
+    #[test]
+    fn add_blacklist() {
+        let env = TestEnvBuilder::new().build();
+        let ftl = get_ftl();
+        let mut repo = MockListRepository::new();
+
+        repo.expect_contains()
+            .with(eq(List::Black), eq("example.com"))
+            .return_const(Ok(false));
+        repo.expect_add()
+            .with(eq(List::Black), eq("example.com"), eq(None))
+            .return_const(Ok(()));
+        repo.expect_contains()
+            .with(eq(List::White), eq("example.com"))
+            .return_const(Ok(false));
+
+        let service = ListServiceImpl {
+            repo: Box::new(repo),
+            env: Arc::new(env),
+            ftl: Arc::new(ftl),
+            webhooks: get_webhooks(),
+        };
+
+        service
+            .add(List::Black, "example.com", None, false)
+            .unwrap();
+    }
+
+    /// The webhook is notified of a successful add
+    #[test]
+    fn add_notifies_webhook() {
+        let env = TestEnvBuilder::new().build();
+        let ftl = get_ftl();
+        let mut repo = MockListRepository::new();
+        let mut webhooks = MockWebhookService::new();
+
+        repo.expect_contains().return_const(Ok(false));
+        repo.expect_add()
+            .with(eq(List::Black), eq("example.com"), eq(None))
+            .return_const(Ok(()));
+        webhooks
+            .expect_notify()
+            .withf(|event| {
+                event.list == List::Black
+                    && event.action == ListAction::Add
+                    && event.domain == Some("example.com".to_owned())
+            })
+            .times(1)
+            .return_const(());
+
+        let service = ListServiceImpl {
+            repo: Box::new(repo),
+            env: Arc::new(env),
+            ftl: Arc::new(ftl),
+            webhooks: Arc::new(webhooks),
+        };
+
+        service
+            .add(List::Black, "example.com", None, false)
+            .unwrap();
+    }
+
+    /// Adding a domain which is on the opposite list is a conflict unless it
+    /// is forced, and neither list is changed
+    #[test]
+    fn add_opposite_list() {
+        let env = TestEnvBuilder::new().build();
+        let ftl = get_ftl();
+        let mut repo = MockListRepository::new();
+
+        repo.expect_contains()
+            .with(eq(List::White), eq("example.com"))
+            .return_const(Ok(false));
+        repo.expect_contains()
+            .with(eq(List::Black), eq("example.com"))
+            .return_const(Ok(true));
+
+        let service = ListServiceImpl {
+            repo: Box::new(repo),
+            env: Arc::new(env),
+            ftl: Arc::new(ftl),
+            webhooks: get_webhooks(),
+        };
+
+        assert_eq!(
+            service
+                .add(List::White, "example.com", None, false)
+                .unwrap_err()
+                .kind(),
+            ErrorKind::DomainOnOppositeList("example.com".to_owned(), "blacklist".to_owned())
+        );
+    }
+
+    /// Adding a domain to the regex list works when the domain does not already
+    /// exist in the regex list
+    #[test]
+    fn add_regexlist() {
+        let env = TestEnvBuilder::new().build();
+        let ftl = get_ftl();
+        let mut repo = MockListRepository::new();
+
+        repo.expect_contains()
+            .with(eq(List::Regex), eq("example.com"))
+            .return_const(Ok(false));
+        repo.expect_add()
+            .with(eq(List::Regex), eq("example.com"), eq(None))
+            .return_const(Ok(()));
+
+        let service = ListServiceImpl {
+            repo: Box::new(repo),
+            env: Arc::new(env),
+            ftl: Arc::new(ftl),
+            webhooks: get_webhooks(),
+        };
+
+        service
+            .add(List::Regex, "example.com", None, false)
+            .unwrap();
+    }
+
+    /// Test that adding a domain which is already on the list is an error
+    fn add_duplicate_test(list: List, list_name: &str) {
+        let env = TestEnvBuilder::new().build();
+        let ftl = get_ftl();
+        let mut repo = MockListRepository::new();
+
+        repo.expect_contains()
+            .with(eq(list), eq("example.com"))
+            .return_const(Ok(true));
+
+        let service = ListServiceImpl {
+            repo: Box::new(repo),
+            env: Arc::new(env),
+            ftl: Arc::new(ftl),
+            webhooks: get_webhooks(),
+        };
+
+        assert_eq!(
+            service
+                .add(list, "example.com", None, false)
+                .unwrap_err()
+                .kind(),
+            ErrorKind::DomainAlreadyExists("example.com".to_owned(), list_name.to_owned())
+        );
+    }
+
+    /// Adding a domain which is already on the list is an error
+    #[test]
+    fn add_duplicate() {
+        add_duplicate_test(List::White, "whitelist");
+        add_duplicate_test(List::Black, "blacklist");
+        add_duplicate_test(List::Regex, "regex list");
+    }
+
+    /// Internationalized domains are added in their punycode form
+    #[test]
+    fn add_idn() {
+        let env = TestEnvBuilder::new().build();
+        let ftl = get_ftl();
+        let mut repo = MockListRepository::new();
+
+        repo.expect_contains()
+            .with(eq(List::Black), eq("xn--mnchen-3ya.de"))
+            .return_const(Ok(false));
+        repo.expect_add()
+            .with(eq(List::Black), eq("xn--mnchen-3ya.de"), eq(None))
+            .return_const(Ok(()));
+        repo.expect_contains()
+            .with(eq(List::White), eq("xn--mnchen-3ya.de"))
+            .return_const(Ok(false));
+
+        let service = ListServiceImpl {
+            repo: Box::new(repo),
+            env: Arc::new(env),
+            ftl: Arc::new(ftl),
+            webhooks: get_webhooks(),
+        };
+
+        service.add(List::Black, "münchen.de", None, false).unwrap();
+    }
+
+    /// Invalid internationalized domains are rejected without touching the
+    /// repository
+    #[test]
+    fn add_invalid_idn() {
+        let env = TestEnvBuilder::new().build();
+        let ftl = get_ftl();
+        let repo = MockListRepository::new();
+
+        let service = ListServiceImpl {
+            repo: Box::new(repo),
+            env: Arc::new(env),
+            ftl: Arc::new(ftl),
+            webhooks: get_webhooks(),
+        };
+
+        let error = service
+            .add(List::White, "bad\u{fffd}domain.com", None, false)
+            .unwrap_err();
+        assert_eq!(
+            error.kind(),
+            ErrorKind::InvalidIdn("bad\u{fffd}domain.com".to_owned())
+        );
+    }
+
+    /// Internationalized domains can be removed using either form
+    #[test]
+    fn remove_idn() {
+        delete_test(List::White, "xn--mnchen-3ya.de");
+
+        let env = TestEnvBuilder::new().build();
+        let ftl = get_ftl();
+        let mut repo = MockListRepository::new();
+
+        repo.expect_contains()
+            .with(eq(List::White), eq("xn--mnchen-3ya.de"))
+            .return_const(Ok(true));
+        repo.expect_remove()
+            .with(eq(List::White), eq("xn--mnchen-3ya.de"))
+            .return_const(Ok(()));
+
+        let service = ListServiceImpl {
+            repo: Box::new(repo),
+            env: Arc::new(env),
+            ftl: Arc::new(ftl),
+            webhooks: get_webhooks(),
+        };
+
+        service.remove(List::White, "münchen.de").unwrap();
+    }
+
+    /// Adding an invalid regex is an error and does not touch the repository
+    #[test]
+    fn add_invalid_regex() {
+        let env = TestEnvBuilder::new().build();
+        let ftl = get_ftl();
+        let repo = MockListRepository::new();
+
+        let service = ListServiceImpl {
+            repo: Box::new(repo),
+            env: Arc::new(env),
+            ftl: Arc::new(ftl),
+            webhooks: get_webhooks(),
+        };
+
+        let error = service.add(List::Regex, "([a-z", None, false).unwrap_err();
+        match error.kind() {
+            ErrorKind::InvalidRegex(pattern, _) => assert_eq!(pattern, "([a-z"),
+            kind => panic!("Unexpected error kind: {:?}", kind),
+        }
+    }
+
+    /// Create an enabled entry to import
+    fn new_entry(domain: &str) -> NewListEntry {
+        NewListEntry {
+            domain: domain.to_owned(),
+            enabled: true,
+            comment: None,
+        }
+    }
+
+    /// Importing in merge mode skips invalid entries, duplicates, and entries
+    /// which are already on the list
+    #[test]
+    fn import_merge() {
+        let env = TestEnvBuilder::new().build();
+        let ftl = get_ftl();
+        let mut repo = MockListRepository::new();
+
+        repo.expect_contains()
+            .with(eq(List::White), eq("a.com"))
+            .return_const(Ok(false));
+        repo.expect_contains()
+            .with(eq(List::Black), eq("a.com"))
+            .return_const(Ok(false));
+        repo.expect_contains()
+            .with(eq(List::Black), eq("example.com"))
+            .return_const(Ok(true));
+        repo.expect_import()
+            .withf(|lists, replace| {
+                lists
+                    == [
+                        (List::White, vec![new_entry("a.com")]),
+                        (List::Black, Vec::new()),
+                        (List::Regex, Vec::new()),
+                        (List::RegexWhite, Vec::new()),
+                    ]
+                    && !*replace
+            })
+            .return_const(Ok(()));
+
+        let service = ListServiceImpl {
+            repo: Box::new(repo),
+            env: Arc::new(env),
+            ftl: Arc::new(ftl),
+            webhooks: get_webhooks(),
+        };
+
+        let import = ListsImport {
+            whitelist: vec![
+                new_entry("a.com"),
+                new_entry("a.com"),
+                new_entry("invalid domain"),
+            ],
+            blacklist: vec![new_entry("example.com")],
+            regexlist: vec![new_entry("([a-z")],
+            ..ListsImport::default()
+        };
+
+        assert_eq!(
+            service.import(import, ImportMode::Merge, false).unwrap(),
+            ListsImportResult {
+                whitelist: ImportCounts {
+                    inserted: 1,
+                    skipped: 1,
+                    invalid: 1,
+                    conflicts: 0,
+                    moved: 0
+                },
+                blacklist: ImportCounts {
+                    inserted: 0,
+                    skipped: 1,
+                    invalid: 0,
+                    conflicts: 0,
+                    moved: 0
+                },
+                regexlist: ImportCounts {
+                    inserted: 0,
+                    skipped: 0,
+                    invalid: 1,
+                    conflicts: 0,
+                    moved: 0
+                },
+                ..ListsImportResult::default()
+            }
+        );
+    }
+
+    /// Importing in replace mode does not check for existing entries
+    #[test]
+    fn import_replace() {
+        let env = TestEnvBuilder::new().build();
+        let ftl = get_ftl();
+        let mut repo = MockListRepository::new();
+
+        repo.expect_import()
+            .withf(|lists, replace| {
+                lists
+                    == [
+                        (List::White, Vec::new()),
+                        (List::Black, vec![new_entry("example.com")]),
+                        (List::Regex, Vec::new()),
+                        (List::RegexWhite, Vec::new()),
+                    ]
+                    && *replace
+            })
+            .return_const(Ok(()));
+
+        let service = ListServiceImpl {
+            repo: Box::new(repo),
+            env: Arc::new(env),
+            ftl: Arc::new(ftl),
+            webhooks: get_webhooks(),
+        };
+
+        let import = ListsImport {
+            blacklist: vec![new_entry("example.com")],
+            ..ListsImport::default()
+        };
+
+        assert_eq!(
+            service.import(import, ImportMode::Replace, false).unwrap(),
+            ListsImportResult {
+                blacklist: ImportCounts {
+                    inserted: 1,
+                    skipped: 0,
+                    invalid: 0,
+                    conflicts: 0,
+                    moved: 0
+                },
+                ..ListsImportResult::default()
+            }
+        );
+    }
+
+    /// Adding multiple domains only adds the valid domains which are not on
+    /// the list yet
+    #[test]
+    fn add_many_blacklist() {
+        let env = TestEnvBuilder::new().build();
+        let ftl = get_ftl();
+        let mut repo = MockListRepository::new();
+
+        repo.expect_contains()
+            .with(eq(List::Black), eq("new.com"))
+            .return_const(Ok(false));
+        repo.expect_contains()
+            .with(eq(List::Black), eq("existing.com"))
+            .return_const(Ok(true));
+        repo.expect_add_many()
+            .withf(|list, domains| *list == List::Black && domains == ["new.com"])
+            .return_const(Ok(()));
+        repo.expect_contains()
+            .with(eq(List::White), eq("new.com"))
+            .return_const(Ok(false));
+
+        let service = ListServiceImpl {
+            repo: Box::new(repo),
+            env: Arc::new(env),
+            ftl: Arc::new(ftl),
+            webhooks: get_webhooks(),
+        };
+
+        let domains = vec![
+            "new.com".to_owned(),
+            "existing.com".to_owned(),
+            "not valid".to_owned(),
+            "new.com".to_owned(),
+        ];
+
+        assert_eq!(
+            service.add_many(List::Black, &domains, false).unwrap(),
+            BatchAddResult {
+                added: vec!["new.com".to_owned()],
+                already_exists: vec!["existing.com".to_owned(), "new.com".to_owned()],
+                invalid: vec!["not valid".to_owned()],
+                conflicts: Vec::new(),
+                moved: Vec::new()
+            }
+        );
+    }
+
+    /// Domains which are on the opposite list are reported as conflicts when
+    /// the add is not forced, and neither list is changed
+    #[test]
+    fn add_many_opposite_list() {
+        let env = TestEnvBuilder::new().build();
+        let ftl = get_ftl();
+        let mut repo = MockListRepository::new();
+
+        repo.expect_contains()
+            .with(eq(List::White), eq("example.com"))
+            .return_const(Ok(false));
+        repo.expect_contains()
+            .with(eq(List::Black), eq("example.com"))
+            .return_const(Ok(true));
+
+        let service = ListServiceImpl {
+            repo: Box::new(repo),
+            env: Arc::new(env),
+            ftl: Arc::new(ftl),
+            webhooks: get_webhooks(),
+        };
+
+        assert_eq!(
+            service
+                .add_many(List::White, &["example.com".to_owned()], false)
+                .unwrap(),
+            BatchAddResult {
+                conflicts: vec!["example.com".to_owned()],
+                ..BatchAddResult::default()
+            }
+        );
+    }
+
+    /// Imported whitelist and blacklist entries which would be on both lists
+    /// are counted as conflicts and not imported
+    #[test]
+    fn import_conflicts() {
+        let env = TestEnvBuilder::new().build();
+        let ftl = get_ftl();
+        let mut repo = MockListRepository::new();
+
+        repo.expect_contains()
+            .with(eq(List::White), eq("both.com"))
+            .return_const(Ok(false));
+        repo.expect_contains()
+            .with(eq(List::Black), eq("both.com"))
+            .return_const(Ok(false));
+        repo.expect_contains()
+            .with(eq(List::White), eq("example.com"))
+            .return_const(Ok(false));
+        repo.expect_contains()
+            .with(eq(List::Black), eq("example.com"))
+            .return_const(Ok(true));
+        repo.expect_import()
+            .withf(|lists, replace| {
+                lists
+                    == [
+                        (List::White, Vec::new()),
+                        (List::Black, Vec::new()),
+                        (List::Regex, Vec::new()),
+                        (List::RegexWhite, Vec::new()),
+                    ]
+                    && !*replace
+            })
+            .return_const(Ok(()));
+
+        let service = ListServiceImpl {
+            repo: Box::new(repo),
+            env: Arc::new(env),
+            ftl: Arc::new(ftl),
+            webhooks: get_webhooks(),
+        };
+
+        let import = ListsImport {
+            whitelist: vec![new_entry("both.com"), new_entry("example.com")],
+            blacklist: vec![new_entry("both.com")],
+            ..ListsImport::default()
+        };
+
+        assert_eq!(
+            service.import(import, ImportMode::Merge, false).unwrap(),
+            ListsImportResult {
+                whitelist: ImportCounts {
+                    inserted: 0,
+                    skipped: 0,
+                    invalid: 0,
+                    conflicts: 2,
+                    moved: 0
+                },
+                blacklist: ImportCounts {
+                    inserted: 0,
+                    skipped: 0,
+                    invalid: 0,
+                    conflicts: 1,
+                    moved: 0
+                },
+                ..ListsImportResult::default()
+            }
+        );
+    }
+
+    /// Domains are removed in one batch, and the ones which were not on the
+    /// list are reported
+    #[test]
+    fn remove_many_blacklist() {
+        let env = TestEnvBuilder::new().build();
+        let ftl = get_ftl();
+        let mut repo = MockListRepository::new();
+
+        repo.expect_remove_many()
+            .withf(|list, domains| {
+                *list == List::Black && domains == ["example.com", "not-found.com"]
+            })
+            .return_const(Ok(vec!["example.com".to_owned()]));
+
+        let service = ListServiceImpl {
+            repo: Box::new(repo),
+            env: Arc::new(env),
+            ftl: Arc::new(ftl),
+            webhooks: get_webhooks(),
+        };
+
+        let domains = vec!["example.com".to_owned(), "not-found.com".to_owned()];
+
+        assert_eq!(
+            service.remove_many(List::Black, &domains).unwrap(),
+            BatchRemoveResult {
+                removed: 1,
+                not_found: vec!["not-found.com".to_owned()]
+            }
+        );
+    }
+
+    /// Removing no domains does not touch the database
+    #[test]
+    fn remove_many_empty() {
+        let env = TestEnvBuilder::new().build();
+        let ftl = get_ftl();
+        let repo = MockListRepository::new();
+
+        let service = ListServiceImpl {
+            repo: Box::new(repo),
+            env: Arc::new(env),
+            ftl: Arc::new(ftl),
+            webhooks: get_webhooks(),
         };
 
-        service.add(List::Regex, "example.com").unwrap();
+        assert_eq!(
+            service.remove_many(List::Regex, &[]).unwrap(),
+            BatchRemoveResult::default()
+        );
     }
 
     #[test]
@@ -0,0 +1,157 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// List Entry Sorting
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::util::{Error, ErrorKind};
+
+/// The values accepted by the `sort` parameter
+const SORT_KEYS: &[&str] = &["domain", "date_added", "date_modified"];
+
+/// The values accepted by the `order` parameter
+const SORT_ORDERS: &[&str] = &["asc", "desc"];
+
+/// The column which list entries are sorted by
+#[cfg_attr(test, derive(Debug))]
+#[derive(Copy, Clone, PartialEq)]
+pub enum SortKey {
+    Domain,
+    DateAdded,
+    DateModified,
+}
+
+/// The direction list entries are sorted in
+#[cfg_attr(test, derive(Debug))]
+#[derive(Copy, Clone, PartialEq)]
+pub enum SortOrder {
+    Asc,
+    Desc,
+}
+
+/// How to sort list entries
+#[cfg_attr(test, derive(Debug))]
+#[derive(Copy, Clone, PartialEq)]
+pub struct ListSort {
+    pub key: SortKey,
+    pub order: SortOrder,
+}
+
+impl ListSort {
+    /// Parse the `sort` and `order` parameters. If there is no `sort`
+    /// parameter, the entries are not sorted and `None` is returned. The
+    /// order defaults to ascending.
+    pub fn from_params(sort: Option<&str>, order: Option<&str>) -> Result<Option<Self>, Error> {
+        let order = match order {
+            None | Some("asc") => SortOrder::Asc,
+            Some("desc") => SortOrder::Desc,
+            Some(order) => return Err(invalid_parameter("order", order, SORT_ORDERS)),
+        };
+
+        let key = match sort {
+            None => return Ok(None),
+            Some("domain") => SortKey::Domain,
+            Some("date_added") => SortKey::DateAdded,
+            Some("date_modified") => SortKey::DateModified,
+            Some(sort) => return Err(invalid_parameter("sort", sort, SORT_KEYS)),
+        };
+
+        Ok(Some(ListSort { key, order }))
+    }
+
+    /// Get the `ORDER BY` clause for the sort. Entries with the same value
+    /// are ordered by ID, so that pages of sorted entries are stable.
+    pub fn order_clause(self) -> String {
+        let column = match self.key {
+            SortKey::Domain => "domain",
+            SortKey::DateAdded => "date_added",
+            SortKey::DateModified => "date_modified",
+        };
+        let direction = match self.order {
+            SortOrder::Asc => "ASC",
+            SortOrder::Desc => "DESC",
+        };
+
+        format!("{} {}, id ASC", column, direction)
+    }
+}
+
+/// Create the error for a parameter value which is not one of the valid
+/// values
+fn invalid_parameter(parameter: &str, value: &str, valid: &[&str]) -> Error {
+    Error::from(ErrorKind::InvalidParameter(
+        parameter.to_owned(),
+        value.to_owned(),
+        valid.iter().map(|value| (*value).to_owned()).collect(),
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ListSort, SortKey, SortOrder};
+    use crate::util::ErrorKind;
+
+    /// The sort key and order are parsed, with ascending as the default
+    #[test]
+    fn parse() {
+        assert_eq!(
+            ListSort::from_params(Some("date_added"), None).unwrap(),
+            Some(ListSort {
+                key: SortKey::DateAdded,
+                order: SortOrder::Asc
+            })
+        );
+        assert_eq!(
+            ListSort::from_params(Some("domain"), Some("desc")).unwrap(),
+            Some(ListSort {
+                key: SortKey::Domain,
+                order: SortOrder::Desc
+            })
+        );
+        assert_eq!(ListSort::from_params(None, None).unwrap(), None);
+    }
+
+    /// Unknown values are rejected along with the valid values
+    #[test]
+    fn invalid() {
+        assert_eq!(
+            ListSort::from_params(Some("id"), None).unwrap_err().kind(),
+            ErrorKind::InvalidParameter(
+                "sort".to_owned(),
+                "id".to_owned(),
+                vec![
+                    "domain".to_owned(),
+                    "date_added".to_owned(),
+                    "date_modified".to_owned()
+                ]
+            )
+        );
+        assert_eq!(
+            ListSort::from_params(Some("domain"), Some("up"))
+                .unwrap_err()
+                .kind(),
+            ErrorKind::InvalidParameter(
+                "order".to_owned(),
+                "up".to_owned(),
+                vec!["asc".to_owned(), "desc".to_owned()]
+            )
+        );
+    }
+
+    /// Ties are broken by ID
+    #[test]
+    fn order_clause() {
+        assert_eq!(
+            ListSort {
+                key: SortKey::DateModified,
+                order: SortOrder::Desc
+            }
+            .order_clause(),
+            "date_modified DESC, id ASC"
+        );
+    }
+}
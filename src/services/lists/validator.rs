@@ -0,0 +1,234 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Domain List Validation
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::util::{Error, ErrorKind};
+use regex::Regex;
+use std::net::IpAddr;
+
+/// Check that the domain is a valid hostname for the exact lists. The error
+/// explains what is wrong with the domain, so the user can correct it.
+/// Underscores are only allowed at the start of a label, as in service
+/// records like `_dmarc.example.com`.
+pub fn validate_domain(domain: &str) -> Result<(), Error> {
+    let invalid = |reason: String| {
+        Err(Error::from(ErrorKind::InvalidHostname(
+            domain.to_owned(),
+            reason,
+        )))
+    };
+
+    if domain.is_empty() {
+        return invalid("domain is empty".to_owned());
+    }
+
+    // FTL matches queries by name, so IP addresses would never match
+    if domain.parse::<IpAddr>().is_ok()
+        || domain
+            .split('.')
+            .all(|label| label.parse::<usize>().is_ok())
+    {
+        return invalid("IP addresses are not allowed".to_owned());
+    }
+
+    if domain.len() > 253 {
+        return invalid("domain is longer than 253 characters".to_owned());
+    }
+
+    if domain.starts_with('.') {
+        return invalid("domain starts with a dot".to_owned());
+    }
+
+    if domain.ends_with('.') {
+        return invalid("domain ends with a dot".to_owned());
+    }
+
+    let mut label_start = 0;
+
+    for label in domain.split('.') {
+        if label.is_empty() {
+            return invalid(format!("empty label at position {}", label_start));
+        }
+
+        if label.len() > 63 {
+            return invalid("label too long".to_owned());
+        }
+
+        if label.starts_with('-') {
+            return invalid(format!(
+                "label starts with a hyphen at position {}",
+                label_start
+            ));
+        }
+
+        if label.ends_with('-') {
+            return invalid(format!(
+                "label ends with a hyphen at position {}",
+                label_start + label.len() - 1
+            ));
+        }
+
+        for (index, c) in label.char_indices() {
+            let valid = c.is_ascii_alphanumeric() || c == '-' || (c == '_' && index == 0);
+
+            if !valid {
+                return invalid(format!(
+                    "invalid character '{}' at position {}",
+                    c,
+                    label_start + index
+                ));
+            }
+        }
+
+        // Skip over the label and the dot after it
+        label_start += label.len() + 1;
+    }
+
+    Ok(())
+}
+
+/// Check that the pattern will compile in FTL. The `regex` crate is used as
+/// an approximation of FTL's POSIX extended regular expressions, which is
+/// enough to catch syntax errors such as unbalanced brackets.
+pub fn validate_regex(pattern: &str) -> Result<(), Error> {
+    compile_regex(pattern).map(|_| ())
+}
+
+/// Compile the pattern, applying the same checks as `validate_regex`
+pub fn compile_regex(pattern: &str) -> Result<Regex, Error> {
+    // An empty pattern would match every domain
+    if pattern.is_empty() {
+        return Err(Error::from(ErrorKind::InvalidRegex(
+            pattern.to_owned(),
+            "The pattern is empty".to_owned(),
+        )));
+    }
+
+    Regex::new(pattern)
+        .map_err(|e| Error::from(ErrorKind::InvalidRegex(pattern.to_owned(), e.to_string())))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{validate_domain, validate_regex};
+    use crate::util::ErrorKind;
+
+    /// Assert that the domain is rejected for the reason
+    fn invalid_domain_test(domain: &str, reason: &str) {
+        assert_eq!(
+            validate_domain(domain).unwrap_err().kind(),
+            ErrorKind::InvalidHostname(domain.to_owned(), reason.to_owned())
+        );
+    }
+
+    /// Valid domains are accepted
+    #[test]
+    fn valid_domains() {
+        for domain in &[
+            "example.com",
+            "sub.example.com",
+            "localhost",
+            "xn--mnchen-3ya.de",
+            "my-host1.example.com",
+            "_dmarc.example.com",
+            "UPPER.example.com",
+        ] {
+            assert!(validate_domain(domain).is_ok(), "{} was rejected", domain);
+        }
+    }
+
+    /// Empty domains and labels are rejected
+    #[test]
+    fn empty_labels() {
+        invalid_domain_test("", "domain is empty");
+        invalid_domain_test("example..com", "empty label at position 8");
+    }
+
+    /// Leading and trailing dots are rejected
+    #[test]
+    fn dots() {
+        invalid_domain_test(".example.com", "domain starts with a dot");
+        invalid_domain_test("example.com.", "domain ends with a dot");
+    }
+
+    /// Labels over 63 characters are rejected
+    #[test]
+    fn label_too_long() {
+        let label = "a".repeat(63);
+        assert!(validate_domain(&format!("{}.com", label)).is_ok());
+
+        invalid_domain_test(&format!("a{}.com", label), "label too long");
+    }
+
+    /// Domains over 253 characters are rejected
+    #[test]
+    fn domain_too_long() {
+        let label = "a".repeat(63);
+        let domain = format!("{0}.{0}.{0}.{0}", label);
+
+        assert_eq!(domain.len(), 255);
+        invalid_domain_test(&domain, "domain is longer than 253 characters");
+    }
+
+    /// Underscores are only allowed at the start of a label, and other
+    /// characters are rejected with their position
+    #[test]
+    fn invalid_characters() {
+        invalid_domain_test("ads_x.com", "invalid character '_' at position 3");
+        invalid_domain_test("ads.x_y.com", "invalid character '_' at position 5");
+        invalid_domain_test("ads example.com", "invalid character ' ' at position 3");
+        invalid_domain_test("example.com/ads", "invalid character '/' at position 11");
+    }
+
+    /// Labels can not start or end with a hyphen
+    #[test]
+    fn hyphens() {
+        invalid_domain_test(
+            "-ads.example.com",
+            "label starts with a hyphen at position 0",
+        );
+        invalid_domain_test("ads-.example.com", "label ends with a hyphen at position 3");
+    }
+
+    /// IP addresses are rejected
+    #[test]
+    fn ip_addresses() {
+        invalid_domain_test("127.0.0.1", "IP addresses are not allowed");
+        invalid_domain_test("::1", "IP addresses are not allowed");
+        invalid_domain_test("2001:db8::1", "IP addresses are not allowed");
+        invalid_domain_test("10.0", "IP addresses are not allowed");
+    }
+
+    /// Valid patterns are accepted
+    #[test]
+    fn valid() {
+        assert!(validate_regex("(^|\\.)example\\.com$").is_ok());
+        assert!(validate_regex("^ad[s]?[0-9]*\\.").is_ok());
+    }
+
+    /// Unbalanced brackets are rejected, and the error includes the pattern
+    #[test]
+    fn unbalanced_brackets() {
+        for pattern in &["([a-z", "[a-z", "(example"] {
+            match validate_regex(pattern).unwrap_err().kind() {
+                ErrorKind::InvalidRegex(error_pattern, _) => assert_eq!(error_pattern, *pattern),
+                kind => panic!("Unexpected error kind: {:?}", kind),
+            }
+        }
+    }
+
+    /// Empty patterns are rejected
+    #[test]
+    fn empty() {
+        assert_eq!(
+            validate_regex("").unwrap_err().kind(),
+            ErrorKind::InvalidRegex("".to_owned(), "The pattern is empty".to_owned())
+        );
+    }
+}
@@ -0,0 +1,69 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Wildcard Domain Expansion
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{services::lists::List, util::Error};
+
+/// Generate the regex entry which matches the domain and all of its
+/// subdomains, such as `(\.|^)example\.com$` for `example.com`. The domain is
+/// validated as if it were added to the blacklist, so the only character
+/// which needs escaping is the dot.
+pub fn wildcard_regex(domain: &str) -> Result<String, Error> {
+    let domain = List::Black.normalize(&domain.to_lowercase())?;
+    List::Black.validate(&domain)?;
+
+    Ok(format!("(\\.|^){}$", domain.replace('.', "\\.")))
+}
+
+/// The comment stored with a wildcard entry when none is given, noting the
+/// domain it was generated from
+pub fn wildcard_comment(domain: &str) -> String {
+    format!("Wildcard for {}", domain)
+}
+
+#[cfg(test)]
+mod test {
+    use super::wildcard_regex;
+    use crate::util::ErrorKind;
+    use regex::Regex;
+
+    /// The generated regex matches the domain and its subdomains only
+    #[test]
+    fn matches_subdomains() {
+        let pattern = wildcard_regex("Example.com").unwrap();
+        let regex = Regex::new(&pattern).unwrap();
+
+        assert_eq!(pattern, "(\\.|^)example\\.com$");
+        assert!(regex.is_match("example.com"));
+        assert!(regex.is_match("ads.example.com"));
+        assert!(!regex.is_match("badexample.com"));
+        assert!(!regex.is_match("example.com.evil.net"));
+    }
+
+    /// Internationalized domains are converted to punycode first
+    #[test]
+    fn idn() {
+        assert_eq!(
+            wildcard_regex("münchen.de").unwrap(),
+            "(\\.|^)xn--mnchen-3ya\\.de$"
+        );
+    }
+
+    /// Invalid domains are rejected
+    #[test]
+    fn invalid() {
+        assert_eq!(
+            wildcard_regex("ex*ample.com").unwrap_err().kind(),
+            ErrorKind::InvalidHostname(
+                "ex*ample.com".to_owned(),
+                "invalid character '*' at position 2".to_owned()
+            )
+        );
+    }
+}
@@ -0,0 +1,208 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// MAC Vendor Lookup
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use shaku::{Component, Module, ModuleBuildContext};
+use std::collections::HashMap;
+
+/// The lengths (in hex digits) of the assigned MAC prefixes: MA-S, MA-M, and
+/// MA-L (the OUI). Longer prefixes are checked first.
+const PREFIX_LENGTHS: [usize; 3] = [9, 7, 6];
+
+/// The vendor table, embedded in the binary
+#[cfg(feature = "mac-vendors")]
+#[derive(RustEmbed)]
+#[folder = "data/"]
+struct VendorAssets;
+
+/// Looks up the vendors of MAC addresses by their prefix. The table is
+/// embedded in the binary, unless the API is built without the `mac-vendors`
+/// feature, in which case no vendors are found.
+#[derive(Default)]
+pub struct MacVendors {
+    /// The vendors, keyed by their prefix in uppercase hex digits
+    vendors: HashMap<String, String>,
+}
+
+impl<M: Module> Component<M> for MacVendors {
+    type Interface = Self;
+    type Parameters = ();
+
+    fn build(_: &mut ModuleBuildContext<M>, _: ()) -> Box<Self> {
+        Box::new(MacVendors::embedded())
+    }
+}
+
+impl MacVendors {
+    /// Load the embedded vendor table
+    #[cfg(feature = "mac-vendors")]
+    pub fn embedded() -> Self {
+        VendorAssets::get("mac_vendors.txt")
+            .map(|table| MacVendors::parse(&String::from_utf8_lossy(&table)))
+            .unwrap_or_default()
+    }
+
+    /// The vendor table is not embedded, so there are no vendors
+    #[cfg(not(feature = "mac-vendors"))]
+    pub fn embedded() -> Self {
+        MacVendors::default()
+    }
+
+    /// Parse a vendor table. Each line holds a prefix in hex digits and the
+    /// vendor, separated by a tab. Empty lines, comments (starting with `#`),
+    /// and lines with an invalid prefix are skipped.
+    pub fn parse(table: &str) -> Self {
+        let vendors = table
+            .lines()
+            .filter(|line| !line.trim().is_empty() && !line.starts_with('#'))
+            .filter_map(|line| {
+                let mut parts = line.splitn(2, '\t');
+                let prefix = parts.next()?.trim();
+                let vendor = parts.next()?.trim();
+
+                if !PREFIX_LENGTHS.contains(&prefix.len())
+                    || !prefix.chars().all(|c| c.is_ascii_hexdigit())
+                    || vendor.is_empty()
+                {
+                    return None;
+                }
+
+                Some((prefix.to_ascii_uppercase(), vendor.to_owned()))
+            })
+            .collect();
+
+        MacVendors { vendors }
+    }
+
+    /// Get the vendor of a MAC address, such as `b8:27:eb:01:02:03`, using
+    /// the longest matching prefix. Locally administered addresses, which
+    /// includes randomized addresses, have no vendor.
+    pub fn lookup(&self, mac: &str) -> Option<&str> {
+        let digits = normalize_mac(mac)?;
+
+        if is_locally_administered(&digits) {
+            return None;
+        }
+
+        PREFIX_LENGTHS
+            .iter()
+            .find_map(|&length| self.vendors.get(&digits[..length]))
+            .map(String::as_str)
+    }
+}
+
+/// Convert a MAC address to its 12 uppercase hex digits. The address may be
+/// separated by colons, dashes, or dots (`b827.eb01.0203`), or not at all.
+fn normalize_mac(mac: &str) -> Option<String> {
+    let digits: String = mac
+        .chars()
+        .filter(|c| !matches!(c, ':' | '-' | '.'))
+        .collect();
+
+    if digits.len() != 12 || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    Some(digits.to_ascii_uppercase())
+}
+
+/// Check the locally administered bit (0x02) of the first octet
+fn is_locally_administered(digits: &str) -> bool {
+    u8::from_str_radix(&digits[..2], 16)
+        .map(|octet| octet & 0x02 != 0)
+        .unwrap_or(true)
+}
+
+#[cfg(test)]
+mod test {
+    use super::MacVendors;
+
+    /// A table with an OUI, and longer prefixes inside of another OUI
+    fn test_table() -> MacVendors {
+        MacVendors::parse(
+            "# Test vendors\n\
+             \n\
+             B827EB\tRaspberry Pi Foundation\n\
+             70b3d5\tIEEE Registration Authority\n\
+             70B3D5F\tExample MA-M Vendor\n\
+             70B3D5F2A\tExample MA-S Vendor\n\
+             XYZ123\tInvalid Prefix\n\
+             00000\tShort Prefix\n\
+             001788\n",
+        )
+    }
+
+    /// An address is matched by its OUI, in any case and with any separator
+    #[test]
+    fn oui_prefix() {
+        let vendors = test_table();
+
+        for mac in &[
+            "b8:27:eb:01:02:03",
+            "B8-27-EB-01-02-03",
+            "b827.eb01.0203",
+            "B827EB010203",
+        ] {
+            assert_eq!(vendors.lookup(mac), Some("Raspberry Pi Foundation"));
+        }
+    }
+
+    /// The longest matching prefix is used
+    #[test]
+    fn longest_prefix() {
+        let vendors = test_table();
+
+        assert_eq!(
+            vendors.lookup("70:b3:d5:f2:a1:00"),
+            Some("Example MA-S Vendor")
+        );
+        assert_eq!(
+            vendors.lookup("70:b3:d5:f2:b1:00"),
+            Some("Example MA-M Vendor")
+        );
+        assert_eq!(
+            vendors.lookup("70:b3:d5:01:02:03"),
+            Some("IEEE Registration Authority")
+        );
+    }
+
+    /// Locally administered (including randomized) addresses have no vendor,
+    /// even if the table has their prefix
+    #[test]
+    fn locally_administered() {
+        let vendors = MacVendors::parse("BA27EB\tLocal Vendor\n525400\tQEMU\n");
+
+        assert_eq!(vendors.lookup("ba:27:eb:01:02:03"), None);
+        assert_eq!(vendors.lookup("52:54:00:12:34:56"), None);
+        assert_eq!(vendors.lookup("da:a1:19:00:00:01"), None);
+    }
+
+    /// Unknown prefixes, invalid table lines, and values which are not MAC
+    /// addresses have no vendor
+    #[test]
+    fn no_match() {
+        let vendors = test_table();
+
+        assert_eq!(vendors.lookup("00:11:22:33:44:55"), None);
+        assert_eq!(vendors.lookup("00:17:88:01:02:03"), None);
+        assert_eq!(vendors.lookup("b8:27:eb:01:02"), None);
+        assert_eq!(vendors.lookup("ip-10.1.1.1"), None);
+        assert_eq!(vendors.lookup(""), None);
+    }
+
+    /// The embedded table is loaded
+    #[cfg(feature = "mac-vendors")]
+    #[test]
+    fn embedded_table() {
+        assert_eq!(
+            MacVendors::embedded().lookup("b8:27:eb:01:02:03"),
+            Some("Raspberry Pi Foundation")
+        );
+    }
+}
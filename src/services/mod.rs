@@ -8,8 +8,20 @@
 // This file is copyright under the latest version of the EUPL.
 // Please see LICENSE file for your rights under this license.
 
+pub mod adlists;
+pub mod dnsmasq;
 pub mod domain_audit;
+pub mod exclusions;
+pub mod gravity;
+pub mod groups;
+pub mod hostnames;
 pub mod lists;
+pub mod mac_vendors;
+pub mod privacy;
+pub mod restart;
+pub mod settings;
+pub mod system_info;
+pub mod webhooks;
 
 use crate::{
     databases::{
@@ -19,9 +31,21 @@ use crate::{
     env::Env,
     ftl::FtlConnectionType,
 };
+use adlists::AdlistRepositoryImpl;
+use dnsmasq::DnsmasqConfigGeneratorImpl;
 use domain_audit::DomainAuditRepositoryImpl;
+use exclusions::ExclusionServiceImpl;
+use gravity::{GravityCountCache, GravityRepositoryImpl};
+use groups::GroupRepositoryImpl;
+use hostnames::HostnameCache;
 use lists::{ListRepositoryImpl, ListServiceImpl};
+use mac_vendors::MacVendors;
+use privacy::PrivacyServiceImpl;
+use restart::RestartServiceImpl;
+use settings::SettingsRepositoryImpl;
 use shaku::module;
+use system_info::SystemInfoServiceImpl;
+use webhooks::WebhookServiceImpl;
 
 module! {
     pub PiholeModule {
@@ -29,12 +53,25 @@ module! {
             Env,
             FtlConnectionType,
             GravityDatabasePool,
-            FtlDatabasePool
+            FtlDatabasePool,
+            WebhookServiceImpl,
+            PrivacyServiceImpl,
+            SystemInfoServiceImpl,
+            RestartServiceImpl,
+            SettingsRepositoryImpl,
+            DnsmasqConfigGeneratorImpl,
+            ExclusionServiceImpl,
+            GravityCountCache,
+            HostnameCache,
+            MacVendors
         ],
         providers = [
             ListRepositoryImpl,
             ListServiceImpl,
             DomainAuditRepositoryImpl,
+            AdlistRepositoryImpl,
+            GroupRepositoryImpl,
+            GravityRepositoryImpl,
             GravityDatabase,
             FtlDatabase
         ]
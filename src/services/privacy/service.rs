@@ -0,0 +1,58 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Privacy Service
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    ftl::FtlRedactor,
+    services::settings::{parse_setting, SettingsRepository},
+    settings::{FtlConfEntry, FtlPrivacyLevel},
+    util::Error,
+};
+use shaku::{Component, Interface};
+use std::sync::Arc;
+
+/// Describes reading the privacy level which the statistics endpoints must
+/// enforce. Endpoints which read FTL's shared memory apply it through an
+/// `FtlRedactor`, which decides if they can return data and hides domains and
+/// clients.
+#[cfg_attr(test, mockall::automock)]
+pub trait PrivacyService: Interface {
+    /// Get the privacy level FTL is configured with
+    fn privacy_level(&self) -> Result<FtlPrivacyLevel, Error>;
+
+    /// Get the redactor for the configured privacy level
+    fn redactor(&self) -> Result<FtlRedactor, Error>;
+
+    /// Set the privacy level in the FTL config
+    fn set_privacy_level(&self, level: FtlPrivacyLevel) -> Result<(), Error>;
+}
+
+/// The implementation of `PrivacyService`, which reads and writes the level in
+/// the FTL config through the settings repository
+#[derive(Component)]
+#[shaku(interface = PrivacyService)]
+pub struct PrivacyServiceImpl {
+    #[shaku(inject)]
+    settings: Arc<dyn SettingsRepository>,
+}
+
+impl PrivacyService for PrivacyServiceImpl {
+    fn privacy_level(&self) -> Result<FtlPrivacyLevel, Error> {
+        parse_setting(&self.settings.get_ftl(FtlConfEntry::PrivacyLevel)?)
+    }
+
+    fn redactor(&self) -> Result<FtlRedactor, Error> {
+        Ok(FtlRedactor::new(self.privacy_level()?))
+    }
+
+    fn set_privacy_level(&self, level: FtlPrivacyLevel) -> Result<(), Error> {
+        self.settings
+            .set_ftl(FtlConfEntry::PrivacyLevel, &(level as u8).to_string())
+    }
+}
@@ -0,0 +1,118 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// DNS Server Restart Service
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    env::Env,
+    util::{Error, ErrorKind},
+};
+use failure::ResultExt;
+use nix::{
+    sys::signal::{kill, Signal},
+    unistd::Pid,
+};
+use shaku::{Component, Interface};
+use std::{
+    process::{Command, Stdio},
+    sync::Arc,
+};
+
+/// Describes reloading and restarting the DNS server, so changed settings
+/// take effect
+#[cfg_attr(test, mockall::automock)]
+pub trait RestartService: Interface {
+    /// Check if the API is allowed to reload or restart the DNS server
+    fn is_enabled(&self) -> bool;
+
+    /// Send SIGHUP to FTL, so it reloads the lists and local records
+    fn reload_dns(&self) -> Result<(), Error>;
+
+    /// Restart FTL, so it reads its config and the dnsmasq config again
+    fn restart_dns(&self) -> Result<(), Error>;
+}
+
+/// The implementation of `RestartService`, which signals or restarts the
+/// running FTL process
+#[derive(Component)]
+#[shaku(interface = RestartService)]
+pub struct RestartServiceImpl {
+    #[shaku(inject)]
+    env: Arc<Env>,
+}
+
+impl RestartService for RestartServiceImpl {
+    fn is_enabled(&self) -> bool {
+        self.env.config().general.allow_dns_restart
+    }
+
+    fn reload_dns(&self) -> Result<(), Error> {
+        reload_dns(&self.env)
+    }
+
+    fn restart_dns(&self) -> Result<(), Error> {
+        restart_dns(&self.env)
+    }
+}
+
+/// Reload the DNS server to activate config changes. Nothing is done if
+/// restarting the DNS server is turned off in the config.
+pub fn reload_dns(env: &Env) -> Result<(), Error> {
+    // Don't actually reload the DNS server during testing
+    if env.is_test() || !env.config().general.allow_dns_restart {
+        return Ok(());
+    }
+
+    // Get the PID of FTLDNS. There doesn't seem to be a better way than to run
+    // pidof in a shell.
+    let output = Command::new("pidof")
+        .arg("pihole-FTL")
+        .output()
+        .context(ErrorKind::ReloadDnsError)?;
+
+    // Check if it returned successfully
+    if !output.status.success() {
+        return Err(Error::from(ErrorKind::ReloadDnsError));
+    }
+
+    // Parse the output for the PID
+    let pid_str = String::from_utf8_lossy(&output.stdout);
+    let pid = pid_str
+        .trim()
+        .parse::<usize>()
+        .context(ErrorKind::ReloadDnsError)?;
+
+    // Send SIGHUP to FTLDNS so it reloads the lists
+    kill(Pid::from_raw(pid as libc::pid_t), Signal::SIGHUP).context(ErrorKind::ReloadDnsError)?;
+
+    Ok(())
+}
+
+/// Restart the DNS server (via `pihole restartdns`). Nothing is done if
+/// restarting the DNS server is turned off in the config.
+pub fn restart_dns(env: &Env) -> Result<(), Error> {
+    // Don't actually run anything during a test
+    if env.is_test() || !env.config().general.allow_dns_restart {
+        return Ok(());
+    }
+
+    let status = Command::new("sudo")
+        .arg("pihole")
+        .arg("restartdns")
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .context(ErrorKind::RestartDnsError)?;
+
+    if status.success() {
+        Ok(())
+    } else {
+        Err(Error::from(ErrorKind::RestartDnsError))
+    }
+}
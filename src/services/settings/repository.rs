@@ -0,0 +1,509 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// SetupVars Repository
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    env::{Env, FileStamp, PiholeFile},
+    settings::{
+        find_value, read_config, replace_value, update_config, ConfigEntry, FtlConfEntry,
+        SetupVarsEntry,
+    },
+    util::{Error, ErrorKind},
+};
+use shaku::{Component, Interface};
+use std::{
+    collections::HashMap,
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
+
+/// Describes reading and writing the settings in SetupVars and the FTL
+/// config. Values are validated against the entry before they are written.
+#[cfg_attr(test, mockall::automock)]
+pub trait SettingsRepository: Interface {
+    /// Get the value of the entry, or its default if it is not set
+    fn get(&self, entry: SetupVarsEntry) -> Result<String, Error>;
+
+    /// Get the value of a `true`/`false` entry
+    fn get_bool(&self, entry: SetupVarsEntry) -> Result<bool, Error>;
+
+    /// Get the value of a numeric entry
+    fn get_usize(&self, entry: SetupVarsEntry) -> Result<usize, Error>;
+
+    /// Get the items of a comma-separated entry. Empty items are skipped.
+    fn get_list(&self, entry: SetupVarsEntry) -> Result<Vec<String>, Error>;
+
+    /// Set the value of the entry. An empty value removes the entry, so its
+    /// default is used. An `InvalidSettingValue` error is returned if the
+    /// value is not valid for the entry.
+    fn set(&self, entry: SetupVarsEntry, value: &str) -> Result<(), Error>;
+
+    /// Get the upstream DNS servers, in order until the first missing number
+    fn upstreams(&self) -> Result<Vec<String>, Error>;
+
+    /// Replace the upstream DNS servers, numbering them from one in the order
+    /// they are given
+    fn set_upstreams(&self, upstreams: &[String]) -> Result<(), Error>;
+
+    /// Get the value of the FTL config entry, or its default if it is not set
+    fn get_ftl(&self, entry: FtlConfEntry) -> Result<String, Error>;
+
+    /// Set the value of the FTL config entry, like `set`
+    fn set_ftl(&self, entry: FtlConfEntry, value: &str) -> Result<(), Error>;
+}
+
+/// Parse a setting value. An `InvalidSettingValue` error is returned if it
+/// can not be parsed.
+pub fn parse_setting<T: FromStr>(value: &str) -> Result<T, Error> {
+    value
+        .parse()
+        .map_err(|_| Error::from(ErrorKind::InvalidSettingValue))
+}
+
+/// The cached lines of a file, along with the stamp of the file they were read
+/// from
+type CachedLines = (FileStamp, Arc<Vec<String>>);
+
+/// The implementation of `SettingsRepository`. The lines of each file are
+/// cached until the file changes, and the file is replaced atomically when it
+/// is written.
+#[derive(Component)]
+#[shaku(interface = SettingsRepository)]
+pub struct SettingsRepositoryImpl {
+    #[shaku(inject)]
+    env: Arc<Env>,
+    #[shaku(default)]
+    cached: Mutex<HashMap<PiholeFile, CachedLines>>,
+}
+
+impl SettingsRepositoryImpl {
+    /// Create a repository outside of the dependency injection module, such
+    /// as for the CLI
+    pub fn new(env: Arc<Env>) -> Self {
+        SettingsRepositoryImpl {
+            env,
+            cached: Mutex::default(),
+        }
+    }
+
+    /// Get the lines of the file, from the cache if the file did not change
+    /// since it was read. A file is considered changed if its modification
+    /// time, length, or inode changed.
+    fn lines(&self, file: PiholeFile) -> Result<Arc<Vec<String>>, Error> {
+        // If the file can not be found, the cache can not be checked
+        let stamp = match self.env.file_stamp(file) {
+            Some(stamp) => stamp,
+            None => return Ok(Arc::new(read_config(file, &self.env)?)),
+        };
+        let mut cached = self.cached.lock().unwrap();
+
+        if let Some((cached_stamp, lines)) = cached.get(&file) {
+            if *cached_stamp == stamp {
+                return Ok(Arc::clone(lines));
+            }
+        }
+
+        let lines = Arc::new(read_config(file, &self.env)?);
+        cached.insert(file, (stamp, Arc::clone(&lines)));
+        Ok(lines)
+    }
+
+    /// Get the value of the entry, or its default if it is not set
+    fn value(&self, entry: &impl ConfigEntry) -> Result<String, Error> {
+        Ok(find_value(&self.lines(entry.file())?, &entry.key())
+            .unwrap_or_else(|| entry.get_default())
+            .to_owned())
+    }
+
+    /// Validate the value and write it to the entry's file
+    fn set_value(&self, entry: &impl ConfigEntry, value: &str) -> Result<(), Error> {
+        if !entry.is_valid(value) {
+            return Err(Error::from(ErrorKind::InvalidSettingValue));
+        }
+
+        self.update(entry.file(), |lines| {
+            replace_value(lines, &entry.key(), value)
+        })
+    }
+
+    /// Read the file, change the lines, and write it. The cache is locked
+    /// while the file is rewritten, and the file is removed from the cache
+    /// afterwards.
+    fn update(&self, file: PiholeFile, change: impl FnOnce(&mut Vec<String>)) -> Result<(), Error> {
+        let mut cached = self.cached.lock().unwrap();
+        cached.remove(&file);
+
+        update_config(file, &self.env, |lines| {
+            change(lines);
+            Ok(())
+        })
+    }
+}
+
+impl SettingsRepository for SettingsRepositoryImpl {
+    fn get(&self, entry: SetupVarsEntry) -> Result<String, Error> {
+        self.value(&entry)
+    }
+
+    fn get_bool(&self, entry: SetupVarsEntry) -> Result<bool, Error> {
+        parse_setting(&self.get(entry)?)
+    }
+
+    fn get_usize(&self, entry: SetupVarsEntry) -> Result<usize, Error> {
+        parse_setting(&self.get(entry)?)
+    }
+
+    fn get_list(&self, entry: SetupVarsEntry) -> Result<Vec<String>, Error> {
+        Ok(self
+            .get(entry)?
+            .split(',')
+            .filter(|item| !item.is_empty())
+            .map(ToOwned::to_owned)
+            .collect())
+    }
+
+    fn set(&self, entry: SetupVarsEntry, value: &str) -> Result<(), Error> {
+        self.set_value(&entry, value)
+    }
+
+    fn upstreams(&self) -> Result<Vec<String>, Error> {
+        let lines = self.lines(PiholeFile::SetupVars)?;
+
+        Ok((1..)
+            .map(|number| find_value(&lines, &SetupVarsEntry::PiholeDns(number).key()))
+            .take_while(Option::is_some)
+            .flatten()
+            .map(ToOwned::to_owned)
+            .collect())
+    }
+
+    fn set_upstreams(&self, upstreams: &[String]) -> Result<(), Error> {
+        if upstreams
+            .iter()
+            .any(|upstream| upstream.is_empty() || !SetupVarsEntry::PiholeDns(1).is_valid(upstream))
+        {
+            return Err(Error::from(ErrorKind::InvalidSettingValue));
+        }
+
+        self.update(PiholeFile::SetupVars, |lines| {
+            lines.retain(|line| !line.starts_with("PIHOLE_DNS_"));
+
+            for (index, upstream) in upstreams.iter().enumerate() {
+                replace_value(lines, &SetupVarsEntry::PiholeDns(index + 1).key(), upstream);
+            }
+        })
+    }
+
+    fn get_ftl(&self, entry: FtlConfEntry) -> Result<String, Error> {
+        self.value(&entry)
+    }
+
+    fn set_ftl(&self, entry: FtlConfEntry, value: &str) -> Result<(), Error> {
+        self.set_value(&entry, value)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{SettingsRepository, SettingsRepositoryImpl};
+    use crate::{
+        env::{Env, FileStamp, PiholeFile},
+        settings::{FtlConfEntry, SetupVarsEntry},
+        testing::TestEnvBuilder,
+        util::ErrorKind,
+    };
+    use std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
+        time::SystemTime,
+    };
+
+    /// Create a repository with SetupVars
+    fn repository(setup_vars: &str) -> SettingsRepositoryImpl {
+        repository_with_env(
+            TestEnvBuilder::new()
+                .file(PiholeFile::SetupVars, setup_vars)
+                .build(),
+        )
+    }
+
+    /// Create a repository with the environment
+    fn repository_with_env(env: Env) -> SettingsRepositoryImpl {
+        SettingsRepositoryImpl::new(Arc::new(env))
+    }
+
+    /// Set values and check SetupVars afterwards
+    fn test_update(before: &str, after: &str, update: impl FnOnce(&SettingsRepositoryImpl)) {
+        let env_builder = TestEnvBuilder::new().file_expect(PiholeFile::SetupVars, before, after);
+        let mut test_file = env_builder.clone_test_files().into_iter().next().unwrap();
+        let repository = repository_with_env(env_builder.build());
+
+        update(&repository);
+
+        let mut buffer = String::new();
+        test_file.assert_expected(&mut buffer);
+    }
+
+    /// Comments and blank lines are skipped
+    #[test]
+    fn comments_and_blank_lines() {
+        let repository = repository(
+            "# Pi-hole settings\n\
+             \n\
+             #WEBUIBOXEDLAYOUT=traditional\n\
+             WEBUIBOXEDLAYOUT=boxed\n\
+             \n\
+             # QUERY_LOGGING=true\n",
+        );
+
+        assert_eq!(repository.get(SetupVarsEntry::WebLayout).unwrap(), "boxed");
+        assert!(!repository.get_bool(SetupVarsEntry::QueryLogging).unwrap());
+    }
+
+    /// If a key is set more than once, the last value is used
+    #[test]
+    fn duplicate_keys() {
+        let repository = repository(
+            "QUERY_LOGGING=true\n\
+             DHCP_LEASETIME=24\n\
+             QUERY_LOGGING=false\n",
+        );
+
+        assert!(!repository.get_bool(SetupVarsEntry::QueryLogging).unwrap());
+        assert_eq!(
+            repository.get_usize(SetupVarsEntry::DhcpLeasetime).unwrap(),
+            24
+        );
+    }
+
+    /// Windows line endings are not part of the values
+    #[test]
+    fn crlf_endings() {
+        let repository = repository(
+            "QUERY_LOGGING=false\r\n\
+             PIHOLE_DNS_1=8.8.8.8\r\n\
+             PIHOLE_DNS_2=8.8.4.4\r\n",
+        );
+
+        assert!(!repository.get_bool(SetupVarsEntry::QueryLogging).unwrap());
+        assert_eq!(repository.upstreams().unwrap(), vec!["8.8.8.8", "8.8.4.4"]);
+    }
+
+    /// Missing keys, empty values, and a missing file use the default
+    #[test]
+    fn defaults() {
+        let repository = repository("WEBUIBOXEDLAYOUT=\n");
+
+        assert_eq!(repository.get(SetupVarsEntry::WebLayout).unwrap(), "boxed");
+        assert_eq!(repository.get(SetupVarsEntry::WebLanguage).unwrap(), "en");
+
+        let repository = repository_with_env(TestEnvBuilder::new().build());
+
+        assert_eq!(repository.get(SetupVarsEntry::WebLanguage).unwrap(), "en");
+        assert!(repository.upstreams().unwrap().is_empty());
+    }
+
+    /// Values which can not be parsed are an error
+    #[test]
+    fn invalid_value() {
+        let repository = repository("QUERY_LOGGING=yes\n");
+
+        assert_eq!(
+            repository
+                .get_bool(SetupVarsEntry::QueryLogging)
+                .unwrap_err()
+                .kind(),
+            ErrorKind::InvalidSettingValue
+        );
+    }
+
+    /// Create a repository with a cached version of SetupVars, which has the
+    /// stamp returned by `stamp`
+    fn repository_with_cache(
+        setup_vars: &str,
+        cached: &str,
+        stamp: impl FnOnce(FileStamp) -> FileStamp,
+    ) -> SettingsRepositoryImpl {
+        let env = TestEnvBuilder::new()
+            .file(PiholeFile::SetupVars, setup_vars)
+            .build();
+        let stamp = stamp(env.file_stamp(PiholeFile::SetupVars).unwrap());
+        let mut cache = HashMap::new();
+        cache.insert(
+            PiholeFile::SetupVars,
+            (stamp, Arc::new(vec![cached.to_owned()])),
+        );
+
+        SettingsRepositoryImpl {
+            env: Arc::new(env),
+            cached: Mutex::new(cache),
+        }
+    }
+
+    /// The cached lines are used while SetupVars does not change
+    #[test]
+    fn cached() {
+        let repository = repository_with_cache(
+            "WEBUIBOXEDLAYOUT=boxed\n",
+            "WEBUIBOXEDLAYOUT=traditional",
+            |stamp| stamp,
+        );
+
+        assert_eq!(
+            repository.get(SetupVarsEntry::WebLayout).unwrap(),
+            "traditional"
+        );
+    }
+
+    /// SetupVars is read again if its length or inode changed, even if the
+    /// modification time is the same
+    #[test]
+    fn cache_invalidated() {
+        let changes: [fn(FileStamp) -> FileStamp; 3] = [
+            |stamp| FileStamp {
+                modified: SystemTime::UNIX_EPOCH,
+                ..stamp
+            },
+            |stamp| FileStamp {
+                len: stamp.len + 1,
+                ..stamp
+            },
+            |stamp| FileStamp {
+                inode: stamp.inode + 1,
+                ..stamp
+            },
+        ];
+
+        for change in changes.iter() {
+            let repository = repository_with_cache(
+                "WEBUIBOXEDLAYOUT=boxed\n",
+                "WEBUIBOXEDLAYOUT=traditional",
+                change,
+            );
+
+            assert_eq!(repository.get(SetupVarsEntry::WebLayout).unwrap(), "boxed");
+        }
+    }
+
+    /// Lists are split on commas, skipping empty items
+    #[test]
+    fn get_list() {
+        let repository = repository("API_EXCLUDE_DOMAINS=example.com,,ads.net\n");
+
+        assert_eq!(
+            repository
+                .get_list(SetupVarsEntry::ApiExcludeDomains)
+                .unwrap(),
+            vec!["example.com", "ads.net"]
+        );
+        assert!(repository
+            .get_list(SetupVarsEntry::ApiExcludeClients)
+            .unwrap()
+            .is_empty());
+    }
+
+    /// FTL config entries are read and written in the FTL config, which is
+    /// cached separately from SetupVars
+    #[test]
+    fn ftl_config() {
+        let env_builder = TestEnvBuilder::new()
+            .file(PiholeFile::SetupVars, "QUERY_LOGGING=true\n")
+            .file_expect(
+                PiholeFile::FtlConfig,
+                "PRIVACYLEVEL=1\n",
+                "PRIVACYLEVEL=2\n",
+            );
+        let mut test_files = env_builder.clone_test_files();
+        let repository = repository_with_env(env_builder.build());
+
+        assert!(repository.get_bool(SetupVarsEntry::QueryLogging).unwrap());
+        assert_eq!(repository.get_ftl(FtlConfEntry::PrivacyLevel).unwrap(), "1");
+        repository.set_ftl(FtlConfEntry::PrivacyLevel, "2").unwrap();
+        assert_eq!(repository.get_ftl(FtlConfEntry::PrivacyLevel).unwrap(), "2");
+        assert_eq!(
+            repository.get_ftl(FtlConfEntry::BlockingMode).unwrap(),
+            "NULL"
+        );
+
+        let mut buffer = String::new();
+        for test_file in &mut test_files {
+            test_file.assert_expected(&mut buffer);
+        }
+    }
+
+    /// Setting a value replaces every line of the key, keeps the other lines,
+    /// and rewrites Windows line endings
+    #[test]
+    fn set() {
+        test_update(
+            "# Pi-hole settings\r\n\
+             QUERY_LOGGING=true\r\n\
+             \r\n\
+             QUERY_LOGGING=true\r\n\
+             WEBUIBOXEDLAYOUT=boxed\r\n",
+            "# Pi-hole settings\n\
+             \n\
+             WEBUIBOXEDLAYOUT=boxed\n\
+             QUERY_LOGGING=false\n",
+            |repository| {
+                repository
+                    .set(SetupVarsEntry::QueryLogging, "false")
+                    .unwrap();
+            },
+        );
+    }
+
+    /// The new value is read after it is set
+    #[test]
+    fn set_then_get() {
+        let repository = repository("QUERY_LOGGING=true\n");
+
+        assert!(repository.get_bool(SetupVarsEntry::QueryLogging).unwrap());
+        repository
+            .set(SetupVarsEntry::QueryLogging, "false")
+            .unwrap();
+        assert!(!repository.get_bool(SetupVarsEntry::QueryLogging).unwrap());
+    }
+
+    /// Invalid values are rejected without changing SetupVars
+    #[test]
+    fn set_invalid() {
+        test_update(
+            "QUERY_LOGGING=true\n",
+            "QUERY_LOGGING=true\n",
+            |repository| {
+                assert_eq!(
+                    repository
+                        .set(SetupVarsEntry::QueryLogging, "yes")
+                        .unwrap_err()
+                        .kind(),
+                    ErrorKind::InvalidSettingValue
+                );
+            },
+        );
+    }
+
+    /// The upstreams are renumbered from one, and the old numbers are removed
+    #[test]
+    fn set_upstreams() {
+        test_update(
+            "PIHOLE_DNS_1=8.8.8.8\n\
+             DNSSEC=false\n\
+             PIHOLE_DNS_3=8.8.4.4\n",
+            "DNSSEC=false\n\
+             PIHOLE_DNS_1=1.1.1.1\n\
+             PIHOLE_DNS_2=127.0.0.1:5353\n",
+            |repository| {
+                repository
+                    .set_upstreams(&["1.1.1.1".to_owned(), "127.0.0.1:5353".to_owned()])
+                    .unwrap();
+            },
+        );
+    }
+}
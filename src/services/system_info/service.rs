@@ -0,0 +1,203 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// System Information Service
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use shaku::{Component, Interface};
+use std::{fs, path::PathBuf};
+
+/// The memory usage of the host, in kB
+#[derive(Serialize, Clone, Copy)]
+#[cfg_attr(test, derive(Debug, PartialEq))]
+pub struct MemoryUsage {
+    pub total: u64,
+    pub used: u64,
+}
+
+/// Describes reading metrics of the host. A metric which is not available,
+/// such as the CPU temperature inside a VM or container, is `None` instead of
+/// an error.
+#[cfg_attr(test, mockall::automock)]
+pub trait SystemInfoService: Interface {
+    /// Get the CPU temperature in degrees Celsius
+    fn cpu_temperature(&self) -> Option<f64>;
+
+    /// Get the load average over the last 1, 5, and 15 minutes
+    fn load_average(&self) -> Option<[f64; 3]>;
+
+    /// Get the memory usage
+    fn memory_usage(&self) -> Option<MemoryUsage>;
+}
+
+/// The implementation of `SystemInfoService`, which reads the metrics from
+/// `/sys` and `/proc`
+#[derive(Component)]
+#[shaku(interface = SystemInfoService)]
+pub struct SystemInfoServiceImpl {
+    /// The directory containing `sys` and `proc`
+    #[shaku(default = PathBuf::from("/"))]
+    root: PathBuf,
+}
+
+impl SystemInfoServiceImpl {
+    /// Read a file under the root directory. Missing and unreadable files are
+    /// `None`.
+    fn read(&self, path: &str) -> Option<String> {
+        fs::read_to_string(self.root.join(path)).ok()
+    }
+}
+
+impl SystemInfoService for SystemInfoServiceImpl {
+    fn cpu_temperature(&self) -> Option<f64> {
+        self.read("sys/class/thermal/thermal_zone0/temp")
+            .or_else(|| self.read("sys/class/hwmon/hwmon0/temp1_input"))
+            .and_then(|contents| parse_temperature(&contents))
+    }
+
+    fn load_average(&self) -> Option<[f64; 3]> {
+        self.read("proc/loadavg")
+            .and_then(|contents| parse_load_average(&contents))
+    }
+
+    fn memory_usage(&self) -> Option<MemoryUsage> {
+        self.read("proc/meminfo")
+            .and_then(|contents| parse_memory_usage(&contents))
+    }
+}
+
+/// Parse a temperature sensor reading. Most sensors report millidegrees
+/// Celsius, but some report whole degrees.
+fn parse_temperature(contents: &str) -> Option<f64> {
+    let value: f64 = contents.trim().parse().ok()?;
+
+    if value.abs() >= 1000.0 {
+        Some(value / 1000.0)
+    } else {
+        Some(value)
+    }
+}
+
+/// Parse the first three fields of `/proc/loadavg`
+fn parse_load_average(contents: &str) -> Option<[f64; 3]> {
+    let mut fields = contents.split_whitespace().map(str::parse::<f64>);
+    let mut load = [0.0; 3];
+
+    for value in load.iter_mut() {
+        *value = fields.next()?.ok()?;
+    }
+
+    Some(load)
+}
+
+/// Parse `/proc/meminfo`. Older kernels do not have `MemAvailable`, so the
+/// free, buffer, and cache memory is used instead.
+fn parse_memory_usage(contents: &str) -> Option<MemoryUsage> {
+    let field = |name: &str| -> Option<u64> {
+        contents
+            .lines()
+            .find(|line| line.split(':').next() == Some(name))?
+            .split_whitespace()
+            .nth(1)?
+            .parse()
+            .ok()
+    };
+
+    let total = field("MemTotal")?;
+    let available = match field("MemAvailable") {
+        Some(available) => available,
+        None => field("MemFree")? + field("Buffers")? + field("Cached")?,
+    };
+
+    Some(MemoryUsage {
+        total,
+        used: total.saturating_sub(available),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        parse_load_average, parse_memory_usage, parse_temperature, MemoryUsage, SystemInfoService,
+        SystemInfoServiceImpl,
+    };
+    use std::fs;
+    use tempfile::TempDir;
+
+    /// Millidegrees and whole degrees are both read as degrees
+    #[test]
+    fn temperature() {
+        assert_eq!(parse_temperature("47234\n"), Some(47.234));
+        assert_eq!(parse_temperature("52\n"), Some(52.0));
+        assert_eq!(parse_temperature(""), None);
+    }
+
+    /// The load average is the first three fields
+    #[test]
+    fn load_average() {
+        assert_eq!(
+            parse_load_average("0.15 0.30 0.45 1/180 2345\n"),
+            Some([0.15, 0.30, 0.45])
+        );
+        assert_eq!(parse_load_average("0.15 0.30"), None);
+    }
+
+    /// The used memory is the total minus the available memory
+    #[test]
+    fn memory_usage() {
+        assert_eq!(
+            parse_memory_usage(
+                "MemTotal:        1000000 kB\n\
+                 MemFree:          200000 kB\n\
+                 MemAvailable:     600000 kB\n\
+                 Buffers:           50000 kB\n\
+                 Cached:           300000 kB\n"
+            ),
+            Some(MemoryUsage {
+                total: 1_000_000,
+                used: 400_000
+            })
+        );
+    }
+
+    /// Without `MemAvailable`, the free, buffer, and cache memory is available
+    #[test]
+    fn memory_usage_without_available() {
+        assert_eq!(
+            parse_memory_usage(
+                "MemTotal:        1000000 kB\n\
+                 MemFree:          200000 kB\n\
+                 Buffers:           50000 kB\n\
+                 Cached:           300000 kB\n"
+            ),
+            Some(MemoryUsage {
+                total: 1_000_000,
+                used: 450_000
+            })
+        );
+    }
+
+    /// Missing files, such as a missing thermal sensor, are `None`
+    #[test]
+    fn missing_files() {
+        let root = TempDir::new().unwrap();
+        let service = SystemInfoServiceImpl {
+            root: root.path().to_owned(),
+        };
+
+        fs::create_dir(root.path().join("proc")).unwrap();
+        fs::write(
+            root.path().join("proc/loadavg"),
+            "1.00 0.50 0.25 2/200 999\n",
+        )
+        .unwrap();
+
+        assert_eq!(service.cpu_temperature(), None);
+        assert_eq!(service.load_average(), Some([1.0, 0.5, 0.25]));
+        assert_eq!(service.memory_usage(), None);
+    }
+}
@@ -0,0 +1,78 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// List Change Events
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::services::lists::List;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The kind of change which was made to a list
+#[cfg_attr(test, derive(Debug))]
+#[derive(Copy, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ListAction {
+    Add,
+    Remove,
+    Enable,
+    Disable,
+    Update,
+    Import,
+}
+
+/// A change to a list, which is sent to the webhook. Changes which affect
+/// many domains at once, such as imports, have no domain.
+#[cfg_attr(test, derive(Debug))]
+#[derive(Clone, PartialEq, Serialize)]
+pub struct ListEvent {
+    pub list: List,
+    pub action: ListAction,
+    pub domain: Option<String>,
+    pub timestamp: u64,
+}
+
+impl ListEvent {
+    /// Create an event for a change which was just made
+    pub fn new(list: List, action: ListAction, domain: Option<&str>) -> Self {
+        ListEvent {
+            list,
+            action,
+            domain: domain.map(ToOwned::to_owned),
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ListAction, ListEvent};
+    use crate::services::lists::List;
+
+    /// Events are sent as a flat JSON object
+    #[test]
+    fn serialize() {
+        let event = ListEvent {
+            list: List::RegexWhite,
+            action: ListAction::Disable,
+            domain: Some("^example\\.com$".to_owned()),
+            timestamp: 1_557_712_118,
+        };
+
+        assert_eq!(
+            serde_json::to_value(event).unwrap(),
+            json!({
+                "list": "regexwhite",
+                "action": "disable",
+                "domain": "^example\\.com$",
+                "timestamp": 1_557_712_118
+            })
+        );
+    }
+}
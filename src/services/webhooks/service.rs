@@ -0,0 +1,211 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Webhook Service
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    env::{Env, WebhookConfig},
+    services::webhooks::ListEvent,
+    util::{Error, ErrorKind},
+};
+use hmac::{Hmac, Mac, NewMac};
+use sha2::Sha256;
+use shaku::{Component, Interface};
+use std::{
+    sync::{
+        mpsc::{sync_channel, SyncSender, TrySendError},
+        Arc, Mutex, PoisonError,
+    },
+    thread,
+    time::Duration,
+};
+use ureq::{Agent, AgentBuilder};
+
+/// The header which holds the signature of the event
+pub const SIGNATURE_HEADER: &str = "X-Pihole-Signature";
+
+/// The number of events which can wait to be delivered. Events are dropped
+/// while the queue is full, so a slow webhook can not build up a backlog.
+const QUEUE_SIZE: usize = 64;
+
+/// How long to wait for the connection to the webhook
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long to wait for the webhook to respond
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// An event waiting to be delivered, along with the webhook it is sent to
+type Delivery = (WebhookConfig, ListEvent);
+
+/// Describes notifying another service of list changes
+#[cfg_attr(test, mockall::automock)]
+pub trait WebhookService: Interface {
+    /// Send the event to the configured webhook, if there is one. The event
+    /// is queued and delivered in the background, and failures are logged
+    /// instead of returned.
+    fn notify(&self, event: ListEvent);
+}
+
+/// The implementation of `WebhookService`
+#[derive(Component)]
+#[shaku(interface = WebhookService)]
+pub struct WebhookServiceImpl {
+    #[shaku(inject)]
+    env: Arc<Env>,
+    /// The queue of the delivery thread. The thread is started by the first
+    /// event, and stops when the service is dropped.
+    #[shaku(default)]
+    queue: Mutex<Option<SyncSender<Delivery>>>,
+}
+
+impl WebhookService for WebhookServiceImpl {
+    fn notify(&self, event: ListEvent) {
+        let config = match self.env.config().webhooks {
+            Some(ref config) => config.clone(),
+            None => return,
+        };
+
+        // Ignore the poison error because the sender is still usable
+        let mut queue = self.queue.lock().unwrap_or_else(PoisonError::into_inner);
+        let sender = queue.get_or_insert_with(start_delivery_thread);
+
+        let reason = match sender.try_send((config, event)) {
+            Ok(()) => return,
+            Err(TrySendError::Full((config, _))) => {
+                ErrorKind::WebhookDelivery(config.url, "the delivery queue is full".to_owned())
+            }
+            Err(TrySendError::Disconnected((config, _))) => {
+                // The delivery thread stopped, so start a new one for the
+                // next event
+                *queue = None;
+                ErrorKind::WebhookDelivery(config.url, "the delivery thread stopped".to_owned())
+            }
+        };
+
+        Error::from(reason).print_stacktrace();
+    }
+}
+
+/// Start the thread which delivers the queued events one at a time, so the
+/// API responses are not delayed by the webhook
+fn start_delivery_thread() -> SyncSender<Delivery> {
+    let (sender, receiver) = sync_channel::<Delivery>(QUEUE_SIZE);
+
+    thread::Builder::new()
+        .name("Webhook Delivery".to_owned())
+        .spawn(move || {
+            let agent = AgentBuilder::new()
+                .timeout_connect(CONNECT_TIMEOUT)
+                .timeout_read(READ_TIMEOUT)
+                .build();
+
+            for (config, event) in receiver.iter() {
+                if let Err(e) = deliver(&agent, &config, &event) {
+                    e.print_stacktrace();
+                }
+            }
+        })
+        .unwrap();
+
+    sender
+}
+
+/// POST the event to the webhook URL, along with its signature
+fn deliver(agent: &Agent, config: &WebhookConfig, event: &ListEvent) -> Result<(), Error> {
+    let delivery_error =
+        |reason: String| Error::from(ErrorKind::WebhookDelivery(config.url.clone(), reason));
+    let body = serde_json::to_string(event).map_err(|e| delivery_error(e.to_string()))?;
+
+    agent
+        .post(&config.url)
+        .set("Content-Type", "application/json")
+        .set(SIGNATURE_HEADER, &sign(&config.secret, &body))
+        .send_string(&body)
+        .map_err(|e| delivery_error(e.to_string()))?;
+
+    Ok(())
+}
+
+/// Sign the body with the secret using HMAC-SHA256. The signature is
+/// formatted as `sha256=<hex digest>`.
+pub fn sign(secret: &str, body: &str) -> String {
+    // HMAC accepts keys of any length, so this can not fail
+    let mut mac = Hmac::<Sha256>::new_varkey(secret.as_bytes()).unwrap();
+    mac.update(body.as_bytes());
+
+    let digest: String = mac
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+
+    format!("sha256={}", digest)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{deliver, sign, WebhookService, WebhookServiceImpl};
+    use crate::{
+        env::WebhookConfig,
+        services::{
+            lists::List,
+            webhooks::{ListAction, ListEvent},
+        },
+        testing::TestEnvBuilder,
+        util::ErrorKind,
+    };
+    use std::{net::TcpListener, sync::Arc, time::Duration};
+    use ureq::AgentBuilder;
+
+    /// Events are dropped without starting the delivery thread when there is
+    /// no webhook
+    #[test]
+    fn no_webhook() {
+        let service = WebhookServiceImpl {
+            env: Arc::new(TestEnvBuilder::new().build()),
+            queue: Default::default(),
+        };
+
+        service.notify(ListEvent::new(List::Black, ListAction::Add, None));
+
+        assert!(service.queue.lock().unwrap().is_none());
+    }
+
+    /// A webhook which does not respond times out instead of blocking the
+    /// delivery thread
+    #[test]
+    fn delivery_timeout() {
+        // The listener accepts the connection but never responds
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let config = WebhookConfig {
+            url: format!("http://{}/", listener.local_addr().unwrap()),
+            secret: "secret".to_owned(),
+        };
+        let agent = AgentBuilder::new()
+            .timeout_read(Duration::from_millis(100))
+            .build();
+        let event = ListEvent::new(List::Black, ListAction::Add, None);
+
+        let error = deliver(&agent, &config, &event).unwrap_err();
+
+        match error.kind() {
+            ErrorKind::WebhookDelivery(url, _) => assert_eq!(url, config.url),
+            kind => panic!("Unexpected error: {:?}", kind),
+        }
+    }
+
+    /// The signature is the hex encoded HMAC-SHA256 of the body
+    #[test]
+    fn signature() {
+        assert_eq!(
+            sign("key", "The quick brown fox jumps over the lazy dog"),
+            "sha256=f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8"
+        );
+    }
+}
@@ -10,15 +10,14 @@
 
 use crate::{
     env::{Env, PiholeFile},
-    settings::{get_ipv6_address_and_port, ConfigEntry, SetupVarsEntry},
+    services::settings::SettingsRepository,
+    settings::{SetupVarsEntry, Upstream},
     util::{Error, ErrorKind},
 };
 use failure::ResultExt;
-use std::{
-    fs::File,
-    io::{BufWriter, Write},
-};
 
+/// The comment at the top of the dnsmasq config. A config without it was not
+/// generated by Pi-hole, and is never overwritten.
 const DNSMASQ_HEADER: &str = "\
 ################################################################
 #       THIS FILE IS AUTOMATICALLY GENERATED BY PI-HOLE.       #
@@ -27,277 +26,583 @@ const DNSMASQ_HEADER: &str = "\
 #  NEW CONFIG SETTINGS MUST BE MADE IN A SEPARATE CONFIG FILE  #
 #                OR IN /etc/dnsmasq.conf                       #
 ################################################################
-
-localise-queries
-local-ttl=2
-cache-size=10000
 ";
 
-/// Generate a dnsmasq config based off of SetupVars.
-pub fn generate_dnsmasq_config(env: &Env) -> Result<(), Error> {
-    let mut config_file = open_config(env)?;
-
-    write_header(&mut config_file)?;
-    write_servers(&mut config_file, env)?;
-    write_lists(&mut config_file)?;
-    write_dns_options(&mut config_file, env)?;
-    write_dhcp(&mut config_file, env)?;
+/// The options which are always written after the header
+const DEFAULT_OPTIONS: [&str; 3] = ["localise-queries", "local-ttl=2", "cache-size=10000"];
+
+/// The options which turn on query logging
+const QUERY_LOGGING_OPTIONS: [&str; 3] = [
+    "log-queries",
+    "log-facility=/var/log/pihole.log",
+    "log-async",
+];
+
+/// The options which turn on DNSSEC, with the trust anchors of the root zone
+const DNSSEC_OPTIONS: [&str; 3] = [
+    "dnssec",
+    "trust-anchor=.,19036,8,2,49AAC11D7B6F6446702E54A1607371607A1A41855200FD2CE1CDDE32F24E8FB5",
+    "trust-anchor=.,20326,8,2,E06D44B80B8F1D39A95C0B0D7C65D08458E880409BBC683457104237C7F8EC8D",
+];
+
+/// The settings which the dnsmasq config is generated from
+#[derive(Clone)]
+#[cfg_attr(test, derive(Debug, PartialEq))]
+pub struct DnsmasqSettings {
+    /// The upstream DNS servers, in the SetupVars format
+    pub upstreams: Vec<String>,
+    pub query_logging: bool,
+    pub fqdn_required: bool,
+    pub bogus_priv: bool,
+    pub dnssec: bool,
+    pub host_record: String,
+    pub listening: DnsmasqListening,
+    pub conditional_forwarding: Option<ConditionalForwardingConfig>,
+    pub dhcp: Option<DhcpConfig>,
+}
 
-    Ok(())
+/// The interfaces dnsmasq answers queries on
+#[derive(Clone)]
+#[cfg_attr(test, derive(Debug, PartialEq))]
+pub enum DnsmasqListening {
+    /// Answer queries on all interfaces
+    All,
+    /// Only answer queries from the local networks
+    Local,
+    /// Only answer queries on the interface
+    Single(String),
 }
 
-/// Open the dnsmasq config and truncate it
-fn open_config(env: &Env) -> Result<BufWriter<File>, Error> {
-    env.write_file(PiholeFile::DnsmasqConfig, false)
-        .map(BufWriter::new)
+/// Forward queries for the local domain and reverse lookups in the network to
+/// the target, usually the router
+#[derive(Clone)]
+#[cfg_attr(test, derive(Debug, PartialEq))]
+pub struct ConditionalForwardingConfig {
+    /// The local domain. Only reverse lookups are forwarded if it is empty.
+    pub domain: String,
+    /// The network in CIDR notation
+    pub cidr: String,
+    pub target: String,
 }
 
-/// Write the header to the config file
-fn write_header(config_file: &mut BufWriter<File>) -> Result<(), Error> {
-    config_file
-        .write_all(DNSMASQ_HEADER.as_bytes())
-        .context(ErrorKind::DnsmasqConfigWrite)
-        .map_err(Error::from)
+/// The settings of the DHCP server
+#[derive(Clone)]
+#[cfg_attr(test, derive(Debug, PartialEq))]
+pub struct DhcpConfig {
+    pub start: String,
+    pub end: String,
+    pub router: String,
+    /// The lease time in hours, or zero for an infinite lease time
+    pub lease_time: usize,
+    pub domain: String,
+    pub rapid_commit: bool,
+    pub ipv6: bool,
+    /// The interface to advertise the IPv6 range on
+    pub interface: String,
 }
 
-/// Write the upstream DNS servers
-fn write_servers(config_file: &mut BufWriter<File>, env: &Env) -> Result<(), Error> {
-    for i in 1.. {
-        let dns = SetupVarsEntry::PiholeDns(i).read(env)?;
+impl DnsmasqSettings {
+    /// Read the settings from SetupVars
+    pub fn from_setup_vars(settings: &dyn SettingsRepository) -> Result<Self, Error> {
+        let upstreams = settings.upstreams()?;
+
+        #[allow(clippy::wildcard_in_or_patterns)]
+        let listening = match settings.get(SetupVarsEntry::DnsmasqListening)?.as_str() {
+            "all" => DnsmasqListening::All,
+            "local" => DnsmasqListening::Local,
+            "single" | _ => {
+                DnsmasqListening::Single(settings.get(SetupVarsEntry::PiholeInterface)?)
+            }
+        };
 
-        // When the setting is empty, we are finished adding servers
-        if dns.is_empty() {
-            break;
-        }
+        // The REV_SERVER settings replace the older CONDITIONAL_FORWARDING
+        // settings
+        let conditional_forwarding = if settings.get_bool(SetupVarsEntry::RevServer)? {
+            Some(ConditionalForwardingConfig {
+                domain: settings.get(SetupVarsEntry::RevServerDomain)?,
+                cidr: settings.get(SetupVarsEntry::RevServerCidr)?,
+                target: settings.get(SetupVarsEntry::RevServerTarget)?,
+            })
+        } else if settings.get_bool(SetupVarsEntry::ConditionalForwarding)? {
+            let ip = settings.get(SetupVarsEntry::ConditionalForwardingIp)?;
+
+            Some(ConditionalForwardingConfig {
+                domain: settings.get(SetupVarsEntry::ConditionalForwardingDomain)?,
+                cidr: format!(
+                    "{}/{}",
+                    ip,
+                    settings.get(SetupVarsEntry::ConditionalForwardingCIDR)?
+                ),
+                target: ip,
+            })
+        } else {
+            None
+        };
 
-        // Transform addresses with ports into the format dnsmasq understands
-        // Example: 127.0.0.1:5353               -> 127.0.0.1#5353
-        //          [1fff:0:a88:85a3::ac1f]:8001 -> 1fff:0:a88:85a3::ac1f#8001
-        let dnsmasq_address = match get_ipv6_address_and_port(&dns) {
-            Some((address, None)) => {
-                // This is an IPv6 address without a port
-                address.to_owned()
-            }
-            Some((address, Some(port))) => {
-                // This is an IPv6 address with a port
-                format!("{}#{}", address, port)
-            }
-            None => {
-                // This is an IPv4 address
-                dns.replace(":", "#")
-            }
+        let dhcp = if settings.get_bool(SetupVarsEntry::DhcpActive)? {
+            Some(DhcpConfig {
+                start: settings.get(SetupVarsEntry::DhcpStart)?,
+                end: settings.get(SetupVarsEntry::DhcpEnd)?,
+                router: settings.get(SetupVarsEntry::DhcpRouter)?,
+                lease_time: settings.get_usize(SetupVarsEntry::DhcpLeasetime)?,
+                domain: settings.get(SetupVarsEntry::PiholeDomain)?,
+                rapid_commit: settings.get_bool(SetupVarsEntry::DhcpRapidCommit)?,
+                ipv6: settings.get_bool(SetupVarsEntry::DhcpIpv6)?,
+                interface: settings.get(SetupVarsEntry::PiholeInterface)?,
+            })
+        } else {
+            None
         };
 
-        writeln!(config_file, "server={}", dnsmasq_address)
-            .context(ErrorKind::DnsmasqConfigWrite)?;
+        Ok(DnsmasqSettings {
+            upstreams,
+            query_logging: settings.get_bool(SetupVarsEntry::QueryLogging)?,
+            fqdn_required: settings.get_bool(SetupVarsEntry::DnsFqdnRequired)?,
+            bogus_priv: settings.get_bool(SetupVarsEntry::DnsBogusPriv)?,
+            dnssec: settings.get_bool(SetupVarsEntry::Dnssec)?,
+            host_record: settings.get(SetupVarsEntry::HostRecord)?,
+            listening,
+            conditional_forwarding,
+            dhcp,
+        })
     }
 
-    Ok(())
-}
+    /// Render the complete dnsmasq config. The same settings always render
+    /// the same config.
+    pub fn render(&self) -> String {
+        let mut lines = Vec::new();
 
-/// Write the blocklist, blacklist, and local list
-fn write_lists(config_file: &mut BufWriter<File>) -> Result<(), Error> {
-    // Always write the blocklist and blacklist, even if Pi-hole is disabled.
-    // When Pi-hole is disabled, the files will be empty. This is to make
-    // enabling/disabling very fast.
-    config_file
-        .write_all(b"addn-hosts=/etc/pihole/gravity.list\n")
-        .context(ErrorKind::DnsmasqConfigWrite)?;
-    config_file
-        .write_all(b"addn-hosts=/etc/pihole/black.list\n")
-        .context(ErrorKind::DnsmasqConfigWrite)?;
+        write_default_options(&mut lines);
+        self.write_servers(&mut lines);
+        write_lists(&mut lines);
+        self.write_dns_options(&mut lines);
+        self.write_dhcp(&mut lines);
 
-    // Always add local.list after the blocklists
-    config_file
-        .write_all(b"addn-hosts=/etc/pihole/local.list\n")
-        .context(ErrorKind::DnsmasqConfigWrite)?;
+        let mut config = String::from(DNSMASQ_HEADER);
+        config.push('\n');
 
-    Ok(())
-}
+        for line in lines {
+            config.push_str(&line);
+            config.push('\n');
+        }
 
-/// Write various DNS settings
-fn write_dns_options(config_file: &mut BufWriter<File>, env: &Env) -> Result<(), Error> {
-    if SetupVarsEntry::QueryLogging.is_true(env)? {
-        config_file
-            .write_all(
-                b"log-queries\n\
-            log-facility=/var/log/pihole.log\n\
-            log-async\n",
-            )
-            .context(ErrorKind::DnsmasqConfigWrite)?;
+        config
     }
 
-    if SetupVarsEntry::DnsFqdnRequired.is_true(env)? {
-        config_file
-            .write_all(b"domain-needed\n")
-            .context(ErrorKind::DnsmasqConfigWrite)?;
+    /// Write the upstream DNS servers
+    fn write_servers(&self, lines: &mut Vec<String>) {
+        for dns in &self.upstreams {
+            // Transform addresses with ports into the format dnsmasq understands
+            // Example: 127.0.0.1:5353               -> 127.0.0.1#5353
+            //          [1fff:0:a88:85a3::ac1f]:8001 -> 1fff:0:a88:85a3::ac1f#8001
+            // Invalid upstreams are skipped, since dnsmasq would not start
+            let dnsmasq_address = match dns.parse::<Upstream>() {
+                Ok(upstream) => upstream.to_dnsmasq(),
+                Err(_) => continue,
+            };
+
+            lines.push(format!("server={}", dnsmasq_address));
+        }
     }
 
-    if SetupVarsEntry::DnsBogusPriv.is_true(env)? {
-        config_file
-            .write_all(b"bogus-priv\n")
-            .context(ErrorKind::DnsmasqConfigWrite)?;
-    }
+    /// Write various DNS settings
+    fn write_dns_options(&self, lines: &mut Vec<String>) {
+        if self.query_logging {
+            push_options(lines, &QUERY_LOGGING_OPTIONS);
+        }
 
-    if SetupVarsEntry::Dnssec.is_true(env)? {
-        config_file.write_all(
-            b"dnssec\n\
-            trust-anchor=.,19036,8,2,49AAC11D7B6F6446702E54A1607371607A1A41855200FD2CE1CDDE32F24E8FB5\n\
-            trust-anchor=.,20326,8,2,E06D44B80B8F1D39A95C0B0D7C65D08458E880409BBC683457104237C7F8EC8D\n"
-        ).context(ErrorKind::DnsmasqConfigWrite)?;
-    }
+        if self.fqdn_required {
+            lines.push("domain-needed".to_owned());
+        }
+
+        if self.bogus_priv {
+            lines.push("bogus-priv".to_owned());
+        }
+
+        if self.dnssec {
+            push_options(lines, &DNSSEC_OPTIONS);
+        }
+
+        if !self.host_record.is_empty() {
+            lines.push(format!("host-record={}", self.host_record));
+        }
+
+        lines.push(match &self.listening {
+            DnsmasqListening::All => "except-interface=nonexisting".to_owned(),
+            DnsmasqListening::Local => "local-service".to_owned(),
+            DnsmasqListening::Single(interface) => format!("interface={}", interface),
+        });
+
+        if let Some(forwarding) = &self.conditional_forwarding {
+            // Add an entry to use the target for the local domain, and add an
+            // entry to forward reverse lookups to the target
+            if !forwarding.domain.is_empty() {
+                lines.push(format!(
+                    "server=/{}/{}",
+                    forwarding.domain, forwarding.target
+                ));
+            }
 
-    let host_record = SetupVarsEntry::HostRecord.read(env)?;
-    if !host_record.is_empty() {
-        writeln!(config_file, "host-record={}", host_record)
-            .context(ErrorKind::DnsmasqConfigWrite)?;
+            lines.push(format!(
+                "rev-server={},{}",
+                forwarding.cidr, forwarding.target
+            ));
+        }
     }
 
-    #[allow(clippy::wildcard_in_or_patterns)]
-    match SetupVarsEntry::DnsmasqListening.read(env)?.as_str() {
-        "all" => config_file
-            .write_all(b"except-interface=nonexisting\n")
-            .context(ErrorKind::DnsmasqConfigWrite)?,
-        "local" => config_file
-            .write_all(b"local-service\n")
-            .context(ErrorKind::DnsmasqConfigWrite)?,
-        "single" | _ => {
-            writeln!(
-                config_file,
-                "interface={}",
-                SetupVarsEntry::PiholeInterface.read(env)?
-            )
-            .context(ErrorKind::DnsmasqConfigWrite)?;
+    /// Write DHCP settings, if enabled
+    fn write_dhcp(&self, lines: &mut Vec<String>) {
+        // Skip DHCP settings if it is not enabled
+        let dhcp = match &self.dhcp {
+            Some(dhcp) => dhcp,
+            None => return,
+        };
+
+        let lease_time = if dhcp.lease_time == 0 {
+            "infinite".to_owned()
+        } else {
+            format!("{}h", dhcp.lease_time)
+        };
+
+        // Main DHCP settings. The "wpad" lines fix CERT vulnerability VU#598349 by
+        // preventing clients from using "wpad" as their hostname. We also prevent
+        // clients from using "localhost" as their hostname, as this confuses
+        // Windows
+        lines.push("dhcp-authoritative".to_owned());
+        lines.push("dhcp-leasefile=/etc/pihole/dhcp.leases".to_owned());
+        lines.push(format!(
+            "dhcp-range={},{},{}",
+            dhcp.start, dhcp.end, lease_time
+        ));
+        lines.push(format!("dhcp-option=option:router,{}", dhcp.router));
+        lines.push("dhcp-name-match=set:hostname-ignore,wpad".to_owned());
+        lines.push("dhcp-name-match=set:hostname-ignore,localhost".to_owned());
+        lines.push("dhcp-ignore-names=tag:hostname-ignore".to_owned());
+        lines.push(format!("domain={}", dhcp.domain));
+
+        if dhcp.rapid_commit {
+            lines.push("dhcp-rapid-commit".to_owned());
+        }
+
+        // Additional settings for IPv6
+        if dhcp.ipv6 {
+            lines.push("dhcp-option=option6:dns-server,[::]".to_owned());
+            lines.push(format!(
+                "dhcp-range=::100,::1ff,constructor:{},ra-names,slaac,{}",
+                dhcp.interface, lease_time
+            ));
+            lines.push("ra-param=*,0,0".to_owned());
         }
     }
+}
 
-    if SetupVarsEntry::ConditionalForwarding.is_true(env)? {
-        let ip = SetupVarsEntry::ConditionalForwardingIp.read(env)?;
-
-        // Add an entry to use the custom upstream for the custom domain,
-        // and add an entry to forward reverse lookups to the custom upstream
-        writeln!(
-            config_file,
-            "server=/{}/{}\n\
-             rev-server={}/{},{}",
-            SetupVarsEntry::ConditionalForwardingDomain.read(env)?,
-            ip,
-            ip,
-            SetupVarsEntry::ConditionalForwardingCIDR.read(env)?,
-            ip
-        )
-        .context(ErrorKind::DnsmasqConfigWrite)?;
+/// Generate the dnsmasq config from the settings in SetupVars
+pub fn generate_dnsmasq_config(settings: &dyn SettingsRepository, env: &Env) -> Result<(), Error> {
+    write_dnsmasq_config(&DnsmasqSettings::from_setup_vars(settings)?, env)
+}
+
+/// Render the dnsmasq config and replace the existing config. If the existing
+/// config does not have the Pi-hole header, it is not overwritten, because it
+/// is managed by hand.
+pub fn write_dnsmasq_config(settings: &DnsmasqSettings, env: &Env) -> Result<(), Error> {
+    if env.file_exists(PiholeFile::DnsmasqConfig)
+        && !is_generated_config(&env.read_file_lines(PiholeFile::DnsmasqConfig)?)
+    {
+        return Err(Error::from(ErrorKind::DnsmasqConfigUnmanaged));
     }
 
+    env.write_file_atomic(PiholeFile::DnsmasqConfig, settings.render().as_bytes())
+        .context(ErrorKind::DnsmasqConfigWrite)?;
+
     Ok(())
 }
 
-/// Write DHCP settings, if enabled
-fn write_dhcp(config_file: &mut BufWriter<File>, env: &Env) -> Result<(), Error> {
-    if !SetupVarsEntry::DhcpActive.is_true(env)? {
-        // Skip DHCP settings if it is not enabled
-        return Ok(());
+/// Check if the config was generated by Pi-hole. An empty config has nothing
+/// to lose, so it counts as generated.
+fn is_generated_config(lines: &[String]) -> bool {
+    if lines.iter().all(|line| line.trim().is_empty()) {
+        return true;
     }
 
-    let lease_time: usize = SetupVarsEntry::DhcpLeasetime.read_as(env)?;
-    let lease_time = if lease_time == 0 {
-        "infinite".to_owned()
-    } else {
-        format!("{}h", lease_time)
-    };
+    let header: Vec<&str> = DNSMASQ_HEADER.lines().collect();
 
-    // Main DHCP settings. The "wpad" lines fix CERT vulnerability VU#598349 by
-    // preventing clients from using "wpad" as their hostname. We also prevent
-    // clients from using "localhost" as their hostname, as this confuses
-    // Windows
-    writeln!(
-        config_file,
-        "dhcp-authoritative\n\
-         dhcp-leasefile=/etc/pihole/dhcp.leases\n\
-         dhcp-range={},{},{}\n\
-         dhcp-option=option:router,{}\n\
-         dhcp-name-match=set:hostname-ignore,wpad\n\
-         dhcp-name-match=set:hostname-ignore,localhost\n\
-         dhcp-ignore-names=tag:hostname-ignore\n\
-         domain={}",
-        SetupVarsEntry::DhcpStart.read(env)?,
-        SetupVarsEntry::DhcpEnd.read(env)?,
-        lease_time,
-        SetupVarsEntry::DhcpRouter.read(env)?,
-        SetupVarsEntry::PiholeDomain.read(env)?
-    )
-    .context(ErrorKind::DnsmasqConfigWrite)?;
-
-    if SetupVarsEntry::DhcpRapidCommit.is_true(env)? {
-        writeln!(config_file, "dhcp-rapid-commit").context(ErrorKind::DnsmasqConfigWrite)?;
-    }
+    lines.len() >= header.len()
+        && lines
+            .iter()
+            .zip(header)
+            .all(|(line, header_line)| line == header_line)
+}
 
-    // Additional settings for IPv6
-    if SetupVarsEntry::DhcpIpv6.is_true(env)? {
-        writeln!(
-            config_file,
-            "dhcp-option=option6:dns-server,[::]\n\
-             dhcp-range=::100,::1ff,constructor:{},ra-names,slaac,{}\n\
-             ra-param=*,0,0",
-            SetupVarsEntry::PiholeInterface.read(env)?,
-            lease_time
-        )
-        .context(ErrorKind::DnsmasqConfigWrite)?;
-    }
+/// Write the options which are always used
+fn write_default_options(lines: &mut Vec<String>) {
+    push_options(lines, &DEFAULT_OPTIONS);
+}
 
-    Ok(())
+/// Write the blocklist, blacklist, and local list
+fn write_lists(lines: &mut Vec<String>) {
+    // Always write the blocklist and blacklist, even if Pi-hole is disabled.
+    // When Pi-hole is disabled, the files will be empty. This is to make
+    // enabling/disabling very fast.
+    lines.push("addn-hosts=/etc/pihole/gravity.list".to_owned());
+    lines.push("addn-hosts=/etc/pihole/black.list".to_owned());
+
+    // Always add local.list after the blocklists
+    lines.push("addn-hosts=/etc/pihole/local.list".to_owned());
+}
+
+/// Add each of the options as a line
+fn push_options(lines: &mut Vec<String>, options: &[&str]) {
+    lines.extend(options.iter().map(|&option| option.to_owned()));
 }
 
 #[cfg(test)]
 mod tests {
     use super::{
-        open_config, write_dhcp, write_dns_options, write_header, write_lists, write_servers,
-        DNSMASQ_HEADER,
+        generate_dnsmasq_config, write_lists, ConditionalForwardingConfig, DhcpConfig,
+        DnsmasqListening, DnsmasqSettings,
     };
     use crate::{
-        env::{Env, PiholeFile},
-        testing::TestEnvBuilder,
-        util::Error,
-    };
-    use std::{
-        fs::File,
-        io::{BufWriter, Write},
+        env::PiholeFile, services::settings::SettingsRepositoryImpl, testing::TestEnvBuilder,
+        util::ErrorKind,
     };
+    use std::sync::Arc;
+
+    /// The header and the options which are always written
+    const HEADER: &str = "\
+################################################################
+#       THIS FILE IS AUTOMATICALLY GENERATED BY PI-HOLE.       #
+#          ANY CHANGES MADE TO THIS FILE WILL BE LOST.         #
+#                                                              #
+#  NEW CONFIG SETTINGS MUST BE MADE IN A SEPARATE CONFIG FILE  #
+#                OR IN /etc/dnsmasq.conf                       #
+################################################################
 
-    /// Generalized test for dnsmasq config generation. This sets up SetupVars
-    /// with the initial data, runs `test_fn`, then verifies that the
-    /// dnsmasq config content matches the expected content.
+localise-queries
+local-ttl=2
+cache-size=10000
+";
+
+    /// Generalized test for a section of the dnsmasq config. This reads the
+    /// settings from SetupVars, renders the section with `test_fn`, then
+    /// verifies that the rendered lines match the expected content.
     ///
     /// # Arguments
-    /// - `expected_config`: The expected contents of the dnsmasq config after
-    /// running `test_fn`. The dnsmasq config starts out empty.
-    /// - `setup_vars`: The initial contents of SetupVars
-    /// - `test_fn`: The function to run for the test. It takes in the buffered
-    /// file writer and the environment data.
+    /// - `expected_config`: The expected lines of the section
+    /// - `setup_vars`: The contents of SetupVars
+    /// - `test_fn`: The function which renders the section. It takes in the
+    /// settings and the lines to add to.
     fn test_config(
         expected_config: &str,
         setup_vars: &str,
-        test_fn: impl Fn(&mut BufWriter<File>, &Env) -> Result<(), Error>,
+        test_fn: impl Fn(&DnsmasqSettings, &mut Vec<String>),
     ) {
-        let env_builder = TestEnvBuilder::new()
-            .file_expect(PiholeFile::DnsmasqConfig, "", expected_config)
-            .file(PiholeFile::SetupVars, setup_vars);
+        let repository = TestEnvBuilder::new()
+            .file(PiholeFile::SetupVars, setup_vars)
+            .build_settings();
+        let settings = DnsmasqSettings::from_setup_vars(&repository).unwrap();
+        let mut lines = Vec::new();
 
-        let mut dnsmasq_config = env_builder.clone_test_files().into_iter().next().unwrap();
-        let env = env_builder.build();
-        let mut file_writer = open_config(&env).unwrap();
+        test_fn(&settings, &mut lines);
 
-        test_fn(&mut file_writer, &env).unwrap();
-        file_writer.flush().unwrap();
+        let rendered: String = lines.into_iter().map(|line| line + "\n").collect();
+        assert_eq!(rendered, expected_config);
+    }
 
-        let mut buffer = String::new();
-        dnsmasq_config.assert_expected(&mut buffer);
+    /// Settings with all of the options turned on
+    fn maximal_settings() -> DnsmasqSettings {
+        DnsmasqSettings {
+            upstreams: vec!["8.8.8.8".to_owned(), "[2001:4860:4860::8888]:53".to_owned()],
+            query_logging: true,
+            fqdn_required: true,
+            bogus_priv: true,
+            dnssec: true,
+            host_record: "pi.hole,192.168.1.10".to_owned(),
+            listening: DnsmasqListening::All,
+            conditional_forwarding: Some(ConditionalForwardingConfig {
+                domain: "lan".to_owned(),
+                cidr: "192.168.1.0/24".to_owned(),
+                target: "192.168.1.1".to_owned(),
+            }),
+            dhcp: Some(DhcpConfig {
+                start: "192.168.1.50".to_owned(),
+                end: "192.168.1.150".to_owned(),
+                router: "192.168.1.1".to_owned(),
+                lease_time: 24,
+                domain: "lan".to_owned(),
+                rapid_commit: true,
+                ipv6: true,
+                interface: "eth0".to_owned(),
+            }),
+        }
     }
 
-    /// Confirm that the header is written
+    /// Confirm that the header is written, even without any settings
     #[test]
     fn header_written() {
-        test_config(DNSMASQ_HEADER, "", |writer, _env| write_header(writer));
+        let settings = DnsmasqSettings {
+            upstreams: Vec::new(),
+            query_logging: false,
+            fqdn_required: false,
+            bogus_priv: false,
+            dnssec: false,
+            host_record: String::new(),
+            listening: DnsmasqListening::Local,
+            conditional_forwarding: None,
+            dhcp: None,
+        };
+
+        assert_eq!(
+            settings.render(),
+            format!(
+                "{}\
+                 addn-hosts=/etc/pihole/gravity.list\n\
+                 addn-hosts=/etc/pihole/black.list\n\
+                 addn-hosts=/etc/pihole/local.list\n\
+                 local-service\n",
+                HEADER
+            )
+        );
+    }
+
+    /// The complete config is rendered with every option turned on
+    #[test]
+    fn maximal_config() {
+        assert_eq!(
+            maximal_settings().render(),
+            format!(
+                "{}\
+                 server=8.8.8.8\n\
+                 server=2001:4860:4860::8888#53\n\
+                 addn-hosts=/etc/pihole/gravity.list\n\
+                 addn-hosts=/etc/pihole/black.list\n\
+                 addn-hosts=/etc/pihole/local.list\n\
+                 log-queries\n\
+                 log-facility=/var/log/pihole.log\n\
+                 log-async\n\
+                 domain-needed\n\
+                 bogus-priv\n\
+                 dnssec\n\
+                 trust-anchor=.,19036,8,2,49AAC11D7B6F6446702E54A1607371607A1A41855200FD2CE1CDDE32F24E8FB5\n\
+                 trust-anchor=.,20326,8,2,E06D44B80B8F1D39A95C0B0D7C65D08458E880409BBC683457104237C7F8EC8D\n\
+                 host-record=pi.hole,192.168.1.10\n\
+                 except-interface=nonexisting\n\
+                 server=/lan/192.168.1.1\n\
+                 rev-server=192.168.1.0/24,192.168.1.1\n\
+                 dhcp-authoritative\n\
+                 dhcp-leasefile=/etc/pihole/dhcp.leases\n\
+                 dhcp-range=192.168.1.50,192.168.1.150,24h\n\
+                 dhcp-option=option:router,192.168.1.1\n\
+                 dhcp-name-match=set:hostname-ignore,wpad\n\
+                 dhcp-name-match=set:hostname-ignore,localhost\n\
+                 dhcp-ignore-names=tag:hostname-ignore\n\
+                 domain=lan\n\
+                 dhcp-rapid-commit\n\
+                 dhcp-option=option6:dns-server,[::]\n\
+                 dhcp-range=::100,::1ff,constructor:eth0,ra-names,slaac,24h\n\
+                 ra-param=*,0,0\n",
+                HEADER
+            )
+        );
+    }
+
+    /// The settings are read from SetupVars
+    #[test]
+    fn settings_from_setup_vars() {
+        let repository = TestEnvBuilder::new()
+            .file(
+                PiholeFile::SetupVars,
+                "PIHOLE_DNS_1=8.8.8.8\n\
+                 PIHOLE_DNS_2=[2001:4860:4860::8888]:53\n\
+                 PIHOLE_INTERFACE=eth0\n\
+                 QUERY_LOGGING=true\n\
+                 DNS_FQDN_REQUIRED=true\n\
+                 DNS_BOGUS_PRIV=true\n\
+                 DNSSEC=true\n\
+                 HOSTRECORD=pi.hole,192.168.1.10\n\
+                 DNSMASQ_LISTENING=all\n\
+                 REV_SERVER=true\n\
+                 REV_SERVER_CIDR=192.168.1.0/24\n\
+                 REV_SERVER_TARGET=192.168.1.1\n\
+                 REV_SERVER_DOMAIN=lan\n\
+                 DHCP_ACTIVE=true\n\
+                 DHCP_START=192.168.1.50\n\
+                 DHCP_END=192.168.1.150\n\
+                 DHCP_ROUTER=192.168.1.1\n\
+                 DHCP_LEASETIME=24\n\
+                 PIHOLE_DOMAIN=lan\n\
+                 DHCP_rapid_commit=true\n\
+                 DHCP_IPv6=true\n",
+            )
+            .build_settings();
+
+        assert_eq!(
+            DnsmasqSettings::from_setup_vars(&repository).unwrap(),
+            maximal_settings()
+        );
+    }
+
+    /// The config is generated from SetupVars, replacing the previous config
+    #[test]
+    fn generate_replaces_config() {
+        let env_builder = TestEnvBuilder::new()
+            .file(
+                PiholeFile::SetupVars,
+                "PIHOLE_DNS_1=8.8.8.8\n\
+                 DNSMASQ_LISTENING=local\n",
+            )
+            .file_expect(
+                PiholeFile::DnsmasqConfig,
+                &format!("{}server=1.1.1.1\n", HEADER),
+                &format!(
+                    "{}\
+                     server=8.8.8.8\n\
+                     addn-hosts=/etc/pihole/gravity.list\n\
+                     addn-hosts=/etc/pihole/black.list\n\
+                     addn-hosts=/etc/pihole/local.list\n\
+                     domain-needed\n\
+                     bogus-priv\n\
+                     local-service\n",
+                    HEADER
+                ),
+            );
+        let mut test_files = env_builder.clone_test_files();
+        let env = Arc::new(env_builder.build());
+        let repository = SettingsRepositoryImpl::new(Arc::clone(&env));
+
+        generate_dnsmasq_config(&repository, &env).unwrap();
+
+        let mut buffer = String::new();
+        for test_file in &mut test_files {
+            test_file.assert_expected(&mut buffer);
+        }
+    }
+
+    /// A config without the Pi-hole header is managed by hand, so it is not
+    /// overwritten
+    #[test]
+    fn unmanaged_config_kept() {
+        let env_builder = TestEnvBuilder::new()
+            .file(PiholeFile::SetupVars, "PIHOLE_DNS_1=8.8.8.8\n")
+            .file_expect(
+                PiholeFile::DnsmasqConfig,
+                "# My own config\nserver=1.1.1.1\n",
+                "# My own config\nserver=1.1.1.1\n",
+            );
+        let mut test_files = env_builder.clone_test_files();
+        let env = Arc::new(env_builder.build());
+        let repository = SettingsRepositoryImpl::new(Arc::clone(&env));
+
+        assert_eq!(
+            generate_dnsmasq_config(&repository, &env)
+                .unwrap_err()
+                .kind(),
+            ErrorKind::DnsmasqConfigUnmanaged
+        );
+
+        let mut buffer = String::new();
+        for test_file in &mut test_files {
+            test_file.assert_expected(&mut buffer);
+        }
     }
 
     /// Confirm all (sequential) DNS servers listed are written
@@ -307,7 +612,7 @@ mod tests {
             "server=8.8.8.8\nserver=8.8.4.4\n",
             "PIHOLE_DNS_1=8.8.8.8\n\
              PIHOLE_DNS_2=8.8.4.4",
-            write_servers,
+            DnsmasqSettings::write_servers,
         );
     }
 
@@ -317,7 +622,7 @@ mod tests {
         test_config(
             "server=127.0.0.1#5353\n",
             "PIHOLE_DNS_1=127.0.0.1:5353",
-            write_servers,
+            DnsmasqSettings::write_servers,
         );
     }
 
@@ -327,7 +632,7 @@ mod tests {
         test_config(
             "server=f7c4:12f8:4f5a:8454:5241:cf80:d61c:3e2c\n",
             "PIHOLE_DNS_1=f7c4:12f8:4f5a:8454:5241:cf80:d61c:3e2c",
-            write_servers,
+            DnsmasqSettings::write_servers,
         )
     }
 
@@ -337,7 +642,7 @@ mod tests {
         test_config(
             "server=1fff:0:a88:85a3::ac1f#8001\n",
             "PIHOLE_DNS_1=[1fff:0:a88:85a3::ac1f]:8001",
-            write_servers,
+            DnsmasqSettings::write_servers,
         )
     }
 
@@ -350,7 +655,7 @@ mod tests {
             "PIHOLE_DNS_1=8.8.8.8\n\
              PIHOLE_DNS_2=8.8.4.4\n\
              PIHOLE_DNS_4=1.1.1.1",
-            write_servers,
+            DnsmasqSettings::write_servers,
         );
     }
 
@@ -362,7 +667,7 @@ mod tests {
              addn-hosts=/etc/pihole/black.list\n\
              addn-hosts=/etc/pihole/local.list\n",
             "",
-            |config, _| write_lists(config),
+            |_, lines| write_lists(lines),
         );
     }
 
@@ -379,7 +684,7 @@ mod tests {
              DNSMASQ_LISTENING=single\n\
              PIHOLE_INTERFACE=eth0\n\
              CONDITIONAL_FORWARDING=false",
-            write_dns_options,
+            DnsmasqSettings::write_dns_options,
         );
     }
 
@@ -405,7 +710,29 @@ mod tests {
             CONDITIONAL_FORWARDING_IP=8.8.8.8\n\
             CONDITIONAL_FORWARDING_DOMAIN=domain.com\n\
             CONDITIONAL_FORWARDING_CIDR=24",
-            write_dns_options
+            DnsmasqSettings::write_dns_options,
+        );
+    }
+
+    /// The REV_SERVER settings are used for conditional forwarding instead of
+    /// the older CONDITIONAL_FORWARDING settings
+    #[test]
+    fn rev_server() {
+        test_config(
+            "local-service\n\
+            server=/lan/192.168.1.1\n\
+            rev-server=192.168.1.0/24,192.168.1.1\n",
+            "DNS_FQDN_REQUIRED=false\n\
+            DNS_BOGUS_PRIV=false\n\
+            REV_SERVER=true\n\
+            REV_SERVER_CIDR=192.168.1.0/24\n\
+            REV_SERVER_TARGET=192.168.1.1\n\
+            REV_SERVER_DOMAIN=lan\n\
+            CONDITIONAL_FORWARDING=true\n\
+            CONDITIONAL_FORWARDING_IP=8.8.8.8\n\
+            CONDITIONAL_FORWARDING_DOMAIN=domain.com\n\
+            CONDITIONAL_FORWARDING_CIDR=24",
+            DnsmasqSettings::write_dns_options,
         );
     }
 
@@ -422,7 +749,7 @@ mod tests {
              DHCP_LEASETIME=24\n\
              PIHOLE_DOMAIN=lan\n\
              DHCP_IPv6=false",
-            write_dhcp,
+            DnsmasqSettings::write_dhcp,
         )
     }
 
@@ -449,7 +776,7 @@ mod tests {
              PIHOLE_DOMAIN=lan\n\
              DHCP_rapid_commit=true\n\
              DHCP_IPv6=false",
-            write_dhcp,
+            DnsmasqSettings::write_dhcp,
         )
     }
 
@@ -477,7 +804,7 @@ mod tests {
              DHCP_LEASETIME=24\n\
              PIHOLE_DOMAIN=lan\n\
              DHCP_IPv6=true",
-            write_dhcp,
+            DnsmasqSettings::write_dhcp,
         )
     }
 
@@ -506,7 +833,7 @@ mod tests {
              DHCP_LEASETIME=0\n\
              PIHOLE_DOMAIN=lan\n\
              DHCP_IPv6=true",
-            write_dhcp,
+            DnsmasqSettings::write_dhcp,
         )
     }
 }
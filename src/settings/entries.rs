@@ -13,12 +13,8 @@ use crate::{
     settings::value_type::ValueType,
     util::{Error, ErrorKind},
 };
-use failure::{Fail, ResultExt};
-use std::{
-    borrow::Cow,
-    io::{self, prelude::*, BufWriter},
-    str::FromStr,
-};
+use failure::Fail;
+use std::{borrow::Cow, io};
 
 /// Common functions for a configuration entry
 pub trait ConfigEntry {
@@ -40,141 +36,106 @@ pub trait ConfigEntry {
         value.is_empty() || self.value_type().is_valid(value)
     }
 
-    /// Try to read the value and parse into a boolean.
-    fn is_true(&self, env: &Env) -> Result<bool, Error> {
-        self.read_as::<bool>(env)
-    }
-
-    /// Try to read the value and parse into `T`.
-    /// If it is unable to be parsed into `T`, an error is returned.
-    fn read_as<T: FromStr>(&self, env: &Env) -> Result<T, Error>
-    where
-        <T as FromStr>::Err: Fail,
-    {
-        self.read(env)?
-            .parse::<T>()
-            .context(ErrorKind::InvalidSettingValue)
-            .map_err(Error::from)
-    }
-
-    /// Try to read the value as a comma-separated list
-    fn read_list(&self, env: &Env) -> Result<Vec<String>, Error> {
-        Ok(self
-            .read(env)?
-            .split(',')
-            .filter(|s| !s.is_empty())
-            .map(ToOwned::to_owned)
-            .collect())
-    }
-
     /// Read this setting from the config file it appears in.
     /// If the setting is not found, its default value is returned.
     fn read(&self, env: &Env) -> Result<String, Error> {
-        let lines = match env.read_file_lines(self.file()) {
-            Ok(lines) => lines,
-            Err(e) => {
-                // If the file does not exist, use the default
-
-                // Chaining `if let` is not supported yet, so we have to nest
-                // them. https://github.com/rust-lang/rust/issues/53667
-                if let ErrorKind::FileRead(_) = e.kind() {
-                    if let Some(io::ErrorKind::NotFound) = e
-                        .cause()
-                        .unwrap()
-                        .downcast_ref::<io::Error>()
-                        .map(io::Error::kind)
-                    {
-                        return Ok(self.get_default().to_owned());
-                    }
-                }
-
-                // Return the original error if it was not a "not found" error
-                return Err(e);
-            }
-        };
-        let key = self.key();
+        let lines = read_config(self.file(), env)?;
 
-        // Check every line for the key (filter out lines which could not be read)
-        for line in lines {
-            // Ignore lines without the entry as a substring
-            if !line.contains(key.as_ref()) {
-                continue;
-            }
+        Ok(find_value(&lines, &self.key())
+            .unwrap_or_else(|| self.get_default())
+            .to_owned())
+    }
+}
 
-            let mut split = line.split('=');
-
-            // Check if we found the key by checking if the line starts with `entry=`
-            if split.next().map_or(false, |section| section == key) {
-                return Ok(
-                    // Get the right hand side if it exists and is not empty
-                    split.next().map_or_else(
-                        || self.get_default().to_owned(),
-                        |item| {
-                            if item.is_empty() {
-                                self.get_default().to_owned()
-                            } else {
-                                item.to_owned()
-                            }
-                        },
-                    ),
-                );
-            }
+/// Check if the error is from reading a file which does not exist
+fn is_not_found(error: &Error) -> bool {
+    match error.kind() {
+        ErrorKind::FileRead(_) => {
+            error
+                .cause()
+                .and_then(|cause| cause.downcast_ref::<io::Error>())
+                .map(io::Error::kind)
+                == Some(io::ErrorKind::NotFound)
         }
+        _ => false,
+    }
+}
 
-        Ok(self.get_default().to_owned())
+/// Read the lines of a config file. A missing file has no lines. Windows line
+/// endings are removed, so the file is rewritten with Unix line endings.
+pub fn read_config(file: PiholeFile, env: &Env) -> Result<Vec<String>, Error> {
+    match env.read_file_lines(file) {
+        Ok(lines) => Ok(lines
+            .into_iter()
+            .map(|line| line.trim_end_matches('\r').to_owned())
+            .collect()),
+        Err(e) if is_not_found(&e) => Ok(Vec::new()),
+        Err(e) => Err(e),
     }
+}
 
-    /// Write a value to the config file. If the value is empty then the entry
-    /// will be deleted. If the value is invalid, an error will be returned.
-    fn write(&self, value: &str, env: &Env) -> Result<(), Error> {
-        // Validate new value
-        if !self.is_valid(value) {
-            return Err(Error::from(ErrorKind::InvalidSettingValue));
-        }
+/// Read the config file, change its lines, and replace it atomically, so it
+/// is never left half written. The file's lock is held from the read until
+/// the write, so changes made to the file at the same time are not lost. If
+/// `change` returns an error, the file is not written.
+pub fn update_config<T>(
+    file: PiholeFile,
+    env: &Env,
+    change: impl FnOnce(&mut Vec<String>) -> Result<T, Error>,
+) -> Result<T, Error> {
+    env.with_file_lock(file, |writer| {
+        let mut lines = read_config(file, env)?;
+        let result = change(&mut lines)?;
+
+        writer.replace(config_contents(&lines).as_bytes())?;
+        Ok(result)
+    })
+}
 
-        // Read specified file, removing any line matching the setting we are writing
-        let key = self.key();
-        let entry_equals = format!("{}=", key);
-        let mut entries: Vec<String> = env
-            .read_file_lines(self.file())?
-            .into_iter()
-            .filter(|line| !line.starts_with(&entry_equals))
-            .collect();
+/// Join the lines of a config file, each followed by a newline
+fn config_contents(lines: &[String]) -> String {
+    let mut contents = String::new();
 
-        // Append entry to working copy if not empty
-        if !value.is_empty() {
-            let new_entry = format!("{}={}", key, value);
-            entries.push(new_entry);
-        }
+    for line in lines {
+        contents.push_str(line);
+        contents.push('\n');
+    }
 
-        // Open the config file to be overwritten
-        let mut file_writer = BufWriter::new(env.write_file(self.file(), false)?);
+    contents
+}
 
-        // Create the context for the error lazily.
-        // This way it is not allocating for errors at all, unless an error is thrown.
-        let apply_context = |error: io::Error| {
-            error.context(ErrorKind::FileWrite(
-                env.file_location(self.file()).to_owned(),
-            ))
-        };
+/// Split a `KEY=value` line of a config file into the key and the value.
+/// Comments and lines without a value are skipped.
+fn split_line(line: &str) -> Option<(&str, &str)> {
+    if line.trim_start().starts_with('#') {
+        return None;
+    }
 
-        // Write settings to file
-        for line in entries {
-            file_writer
-                .write_all(line.as_bytes())
-                .map_err(apply_context)?;
-            file_writer.write_all(b"\n").map_err(apply_context)?;
-        }
+    let index = line.find('=')?;
 
-        file_writer.flush().map_err(apply_context)?;
+    Some((&line[..index], &line[index + 1..]))
+}
 
-        Ok(())
-    }
+/// Find the value of the key in the lines of a config file. If the key is
+/// set more than once, the last value is used. An empty value is the same as
+/// a missing key.
+pub fn find_value<'a>(lines: &'a [String], key: &str) -> Option<&'a str> {
+    lines
+        .iter()
+        .rev()
+        .filter_map(|line| split_line(line))
+        .find(|(line_key, _)| *line_key == key)
+        .map(|(_, value)| value)
+        .filter(|value| !value.is_empty())
+}
 
-    /// Delete the entry from the config file. This is the same as writing an
-    /// empty string.
-    fn delete(&self, env: &Env) -> Result<(), Error> {
-        self.write("", env)
+/// Remove every line of the key, and append the key with the value. An empty
+/// value only removes the key. Other lines, including comments, are kept.
+pub fn replace_value(lines: &mut Vec<String>, key: &str, value: &str) {
+    lines.retain(|line| split_line(line).map_or(true, |(line_key, _)| line_key != key));
+
+    if !value.is_empty() {
+        lines.push(format!("{}={}", key, value));
     }
 }
 
@@ -207,6 +168,11 @@ pub enum SetupVarsEntry {
     PiholeDomain,
     PiholeInterface,
     QueryLogging,
+    RevServer,
+    RevServerCidr,
+    RevServerDomain,
+    RevServerTarget,
+    TemperatureUnit,
     WebPassword,
     WebLayout,
     WebLanguage,
@@ -249,6 +215,11 @@ impl ConfigEntry for SetupVarsEntry {
             SetupVarsEntry::PiholeDomain => Cow::Borrowed("PIHOLE_DOMAIN"),
             SetupVarsEntry::PiholeInterface => Cow::Borrowed("PIHOLE_INTERFACE"),
             SetupVarsEntry::QueryLogging => Cow::Borrowed("QUERY_LOGGING"),
+            SetupVarsEntry::RevServer => Cow::Borrowed("REV_SERVER"),
+            SetupVarsEntry::RevServerCidr => Cow::Borrowed("REV_SERVER_CIDR"),
+            SetupVarsEntry::RevServerDomain => Cow::Borrowed("REV_SERVER_DOMAIN"),
+            SetupVarsEntry::RevServerTarget => Cow::Borrowed("REV_SERVER_TARGET"),
+            SetupVarsEntry::TemperatureUnit => Cow::Borrowed("TEMPERATUREUNIT"),
             SetupVarsEntry::WebPassword => Cow::Borrowed("WEBPASSWORD"),
             SetupVarsEntry::WebLayout => Cow::Borrowed("WEBUIBOXEDLAYOUT"),
             SetupVarsEntry::WebLanguage => Cow::Borrowed("WEB_LANGUAGE"),
@@ -287,12 +258,15 @@ impl ConfigEntry for SetupVarsEntry {
             SetupVarsEntry::HostRecord => ValueType::Domain,
             SetupVarsEntry::Ipv4Address => ValueType::IPv4Mask,
             SetupVarsEntry::Ipv6Address => ValueType::IPv6,
-            SetupVarsEntry::PiholeDns(_) => {
-                ValueType::Any(&[ValueType::IPv4OptionalPort, ValueType::IPv6OptionalPort])
-            }
+            SetupVarsEntry::PiholeDns(_) => ValueType::Upstream,
             SetupVarsEntry::PiholeDomain => ValueType::Hostname,
             SetupVarsEntry::PiholeInterface => ValueType::Interface,
             SetupVarsEntry::QueryLogging => ValueType::Boolean,
+            SetupVarsEntry::RevServer => ValueType::Boolean,
+            SetupVarsEntry::RevServerCidr => ValueType::Network,
+            SetupVarsEntry::RevServerDomain => ValueType::Hostname,
+            SetupVarsEntry::RevServerTarget => ValueType::Any(&[ValueType::IPv4, ValueType::IPv6]),
+            SetupVarsEntry::TemperatureUnit => ValueType::String(&["C", "F", "K"]),
             SetupVarsEntry::WebPassword => ValueType::WebPassword,
             SetupVarsEntry::WebLayout => ValueType::String(&["boxed", "traditional"]),
             SetupVarsEntry::WebLanguage => ValueType::LanguageCode,
@@ -327,6 +301,11 @@ impl ConfigEntry for SetupVarsEntry {
             SetupVarsEntry::PiholeDomain => "lan",
             SetupVarsEntry::PiholeInterface => "",
             SetupVarsEntry::QueryLogging => "false",
+            SetupVarsEntry::RevServer => "false",
+            SetupVarsEntry::RevServerCidr => "",
+            SetupVarsEntry::RevServerDomain => "",
+            SetupVarsEntry::RevServerTarget => "",
+            SetupVarsEntry::TemperatureUnit => "C",
             SetupVarsEntry::WebPassword => "",
             SetupVarsEntry::WebLayout => "boxed",
             SetupVarsEntry::WebLanguage => "en",
@@ -334,40 +313,6 @@ impl ConfigEntry for SetupVarsEntry {
     }
 }
 
-impl SetupVarsEntry {
-    /// Delete all `SetupVarsEntry::PiholeDns` entries
-    pub fn delete_upstream_dns(env: &Env) -> Result<(), Error> {
-        let entries: Vec<String> = env
-            .read_file_lines(PiholeFile::SetupVars)?
-            .into_iter()
-            .filter(|line| !line.starts_with("PIHOLE_DNS_"))
-            .collect();
-
-        // Open the config file to be overwritten
-        let mut file_writer = BufWriter::new(env.write_file(PiholeFile::SetupVars, false)?);
-
-        // Create the context for the error lazily.
-        // This way it is not allocating for errors at all, unless an error is thrown.
-        let apply_context = |error: io::Error| {
-            error.context(ErrorKind::FileWrite(
-                env.file_location(PiholeFile::SetupVars).to_owned(),
-            ))
-        };
-
-        // Write settings to file
-        for line in entries {
-            file_writer
-                .write_all(line.as_bytes())
-                .map_err(apply_context)?;
-            file_writer.write_all(b"\n").map_err(apply_context)?;
-        }
-
-        file_writer.flush().map_err(apply_context)?;
-
-        Ok(())
-    }
-}
-
 /// pihole-FTL.conf settings file entries
 #[derive(Eq, PartialEq, Hash, Copy, Clone, Debug)]
 pub enum FtlConfEntry {
@@ -382,6 +327,7 @@ pub enum FtlConfEntry {
     MaxLogAge,
     PrivacyLevel,
     QueryDisplay,
+    RateLimit,
     RegexDebugMode,
     ResolveIpv4,
     ResolveIpv6,
@@ -406,6 +352,7 @@ impl ConfigEntry for FtlConfEntry {
             FtlConfEntry::MaxLogAge => "MAXLOGAGE",
             FtlConfEntry::PrivacyLevel => "PRIVACYLEVEL",
             FtlConfEntry::QueryDisplay => "QUERY_DISPLAY",
+            FtlConfEntry::RateLimit => "RATE_LIMIT",
             FtlConfEntry::RegexDebugMode => "REGEX_DEBUGMODE",
             FtlConfEntry::ResolveIpv4 => "RESOLVE_IPV6",
             FtlConfEntry::ResolveIpv6 => "RESOLVE_IPV6",
@@ -428,6 +375,7 @@ impl ConfigEntry for FtlConfEntry {
             FtlConfEntry::MaxLogAge => ValueType::Decimal,
             FtlConfEntry::PrivacyLevel => ValueType::String(&["0", "1", "2", "3", "4"]),
             FtlConfEntry::QueryDisplay => ValueType::YesNo,
+            FtlConfEntry::RateLimit => ValueType::RateLimit,
             FtlConfEntry::RegexDebugMode => ValueType::Boolean,
             FtlConfEntry::ResolveIpv4 => ValueType::YesNo,
             FtlConfEntry::ResolveIpv6 => ValueType::YesNo,
@@ -448,6 +396,7 @@ impl ConfigEntry for FtlConfEntry {
             FtlConfEntry::MaxLogAge => "24.0",
             FtlConfEntry::PrivacyLevel => "0",
             FtlConfEntry::QueryDisplay => "yes",
+            FtlConfEntry::RateLimit => "1000/60",
             FtlConfEntry::RegexDebugMode => "false",
             FtlConfEntry::ResolveIpv4 => "yes",
             FtlConfEntry::ResolveIpv6 => "yes",
@@ -458,11 +407,16 @@ impl ConfigEntry for FtlConfEntry {
 
 #[cfg(test)]
 mod tests {
-    use super::{ConfigEntry, SetupVarsEntry};
+    use super::{
+        is_not_found, read_config, replace_value, update_config, ConfigEntry, SetupVarsEntry,
+    };
     use crate::{
-        env::{Env, PiholeFile},
+        env::{Config, Env, FileLocks, PiholeFile},
         testing::TestEnvBuilder,
+        util::{Error, ErrorKind},
     };
+    use std::{fs, sync::Arc, thread};
+    use tempfile::tempdir;
 
     /// Run a test with a single file.
     ///
@@ -487,6 +441,15 @@ mod tests {
         test_file.assert_expected(&mut buffer);
     }
 
+    /// Write the entry's value to its config file, deleting the entry if the
+    /// value is empty
+    fn write_entry(entry: SetupVarsEntry, value: &str, env: &Env) -> Result<(), Error> {
+        update_config(entry.file(), env, |lines| {
+            replace_value(lines, &entry.key(), value);
+            Ok(())
+        })
+    }
+
     /// Test to make sure when writing a setting, a similar setting does not
     /// get deleted. Example: Adding PIHOLE_DNS_1 should not delete
     /// PIHOLE_DNS_10
@@ -498,7 +461,7 @@ mod tests {
             "PIHOLE_DNS_10=1.1.1.1\n\
              PIHOLE_DNS_1=2.2.2.2\n",
             |env| {
-                SetupVarsEntry::PiholeDns(1).write("2.2.2.2", &env).unwrap();
+                write_entry(SetupVarsEntry::PiholeDns(1), "2.2.2.2", &env).unwrap();
             },
         );
     }
@@ -507,7 +470,7 @@ mod tests {
     #[test]
     fn delete_value() {
         test_with_file(PiholeFile::SetupVars, "PIHOLE_DNS_1=1.2.3.4\n", "", |env| {
-            SetupVarsEntry::PiholeDns(1).write("", &env).unwrap();
+            write_entry(SetupVarsEntry::PiholeDns(1), "", &env).unwrap();
         });
     }
 
@@ -520,7 +483,7 @@ mod tests {
              PIHOLE_DNS_1=1.2.3.4\n",
             "PIHOLE_DNS_1=5.6.7.8\n",
             |env| {
-                SetupVarsEntry::PiholeDns(1).write("5.6.7.8", &env).unwrap();
+                write_entry(SetupVarsEntry::PiholeDns(1), "5.6.7.8", &env).unwrap();
             },
         );
     }
@@ -534,7 +497,7 @@ mod tests {
             "PIHOLE_DNS_1=\n",
             "PIHOLE_DNS_1=1.2.3.4\n",
             |env| {
-                SetupVarsEntry::PiholeDns(1).write("1.2.3.4", &env).unwrap();
+                write_entry(SetupVarsEntry::PiholeDns(1), "1.2.3.4", &env).unwrap();
             },
         );
     }
@@ -543,7 +506,7 @@ mod tests {
     #[test]
     fn write_to_empty_file() {
         test_with_file(PiholeFile::SetupVars, "", "PIHOLE_DNS_1=1.1.1.1\n", |env| {
-            SetupVarsEntry::PiholeDns(1).write("1.1.1.1", &env).unwrap();
+            write_entry(SetupVarsEntry::PiholeDns(1), "1.1.1.1", &env).unwrap();
         });
     }
 
@@ -557,4 +520,87 @@ mod tests {
 
         assert_eq!(value, default);
     }
+
+    /// Missing files are told apart from other errors, so writing an entry
+    /// can create the file
+    #[test]
+    fn not_found_error() {
+        let env = TestEnvBuilder::new().build();
+        let missing = env.read_file_lines(PiholeFile::FtlConfig).unwrap_err();
+
+        assert!(is_not_found(&missing));
+        assert!(!is_not_found(&Error::from(ErrorKind::Unknown)));
+    }
+
+    /// A missing file has no lines, and each written line ends with a
+    /// newline
+    #[test]
+    fn config_lines() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("custom.list");
+        let config = Config {
+            file_locations: toml::from_str(&format!("custom_list = \"{}\"", path.display()))
+                .unwrap(),
+            ..Config::default()
+        };
+        let env = Env::Production(config, FileLocks::default());
+        let lines = vec!["10.0.0.1 nas.lan".to_owned(), "10.0.0.2 tv.lan".to_owned()];
+
+        assert!(read_config(PiholeFile::CustomList, &env)
+            .unwrap()
+            .is_empty());
+
+        update_config(PiholeFile::CustomList, &env, |config| {
+            *config = lines.clone();
+            Ok(())
+        })
+        .unwrap();
+
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "10.0.0.1 nas.lan\n10.0.0.2 tv.lan\n"
+        );
+        assert_eq!(read_config(PiholeFile::CustomList, &env).unwrap(), lines);
+    }
+
+    /// Updates made at the same time are all kept, and a failed update does
+    /// not write the file
+    #[test]
+    fn concurrent_updates() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("custom.list");
+        let config = Config {
+            file_locations: toml::from_str(&format!("custom_list = \"{}\"", path.display()))
+                .unwrap(),
+            ..Config::default()
+        };
+        let env = Arc::new(Env::Production(config, FileLocks::default()));
+
+        let threads: Vec<_> = (0..8)
+            .map(|i| {
+                let env = Arc::clone(&env);
+
+                thread::spawn(move || {
+                    update_config(PiholeFile::CustomList, &env, |lines| {
+                        lines.push(format!("10.0.0.{} host{}.lan", i, i));
+                        Ok(())
+                    })
+                    .unwrap();
+                })
+            })
+            .collect();
+
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        let error = update_config(PiholeFile::CustomList, &env, |lines| {
+            lines.clear();
+            Err::<(), _>(Error::from(ErrorKind::Unknown))
+        })
+        .unwrap_err();
+
+        assert_eq!(error.kind(), ErrorKind::Unknown);
+        assert_eq!(read_config(PiholeFile::CustomList, &env).unwrap().len(), 8);
+    }
 }
@@ -14,8 +14,11 @@ mod privacy_level;
 mod value_type;
 
 pub use self::{
-    dnsmasq::generate_dnsmasq_config,
-    entries::{ConfigEntry, FtlConfEntry, SetupVarsEntry},
-    privacy_level::FtlPrivacyLevel,
+    dnsmasq::{generate_dnsmasq_config, write_dnsmasq_config, DnsmasqSettings},
+    entries::{
+        find_value, read_config, replace_value, update_config, ConfigEntry, FtlConfEntry,
+        SetupVarsEntry,
+    },
+    privacy_level::{FtlPrivacyLevel, HIDDEN_CLIENT, HIDDEN_DOMAIN},
     value_type::*,
 };
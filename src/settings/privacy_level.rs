@@ -11,6 +11,12 @@
 use crate::util::{Error, ErrorKind};
 use std::str::FromStr;
 
+/// The designated hidden domain
+pub const HIDDEN_DOMAIN: &str = "hidden";
+
+/// The designated hidden client IP address
+pub const HIDDEN_CLIENT: &str = "0.0.0.0";
+
 /// The privacy levels used by FTL
 #[repr(u8)]
 #[cfg_attr(test, derive(Debug))]
@@ -23,6 +29,52 @@ pub enum FtlPrivacyLevel {
     NoStats,
 }
 
+impl FtlPrivacyLevel {
+    /// Check if an endpoint which returns no data at `hidden_at` and higher
+    /// levels can return data at this level
+    pub fn allows(self, hidden_at: FtlPrivacyLevel) -> bool {
+        self < hidden_at
+    }
+
+    /// Get the stricter of two levels, such as the configured level and the
+    /// level a query was made with
+    pub fn stricter(self, other: FtlPrivacyLevel) -> FtlPrivacyLevel {
+        if other > self {
+            other
+        } else {
+            self
+        }
+    }
+
+    /// Check if domains are hidden at this level
+    pub fn hides_domains(self) -> bool {
+        self >= FtlPrivacyLevel::HideDomains
+    }
+
+    /// Check if clients are hidden at this level
+    pub fn hides_clients(self) -> bool {
+        self >= FtlPrivacyLevel::HideDomainsAndClients
+    }
+
+    /// Get the domain to show at this level
+    pub fn redact_domain(self, domain: &str) -> &str {
+        if self.hides_domains() {
+            HIDDEN_DOMAIN
+        } else {
+            domain
+        }
+    }
+
+    /// Get the client to show at this level
+    pub fn redact_client(self, client: &str) -> &str {
+        if self.hides_clients() {
+            HIDDEN_CLIENT
+        } else {
+            client
+        }
+    }
+}
+
 impl FromStr for FtlPrivacyLevel {
     type Err = Error;
 
@@ -11,7 +11,8 @@
 use get_if_addrs::get_if_addrs;
 use regex::Regex;
 use std::{
-    net::{Ipv4Addr, Ipv6Addr},
+    fmt::{self, Display},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
     path::Path,
     str::FromStr,
 };
@@ -33,15 +34,20 @@ pub enum ValueType {
     Integer,
     Interface,
     IPv4,
-    IPv4OptionalPort,
     IPv4Mask,
     IPv4CIDR,
     IPv6,
-    IPv6OptionalPort,
     IPv6CIDR,
+    /// A colon separated MAC address, such as `b8:27:eb:01:02:03`
+    MacAddress,
+    /// An IPv4 or IPv6 network in CIDR notation, such as `192.168.0.0/16`
+    Network,
     Path,
     PortNumber,
-    Regex,
+    /// An FTL rate limit, see [`RateLimit`]
+    RateLimit,
+    /// An upstream DNS server, see [`Upstream`]
+    Upstream,
     YesNo,
     WebPassword,
     String(&'static [&'static str]),
@@ -126,25 +132,6 @@ impl ValueType {
                 // Test if valid address falls within permitted ranges
                 is_ipv4_valid(value)
             }
-            ValueType::IPv4OptionalPort => {
-                // Valid, in allowable range, with optional port
-                // (4 octets, with port from 0 to 65535, colon delimited)
-                if is_ipv4_valid(value) {
-                    return true;
-                }
-
-                if !value.contains(':') {
-                    return false;
-                }
-
-                // Check if port is specified
-                let (ip, port) = value.split_at(value.rfind(':').unwrap());
-                if let Ok(port) = port.replace(":", "").parse::<usize>() {
-                    is_ipv4_valid(ip) && port <= 65535
-                } else {
-                    false
-                }
-            }
             ValueType::IPv4Mask => {
                 // Valid, in allowable range, and with mask
                 // (4 octets, with mask)
@@ -157,7 +144,6 @@ impl ValueType {
             }
             ValueType::IPv4CIDR => ValueType::String(&["8", "16", "24", "32"]).is_valid(value),
             ValueType::IPv6 => is_ipv6_valid(value),
-            ValueType::IPv6OptionalPort => get_ipv6_address_and_port(value).is_some(),
             ValueType::IPv6CIDR => {
                 // The CIDR must be a positive number
                 let cidr: usize = match value.parse() {
@@ -167,6 +153,10 @@ impl ValueType {
 
                 cidr > 0 && cidr <= 128 && cidr % 4 == 0
             }
+            ValueType::MacAddress => Regex::new("^([0-9a-fA-F]{2}:){5}[0-9a-fA-F]{2}$")
+                .unwrap()
+                .is_match(value),
+            ValueType::Network => parse_network(value).is_some(),
             ValueType::Path => {
                 // Test if a path and filename have been specified
                 let path = Path::new(value);
@@ -180,7 +170,10 @@ impl ValueType {
                     false
                 }
             }
-            ValueType::Regex => Regex::new(value).is_ok(),
+            ValueType::RateLimit => value
+                .parse::<RateLimit>()
+                .map_or(false, |rate_limit| rate_limit.is_valid()),
+            ValueType::Upstream => value.parse::<Upstream>().is_ok(),
             ValueType::YesNo => matches!(value, "yes" | "no"),
             ValueType::WebPassword => {
                 // Web password is a valid key, but altering it is disallowed
@@ -223,37 +216,182 @@ fn is_ipv6_valid(value: &str) -> bool {
     }
 }
 
-/// Get the address and port of an string representing an IPv6 address with or
-/// without a port.
-///
-/// If the address is invalid, None is returned.
-/// Otherwise, the returned tuple represents the address and optional port
-pub fn get_ipv6_address_and_port(value: &str) -> Option<(&str, Option<usize>)> {
-    // If this is a valid IPv6 address without a port, we can stop here
-    if is_ipv6_valid(value) {
-        return Some((value, None));
+/// An upstream DNS server: an IP address with an optional port. Upstreams are
+/// parsed from the dnsmasq format (`8.8.8.8#5353`, `2606:4700:4700::1111#53`
+/// or `[2606:4700:4700::1111]#53`) or the SetupVars format (`8.8.8.8:5353` or
+/// `[2606:4700:4700::1111]:53`). Hostnames are not allowed.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Upstream {
+    pub address: IpAddr,
+    pub port: Option<u16>,
+}
+
+impl Upstream {
+    /// Get the upstream in the format dnsmasq understands, such as
+    /// `127.0.0.1#5353` or `::1#5353`
+    pub fn to_dnsmasq(self) -> String {
+        match self.port {
+            Some(port) => format!("{}#{}", self.address, port),
+            None => self.address.to_string(),
+        }
     }
+}
 
-    // Extract the address and port using Regex
-    let ipv6_re = Regex::new(r"^\[([a-fA-F0-9:]+)]:(\d+)$").unwrap();
+/// The upstream in the format stored in SetupVars. The address is normalized,
+/// and IPv6 addresses only have brackets when there is a port.
+impl Display for Upstream {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (self.address, self.port) {
+            (IpAddr::V6(address), Some(port)) => write!(f, "[{}]:{}", address, port),
+            (address, Some(port)) => write!(f, "{}:{}", address, port),
+            (address, None) => write!(f, "{}", address),
+        }
+    }
+}
+
+/// Parse an upstream. The error is the reason the upstream is invalid.
+impl FromStr for Upstream {
+    type Err = &'static str;
 
-    // If the value does not match the regex, we return None via ?
-    let captures = ipv6_re.captures(value)?;
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (address, port) = if let Some(index) = value.rfind('#') {
+            (&value[..index], Some(&value[index + 1..]))
+        } else if value.starts_with('[') {
+            // A bracketed IPv6 address in the SetupVars format
+            match value.find("]:") {
+                Some(index) => (&value[..=index], Some(&value[index + 2..])),
+                None => (value, None),
+            }
+        } else if value.matches(':').count() == 1 {
+            // An IPv4 address in the SetupVars format
+            let index = value.find(':').unwrap();
+            (&value[..index], Some(&value[index + 1..]))
+        } else {
+            (value, None)
+        };
+
+        let address = match address
+            .strip_prefix('[')
+            .and_then(|address| address.strip_suffix(']'))
+        {
+            Some(address) => IpAddr::V6(
+                address
+                    .parse()
+                    .map_err(|_| "Brackets are only allowed around IPv6 addresses")?,
+            ),
+            None => parse_upstream_address(address)?,
+        };
 
-    // Get the IPv6 address and port
-    let address = captures.get(1).map(|m| m.as_str())?;
-    let port: usize = captures[2].parse().ok()?;
+        let port = match port {
+            Some(port) => match port.parse::<u16>() {
+                Ok(port) if port > 0 => Some(port),
+                _ => return Err("The port must be a number from 1 to 65535"),
+            },
+            None => None,
+        };
 
-    if is_ipv6_valid(address) && port <= 65535 {
-        Some((address, Some(port)))
+        Ok(Upstream { address, port })
+    }
+}
+
+/// Parse the address of an upstream, rejecting hostnames and addresses which
+/// can not be used to reach a DNS server
+fn parse_upstream_address(value: &str) -> Result<IpAddr, &'static str> {
+    let address = match IpAddr::from_str(value) {
+        Ok(address) => address,
+        Err(_) if ValueType::Hostname.is_valid(value) => {
+            return Err("Hostnames are not allowed, use an IP address")
+        }
+        Err(_) => return Err("Invalid IP address"),
+    };
+
+    let usable = match address {
+        IpAddr::V4(address) => is_ipv4_valid(&address.to_string()),
+        IpAddr::V6(address) => is_ipv6_valid(&address.to_string()),
+    };
+
+    if usable {
+        Ok(address)
+    } else {
+        Err("The address can not be used as an upstream")
+    }
+}
+
+/// An FTL rate limit: clients may make at most `queries` queries in
+/// `seconds` seconds. It is stored as `queries/seconds`, and `0/0` turns rate
+/// limiting off.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Eq, PartialEq)]
+pub struct RateLimit {
+    pub queries: u32,
+    pub seconds: u32,
+}
+
+impl RateLimit {
+    /// Check if the rate limit can be used by FTL. Both values must be
+    /// positive, unless rate limiting is off.
+    pub fn is_valid(&self) -> bool {
+        (self.queries == 0) == (self.seconds == 0)
+    }
+}
+
+impl Display for RateLimit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.queries, self.seconds)
+    }
+}
+
+impl FromStr for RateLimit {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (queries, seconds) = value.split_at(value.find('/').ok_or(())?);
+
+        Ok(RateLimit {
+            queries: queries.parse().map_err(|_| ())?,
+            seconds: seconds[1..].parse().map_err(|_| ())?,
+        })
+    }
+}
+
+/// Parse a network in CIDR notation into its address and prefix length. The
+/// prefix length must fit the address family.
+pub fn parse_network(value: &str) -> Option<(IpAddr, u32)> {
+    let (address, prefix) = value.split_at(value.find('/')?);
+    let address = IpAddr::from_str(address).ok()?;
+    let prefix: u32 = prefix[1..].parse().ok()?;
+    let max_prefix = if address.is_ipv4() { 32 } else { 128 };
+
+    if prefix <= max_prefix {
+        Some((address, prefix))
     } else {
         None
     }
 }
 
+/// Check if the address is inside the network. Addresses are never inside a
+/// network of the other address family.
+pub fn network_contains(network: (IpAddr, u32), address: IpAddr) -> bool {
+    let (network, prefix) = network;
+
+    match (network, address) {
+        (IpAddr::V4(network), IpAddr::V4(address)) => {
+            let mask = u32::MAX.checked_shl(32 - prefix).unwrap_or(0);
+            u32::from(network) & mask == u32::from(address) & mask
+        }
+        (IpAddr::V6(network), IpAddr::V6(address)) => {
+            let mask = u128::MAX.checked_shl(128 - prefix).unwrap_or(0);
+            u128::from(network) & mask == u128::from(address) & mask
+        }
+        _ => false,
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{get_if_addrs, get_ipv6_address_and_port, is_ipv4_valid, ValueType};
+    use super::{
+        get_if_addrs, is_ipv4_valid, network_contains, parse_network, RateLimit, Upstream,
+        ValueType,
+    };
 
     #[test]
     fn test_value_type_valid() {
@@ -284,20 +422,24 @@ mod tests {
             (ValueType::Integer, "8675309"),
             (ValueType::Interface, &available_interface),
             (ValueType::IPv4, "192.168.2.9"),
-            (ValueType::IPv4OptionalPort, "192.168.4.5:80"),
-            (ValueType::IPv4OptionalPort, "192.168.3.3"),
             (ValueType::IPv4Mask, "192.168.0.3/24"),
             (ValueType::IPv4CIDR, "24"),
             (ValueType::IPv6, "f7c4:12f8:4f5a:8454:5241:cf80:d61c:3e2c"),
-            (
-                ValueType::IPv6OptionalPort,
-                "f7c4:12f8:4f5a:8454:5241:cf80:d61c:3e2c",
-            ),
-            (ValueType::IPv6OptionalPort, "[1fff:0:a88:85a3::ac1f]:8001"),
             (ValueType::IPv6CIDR, "64"),
+            (ValueType::MacAddress, "B8:27:eb:01:02:03"),
+            (ValueType::Network, "192.168.0.0/16"),
+            (ValueType::Network, "fd00::/8"),
             (ValueType::Path, "/tmp/directory/file.ext"),
             (ValueType::PortNumber, "9000"),
-            (ValueType::Regex, "^.*example$"),
+            (ValueType::RateLimit, "1000/60"),
+            (ValueType::RateLimit, "0/0"),
+            (ValueType::Upstream, "192.168.4.5:80"),
+            (ValueType::Upstream, "192.168.3.3"),
+            (
+                ValueType::Upstream,
+                "f7c4:12f8:4f5a:8454:5241:cf80:d61c:3e2c",
+            ),
+            (ValueType::Upstream, "[1fff:0:a88:85a3::ac1f]:8001"),
             (ValueType::YesNo, "yes"),
             (ValueType::String(&["boxed", ""]), "boxed"),
         ];
@@ -346,21 +488,29 @@ mod tests {
             (ValueType::Interface, "/dev/net/ev9d9"),
             (ValueType::IPv4, "192.168.0.3/24"),
             (ValueType::IPv4, "192.168.0.2:53"),
-            (ValueType::IPv4OptionalPort, "192.168.4.5 port 1000"),
-            (ValueType::IPv4OptionalPort, "192.168.6.8:arst"),
             (ValueType::IPv4Mask, "192.168.2.9"),
             (ValueType::IPv4Mask, "192.168.1.1/qwfp"),
             (ValueType::IPv4CIDR, "-1"),
             (ValueType::IPv4CIDR, "124"),
             (ValueType::IPv6, "192.168.0.3"),
-            (ValueType::IPv6OptionalPort, "192.168.0.3"),
-            (ValueType::IPv6OptionalPort, "1fff:0:a88:85a3::ac1f#8001"),
             (ValueType::IPv6CIDR, "-1"),
             (ValueType::IPv6CIDR, "23"),
             (ValueType::IPv6CIDR, "150"),
+            (ValueType::MacAddress, "b8:27:eb:01:02"),
+            (ValueType::MacAddress, "b8-27-eb-01-02-03"),
+            (ValueType::MacAddress, "b8:27:eb:01:02:0g"),
+            (ValueType::Network, "192.168.0.0"),
+            (ValueType::Network, "192.168.0.0/33"),
+            (ValueType::Network, "fd00::/129"),
+            (ValueType::Network, "lan/24"),
             (ValueType::Path, "~/tmp/directory/file.ext"),
             (ValueType::PortNumber, "65536"),
-            (ValueType::Regex, "example\\"),
+            (ValueType::RateLimit, "1000/0"),
+            (ValueType::RateLimit, "0/60"),
+            (ValueType::RateLimit, "1000"),
+            (ValueType::RateLimit, "-1/60"),
+            (ValueType::Upstream, "192.168.4.5 port 1000"),
+            (ValueType::Upstream, "192.168.6.8:arst"),
             (ValueType::YesNo, "true"),
             (ValueType::String(&["boxed", ""]), "lan"),
         ];
@@ -394,23 +544,128 @@ mod tests {
         }
     }
 
-    /// `get_ipv6_address_and_port` returns a tuple without a port when given
-    /// an IPv6 address with no port
+    /// Addresses are only inside networks with the same prefix and address
+    /// family
+    #[test]
+    fn network_contains_address() {
+        let network = parse_network("192.168.1.0/24").unwrap();
+
+        assert!(network_contains(network, "192.168.1.1".parse().unwrap()));
+        assert!(!network_contains(network, "192.168.2.1".parse().unwrap()));
+        assert!(!network_contains(network, "fd00::1".parse().unwrap()));
+        assert!(network_contains(
+            parse_network("0.0.0.0/0").unwrap(),
+            "10.0.0.1".parse().unwrap()
+        ));
+        assert!(network_contains(
+            parse_network("fd00::/8").unwrap(),
+            "fd12:3456::1".parse().unwrap()
+        ));
+    }
+
+    /// Valid upstreams in each format are parsed, and normalized into the
+    /// SetupVars and dnsmasq formats. Parsing either format again gives the
+    /// same upstream, so round trips are stable.
+    #[test]
+    fn upstream_valid() {
+        let tests = [
+            ("8.8.8.8", "8.8.8.8", "8.8.8.8"),
+            ("8.8.8.8#5353", "8.8.8.8:5353", "8.8.8.8#5353"),
+            ("8.8.8.8:5353", "8.8.8.8:5353", "8.8.8.8#5353"),
+            (
+                "2606:4700:4700::1111",
+                "2606:4700:4700::1111",
+                "2606:4700:4700::1111",
+            ),
+            (
+                "[2606:4700:4700::1111]#53",
+                "[2606:4700:4700::1111]:53",
+                "2606:4700:4700::1111#53",
+            ),
+            (
+                "[2606:4700:4700::1111]:53",
+                "[2606:4700:4700::1111]:53",
+                "2606:4700:4700::1111#53",
+            ),
+            (
+                "2606:4700:4700::1111#53",
+                "[2606:4700:4700::1111]:53",
+                "2606:4700:4700::1111#53",
+            ),
+            (
+                "[2606:4700:4700:0:0:0:0:1111]",
+                "2606:4700:4700::1111",
+                "2606:4700:4700::1111",
+            ),
+            (
+                "2001:DB8::AC1F#65535",
+                "[2001:db8::ac1f]:65535",
+                "2001:db8::ac1f#65535",
+            ),
+            ("127.0.0.1#1", "127.0.0.1:1", "127.0.0.1#1"),
+        ];
+
+        for (value, setup_vars, dnsmasq) in tests.iter() {
+            let upstream: Upstream = value.parse().unwrap();
+
+            assert_eq!(upstream.to_string(), *setup_vars, "{}", value);
+            assert_eq!(upstream.to_dnsmasq(), *dnsmasq, "{}", value);
+            assert_eq!(setup_vars.parse(), Ok(upstream), "{}", value);
+            assert_eq!(dnsmasq.parse(), Ok(upstream), "{}", value);
+            assert!(ValueType::Upstream.is_valid(value), "{}", value);
+        }
+    }
+
+    /// Invalid upstreams are rejected with the reason
     #[test]
-    fn get_ipv6_info_no_port() {
-        assert_eq!(
-            get_ipv6_address_and_port("f7c4:12f8:4f5a:8454:5241:cf80:d61c:3e2c"),
-            Some(("f7c4:12f8:4f5a:8454:5241:cf80:d61c:3e2c", None))
-        );
+    fn upstream_invalid() {
+        let hostname = "Hostnames are not allowed, use an IP address";
+        let address = "Invalid IP address";
+        let port = "The port must be a number from 1 to 65535";
+        let brackets = "Brackets are only allowed around IPv6 addresses";
+        let unusable = "The address can not be used as an upstream";
+        let tests = [
+            ("", address),
+            ("dns.google", hostname),
+            ("dns.google#53", hostname),
+            ("localhost", hostname),
+            ("8.8.8", address),
+            ("8.8.8.256", address),
+            ("8.8.8.8#0", port),
+            ("8.8.8.8#65536", port),
+            ("8.8.8.8#", port),
+            ("8.8.8.8#53a", port),
+            ("8.8.8.8:-1", port),
+            ("[8.8.8.8]#53", brackets),
+            ("[2606:4700:4700::1111", address),
+            ("2606:4700:4700::1111]#53", address),
+            ("[2606:4700:4700::1111]#0", port),
+            ("[2606:4700:4700::1111]:70000", port),
+            ("2606:4700:4700:::1111", address),
+            ("2606:4700:4700::1111%eth0", address),
+            ("0.0.0.0", unusable),
+            ("224.0.0.1", unusable),
+            ("ff02::1", unusable),
+            ("::", unusable),
+        ];
+
+        for (value, reason) in tests.iter() {
+            assert_eq!(value.parse::<Upstream>(), Err(*reason), "{}", value);
+            assert!(!ValueType::Upstream.is_valid(value), "{}", value);
+        }
     }
 
-    /// `get_ipv6_address_and_port` returns a tuple with a port when given an
-    /// IPv6 address with a port
+    /// Rate limits are parsed from and written as `queries/seconds`
     #[test]
-    fn get_ipv6_info_with_port() {
-        assert_eq!(
-            get_ipv6_address_and_port("[1fff:0:a88:85a3::ac1f]:8001"),
-            Some(("1fff:0:a88:85a3::ac1f", Some(8001)))
-        );
+    fn rate_limit() {
+        let rate_limit = RateLimit {
+            queries: 1000,
+            seconds: 60,
+        };
+
+        assert_eq!("1000/60".parse::<RateLimit>(), Ok(rate_limit));
+        assert_eq!(rate_limit.to_string(), "1000/60");
+        assert_eq!("1000/".parse::<RateLimit>(), Err(()));
+        assert_eq!("1000 60".parse::<RateLimit>(), Err(()));
     }
 }
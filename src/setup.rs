@@ -15,19 +15,20 @@ use crate::{
         gravity::{GravityDatabasePool, GravityDatabasePoolParameters},
         load_ftl_db_config, load_gravity_db_config,
     },
-    env::{Config, Env},
+    env::{Config, Env, FileLocks},
     ftl::FtlMemory,
     routes::{
         auth::{self, AuthData},
-        dns, settings, stats, version, web,
+        dns, network, settings, stats, version, web,
     },
-    services::PiholeModule,
-    settings::{ConfigEntry, SetupVarsEntry},
+    services::{settings::SettingsRepository, PiholeModule},
+    settings::SetupVarsEntry,
     util::{Error, ErrorKind},
 };
 use failure::ResultExt;
 use rocket::{Build, Rocket};
 use rocket_cors::CorsOptions;
+use shaku::HasComponent;
 
 #[cfg(test)]
 use rocket::config::LogLevel;
@@ -46,16 +47,15 @@ fn unauthorized() -> Error {
 /// Run the API normally (connect to FTL over the socket)
 pub async fn start(config_location: &Path) -> Result<(), Error> {
     let config = Config::load(config_location)?;
-    let env = Env::Production(config);
-    let key = SetupVarsEntry::WebPassword.read(&env)?;
+    let env = Env::Production(config, FileLocks::default());
 
     println!("{:#?}", env.config());
 
     let module = PiholeModule::builder()
-        .with_component_parameters::<GravityDatabasePool>(GravityDatabasePoolParameters {
-            pool: CustomSqliteConnection::pool(load_gravity_db_config(&env)?)
+        .with_component_parameters::<GravityDatabasePool>(GravityDatabasePoolParameters::new(
+            CustomSqliteConnection::pool(load_gravity_db_config(&env)?)
                 .context(ErrorKind::GravityDatabase)?,
-        })
+        )?)
         .with_component_parameters::<FtlDatabasePool>(FtlDatabasePoolParameters {
             pool: CustomSqliteConnection::pool(load_ftl_db_config(&env)?)
                 .context(ErrorKind::FtlDatabase)?,
@@ -63,6 +63,9 @@ pub async fn start(config_location: &Path) -> Result<(), Error> {
         .with_component_parameters::<Env>(env.clone())
         .build();
 
+    let repository: &dyn SettingsRepository = module.resolve_ref();
+    let key = repository.get(SetupVarsEntry::WebPassword)?;
+
     setup(
         rocket::custom(rocket::Config {
             address: env.config().general.address.parse().unwrap(),
@@ -166,18 +169,31 @@ fn setup(
             auth::check,
             auth::logout,
             stats::summary::get_summary,
+            stats::ftl_counters::route,
             stats::top_domains::route,
+            stats::top_blocked::route,
             stats::top_clients::route,
             stats::upstreams::route,
+            stats::upstream_response_times::route,
+            stats::query_statuses::route,
             stats::query_types::route,
+            stats::reply_types::route,
+            stats::reply_times::route,
             stats::history::route,
             stats::recent_blocked::route,
+            stats::recent_blocked::recent_blocked_latest,
+            stats::recent_activity::route,
             stats::clients::route,
+            stats::client_activity::route,
+            stats::client_detail::route,
             stats::over_time_history::route,
             stats::over_time_clients::route,
+            stats::over_time_upstreams::route,
+            stats::over_time_query_types::route,
             stats::database::summary_db::get_summary_db,
             stats::database::over_time_clients_db::route,
             stats::database::over_time_history_db::route,
+            stats::database::query_db::route,
             stats::database::query_types_db::route,
             stats::database::top_clients_db::route,
             stats::database::top_domains_db::route,
@@ -185,22 +201,97 @@ fn setup(
             dns::get_whitelist,
             dns::get_blacklist,
             dns::get_regexlist,
+            dns::get_regex_whitelist,
+            dns::get_whitelist_entry,
+            dns::get_blacklist_entry,
+            dns::get_regexlist_entry,
+            dns::get_regex_whitelist_entry,
+            dns::export_lists,
+            dns::get_lists_summary,
+            dns::import_lists,
+            dns::search_lists,
+            dns::move_domain,
             dns::get_status,
             dns::change_status,
+            dns::get_domain_status,
             dns::add_whitelist,
             dns::add_blacklist,
             dns::add_regexlist,
+            dns::add_regex_whitelist,
+            dns::add_whitelist_batch,
+            dns::add_blacklist_batch,
+            dns::add_regexlist_batch,
+            dns::test_regex,
             dns::delete_whitelist,
             dns::delete_blacklist,
             dns::delete_regexlist,
+            dns::delete_regex_whitelist,
+            dns::delete_whitelist_batch,
+            dns::delete_blacklist_batch,
+            dns::delete_regexlist_batch,
+            dns::update_whitelist,
+            dns::update_blacklist,
+            dns::update_regexlist,
+            dns::edit_whitelist,
+            dns::edit_blacklist,
+            dns::edit_regexlist,
+            dns::edit_regex_whitelist,
+            dns::get_whitelist_groups,
+            dns::get_blacklist_groups,
+            dns::get_regexlist_groups,
+            dns::set_whitelist_groups,
+            dns::set_blacklist_groups,
+            dns::set_regexlist_groups,
+            dns::get_audit,
+            dns::add_audit,
+            dns::delete_audit,
+            dns::get_adlists,
+            dns::get_adlist_stats,
+            dns::get_adlist_status,
+            dns::get_gravity_count,
+            dns::add_adlist,
+            dns::delete_adlist,
+            dns::update_adlist,
+            dns::get_groups,
+            dns::add_group,
+            dns::update_group,
+            dns::delete_group,
+            dns::get_local_records,
+            dns::add_local_record,
+            dns::delete_local_record,
+            dns::get_local_cnames,
+            dns::add_local_cname,
+            dns::delete_local_cname,
             settings::get_dhcp,
             settings::put_dhcp,
+            settings::get_static_leases,
+            settings::add_static_lease,
+            settings::delete_static_lease,
+            settings::get_dhcp_leases,
+            settings::delete_dhcp_lease,
             settings::get_dns,
             settings::put_dns,
+            settings::get_dns_upstreams,
+            settings::put_dns_upstreams,
+            settings::get_dns_options,
+            settings::put_dns_options,
+            settings::get_conditional_forwarding,
+            settings::put_conditional_forwarding,
+            settings::get_dnssec,
+            settings::put_dnssec,
+            settings::get_rate_limit,
+            settings::put_rate_limit,
+            settings::get_query_logging,
+            settings::put_query_logging,
             settings::get_ftldb,
             settings::get_ftl,
             settings::get_network,
+            settings::get_privacy,
+            settings::put_privacy,
             settings::get_web,
-            settings::put_web
+            settings::put_web,
+            settings::get_display,
+            settings::put_display,
+            network::get_devices
         ])
 }
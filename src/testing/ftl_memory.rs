@@ -0,0 +1,683 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// FTL Shared Memory Test Data Builder
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    ftl::{
+        FtlClient, FtlCounters, FtlDnssecType, FtlDomain, FtlMemory, FtlOverTime, FtlQuery,
+        FtlQueryReplyType, FtlQueryStatus, FtlQueryType, FtlRegexMatch, FtlSettings, FtlUpstream,
+        MAGIC_BYTE, OVERTIME_INTERVAL, OVERTIME_SLOTS,
+    },
+    settings::{FtlPrivacyLevel, HIDDEN_CLIENT, HIDDEN_DOMAIN},
+};
+use std::collections::{BTreeMap, HashMap};
+
+/// Builds a consistent `FtlMemory::Test`. Strings are added to the string
+/// table automatically, and the counters, per-domain/client/upstream counts,
+/// and overTime slots are all derived from the added queries.
+///
+/// Clients and upstreams are registered up front and referenced by the id
+/// which is returned. Domains are registered the first time a query uses them.
+pub struct TestFtlMemoryBuilder {
+    strings: HashMap<usize, String>,
+    string_ids: HashMap<String, usize>,
+    /// The offset of the next string in the string table
+    next_str_pos: usize,
+    clients: Vec<TestClient>,
+    upstreams: Vec<TestUpstream>,
+    domains: Vec<TestDomain>,
+    domain_ids: HashMap<String, usize>,
+    queries: Vec<FtlQuery>,
+    /// The clients and upstreams used by `random_queries`, once created
+    random_clients: Vec<usize>,
+    random_upstreams: Vec<usize>,
+}
+
+struct TestClient {
+    ip_str_id: usize,
+    name_str_id: Option<usize>,
+    query_count: usize,
+    blocked_count: usize,
+    /// Query counts keyed by overTime slot timestamp
+    over_time: BTreeMap<usize, libc::c_int>,
+}
+
+struct TestUpstream {
+    ip_str_id: usize,
+    name_str_id: Option<usize>,
+    query_count: usize,
+}
+
+struct TestDomain {
+    domain_str_id: usize,
+    query_count: usize,
+    blocked_count: usize,
+    regex_match: FtlRegexMatch,
+}
+
+/// The counts of a single overTime slot
+#[derive(Default)]
+struct TestOverTime {
+    total: usize,
+    blocked: usize,
+    cached: usize,
+    forwarded: usize,
+    query_types: [libc::c_int; 7],
+}
+
+impl TestFtlMemoryBuilder {
+    /// Create a new, empty `TestFtlMemoryBuilder`
+    pub fn new() -> Self {
+        TestFtlMemoryBuilder {
+            strings: HashMap::new(),
+            string_ids: HashMap::new(),
+            next_str_pos: 1,
+            clients: Vec::new(),
+            upstreams: Vec::new(),
+            domains: Vec::new(),
+            domain_ids: HashMap::new(),
+            queries: Vec::new(),
+            random_clients: Vec::new(),
+            random_upstreams: Vec::new(),
+        }
+    }
+
+    /// Add a client with a hostname and get its id
+    pub fn client(&mut self, ip: &str, name: &str) -> usize {
+        let name_str_id = self.string(name);
+        self.add_client(ip, Some(name_str_id))
+    }
+
+    /// Add a client without a hostname and get its id
+    pub fn unnamed_client(&mut self, ip: &str) -> usize {
+        self.add_client(ip, None)
+    }
+
+    /// Add an upstream with a hostname and get its id
+    pub fn upstream(&mut self, ip: &str, name: &str) -> usize {
+        let ip_str_id = self.string(ip);
+        let name_str_id = self.string(name);
+
+        self.upstreams.push(TestUpstream {
+            ip_str_id,
+            name_str_id: Some(name_str_id),
+            query_count: 0,
+        });
+        self.upstreams.len() - 1
+    }
+
+    /// Add an A query and get its id. The upstream is only used when the
+    /// query was forwarded.
+    pub fn query(
+        &mut self,
+        timestamp: usize,
+        domain: &str,
+        client: usize,
+        status: FtlQueryStatus,
+        upstream: Option<usize>,
+    ) -> usize {
+        self.typed_query(timestamp, FtlQueryType::A, domain, client, status, upstream)
+    }
+
+    /// Add a query of the given type and get its id. The upstream is only used
+    /// when the query was forwarded.
+    pub fn typed_query(
+        &mut self,
+        timestamp: usize,
+        query_type: FtlQueryType,
+        domain: &str,
+        client: usize,
+        status: FtlQueryStatus,
+        upstream: Option<usize>,
+    ) -> usize {
+        let domain_id = self.domain(domain);
+        let id = self.queries.len() + 1;
+
+        let mut query = FtlQuery {
+            magic: MAGIC_BYTE,
+            id: id as libc::c_int,
+            database_id: id as i64,
+            timestamp: timestamp as libc::time_t,
+            time_index: 0,
+            response_time: 1,
+            domain_id: domain_id as libc::c_int,
+            client_id: client as libc::c_int,
+            upstream_id: 0,
+            query_type,
+            status,
+            reply_type: FtlQueryReplyType::IP,
+            dnssec_type: FtlDnssecType::Unspecified,
+            is_complete: true,
+            privacy_level: FtlPrivacyLevel::ShowAll,
+        };
+
+        let blocked = query.is_blocked();
+
+        let domain = &mut self.domains[domain_id];
+        domain.query_count += 1;
+        if blocked {
+            domain.blocked_count += 1;
+        }
+        if status == FtlQueryStatus::Wildcard {
+            domain.regex_match = FtlRegexMatch::Blocked;
+        }
+
+        let client = &mut self.clients[client];
+        client.query_count += 1;
+        if blocked {
+            client.blocked_count += 1;
+        }
+        *client
+            .over_time
+            .entry(over_time_slot(timestamp))
+            .or_default() += 1;
+
+        if status == FtlQueryStatus::Forward {
+            if let Some(upstream) = upstream {
+                self.upstreams[upstream].query_count += 1;
+                query.upstream_id = upstream as libc::c_int;
+            }
+        }
+
+        self.queries.push(query);
+        id
+    }
+
+    /// Set the reply type of a query, using the id returned when it was
+    /// added
+    pub fn reply_type(&mut self, query: usize, reply_type: FtlQueryReplyType) -> &mut Self {
+        self.queries[query - 1].reply_type = reply_type;
+        self
+    }
+
+    /// Set the privacy level a query was made with, using the id returned when
+    /// it was added
+    pub fn privacy_level(&mut self, query: usize, privacy_level: FtlPrivacyLevel) -> &mut Self {
+        self.queries[query - 1].privacy_level = privacy_level;
+        self
+    }
+
+    /// Set the response time of a query in units of 1/10 milliseconds, using
+    /// the id returned when it was added
+    pub fn response_time(&mut self, query: usize, response_time: u64) -> &mut Self {
+        self.queries[query - 1].response_time = response_time as libc::c_ulong;
+        self
+    }
+
+    /// Add `count` queries generated from the seed. The same seed always
+    /// generates the same queries. Domains are created as needed, and the
+    /// clients and upstreams are created by the first call and reused by later
+    /// calls.
+    pub fn random_queries(&mut self, count: usize, seed: u64) -> &mut Self {
+        const STATUSES: [FtlQueryStatus; 6] = [
+            FtlQueryStatus::Forward,
+            FtlQueryStatus::Forward,
+            FtlQueryStatus::Cache,
+            FtlQueryStatus::Gravity,
+            FtlQueryStatus::Blacklist,
+            FtlQueryStatus::Wildcard,
+        ];
+
+        if self.random_clients.is_empty() {
+            self.random_clients = (1..=16)
+                .map(|i| self.unnamed_client(&format!("10.0.{}.{}", i / 256, i % 256)))
+                .collect();
+            self.random_upstreams = (1..=2)
+                .map(|i| self.upstream(&format!("10.1.0.{}", i), &format!("upstream{}", i)))
+                .collect();
+        }
+
+        let mut rng = TestRng::new(seed);
+        let clients = self.random_clients.clone();
+        let upstreams = self.random_upstreams.clone();
+
+        // Spread the queries over a day, after any existing queries
+        let start = self
+            .queries
+            .last()
+            .map(|query| query.timestamp as usize)
+            .unwrap_or(1_000_000);
+
+        for i in 0..count {
+            let timestamp = start + i * (MAX_RANDOM_SPAN / count.max(1));
+            let query_type = FtlQueryType::variants()[rng.below(7)];
+            let domain = format!("domain{}.example.com", rng.below(256));
+            let client = clients[rng.below(clients.len())];
+            let status = STATUSES[rng.below(STATUSES.len())];
+            let upstream = upstreams[rng.below(upstreams.len())];
+
+            self.typed_query(
+                timestamp,
+                query_type,
+                &domain,
+                client,
+                status,
+                Some(upstream),
+            );
+        }
+
+        self
+    }
+
+    /// Build the `FtlMemory`
+    pub fn build(mut self) -> FtlMemory {
+        // Create the overTime slots, oldest first
+        let mut slots: BTreeMap<usize, TestOverTime> = BTreeMap::new();
+        for query in &self.queries {
+            let slot = slots
+                .entry(over_time_slot(query.timestamp as usize))
+                .or_default();
+
+            slot.total += 1;
+            slot.query_types[query.query_type as usize - 1] += 1;
+            match query.status {
+                FtlQueryStatus::Forward => slot.forwarded += 1,
+                FtlQueryStatus::Cache => slot.cached += 1,
+                _ if query.is_blocked() => slot.blocked += 1,
+                _ => (),
+            }
+        }
+
+        // FTL only keeps as many slots as fit in memory, and garbage collects
+        // the queries of older slots, so the queries can not span more slots
+        let span = match (slots.keys().next(), slots.keys().next_back()) {
+            (Some(first), Some(last)) => (last - first) / OVERTIME_INTERVAL + 1,
+            _ => 0,
+        };
+        assert!(
+            span <= OVERTIME_SLOTS,
+            "the queries span {} overTime slots, but FTL only keeps {}",
+            span,
+            OVERTIME_SLOTS
+        );
+
+        let slot_indexes: HashMap<usize, usize> = slots
+            .keys()
+            .enumerate()
+            .map(|(index, timestamp)| (*timestamp, index))
+            .collect();
+
+        for query in &mut self.queries {
+            query.time_index =
+                slot_indexes[&over_time_slot(query.timestamp as usize)] as libc::c_uint;
+        }
+
+        let over_time: Vec<FtlOverTime> = slots
+            .iter()
+            .map(|(timestamp, slot)| {
+                FtlOverTime::new(
+                    *timestamp,
+                    slot.total,
+                    slot.blocked,
+                    slot.cached,
+                    slot.forwarded,
+                    slot.query_types,
+                )
+            })
+            .collect();
+
+        let clients: Vec<FtlClient> = self
+            .clients
+            .iter()
+            .map(|client| {
+                let mut client_over_time = vec![0; slots.len()];
+                for (timestamp, count) in &client.over_time {
+                    if let Some(index) = slot_indexes.get(timestamp) {
+                        client_over_time[*index] = *count;
+                    }
+                }
+
+                FtlClient::new(
+                    client.query_count,
+                    client.blocked_count,
+                    client.ip_str_id,
+                    client.name_str_id,
+                )
+                .with_over_time(client_over_time)
+            })
+            .collect();
+
+        let domains: Vec<FtlDomain> = self
+            .domains
+            .iter()
+            .map(|domain| {
+                FtlDomain::new(
+                    domain.query_count,
+                    domain.blocked_count,
+                    domain.domain_str_id,
+                    domain.regex_match,
+                )
+            })
+            .collect();
+
+        let upstreams: Vec<FtlUpstream> = self
+            .upstreams
+            .iter()
+            .map(|upstream| {
+                FtlUpstream::new(
+                    upstream.query_count,
+                    0,
+                    upstream.ip_str_id,
+                    upstream.name_str_id,
+                )
+            })
+            .collect();
+
+        let mut counters = FtlCounters {
+            total_queries: self.queries.len() as libc::c_int,
+            total_clients: clients.len() as libc::c_int,
+            total_domains: domains.len() as libc::c_int,
+            total_upstreams: upstreams.len() as libc::c_int,
+            ..FtlCounters::default()
+        };
+
+        for query in &self.queries {
+            counters.query_type_counters[query.query_type as usize - 1] += 1;
+
+            match query.status {
+                FtlQueryStatus::Forward => counters.forwarded_queries += 1,
+                FtlQueryStatus::Cache => counters.cached_queries += 1,
+                FtlQueryStatus::Unknown => counters.unknown_queries += 1,
+                _ => (),
+            }
+
+            if query.is_blocked() {
+                counters.blocked_queries += 1;
+            } else {
+                match query.reply_type {
+                    FtlQueryReplyType::NODATA => counters.reply_count_nodata += 1,
+                    FtlQueryReplyType::NXDOMAIN => counters.reply_count_nxdomain += 1,
+                    FtlQueryReplyType::CNAME => counters.reply_count_cname += 1,
+                    FtlQueryReplyType::IP => counters.reply_count_ip += 1,
+                    FtlQueryReplyType::DOMAIN => counters.reply_count_domain += 1,
+                    _ => (),
+                }
+            }
+        }
+
+        FtlMemory::Test {
+            clients,
+            domains,
+            over_time,
+            upstreams,
+            queries: self.queries,
+            strings: self.strings,
+            counters,
+            settings: FtlSettings {
+                next_str_pos: self.next_str_pos as libc::c_uint,
+                ..FtlSettings::default()
+            },
+        }
+    }
+
+    /// Add a client to the client list
+    fn add_client(&mut self, ip: &str, name_str_id: Option<usize>) -> usize {
+        let ip_str_id = self.string(ip);
+
+        self.clients.push(TestClient {
+            ip_str_id,
+            name_str_id,
+            query_count: 0,
+            blocked_count: 0,
+            over_time: BTreeMap::new(),
+        });
+        self.clients.len() - 1
+    }
+
+    /// Get the id of the domain, adding it if it does not exist yet
+    fn domain(&mut self, domain: &str) -> usize {
+        if let Some(id) = self.domain_ids.get(domain) {
+            return *id;
+        }
+
+        let domain_str_id = self.string(domain);
+        self.domains.push(TestDomain {
+            domain_str_id,
+            query_count: 0,
+            blocked_count: 0,
+            regex_match: FtlRegexMatch::NotBlocked,
+        });
+
+        let id = self.domains.len() - 1;
+        self.domain_ids.insert(domain.to_owned(), id);
+        id
+    }
+
+    /// Get the id of the string, adding it to the string table if it does not
+    /// exist yet. Like in FTL, the id is the offset of the string in the
+    /// table, and each string is followed by a null terminator. Id 0 is
+    /// reserved for the empty string.
+    fn string(&mut self, string: &str) -> usize {
+        if string.is_empty() {
+            return 0;
+        }
+
+        if let Some(id) = self.string_ids.get(string) {
+            return *id;
+        }
+
+        let id = self.next_str_pos;
+        self.next_str_pos += string.len() + 1;
+        self.strings.insert(id, string.to_owned());
+        self.string_ids.insert(string.to_owned(), id);
+        id
+    }
+}
+
+/// The fixture used to test each privacy level. There are four queries, the
+/// last two blocked. The third query was made while FTL hid domains, and the
+/// fourth while FTL hid domains and clients, so FTL stored the placeholder
+/// domain and client for it.
+pub fn privacy_test_memory() -> FtlMemory {
+    let mut builder = TestFtlMemoryBuilder::new();
+    let laptop = builder.client("10.1.1.1", "laptop");
+    let phone = builder.unnamed_client("10.1.1.2");
+    let hidden = builder.unnamed_client(HIDDEN_CLIENT);
+    let upstream = builder.upstream("8.8.8.8", "google-public-dns-a.google.com");
+
+    builder.query(
+        1000,
+        "example.com",
+        laptop,
+        FtlQueryStatus::Forward,
+        Some(upstream),
+    );
+    builder.query(1001, "ads.com", laptop, FtlQueryStatus::Gravity, None);
+    let private = builder.query(1002, "tracker.com", phone, FtlQueryStatus::Blacklist, None);
+    builder.privacy_level(private, FtlPrivacyLevel::HideDomains);
+    let hidden_query = builder.query(1003, HIDDEN_DOMAIN, hidden, FtlQueryStatus::Gravity, None);
+    builder.privacy_level(hidden_query, FtlPrivacyLevel::HideDomainsAndClients);
+
+    builder.build()
+}
+
+/// The span of time (in seconds) that random queries are spread over
+const MAX_RANDOM_SPAN: usize = 24 * 3600;
+
+/// Get the timestamp of the overTime slot containing the timestamp. Like FTL,
+/// the slot timestamp is the middle of the interval.
+fn over_time_slot(timestamp: usize) -> usize {
+    timestamp - timestamp % OVERTIME_INTERVAL + OVERTIME_INTERVAL / 2
+}
+
+/// A small deterministic random number generator (xorshift64*), so test data
+/// can be reproduced from a seed without pulling in a dependency
+struct TestRng(u64);
+
+impl TestRng {
+    fn new(seed: u64) -> Self {
+        // Xorshift gets stuck on zero
+        TestRng(seed.max(1))
+    }
+
+    /// Get a random number in `0..bound`
+    fn below(&mut self, bound: usize) -> usize {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+
+        (self.0.wrapping_mul(0x2545_f491_4f6c_dd1d) >> 33) as usize % bound
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::TestFtlMemoryBuilder;
+    use crate::ftl::{FtlMemory, FtlQueryStatus, FtlStrings, OVERTIME_INTERVAL, OVERTIME_SLOTS};
+
+    /// Counters and per-item counts are derived from the queries
+    #[test]
+    fn derives_counts() {
+        let mut builder = TestFtlMemoryBuilder::new();
+        let laptop = builder.client("10.0.0.2", "laptop");
+        let phone = builder.unnamed_client("10.0.0.3");
+        let upstream = builder.upstream("8.8.8.8", "google-public-dns-a.google.com");
+
+        builder.query(
+            1000,
+            "example.com",
+            laptop,
+            FtlQueryStatus::Forward,
+            Some(upstream),
+        );
+        builder.query(
+            1001,
+            "ads.example.com",
+            laptop,
+            FtlQueryStatus::Gravity,
+            None,
+        );
+        builder.query(1700, "example.com", phone, FtlQueryStatus::Cache, None);
+
+        match builder.build() {
+            FtlMemory::Test {
+                clients,
+                domains,
+                over_time,
+                upstreams,
+                queries,
+                strings,
+                counters,
+                ..
+            } => {
+                assert_eq!(counters.total_queries, 3);
+                assert_eq!(counters.blocked_queries, 1);
+                assert_eq!(counters.cached_queries, 1);
+                assert_eq!(counters.forwarded_queries, 1);
+                assert_eq!(counters.total_clients, 2);
+                assert_eq!(counters.total_domains, 2);
+                assert_eq!(clients[laptop].query_count, 2);
+                assert_eq!(clients[laptop].blocked_count, 1);
+                assert_eq!(&clients[laptop].over_time[..2], &[2, 0]);
+                assert_eq!(&clients[phone].over_time[..2], &[0, 1]);
+                assert_eq!(domains[0].query_count, 2);
+                assert_eq!(upstreams[upstream].query_count, 1);
+                assert_eq!(over_time.len(), 2);
+                assert_eq!(over_time[0].timestamp, 900);
+                assert_eq!(over_time[0].blocked_queries, 1);
+                assert_eq!(queries[2].time_index, 1);
+                assert_eq!(strings.len(), 7);
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    /// The same seed generates the same queries
+    #[test]
+    fn random_queries_deterministic() {
+        let mut first = TestFtlMemoryBuilder::new();
+        first.random_queries(100, 42);
+        let mut second = TestFtlMemoryBuilder::new();
+        second.random_queries(100, 42);
+
+        match (first.build(), second.build()) {
+            (
+                FtlMemory::Test {
+                    queries: first_queries,
+                    counters: first_counters,
+                    ..
+                },
+                FtlMemory::Test {
+                    queries: second_queries,
+                    counters: second_counters,
+                    ..
+                },
+            ) => {
+                assert_eq!(first_queries.len(), 100);
+                assert_eq!(first_queries, second_queries);
+                assert_eq!(
+                    first_counters.blocked_queries,
+                    second_counters.blocked_queries
+                );
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    /// String ids are offsets into the string table, and the table ends where
+    /// the next string would be added
+    #[test]
+    fn string_offsets() {
+        let mut builder = TestFtlMemoryBuilder::new();
+        let client = builder.client("10.0.0.2", "laptop");
+        builder.query(1000, "example.com", client, FtlQueryStatus::Cache, None);
+
+        match builder.build() {
+            FtlMemory::Test {
+                strings, settings, ..
+            } => {
+                assert_eq!(strings[&1], "laptop");
+                assert_eq!(strings[&8], "10.0.0.2");
+                assert_eq!(strings[&17], "example.com");
+                assert_eq!(settings.next_str_pos, 29);
+                assert_eq!(FtlStrings::Test(&strings).len(), 29);
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    /// Later calls add queries for the clients and upstreams of the first call
+    #[test]
+    fn random_queries_reuse_clients() {
+        let mut builder = TestFtlMemoryBuilder::new();
+        builder.random_queries(1, 1).random_queries(1, 2);
+
+        match builder.build() {
+            FtlMemory::Test {
+                clients,
+                upstreams,
+                queries,
+                ..
+            } => {
+                assert_eq!(clients.len(), 16);
+                assert_eq!(upstreams.len(), 2);
+                assert_eq!(queries.len(), 2);
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    /// Queries older than the overTime slots FTL keeps are rejected
+    #[test]
+    #[should_panic(expected = "overTime slots")]
+    fn queries_outside_over_time() {
+        let mut builder = TestFtlMemoryBuilder::new();
+        let client = builder.unnamed_client("10.0.0.2");
+
+        builder.query(1000, "example.com", client, FtlQueryStatus::Cache, None);
+        builder.query(
+            1000 + OVERTIME_SLOTS * OVERTIME_INTERVAL,
+            "example.com",
+            client,
+            FtlQueryStatus::Cache,
+            None,
+        );
+        builder.build();
+    }
+}
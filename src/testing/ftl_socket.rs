@@ -0,0 +1,290 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Mock FTL Socket Server For Tests
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    io::{prelude::*, BufReader},
+    os::unix::net::{UnixListener, UnixStream},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+use tempfile::TempDir;
+
+/// A MessagePack frame which is cut off in the middle of a string (the header
+/// says 255 bytes follow, but only one does)
+const MALFORMED_FRAME: [u8; 3] = [0xd9, 0xff, b'x'];
+
+/// How the mock server responds to a single connection
+#[derive(Clone, Debug)]
+pub enum MockFtlResponse {
+    /// Write the data after the delay, then close the connection
+    Data(Vec<u8>, Duration),
+    /// Close the connection without writing anything
+    Drop,
+    /// Write a truncated MessagePack frame, then close the connection
+    Malformed,
+}
+
+/// The responses the mock server gives for each command. When a command has
+/// multiple responses, each connection uses the next one, and the last one is
+/// repeated once the others are used up. Unknown commands get their
+/// connection dropped.
+#[derive(Clone, Debug, Default)]
+pub struct FtlSocketScript {
+    responses: HashMap<String, VecDeque<MockFtlResponse>>,
+}
+
+impl FtlSocketScript {
+    /// Create an empty script
+    pub fn new() -> Self {
+        FtlSocketScript::default()
+    }
+
+    /// Respond to the command with the data
+    pub fn respond(self, command: &str, data: Vec<u8>) -> Self {
+        self.respond_after(command, Duration::from_secs(0), data)
+    }
+
+    /// Respond to the command with the data after waiting for the delay
+    pub fn respond_after(self, command: &str, delay: Duration, data: Vec<u8>) -> Self {
+        self.response(command, MockFtlResponse::Data(data, delay))
+    }
+
+    /// Close the connection without responding to the command
+    pub fn drop_connection(self, command: &str) -> Self {
+        self.response(command, MockFtlResponse::Drop)
+    }
+
+    /// Respond to the command with a malformed frame
+    pub fn malformed(self, command: &str) -> Self {
+        self.response(command, MockFtlResponse::Malformed)
+    }
+
+    /// Add the next response for the command
+    pub fn response(mut self, command: &str, response: MockFtlResponse) -> Self {
+        self.responses
+            .entry(command.to_owned())
+            .or_default()
+            .push_back(response);
+        self
+    }
+
+    /// Get the response for the next connection which sent the command
+    fn next_response(&mut self, command: &str) -> Option<MockFtlResponse> {
+        let responses = self.responses.get_mut(command)?;
+
+        if responses.len() > 1 {
+            responses.pop_front()
+        } else {
+            responses.front().cloned()
+        }
+    }
+}
+
+/// A fake FTL which listens on a temporary Unix socket and responds according
+/// to a `FtlSocketScript`. The server is stopped when it is dropped.
+pub struct MockFtlServer {
+    socket_dir: TempDir,
+    received_commands: Arc<Mutex<Vec<String>>>,
+    stopped: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl MockFtlServer {
+    /// Start listening for connections
+    pub fn start(script: FtlSocketScript) -> Self {
+        let socket_dir = TempDir::new().unwrap();
+        let listener = UnixListener::bind(socket_dir.path().join("FTL.sock")).unwrap();
+        let received_commands = Arc::new(Mutex::new(Vec::new()));
+        let stopped = Arc::new(AtomicBool::new(false));
+
+        let thread = {
+            let script = Arc::new(Mutex::new(script));
+            let received_commands = Arc::clone(&received_commands);
+            let stopped = Arc::clone(&stopped);
+
+            thread::spawn(move || {
+                for stream in listener.incoming() {
+                    if stopped.load(Ordering::SeqCst) {
+                        break;
+                    }
+
+                    let stream = match stream {
+                        Ok(stream) => stream,
+                        Err(_) => continue,
+                    };
+                    let script = Arc::clone(&script);
+                    let received_commands = Arc::clone(&received_commands);
+
+                    // Handle each connection separately so delayed responses
+                    // do not hold up other connections
+                    thread::spawn(move || handle_connection(stream, &script, &received_commands));
+                }
+            })
+        };
+
+        MockFtlServer {
+            socket_dir,
+            received_commands,
+            stopped,
+            thread: Some(thread),
+        }
+    }
+
+    /// The path of the server's socket
+    pub fn path(&self) -> PathBuf {
+        self.socket_dir.path().join("FTL.sock")
+    }
+
+    /// The commands the server has received so far, in order
+    pub fn received_commands(&self) -> Vec<String> {
+        self.received_commands.lock().unwrap().clone()
+    }
+}
+
+impl Drop for MockFtlServer {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::SeqCst);
+
+        // Wake up the listener so it sees that it should stop
+        let _ = UnixStream::connect(self.path());
+
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Read the command from the connection and write the scripted response
+fn handle_connection(
+    stream: UnixStream,
+    script: &Mutex<FtlSocketScript>,
+    received_commands: &Mutex<Vec<String>>,
+) {
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+
+    if reader.read_line(&mut line).is_err() {
+        return;
+    }
+
+    // Commands are sent as ">command\n"
+    let command = line.trim_end().trim_start_matches('>').to_owned();
+    received_commands.lock().unwrap().push(command.clone());
+
+    let response = script.lock().unwrap().next_response(&command);
+    let mut stream = reader.into_inner();
+
+    match response {
+        Some(MockFtlResponse::Data(data, delay)) => {
+            thread::sleep(delay);
+            let _ = stream.write_all(&data);
+        }
+        Some(MockFtlResponse::Malformed) => {
+            let _ = stream.write_all(&MALFORMED_FRAME);
+        }
+        Some(MockFtlResponse::Drop) | None => (),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{FtlSocketScript, MockFtlServer};
+    use crate::{ftl::FtlConnectionType, testing::write_eom, util::ErrorKind};
+    use rmp::encode;
+    use std::time::{Duration, Instant};
+
+    /// Scripted data is returned for the command, and the command is recorded
+    #[test]
+    fn responds_with_data() {
+        let mut data = Vec::new();
+        encode::write_i32(&mut data, 42).unwrap();
+        write_eom(&mut data);
+
+        let server = MockFtlServer::start(FtlSocketScript::new().respond("stats", data));
+        let connection_type = FtlConnectionType::TestSocket(server.path());
+
+        let mut con = connection_type.connect("stats").unwrap();
+        assert_eq!(con.read_i32().unwrap(), 42);
+        con.expect_eom().unwrap();
+
+        assert_eq!(server.received_commands(), vec!["stats".to_owned()]);
+    }
+
+    /// Responses for a command are used in order, repeating the last one
+    #[test]
+    fn responses_in_order() {
+        let mut data = Vec::new();
+        write_eom(&mut data);
+
+        let server = MockFtlServer::start(
+            FtlSocketScript::new()
+                .drop_connection("stats")
+                .respond("stats", data),
+        );
+        let connection_type = FtlConnectionType::TestSocket(server.path());
+
+        let error = connection_type
+            .connect("stats")
+            .unwrap()
+            .expect_eom()
+            .unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::FtlReadError);
+
+        for _ in 0..2 {
+            connection_type
+                .connect("stats")
+                .unwrap()
+                .expect_eom()
+                .unwrap();
+        }
+
+        assert_eq!(server.received_commands().len(), 3);
+    }
+
+    /// A malformed frame results in a read error
+    #[test]
+    fn malformed_frame() {
+        let server = MockFtlServer::start(FtlSocketScript::new().malformed("version"));
+        let connection_type = FtlConnectionType::TestSocket(server.path());
+
+        let mut buffer = [0u8; 4096];
+        let mut con = connection_type.connect("version").unwrap();
+        let error = con.read_str(&mut buffer).unwrap_err();
+
+        assert_eq!(error.kind(), ErrorKind::FtlReadError);
+    }
+
+    /// Delayed responses are not sent until the delay has passed
+    #[test]
+    fn delayed_response() {
+        let mut data = Vec::new();
+        write_eom(&mut data);
+
+        let delay = Duration::from_millis(100);
+        let server =
+            MockFtlServer::start(FtlSocketScript::new().respond_after("stats", delay, data));
+        let connection_type = FtlConnectionType::TestSocket(server.path());
+
+        let start = Instant::now();
+        connection_type
+            .connect("stats")
+            .unwrap()
+            .expect_eom()
+            .unwrap();
+
+        assert!(start.elapsed() >= delay);
+    }
+}
@@ -0,0 +1,432 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// JSON Matching For Tests
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use serde_json::Value;
+use std::fmt::{self, Display};
+
+/// How arrays are compared against each other
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ArrayMatching {
+    /// Each expected element must match the actual element at the same
+    /// index, and both arrays must have the same length
+    Ordered,
+    /// Each expected element must match a distinct actual element, in any
+    /// order. When only checking containment, the actual array may have extra
+    /// elements.
+    Unordered,
+}
+
+/// Compares an expected JSON document to an actual JSON document, either
+/// exactly or as a subset, skipping any ignored fields
+#[derive(Clone, Debug)]
+pub struct JsonMatcher {
+    contains: bool,
+    array_matching: ArrayMatching,
+    ignored_fields: Vec<Vec<String>>,
+}
+
+impl Default for JsonMatcher {
+    fn default() -> Self {
+        JsonMatcher {
+            contains: false,
+            array_matching: ArrayMatching::Ordered,
+            ignored_fields: Vec::new(),
+        }
+    }
+}
+
+impl JsonMatcher {
+    /// If true, the expected JSON only needs to be a subset of the actual
+    /// JSON. Otherwise the documents must be equal (minus ignored fields).
+    pub fn contains(mut self, contains: bool) -> Self {
+        self.contains = contains;
+        self
+    }
+
+    /// Set how arrays are compared
+    pub fn array_matching(mut self, array_matching: ArrayMatching) -> Self {
+        self.array_matching = array_matching;
+        self
+    }
+
+    /// Ignore the field at the path. Path segments are separated by dots, and
+    /// a `*` segment matches any object key or array index. For example,
+    /// `data.*.timestamp` ignores the timestamp of every item in `data`.
+    pub fn ignore_field(mut self, path: &str) -> Self {
+        self.ignored_fields
+            .push(path.split('.').map(ToOwned::to_owned).collect());
+        self
+    }
+
+    /// Find the first place where the actual JSON does not match the expected
+    /// JSON. If the documents match, `None` is returned.
+    pub fn find_mismatch(&self, expected: &Value, actual: &Value) -> Option<JsonMismatch> {
+        self.compare(expected, actual, &mut Vec::new())
+    }
+
+    /// Check if the path is covered by one of the ignored field patterns
+    fn is_ignored(&self, path: &[String]) -> bool {
+        self.ignored_fields.iter().any(|pattern| {
+            pattern.len() == path.len()
+                && pattern
+                    .iter()
+                    .zip(path)
+                    .all(|(pattern_segment, segment)| {
+                        pattern_segment == "*" || pattern_segment == segment
+                    })
+        })
+    }
+
+    fn compare(
+        &self,
+        expected: &Value,
+        actual: &Value,
+        path: &mut Vec<String>,
+    ) -> Option<JsonMismatch> {
+        if self.is_ignored(path) {
+            return None;
+        }
+
+        match (expected, actual) {
+            (Value::Object(expected_map), Value::Object(actual_map)) => {
+                for (key, expected_value) in expected_map {
+                    path.push(key.clone());
+                    let mismatch = match actual_map.get(key) {
+                        Some(actual_value) => self.compare(expected_value, actual_value, path),
+                        None if self.is_ignored(path) => None,
+                        None => Some(JsonMismatch::new(
+                            path,
+                            "missing field",
+                            Some(expected_value),
+                            None,
+                        )),
+                    };
+                    path.pop();
+
+                    if mismatch.is_some() {
+                        return mismatch;
+                    }
+                }
+
+                // Extra fields are only a problem when matching exactly
+                if !self.contains {
+                    for (key, actual_value) in actual_map {
+                        if expected_map.contains_key(key) {
+                            continue;
+                        }
+
+                        path.push(key.clone());
+                        let mismatch = if self.is_ignored(path) {
+                            None
+                        } else {
+                            Some(JsonMismatch::new(
+                                path,
+                                "unexpected field",
+                                None,
+                                Some(actual_value),
+                            ))
+                        };
+                        path.pop();
+
+                        if mismatch.is_some() {
+                            return mismatch;
+                        }
+                    }
+                }
+
+                None
+            }
+            (Value::Array(expected_items), Value::Array(actual_items)) => {
+                let length_must_match =
+                    !self.contains || self.array_matching == ArrayMatching::Ordered;
+
+                if length_must_match && expected_items.len() != actual_items.len() {
+                    return Some(JsonMismatch::new(
+                        path,
+                        &format!(
+                            "expected {} array items, found {}",
+                            expected_items.len(),
+                            actual_items.len()
+                        ),
+                        Some(expected),
+                        Some(actual),
+                    ));
+                }
+
+                match self.array_matching {
+                    ArrayMatching::Ordered => {
+                        self.compare_ordered(expected_items, actual_items, path)
+                    }
+                    ArrayMatching::Unordered => {
+                        self.compare_unordered(expected_items, actual_items, path)
+                    }
+                }
+            }
+            _ => {
+                if expected == actual {
+                    None
+                } else {
+                    Some(JsonMismatch::new(
+                        path,
+                        "values differ",
+                        Some(expected),
+                        Some(actual),
+                    ))
+                }
+            }
+        }
+    }
+
+    /// Compare array items pairwise
+    fn compare_ordered(
+        &self,
+        expected_items: &[Value],
+        actual_items: &[Value],
+        path: &mut Vec<String>,
+    ) -> Option<JsonMismatch> {
+        for (i, (expected_item, actual_item)) in
+            expected_items.iter().zip(actual_items).enumerate()
+        {
+            path.push(i.to_string());
+            let mismatch = self.compare(expected_item, actual_item, path);
+            path.pop();
+
+            if mismatch.is_some() {
+                return mismatch;
+            }
+        }
+
+        None
+    }
+
+    /// Match each expected item to a distinct actual item. An expected item
+    /// can match more than one actual item (when only checking containment),
+    /// so a maximum bipartite matching is found instead of taking the first
+    /// match of each item.
+    fn compare_unordered(
+        &self,
+        expected_items: &[Value],
+        actual_items: &[Value],
+        path: &mut Vec<String>,
+    ) -> Option<JsonMismatch> {
+        // The actual items which each expected item matches
+        let candidates: Vec<Vec<usize>> = expected_items
+            .iter()
+            .enumerate()
+            .map(|(i, expected_item)| {
+                path.push(i.to_string());
+                let candidates = actual_items
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, actual_item)| {
+                        self.compare(expected_item, actual_item, path).is_none()
+                    })
+                    .map(|(j, _)| j)
+                    .collect();
+                path.pop();
+
+                candidates
+            })
+            .collect();
+        let mut matched_by = vec![None; actual_items.len()];
+
+        for (i, expected_item) in expected_items.iter().enumerate() {
+            let mut visited = vec![false; actual_items.len()];
+
+            if !assign_item(i, &candidates, &mut matched_by, &mut visited) {
+                path.push(i.to_string());
+                let mismatch =
+                    JsonMismatch::new(path, "no matching array item", Some(expected_item), None);
+                path.pop();
+
+                return Some(mismatch);
+            }
+        }
+
+        None
+    }
+}
+
+/// Find an actual item for the expected item, moving the items matched so far
+/// to their other candidates if needed (an augmenting path). `matched_by`
+/// holds the expected item matched to each actual item.
+fn assign_item(
+    expected: usize,
+    candidates: &[Vec<usize>],
+    matched_by: &mut [Option<usize>],
+    visited: &mut [bool],
+) -> bool {
+    for &actual in &candidates[expected] {
+        if visited[actual] {
+            continue;
+        }
+        visited[actual] = true;
+
+        let available = match matched_by[actual] {
+            Some(other) => assign_item(other, candidates, matched_by, visited),
+            None => true,
+        };
+
+        if available {
+            matched_by[actual] = Some(expected);
+            return true;
+        }
+    }
+
+    false
+}
+
+/// Describes the first place where two JSON documents differ
+#[derive(Debug, PartialEq)]
+pub struct JsonMismatch {
+    pub path: String,
+    pub reason: String,
+    pub expected: Option<Value>,
+    pub actual: Option<Value>,
+}
+
+impl JsonMismatch {
+    fn new(
+        path: &[String],
+        reason: &str,
+        expected: Option<&Value>,
+        actual: Option<&Value>,
+    ) -> JsonMismatch {
+        JsonMismatch {
+            path: if path.is_empty() {
+                "<root>".to_owned()
+            } else {
+                path.join(".")
+            },
+            reason: reason.to_owned(),
+            expected: expected.cloned(),
+            actual: actual.cloned(),
+        }
+    }
+}
+
+impl Display for JsonMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let format_value = |value: &Option<Value>| match value {
+            Some(value) => value.to_string(),
+            None => "<missing>".to_owned(),
+        };
+
+        writeln!(f, "JSON mismatch at `{}`: {}", self.path, self.reason)?;
+        writeln!(f, "  expected: {}", format_value(&self.expected))?;
+        write!(f, "  actual:   {}", format_value(&self.actual))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ArrayMatching, JsonMatcher};
+
+    /// Equal documents match exactly
+    #[test]
+    fn exact_match() {
+        let value = json!({ "data": [1, 2, { "a": "b" }] });
+
+        assert_eq!(JsonMatcher::default().find_mismatch(&value, &value), None);
+    }
+
+    /// Extra fields fail an exact match but pass a contains match
+    #[test]
+    fn extra_fields() {
+        let expected = json!({ "status": "success" });
+        let actual = json!({ "status": "success", "id": 5 });
+
+        let mismatch = JsonMatcher::default()
+            .find_mismatch(&expected, &actual)
+            .unwrap();
+        assert_eq!(mismatch.path, "id");
+        assert_eq!(mismatch.reason, "unexpected field");
+
+        assert_eq!(
+            JsonMatcher::default()
+                .contains(true)
+                .find_mismatch(&expected, &actual),
+            None
+        );
+    }
+
+    /// The mismatch reports the path of the first differing value
+    #[test]
+    fn mismatch_path() {
+        let expected = json!({ "data": [{ "count": 1 }, { "count": 2 }] });
+        let actual = json!({ "data": [{ "count": 1 }, { "count": 3 }] });
+
+        let mismatch = JsonMatcher::default()
+            .find_mismatch(&expected, &actual)
+            .unwrap();
+
+        assert_eq!(mismatch.path, "data.1.count");
+        assert_eq!(mismatch.expected, Some(json!(2)));
+        assert_eq!(mismatch.actual, Some(json!(3)));
+    }
+
+    /// Ignored fields are skipped, including wildcard segments
+    #[test]
+    fn ignored_fields() {
+        let expected = json!({ "data": [{ "domain": "a.com" }, { "domain": "b.com" }] });
+        let actual = json!({
+            "data": [
+                { "domain": "a.com", "timestamp": 1 },
+                { "domain": "b.com", "timestamp": 2 }
+            ]
+        });
+
+        assert_eq!(
+            JsonMatcher::default()
+                .ignore_field("data.*.timestamp")
+                .find_mismatch(&expected, &actual),
+            None
+        );
+    }
+
+    /// Ordered arrays must line up, unordered arrays can be in any order
+    #[test]
+    fn array_matching() {
+        let expected = json!(["b.com", "a.com"]);
+        let actual = json!(["a.com", "b.com", "c.com"]);
+
+        let ordered = JsonMatcher::default().contains(true);
+        let mismatch = ordered.find_mismatch(&expected, &actual).unwrap();
+        assert_eq!(mismatch.path, "<root>");
+
+        let unordered = ordered.array_matching(ArrayMatching::Unordered);
+        assert_eq!(unordered.find_mismatch(&expected, &actual), None);
+
+        let mismatch = unordered
+            .find_mismatch(&json!(["d.com"]), &actual)
+            .unwrap();
+        assert_eq!(mismatch.path, "0");
+        assert_eq!(mismatch.reason, "no matching array item");
+    }
+
+    /// An expected item which matches several actual items does not take the
+    /// only match of a later item
+    #[test]
+    fn unordered_overlapping_items() {
+        let expected = json!([{ "a": 1 }, { "a": 1, "b": 2 }]);
+        let actual = json!([{ "a": 1, "b": 2 }, { "a": 1 }]);
+
+        let unordered = JsonMatcher::default()
+            .contains(true)
+            .array_matching(ArrayMatching::Unordered);
+        assert_eq!(unordered.find_mismatch(&expected, &actual), None);
+
+        let mismatch = unordered
+            .find_mismatch(&json!([{ "a": 1, "b": 2 }, { "b": 2 }]), &actual)
+            .unwrap();
+        assert_eq!(mismatch.path, "1");
+        assert_eq!(mismatch.reason, "no matching array item");
+    }
+}
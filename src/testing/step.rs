@@ -0,0 +1,153 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Test Request Steps
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::testing::{ArrayMatching, JsonMatcher};
+use rocket::{
+    http::{ContentType, Header, Method, Status},
+    local::blocking::Client,
+};
+
+/// A single request to make during a test, along with the expected response
+pub struct TestStep {
+    endpoint: String,
+    method: Method,
+    headers: Vec<Header<'static>>,
+    should_auth: bool,
+    body_data: Option<serde_json::Value>,
+    expected_json: serde_json::Value,
+    json_matcher: JsonMatcher,
+    expected_status: Status,
+}
+
+impl TestStep {
+    pub fn new() -> TestStep {
+        TestStep {
+            endpoint: "".to_owned(),
+            method: Method::Get,
+            headers: Vec::new(),
+            should_auth: true,
+            body_data: None,
+            expected_json: json!({
+                "data": [],
+                "errors": []
+            }),
+            json_matcher: JsonMatcher::default(),
+            expected_status: Status::Ok,
+        }
+    }
+
+    pub fn endpoint(mut self, endpoint: &str) -> Self {
+        self.endpoint = endpoint.to_owned();
+        self
+    }
+
+    pub fn method(mut self, method: Method) -> Self {
+        self.method = method;
+        self
+    }
+
+    pub fn header<H: Into<Header<'static>>>(mut self, header: H) -> Self {
+        self.headers.push(header.into());
+        self
+    }
+
+    pub fn should_auth(mut self, should_auth: bool) -> Self {
+        self.should_auth = should_auth;
+        self
+    }
+
+    pub fn body<T: Into<serde_json::Value>>(mut self, body: T) -> Self {
+        self.body_data = Some(body.into());
+        self
+    }
+
+    pub fn expect_json<T: Into<serde_json::Value>>(mut self, expected_json: T) -> Self {
+        self.expected_json = expected_json.into();
+        self.json_matcher = self.json_matcher.contains(false);
+        self
+    }
+
+    /// See `TestBuilder::expect_json_contains`
+    pub fn expect_json_contains<T: Into<serde_json::Value>>(mut self, expected_json: T) -> Self {
+        self.expected_json = expected_json.into();
+        self.json_matcher = self.json_matcher.contains(true);
+        self
+    }
+
+    /// See `TestBuilder::ignore_fields`
+    pub fn ignore_fields(mut self, paths: &[&str]) -> Self {
+        for path in paths {
+            self.json_matcher = self.json_matcher.ignore_field(path);
+        }
+        self
+    }
+
+    /// See `TestBuilder::array_matching`
+    pub fn array_matching(mut self, array_matching: ArrayMatching) -> Self {
+        self.json_matcher = self.json_matcher.array_matching(array_matching);
+        self
+    }
+
+    pub fn expect_status(mut self, status: Status) -> Self {
+        self.expected_status = status;
+        self
+    }
+
+    /// Send the request and check the response. The parsed response body is
+    /// returned so later steps can use it.
+    pub(super) fn run(self, client: &Client) -> serde_json::Value {
+        // Create the request
+        let mut request = client.req(self.method, self.endpoint);
+
+        // Add the authentication header
+        if self.should_auth {
+            request.add_header(Header::new("X-Pi-hole-Authenticate", "test_key"));
+        }
+
+        // Add the rest of the headers
+        for header in self.headers {
+            request.add_header(header);
+        }
+
+        // Set the body data if necessary
+        if let Some(data) = self.body_data {
+            request.add_header(ContentType::JSON);
+            request.set_body(serde_json::to_vec(&data).unwrap());
+        }
+
+        // Dispatch the request
+        println!("{:#?}", request);
+        let response = request.dispatch();
+        println!("\nResponse:\n{:?}", response);
+
+        // Check the status
+        assert_eq!(self.expected_status, response.status());
+
+        // Check that something was returned
+        let body = response.into_string();
+        assert!(body.is_some());
+
+        let body_str = body.unwrap();
+        println!("Body:\n{}", body_str);
+
+        // Check that it is correct JSON
+        let parsed: serde_json::Value = serde_json::from_str(&body_str).unwrap();
+
+        // Check that it matches the expected JSON
+        if let Some(mismatch) = self
+            .json_matcher
+            .find_mismatch(&self.expected_json, &parsed)
+        {
+            panic!("{}", mismatch);
+        }
+
+        parsed
+    }
+}
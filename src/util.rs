@@ -113,8 +113,32 @@ pub enum ErrorKind {
     NotFound,
     #[fail(display = "Item already exists")]
     AlreadyExists,
+    #[fail(display = "{} is already on the {}", _0, _1)]
+    DomainAlreadyExists(String, String),
+    #[fail(display = "{} is on the {}, use force to move it", _0, _1)]
+    DomainOnOppositeList(String, String),
     #[fail(display = "Invalid domain")]
     InvalidDomain,
+    #[fail(display = "Invalid domain {}: {}", _0, _1)]
+    InvalidHostname(String, String),
+    #[fail(display = "Invalid internationalized domain {}", _0)]
+    InvalidIdn(String),
+    #[fail(display = "Invalid regex {}: {}", _0, _1)]
+    InvalidRegex(String, String),
+    #[fail(display = "Invalid adlist address {}", _0)]
+    InvalidAdlist(String),
+    #[fail(display = "Group {} still has members", _0)]
+    GroupNotEmpty(i32),
+    #[fail(display = "Group {} does not exist", _0)]
+    GroupNotFound(i32),
+    #[fail(display = "Invalid value {} for parameter {}", _1, _0)]
+    InvalidParameter(String, String, Vec<String>),
+    #[fail(display = "Invalid cursor {}", _0)]
+    InvalidCursor(String),
+    #[fail(display = "Interval {} is too small for the time range", _0)]
+    InvalidInterval(u64),
+    #[fail(display = "Time range is longer than {} seconds", _0)]
+    InvalidTimeRange(u64),
     #[fail(display = "Bad request")]
     BadRequest,
     #[fail(display = "Unauthorized")]
@@ -127,12 +151,22 @@ pub enum ErrorKind {
     ConfigParsingError,
     #[fail(display = "Invalid setting value")]
     InvalidSettingValue,
+    #[fail(display = "Invalid upstream {} at index {}: {}", _1, _0, _2)]
+    InvalidUpstream(usize, String, String),
+    #[fail(display = "Invalid CNAME record for {}: {}", _0, _1)]
+    InvalidCname(String, String),
+    #[fail(display = "{} is already assigned to a static lease", _0)]
+    LeaseAlreadyExists(String),
     #[fail(display = "Failed to restart the DNS server")]
     RestartDnsError,
     #[fail(display = "Failed to reload the DNS server")]
     ReloadDnsError,
     #[fail(display = "Error generating the dnsmasq config")]
     DnsmasqConfigWrite,
+    #[fail(display = "The dnsmasq config is not managed by Pi-hole")]
+    DnsmasqConfigUnmanaged,
+    #[fail(display = "Failed to flush the query log")]
+    FlushLogError,
     /// `shmem::Error` does not implement `std::error::Error`, so we can not use
     /// `.context()` on a `Result<T, shmem::Error>`. It also does not implement
     /// `Eq` or `PartialEq`, so the best we can do is have the error message
@@ -144,10 +178,14 @@ pub enum ErrorKind {
     #[fail(display = "Failed to lock shared memory")]
     SharedMemoryLock,
     #[fail(
-        display = "Incompatible version of shared memory. Found {}, expected {}",
+        display = "FTL shared memory version {} is not supported, expected {}",
         _0, _1
     )]
     SharedMemoryVersion(usize, usize),
+    #[fail(display = "Shared memory was modified while it was being read")]
+    SharedMemoryInconsistent,
+    #[fail(display = "Failed to deliver webhook to {}: {}", _0, _1)]
+    WebhookDelivery(String, String),
     #[fail(display = "Error while interacting with the FTL database")]
     FtlDatabase,
     #[fail(display = "Error while interacting with the Gravity database")]
@@ -223,21 +261,39 @@ impl ErrorKind {
             ErrorKind::FtlReadError => "ftl_read_error",
             ErrorKind::FtlEomError => "ftl_eom_error",
             ErrorKind::NotFound => "not_found",
-            ErrorKind::AlreadyExists => "already_exists",
-            ErrorKind::InvalidDomain => "invalid_domain",
+            ErrorKind::AlreadyExists
+            | ErrorKind::DomainAlreadyExists(_, _)
+            | ErrorKind::LeaseAlreadyExists(_) => "already_exists",
+            ErrorKind::DomainOnOppositeList(_, _) => "domain_conflict",
+            ErrorKind::InvalidDomain | ErrorKind::InvalidHostname(_, _) => "invalid_domain",
+            ErrorKind::InvalidIdn(_) => "invalid_idn",
+            ErrorKind::InvalidRegex(_, _) => "invalid_regex",
+            ErrorKind::InvalidAdlist(_) => "invalid_adlist",
+            ErrorKind::GroupNotEmpty(_) => "group_not_empty",
+            ErrorKind::GroupNotFound(_) => "group_not_found",
+            ErrorKind::InvalidParameter(_, _, _) => "invalid_parameter",
+            ErrorKind::InvalidCursor(_) => "invalid_cursor",
+            ErrorKind::InvalidInterval(_) => "invalid_interval",
+            ErrorKind::InvalidTimeRange(_) => "invalid_time_range",
             ErrorKind::BadRequest => "bad_request",
             ErrorKind::Unauthorized => "unauthorized",
             ErrorKind::FileRead(_) => "file_read",
             ErrorKind::FileWrite(_) => "file_write",
             ErrorKind::ConfigParsingError => "config_parsing_error",
             ErrorKind::InvalidSettingValue => "invalid_setting_value",
+            ErrorKind::InvalidUpstream(_, _, _) => "invalid_upstream",
+            ErrorKind::InvalidCname(_, _) => "invalid_cname",
             ErrorKind::RestartDnsError => "restart_dns_error",
             ErrorKind::ReloadDnsError => "reload_dns_error",
             ErrorKind::DnsmasqConfigWrite => "dnsmasq_config_write",
+            ErrorKind::DnsmasqConfigUnmanaged => "dnsmasq_config_unmanaged",
+            ErrorKind::FlushLogError => "flush_log_error",
             ErrorKind::SharedMemoryOpen(_) => "shared_memory_open",
             ErrorKind::SharedMemoryRead => "shared_memory_read",
             ErrorKind::SharedMemoryLock => "shared_memory_lock",
             ErrorKind::SharedMemoryVersion(_, _) => "shared_memory_version",
+            ErrorKind::SharedMemoryInconsistent => "shared_memory_inconsistent",
+            ErrorKind::WebhookDelivery(_, _) => "webhook_delivery",
             ErrorKind::FtlDatabase => "ftl_database",
             ErrorKind::GravityDatabase => "gravity_database",
         }
@@ -247,11 +303,28 @@ impl ErrorKind {
     pub fn status(&self) -> Status {
         match self {
             ErrorKind::NotFound => Status::NotFound,
-            ErrorKind::AlreadyExists => Status::Conflict,
-            ErrorKind::InvalidDomain | ErrorKind::BadRequest | ErrorKind::InvalidSettingValue => {
-                Status::BadRequest
-            }
+            ErrorKind::AlreadyExists
+            | ErrorKind::DomainAlreadyExists(_, _)
+            | ErrorKind::DomainOnOppositeList(_, _)
+            | ErrorKind::GroupNotEmpty(_)
+            | ErrorKind::DnsmasqConfigUnmanaged
+            | ErrorKind::LeaseAlreadyExists(_) => Status::Conflict,
+            ErrorKind::InvalidDomain
+            | ErrorKind::InvalidHostname(_, _)
+            | ErrorKind::InvalidIdn(_)
+            | ErrorKind::InvalidRegex(_, _)
+            | ErrorKind::InvalidAdlist(_)
+            | ErrorKind::GroupNotFound(_)
+            | ErrorKind::InvalidParameter(_, _, _)
+            | ErrorKind::InvalidCursor(_)
+            | ErrorKind::InvalidInterval(_)
+            | ErrorKind::InvalidTimeRange(_)
+            | ErrorKind::BadRequest
+            | ErrorKind::InvalidSettingValue
+            | ErrorKind::InvalidUpstream(_, _, _)
+            | ErrorKind::InvalidCname(_, _) => Status::BadRequest,
             ErrorKind::Unauthorized => Status::Unauthorized,
+            ErrorKind::SharedMemoryInconsistent => Status::ServiceUnavailable,
             ErrorKind::Unknown
             | ErrorKind::GravityError
             | ErrorKind::FtlConnectionFail
@@ -263,10 +336,12 @@ impl ErrorKind {
             | ErrorKind::RestartDnsError
             | ErrorKind::ReloadDnsError
             | ErrorKind::DnsmasqConfigWrite
+            | ErrorKind::FlushLogError
             | ErrorKind::SharedMemoryOpen(_)
             | ErrorKind::SharedMemoryRead
             | ErrorKind::SharedMemoryLock
             | ErrorKind::SharedMemoryVersion(_, _)
+            | ErrorKind::WebhookDelivery(_, _)
             | ErrorKind::FtlDatabase
             | ErrorKind::GravityDatabase => Status::InternalServerError,
         }
@@ -277,6 +352,42 @@ impl ErrorKind {
         match self {
             ErrorKind::FileRead(file) => Some(json!({ "file": file })),
             ErrorKind::FileWrite(file) => Some(json!({ "file": file })),
+            ErrorKind::DomainAlreadyExists(domain, list)
+            | ErrorKind::DomainOnOppositeList(domain, list) => Some(json!({
+                "domain": domain,
+                "list": list
+            })),
+            ErrorKind::LeaseAlreadyExists(assigned) => Some(json!({ "assigned": assigned })),
+            ErrorKind::InvalidHostname(domain, reason) => Some(json!({
+                "domain": domain,
+                "reason": reason
+            })),
+            ErrorKind::InvalidIdn(domain) => Some(json!({ "domain": domain })),
+            ErrorKind::InvalidRegex(pattern, reason) => Some(json!({
+                "pattern": pattern,
+                "reason": reason
+            })),
+            ErrorKind::InvalidAdlist(address) => Some(json!({ "address": address })),
+            ErrorKind::GroupNotEmpty(id) | ErrorKind::GroupNotFound(id) => {
+                Some(json!({ "id": id }))
+            }
+            ErrorKind::InvalidParameter(parameter, value, valid) => Some(json!({
+                "parameter": parameter,
+                "value": value,
+                "valid": valid
+            })),
+            ErrorKind::InvalidCursor(cursor) => Some(json!({ "cursor": cursor })),
+            ErrorKind::InvalidInterval(interval) => Some(json!({ "interval": interval })),
+            ErrorKind::InvalidTimeRange(max_range) => Some(json!({ "max_range": max_range })),
+            ErrorKind::InvalidUpstream(index, upstream, reason) => Some(json!({
+                "index": index,
+                "upstream": upstream,
+                "reason": reason
+            })),
+            ErrorKind::InvalidCname(domain, reason) => Some(json!({
+                "domain": domain,
+                "reason": reason
+            })),
             _ => None,
         }
     }
@@ -174,6 +174,45 @@ UPDATE info
 SET value = 2
 WHERE property = 'version';
 
+-- Update to v3
+
+CREATE TABLE regex_whitelist
+(
+    id            INTEGER PRIMARY KEY AUTOINCREMENT,
+    domain        TEXT UNIQUE NOT NULL,
+    enabled       BOOLEAN     NOT NULL DEFAULT 1,
+    date_added    INTEGER     NOT NULL DEFAULT (cast(strftime('%s', 'now') as int)),
+    date_modified INTEGER     NOT NULL DEFAULT (cast(strftime('%s', 'now') as int)),
+    comment       TEXT
+);
+
+CREATE TABLE regex_whitelist_by_group
+(
+    regex_whitelist_id INTEGER NOT NULL REFERENCES regex_whitelist (id),
+    group_id           INTEGER NOT NULL REFERENCES "group" (id),
+    PRIMARY KEY (regex_whitelist_id, group_id)
+);
+
+CREATE VIEW vw_regex_whitelist AS
+SELECT DISTINCT domain
+FROM regex_whitelist
+         LEFT JOIN regex_whitelist_by_group ON regex_whitelist_by_group.regex_whitelist_id = regex_whitelist.id
+         LEFT JOIN "group" ON "group".id = regex_whitelist_by_group.group_id
+WHERE regex_whitelist.enabled = 1
+  AND (regex_whitelist_by_group.group_id IS NULL OR "group".enabled = 1)
+ORDER BY regex_whitelist.id;
+
+CREATE TRIGGER tr_regex_whitelist_update
+    AFTER UPDATE
+    ON regex_whitelist
+BEGIN
+    UPDATE regex_whitelist SET date_modified = (cast(strftime('%s', 'now') as int)) WHERE domain = NEW.domain;
+END;
+
+UPDATE info
+SET value = 3
+WHERE property = 'version';
+
 -- BEGIN TEST DATA
 
 INSERT INTO whitelist
@@ -188,12 +227,21 @@ INSERT INTO regex
 VALUES (1, '(^|\.)example\.com$', 1, 1557712181, 1557712181, NULL),
        (2, 'disabled\-regex\.com', 0, 1557723854, 1557723872, NULL);
 
+INSERT INTO regex_whitelist
+VALUES (1, '^allowed\.example\.com$', 1, 1557712185, 1557712185, NULL);
+
 INSERT INTO adlist
 VALUES (1, 'https://raw.githubusercontent.com/StevenBlack/hosts/master/hosts', 1, 1557712118, 1557712118,
         'Migrated from /etc/pihole/adlists.list'),
        (2, 'https://mirror1.malwaredomains.com/files/justdomains', 1, 1557712118, 1557712118,
         'Migrated from /etc/pihole/adlists.list');
 
+INSERT INTO "group"
+VALUES (1, 1, 'Test', 'A test group');
+
+INSERT INTO adlist_by_group
+VALUES (2, 1);
+
 INSERT INTO gravity
 VALUES ('test.com'),
        ('vqubwduhbsd.com'),
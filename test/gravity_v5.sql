@@ -0,0 +1,158 @@
+PRAGMA FOREIGN_KEYS = ON;
+
+-- BEGIN SCHEMA
+
+CREATE TABLE "group"
+(
+    id          INTEGER PRIMARY KEY AUTOINCREMENT,
+    enabled     BOOLEAN NOT NULL DEFAULT 1,
+    name        TEXT    NOT NULL,
+    description TEXT
+);
+
+CREATE TABLE domainlist
+(
+    id            INTEGER PRIMARY KEY AUTOINCREMENT,
+    type          INTEGER NOT NULL DEFAULT 0,
+    domain        TEXT    NOT NULL,
+    enabled       BOOLEAN NOT NULL DEFAULT 1,
+    date_added    INTEGER NOT NULL DEFAULT (cast(strftime('%s', 'now') as int)),
+    date_modified INTEGER NOT NULL DEFAULT (cast(strftime('%s', 'now') as int)),
+    comment       TEXT,
+    UNIQUE (domain, type)
+);
+
+CREATE TABLE domainlist_by_group
+(
+    domainlist_id INTEGER NOT NULL REFERENCES domainlist (id),
+    group_id      INTEGER NOT NULL REFERENCES "group" (id),
+    PRIMARY KEY (domainlist_id, group_id)
+);
+
+CREATE TABLE adlist
+(
+    id            INTEGER PRIMARY KEY AUTOINCREMENT,
+    address       TEXT UNIQUE NOT NULL,
+    enabled       BOOLEAN     NOT NULL DEFAULT 1,
+    date_added    INTEGER     NOT NULL DEFAULT (cast(strftime('%s', 'now') as int)),
+    date_modified INTEGER     NOT NULL DEFAULT (cast(strftime('%s', 'now') as int)),
+    comment       TEXT,
+    date_updated  INTEGER,
+    number        INTEGER     NOT NULL DEFAULT 0,
+    status        INTEGER     NOT NULL DEFAULT 0
+);
+
+CREATE TABLE adlist_by_group
+(
+    adlist_id INTEGER NOT NULL REFERENCES adlist (id),
+    group_id  INTEGER NOT NULL REFERENCES "group" (id),
+    PRIMARY KEY (adlist_id, group_id)
+);
+
+CREATE TABLE gravity
+(
+    domain    TEXT    NOT NULL,
+    adlist_id INTEGER NOT NULL REFERENCES adlist (id),
+    PRIMARY KEY (domain, adlist_id)
+);
+
+CREATE TABLE domain_audit
+(
+    id         INTEGER PRIMARY KEY AUTOINCREMENT,
+    domain     TEXT UNIQUE NOT NULL,
+    date_added INTEGER     NOT NULL DEFAULT (cast(strftime('%s', 'now') as int))
+);
+
+CREATE TABLE info
+(
+    property TEXT PRIMARY KEY,
+    value    TEXT NOT NULL
+);
+
+INSERT INTO info
+VALUES ('version', '5');
+
+CREATE TRIGGER tr_domainlist_update
+    AFTER UPDATE OF domain
+    ON domainlist
+BEGIN
+    UPDATE domainlist SET date_modified = (cast(strftime('%s', 'now') as int)) WHERE domain = NEW.domain;
+END;
+
+CREATE VIEW vw_whitelist AS
+SELECT domain, domainlist.id AS id, domainlist_by_group.group_id AS group_id
+FROM domainlist
+         LEFT JOIN domainlist_by_group ON domainlist_by_group.domainlist_id = domainlist.id
+         LEFT JOIN "group" ON "group".id = domainlist_by_group.group_id
+WHERE domainlist.enabled = 1
+  AND (domainlist_by_group.group_id IS NULL OR "group".enabled = 1)
+  AND domainlist.type = 0
+ORDER BY domainlist.id;
+
+CREATE VIEW vw_blacklist AS
+SELECT domain, domainlist.id AS id, domainlist_by_group.group_id AS group_id
+FROM domainlist
+         LEFT JOIN domainlist_by_group ON domainlist_by_group.domainlist_id = domainlist.id
+         LEFT JOIN "group" ON "group".id = domainlist_by_group.group_id
+WHERE domainlist.enabled = 1
+  AND (domainlist_by_group.group_id IS NULL OR "group".enabled = 1)
+  AND domainlist.type = 1
+ORDER BY domainlist.id;
+
+CREATE VIEW vw_regex_whitelist AS
+SELECT domain, domainlist.id AS id, domainlist_by_group.group_id AS group_id
+FROM domainlist
+         LEFT JOIN domainlist_by_group ON domainlist_by_group.domainlist_id = domainlist.id
+         LEFT JOIN "group" ON "group".id = domainlist_by_group.group_id
+WHERE domainlist.enabled = 1
+  AND (domainlist_by_group.group_id IS NULL OR "group".enabled = 1)
+  AND domainlist.type = 2
+ORDER BY domainlist.id;
+
+CREATE VIEW vw_regex_blacklist AS
+SELECT domain, domainlist.id AS id, domainlist_by_group.group_id AS group_id
+FROM domainlist
+         LEFT JOIN domainlist_by_group ON domainlist_by_group.domainlist_id = domainlist.id
+         LEFT JOIN "group" ON "group".id = domainlist_by_group.group_id
+WHERE domainlist.enabled = 1
+  AND (domainlist_by_group.group_id IS NULL OR "group".enabled = 1)
+  AND domainlist.type = 3
+ORDER BY domainlist.id;
+
+-- BEGIN TEST DATA
+
+INSERT INTO domainlist
+VALUES (1, 0, 'test.com', 1, 1557712172, 1557712172, NULL),
+       (2, 0, 'disabled-white.com', 0, 1557723854, 1557723911, NULL),
+       (3, 1, 'example.com', 1, 1557712177, 1557712177, NULL),
+       (4, 1, 'disabled-black.com', 0, 1557723854, 1557723864, NULL),
+       (5, 3, '(^|\.)example\.com$', 1, 1557712181, 1557712181, NULL),
+       (6, 3, 'disabled\-regex\.com', 0, 1557723854, 1557723872, NULL),
+       (7, 2, '^allowed\.example\.com$', 1, 1557712185, 1557712185, NULL);
+
+INSERT INTO adlist
+VALUES (1, 'https://raw.githubusercontent.com/StevenBlack/hosts/master/hosts', 1, 1557712118, 1557712118,
+        'Migrated from /etc/pihole/adlists.list', 1557712200, 6, 1),
+       (2, 'https://mirror1.malwaredomains.com/files/justdomains', 1, 1557712118, 1557712118,
+        'Migrated from /etc/pihole/adlists.list', 1557712200, 4, 3);
+
+INSERT INTO "group"
+VALUES (1, 1, 'Test', 'A test group');
+
+INSERT INTO adlist_by_group
+VALUES (2, 1);
+
+INSERT INTO gravity
+VALUES ('test.com', 1),
+       ('vqubwduhbsd.com', 1),
+       ('vquf4tcdpt22px9l2jqqq.science', 1),
+       ('vqwdsvjygnah.com', 1),
+       ('vqxzysmhsvloijm12fsuswlu.download', 1),
+       ('vr-private-kunden-de.tk', 1),
+       ('vr-private-kundes-de.tk', 2),
+       ('vra.outbrain.com', 2),
+       ('vra4.com', 2),
+       ('vriaj.com', 2);
+
+INSERT INTO domain_audit (domain)
+VALUES ('audited.domain');